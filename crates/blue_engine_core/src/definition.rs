@@ -25,8 +25,6 @@ pub struct Pipeline {
     /// the Uniform buffers that are sent to the gpu
     pub uniform: PipelineData<Option<crate::UniformBuffers>>,
 }
-unsafe impl Send for Pipeline {}
-unsafe impl Sync for Pipeline {}
 
 /// Container for pipeline data. Allows for sharing resources with other objects
 #[derive(Debug)]
@@ -37,6 +35,41 @@ pub enum PipelineData<T> {
     Data(T),
 }
 
+/// A buffer being mapped for reading, which holds the result once the GPU is done with it
+#[derive(Debug, Clone)]
+pub(crate) struct BufferMapping(
+    std::sync::Arc<std::sync::OnceLock<Result<(), wgpu::BufferAsyncError>>>,
+);
+
+impl BufferMapping {
+    /// Starts mapping the whole buffer for reading
+    pub(crate) fn read(buffer: &wgpu::Buffer) -> Self {
+        let result = std::sync::Arc::new(std::sync::OnceLock::new());
+        let callback_result = result.clone();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |mapped| {
+                let _ = callback_result.set(mapped);
+            });
+
+        Self(result)
+    }
+
+    /// Returns the result of the mapping, or `None` while it is still being mapped
+    pub(crate) fn result(&self) -> Option<Result<(), crate::error::Error>> {
+        match self.0.get() {
+            Some(result) => Some(result.clone().map_err(Into::into)),
+            // the callback holds the other reference, so it was dropped without a result
+            None if std::sync::Arc::strong_count(&self.0) == 1 => {
+                Some(Err(crate::error::Error::Custom(
+                    "The buffer was dropped before it was read".to_string(),
+                )))
+            }
+            None => None,
+        }
+    }
+}
+
 /// Container for vertex and index buffer
 #[derive(Debug)]
 pub struct VertexBuffers {
@@ -49,21 +82,19 @@ pub struct VertexBuffers {
     /// The length of the vertex buffer
     pub length: u32,
 }
-unsafe impl Send for VertexBuffers {}
-unsafe impl Sync for VertexBuffers {}
 
 /// Defines how the texture data is
 #[derive(Debug, Clone)]
 pub enum TextureData {
-    /// the texture file bytes directly
+    /// the texture file bytes directly. DDS and KTX2 files are uploaded in their
+    /// compressed formats
     Bytes(Vec<u8>),
     /// the texture as a [`image::DynamicImage`]
     Image(image::DynamicImage),
-    /// path to a texture file to load
+    /// path to a texture file to load. Files ending in `.dds` or `.ktx2` are uploaded in
+    /// their compressed formats
     Path(String),
 }
-unsafe impl Send for TextureData {}
-unsafe impl Sync for TextureData {}
 
 /// Defines how the borders of texture would look like
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -75,11 +106,165 @@ pub enum TextureMode {
     /// Repeats the texture, but mirrors it on edges
     MirrorRepeat,
 }
-unsafe impl Send for TextureMode {}
-unsafe impl Sync for TextureMode {}
+
+/// The full settings of how a texture is sampled. Anything that takes a [TextureMode] also
+/// takes these, for control over the filtering and the borders of each axis:
+///
+/// ```ignore
+/// // crisp pixel art that repeats sideways
+/// let sampler = SamplerSettings::new()
+///     .with_filter(wgpu::FilterMode::Nearest)
+///     .with_address_mode(wgpu::AddressMode::Repeat, wgpu::AddressMode::ClampToEdge);
+/// renderer.build_texture("tiles", TextureData::Path("tiles.png".into()), sampler)?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerSettings {
+    /// How the texture is sampled outside of 0 to 1 on the horizontal axis
+    pub address_mode_u: wgpu::AddressMode,
+    /// How the texture is sampled outside of 0 to 1 on the vertical axis
+    pub address_mode_v: wgpu::AddressMode,
+    /// How the texture is sampled outside of 0 to 1 on the depth axis
+    pub address_mode_w: wgpu::AddressMode,
+    /// How the texture is filtered when it is drawn larger than it is
+    pub mag_filter: wgpu::FilterMode,
+    /// How the texture is filtered when it is drawn smaller than it is
+    pub min_filter: wgpu::FilterMode,
+    /// How the mipmap levels are blended between
+    pub mipmap_filter: wgpu::FilterMode,
+    /// The most samples taken for anisotropic filtering, between 1 and 16. Keeps the textures
+    /// sharp when seen at a steep angle, and only works with all the filters linear
+    pub anisotropy: u16,
+    /// The smallest mipmap level used, where 0 is the full texture
+    pub lod_min_clamp: f32,
+    /// The largest mipmap level used
+    pub lod_max_clamp: f32,
+    /// The color outside of the texture, for [wgpu::AddressMode::ClampToBorder]
+    pub border_color: Option<wgpu::SamplerBorderColor>,
+}
+
+impl SamplerSettings {
+    /// Creates the default settings, which are linear filtering clamped to the edges
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how the texture is sampled outside of 0 to 1 on the horizontal and vertical axes
+    pub fn with_address_mode(mut self, u: wgpu::AddressMode, v: wgpu::AddressMode) -> Self {
+        self.address_mode_u = u;
+        self.address_mode_v = v;
+        self
+    }
+
+    /// Sets how the texture is sampled outside of 0 to 1 on the depth axis
+    pub fn with_address_mode_w(mut self, w: wgpu::AddressMode) -> Self {
+        self.address_mode_w = w;
+        self
+    }
+
+    /// Sets the filtering of the magnifying, minifying and mipmaps at once
+    pub fn with_filter(mut self, filter: wgpu::FilterMode) -> Self {
+        self.mag_filter = filter;
+        self.min_filter = filter;
+        self.mipmap_filter = filter;
+        self
+    }
+
+    /// Sets the filtering for when the texture is drawn larger than it is
+    pub fn with_mag_filter(mut self, filter: wgpu::FilterMode) -> Self {
+        self.mag_filter = filter;
+        self
+    }
+
+    /// Sets the filtering for when the texture is drawn smaller than it is
+    pub fn with_min_filter(mut self, filter: wgpu::FilterMode) -> Self {
+        self.min_filter = filter;
+        self
+    }
+
+    /// Sets how the mipmap levels are blended between
+    pub fn with_mipmap_filter(mut self, filter: wgpu::FilterMode) -> Self {
+        self.mipmap_filter = filter;
+        self
+    }
+
+    /// Sets the most samples taken for anisotropic filtering, see [SamplerSettings::anisotropy]
+    pub fn with_anisotropy(mut self, anisotropy: u16) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    /// Sets the range of the mipmap levels that are used
+    pub fn with_lod_clamp(mut self, min: f32, max: f32) -> Self {
+        self.lod_min_clamp = min;
+        self.lod_max_clamp = max;
+        self
+    }
+
+    /// Sets the color outside of the texture, for [wgpu::AddressMode::ClampToBorder]
+    pub fn with_border_color(mut self, color: wgpu::SamplerBorderColor) -> Self {
+        self.border_color = Some(color);
+        self
+    }
+
+    /// The descriptor of the sampler. The anisotropy falls back to 1 when a filter is not
+    /// linear, since the GPU can not blend them together otherwise
+    pub fn descriptor<'a>(&self, label: Option<&'a str>) -> wgpu::SamplerDescriptor<'a> {
+        let all_linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|i| *i == wgpu::FilterMode::Linear);
+
+        wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: self.address_mode_w,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            lod_min_clamp: self.lod_min_clamp.max(0.0),
+            lod_max_clamp: self.lod_max_clamp.max(self.lod_min_clamp.max(0.0)),
+            compare: None,
+            anisotropy_clamp: if all_linear {
+                self.anisotropy.clamp(1, 16)
+            } else {
+                1
+            },
+            border_color: self.border_color,
+        }
+    }
+}
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy: 1,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 32.0,
+            border_color: None,
+        }
+    }
+}
+impl From<TextureMode> for SamplerSettings {
+    fn from(texture_mode: TextureMode) -> Self {
+        let mode = match texture_mode {
+            TextureMode::Clamp => wgpu::AddressMode::Repeat,
+            TextureMode::Repeat => wgpu::AddressMode::MirrorRepeat,
+            TextureMode::MirrorRepeat => wgpu::AddressMode::ClampToEdge,
+        };
+
+        Self::default()
+            .with_address_mode(mode, mode)
+            .with_address_mode_w(mode)
+    }
+}
 
 /// These definitions are taken from wgpu API docs
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ShaderSettings {
     // ===== PRIMITIVE ===== //
     /// The primitive topology used to interpret vertices
@@ -112,6 +297,10 @@ pub struct ShaderSettings {
     /// Enabling this requires `CONSERVATIVE_RASTERIZATION`
     /// features to be enabled.
     pub conservative: bool,
+    // ===== Depth ===== //
+    /// Does the object write its depth, hiding what is drawn after it behind it. Turned off
+    /// while the object fades, see [crate::Object::fade_out]
+    pub depth_write: bool,
     // ===== Multisample ===== //
     /// The number of samples calculated per pixel (for MSAA).
     /// For non-multisampled textures, this should be `1`
@@ -129,6 +318,23 @@ pub struct ShaderSettings {
     /// to be zero, and for alpha of one is guaranteed to be all
     /// 1-s.
     pub alpha_to_coverage_enabled: bool,
+    // ===== Vertex ===== //
+    /// The vertices are [crate::utils::vertex_quantization::QuantizedVertex] instead of
+    /// [Vertex], see [crate::Object::set_quantized]
+    pub quantized_vertices: bool,
+    /// The shader reads the vertices from a storage buffer at binding 2 of the uniforms, by
+    /// the index of the vertex, see [crate::Object::set_vertex_pulling]
+    pub vertex_pulling: bool,
+    /// The shader skins the vertices by the bones of a skinned crowd, at bindings 2 to 5 of
+    /// the uniforms, see [crate::utils::skinned_instancing::SkinnedCrowd]
+    pub skinned_instancing: bool,
+    /// The shader blends the morph targets of the object into its vertices, at bindings 2 to
+    /// 4 of the uniforms, see [crate::utils::morph_targets::MorphedObject]
+    pub morph_targets: bool,
+    // ===== Fragment ===== //
+    /// Is the fog of the renderer applied, for the shaders that use the `//@FOG` placeholder.
+    /// Turn it off for objects such as the sky, see [crate::utils::fog::Fog]
+    pub fog: bool,
 }
 impl Default for ShaderSettings {
     fn default() -> Self {
@@ -140,14 +346,18 @@ impl Default for ShaderSettings {
             polygon_mode: wgpu::PolygonMode::Fill,
             clamp_depth: false,
             conservative: false,
+            depth_write: true,
             count: 1,
             mask: !0,
             alpha_to_coverage_enabled: true,
+            quantized_vertices: false,
+            vertex_pulling: false,
+            skinned_instancing: false,
+            morph_targets: false,
+            fog: true,
         }
     }
 }
-unsafe impl Send for ShaderSettings {}
-unsafe impl Sync for ShaderSettings {}
 
 /// This function helps in converting pixel value to the value that is between -1 and +1
 pub fn pixel_to_cartesian(value: f32, max: u32) -> f32 {
@@ -178,7 +388,10 @@ impl crate::prelude::Renderer {
         }
     }
 
-    /// Creates a shader group, the input must be spir-v compiled vertex and fragment shader
+    /// Creates a shader group, the input must be spir-v compiled vertex and fragment shader.
+    ///
+    /// The shaders with the same source, layout, and settings are built once and shared,
+    /// see [crate::utils::pipeline_cache::PipelineCache]
     pub fn build_shader(
         &mut self,
         name: impl StringBuffer,
@@ -186,11 +399,21 @@ impl crate::prelude::Renderer {
         uniform_layout: Option<&BindGroupLayout>,
         settings: ShaderSettings,
     ) -> Shaders {
+        let key = crate::utils::pipeline_cache::PipelineKey {
+            source: shader_source,
+            settings,
+            uniform_layout: uniform_layout.cloned(),
+            format: self.scene_format(),
+        };
+        if let Some(render_pipeline) = self.pipeline_cache.pipeline(&key) {
+            return render_pipeline;
+        }
+
         let shader = self
             .device
             .create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: Some(format!("{} Shader", name.as_str()).as_str()),
-                source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+                source: wgpu::ShaderSource::Wgsl(key.source.as_str().into()),
             });
 
         let mut bind_group_layouts = vec![
@@ -201,6 +424,18 @@ impl crate::prelude::Renderer {
             bind_group_layouts.push(uniform_layout);
         }
 
+        // the fog is turned off by the override constant of the //@FOG placeholder, where the
+        // shader has it
+        let mut constants = std::collections::HashMap::new();
+        let fog_override = crate::utils::fog::FOG_OVERRIDE;
+        if !settings.fog && key.source.contains(&format!("override {fog_override}")) {
+            constants.insert(fog_override.to_string(), 0.0);
+        }
+        let compilation_options = wgpu::PipelineCompilationOptions {
+            constants: &constants,
+            ..Default::default()
+        };
+
         let render_pipeline_layout =
             self.device
                 .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -217,18 +452,24 @@ impl crate::prelude::Renderer {
                 vertex: wgpu::VertexState {
                     module: &shader,
                     entry_point: Some("vs_main"),
-                    buffers: &[Vertex::desc(), InstanceRaw::desc()],
-                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    buffers: &[
+                        match settings.quantized_vertices {
+                            true => crate::utils::vertex_quantization::QuantizedVertex::desc(),
+                            false => Vertex::desc(),
+                        },
+                        InstanceRaw::desc(),
+                    ],
+                    compilation_options: compilation_options.clone(),
                 },
                 fragment: Some(wgpu::FragmentState {
                     module: &shader,
                     entry_point: Some("fs_main"),
                     targets: &[Some(wgpu::ColorTargetState {
-                        format: self.config.format,
+                        format: self.scene_format(),
                         write_mask: wgpu::ColorWrites::ALL,
                         blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                     })],
-                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    compilation_options,
                 }),
                 primitive: wgpu::PrimitiveState {
                     topology: settings.topology,
@@ -242,7 +483,7 @@ impl crate::prelude::Renderer {
                 },
                 depth_stencil: Some(wgpu::DepthStencilState {
                     format: crate::DEPTH_FORMAT,
-                    depth_write_enabled: true,
+                    depth_write_enabled: settings.depth_write,
                     depth_compare: wgpu::CompareFunction::Less,
                     stencil: wgpu::StencilState::default(),
                     bias: wgpu::DepthBiasState::default(),
@@ -255,6 +496,8 @@ impl crate::prelude::Renderer {
                 multiview: None,
                 cache: None,
             });
+        self.pipeline_cache
+            .insert_pipeline(key, render_pipeline.clone());
 
         render_pipeline
     }
@@ -264,20 +507,25 @@ impl crate::prelude::Renderer {
         &mut self,
         name: impl StringBuffer,
         texture_data: TextureData,
-        texture_mode: TextureMode,
+        texture_mode: impl Into<SamplerSettings>,
         //texture_format: TextureFormat,
     ) -> Result<Textures, crate::error::Error> {
-        let mode: wgpu::AddressMode = match texture_mode {
-            TextureMode::Clamp => wgpu::AddressMode::Repeat,
-            TextureMode::Repeat => wgpu::AddressMode::MirrorRepeat,
-            TextureMode::MirrorRepeat => wgpu::AddressMode::ClampToEdge,
-        };
+        // DDS and KTX2 textures are uploaded in the format they are stored in
+        if let Some(compressed) = crate::utils::compressed_textures::read_compressed(&texture_data)
+        {
+            return self.build_compressed_texture(name, &compressed?, texture_mode);
+        }
 
         let img = match texture_data {
             TextureData::Bytes(data) => image::load_from_memory(data.as_slice())?,
             TextureData::Image(data) => data,
             TextureData::Path(path) => image::open(path)?,
         };
+        // the colors of HDR images are kept above 1.0, in a float texture
+        if crate::utils::hdr::is_hdr_image(&img) {
+            return Ok(self.build_texture_hdr(name, &img, texture_mode));
+        }
+        let img = self.fit_texture(name.as_str(), img);
 
         let rgba = img.to_rgba8();
         let dimensions = img.dimensions();
@@ -290,7 +538,7 @@ impl crate::prelude::Renderer {
         let texture = self.device.create_texture(&wgpu::TextureDescriptor {
             label: Some(name.as_str()),
             size,
-            mip_level_count: 1,
+            mip_level_count: crate::utils::mipmaps::mip_level_count(dimensions.0, dimensions.1),
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
@@ -316,19 +564,32 @@ impl crate::prelude::Renderer {
             },
             size,
         );
+        // the smaller levels keep the texture from shimmering when seen from afar
+        self.generate_mipmaps(&texture);
 
+        let textures = self.build_texture_from_gpu(name, &texture, texture_mode);
+        self.track_texture(&textures, &texture);
+        Ok(textures)
+    }
+
+    /// Creates a new texture for objects out of a texture on the GPU, such as the result of
+    /// [crate::utils::image_processing::ImageProcessing]
+    pub fn build_texture_from_gpu(
+        &self,
+        name: impl StringBuffer,
+        texture: &wgpu::Texture,
+        texture_mode: impl Into<SamplerSettings>,
+    ) -> Textures {
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = self.device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: mode,
-            address_mode_v: mode,
-            address_mode_w: mode,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+        let sampler = self.device.create_sampler(
+            &self
+                .quality
+                .sampler(texture_mode.into())
+                .descriptor(Some(name.as_str())),
+        );
 
-        let diffuse_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        crate::utils::allocation_audit::count_bind_group();
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &self.texture_bind_group_layout,
             label: Some("Diffuse Bind Group"),
             entries: &[
@@ -341,9 +602,7 @@ impl crate::prelude::Renderer {
                     resource: wgpu::BindingResource::Sampler(&sampler),
                 },
             ],
-        });
-
-        Ok(diffuse_bind_group)
+        })
     }
 
     pub(crate) fn build_depth_buffer(
@@ -363,7 +622,9 @@ impl crate::prelude::Renderer {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: crate::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[wgpu::TextureFormat::Depth32Float],
         };
         let texture = device.create_texture(&desc);
@@ -393,11 +654,12 @@ impl crate::prelude::Renderer {
         name: impl StringBuffer,
         value: T,
     ) -> wgpu::Buffer {
+        crate::utils::allocation_audit::count_buffer();
         self.device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some(name.as_str()),
                 contents: bytemuck::cast_slice(&[value]),
-                usage: wgpu::BufferUsages::UNIFORM,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             })
     }
 
@@ -416,11 +678,16 @@ impl crate::prelude::Renderer {
                     resource: uniform.as_entire_binding(),
                 };
                 buffer_entry.push(descriptor);
+                // the storage buffers are read only, such as the vertices of vertex pulling
+                let ty = match uniform.usage().contains(wgpu::BufferUsages::STORAGE) {
+                    true => wgpu::BufferBindingType::Storage { read_only: true },
+                    false => wgpu::BufferBindingType::Uniform,
+                };
                 buffer_layout.push(wgpu::BindGroupLayoutEntry {
                     binding: i as u32,
                     visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
+                        ty,
                         has_dynamic_offset: false,
                         min_binding_size: None,
                     },
@@ -429,13 +696,12 @@ impl crate::prelude::Renderer {
             }
         }
 
-        let uniform_bind_group_layout =
-            self.device
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    label: Some("uniform dynamic bind group layout"),
-                    entries: buffer_layout.as_slice(),
-                });
+        // the objects with the same uniforms share the layout, and so can share the shader
+        let uniform_bind_group_layout = self
+            .pipeline_cache
+            .layout(&self.device, buffer_layout.as_slice());
 
+        crate::utils::allocation_audit::count_bind_group();
         let uniform_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Uniform Bind Groups"),
             layout: &uniform_bind_group_layout,
@@ -451,6 +717,7 @@ impl crate::prelude::Renderer {
         vertices: &Vec<Vertex>,
         indices: &Vec<UnsignedIntType>,
     ) -> VertexBuffers {
+        crate::utils::allocation_audit::count_buffer();
         let vertex_buffer = self
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -459,6 +726,7 @@ impl crate::prelude::Renderer {
                 usage: wgpu::BufferUsages::VERTEX,
             });
 
+        crate::utils::allocation_audit::count_buffer();
         let index_buffer = self
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -474,15 +742,94 @@ impl crate::prelude::Renderer {
         }
     }
 
+    /// Writes the vertices and indices into the buffers in place, or replaces the buffers they
+    /// do not fit in. A replaced buffer has room to grow, so it is not replaced again soon
+    pub(crate) fn write_vertex_buffers(
+        &self,
+        buffers: Option<VertexBuffers>,
+        vertices: &[u8],
+        indices: &[u8],
+        label: &str,
+        usage: wgpu::BufferUsages,
+        length: u32,
+    ) -> VertexBuffers {
+        let (vertex_buffer, index_buffer) = match buffers {
+            Some(buffers) => (Some(buffers.vertex_buffer), Some(buffers.index_buffer)),
+            None => (None, None),
+        };
+
+        VertexBuffers {
+            vertex_buffer: self.write_mesh_buffer(vertex_buffer, vertices, label, usage),
+            index_buffer: self.write_mesh_buffer(
+                index_buffer,
+                indices,
+                "Index Buffer",
+                wgpu::BufferUsages::INDEX,
+            ),
+            length,
+        }
+    }
+
+    /// Writes the data into the buffer if it fits and can be written to, otherwise into a new
+    /// one. The data is padded to the 4 bytes the buffers are written in
+    fn write_mesh_buffer(
+        &self,
+        buffer: Option<wgpu::Buffer>,
+        data: &[u8],
+        label: &str,
+        usage: wgpu::BufferUsages,
+    ) -> wgpu::Buffer {
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+        let padded = wgpu::util::align_to(data.len() as u64, wgpu::COPY_BUFFER_ALIGNMENT)
+            .max(wgpu::COPY_BUFFER_ALIGNMENT);
+
+        let buffer = match buffer {
+            Some(buffer) if buffer.size() >= padded && buffer.usage().contains(usage) => buffer,
+            previous => {
+                crate::utils::allocation_audit::count_buffer();
+                self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size: match previous {
+                        Some(_) => padded.next_power_of_two(),
+                        None => padded,
+                    },
+                    usage,
+                    mapped_at_creation: false,
+                })
+            }
+        };
+
+        let aligned = data.len() - data.len() % wgpu::COPY_BUFFER_ALIGNMENT as usize;
+        if aligned > 0 {
+            self.queue.write_buffer(&buffer, 0, &data[..aligned]);
+        }
+        if aligned < data.len() {
+            let mut tail = [0u8; wgpu::COPY_BUFFER_ALIGNMENT as usize];
+            tail[..data.len() - aligned].copy_from_slice(&data[aligned..]);
+            self.queue.write_buffer(&buffer, aligned as u64, &tail);
+        }
+
+        buffer
+    }
+
     /// Creates a new instance buffer for the object
     pub fn build_instance(&self, instance_data: Vec<InstanceRaw>) -> wgpu::Buffer {
+        crate::utils::allocation_audit::count_buffer();
         self.device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Instance Buffer"),
                 contents: bytemuck::cast_slice(&instance_data),
-                usage: wgpu::BufferUsages::VERTEX,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
             })
     }
+
+    /// Reads the content of a buffer back to the CPU, blocking until the GPU is done with it.
+    /// See [Self::read_buffer_async] to read it without waiting.
+    ///
+    /// The buffer must have been created with [`wgpu::BufferUsages::MAP_READ`]
+    pub fn read_buffer(&self, buffer: &wgpu::Buffer) -> Result<Vec<u8>, crate::error::Error> {
+        self.read_buffer_async(buffer).wait(self)
+    }
 }
 
 impl crate::SignalStorage {
@@ -9,17 +9,73 @@ pub enum Error {
     /// Errors that occur throughout the update_loop
     #[error("An error occurred with the update loop: {0}")]
     UpdateLoopError(#[from] winit::error::EventLoopError),
+    /// Errors of the cursor and other requests that the platform may not support
+    #[error("The request is not supported by the window: {0}")]
+    WindowRequestError(#[from] winit::error::ExternalError),
 
     // ===== WGPU
     #[error("Failed to find an appropriate adapter")]
     AdapterNotFound,
+    #[error("No adapter has {0} in its name, the adapters are {1:?}")]
+    AdapterNameNotFound(String, Vec<String>),
     #[error("Failed to find an appropriate device for rendering")]
     DeviceNotFound(#[from] wgpu::RequestDeviceError),
+    #[error("Failed to get the next frame: {0}")]
+    SurfaceError(#[from] wgpu::SurfaceError),
+    #[error("The required GPU features are not enabled: {0:?}")]
+    MissingFeatures(wgpu::Features),
+    #[error("Failed to read the data back from the GPU")]
+    BufferReadError(#[from] wgpu::BufferAsyncError),
+
+    // ===== Shader
+    #[error("Failed to compile the shader: {0}")]
+    ShaderCompileError(Box<crate::objects::ShaderError>),
 
     // ===== Image
     #[error("Failed to load the texture data from given source")]
     LoadingTextureDataError(#[from] image::error::ImageError),
 
+    #[error("Failed to read the compressed texture: {0}")]
+    CompressedTextureError(&'static str),
+
+    #[error("Failed to write to the texture: {0}")]
+    TextureWriteError(&'static str),
+
+    // ===== IO
+    #[error("Failed to write the file: {0}")]
+    WriteFileError(#[from] std::io::Error),
+
+    #[error("Failed to read the asset pack: {0}")]
+    AssetPackError(String),
+
+    #[error("Failed to read the meshlets: {0}")]
+    MeshletError(&'static str),
+
+    #[cfg(feature = "audio")]
+    #[error("Failed to read the sound: {0}")]
+    SoundDecodeError(#[from] rodio::decoder::DecoderError),
+
+    // ===== Audio
+    #[cfg(feature = "audio")]
+    #[error("Failed to open the audio device: {0}")]
+    AudioDeviceError(#[from] rodio::DeviceSinkError),
+
+    // ===== Rendering
+    #[error("Failed to place the pass in the frame graph: {0}")]
+    FrameGraphError(String),
+
+    // ===== Settings
+    #[error("Failed to read or save the settings: {0}")]
+    SettingsError(String),
+
+    // ===== Replication
+    #[error("Failed to read the replication data: {0}")]
+    ReplicationDecodeError(&'static str),
+
+    // ===== Web
+    #[error("Failed to reach the page: {0}")]
+    WebError(String),
+
     #[error("{0}")]
     Custom(String),
 }
@@ -24,5 +24,38 @@ pub mod utils;
 pub use utils::*;
 /// contains definition for creation of window and instance creation.
 pub mod window;
+/// contains the secondary windows that share the renderer of the engine.
+pub mod windows;
 #[doc(inline)]
 pub use crate::prelude::*;
+
+// the clock of the standard library panics on the web, where the one of the browser is used
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time;
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time as time;
+
+// the types that can be shared with other threads are checked here, so a field that is not
+// thread safe fails the build. The wgpu types are not thread safe on the web
+#[cfg(not(target_arch = "wasm32"))]
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Vertex>();
+    assert_send_sync::<Object>();
+    assert_send_sync::<ObjectSettings>();
+    assert_send_sync::<ObjectStorage>();
+    assert_send_sync::<RotateAxis>();
+    assert_send_sync::<RotateAmount>();
+    assert_send_sync::<objects::Material>();
+    assert_send_sync::<Pipeline>();
+    assert_send_sync::<VertexBuffers>();
+    assert_send_sync::<TextureData>();
+    assert_send_sync::<TextureMode>();
+    assert_send_sync::<SamplerSettings>();
+    assert_send_sync::<ShaderSettings>();
+    assert_send_sync::<Camera>();
+    assert_send_sync::<CameraContainer>();
+    assert_send_sync::<WindowDescriptor>();
+    assert_send_sync::<Renderer>();
+    assert_send_sync::<Window>();
+};
@@ -0,0 +1,188 @@
+use super::{Mesh, Object, ObjectId, ObjectSettings, ObjectStorage, Transform};
+use crate::{Renderer, StringBuffer, UnsignedIntType, Vector4, Vertex};
+
+/// The vertices an [ObjectBuilder] is built from
+#[derive(Debug, Clone)]
+enum ObjectGeometry {
+    Data(Vec<Vertex>, Vec<UnsignedIntType>),
+    Mesh(Mesh),
+}
+
+/// Describes an object without creating its GPU resources, so it can be made anywhere, such
+/// as in game systems that do not have the renderer.
+///
+/// The object is created by [ObjectBuilder::build], or by the engine at the start of the next
+/// frame after [ObjectStorage::queue].
+#[derive(Debug, Clone)]
+pub struct ObjectBuilder {
+    /// Name of the object
+    pub name: std::sync::Arc<str>,
+    geometry: ObjectGeometry,
+    /// Extra settings of the object
+    pub settings: ObjectSettings,
+    /// The position, rotation, and scale of the object
+    pub transform: Option<Transform>,
+    /// The color of the object
+    pub color: Option<Vector4>,
+    /// Should the object be rendered
+    pub is_visible: bool,
+    /// Objects with higher number get rendered later
+    pub render_order: usize,
+    /// Should the object be merged with the other static objects, see [Object::set_static]
+    pub is_static: bool,
+    /// Should the mesh be in the shared buffers of the pool, see [Object::set_pooled]
+    pub is_pooled: bool,
+    /// Categories of the object, see [Object::add_tag]
+    pub tags: Vec<std::sync::Arc<str>>,
+}
+
+impl ObjectBuilder {
+    /// Describes a new object made of the vertices and indices
+    pub fn new(
+        name: impl StringBuffer,
+        vertices: Vec<Vertex>,
+        indices: Vec<UnsignedIntType>,
+    ) -> Self {
+        Self::with_geometry(name, ObjectGeometry::Data(vertices, indices))
+    }
+
+    /// Describes a new object that uses a shared mesh, see [Object::new_from_mesh]
+    pub fn from_mesh(name: impl StringBuffer, mesh: &Mesh) -> Self {
+        Self::with_geometry(name, ObjectGeometry::Mesh(mesh.clone()))
+    }
+
+    fn with_geometry(name: impl StringBuffer, geometry: ObjectGeometry) -> Self {
+        Self {
+            name: name.as_arc(),
+            geometry,
+            settings: ObjectSettings::default(),
+            transform: None,
+            color: None,
+            is_visible: true,
+            render_order: 0,
+            is_static: false,
+            is_pooled: false,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Sets the extra settings of the object
+    pub fn with_settings(mut self, settings: ObjectSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Sets the position, rotation, and scale of the object
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Sets the color of the object
+    pub fn with_color(mut self, red: f32, green: f32, blue: f32, alpha: f32) -> Self {
+        self.color = Some(Vector4::new(red, green, blue, alpha));
+        self
+    }
+
+    /// Sets if the object will be rendered or not
+    pub fn with_visibility(mut self, is_visible: bool) -> Self {
+        self.is_visible = is_visible;
+        self
+    }
+
+    /// Sets the render order of the object, see [Object::set_render_order]
+    pub fn with_render_order(mut self, render_order: usize) -> Self {
+        self.render_order = render_order;
+        self
+    }
+
+    /// Marks the object as static, see [Object::set_static]
+    pub fn with_static(mut self, is_static: bool) -> Self {
+        self.is_static = is_static;
+        self
+    }
+
+    /// Puts the mesh in the shared buffers of the pool, see [Object::set_pooled]
+    pub fn with_pooled(mut self, is_pooled: bool) -> Self {
+        self.is_pooled = is_pooled;
+        self
+    }
+
+    /// Adds a tag to the object, see [Object::add_tag]
+    pub fn with_tag(mut self, tag: impl StringBuffer) -> Self {
+        self.tags.push(tag.as_arc());
+        self
+    }
+
+    /// Creates the object and its GPU resources
+    pub fn build(self, renderer: &mut Renderer) -> Result<Object, crate::error::Error> {
+        let mut object = match self.geometry {
+            ObjectGeometry::Data(vertices, indices) => Object::new(
+                self.name.as_ref(),
+                vertices,
+                indices,
+                self.settings,
+                renderer,
+            )?,
+            ObjectGeometry::Mesh(mesh) => {
+                Object::new_from_mesh(self.name.as_ref(), &mesh, self.settings, renderer)?
+            }
+        };
+
+        if let Some(transform) = self.transform {
+            object.set_transform(transform);
+        }
+        if let Some(color) = self.color {
+            object.set_color(color.x, color.y, color.z, color.w);
+        }
+        object.set_visibility(self.is_visible);
+        object.set_render_order(self.render_order);
+        object.set_static(self.is_static);
+        object.set_pooled(self.is_pooled);
+        for tag in self.tags {
+            object.add_tag(tag.as_ref());
+        }
+
+        Ok(object)
+    }
+}
+
+impl ObjectStorage {
+    /// Queues the object to be created by the engine at the start of the next frame, and
+    /// returns its handle. The handle resolves once the object is created
+    pub fn queue(&mut self, builder: ObjectBuilder) -> ObjectId {
+        let id = self.reserve_id();
+        self.queued.push((id, builder));
+
+        id
+    }
+
+    /// Returns the objects waiting to be created
+    pub fn queued(&self) -> impl Iterator<Item = &ObjectBuilder> {
+        self.queued.iter().map(|(_, builder)| builder)
+    }
+
+    /// Drops the objects waiting to be created
+    pub fn clear_queue(&mut self) {
+        for (id, _) in std::mem::take(&mut self.queued) {
+            self.free_id(id);
+        }
+    }
+
+    /// Creates the queued objects, in the order they were queued. Objects that fail to be
+    /// created are reported and dropped
+    pub fn build_queued(&mut self, renderer: &mut Renderer) {
+        for (id, builder) in std::mem::take(&mut self.queued) {
+            let name = builder.name.clone();
+            match builder.build(renderer) {
+                Ok(object) => {
+                    self.insert_with_id(name.as_ref(), object, id);
+                }
+                Err(e) => {
+                    eprintln!("Could not create the queued object {name}: {e}");
+                    self.free_id(id);
+                }
+            }
+        }
+    }
+}
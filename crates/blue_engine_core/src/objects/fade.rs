@@ -0,0 +1,162 @@
+use super::{Object, ObjectStorage};
+use crate::Vector4;
+
+/// The lowest alpha drawn while fading. A color with an alpha of zero is not applied by the
+/// default shaders, which would show the object in full instead
+const MIN_ALPHA: f32 = 1.0 / 1024.0;
+
+/// A fade of the alpha of an object, moved forward by the engine every frame. See
+/// [Object::fade_in] and [Object::fade_out]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fade {
+    /// The object ends up visible, or hidden
+    pub fade_in: bool,
+    /// The seconds the whole fade takes
+    pub duration: f32,
+    /// How far the fade is, from 0 to 1
+    pub progress: f32,
+    /// The color of the object from before the fade, given back when it is done
+    color: Vector4,
+    /// The depth writes and alpha to coverage of the object from before the fade
+    settings: (bool, bool),
+}
+
+impl Fade {
+    /// The alpha of the object at this point of the fade
+    pub fn alpha(&self) -> f32 {
+        let opaque = match self.color.w {
+            0.0 => 1.0,
+            alpha => alpha,
+        };
+        let progress = match self.fade_in {
+            true => self.progress,
+            false => 1.0 - self.progress,
+        };
+
+        (opaque * progress).max(MIN_ALPHA)
+    }
+
+    /// Returns true once the fade is done
+    pub fn is_finished(&self) -> bool {
+        self.progress >= 1.0
+    }
+}
+
+impl Object {
+    /// Makes the object visible and fades its alpha in from zero over the given seconds, or
+    /// from where the fade out it is in was. The object is drawn blended while fading, without
+    /// writing its depth, and gets its color and settings back when the fade is done
+    pub fn fade_in(&mut self, duration: f32) -> &mut Self {
+        let progress = match self.fade {
+            Some(fade) if !fade.fade_in => 1.0 - fade.progress,
+            Some(fade) => fade.progress,
+            None if self.is_visible => 1.0,
+            None => 0.0,
+        };
+        self.start_fade(true, duration, progress);
+        self.is_visible = true;
+
+        self
+    }
+
+    /// Fades the alpha of the object out over the given seconds, and then hides it. Its color
+    /// is given back when hidden, so it is drawn as before when made visible again
+    pub fn fade_out(&mut self, duration: f32) -> &mut Self {
+        let progress = match self.fade {
+            Some(fade) if fade.fade_in => 1.0 - fade.progress,
+            Some(fade) => fade.progress,
+            None if self.is_visible => 0.0,
+            None => 1.0,
+        };
+        self.start_fade(false, duration, progress);
+
+        self
+    }
+
+    /// Returns true while the object is fading in or out
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// Returns the fade the object is in, if any
+    pub fn fade(&self) -> Option<&Fade> {
+        self.fade.as_ref()
+    }
+
+    /// Jumps to the end of the fade the object is in, showing or hiding it
+    pub fn finish_fade(&mut self) -> &mut Self {
+        if let Some(fade) = self.fade.as_mut() {
+            fade.progress = 1.0;
+        }
+        self.advance_fade(0.0);
+
+        self
+    }
+
+    /// Starts a fade, keeping the color and settings from before the fade it replaces
+    fn start_fade(&mut self, fade_in: bool, duration: f32, progress: f32) {
+        let (color, settings) = match self.fade {
+            Some(fade) => (fade.color, fade.settings),
+            None => (
+                self.color,
+                (
+                    self.shader_settings.depth_write,
+                    self.shader_settings.alpha_to_coverage_enabled,
+                ),
+            ),
+        };
+        self.fade = Some(Fade {
+            fade_in,
+            duration: duration.max(0.0),
+            progress,
+            color,
+            settings,
+        });
+
+        // the blended pixels must not hide the objects behind them
+        self.shader_settings.depth_write = false;
+        self.shader_settings.alpha_to_coverage_enabled = false;
+        self.advance_fade(0.0);
+    }
+
+    /// Moves the fade forward by the seconds, and ends it once it is done
+    pub(crate) fn advance_fade(&mut self, delta_time: f32) {
+        let Some(fade) = self.fade.as_mut() else {
+            return;
+        };
+        fade.progress = match fade.duration > 0.0 {
+            true => (fade.progress + delta_time / fade.duration).min(1.0),
+            false => 1.0,
+        };
+        let fade = *fade;
+        self.changed = true;
+
+        if !fade.is_finished() {
+            let color = match fade.color.w {
+                0.0 => Vector4::ONE,
+                _ => fade.color,
+            };
+            self.color = color.with_w(fade.alpha());
+            return;
+        }
+
+        self.fade = None;
+        self.color = fade.color;
+        (
+            self.shader_settings.depth_write,
+            self.shader_settings.alpha_to_coverage_enabled,
+        ) = fade.settings;
+        if !fade.fade_in {
+            self.is_visible = false;
+        }
+    }
+}
+
+impl ObjectStorage {
+    /// Moves the fades of the objects forward, called by the engine every frame
+    pub(crate) fn advance_fades(&mut self, delta_time: f32) {
+        for object in self.values_mut() {
+            object.advance_fade(delta_time);
+        }
+    }
+}
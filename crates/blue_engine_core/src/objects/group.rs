@@ -0,0 +1,210 @@
+use super::{ObjectStorage, Transform};
+use crate::{Quaternion, StringBuffer, Vector3};
+
+/// A group of objects that share a transform, such as a level chunk or a UI panel with its
+/// children. Each member keeps its own local transform relative to the group, so moving,
+/// rotating, or scaling the group moves all of them together.
+///
+/// The group does not own the objects, they stay in the [ObjectStorage]. Members that are
+/// removed from the storage are skipped.
+#[derive(Debug, Clone)]
+pub struct ObjectGroup {
+    /// Name of the group, which can help later on for debugging
+    pub name: std::sync::Arc<str>,
+    /// The transform shared by all the members
+    transform: Transform,
+    /// The members and their transforms relative to the group
+    members: std::collections::HashMap<String, Transform>,
+}
+
+impl ObjectGroup {
+    /// Creates a new empty group at the origin
+    pub fn new(name: impl StringBuffer) -> Self {
+        Self {
+            name: name.as_arc(),
+            transform: Transform::default(),
+            members: std::collections::HashMap::new(),
+        }
+    }
+
+    /// Adds the object to the group. Its current transform is kept, and becomes relative to
+    /// the group. Returns false if the object does not exist
+    pub fn add(&mut self, object: impl StringBuffer, objects: &ObjectStorage) -> bool {
+        let Some(value) = objects.get(object.as_str()) else {
+            return false;
+        };
+
+        let local = self.transform.inverse().mul_transform(value.transform());
+        self.members.insert(object.as_string(), local);
+
+        true
+    }
+
+    /// Removes the object from the group, leaving it where it is.
+    /// Returns false if it was not a member
+    pub fn remove(&mut self, object: impl StringBuffer) -> bool {
+        self.members.remove(object.as_str()).is_some()
+    }
+
+    /// Returns true if the object is a member of the group
+    pub fn contains(&self, object: impl StringBuffer) -> bool {
+        self.members.contains_key(object.as_str())
+    }
+
+    /// Returns the names of the members
+    pub fn members(&self) -> impl Iterator<Item = &str> {
+        self.members.keys().map(|i| i.as_str())
+    }
+
+    /// Returns the transform shared by all the members
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    /// Replaces the transform of the group, and moves all the members with it
+    pub fn set_transform(&mut self, transform: Transform, objects: &mut ObjectStorage) {
+        self.transform = transform;
+        self.apply(objects);
+    }
+
+    /// Sets the position of the group
+    pub fn set_position(&mut self, position: impl Into<Vector3>, objects: &mut ObjectStorage) {
+        self.transform.position = position.into();
+        self.apply(objects);
+    }
+
+    /// Moves the group by the offset
+    pub fn translate(&mut self, offset: impl Into<Vector3>, objects: &mut ObjectStorage) {
+        self.transform.position += offset.into();
+        self.apply(objects);
+    }
+
+    /// Sets the rotation of the group, around its position
+    pub fn set_rotation(&mut self, rotation: Quaternion, objects: &mut ObjectStorage) {
+        self.transform.rotation = rotation;
+        self.apply(objects);
+    }
+
+    /// Sets the scale of the group, from its position
+    pub fn set_scale(&mut self, scale: impl Into<Vector3>, objects: &mut ObjectStorage) {
+        self.transform.scale = scale.into();
+        self.apply(objects);
+    }
+
+    /// Returns the transform of the member relative to the group
+    pub fn local_transform(&self, object: impl StringBuffer) -> Option<Transform> {
+        self.members.get(object.as_str()).copied()
+    }
+
+    /// Replaces the transform of the member relative to the group.
+    /// Returns false if it was not a member
+    pub fn set_local_transform(
+        &mut self,
+        object: impl StringBuffer,
+        transform: Transform,
+        objects: &mut ObjectStorage,
+    ) -> bool {
+        let Some(local) = self.members.get_mut(object.as_str()) else {
+            return false;
+        };
+        *local = transform;
+
+        if let Some(value) = objects.get_mut(object.as_str()) {
+            value.set_transform(self.transform.mul_transform(transform));
+        }
+
+        true
+    }
+
+    /// Clips all the members to the rectangle of the frame, see [crate::Object::set_scissor]
+    pub fn set_scissor(&self, scissor: Option<(u32, u32, u32, u32)>, objects: &mut ObjectStorage) {
+        for name in self.members.keys() {
+            if let Some(object) = objects.get_mut(name) {
+                object.set_scissor(scissor);
+            }
+        }
+    }
+
+    /// Writes the transform of the group on top of the local transforms of all the members
+    pub fn apply(&self, objects: &mut ObjectStorage) {
+        for (name, local) in self.members.iter() {
+            if let Some(object) = objects.get_mut(name) {
+                object.set_transform(self.transform.mul_transform(*local));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Object, ObjectSettings, Renderer, primitive_shapes::triangle_data};
+
+    fn add_object(name: &str, objects: &mut ObjectStorage, renderer: &mut Renderer) {
+        let (vertices, indices) = triangle_data();
+        let object = Object::new(name, vertices, indices, ObjectSettings::default(), renderer)
+            .expect("the triangle is built");
+        objects.add(name, object);
+    }
+
+    #[test]
+    fn members_keep_the_position_they_were_added_at() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = ObjectStorage::new();
+        add_object("door", &mut objects, &mut renderer);
+        objects
+            .get_mut("door")
+            .unwrap()
+            .set_position((1.0, 2.0, 3.0));
+
+        let mut group = ObjectGroup::new("room");
+        group.set_position((1.0, 0.0, 0.0), &mut objects);
+        assert!(group.add("door", &objects));
+
+        assert_eq!(
+            group.local_transform("door").unwrap().position,
+            Vector3::new(0.0, 2.0, 3.0)
+        );
+        assert_eq!(objects["door"].position, Vector3::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn moving_the_group_moves_the_members() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = ObjectStorage::new();
+        add_object("door", &mut objects, &mut renderer);
+        add_object("window", &mut objects, &mut renderer);
+        objects
+            .get_mut("door")
+            .unwrap()
+            .set_position((1.0, 0.0, 0.0));
+        objects
+            .get_mut("window")
+            .unwrap()
+            .translate((0.0, 1.0, 0.0));
+
+        let mut group = ObjectGroup::new("room");
+        group.add("door", &objects);
+        group.add("window", &objects);
+        group.translate((0.0, 0.0, 5.0), &mut objects);
+        group.translate((0.0, 0.0, 5.0), &mut objects);
+
+        assert_eq!(objects["door"].position, Vector3::new(1.0, 0.0, 10.0));
+        assert_eq!(objects["window"].position, Vector3::new(0.0, 1.0, 10.0));
+        assert_eq!(
+            objects["door"].transform().position,
+            objects["door"].position
+        );
+
+        // a member moved by itself afterwards starts from where the group put it
+        objects.get_mut("door").unwrap().translate((1.0, 0.0, 0.0));
+        assert_eq!(
+            objects["door"].transform().position,
+            Vector3::new(2.0, 0.0, 10.0)
+        );
+    }
+}
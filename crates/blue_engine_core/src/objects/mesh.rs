@@ -0,0 +1,144 @@
+use super::{Object, SubMesh};
+use crate::{
+    PipelineData, Renderer, UnsignedIntType, Vertex, VertexBuffers,
+    prelude::primitive_shapes::{
+        cube_data, rectangle_data, square_data, triangle_data, uv_sphere_data,
+    },
+};
+
+/// The data of a mesh that is shared between objects
+#[derive(Debug)]
+pub struct MeshData {
+    /// A list of Vertex
+    pub vertices: Vec<Vertex>,
+    /// A list of indices that dictates the order that vertices appear
+    pub indices: Vec<UnsignedIntType>,
+    /// The vertex and index buffers on the GPU
+    pub vertex_buffers: VertexBuffers,
+    /// Ranges of the indices that are drawn separately, used by objects that have no
+    /// sub meshes of their own
+    pub sub_meshes: Vec<SubMesh>,
+}
+
+/// A handle to a mesh whose vertices and GPU buffers are stored once, and can be
+/// referenced by many objects. Cloning the handle does not copy the mesh.
+#[derive(Debug, Clone)]
+pub struct Mesh(std::sync::Arc<MeshData>);
+impl std::ops::Deref for Mesh {
+    type Target = MeshData;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Mesh {
+    /// Creates a new mesh and uploads it to the GPU
+    pub fn new(
+        vertices: Vec<Vertex>,
+        indices: Vec<UnsignedIntType>,
+        renderer: &mut Renderer,
+    ) -> Self {
+        Self::new_with_sub_meshes(vertices, indices, Vec::new(), renderer)
+    }
+
+    /// Creates a new mesh made of several sub meshes, such as an imported model with
+    /// multiple materials, and uploads it to the GPU
+    pub fn new_with_sub_meshes(
+        vertices: Vec<Vertex>,
+        indices: Vec<UnsignedIntType>,
+        sub_meshes: Vec<SubMesh>,
+        renderer: &mut Renderer,
+    ) -> Self {
+        let vertex_buffers = renderer.build_vertex_buffer(&vertices, &indices);
+
+        Self(std::sync::Arc::new(MeshData {
+            vertices,
+            indices,
+            vertex_buffers,
+            sub_meshes,
+        }))
+    }
+
+    /// Creates a 2D triangle mesh
+    pub fn triangle(renderer: &mut Renderer) -> Self {
+        let (vertices, indices) = triangle_data();
+        Self::new(vertices, indices, renderer)
+    }
+
+    /// Creates a 2D square mesh
+    pub fn square(renderer: &mut Renderer) -> Self {
+        let (vertices, indices) = square_data();
+        Self::new(vertices, indices, renderer)
+    }
+
+    /// Creates a 2D rectangle mesh based on a width and height
+    pub fn rectangle(width: f32, height: f32, renderer: &mut Renderer) -> Self {
+        let (vertices, indices) = rectangle_data(width, height);
+        Self::new(vertices, indices, renderer)
+    }
+
+    /// Creates a 3D cube mesh
+    pub fn cube(renderer: &mut Renderer) -> Self {
+        let (vertices, indices) = cube_data();
+        Self::new(vertices, indices, renderer)
+    }
+
+    /// Creates a 3D UV Sphere mesh, the details are same as [crate::primitive_shapes::uv_sphere]
+    pub fn uv_sphere(details: (usize, usize, f32), renderer: &mut Renderer) -> Self {
+        let (vertices, indices) = uv_sphere_data(details);
+        Self::new(vertices, indices, renderer)
+    }
+
+    /// Returns a reference to the GPU buffers of the mesh, the buffers themselves are not copied
+    pub fn shared_vertex_buffers(&self) -> VertexBuffers {
+        VertexBuffers {
+            vertex_buffer: self.vertex_buffers.vertex_buffer.clone(),
+            index_buffer: self.vertex_buffers.index_buffer.clone(),
+            length: self.vertex_buffers.length,
+        }
+    }
+
+    /// Returns true if both handles point to the same mesh
+    pub fn ptr_eq(&self, other: &Mesh) -> bool {
+        std::sync::Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Returns the amount of handles to this mesh
+    pub fn handle_count(&self) -> usize {
+        std::sync::Arc::strong_count(&self.0)
+    }
+
+    /// A unique id of the mesh, valid as long as a handle to it exists
+    pub fn id(&self) -> usize {
+        std::sync::Arc::as_ptr(&self.0) as usize
+    }
+}
+
+impl Object {
+    /// Makes the object use the mesh instead of its own vertices.
+    ///
+    /// The object's own vertices and indices are cleared, and changes to them are ignored
+    /// until [Object::detach_mesh] is called.
+    pub fn set_mesh(&mut self, mesh: Mesh) -> &mut Self {
+        self.pipeline.vertex_buffer = PipelineData::Data(mesh.shared_vertex_buffers());
+        self.mesh_allocation = None;
+        self.vertices = Vec::new();
+        self.indices = Vec::new();
+        self.mesh = Some(mesh);
+
+        self
+    }
+
+    /// Stops using the shared mesh, and copies its vertices and indices into the object
+    /// so they can be modified on their own
+    pub fn detach_mesh(&mut self) -> &mut Self {
+        if let Some(mesh) = self.mesh.take() {
+            self.vertices = mesh.vertices.clone();
+            self.indices = mesh.indices.clone();
+            self.changed = true;
+        }
+
+        self
+    }
+}
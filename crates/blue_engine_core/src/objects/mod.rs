@@ -1,23 +1,40 @@
 //! # Objects
-//! 
+//!
 //! Objects make it easier to work with Blue Engine, it automates most of work needed for
 //! creating 3D objects and showing them on screen. A range of default objects are available
 //! as well as ability to customize each of them and even create your own! You can also
 //! customize almost everything there is about them!
 
 use crate::utils::default_resources::{DEFAULT_SHADER, DEFAULT_TEXTURE};
+use crate::utils::shader_variation::{FragmentUniform, ShaderVariation};
 use crate::{
-    Matrix4, Pipeline, PipelineData, Quaternion, Renderer, ShaderSettings, StringBuffer,
-    TextureData, UnsignedIntType, Vector3, Vector4, Vertex,
+    Matrix4, Pipeline, PipelineData, Quaternion, Renderer, SamplerSettings, ShaderSettings,
+    StringBuffer, TextureData, UnsignedIntType, Vector3, Vector4, Vertex,
 };
 
 mod transformation;
-pub use transformation::{RotateAmount, RotateAxis};
+pub use transformation::{RotateAmount, RotateAxis, Transform};
 mod instance;
 pub use instance::{Instance, InstanceRaw};
+mod group;
+pub use group::ObjectGroup;
+mod builder;
+pub use builder::ObjectBuilder;
+mod fade;
+pub use fade::Fade;
+mod mesh;
+pub use mesh::{Mesh, MeshData};
+mod object_id;
+pub use object_id::ObjectId;
+mod parallel_update;
+pub use parallel_update::PreparedUpdate;
+mod sub_mesh;
+pub use sub_mesh::{Material, SubMesh};
 mod shader_builder;
-pub use shader_builder::{ShaderBuilder, ShaderConfigs};
+pub use shader_builder::{ShaderBuilder, ShaderConfigs, ShaderError};
+mod observers;
 mod resource_sharing;
+mod tags;
 mod updating;
 
 /// Objects make it easier to work with Blue Engine, it automates most of work needed for
@@ -39,6 +56,12 @@ pub struct Object {
     pub instances: Vec<Instance>,
     /// instance buffer
     pub instance_buffer: wgpu::Buffer,
+    /// Should the instances be written to a second buffer while the GPU reads the first, see
+    /// [Object::set_double_buffered_instances]
+    pub double_buffered_instances: bool,
+    /// The instance buffer the GPU read in the previous frame, written next when the instances
+    /// are double buffered
+    pub(crate) back_instance_buffer: Option<wgpu::Buffer>,
     /// Dictates the size of your object in relation to the world
     pub size: Vector3,
     /// Dictates the position of your object in pixels
@@ -60,6 +83,9 @@ pub struct Object {
     pub inverse_transformation_matrix: Matrix4,
     /// The main color of your object
     pub color: Vector4,
+    /// The seed and time offset given to the shader next to the color, see
+    /// [Object::set_variation]
+    pub variation: crate::utils::shader_variation::ShaderVariation,
     /// A struct making it easier to manipulate specific parts of shader
     pub shader_builder: crate::objects::ShaderBuilder,
     /// Shader settings
@@ -72,9 +98,49 @@ pub struct Object {
     pub is_visible: bool,
     /// Objects with higher number get rendered later and appear "on top" when occupying the same space
     pub render_order: usize,
+    /// The part of the frame the object is clipped to, in pixels, see [Object::set_scissor]
+    /// (x, y, width, height)
+    pub scissor: Option<(u32, u32, u32, u32)>,
+    /// Should the object be merged with the other static objects, see [Object::set_static]
+    pub is_static: bool,
+    /// Should the mesh of the object be in the shared buffers of the pool, see
+    /// [Object::set_pooled]
+    pub is_pooled: bool,
+    /// The place of the mesh of the object in the shared buffers, while it is pooled
+    pub(crate) mesh_allocation: Option<crate::utils::mesh_pool::MeshAllocation>,
+    /// The quantized or pulled vertices made for the object alone, which are written again
+    /// in place when the mesh changes
+    pub(crate) own_vertex_buffers: Option<crate::VertexBuffers>,
+    /// The place of the object in the shared uniform buffer, while it only has the default
+    /// uniforms
+    pub(crate) uniform_slot: Option<crate::utils::uniform_pool::UniformSlot>,
+    /// The meshlets the object is culled by, see [Object::set_meshlets]
+    pub meshlets: Option<std::sync::Arc<crate::utils::meshlets::Meshlets>>,
+    /// The transformation matrix of the previous frame, kept by the engine after each frame.
+    /// None until the object is drawn once, see [Object::previous_transformation_matrix]
+    pub previous_transform: Option<Matrix4>,
+    /// The shared mesh used instead of the object's own vertices, if any
+    pub mesh: Option<Mesh>,
+    /// Ranges of the indices that are drawn separately, each with its own material.
+    /// If empty, the sub meshes of the shared mesh are used, if any
+    pub sub_meshes: Vec<SubMesh>,
+    /// Materials that the sub meshes refer to by index
+    pub materials: Vec<Material>,
+    /// Categories of the object, such as `"enemy"`, used to operate on groups of objects
+    pub tags: std::collections::HashSet<std::sync::Arc<str>>,
+    /// The handle of the object in the storage, given by [ObjectStorage::add]
+    pub(crate) id: Option<ObjectId>,
+    /// The buffers of the vertices written on the GPU, drawn instead of the vertices of the
+    /// object, see [Renderer::add_compute_mesh]
+    pub(crate) gpu_vertices: Option<crate::VertexBuffers>,
+    /// The texture set with [Object::set_texture], kept to upload it again if the device is lost
+    pub(crate) texture_source: Option<(std::sync::Arc<str>, TextureData, SamplerSettings)>,
+    /// The bytes written with [Object::set_uniform_buffer] by their buffer, kept to upload them
+    /// again if the device is lost
+    pub(crate) uniform_sources: Vec<(wgpu::Buffer, Vec<u8>)>,
+    /// The fade the object is in, see [Object::fade_in] and [Object::fade_out]
+    pub(crate) fade: Option<Fade>,
 }
-unsafe impl Send for Object {}
-unsafe impl Sync for Object {}
 
 /// Extra settings to customize objects on time of creation
 #[derive(Debug, Clone)]
@@ -92,18 +158,30 @@ impl Default for ObjectSettings {
         }
     }
 }
-unsafe impl Send for ObjectSettings {}
-unsafe impl Sync for ObjectSettings {}
 
 /// A unified way to handle objects
 ///
 /// This is a container for objects that is used to apply different operations on the objects at the same time.
-/// It can deref to the object hashmap itself when needed.
-pub struct ObjectStorage(std::collections::HashMap<String, Object>);
+/// The objects are kept by their [ObjectId], and can be looked up by their name like a map.
+pub struct ObjectStorage {
+    /// The objects by their handle, and the handles by their name
+    slots: object_id::Slots<Object>,
+    /// Objects waiting for their GPU resources to be built at the start of the next frame
+    queued: Vec<(ObjectId, ObjectBuilder)>,
+    /// The callbacks for the objects that are added, removed, and changed
+    observers: observers::ObjectObservers,
+    /// The tree of the boxes of the objects, if enabled
+    pub(crate) spatial_index: Option<crate::utils::spatial_index::SpatialIndex>,
+}
 impl ObjectStorage {
     /// Creates a new object storage
     pub fn new() -> Self {
-        ObjectStorage(std::collections::HashMap::new())
+        ObjectStorage {
+            slots: object_id::Slots::default(),
+            queued: Vec::new(),
+            observers: observers::ObjectObservers::default(),
+            spatial_index: None,
+        }
     }
 }
 impl Default for ObjectStorage {
@@ -111,9 +189,34 @@ impl Default for ObjectStorage {
         Self::new()
     }
 }
-unsafe impl Send for ObjectStorage {}
-unsafe impl Sync for ObjectStorage {}
-crate::macros::impl_deref!(ObjectStorage, std::collections::HashMap<String, Object>);
+impl<Q> std::ops::Index<&Q> for ObjectStorage
+where
+    String: std::borrow::Borrow<Q>,
+    Q: std::hash::Hash + Eq + ?Sized,
+{
+    type Output = Object;
+
+    /// Returns the object with the name, and panics if there is none
+    fn index(&self, name: &Q) -> &Object {
+        self.get(name).expect("no object with the name")
+    }
+}
+impl<'a> IntoIterator for &'a ObjectStorage {
+    type Item = (&'a String, &'a Object);
+    type IntoIter = Box<dyn Iterator<Item = Self::Item> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+impl<'a> IntoIterator for &'a mut ObjectStorage {
+    type Item = (&'a String, &'a mut Object);
+    type IntoIter = Box<dyn Iterator<Item = Self::Item> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter_mut())
+    }
+}
 
 impl Object {
     /// Creates a new object
@@ -129,11 +232,57 @@ impl Object {
     ) -> Result<Object, crate::error::Error> {
         let vertex_buffer = renderer.build_vertex_buffer(&vertices, &indices);
 
-        let uniform = renderer.build_uniform_buffer(&vec![
-            renderer.build_uniform_buffer_part("Transformation Matrix", Matrix4::IDENTITY),
-            renderer
-                .build_uniform_buffer_part("Color", crate::utils::default_resources::DEFAULT_COLOR),
-        ]);
+        Self::new_inner(
+            name,
+            vertices,
+            indices,
+            vertex_buffer,
+            None,
+            settings,
+            renderer,
+        )
+    }
+
+    /// Creates a new object that uses a shared mesh
+    ///
+    /// The vertices and GPU buffers of the mesh are not copied, and are shared with
+    /// all the other objects that use the same mesh.
+    pub fn new_from_mesh(
+        name: impl StringBuffer,
+        mesh: &Mesh,
+        settings: ObjectSettings,
+        renderer: &mut Renderer,
+    ) -> Result<Object, crate::error::Error> {
+        Self::new_inner(
+            name,
+            Vec::new(),
+            Vec::new(),
+            mesh.shared_vertex_buffers(),
+            Some(mesh.clone()),
+            settings,
+            renderer,
+        )
+    }
+
+    fn new_inner(
+        name: impl StringBuffer,
+        vertices: Vec<Vertex>,
+        indices: Vec<UnsignedIntType>,
+        vertex_buffer: crate::VertexBuffers,
+        mesh: Option<Mesh>,
+        settings: ObjectSettings,
+        renderer: &mut Renderer,
+    ) -> Result<Object, crate::error::Error> {
+        let uniform_slot = renderer.allocate_uniform_slot();
+        renderer.write_uniform_slot(
+            &uniform_slot,
+            Matrix4::IDENTITY,
+            FragmentUniform::new(
+                crate::utils::default_resources::DEFAULT_COLOR,
+                ShaderVariation::default(),
+            ),
+        );
+        let uniform = renderer.uniform_pool_bind_group();
 
         let shader_source =
             ShaderBuilder::new(DEFAULT_SHADER.to_string(), settings.camera_effect.clone());
@@ -166,6 +315,8 @@ impl Object {
             },
             instances: vec![instance],
             instance_buffer,
+            double_buffered_instances: false,
+            back_instance_buffer: None,
             uniform_layout: uniform.1,
             size: Vector3::ONE,
             position: Vector3::ZERO,
@@ -178,6 +329,7 @@ impl Object {
                 &Matrix4::IDENTITY,
             )),
             color: crate::utils::default_resources::DEFAULT_COLOR,
+            variation: ShaderVariation::default(),
             shader_builder: shader_source,
             shader_settings: settings.shader_settings,
             camera_effect: settings.camera_effect,
@@ -185,11 +337,31 @@ impl Object {
                 renderer.build_uniform_buffer_part("Transformation Matrix", Matrix4::IDENTITY),
                 renderer.build_uniform_buffer_part(
                     "Color",
-                    crate::utils::default_resources::DEFAULT_COLOR,
+                    FragmentUniform::new(
+                        crate::utils::default_resources::DEFAULT_COLOR,
+                        ShaderVariation::default(),
+                    ),
                 ),
             ],
             is_visible: true,
             render_order: 0,
+            scissor: None,
+            is_static: false,
+            is_pooled: false,
+            mesh_allocation: None,
+            own_vertex_buffers: None,
+            uniform_slot: Some(uniform_slot),
+            meshlets: None,
+            previous_transform: None,
+            mesh,
+            sub_meshes: Vec::new(),
+            materials: Vec::new(),
+            tags: std::collections::HashSet::new(),
+            id: None,
+            gpu_vertices: None,
+            texture_source: None,
+            uniform_sources: Vec::new(),
+            fade: None,
         })
     }
 }
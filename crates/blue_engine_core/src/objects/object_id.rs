@@ -0,0 +1,516 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::{Object, ObjectStorage};
+use crate::StringBuffer;
+
+/// A copyable handle to an object in the [ObjectStorage], returned by [ObjectStorage::add].
+///
+/// Unlike the name, a handle is never reused: once its object is removed, the handle stops
+/// resolving, even if another object is added with the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ObjectId {
+    index: u32,
+    generation: u32,
+}
+
+impl ObjectId {
+    /// The position of the handle in the storage, which is reused by later objects
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// How many times the position was reused before this handle
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+/// A value and the name it is stored under, or a handle given out for a value that does not
+/// exist yet
+#[derive(Debug)]
+struct Slot<T> {
+    generation: u32,
+    name: Option<String>,
+    value: Option<T>,
+}
+
+/// Values stored by the index of their [ObjectId], with their names as a secondary index.
+/// Looking up a handle does not hash, and a removed handle frees its index right away
+#[derive(Debug)]
+pub(crate) struct Slots<T> {
+    slots: Vec<Slot<T>>,
+    /// Indices of the slots that can be reused
+    free: Vec<u32>,
+    /// The handles of the values by their name
+    names: HashMap<String, ObjectId>,
+}
+
+impl<T> Default for Slots<T> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            names: HashMap::new(),
+        }
+    }
+}
+
+impl<T> Slots<T> {
+    /// Gives out a new handle, before its value exists
+    pub(crate) fn reserve(&mut self) -> ObjectId {
+        match self.free.pop() {
+            Some(index) => ObjectId {
+                index,
+                generation: self.slots[index as usize].generation,
+            },
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    name: None,
+                    value: None,
+                });
+                ObjectId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    /// Stores the value under the name and a handle given by [Slots::reserve], and returns
+    /// the value that had the name
+    pub(crate) fn fill(&mut self, id: ObjectId, name: String, value: T) -> Option<T> {
+        let replaced = self.remove(&name);
+
+        let slot = &mut self.slots[id.index as usize];
+        debug_assert!(slot.generation == id.generation && slot.value.is_none());
+        slot.name = Some(name.clone());
+        slot.value = Some(value);
+        self.names.insert(name, id);
+
+        replaced
+    }
+
+    /// Makes the handle stop resolving, and its index reusable
+    pub(crate) fn release(&mut self, id: ObjectId) -> Option<(String, T)> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index);
+
+        let name = slot.name.take();
+        let value = slot.value.take();
+        if let Some(name) = name.as_ref() {
+            self.names.remove(name);
+        }
+
+        name.zip(value)
+    }
+
+    pub(crate) fn remove<Q>(&mut self, name: &Q) -> Option<T>
+    where
+        String: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let id = *self.names.get(name)?;
+        self.release(id).map(|(_, value)| value)
+    }
+
+    pub(crate) fn id<Q>(&self, name: &Q) -> Option<ObjectId>
+    where
+        String: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.names.get(name).copied()
+    }
+
+    fn slot(&self, id: ObjectId) -> Option<&Slot<T>> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|i| i.generation == id.generation)
+    }
+
+    pub(crate) fn name_of(&self, id: ObjectId) -> Option<&str> {
+        let slot = self.slot(id)?;
+        slot.value.as_ref()?;
+        slot.name.as_deref()
+    }
+
+    pub(crate) fn get_by_id(&self, id: ObjectId) -> Option<&T> {
+        self.slot(id)?.value.as_ref()
+    }
+
+    pub(crate) fn get_by_id_mut(&mut self, id: ObjectId) -> Option<&mut T> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|i| i.generation == id.generation)?
+            .value
+            .as_mut()
+    }
+
+    pub(crate) fn get<Q>(&self, name: &Q) -> Option<&T>
+    where
+        String: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_by_id(self.id(name)?)
+    }
+
+    pub(crate) fn get_mut<Q>(&mut self, name: &Q) -> Option<&mut T>
+    where
+        String: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_by_id_mut(self.id(name)?)
+    }
+
+    pub(crate) fn get_key_value<Q>(&self, name: &Q) -> Option<(&String, &T)>
+    where
+        String: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slot = self.slot(self.id(name)?)?;
+        Some((slot.name.as_ref()?, slot.value.as_ref()?))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// The values in the order of their handles
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&String, &T)> {
+        self.slots
+            .iter()
+            .filter_map(|i| Some((i.name.as_ref()?, i.value.as_ref()?)))
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = (&String, &mut T)> {
+        self.slots
+            .iter_mut()
+            .filter_map(|i| Some((i.name.as_ref()?, i.value.as_mut()?)))
+    }
+}
+
+impl Object {
+    /// Returns the handle of the object, if it is in an [ObjectStorage]
+    pub fn id(&self) -> Option<ObjectId> {
+        self.id
+    }
+}
+
+impl ObjectStorage {
+    /// Adds the object under the name and returns its handle. An object that already has the
+    /// name is replaced, and its handle stops resolving
+    pub fn add(&mut self, name: impl StringBuffer, object: Object) -> ObjectId {
+        let id = self.slots.reserve();
+        self.insert_with_id(name, object, id);
+
+        id
+    }
+
+    /// Adds the object under the name and returns the object that had the name, whose handle
+    /// stops resolving. The object is given a new handle, even if it had one before
+    pub fn insert(&mut self, name: impl StringBuffer, object: Object) -> Option<Object> {
+        let id = self.slots.reserve();
+        self.insert_with_id(name, object, id)
+    }
+
+    /// Inserts the object under a handle given by [ObjectStorage::reserve_id], replacing the
+    /// object that already has the name
+    pub(crate) fn insert_with_id(
+        &mut self,
+        name: impl StringBuffer,
+        mut object: Object,
+        id: ObjectId,
+    ) -> Option<Object> {
+        object.id = Some(id);
+        let replaced = self.slots.fill(id, name.as_string(), object);
+
+        if let Some(replaced_id) = replaced.as_ref().and_then(|i| i.id) {
+            self.observers
+                .record_remove(replaced_id, || name.as_string());
+        }
+        self.observers.record_insert(id);
+
+        replaced
+    }
+
+    /// Gives out a new handle, before the object exists
+    pub(crate) fn reserve_id(&mut self) -> ObjectId {
+        self.slots.reserve()
+    }
+
+    /// Makes the handle stop resolving, and its index reusable
+    pub(crate) fn free_id(&mut self, id: ObjectId) {
+        self.slots.release(id);
+    }
+
+    /// Returns the handle of the object with the name
+    pub fn id<Q>(&self, name: &Q) -> Option<ObjectId>
+    where
+        String: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.slots.id(name)
+    }
+
+    /// Returns the name of the object with the handle, if it still exists
+    pub fn name_of(&self, id: ObjectId) -> Option<&str> {
+        self.slots.name_of(id)
+    }
+
+    /// Returns true if the object with the handle still exists
+    pub fn contains_id(&self, id: ObjectId) -> bool {
+        self.slots.get_by_id(id).is_some()
+    }
+
+    /// Returns the object with the handle, if it still exists
+    pub fn get_by_id(&self, id: ObjectId) -> Option<&Object> {
+        self.slots.get_by_id(id)
+    }
+
+    /// Returns the object with the handle mutably, if it still exists
+    pub fn get_by_id_mut(&mut self, id: ObjectId) -> Option<&mut Object> {
+        self.slots.get_by_id_mut(id)
+    }
+
+    /// Removes the object with the handle and returns it, if it still exists
+    pub fn remove_by_id(&mut self, id: ObjectId) -> Option<Object> {
+        self.slots.get_by_id(id)?;
+        let (name, object) = self.slots.release(id)?;
+        self.observers.record_remove(id, || name);
+
+        Some(object)
+    }
+
+    /// Removes the object with the name and returns it, if it exists. Its handle stops
+    /// resolving
+    pub fn remove<Q>(&mut self, name: &Q) -> Option<Object>
+    where
+        String: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_by_id(self.slots.id(name)?)
+    }
+
+    /// Returns the object with the name
+    pub fn get<Q>(&self, name: &Q) -> Option<&Object>
+    where
+        String: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.slots.get(name)
+    }
+
+    /// Returns the object with the name mutably
+    pub fn get_mut<Q>(&mut self, name: &Q) -> Option<&mut Object>
+    where
+        String: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.slots.get_mut(name)
+    }
+
+    /// Returns the object with the name, and the name it is stored under
+    pub fn get_key_value<Q>(&self, name: &Q) -> Option<(&String, &Object)>
+    where
+        String: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.slots.get_key_value(name)
+    }
+
+    /// Returns true if there is an object with the name
+    pub fn contains_key<Q>(&self, name: &Q) -> bool
+    where
+        String: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.slots.id(name).is_some()
+    }
+
+    /// The amount of objects
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns true if there are no objects
+    pub fn is_empty(&self) -> bool {
+        self.slots.len() == 0
+    }
+
+    /// The objects and their names, in the order of their handles
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Object)> {
+        self.slots.iter()
+    }
+
+    /// The objects and their names mutably, in the order of their handles
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&String, &mut Object)> {
+        self.slots.iter_mut()
+    }
+
+    /// The names of the objects
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.slots.iter().map(|(name, _)| name)
+    }
+
+    /// The objects
+    pub fn values(&self) -> impl Iterator<Item = &Object> {
+        self.slots.iter().map(|(_, object)| object)
+    }
+
+    /// The objects mutably
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Object> {
+        self.slots.iter_mut().map(|(_, object)| object)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ObjectSettings, Renderer, primitive_shapes::triangle_data};
+
+    fn object(name: &str, renderer: &mut Renderer) -> Object {
+        let (vertices, indices) = triangle_data();
+        Object::new(name, vertices, indices, ObjectSettings::default(), renderer)
+            .expect("the triangle is built")
+    }
+
+    #[test]
+    fn replacing_by_name_invalidates_the_old_handle() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = ObjectStorage::new();
+
+        let old = objects.add("player", object("player", &mut renderer));
+        assert_eq!(objects.name_of(old), Some("player"));
+        assert!(objects.get_by_id(old).is_some());
+
+        let new = objects.add("player", object("player", &mut renderer));
+        assert_ne!(old, new);
+        assert_eq!(objects.name_of(old), None);
+        assert!(!objects.contains_id(old));
+        assert!(objects.get_by_id_mut(old).is_none());
+        assert_eq!(objects.name_of(new), Some("player"));
+        assert_eq!(objects.id("player"), Some(new));
+    }
+
+    #[test]
+    fn removing_by_handle() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = ObjectStorage::new();
+
+        let id = objects.add("crate", object("crate", &mut renderer));
+        assert!(objects.remove_by_id(id).is_some());
+        assert!(!objects.contains_key("crate"));
+        assert!(!objects.contains_id(id));
+        assert!(objects.remove_by_id(id).is_none());
+
+        // the index is reused, but not the handle
+        let reused = objects.add("crate", object("crate", &mut renderer));
+        assert_eq!(reused.index(), id.index());
+        assert_eq!(reused.generation(), id.generation() + 1);
+        assert!(!objects.contains_id(id));
+        assert!(objects.contains_id(reused));
+    }
+
+    #[test]
+    fn removing_by_name_frees_the_handle() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = ObjectStorage::new();
+
+        let id = objects.add("door", object("door", &mut renderer));
+        let removed = objects.remove("door").expect("the object exists");
+        assert_eq!(objects.name_of(id), None);
+
+        // put back, the object is given a new handle in the freed index
+        assert!(objects.insert("door", removed).is_none());
+        let given = objects.id("door").expect("the object exists");
+        assert_ne!(given, id);
+        assert_eq!(given.index(), id.index());
+        assert_eq!(objects.get_by_id(given).and_then(|i| i.id()), Some(given));
+        assert_eq!(objects["door"].id(), Some(given));
+    }
+
+    #[test]
+    fn slots_resolve_handles_and_names() {
+        let mut slots = Slots::default();
+
+        let first = slots.reserve();
+        assert_eq!(slots.fill(first, "first".into(), 1), None);
+        let second = slots.reserve();
+        assert_eq!(slots.fill(second, "second".into(), 2), None);
+
+        assert_eq!(slots.len(), 2);
+        assert_eq!(slots.get("first"), Some(&1));
+        assert_eq!(slots.get_by_id(second), Some(&2));
+        assert_eq!(slots.name_of(second), Some("second"));
+        assert_eq!(slots.id("second"), Some(second));
+        *slots.get_by_id_mut(first).unwrap() = 10;
+        assert_eq!(
+            slots.get_key_value("first"),
+            Some((&"first".to_string(), &10))
+        );
+
+        // in the order of the handles
+        let names = slots.iter().map(|(i, _)| i.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["first", "second"]);
+    }
+
+    #[test]
+    fn slots_replace_and_reuse() {
+        let mut slots = Slots::default();
+
+        let old = slots.reserve();
+        slots.fill(old, "player".into(), 1);
+        let new = slots.reserve();
+        assert_eq!(slots.fill(new, "player".into(), 2), Some(1));
+        assert_eq!(slots.get_by_id(old), None);
+        assert_eq!(slots.name_of(old), None);
+        assert_eq!(slots.get("player"), Some(&2));
+        assert_eq!(slots.len(), 1);
+
+        // the index of the replaced value is reused with the next generation
+        let reused = slots.reserve();
+        assert_eq!(reused.index(), old.index());
+        assert_eq!(reused.generation(), old.generation() + 1);
+        slots.fill(reused, "enemy".into(), 3);
+        assert_eq!(slots.get_by_id(old), None);
+        assert_eq!(slots.get_by_id(reused), Some(&3));
+
+        assert_eq!(slots.remove("enemy"), Some(3));
+        assert_eq!(slots.remove("enemy"), None);
+        assert_eq!(slots.get_by_id(reused), None);
+        assert_eq!(slots.id("enemy"), None);
+        assert_eq!(slots.len(), 1);
+    }
+
+    #[test]
+    fn reserved_slots_do_not_resolve_until_filled() {
+        let mut slots = Slots::<u32>::default();
+
+        let reserved = slots.reserve();
+        assert_eq!(slots.get_by_id(reserved), None);
+        assert_eq!(slots.name_of(reserved), None);
+        assert_eq!(slots.len(), 0);
+        assert_eq!(slots.iter().count(), 0);
+
+        // released before being filled, the index is free again
+        assert_eq!(slots.release(reserved), None);
+        assert_eq!(slots.release(reserved), None);
+        let next = slots.reserve();
+        assert_eq!(next.index(), reserved.index());
+        assert_ne!(next, reserved);
+        assert_eq!(slots.slots.len(), 1);
+    }
+}
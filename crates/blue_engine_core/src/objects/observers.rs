@@ -0,0 +1,223 @@
+use super::{Object, ObjectId, ObjectStorage};
+
+type ObjectCallback = Box<dyn FnMut(&str, &Object) + Send + Sync>;
+type RemoveCallback = Box<dyn FnMut(&str) + Send + Sync>;
+
+/// A change to the storage waiting to be reported
+#[derive(Debug, Clone, PartialEq)]
+enum ObjectEvent {
+    Inserted(ObjectId),
+    Removed(ObjectId, String),
+}
+
+/// The callbacks for the changes to the objects, see [ObjectStorage::on_insert]
+#[derive(Default)]
+pub(crate) struct ObjectObservers {
+    on_insert: Vec<ObjectCallback>,
+    on_remove: Vec<RemoveCallback>,
+    on_change: Vec<ObjectCallback>,
+    /// The objects added and removed since the last notification, recorded while there are
+    /// callbacks
+    events: Vec<ObjectEvent>,
+    /// The objects reported as added by the last notification, which are not reported as
+    /// changed in the same frame
+    inserted: Vec<ObjectId>,
+}
+
+impl ObjectObservers {
+    /// Returns true if there are callbacks to record the changes for
+    pub(crate) fn is_observing(&self) -> bool {
+        !(self.on_insert.is_empty() && self.on_remove.is_empty() && self.on_change.is_empty())
+    }
+
+    /// Records the object that was added to the storage
+    pub(crate) fn record_insert(&mut self, id: ObjectId) {
+        if self.is_observing() {
+            self.events.push(ObjectEvent::Inserted(id));
+        }
+    }
+
+    /// Records the object that was removed from the storage. An object added and removed
+    /// before it was reported is not reported at all
+    pub(crate) fn record_remove(&mut self, id: ObjectId, name: impl FnOnce() -> String) {
+        if !self.is_observing() {
+            return;
+        }
+
+        match self
+            .events
+            .iter()
+            .position(|i| *i == ObjectEvent::Inserted(id))
+        {
+            Some(index) => {
+                self.events.remove(index);
+            }
+            None => self.events.push(ObjectEvent::Removed(id, name())),
+        }
+    }
+
+    /// Reports the object that is about to be updated on the GPU, unless it was just added
+    pub(crate) fn report_change(&mut self, name: &str, object: &Object) {
+        if self.on_change.is_empty() || object.id.is_some_and(|i| self.inserted.contains(&i)) {
+            return;
+        }
+        self.on_change.iter_mut().for_each(|i| i(name, object));
+    }
+}
+
+impl ObjectStorage {
+    /// Calls the function for each object added to the storage, once per frame before the
+    /// objects are updated. An object replaced under the same name counts as removed and added
+    pub fn on_insert(&mut self, callback: impl FnMut(&str, &Object) + Send + Sync + 'static) {
+        self.observers.on_insert.push(Box::new(callback));
+    }
+
+    /// Calls the function with the name of each object removed from the storage
+    pub fn on_remove(&mut self, callback: impl FnMut(&str) + Send + Sync + 'static) {
+        self.observers.on_remove.push(Box::new(callback));
+    }
+
+    /// Calls the function for each object that was changed, such as moved or given a new
+    /// texture, and is about to be updated on the GPU. Newly added objects are not included
+    pub fn on_change(&mut self, callback: impl FnMut(&str, &Object) + Send + Sync + 'static) {
+        self.observers.on_change.push(Box::new(callback));
+    }
+
+    /// Removes all the callbacks of [ObjectStorage::on_insert], [ObjectStorage::on_remove],
+    /// and [ObjectStorage::on_change]
+    pub fn clear_observers(&mut self) {
+        self.observers = ObjectObservers::default();
+    }
+
+    /// Reports the objects added and removed since the last call to the callbacks. The
+    /// objects that changed are reported as they are updated, see
+    /// [ObjectStorage::update_changed]
+    pub(crate) fn notify_observers(&mut self) {
+        let observers = &mut self.observers;
+        observers.inserted.clear();
+
+        let mut events = std::mem::take(&mut observers.events);
+        for event in events.drain(..) {
+            match event {
+                ObjectEvent::Inserted(id) => {
+                    let (Some(name), Some(object)) =
+                        (self.slots.name_of(id), self.slots.get_by_id(id))
+                    else {
+                        continue;
+                    };
+                    observers.on_insert.iter_mut().for_each(|i| i(name, object));
+                    observers.inserted.push(id);
+                }
+                ObjectEvent::Removed(_, name) => {
+                    observers.on_remove.iter_mut().for_each(|i| i(&name));
+                }
+            }
+        }
+        // the emptied queue is kept, so it does not allocate again
+        observers.events = events;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observing() -> ObjectObservers {
+        let mut observers = ObjectObservers::default();
+        observers.on_remove.push(Box::new(|_| {}));
+        observers
+    }
+
+    fn id(index: u32) -> ObjectId {
+        let mut slots = crate::objects::object_id::Slots::<()>::default();
+        (0..index).for_each(|_| {
+            slots.reserve();
+        });
+        slots.reserve()
+    }
+
+    #[test]
+    fn nothing_is_recorded_without_callbacks() {
+        let mut observers = ObjectObservers::default();
+        observers.record_insert(id(0));
+        observers.record_remove(id(1), || "removed".into());
+
+        assert!(observers.events.is_empty());
+    }
+
+    #[test]
+    fn changes_are_recorded_in_order() {
+        let mut observers = observing();
+        observers.record_insert(id(0));
+        observers.record_remove(id(1), || "old".into());
+        observers.record_insert(id(2));
+
+        assert_eq!(
+            observers.events,
+            [
+                ObjectEvent::Inserted(id(0)),
+                ObjectEvent::Removed(id(1), "old".into()),
+                ObjectEvent::Inserted(id(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn objects_added_and_removed_before_reporting_are_not_reported() {
+        let mut observers = observing();
+        observers.record_insert(id(0));
+        observers.record_insert(id(1));
+        observers.record_remove(id(0), || unreachable!("the removal is not reported"));
+
+        assert_eq!(observers.events, [ObjectEvent::Inserted(id(1))]);
+    }
+
+    #[test]
+    fn storage_reports_inserts_removals_and_changes() {
+        use std::sync::{Arc, Mutex};
+
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let object = |name: &str, renderer: &mut crate::Renderer| {
+            let (vertices, indices) = crate::primitive_shapes::triangle_data();
+            Object::new(name, vertices, indices, Default::default(), renderer)
+                .expect("the triangle is built")
+        };
+
+        let mut objects = ObjectStorage::new();
+        objects.add("existing", object("existing", &mut renderer));
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let inserts = log.clone();
+        objects.on_insert(move |name, _| inserts.lock().unwrap().push(format!("+{name}")));
+        let removals = log.clone();
+        objects.on_remove(move |name| removals.lock().unwrap().push(format!("-{name}")));
+        let changes = log.clone();
+        objects.on_change(move |name, _| changes.lock().unwrap().push(format!("~{name}")));
+
+        objects.add("new", object("new", &mut renderer));
+        objects
+            .get_mut("new")
+            .unwrap()
+            .set_position((1.0, 0.0, 0.0));
+        objects.add("brief", object("brief", &mut renderer));
+        objects.remove("brief");
+        objects.add("existing", object("existing", &mut renderer));
+        objects.notify_observers();
+        objects.update_changed(&mut renderer);
+        assert_eq!(
+            std::mem::take(&mut *log.lock().unwrap()),
+            ["+new", "-existing", "+existing"]
+        );
+
+        objects
+            .get_mut("new")
+            .unwrap()
+            .set_position((2.0, 0.0, 0.0));
+        objects.remove("existing");
+        objects.notify_observers();
+        objects.update_changed(&mut renderer);
+        assert_eq!(*log.lock().unwrap(), ["-existing", "~new"]);
+    }
+}
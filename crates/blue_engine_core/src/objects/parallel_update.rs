@@ -0,0 +1,100 @@
+use rayon::prelude::*;
+
+use super::{Instance, InstanceRaw, Object, ObjectStorage};
+use crate::{Matrix4, Quaternion, Renderer, Vector4};
+
+/// The CPU side of an object update, such as the composed matrices and the built instances.
+/// It is built without the renderer, so many objects can be prepared on different threads and
+/// only the GPU writes are left for the render thread
+#[derive(Debug, Clone)]
+pub struct PreparedUpdate {
+    /// The transformation matrix, composed from the translation, rotation, and scale
+    pub transform_matrix: Matrix4,
+    /// The color of the object
+    pub color: Vector4,
+    /// The instances, ready to be written to the instance buffer
+    pub instances: Vec<InstanceRaw>,
+}
+
+impl PreparedUpdate {
+    /// Composes the matrices and builds the instances
+    pub fn new(
+        translation_matrix: Matrix4,
+        rotation_quaternion: Quaternion,
+        scale_matrix: Matrix4,
+        color: Vector4,
+        instances: &[Instance],
+    ) -> Self {
+        Self {
+            transform_matrix: translation_matrix
+                * Matrix4::from_quat(rotation_quaternion)
+                * scale_matrix,
+            color,
+            instances: instances.iter().map(Instance::build).collect(),
+        }
+    }
+}
+
+impl Object {
+    /// Builds the CPU side of the update, without touching the GPU. See [PreparedUpdate]
+    pub fn prepare_update(&self) -> PreparedUpdate {
+        PreparedUpdate::new(
+            self.translation_matrix,
+            self.rotation_quaternion,
+            self.scale_matrix,
+            self.color,
+            &self.instances,
+        )
+    }
+
+    /// Applies an update prepared by [Object::prepare_update], and writes it to the GPU
+    pub fn update_prepared(&mut self, renderer: &mut Renderer, prepared: PreparedUpdate) {
+        self.update_vertex_buffer(renderer);
+        let updated_buffer =
+            self.update_uniform_buffer_inner(renderer, prepared.transform_matrix, prepared.color);
+        self.pipeline.uniform = crate::PipelineData::Data(Some(updated_buffer.0));
+        self.uniform_layout = updated_buffer.1;
+        self.update_shader(renderer);
+        self.write_instances(renderer, &prepared.instances);
+        self.changed = false;
+    }
+}
+
+impl ObjectStorage {
+    /// Updates all the changed objects. The objects are prepared in parallel, and only the
+    /// GPU writes run one after another on the render thread. The changed objects are
+    /// reported to the callbacks of [ObjectStorage::on_change] on the way
+    pub fn update_changed(&mut self, renderer: &mut Renderer) {
+        self.refresh_spatial_index();
+
+        // the storage is not changed in between, so both passes visit the objects in the same order
+        let observers = &mut self.observers;
+        let prepared = self
+            .slots
+            .iter()
+            .filter(|(_, i)| i.changed)
+            .map(|(name, i)| {
+                observers.report_change(name, i);
+                (
+                    i.translation_matrix,
+                    i.rotation_quaternion,
+                    i.scale_matrix,
+                    i.color,
+                    i.instances.as_slice(),
+                )
+            })
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|(translation, rotation, scale, color, instances)| {
+                PreparedUpdate::new(translation, rotation, scale, color, instances)
+            })
+            .collect::<Vec<_>>();
+
+        self.slots
+            .iter_mut()
+            .map(|(_, i)| i)
+            .filter(|i| i.changed)
+            .zip(prepared)
+            .for_each(|(object, prepared)| object.update_prepared(renderer, prepared));
+    }
+}
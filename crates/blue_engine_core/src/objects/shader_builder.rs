@@ -1,5 +1,8 @@
 /// Configuration type for ShaderBuilder
-pub type ShaderConfigs = Vec<(String, Box<dyn Fn(Option<std::sync::Arc<str>>) -> String>)>;
+pub type ShaderConfigs = Vec<(
+    String,
+    Box<dyn Fn(Option<std::sync::Arc<str>>) -> String + Send + Sync>,
+)>;
 
 /// Helps with building and updating shader code
 pub struct ShaderBuilder {
@@ -9,6 +12,41 @@ pub struct ShaderBuilder {
     pub camera_effect: Option<std::sync::Arc<str>>,
     /// configurations to be applied to the shader
     pub configs: ShaderConfigs,
+    /// the shader as it was before the configurations were applied
+    pub source: String,
+    /// The line of the source each line of the built shader came from, and the index of the
+    /// configuration that made it, if any
+    line_map: Vec<(usize, Option<usize>)>,
+}
+
+/// A shader that failed to compile, with the error mapped back to the original source
+#[derive(Debug, Clone)]
+pub struct ShaderError {
+    /// The error reported by the shader compiler
+    pub message: String,
+    /// The full report of the compiler, pointing into the built shader
+    pub report: String,
+    /// The line of the error in the built shader, from 1
+    pub line: Option<usize>,
+    /// The line of the error in the original source, from 1
+    pub source_line: Option<usize>,
+    /// The configuration placeholder that made the line, such as `//@CAMERA_VERTEX`
+    pub placeholder: Option<String>,
+    /// The built shader, with all the configurations applied
+    pub expanded_source: String,
+}
+impl std::fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(source_line) = self.source_line {
+            write!(f, " at line {source_line} of the source")?;
+        }
+        if let Some(placeholder) = self.placeholder.as_ref() {
+            write!(f, ", in the code substituted for {placeholder}")?;
+        }
+
+        Ok(())
+    }
 }
 
 impl ShaderBuilder {
@@ -24,6 +62,10 @@ impl ShaderBuilder {
                         if camera_effect.is_some() {
                             r#"struct CameraUniforms {
                             camera_matrix: mat4x4<f32>,
+                            camera_position: vec4<f32>,
+                            fog_color: vec4<f32>,
+                            fog: vec4<f32>,
+                            time: vec4<f32>,
                         };
                         @group(1) @binding(0)
                         var<uniform> camera_uniform: CameraUniforms;"#
@@ -44,7 +86,91 @@ impl ShaderBuilder {
                         }
                     }),
                 ),
+                // before //@FOG, which is a prefix of their names
+                (
+                    "//@FOG_OUTPUT".to_string(),
+                    Box::new(|_| "@location(15) fog_distance: f32,".to_string()),
+                ),
+                (
+                    "//@FOG_VERTEX".to_string(),
+                    Box::new(|camera_effect| {
+                        if camera_effect.is_some() {
+                            r#"out.fog_distance = distance((model_matrix * (transform_uniform.transform_matrix * vec4<f32>(input.position, 1.0))).xyz, camera_uniform.camera_position.xyz);"#
+                            .to_string()
+                        } else {
+                            "out.fog_distance = 0.0;".to_string()
+                        }
+                    }),
+                ),
+                (
+                    "//@VARIATION".to_string(),
+                    Box::new(|camera_effect| {
+                        // the objects without a camera still have the uniform of the main camera
+                        // bound, which the time is read from
+                        let time = match camera_effect.is_some() {
+                            true => "",
+                            false => {
+                                r#"struct TimeUniforms {
+                            camera_matrix: mat4x4<f32>,
+                            camera_position: vec4<f32>,
+                            fog_color: vec4<f32>,
+                            fog: vec4<f32>,
+                            time: vec4<f32>,
+                        };
+                        @group(1) @binding(0)
+                        var<uniform> camera_uniform: TimeUniforms;"#
+                            }
+                        };
+
+                        format!(
+                            r#"{time}
+                        fn hash_u32(value: u32) -> u32 {{
+                            let state = value * 747796405u + 2891336453u;
+                            let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+                            return (word >> 22u) ^ word;
+                        }}
+                        fn shader_time() -> f32 {{
+                            return camera_uniform.time.x;
+                        }}
+                        fn object_time() -> f32 {{
+                            return camera_uniform.time.x + fragment_uniforms.time_offset;
+                        }}
+                        fn instance_random(instance_index: u32) -> f32 {{
+                            return f32(hash_u32(fragment_uniforms.seed ^ hash_u32(instance_index))) / 4294967295.0;
+                        }}"#
+                        )
+                    }),
+                ),
+                (
+                    "//@FOG".to_string(),
+                    Box::new(|camera_effect| {
+                        // the fog is by the distance from the camera, so there is none without it
+                        if camera_effect.is_some() {
+                            r#"override fog_enabled: bool = true;
+                        fn apply_fog(color: vec4<f32>, fog_distance: f32) -> vec4<f32> {
+                            let mode = camera_uniform.fog.x;
+                            if !fog_enabled || mode == 0.0 {
+                                return color;
+                            }
+                            var visibility = exp(-camera_uniform.fog.w * fog_distance);
+                            if mode == 1.0 {
+                                let range = max(camera_uniform.fog.z - camera_uniform.fog.y, 0.0001);
+                                visibility = clamp((camera_uniform.fog.z - fog_distance) / range, 0.0, 1.0);
+                            }
+                            return vec4<f32>(mix(camera_uniform.fog_color.rgb, color.rgb, visibility), color.a);
+                        }"#
+                            .to_string()
+                        } else {
+                            r#"fn apply_fog(color: vec4<f32>, fog_distance: f32) -> vec4<f32> {
+                            return color;
+                        }"#
+                            .to_string()
+                        }
+                    }),
+                ),
             ],
+            source: String::new(),
+            line_map: Vec::new(),
         };
         shader_builder.build();
 
@@ -59,8 +185,78 @@ impl ShaderBuilder {
 
     /// Builds the shader with the configuration defined
     pub fn build(&mut self) {
-        for i in &self.configs {
-            self.shader = self.shader.replace(&i.0, &i.1(self.camera_effect.clone()));
+        self.source = self.shader.clone();
+
+        // the placeholders never span lines, so each line is built on its own to keep track
+        // of where the lines of the built shader came from
+        let mut lines = Vec::new();
+        let mut line_map = Vec::new();
+        for (index, line) in self.source.split('\n').enumerate() {
+            let mut built = line.to_string();
+            let mut config = None;
+            for (config_index, i) in self.configs.iter().enumerate() {
+                if built.contains(i.0.as_str()) {
+                    built = built.replace(&i.0, &i.1(self.camera_effect.clone()));
+                    config.get_or_insert(config_index);
+                }
+            }
+
+            line_map.extend(built.split('\n').map(|_| (index + 1, config)));
+            lines.push(built);
         }
+
+        self.shader = lines.join("\n");
+        self.line_map = line_map;
+    }
+
+    /// Compiles the built shader on the CPU, and returns the error mapped back to the
+    /// original source if it fails. This catches the mistakes before they reach the GPU
+    pub fn validate(&self) -> Result<(), crate::error::Error> {
+        use wgpu::naga;
+
+        let module = naga::front::wgsl::parse_str(&self.shader).map_err(|e| {
+            self.shader_error(
+                e.message().to_string(),
+                e.emit_to_string(&self.shader),
+                e.location(&self.shader),
+            )
+        })?;
+
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .map_err(|e| {
+            self.shader_error(
+                e.to_string(),
+                e.emit_to_string(&self.shader),
+                e.location(&self.shader),
+            )
+        })?;
+
+        Ok(())
+    }
+
+    fn shader_error(
+        &self,
+        message: String,
+        report: String,
+        location: Option<wgpu::naga::SourceLocation>,
+    ) -> crate::error::Error {
+        let line = location.map(|i| i.line_number as usize);
+        let mapped = line.and_then(|i| self.line_map.get(i - 1));
+
+        crate::error::Error::ShaderCompileError(Box::new(ShaderError {
+            message,
+            report,
+            line,
+            source_line: mapped.map(|i| i.0),
+            placeholder: mapped
+                .and_then(|i| i.1)
+                .and_then(|i| self.configs.get(i))
+                .map(|i| i.0.clone()),
+            expanded_source: self.shader.clone(),
+        }))
     }
 }
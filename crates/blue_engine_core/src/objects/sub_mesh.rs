@@ -0,0 +1,135 @@
+use super::{Object, ShaderBuilder};
+use crate::{Renderer, SamplerSettings, Shaders, StringBuffer, TextureData, Textures};
+
+/// The appearance of a sub mesh. Anything that is not set falls back to the object's own
+#[derive(Debug)]
+pub struct Material {
+    /// Name of the material, which can help later on for debugging
+    pub name: std::sync::Arc<str>,
+    /// The texture used instead of the object's texture
+    pub texture: Option<Textures>,
+    /// The shader used instead of the object's shader
+    pub shader: Option<Shaders>,
+    /// The texture made by [Object::add_textured_material] with what it was made from, kept to
+    /// upload it again if the device is lost while it is still the texture of the material
+    pub(crate) texture_source: Option<(Textures, TextureData, SamplerSettings)>,
+    /// The shader made by [Object::set_material_shader] with its source, kept to build it again
+    /// if the device is lost while it is still the shader of the material
+    pub(crate) shader_source: Option<(Shaders, String)>,
+}
+
+impl Material {
+    /// Creates a new material that uses the object's texture and shader
+    pub fn new(name: impl StringBuffer) -> Self {
+        Self {
+            name: name.as_arc(),
+            texture: None,
+            shader: None,
+            texture_source: None,
+            shader_source: None,
+        }
+    }
+}
+
+/// A range of the indices of an object or mesh that is drawn with its own material
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubMesh {
+    /// Name of the sub mesh, which can help later on for debugging
+    pub name: std::sync::Arc<str>,
+    /// The range of indices that are drawn
+    pub indices: std::ops::Range<u32>,
+    /// Index of the material in the object's materials. If `None` or out of bounds,
+    /// the object's own texture and shader are used
+    pub material: Option<usize>,
+}
+
+impl SubMesh {
+    /// Creates a new sub mesh
+    pub fn new(
+        name: impl StringBuffer,
+        indices: std::ops::Range<u32>,
+        material: Option<usize>,
+    ) -> Self {
+        Self {
+            name: name.as_arc(),
+            indices,
+            material,
+        }
+    }
+}
+
+impl Object {
+    /// Adds a range of indices that is drawn with the given material.
+    ///
+    /// Once an object has sub meshes, only the indices covered by them are drawn.
+    pub fn add_sub_mesh(
+        &mut self,
+        name: impl StringBuffer,
+        indices: std::ops::Range<u32>,
+        material: Option<usize>,
+    ) -> &mut Self {
+        self.sub_meshes.push(SubMesh::new(name, indices, material));
+
+        self
+    }
+
+    /// Returns the sub meshes that are drawn, either the object's or the shared mesh's
+    pub fn active_sub_meshes(&self) -> &[SubMesh] {
+        match self.mesh.as_ref() {
+            Some(mesh) if self.sub_meshes.is_empty() => &mesh.sub_meshes,
+            _ => &self.sub_meshes,
+        }
+    }
+
+    /// Adds a material and returns its index, to be used by the sub meshes
+    pub fn add_material(&mut self, material: Material) -> usize {
+        self.materials.push(material);
+
+        self.materials.len() - 1
+    }
+
+    /// Creates a material with the given texture, and returns its index
+    pub fn add_textured_material(
+        &mut self,
+        name: impl StringBuffer,
+        texture_data: TextureData,
+        texture_mode: impl Into<SamplerSettings>,
+        renderer: &mut Renderer,
+    ) -> Result<usize, crate::error::Error> {
+        let texture_mode = texture_mode.into();
+        let mut material = Material::new(name.as_str());
+        let texture = renderer.build_texture(name, texture_data.clone(), texture_mode)?;
+        material.texture = Some(texture.clone());
+        material.texture_source = Some((texture, texture_data, texture_mode));
+
+        Ok(self.add_material(material))
+    }
+
+    /// Sets the shader of a material. The shader uses the same uniforms and camera as the object
+    pub fn set_material_shader(
+        &mut self,
+        material: usize,
+        shader_source: String,
+        renderer: &mut Renderer,
+    ) -> Result<&mut Self, crate::error::Error> {
+        let shader_builder = ShaderBuilder::new(shader_source, self.camera_effect.clone());
+        shader_builder.validate()?;
+        let Some(material) = self.materials.get_mut(material) else {
+            return Err(crate::error::Error::Custom(format!(
+                "Material {material} does not exist on object {}",
+                self.name
+            )));
+        };
+
+        let shader = renderer.build_shader(
+            material.name.as_ref(),
+            shader_builder.shader.clone(),
+            Some(&self.uniform_layout),
+            self.shader_settings,
+        );
+        material.shader = Some(shader.clone());
+        material.shader_source = Some((shader, shader_builder.shader));
+
+        Ok(self)
+    }
+}
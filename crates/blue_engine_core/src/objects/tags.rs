@@ -0,0 +1,87 @@
+use super::{Object, ObjectStorage};
+use crate::{StringBuffer, Vector3};
+
+impl Object {
+    /// Adds a tag to the object, such as `"enemy"`, so it can be found by its category
+    pub fn add_tag(&mut self, tag: impl StringBuffer) -> &mut Self {
+        self.tags.insert(tag.as_arc());
+        self
+    }
+
+    /// Removes a tag from the object
+    pub fn remove_tag(&mut self, tag: impl StringBuffer) -> &mut Self {
+        self.tags.remove(tag.as_str());
+        self
+    }
+
+    /// Returns true if the object has the tag
+    pub fn has_tag(&self, tag: impl StringBuffer) -> bool {
+        self.tags.contains(tag.as_str())
+    }
+
+    /// Returns the tags of the object
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags.iter().map(|i| i.as_ref())
+    }
+}
+
+impl ObjectStorage {
+    /// Returns the objects that have the tag
+    pub fn iter_tagged(&self, tag: impl StringBuffer) -> impl Iterator<Item = (&String, &Object)> {
+        let tag = tag.as_arc();
+        self.iter()
+            .filter(move |(_, object)| object.tags.contains(&tag))
+    }
+
+    /// Returns the objects that have the tag, mutably
+    pub fn iter_tagged_mut(
+        &mut self,
+        tag: impl StringBuffer,
+    ) -> impl Iterator<Item = (&String, &mut Object)> {
+        let tag = tag.as_arc();
+        self.iter_mut()
+            .filter(move |(_, object)| object.tags.contains(&tag))
+    }
+
+    /// Runs the function on every object that has the tag, and returns how many there were
+    pub fn update_tagged(
+        &mut self,
+        tag: impl StringBuffer,
+        mut function: impl FnMut(&mut Object),
+    ) -> usize {
+        let mut count = 0;
+        for (_, object) in self.iter_tagged_mut(tag) {
+            function(object);
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Shows or hides every object that has the tag
+    pub fn set_tagged_visibility(&mut self, tag: impl StringBuffer, is_visible: bool) -> usize {
+        self.update_tagged(tag, |object| object.set_visibility(is_visible))
+    }
+
+    /// Moves every object that has the tag by the offset
+    pub fn translate_tagged(
+        &mut self,
+        tag: impl StringBuffer,
+        offset: impl Into<Vector3>,
+    ) -> usize {
+        let offset = offset.into();
+        self.update_tagged(tag, |object| {
+            object.set_position(object.position + offset);
+        })
+    }
+
+    /// Removes every object that has the tag from the storage, and returns them
+    pub fn remove_tagged(&mut self, tag: impl StringBuffer) -> Vec<Object> {
+        let names = self
+            .iter_tagged(tag)
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+
+        names.iter().filter_map(|name| self.remove(name)).collect()
+    }
+}
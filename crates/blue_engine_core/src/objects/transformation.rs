@@ -1,6 +1,6 @@
 use crate::{
-    Matrix4, PipelineData, Quaternion, Renderer, StringBuffer, TextureData, TextureMode, Textures,
-    Vector3, Vector4,
+    Matrix4, PipelineData, Quaternion, Renderer, SamplerSettings, StringBuffer, TextureData,
+    Textures, Vector3, Vector4,
 };
 
 use super::Object;
@@ -15,8 +15,6 @@ pub enum RotateAxis {
     #[doc(hidden)]
     Z,
 }
-unsafe impl Send for RotateAxis {}
-unsafe impl Sync for RotateAxis {}
 
 /// Defines how the rotation amount is
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -26,10 +24,96 @@ pub enum RotateAmount {
     #[doc(hidden)]
     Degrees(f32),
 }
-unsafe impl Send for RotateAmount {}
-unsafe impl Sync for RotateAmount {}
+
+/// The position, rotation, and scale of an object, which can be saved and restored later
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    /// Position of the object
+    pub position: Vector3,
+    /// Rotation of the object
+    pub rotation: Quaternion,
+    /// Scale of the object
+    pub scale: Vector3,
+}
+impl Transform {
+    /// Applies this transform on top of a local one, as a parent does to its child.
+    ///
+    /// Non uniform scales are only exact when the local transform is not rotated.
+    pub fn mul_transform(&self, local: Transform) -> Transform {
+        Transform {
+            position: self.position + self.rotation * (self.scale * local.position),
+            rotation: self.rotation * local.rotation,
+            scale: self.scale * local.scale,
+        }
+    }
+
+    /// Returns the transform that undoes this one.
+    ///
+    /// Only exact for uniform scales, as the inverse of a rotated non uniform scale is a shear
+    /// that a transform can not hold. Use [Transform::inverse_matrix] for those.
+    pub fn inverse(&self) -> Transform {
+        let rotation = self.rotation.inverse();
+        let scale = self.scale.recip();
+
+        Transform {
+            position: -(rotation * self.position) * scale,
+            rotation,
+            scale,
+        }
+    }
+
+    /// Returns the matrix of this transform, scaling, then rotating, then moving
+    pub fn matrix(&self) -> Matrix4 {
+        Matrix4::from_scale_rotation_translation(self.scale, self.rotation, self.position)
+    }
+
+    /// Returns the exact inverse of the matrix of this transform, for any scale
+    pub fn inverse_matrix(&self) -> Matrix4 {
+        self.matrix().inverse()
+    }
+}
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            position: Vector3::ZERO,
+            rotation: Quaternion::IDENTITY,
+            scale: Vector3::ONE,
+        }
+    }
+}
 
 impl Object {
+    /// Returns the current position, rotation, and scale of the object, as they are sent to the GPU
+    pub fn transform(&self) -> Transform {
+        Transform {
+            position: self.translation_matrix.w_axis.truncate(),
+            rotation: self.rotation_quaternion,
+            scale: Vector3::new(
+                self.scale_matrix.x_axis.x,
+                self.scale_matrix.y_axis.y,
+                self.scale_matrix.z_axis.z,
+            ),
+        }
+    }
+
+    /// Replaces the position, rotation, and scale of the object, such as one returned by [Object::transform]
+    pub fn set_transform(&mut self, transform: Transform) -> &mut Self {
+        self.position = transform.position;
+        self.translation_matrix = Matrix4::from_translation(transform.position);
+
+        self.rotation_quaternion = transform.rotation;
+        let (x, y, z) = transform.rotation.to_euler(glam::EulerRot::XYZ);
+        self.rotation = Vector3::new(x, y, z);
+
+        self.size = transform.scale;
+        self.scale_matrix = Matrix4::from_scale(transform.scale);
+
+        self.inverse_matrices();
+        self.changed = true;
+        self
+    }
+
     /// Sets the name of the object
     pub fn set_name(&mut self, name: impl StringBuffer) -> &mut Self {
         self.name = name.as_arc();
@@ -117,8 +201,8 @@ impl Object {
 
     /// Moves the object by the amount you specify in the axis you specify
     pub fn translate(&mut self, new_pos: impl Into<Vector3>) -> &mut Self {
-        self.position -= new_pos.into();
-        self.translation_matrix *= Matrix4::from_translation(self.position);
+        self.position += new_pos.into();
+        self.translation_matrix = Matrix4::from_translation(self.position);
 
         self.inverse_matrices();
         self.changed = true;
@@ -130,9 +214,11 @@ impl Object {
     pub fn set_position(&mut self, new_pos: impl Into<Vector3>) -> &mut Self {
         let new_pos = new_pos.into();
         self.position = new_pos;
-        self.translation_matrix = Matrix4::IDENTITY;
+        self.translation_matrix = Matrix4::from_translation(new_pos);
 
-        self.translate(new_pos)
+        self.inverse_matrices();
+        self.changed = true;
+        self
     }
 
     /// Changes the color of the object. If textures exist, the color of textures will change
@@ -142,6 +228,28 @@ impl Object {
         self
     }
 
+    /// Sets the seed and time offset given to the shader of the object, so objects sharing a
+    /// material do not animate in lockstep. Objects with different variations are not merged
+    /// by the static batching, see [crate::utils::shader_variation::ShaderVariation]
+    pub fn set_variation(
+        &mut self,
+        variation: crate::utils::shader_variation::ShaderVariation,
+    ) -> &mut Self {
+        if self.variation != variation {
+            self.variation = variation;
+            self.changed = true;
+        }
+
+        self
+    }
+
+    /// Gives the object a random seed, and a random time offset of up to the given seconds
+    pub fn randomize_variation(&mut self, max_time_offset: f32) -> &mut Self {
+        self.set_variation(crate::utils::shader_variation::ShaderVariation::random(
+            max_time_offset,
+        ))
+    }
+
     /// Changes the render order of the Object.
     ///
     /// Objects with higher number get rendered later and appear "on top" when occupying the same space
@@ -151,6 +259,73 @@ impl Object {
         self
     }
 
+    /// Clips the object to the rectangle of the frame, in pixels from the top left as
+    /// (x, y, width, height), such as for the contents of a scrolled UI panel. Clipped objects
+    /// are not merged by the static batching
+    pub fn set_scissor(&mut self, scissor: Option<(u32, u32, u32, u32)>) -> &mut Self {
+        if self.scissor != scissor {
+            self.scissor = scissor;
+            self.changed = true;
+        }
+
+        self
+    }
+
+    /// Marks the object as static, so it is merged into one draw call with the other static
+    /// objects that share its shader, texture, and color. Best for objects that rarely move,
+    /// as the whole batch is rebuilt when any of them change, see
+    /// [crate::utils::static_batching::StaticBatches]
+    pub fn set_static(&mut self, is_static: bool) -> &mut Self {
+        self.is_static = is_static;
+
+        self
+    }
+
+    /// Puts the mesh of the object in the shared buffers of the
+    /// [crate::utils::mesh_pool::MeshPool], so the pooled objects are drawn without binding
+    /// buffers in between. Objects with a shared mesh, meshlets, or quantized vertices keep
+    /// buffers of their own. Pooled objects are not merged by the static batching, and do not
+    /// cast ray traced shadows
+    pub fn set_pooled(&mut self, is_pooled: bool) -> &mut Self {
+        if self.is_pooled != is_pooled {
+            self.is_pooled = is_pooled;
+            self.changed = true;
+        }
+
+        self
+    }
+
+    /// Keeps a second instance buffer for the object, and writes the instances of the next
+    /// frame into the buffer the GPU is not reading before swapping them. Avoids the stall of
+    /// writing to a buffer still in use, for objects with many instances that change every
+    /// frame, at the cost of twice the instance memory
+    pub fn set_double_buffered_instances(&mut self, double_buffered: bool) -> &mut Self {
+        self.double_buffered_instances = double_buffered;
+        if !double_buffered {
+            self.back_instance_buffer = None;
+        }
+
+        self
+    }
+
+    /// Replaces the vertices of the object with the ones of the meshlets, which are culled
+    /// before each frame so only the ones in view are drawn, see
+    /// [crate::utils::meshlets::MeshletCulling]. Objects with sub meshes are not culled
+    pub fn set_meshlets(
+        &mut self,
+        meshlets: Option<std::sync::Arc<crate::utils::meshlets::Meshlets>>,
+    ) -> &mut Self {
+        if let Some(meshlets) = meshlets.as_ref() {
+            self.vertices.clone_from(&meshlets.vertices);
+            self.indices.clone_from(&meshlets.indices);
+            self.mesh = None;
+        }
+        self.meshlets = meshlets;
+        self.changed = true;
+
+        self
+    }
+
     /// Replaces the object's texture with provided one
     ///
     /// This function previously served the role of [crate::Object::set_texture_raw]
@@ -158,21 +333,67 @@ impl Object {
         &mut self,
         name: impl StringBuffer,
         texture_data: TextureData,
-        texture_mode: TextureMode,
+        texture_mode: impl Into<SamplerSettings>,
         renderer: &mut Renderer,
     ) -> Result<&mut Self, crate::error::Error> {
-        let texture = renderer.build_texture(name, texture_data, texture_mode)?;
-        Ok(self.set_texture_raw(texture))
+        let texture_mode = texture_mode.into();
+        let texture = renderer.build_texture(name.as_str(), texture_data.clone(), texture_mode)?;
+        self.set_texture_raw(texture);
+        self.texture_source = Some((name.as_arc(), texture_data, texture_mode));
+
+        Ok(self)
     }
 
     /// Replaces the object's texture with provided one
     pub fn set_texture_raw(&mut self, texture: Textures) -> &mut Self {
         self.pipeline.texture = PipelineData::Data(texture);
+        self.texture_source = None;
         self.changed = true;
 
         self
     }
 
+    /// Writes the value to the uniform buffer at the index, or adds a buffer for it after the
+    /// others if the index is the amount of them. The first two are the transformation and the
+    /// color of the object, so they can not be set. Unlike the buffers put in
+    /// [Object::uniform_buffers] by hand, the bytes are kept to upload them again if the device
+    /// is lost
+    pub fn set_uniform_buffer<T: bytemuck::Zeroable + bytemuck::Pod>(
+        &mut self,
+        index: usize,
+        name: impl StringBuffer,
+        value: T,
+        renderer: &mut Renderer,
+    ) -> Result<&mut Self, crate::error::Error> {
+        if index < 2 || index > self.uniform_buffers.len() {
+            return Err(crate::error::Error::Custom(format!(
+                "Uniform buffer {index} can not be set on object {}",
+                self.name
+            )));
+        }
+
+        if index == self.uniform_buffers.len() {
+            self.uniform_buffers
+                .push(renderer.build_uniform_buffer_part(name.as_str(), value));
+            self.changed = true;
+        } else if renderer.write_uniform_buffer_part(
+            &mut self.uniform_buffers[index],
+            name.as_str(),
+            value,
+        ) {
+            self.changed = true;
+        }
+
+        // the bytes of the buffers that were replaced are not needed anymore
+        let buffer = &self.uniform_buffers[index];
+        self.uniform_sources
+            .retain(|(i, _)| i != buffer && self.uniform_buffers.contains(i));
+        self.uniform_sources
+            .push((buffer.clone(), bytemuck::bytes_of(&value).to_vec()));
+
+        Ok(self)
+    }
+
     /// This will flag object as changed and altered, leading to rebuilding parts, or entirety on next frame.
     /// Best used if you directly altered fields of the object. The functions normally flag the object as
     /// changed on every call anyways. But this function is to manually flag it yourself.
@@ -187,10 +408,140 @@ impl Object {
 
     /// build an inverse of the transformation matrix to be sent to the gpu for lighting and other things.
     pub fn inverse_matrices(&mut self) {
-        self.inverse_transformation_matrix = Matrix4::transpose(&Matrix4::inverse(
-            &(self.translation_matrix
-                * Matrix4::from_quat(self.rotation_quaternion)
-                * self.scale_matrix),
-        ));
+        self.inverse_transformation_matrix =
+            Matrix4::transpose(&Matrix4::inverse(&self.transformation_matrix()));
+    }
+
+    /// Returns the transformation matrix of the object, composed from its translation,
+    /// rotation, and scale
+    pub fn transformation_matrix(&self) -> Matrix4 {
+        self.translation_matrix * Matrix4::from_quat(self.rotation_quaternion) * self.scale_matrix
+    }
+
+    /// Returns the transformation matrix the object was drawn with in the previous frame, or
+    /// the current one if it was not drawn before
+    pub fn previous_transformation_matrix(&self) -> Matrix4 {
+        self.previous_transform
+            .unwrap_or_else(|| self.transformation_matrix())
+    }
+
+    /// Returns true if the object moved, rotated, or was scaled since the previous frame
+    pub fn has_moved(&self) -> bool {
+        self.previous_transform
+            .is_some_and(|i| i != self.transformation_matrix())
+    }
+
+    /// Returns the transformation between the previous frame at 0 and the current one at 1,
+    /// such as for rendering between the steps of [crate::Engine::update_loop_fixed]
+    pub fn interpolated_transformation_matrix(&self, alpha: f32) -> Matrix4 {
+        let (previous_scale, previous_rotation, previous_position) = self
+            .previous_transformation_matrix()
+            .to_scale_rotation_translation();
+        let (scale, rotation, position) =
+            self.transformation_matrix().to_scale_rotation_translation();
+
+        Matrix4::from_scale_rotation_translation(
+            previous_scale.lerp(scale, alpha),
+            previous_rotation.slerp(rotation, alpha),
+            previous_position.lerp(position, alpha),
+        )
+    }
+
+    /// Forgets the transformation of the previous frame, so the next one has no motion, such
+    /// as after teleporting the object
+    pub fn reset_previous_transform(&mut self) -> &mut Self {
+        self.previous_transform = None;
+
+        self
+    }
+}
+
+impl crate::ObjectStorage {
+    /// Keeps the transformation of each object as the one of the previous frame, after the
+    /// frame is drawn with it
+    pub(crate) fn keep_previous_transforms(&mut self) {
+        for object in self.values_mut() {
+            object.previous_transform = Some(object.transformation_matrix());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ObjectSettings, primitive_shapes::triangle_data};
+
+    fn object(renderer: &mut Renderer) -> Object {
+        let (vertices, indices) = triangle_data();
+        Object::new(
+            "triangle",
+            vertices,
+            indices,
+            ObjectSettings::default(),
+            renderer,
+        )
+        .expect("the triangle is built")
+    }
+
+    #[test]
+    fn set_position_places_the_object() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut object = object(&mut renderer);
+
+        object.set_position((1.0, 2.0, 3.0));
+        assert_eq!(object.position, Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(object.transform().position, Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(
+            object.translation_matrix,
+            Matrix4::from_translation(Vector3::new(1.0, 2.0, 3.0))
+        );
+
+        // setting it again replaces the position instead of adding to it
+        object.set_position((-1.0, 0.0, 0.5));
+        assert_eq!(object.transform().position, Vector3::new(-1.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn translate_adds_to_the_position() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut object = object(&mut renderer);
+
+        object.set_position((1.0, 2.0, 3.0));
+        object.translate((0.5, -1.0, 0.0));
+        object.translate((0.5, -1.0, 0.0));
+        assert_eq!(object.position, Vector3::new(2.0, 0.0, 3.0));
+        assert_eq!(object.transform().position, Vector3::new(2.0, 0.0, 3.0));
+        assert_eq!(
+            object.inverse_transformation_matrix,
+            object.transformation_matrix().inverse().transpose()
+        );
+    }
+
+    #[test]
+    fn transform_follows_set_position_and_translate() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut object = object(&mut renderer);
+
+        object.set_position((4.0, 0.0, -2.0));
+        let saved = object.transform();
+        assert_eq!(saved.position, Vector3::new(4.0, 0.0, -2.0));
+
+        object.translate((1.0, 1.0, 1.0));
+        assert_eq!(object.transform().position, Vector3::new(5.0, 1.0, -1.0));
+
+        // restoring the snapshot puts the object back where it was saved
+        object.set_transform(saved);
+        assert_eq!(object.position, saved.position);
+        assert_eq!(object.transform(), saved);
+
+        // and moving from a restored transform starts at its position
+        object.translate((0.0, 2.0, 0.0));
+        assert_eq!(object.transform().position, Vector3::new(4.0, 2.0, -2.0));
     }
 }
@@ -1,14 +1,19 @@
-use super::{Instance, Object};
-use crate::{Matrix4, PipelineData, Renderer};
+use super::{Instance, InstanceRaw, Object, ShaderBuilder};
+use crate::{
+    Matrix4, PipelineData, Renderer, Vector4,
+    utils::default_resources::{
+        DEFAULT_SHADER, MORPH_TARGET_SHADER, QUANTIZED_SHADER, SKINNED_INSTANCING_SHADER,
+        VERTEX_PULLING_SHADER,
+    },
+    utils::shader_variation::FragmentUniform,
+    utils::vertex_quantization::QuantizationUniform,
+};
 
 impl Object {
     /// Update and apply changes done to an object
     pub fn update(&mut self, renderer: &mut Renderer) {
-        self.update_vertex_buffer(renderer);
-        self.update_uniform_buffer(renderer);
-        self.update_shader(renderer);
-        self.update_instance_buffer(renderer);
-        self.changed = false;
+        let prepared = self.prepare_update();
+        self.update_prepared(renderer, prepared);
     }
 
     /// Update and apply changes done to an object and returns a pipeline
@@ -24,7 +29,99 @@ impl Object {
     }
 
     fn update_vertex_buffer_inner(&mut self, renderer: &mut Renderer) -> crate::VertexBuffers {
-        renderer.build_vertex_buffer(&self.vertices, &self.indices)
+        // the vertices written by a compute shader stay on the GPU
+        if let Some(gpu_vertices) = self.gpu_vertices.as_ref() {
+            self.mesh_allocation = None;
+            self.own_vertex_buffers = None;
+            if self.shader_settings.vertex_pulling
+                && let Some(buffer) = self.uniform_buffers.get_mut(2)
+            {
+                *buffer = gpu_vertices.vertex_buffer.clone();
+            }
+            return crate::VertexBuffers {
+                vertex_buffer: gpu_vertices.vertex_buffer.clone(),
+                index_buffer: gpu_vertices.index_buffer.clone(),
+                length: gpu_vertices.length,
+            };
+        }
+
+        if self.shader_settings.quantized_vertices {
+            self.mesh_allocation = None;
+            let (vertices, indices) = match self.mesh.as_ref() {
+                Some(mesh) => (mesh.vertices.as_slice(), mesh.indices.as_slice()),
+                None => (self.vertices.as_slice(), self.indices.as_slice()),
+            };
+            let (vertex_buffers, uniform) = renderer.write_quantized_vertex_buffer(
+                self.own_vertex_buffers.take(),
+                vertices,
+                indices,
+            );
+            if let Some(buffer) = self.uniform_buffers.get_mut(2) {
+                renderer.write_uniform_buffer_part(buffer, "Quantization", uniform);
+            }
+            self.own_vertex_buffers = Some(crate::VertexBuffers {
+                vertex_buffer: vertex_buffers.vertex_buffer.clone(),
+                index_buffer: vertex_buffers.index_buffer.clone(),
+                length: vertex_buffers.length,
+            });
+            return vertex_buffers;
+        }
+
+        // the shader reads the vertices from the buffer, which is bound with the uniforms
+        if self.shader_settings.vertex_pulling {
+            self.mesh_allocation = None;
+            let (vertices, indices) = match self.mesh.as_ref() {
+                Some(mesh) => (mesh.vertices.as_slice(), mesh.indices.as_slice()),
+                None => (self.vertices.as_slice(), self.indices.as_slice()),
+            };
+            let vertex_buffers = renderer.write_pulled_vertex_buffer(
+                self.own_vertex_buffers.take(),
+                vertices,
+                indices,
+            );
+            if let Some(buffer) = self.uniform_buffers.get_mut(2) {
+                *buffer = vertex_buffers.vertex_buffer.clone();
+            }
+            self.own_vertex_buffers = Some(crate::VertexBuffers {
+                vertex_buffer: vertex_buffers.vertex_buffer.clone(),
+                index_buffer: vertex_buffers.index_buffer.clone(),
+                length: vertex_buffers.length,
+            });
+            return vertex_buffers;
+        }
+
+        self.own_vertex_buffers = None;
+
+        // the skin weights and morph targets are found by the index of the vertex, which would
+        // be offset by the place of the vertices in the pool
+        if self.is_pooled
+            && self.mesh.is_none()
+            && self.meshlets.is_none()
+            && !self.shader_settings.skinned_instancing
+            && !self.shader_settings.morph_targets
+        {
+            let allocation =
+                renderer.allocate_mesh(&self.vertices, &self.indices, self.mesh_allocation.take());
+            let vertex_buffers =
+                renderer
+                    .mesh_pool
+                    .buffers()
+                    .map(|(vertices, indices)| crate::VertexBuffers {
+                        vertex_buffer: vertices.clone(),
+                        index_buffer: indices.clone(),
+                        length: self.indices.len() as u32,
+                    });
+            self.mesh_allocation = Some(allocation);
+            if let Some(vertex_buffers) = vertex_buffers {
+                return vertex_buffers;
+            }
+        }
+        self.mesh_allocation = None;
+
+        match self.mesh.as_ref() {
+            Some(mesh) => mesh.shared_vertex_buffers(),
+            None => renderer.build_vertex_buffer(&self.vertices, &self.indices),
+        }
     }
     /// Update and apply changes done to the vertex buffer
     pub fn update_vertex_buffer(&mut self, renderer: &mut Renderer) {
@@ -47,13 +144,179 @@ impl Object {
     }
 
     fn update_shader_inner(&mut self, renderer: &mut Renderer) -> crate::Shaders {
+        // a broken shader would fail deep in the GPU driver, so it is replaced beforehand
+        let shader_source = match self.shader_builder.validate() {
+            Ok(()) => self.shader_builder.shader.clone(),
+            Err(e) => {
+                eprintln!(
+                    "The shader of {} is replaced by the default shader: {e}",
+                    self.name
+                );
+                let settings = self.shader_settings;
+                let default_shader = match (
+                    settings.quantized_vertices,
+                    settings.vertex_pulling,
+                    settings.skinned_instancing,
+                    settings.morph_targets,
+                ) {
+                    (true, _, _, _) => QUANTIZED_SHADER,
+                    (false, true, _, _) => VERTEX_PULLING_SHADER,
+                    (false, false, true, _) => SKINNED_INSTANCING_SHADER,
+                    (false, false, false, true) => MORPH_TARGET_SHADER,
+                    (false, false, false, false) => DEFAULT_SHADER,
+                };
+                ShaderBuilder::new(default_shader.to_string(), self.camera_effect.clone()).shader
+            }
+        };
+
         renderer.build_shader(
             self.name.as_ref(),
-            self.shader_builder.shader.clone(),
+            shader_source,
             Some(&self.uniform_layout),
             self.shader_settings,
         )
     }
+    /// Replaces the shader of the object. The shader is compiled on the CPU first, and the
+    /// old one is kept if it fails, see [ShaderBuilder::validate]
+    pub fn set_shader(&mut self, shader_source: String) -> Result<&mut Self, crate::error::Error> {
+        let shader_builder = ShaderBuilder::new(shader_source, self.camera_effect.clone());
+        shader_builder.validate()?;
+
+        self.shader_builder = shader_builder;
+        self.changed = true;
+
+        Ok(self)
+    }
+    /// Draws the object with [crate::utils::vertex_quantization::QuantizedVertex], in half the
+    /// memory and bandwidth of the full vertices. The object gets its own quantized copy of
+    /// the vertices, also of a shared mesh, and a [QuantizationUniform] at binding 2 of its
+    /// uniforms, so its own uniforms start from binding 3.
+    ///
+    /// The default shader is swapped for [QUANTIZED_SHADER], custom shaders must read the
+    /// vertices the same way. The motion vectors do not include the quantized objects
+    pub fn set_quantized(&mut self, quantized: bool, renderer: &mut Renderer) -> &mut Self {
+        if self.shader_settings.quantized_vertices == quantized {
+            return self;
+        }
+        if quantized && self.shader_settings.skinned_instancing {
+            eprintln!(
+                "{} is animated by a skinned crowd, so it is not quantized",
+                self.name
+            );
+            return self;
+        }
+        if quantized && self.shader_settings.morph_targets {
+            eprintln!("{} has morph targets, so it is not quantized", self.name);
+            return self;
+        }
+        // both are bound at binding 2
+        if quantized {
+            self.set_vertex_pulling(false, renderer);
+        }
+        self.shader_settings.quantized_vertices = quantized;
+
+        if quantized {
+            let buffer =
+                renderer.build_uniform_buffer_part("Quantization", QuantizationUniform::default());
+            self.uniform_buffers
+                .insert(2.min(self.uniform_buffers.len()), buffer);
+        } else if self.uniform_buffers.len() > 2 {
+            self.uniform_buffers.remove(2);
+        }
+
+        let (from, to) = match quantized {
+            true => (DEFAULT_SHADER, QUANTIZED_SHADER),
+            false => (QUANTIZED_SHADER, DEFAULT_SHADER),
+        };
+        if self.shader_builder.source == from {
+            self.shader_builder = ShaderBuilder::new(to.to_string(), self.camera_effect.clone());
+        }
+        self.changed = true;
+
+        self
+    }
+
+    /// Draws the object with a shader that reads the vertices from a storage buffer by the
+    /// index of the vertex, instead of from vertex attributes. The object gets its own copy of
+    /// the vertices, also of a shared mesh, bound at binding 2 of its uniforms, so its own
+    /// uniforms start from binding 3. The buffer can be written by compute shaders to make
+    /// or animate the mesh on the GPU, see [crate::utils::vertex_pulling].
+    ///
+    /// The default shader is swapped for [VERTEX_PULLING_SHADER], custom shaders can read the
+    /// vertices in any layout they like. Quantized objects are no longer quantized. Nothing
+    /// changes if the device can not read storage buffers in the vertex stage, see
+    /// [Renderer::supports_vertex_pulling]
+    pub fn set_vertex_pulling(
+        &mut self,
+        vertex_pulling: bool,
+        renderer: &mut Renderer,
+    ) -> &mut Self {
+        if self.shader_settings.vertex_pulling == vertex_pulling {
+            return self;
+        }
+        if vertex_pulling && self.shader_settings.skinned_instancing {
+            eprintln!(
+                "{} is animated by a skinned crowd, so it does not pull its vertices",
+                self.name
+            );
+            return self;
+        }
+        if vertex_pulling && self.shader_settings.morph_targets {
+            eprintln!(
+                "{} has morph targets, so it does not pull its vertices",
+                self.name
+            );
+            return self;
+        }
+        if vertex_pulling && !renderer.supports_vertex_pulling() {
+            eprintln!(
+                "The device can not read storage buffers in the vertex stage, so {} does not pull its vertices",
+                self.name
+            );
+            return self;
+        }
+        if vertex_pulling {
+            self.set_quantized(false, renderer);
+        }
+        self.shader_settings.vertex_pulling = vertex_pulling;
+
+        // the vertices take the place of the buffer until they are uploaded with the update
+        if vertex_pulling {
+            let (vertices, indices) = match self.mesh.as_ref() {
+                Some(mesh) => (mesh.vertices.clone(), mesh.indices.clone()),
+                None => (self.vertices.clone(), self.indices.clone()),
+            };
+            let buffer = renderer
+                .build_pulled_vertex_buffer(&vertices, &indices)
+                .vertex_buffer;
+            self.uniform_buffers
+                .insert(2.min(self.uniform_buffers.len()), buffer);
+        } else if self.uniform_buffers.len() > 2 {
+            self.uniform_buffers.remove(2);
+        }
+
+        let (from, to) = match vertex_pulling {
+            true => (DEFAULT_SHADER, VERTEX_PULLING_SHADER),
+            false => (VERTEX_PULLING_SHADER, DEFAULT_SHADER),
+        };
+        if self.shader_builder.source == from {
+            self.shader_builder = ShaderBuilder::new(to.to_string(), self.camera_effect.clone());
+        }
+        self.changed = true;
+
+        self
+    }
+
+    /// Sets if the fog of the renderer is applied to the object, such as to keep the sky or
+    /// the interface clear of it. Only the shaders with the `//@FOG` placeholder have fog,
+    /// see [crate::utils::fog::Fog]
+    pub fn set_fog(&mut self, fog: bool) -> &mut Self {
+        self.shader_settings.fog = fog;
+        self.changed = true;
+
+        self
+    }
+
     /// Update and apply changes done to the shader
     pub fn update_shader(&mut self, renderer: &mut Renderer) {
         let updated_shader = self.update_shader_inner(renderer);
@@ -67,25 +330,47 @@ impl Object {
         updated_shader
     }
 
-    fn update_uniform_buffer_inner(
+    pub(super) fn update_uniform_buffer_inner(
         &mut self,
         renderer: &mut Renderer,
+        transform_matrix: Matrix4,
+        color: Vector4,
     ) -> (crate::UniformBuffers, wgpu::BindGroupLayout) {
-        self.uniform_buffers[0] = renderer.build_uniform_buffer_part(
+        // objects with only the default uniforms are kept in the shared uniform pool
+        if self.uniform_buffers.len() == 2 {
+            let slot = match self.uniform_slot.take() {
+                Some(slot) => slot,
+                None => renderer.allocate_uniform_slot(),
+            };
+            renderer.write_uniform_slot(
+                &slot,
+                transform_matrix,
+                FragmentUniform::new(color, self.variation),
+            );
+            self.uniform_slot = Some(slot);
+
+            return renderer.uniform_pool_bind_group();
+        }
+        self.uniform_slot = None;
+
+        renderer.write_uniform_buffer_part(
+            &mut self.uniform_buffers[0],
             "Transformation Matrix",
-            self.translation_matrix
-                * Matrix4::from_quat(self.rotation_quaternion)
-                * self.scale_matrix,
+            transform_matrix,
+        );
+        renderer.write_uniform_buffer_part(
+            &mut self.uniform_buffers[1],
+            "Color",
+            FragmentUniform::new(color, self.variation),
         );
-        self.uniform_buffers[1] = renderer.build_uniform_buffer_part("Color", self.color);
-
-        let updated_buffer = renderer.build_uniform_buffer(&self.uniform_buffers);
 
-        updated_buffer
+        renderer.build_uniform_buffer(&self.uniform_buffers)
     }
     /// Update and apply changes done to the uniform buffer
     pub fn update_uniform_buffer(&mut self, renderer: &mut Renderer) {
-        let updated_buffer = self.update_uniform_buffer_inner(renderer);
+        let prepared = self.prepare_update();
+        let updated_buffer =
+            self.update_uniform_buffer_inner(renderer, prepared.transform_matrix, prepared.color);
 
         self.pipeline.uniform = PipelineData::Data(Some(updated_buffer.0));
         self.uniform_layout = updated_buffer.1;
@@ -95,7 +380,9 @@ impl Object {
         &mut self,
         renderer: &mut Renderer,
     ) -> crate::UniformBuffers {
-        let updated_buffer = self.update_uniform_buffer_inner(renderer);
+        let prepared = self.prepare_update();
+        let updated_buffer =
+            self.update_uniform_buffer_inner(renderer, prepared.transform_matrix, prepared.color);
         let updated_buffer2 = updated_buffer.0.clone();
 
         self.pipeline.uniform = PipelineData::Data(Some(updated_buffer.0));
@@ -110,7 +397,27 @@ impl Object {
             .iter()
             .map(Instance::build)
             .collect::<Vec<_>>();
-        renderer.build_instance(instance_data)
+        self.write_instances(renderer, &instance_data);
+
+        self.instance_buffer.clone()
+    }
+
+    /// Writes the instances to the instance buffer, or to the back buffer which then becomes
+    /// the one drawn with, when they are double buffered
+    pub(crate) fn write_instances(&mut self, renderer: &Renderer, instances: &[InstanceRaw]) {
+        if !self.double_buffered_instances {
+            renderer.write_instance_buffer(&mut self.instance_buffer, instances);
+            return;
+        }
+
+        let back = match self.back_instance_buffer.take() {
+            Some(mut back) => {
+                renderer.write_instance_buffer(&mut back, instances);
+                back
+            }
+            None => renderer.build_instance(instances.to_vec()),
+        };
+        self.back_instance_buffer = Some(std::mem::replace(&mut self.instance_buffer, back));
     }
     /// Updates the instance buffer
     pub fn update_instance_buffer(&mut self, renderer: &mut Renderer) {
@@ -125,3 +432,138 @@ impl Object {
         instance_buffer
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{ObjectSettings, primitive_shapes::triangle_data};
+
+    fn triangle(renderer: &mut crate::Renderer) -> crate::Object {
+        let (vertices, indices) = triangle_data();
+        crate::Object::new(
+            "triangle",
+            vertices,
+            indices,
+            ObjectSettings::default(),
+            renderer,
+        )
+        .expect("the triangle is built")
+    }
+
+    #[test]
+    fn quantized_vertices_are_written_in_place() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut object = triangle(&mut renderer);
+        object.set_quantized(true, &mut renderer);
+        object.update(&mut renderer);
+        let buffers = object
+            .own_vertex_buffers
+            .as_ref()
+            .expect("the object has its own");
+        let (vertex_buffer, index_buffer) =
+            (buffers.vertex_buffer.clone(), buffers.index_buffer.clone());
+
+        object.vertices[0].position[0] += 0.5;
+        object.update(&mut renderer);
+        let buffers = object
+            .own_vertex_buffers
+            .as_ref()
+            .expect("the object has its own");
+        assert_eq!(buffers.vertex_buffer, vertex_buffer);
+        assert_eq!(buffers.index_buffer, index_buffer);
+
+        // more vertices than fit are written into larger buffers
+        let (vertices, indices) = (object.vertices.clone(), object.indices.clone());
+        for _ in 0..8 {
+            let offset = object.vertices.len() as crate::UnsignedIntType;
+            object.vertices.extend_from_slice(&vertices);
+            object.indices.extend(indices.iter().map(|i| i + offset));
+        }
+        object.update(&mut renderer);
+        let buffers = object
+            .own_vertex_buffers
+            .as_ref()
+            .expect("the object has its own");
+        assert_ne!(buffers.vertex_buffer, vertex_buffer);
+        assert_ne!(buffers.index_buffer, index_buffer);
+        assert_eq!(buffers.length, object.indices.len() as u32);
+
+        // and the buffers are no longer kept once the object is not quantized
+        object.set_quantized(false, &mut renderer);
+        object.update(&mut renderer);
+        assert!(object.own_vertex_buffers.is_none());
+    }
+
+    #[test]
+    fn pulled_vertices_are_written_in_place() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        if !renderer.supports_vertex_pulling() {
+            return;
+        }
+        let mut object = triangle(&mut renderer);
+        object.set_vertex_pulling(true, &mut renderer);
+        object.update(&mut renderer);
+        let vertex_buffer = object
+            .own_vertex_buffers
+            .as_ref()
+            .unwrap()
+            .vertex_buffer
+            .clone();
+
+        object.vertices[0].position[1] -= 0.5;
+        object.update(&mut renderer);
+        let buffers = object.own_vertex_buffers.as_ref().unwrap();
+        assert_eq!(buffers.vertex_buffer, vertex_buffer);
+        assert_eq!(object.uniform_buffers[2], vertex_buffer);
+    }
+
+    #[test]
+    fn written_buffers_are_padded_to_four_bytes() {
+        let Some(renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let buffers = renderer.write_vertex_buffers(
+            None,
+            &[1; 12],
+            &[2; 6],
+            "Vertex Buffer",
+            wgpu::BufferUsages::VERTEX,
+            3,
+        );
+        assert_eq!(buffers.vertex_buffer.size(), 12);
+        assert_eq!(buffers.index_buffer.size(), 8);
+
+        // data that still fits is written into the same buffers
+        let index_buffer = buffers.index_buffer.clone();
+        let buffers = renderer.write_vertex_buffers(
+            Some(buffers),
+            &[1; 10],
+            &[2; 2],
+            "Vertex Buffer",
+            wgpu::BufferUsages::VERTEX,
+            1,
+        );
+        assert_eq!(buffers.index_buffer, index_buffer);
+        assert_eq!(buffers.vertex_buffer.size(), 12);
+
+        // a buffer that can not be used as asked is replaced
+        let buffers = renderer.write_vertex_buffers(
+            Some(buffers),
+            &[1; 12],
+            &[2; 2],
+            "Vertex Buffer",
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+            1,
+        );
+        assert!(
+            buffers
+                .vertex_buffer
+                .usage()
+                .contains(wgpu::BufferUsages::STORAGE)
+        );
+        assert_eq!(buffers.index_buffer, index_buffer);
+    }
+}
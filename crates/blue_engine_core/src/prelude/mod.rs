@@ -5,16 +5,23 @@ pub use imports::*;
 /// contains definition for some 2D and 3D shapes. They are basic shapes and
 /// can be used as examples of how to create your own content.
 pub mod primitive_shapes;
-pub use crate::camera::{Camera, CameraContainer, Projection};
+pub use crate::camera::{
+    Camera, CameraContainer, CameraFollow, ContentScale, Projection, ScalePolicy, Viewport,
+};
 pub use crate::definition::{
-    Pipeline, PipelineData, ShaderSettings, TextureData, TextureMode, VertexBuffers,
-    pixel_to_cartesian,
+    Pipeline, PipelineData, SamplerSettings, ShaderSettings, TextureData, TextureMode,
+    VertexBuffers, pixel_to_cartesian,
 };
 pub use crate::objects::{
-    Instance, InstanceRaw, Object, ObjectSettings, ObjectStorage, RotateAmount, RotateAxis,
+    Instance, InstanceRaw, Material, Mesh, MeshData, Object, ObjectBuilder, ObjectGroup, ObjectId,
+    ObjectSettings, ObjectStorage, RotateAmount, RotateAxis, SubMesh, Transform,
+};
+pub use crate::render::{FrameTexture, Renderer};
+pub use crate::window::{
+    BackgroundMode, EngineSettings, FramePacing, MonitorInfo, Orientation, RedrawMode, Window,
+    WindowDescriptor,
 };
-pub use crate::render::Renderer;
-pub use crate::window::{Window, WindowDescriptor};
+pub use crate::windows::{SecondaryWindow, WindowStorage};
 
 /// The uint type used for indices and more
 #[cfg(not(feature = "u32"))]
@@ -100,8 +107,6 @@ impl Vertex {
         }
     }
 }
-unsafe impl Send for Vertex {}
-unsafe impl Sync for Vertex {}
 
 /// The engine is the main starting point of using the Blue Engine.
 /// Everything that runs on Blue Engine will be under this struct.
@@ -161,6 +166,8 @@ pub struct Engine {
     pub camera: CameraContainer,
     /// Handles all engine plugins
     pub signals: SignalStorage,
+    /// The additional windows of the engine, which render the same objects
+    pub windows: crate::windows::WindowStorage,
 
     /// holds the update_loop function
     ///
@@ -181,13 +188,30 @@ pub struct Engine {
         >,
     >,
 
+    /// holds the callbacks of update_loop_fixed
+    ///
+    /// #### USED INTERNALLY
+    pub fixed_update: Option<crate::utils::fixed_timestep::FixedUpdate>,
+
     /// input events
     ///
     /// #### USED INTERNALLY
     pub input_events: crate::utils::winit_input_helper::WinitInputHelper,
+    /// Reads the gamepads into the input events
+    ///
+    /// #### USED INTERNALLY
+    pub gamepad_backend: crate::utils::gamepad::GamepadBackend,
+    /// Runs delayed and repeating callbacks, see [Engine::after] and [Engine::every]
+    pub scheduler: crate::utils::scheduler::Scheduler,
+    /// Runs the async tasks, see [Engine::spawn]
+    pub tasks: crate::utils::tasks::TaskRunner,
+    /// The stack of game states, updated after the update loop
+    pub states: crate::utils::game_state::StateStack,
+    /// The settings of the player, applied after the update loop and saved to the disk
+    pub settings: crate::utils::settings::SettingsStore,
+    /// The plugins of the engine, see [Engine::add_plugin]
+    pub plugins: crate::utils::plugin::PluginStorage,
 }
-unsafe impl Send for Engine {}
-unsafe impl Sync for Engine {}
 
 /// Allows all events to be fetched directly, making it easier to add custom additions to the engine.
 pub trait Signal: Any {
@@ -228,6 +252,53 @@ pub trait Signal: Any {
     ) {
     }
 
+    /// This is ran when the window moves to another monitor
+    fn monitor_changed(
+        &mut self,
+        _renderer: &mut crate::Renderer,
+        _window: &crate::Window,
+        _objects: &mut ObjectStorage,
+        _monitor: &crate::MonitorInfo,
+        _camera: &mut crate::CameraContainer,
+    ) {
+    }
+
+    /// This is ran when the window gains or loses the keyboard focus
+    fn focus_changed(
+        &mut self,
+        _renderer: &mut crate::Renderer,
+        _window: &crate::Window,
+        _objects: &mut ObjectStorage,
+        _focused: bool,
+        _camera: &mut crate::CameraContainer,
+    ) {
+    }
+
+    /// This is ran when the app is sent to the background and loses its surface, such as on
+    /// Android and iOS, with `true`, and with `false` once it is back and the surface is made
+    /// again. Nothing is rendered while it is suspended
+    fn suspended_changed(
+        &mut self,
+        _renderer: &mut crate::Renderer,
+        _window: &crate::Window,
+        _objects: &mut ObjectStorage,
+        _suspended: bool,
+        _camera: &mut crate::CameraContainer,
+    ) {
+    }
+
+    /// This is ran after the GPU device was lost and made again, once the engine has made its
+    /// own resources again, see [crate::Renderer::recover_device]. The textures, buffers, and
+    /// pipelines made outside of the engine must be made again here
+    fn device_recovered(
+        &mut self,
+        _renderer: &mut crate::Renderer,
+        _window: &crate::Window,
+        _objects: &mut ObjectStorage,
+        _camera: &mut crate::CameraContainer,
+    ) {
+    }
+
     /// ran before the frame is rendered
     #[allow(clippy::too_many_arguments)]
     fn frame(
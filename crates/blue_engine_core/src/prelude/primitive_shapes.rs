@@ -10,6 +10,30 @@ use crate::{
 use std::f32::consts::PI;
 
 // MARK: 2D
+/// Returns the vertices and indices of a 2D triangle
+pub fn triangle_data() -> (Vec<Vertex>, Vec<UnsignedIntType>) {
+    (
+        vec![
+            Vertex {
+                position: [0.0, 1.0, 0.0],
+                uv: [0.5, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [-1.0, -1.0, 0.0],
+                uv: [0.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [1.0, -1.0, 0.0],
+                uv: [1.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+        ],
+        vec![0, 1, 2],
+    )
+}
+
 /// Creates a 2D triangle
 pub fn triangle(
     name: impl StringBuffer,
@@ -17,36 +41,44 @@ pub fn triangle(
     renderer: &mut Renderer,
     objects: &mut ObjectStorage,
 ) -> Result<(), crate::error::Error> {
+    let (vertices, indices) = triangle_data();
     objects.insert(
         name.as_string(),
-        Object::new(
-            name,
-            vec![
-                Vertex {
-                    position: [0.0, 1.0, 0.0],
-                    uv: [0.5, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [-1.0, -1.0, 0.0],
-                    uv: [0.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [1.0, -1.0, 0.0],
-                    uv: [1.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-            ],
-            vec![0, 1, 2],
-            settings,
-            renderer,
-        )?,
+        Object::new(name, vertices, indices, settings, renderer)?,
     );
 
     Ok(())
 }
 
+/// Returns the vertices and indices of a 2D square
+pub fn square_data() -> (Vec<Vertex>, Vec<UnsignedIntType>) {
+    (
+        vec![
+            Vertex {
+                position: [1.0, 1.0, 0.0],
+                uv: [1.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [1.0, -1.0, 0.0],
+                uv: [1.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [-1.0, -1.0, 0.0],
+                uv: [0.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [-1.0, 1.0, 0.0],
+                uv: [0.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+        ],
+        vec![2, 1, 0, 2, 0, 3],
+    )
+}
+
 /// Creates a 2D square
 pub fn square(
     name: impl StringBuffer,
@@ -54,41 +86,44 @@ pub fn square(
     renderer: &mut Renderer,
     objects: &mut ObjectStorage,
 ) -> Result<(), crate::error::Error> {
+    let (vertices, indices) = square_data();
     objects.insert(
         name.as_string(),
-        Object::new(
-            name,
-            vec![
-                Vertex {
-                    position: [1.0, 1.0, 0.0],
-                    uv: [1.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [1.0, -1.0, 0.0],
-                    uv: [1.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [-1.0, -1.0, 0.0],
-                    uv: [0.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [-1.0, 1.0, 0.0],
-                    uv: [0.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-            ],
-            vec![2, 1, 0, 2, 0, 3],
-            settings,
-            renderer,
-        )?,
+        Object::new(name, vertices, indices, settings, renderer)?,
     );
 
     Ok(())
 }
 
+/// Returns the vertices and indices of a 2D rectangle based on a width and height
+pub fn rectangle_data(width: f32, height: f32) -> (Vec<Vertex>, Vec<UnsignedIntType>) {
+    (
+        vec![
+            Vertex {
+                position: [width / 2.0, height / 2.0, 0.0],
+                uv: [1.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [width / 2.0, -height / 2.0, 0.0],
+                uv: [1.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [-width / 2.0, -height / 2.0, 0.0],
+                uv: [0.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [-width / 2.0, height / 2.0, 0.0],
+                uv: [0.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+        ],
+        vec![2, 1, 0, 2, 0, 3],
+    )
+}
+
 /// Create a 2D rectangle based on a width and height
 pub fn rectangle(
     width: f32,
@@ -98,36 +133,10 @@ pub fn rectangle(
     renderer: &mut Renderer,
     objects: &mut ObjectStorage,
 ) -> Result<(), crate::error::Error> {
+    let (vertices, indices) = rectangle_data(width, height);
     objects.insert(
         name.as_string(),
-        Object::new(
-            name,
-            vec![
-                Vertex {
-                    position: [width / 2.0, height / 2.0, 0.0],
-                    uv: [1.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [width / 2.0, -height / 2.0, 0.0],
-                    uv: [1.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [-width / 2.0, -height / 2.0, 0.0],
-                    uv: [0.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [-width / 2.0, height / 2.0, 0.0],
-                    uv: [0.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-            ],
-            vec![2, 1, 0, 2, 0, 3],
-            settings,
-            renderer,
-        )?,
+        Object::new(name, vertices, indices, settings, renderer)?,
     );
 
     Ok(())
@@ -135,6 +144,148 @@ pub fn rectangle(
 
 // MARK: 3D
 
+/// Returns the vertices and indices of a 3D cube
+pub fn cube_data() -> (Vec<Vertex>, Vec<UnsignedIntType>) {
+    (
+        vec![
+            // Front Face
+            Vertex {
+                position: [-1.0, -1.0, 1.0],
+                uv: [0.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [1.0, -1.0, 1.0],
+                uv: [1.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [1.0, 1.0, 1.0],
+                uv: [1.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [-1.0, 1.0, 1.0],
+                uv: [0.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            // Back Face
+            Vertex {
+                position: [-1.0, 1.0, -1.0],
+                uv: [1.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [1.0, 1.0, -1.0],
+                uv: [0.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [1.0, -1.0, -1.0],
+                uv: [0.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [-1.0, -1.0, -1.0],
+                uv: [1.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            // Right face
+            Vertex {
+                position: [1.0, -1.0, -1.0],
+                uv: [1.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [1.0, 1.0, -1.0],
+                uv: [1.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [1.0, 1.0, 1.0],
+                uv: [0.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [1.0, -1.0, 1.0],
+                uv: [0.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            // Left face
+            Vertex {
+                position: [-1.0, -1.0, 1.0],
+                uv: [1.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [-1.0, 1.0, 1.0],
+                uv: [1.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [-1.0, 1.0, -1.0],
+                uv: [0.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [-1.0, -1.0, -1.0],
+                uv: [0.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            // Top face
+            Vertex {
+                position: [1.0, 1.0, -1.0],
+                uv: [1.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [-1.0, 1.0, -1.0],
+                uv: [0.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [-1.0, 1.0, 1.0],
+                uv: [0.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [1.0, 1.0, 1.0],
+                uv: [1.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            // Bottom face
+            Vertex {
+                position: [1.0, -1.0, 1.0],
+                uv: [1.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [-1.0, -1.0, 1.0],
+                uv: [0.0, 0.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [-1.0, -1.0, -1.0],
+                uv: [0.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+            Vertex {
+                position: [1.0, -1.0, -1.0],
+                uv: [1.0, 1.0],
+                normal: [0.0, 0.0, 0.0],
+            },
+        ],
+        vec![
+            0, 1, 2, 2, 3, 0, // top
+            4, 5, 6, 6, 7, 4, // bottom
+            8, 9, 10, 10, 11, 8, // right
+            12, 13, 14, 14, 15, 12, // left
+            16, 17, 18, 18, 19, 16, // front
+            20, 21, 22, 22, 23, 20, // back
+        ],
+    )
+}
+
 /// Creates a 3D cube
 pub fn cube(
     name: impl StringBuffer,
@@ -142,166 +293,17 @@ pub fn cube(
     renderer: &mut Renderer,
     objects: &mut ObjectStorage,
 ) -> Result<(), crate::error::Error> {
+    let (vertices, indices) = cube_data();
     objects.insert(
         name.as_string(),
-        Object::new(
-            name,
-            vec![
-                // Front Face
-                Vertex {
-                    position: [-1.0, -1.0, 1.0],
-                    uv: [0.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [1.0, -1.0, 1.0],
-                    uv: [1.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [1.0, 1.0, 1.0],
-                    uv: [1.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [-1.0, 1.0, 1.0],
-                    uv: [0.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                // Back Face
-                Vertex {
-                    position: [-1.0, 1.0, -1.0],
-                    uv: [1.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [1.0, 1.0, -1.0],
-                    uv: [0.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [1.0, -1.0, -1.0],
-                    uv: [0.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [-1.0, -1.0, -1.0],
-                    uv: [1.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                // Right face
-                Vertex {
-                    position: [1.0, -1.0, -1.0],
-                    uv: [1.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [1.0, 1.0, -1.0],
-                    uv: [1.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [1.0, 1.0, 1.0],
-                    uv: [0.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [1.0, -1.0, 1.0],
-                    uv: [0.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                // Left face
-                Vertex {
-                    position: [-1.0, -1.0, 1.0],
-                    uv: [1.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [-1.0, 1.0, 1.0],
-                    uv: [1.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [-1.0, 1.0, -1.0],
-                    uv: [0.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [-1.0, -1.0, -1.0],
-                    uv: [0.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                // Top face
-                Vertex {
-                    position: [1.0, 1.0, -1.0],
-                    uv: [1.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [-1.0, 1.0, -1.0],
-                    uv: [0.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [-1.0, 1.0, 1.0],
-                    uv: [0.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [1.0, 1.0, 1.0],
-                    uv: [1.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                // Bottom face
-                Vertex {
-                    position: [1.0, -1.0, 1.0],
-                    uv: [1.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [-1.0, -1.0, 1.0],
-                    uv: [0.0, 0.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [-1.0, -1.0, -1.0],
-                    uv: [0.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-                Vertex {
-                    position: [1.0, -1.0, -1.0],
-                    uv: [1.0, 1.0],
-                    normal: [0.0, 0.0, 0.0],
-                },
-            ],
-            vec![
-                0, 1, 2, 2, 3, 0, // top
-                4, 5, 6, 6, 7, 4, // bottom
-                8, 9, 10, 10, 11, 8, // right
-                12, 13, 14, 14, 15, 12, // left
-                16, 17, 18, 18, 19, 16, // front
-                20, 21, 22, 22, 23, 20, // back
-            ],
-            settings,
-            renderer,
-        )?,
+        Object::new(name, vertices, indices, settings, renderer)?,
     );
 
     Ok(())
 }
 
-/// Create a 3D UV Sphere
-///
-/// ```
-/// details = (stacks, sectors, radius)
-/// example = (18, 36, 1.0)
-/// ```
-pub fn uv_sphere(
-    name: impl StringBuffer,
-    details: (usize, usize, f32),
-    renderer: &mut Renderer,
-    objects: &mut ObjectStorage,
-) -> Result<(), crate::error::Error> {
+/// Returns the vertices and indices of a 3D UV Sphere, the details are same as [uv_sphere]
+pub fn uv_sphere_data(details: (usize, usize, f32)) -> (Vec<Vertex>, Vec<UnsignedIntType>) {
     let sectors = details.1 as f32;
     let stacks = details.0 as f32;
     let length_inv = 1. / details.2;
@@ -347,6 +349,22 @@ pub fn uv_sphere(
         }
     }
 
+    (vertices, indices)
+}
+
+/// Create a 3D UV Sphere
+///
+/// ```
+/// details = (stacks, sectors, radius)
+/// example = (18, 36, 1.0)
+/// ```
+pub fn uv_sphere(
+    name: impl StringBuffer,
+    details: (usize, usize, f32),
+    renderer: &mut Renderer,
+    objects: &mut ObjectStorage,
+) -> Result<(), crate::error::Error> {
+    let (vertices, indices) = uv_sphere_data(details);
     objects.insert(
         name.as_string(),
         Object::new(name, vertices, indices, ObjectSettings::default(), renderer)?,
@@ -2,10 +2,33 @@
 
 use crate::{
     CameraContainer, ObjectStorage, PipelineData,
+    objects::ShaderBuilder,
     prelude::{ShaderSettings, TextureData},
-    utils::default_resources::{DEFAULT_COLOR, DEFAULT_SHADER, DEFAULT_TEXTURE},
+    utils::{
+        default_resources::{DEFAULT_COLOR, DEFAULT_SHADER, DEFAULT_TEXTURE},
+        frame_graph::{MAIN_PASS, POST_PASS, UI_PASS},
+        shader_variation::{FragmentUniform, ShaderVariation},
+    },
 };
 
+/// The texture a frame is rendered to
+#[derive(Debug)]
+pub enum FrameTexture {
+    /// A texture of the window surface, which is presented after rendering
+    Surface(wgpu::SurfaceTexture),
+    /// The offscreen target of the renderer, used when rendering without a window
+    Offscreen(wgpu::Texture),
+}
+impl FrameTexture {
+    /// Returns the texture that the frame is rendered to
+    pub fn texture(&self) -> &wgpu::Texture {
+        match self {
+            FrameTexture::Surface(frame) => &frame.texture,
+            FrameTexture::Offscreen(texture) => texture,
+        }
+    }
+}
+
 /// Main renderer class. this will contain all methods and data related to the renderer
 #[derive(Debug)]
 pub struct Renderer {
@@ -35,47 +58,143 @@ pub struct Renderer {
     pub default_data: Option<(crate::Textures, crate::Shaders, crate::UniformBuffers)>,
     /// The camera used in the engine
     pub camera: Option<crate::UniformBuffers>,
-    /// Background clear color
-    pub clear_color: wgpu::Color,
+    /// What the frame is cleared to before the objects are drawn
+    pub clear_color: crate::utils::clear::ClearColor,
+    /// The depth the depth buffer is cleared to before the objects are drawn
+    pub clear_depth: f32,
+    /// Draws the gradients the frame is cleared to
+    pub(crate) clear_gradient: crate::utils::clear::ClearGradient,
+    /// The fog of the objects affected by the cameras, see [crate::utils::fog::Fog]
+    pub fog: Option<crate::utils::fog::Fog>,
+    /// When the time of the shaders started, see [Renderer::shader_time]
+    pub(crate) shader_clock: crate::time::Instant,
     /// Scissor cut section of the screen to render to
     /// (x, y, width, height)
     pub scissor_rect: Option<(u32, u32, u32, u32)>,
+    /// Screen space painter drawn on top of the scene
+    pub overlay: crate::utils::overlay::Overlay,
+    /// Statistics of the rendered frames
+    pub stats: crate::utils::performance::RenderStats,
+    /// Should the performance HUD be shown
+    pub show_stats: bool,
+    /// Wraps the passes, layers, and objects in debug groups, so GPU captures such as RenderDoc
+    /// or Xcode show a tree of them instead of a flat list of draws. Layers are the render
+    /// orders of the objects
+    pub debug_groups: bool,
+    /// The objects and batches of the pass in the order they are drawn, kept between the frames
+    pub(crate) draw_list: Vec<(usize, u32, DrawItem)>,
+    /// The label of the debug group being pushed, kept between the frames
+    pub(crate) debug_label: String,
+    /// The objects the spatial index found in the view of their camera, kept between the frames
+    pub(crate) in_view: std::collections::HashSet<crate::ObjectId>,
+    /// The stack of the walks of the spatial index, kept between the frames
+    pub(crate) culling_stack: Vec<usize>,
+    /// Measures the GPU time of the passes, when enabled
+    pub gpu_profiler: Option<crate::utils::gpu_profiler::GpuProfiler>,
+    /// The texture rendered to when there is no window, used in headless mode
+    pub offscreen_target: Option<(wgpu::Texture, wgpu::TextureView)>,
+    /// Captures the presented frames for screenshots and frame sequences
+    pub frame_recorder: crate::utils::frame_recorder::FrameRecorder,
+    /// Keeps the last frames for debugging
+    pub frame_history: crate::utils::frame_history::FrameHistory,
+    /// Loads the assets in the background
+    pub assets: crate::utils::asset_server::AssetServer,
+    /// Uploads the streamed textures over several frames
+    pub texture_streaming: crate::utils::mipmaps::TextureStreaming,
+    /// The vertices of objects written by compute shaders before every frame
+    pub compute_meshes: crate::utils::compute_mesh::ComputeMeshes,
+    /// The animations of the instances of objects drawn as crowds
+    pub skinned_crowds: crate::utils::skinned_instancing::SkinnedCrowds,
+    /// The objects blended by their morph targets
+    pub morphed_objects: crate::utils::morph_targets::MorphedObjects,
+    /// The largest size of the loaded textures, and the GPU memory they may take
+    pub texture_budget: crate::utils::texture_budget::TextureBudget,
+    /// The render pipelines and layouts shared by the objects
+    pub pipeline_cache: crate::utils::pipeline_cache::PipelineCache,
+    /// Merges the static objects into fewer draw calls
+    pub static_batches: crate::utils::static_batching::StaticBatches,
+    /// Culls the meshlets of the objects that have them
+    pub meshlet_culling: crate::utils::meshlets::MeshletCulling,
+    /// The motion of the objects since the previous frame, drawn when enabled
+    pub motion_vectors: crate::utils::motion_vectors::MotionVectors,
+    /// The hierarchical depth of the scene, made after the main pass when enabled
+    pub depth_pyramid: crate::utils::depth_pyramid::DepthPyramid,
+    /// Skips the objects hidden behind others, found by occlusion queries
+    pub occlusion_culling: crate::utils::occlusion_culling::OcclusionCulling,
+    /// The shadow maps of the lights in one texture, drawn before the main pass when enabled
+    pub shadow_atlas: crate::utils::shadow_atlas::ShadowAtlas,
+    /// The experimental visibility buffer renderer, drawn after the main pass when enabled
+    pub visibility_buffer: crate::utils::visibility_buffer::VisibilityBuffer,
+    /// The quality last applied, see [Renderer::apply_quality]
+    pub(crate) quality: crate::utils::quality::QualitySettings,
+    /// The shared uniform buffer of the objects with only the default uniforms
+    pub uniform_pool: crate::utils::uniform_pool::UniformPool,
+    /// The shared vertex and index buffers of the pooled objects
+    pub mesh_pool: crate::utils::mesh_pool::MeshPool,
+    /// The custom passes of the frame, placed among the passes of the engine
+    pub frame_graph: crate::utils::frame_graph::FrameGraph,
+    /// Fills the mip levels of the textures
+    pub(crate) mipmaps: crate::utils::mipmaps::MipmapGenerator,
+    /// The infinite ground grid, drawn when enabled
+    pub grid: crate::utils::grid::Grid,
+    /// The opacity of the window the frame is presented to, set by the window
+    pub(crate) window_opacity: f32,
+    /// The pipeline that fades the frame by the window opacity, and the format it was built for
+    pub(crate) opacity_pipeline: Option<(wgpu::TextureFormat, wgpu::RenderPipeline)>,
+    /// The internal textures of the cameras with a render scale
+    pub(crate) camera_targets: crate::utils::render_scale::CameraTargets,
+    /// The tonemapping of the scene, when it is rendered in HDR
+    pub(crate) hdr: Option<crate::utils::hdr::Hdr>,
+    /// The scene of the window before it is tonemapped
+    pub(crate) hdr_target: Option<crate::utils::hdr::HdrTarget>,
+    /// The shadows traced towards a light, on devices with ray tracing
+    pub(crate) ray_traced_shadows: Option<crate::utils::ray_traced_shadows::RayTracedShadows>,
+    /// The color space asked for by [crate::WindowDescriptor::output_color]
+    pub(crate) requested_output: crate::utils::surface_format::OutputColor,
+    /// What was chosen for the surface of the window, see [Renderer::surface_report]
+    pub(crate) surface_report: Option<crate::utils::surface_format::SurfaceReport>,
+    /// Set when the device is lost, until it is made again
+    pub(crate) device_lost: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    /// The settings the device was asked for with, for when it is made again
+    pub(crate) device_settings: crate::WindowDescriptor,
 }
-unsafe impl Sync for Renderer {}
-unsafe impl Send for Renderer {}
 
 impl Renderer {
     /// Creates a new renderer.
     pub(crate) async fn new(
         size: winit::dpi::PhysicalSize<u32>,
         settings: crate::WindowDescriptor,
+        #[cfg(target_arch = "wasm32")] canvas: web_sys::HtmlCanvasElement,
     ) -> Result<Self, crate::error::Error> {
-        // The instance is a handle to our GPU
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: settings.backends,
+        let instance_descriptor = wgpu::InstanceDescriptor {
+            backends: crate::utils::adapters::instance_backends(&settings),
             ..Default::default()
-        });
+        };
+        // The instance is a handle to our GPU
+        #[cfg(not(target_arch = "wasm32"))]
+        let instance = wgpu::Instance::new(&instance_descriptor);
+        // on the web WebGPU is only used where the browser supports it, WebGL2 otherwise
+        #[cfg(target_arch = "wasm32")]
+        let instance = wgpu::util::new_instance_with_webgpu_detection(&instance_descriptor).await;
+
+        // the adapters of WebGL2 can only draw to the canvas they are made for, so the surface
+        // of the canvas is made before the adapter
+        #[cfg(target_arch = "wasm32")]
+        let surface = instance
+            .create_surface(wgpu::SurfaceTarget::Canvas(canvas))
+            .map_err(|e| crate::error::Error::WebError(e.to_string()))?;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let compatible_surface = None;
+        #[cfg(target_arch = "wasm32")]
+        let compatible_surface = Some(&surface);
 
-        match instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: settings.power_preference,
-                compatible_surface: None,
-                force_fallback_adapter: false,
-            })
+        match crate::utils::adapters::request_adapter(&instance, &settings, compatible_surface)
             .await
         {
-            Some(adapter) => {
-                let (device, queue) = adapter
-                    .request_device(
-                        &wgpu::DeviceDescriptor {
-                            label: Some("Device"),
-                            required_features: settings.features,
-                            required_limits: settings.limits,
-                            memory_hints: wgpu::MemoryHints::Performance,
-                        },
-                        None, // Trace path
-                    )
-                    .await?;
+            Ok(adapter) => {
+                let (device, queue) = Self::request_device(&adapter, &settings).await?;
+                let device_lost = crate::utils::device_recovery::watch_device(&device);
 
                 let texture_format = wgpu::TextureFormat::Bgra8UnormSrgb;
 
@@ -85,13 +204,9 @@ impl Renderer {
                 let config = wgpu::SurfaceConfiguration {
                     usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
                     format: texture_format, //wgpu::TextureFormat::Bgra8UnormSrgb,
-                    #[cfg(target_os = "android")]
-                    width: 1080,
-                    #[cfg(not(feature = "android"))]
+                    // on mobile this is replaced by the size of the screen once the surface
+                    // is made, and again when the device is rotated
                     width: size.width,
-                    #[cfg(target_os = "android")]
-                    height: 2300,
-                    #[cfg(not(target_os = "android"))]
                     height: size.height,
                     #[cfg(target_os = "android")]
                     present_mode: wgpu::PresentMode::Mailbox,
@@ -102,46 +217,8 @@ impl Renderer {
                     desired_maximum_frame_latency: settings.desired_maximum_frame_latency,
                 };
 
-                let texture_bind_group_layout =
-                    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                        entries: &[
-                            wgpu::BindGroupLayoutEntry {
-                                binding: 0,
-                                visibility: wgpu::ShaderStages::FRAGMENT,
-                                ty: wgpu::BindingType::Texture {
-                                    sample_type: wgpu::TextureSampleType::Float {
-                                        filterable: true,
-                                    },
-                                    view_dimension: wgpu::TextureViewDimension::D2,
-                                    multisampled: false,
-                                },
-                                count: None,
-                            },
-                            wgpu::BindGroupLayoutEntry {
-                                binding: 1,
-                                visibility: wgpu::ShaderStages::FRAGMENT,
-                                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering), //comparison: false,
-                                // filtering: true,
-                                count: None,
-                            },
-                        ],
-                        label: Some("texture_bind_group_layout"),
-                    });
-
-                let default_uniform_bind_group_layout =
-                    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                        label: Some("uniform dynamic bind group layout"),
-                        entries: &[wgpu::BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Uniform,
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            count: None,
-                        }],
-                    });
+                let (texture_bind_group_layout, default_uniform_bind_group_layout) =
+                    Self::build_bind_group_layouts(&device);
 
                 let depth_buffer = Renderer::build_depth_buffer("Depth Buffer", &device, &config);
 
@@ -160,18 +237,151 @@ impl Renderer {
 
                     default_data: None,
                     camera: None,
-                    clear_color: wgpu::Color::BLACK,
+                    clear_color: if settings.transparent {
+                        wgpu::Color::TRANSPARENT.into()
+                    } else {
+                        wgpu::Color::BLACK.into()
+                    },
+                    clear_depth: 1.0,
+                    clear_gradient: crate::utils::clear::ClearGradient::default(),
+                    fog: None,
+                    shader_clock: crate::time::Instant::now(),
                     scissor_rect: None,
+                    overlay: crate::utils::overlay::Overlay::new(),
+                    stats: crate::utils::performance::RenderStats::new(),
+                    show_stats: settings.show_stats,
+                    debug_groups: false,
+                    draw_list: Vec::new(),
+                    debug_label: String::new(),
+                    in_view: std::collections::HashSet::new(),
+                    culling_stack: Vec::new(),
+                    gpu_profiler: None,
+                    offscreen_target: None,
+                    frame_recorder: crate::utils::frame_recorder::FrameRecorder::new(),
+                    frame_history: crate::utils::frame_history::FrameHistory::new(),
+                    assets: crate::utils::asset_server::AssetServer::new(),
+                    texture_streaming: crate::utils::mipmaps::TextureStreaming::new(),
+                    compute_meshes: crate::utils::compute_mesh::ComputeMeshes::new(),
+                    skinned_crowds: crate::utils::skinned_instancing::SkinnedCrowds::new(),
+                    morphed_objects: crate::utils::morph_targets::MorphedObjects::new(),
+                    texture_budget: crate::utils::texture_budget::TextureBudget::new(),
+                    pipeline_cache: crate::utils::pipeline_cache::PipelineCache::new(),
+                    static_batches: crate::utils::static_batching::StaticBatches::new(),
+                    meshlet_culling: crate::utils::meshlets::MeshletCulling::new(),
+                    motion_vectors: crate::utils::motion_vectors::MotionVectors::new(),
+                    depth_pyramid: crate::utils::depth_pyramid::DepthPyramid::new(),
+                    occlusion_culling: crate::utils::occlusion_culling::OcclusionCulling::new(),
+                    shadow_atlas: crate::utils::shadow_atlas::ShadowAtlas::new(),
+                    visibility_buffer: crate::utils::visibility_buffer::VisibilityBuffer::new(),
+                    quality: crate::utils::quality::QualitySettings::default(),
+                    uniform_pool: crate::utils::uniform_pool::UniformPool::new(),
+                    mesh_pool: crate::utils::mesh_pool::MeshPool::new(),
+                    frame_graph: crate::utils::frame_graph::FrameGraph::new(),
+                    mipmaps: crate::utils::mipmaps::MipmapGenerator::default(),
+                    grid: crate::utils::grid::Grid::new(),
+                    window_opacity: 1.0,
+                    opacity_pipeline: None,
+                    camera_targets: crate::utils::render_scale::CameraTargets::default(),
+                    hdr: settings.hdr.map(crate::utils::hdr::Hdr::new),
+                    hdr_target: None,
+                    ray_traced_shadows: None,
+                    requested_output: settings.output_color,
+                    surface_report: None,
+                    device_lost,
+                    device_settings: settings.clone(),
                 };
 
+                #[cfg(target_arch = "wasm32")]
+                renderer.attach_surface(surface, settings.transparent);
                 renderer.build_default_data();
 
                 Ok(renderer)
             }
-            None => Err(crate::error::Error::AdapterNotFound),
+            Err(e) => Err(e),
         }
     }
 
+    /// Asks the adapter for the device and its queue, with the features the engine uses
+    /// wherever they are supported
+    pub(crate) async fn request_device(
+        adapter: &wgpu::Adapter,
+        settings: &crate::WindowDescriptor,
+    ) -> Result<(wgpu::Device, wgpu::Queue), crate::error::Error> {
+        // WebGL2 and older GL drivers fall short of the default limits, so the limits
+        // of WebGL2 are asked for instead, with the largest textures the adapter has
+        let required_limits = if adapter.get_info().backend == wgpu::Backend::Gl
+            && !settings.limits.check_limits(&adapter.limits())
+        {
+            wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+        } else {
+            settings.limits.clone()
+        };
+
+        Ok(adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("Device"),
+                    // the compressed texture formats are enabled wherever they are
+                    // supported, so DDS and KTX2 textures can be used as they are, and
+                    // so is the index of the triangles for the visibility buffer
+                    required_features: settings.features
+                        | (adapter.features()
+                            & (crate::utils::compressed_textures::TextureCompression::all_features()
+                                | wgpu::Features::SHADER_PRIMITIVE_INDEX)),
+                    required_limits,
+                    memory_hints: wgpu::MemoryHints::Performance,
+                },
+                None, // Trace path
+            )
+            .await?)
+    }
+
+    /// Creates the layouts of the texture and the default uniforms of the objects
+    pub(crate) fn build_bind_group_layouts(
+        device: &wgpu::Device,
+    ) -> (wgpu::BindGroupLayout, wgpu::BindGroupLayout) {
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering), //comparison: false,
+                        // filtering: true,
+                        count: None,
+                    },
+                ],
+                label: Some("texture_bind_group_layout"),
+            });
+
+        let default_uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("uniform dynamic bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        (texture_bind_group_layout, default_uniform_bind_group_layout)
+    }
+
     pub(crate) fn build_default_data(&mut self) {
         if let Ok(default_texture) = self.build_texture(
             "Default Texture",
@@ -181,12 +391,16 @@ impl Renderer {
         ) {
             let default_uniform = self.build_uniform_buffer(&vec![
                 self.build_uniform_buffer_part("Transformation Matrix", crate::Matrix4::IDENTITY),
-                self.build_uniform_buffer_part("Color", DEFAULT_COLOR),
+                self.build_uniform_buffer_part(
+                    "Color",
+                    FragmentUniform::new(DEFAULT_COLOR, ShaderVariation::default()),
+                ),
             ]);
 
+            // the placeholders are filled in as for the objects seen by the main camera
             let default_shader = self.build_shader(
                 "Default Shader",
-                DEFAULT_SHADER.to_string(),
+                ShaderBuilder::new(DEFAULT_SHADER.to_string(), Some("main".into())).shader,
                 Some(&default_uniform.1),
                 ShaderSettings::default(),
             );
@@ -198,14 +412,57 @@ impl Renderer {
         }
     }
 
+    /// Configures the surface of the window for the adapter, negotiating its format, alpha
+    /// mode, and present mode with the platform, see [crate::utils::surface_format]
+    pub(crate) fn attach_surface(&mut self, surface: wgpu::Surface<'static>, transparent: bool) {
+        let surface_capabilities = surface.get_capabilities(&self.adapter);
+        let report = crate::utils::surface_format::negotiate(
+            &surface_capabilities,
+            &self.config,
+            self.requested_output,
+            self.hdr.is_some(),
+            transparent,
+        );
+
+        self.config.format = report.format;
+        self.config.view_formats = vec![report.format];
+        self.config.alpha_mode = report.alpha_mode;
+        self.config.present_mode = report.present_mode;
+        // allows the frames to be captured for screenshots
+        if surface_capabilities
+            .usages
+            .contains(wgpu::TextureUsages::COPY_SRC)
+        {
+            self.config.usage |= wgpu::TextureUsages::COPY_SRC;
+        }
+        if let Some(hdr) = self.hdr.as_mut() {
+            hdr.output = report.output;
+        }
+        self.surface_report = Some(report);
+
+        surface.configure(&self.device, &self.config);
+        self.depth_buffer =
+            Renderer::build_depth_buffer("Depth Buffer", &self.device, &self.config);
+        self.surface = Some(surface);
+    }
+
     /// Resize the window.
     pub(crate) fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        // the canvas can be resized by the page past the largest texture of WebGL2
+        #[cfg(target_arch = "wasm32")]
+        let new_size = {
+            let max_size = self.device.limits().max_texture_dimension_2d;
+            winit::dpi::PhysicalSize::new(
+                new_size.width.min(max_size),
+                new_size.height.min(max_size),
+            )
+        };
+
         // check if new_size is non-zero
         if new_size.width != 0 && new_size.height != 0 {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            #[cfg(not(target_os = "android"))]
             if let Some(surface) = self.surface.as_ref() {
                 surface.configure(&self.device, &self.config);
                 {
@@ -213,38 +470,96 @@ impl Renderer {
                         Self::build_depth_buffer("Depth Buffer", &self.device, &self.config);
                 }
             }
+            if self.offscreen_target.is_some() {
+                self.offscreen_target = Some(self.build_offscreen_target());
+                self.depth_buffer =
+                    Self::build_depth_buffer("Depth Buffer", &self.device, &self.config);
+            }
         }
     }
 
-    /// Render the scene. Returns the command encoder, the texture view, and the surface texture.
+    /// Creates the texture rendered to when there is no window, based on the config
+    pub(crate) fn build_offscreen_target(&self) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Target"),
+            size: wgpu::Extent3d {
+                width: self.config.width,
+                height: self.config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        (texture, view)
+    }
+
+    /// Makes the renderer draw into an offscreen texture instead of a window.
+    ///
+    /// This is used for headless rendering, and rebuilds the default data and depth buffer
+    /// for the new format.
+    pub fn enable_offscreen_target(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        self.size = size;
+        self.config.width = size.width.max(1);
+        self.config.height = size.height.max(1);
+        self.config.format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        self.config.view_formats = vec![self.config.format];
+
+        self.offscreen_target = Some(self.build_offscreen_target());
+        self.depth_buffer = Self::build_depth_buffer("Depth Buffer", &self.device, &self.config);
+        self.build_default_data();
+    }
+
+    /// Render the scene. Returns the command encoder, the texture view, and the frame texture.
     pub(crate) fn pre_render(
         &mut self,
         objects: &ObjectStorage,
         window_size: winit::dpi::PhysicalSize<u32>,
         camera: &CameraContainer,
-    ) -> Result<
-        Option<(
-            wgpu::CommandEncoder,
-            wgpu::TextureView,
-            wgpu::SurfaceTexture,
-        )>,
-        wgpu::SurfaceError,
-    > {
-        let surface = if let Some(ref surface) = self.surface {
-            surface
-        } else {
-            return Ok(None);
-        };
+    ) -> Result<Option<(wgpu::CommandEncoder, wgpu::TextureView, FrameTexture)>, wgpu::SurfaceError>
+    {
+        let (view, frame) = if let Some(ref surface) = self.surface {
+            let frame = match surface.get_current_texture() {
+                Ok(frame) => frame,
+                // the surface is made again for the window as it is now, and drawn to from
+                // the next frame
+                Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                    surface.configure(&self.device, &self.config);
+                    return Ok(None);
+                }
+                Err(wgpu::SurfaceError::OutOfMemory) => {
+                    return Err(wgpu::SurfaceError::OutOfMemory);
+                }
+                Err(_) => return Ok(None),
+            };
+
+            let view = frame
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default());
 
-        let frame = if let Ok(frame) = surface.get_current_texture() {
-            frame
+            (view, FrameTexture::Surface(frame))
+        } else if let Some((texture, view)) = self.offscreen_target.as_ref() {
+            (view.clone(), FrameTexture::Offscreen(texture.clone()))
         } else {
             return Ok(None);
         };
 
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.refresh_static_batches(objects);
+        self.cull_meshlets(objects, camera);
+        self.collect_textures(objects);
+
+        if let Some(gpu_profiler) = self.gpu_profiler.as_mut() {
+            gpu_profiler.begin_frame(&self.device);
+            self.stats.gpu_timings.clone_from(&gpu_profiler.timings);
+        }
+        self.occlusion_culling.begin_frame(&self.device);
 
         let mut encoder = self
             .device
@@ -252,40 +567,304 @@ impl Renderer {
                 label: Some("Render Encoder"),
             });
 
+        // in HDR the scene is drawn to a float texture first, and tonemapped onto the frame
+        let scene_view = match self.hdr.as_mut() {
+            Some(hdr) => hdr.scene_view(
+                &self.device,
+                &mut self.hdr_target,
+                self.config.width,
+                self.config.height,
+            ),
+            None => view.clone(),
+        };
+        let scene_texture = match (self.hdr.as_ref(), self.hdr_target.as_ref()) {
+            (Some(_), Some(target)) => target.texture().clone(),
+            _ => frame.texture().clone(),
+        };
+        let frame_texture = frame.texture().clone();
+
+        // the meshes are written before anything draws them
+        self.compute_meshes
+            .dispatch(&self.device, &self.queue, &mut encoder, objects);
+        self.skinned_crowds.update(&self.queue, objects);
+        self.morphed_objects.update(&self.queue);
+
+        // the shadows are drawn first, so the objects of the main pass can read them
+        if self.shadow_atlas.enabled {
+            self.shadow_atlas.draw(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                objects,
+                &self.mesh_pool,
+            );
+        }
+
+        self.frame_graph
+            .begin_frame(&self.device, (self.config.width, self.config.height));
+        self.run_frame_graph(
+            Some(MAIN_PASS),
+            &mut encoder,
+            (&scene_texture, &scene_view),
+            (&frame_texture, &view),
+        );
+
+        let (scaled_cameras, scaled_draw_calls) =
+            self.draw_scaled_cameras(&mut encoder, &scene_view, objects, camera);
+
+        let depth_view = self.depth_buffer.1.clone();
+        let load = if scaled_cameras.is_empty() {
+            let format = self.scene_format();
+            self.clear_load(self.clear_color, &mut encoder, &scene_view, format)
+        } else {
+            wgpu::LoadOp::Load
+        };
+        // the objects of the visibility buffer are left out of the main pass when it resolves
+        // them itself, unless their camera is drawn at another scale
+        let replaced_camera = (self.visibility_buffer.replaces_main_pass()
+            && !scaled_cameras.contains(&self.visibility_buffer.camera))
+        .then(|| self.visibility_buffer.camera.clone());
+        let occluded = self.occlusion_culling.occluded();
+        self.stats.draw_calls = scaled_draw_calls
+            + self.draw_objects_filtered(
+                &mut encoder,
+                &scene_view,
+                &depth_view,
+                objects,
+                window_size,
+                camera,
+                Some("Main Pass"),
+                load,
+                |i| {
+                    let camera_name = i.camera_effect.as_deref().unwrap_or("main");
+                    !scaled_cameras.iter().any(|j| j.as_ref() == camera_name)
+                        && !replaced_camera
+                            .as_ref()
+                            .is_some_and(|j| crate::utils::visibility_buffer::draws(i, j))
+                        && !occluded.contains(&i.name)
+                },
+            );
+        if self.visibility_buffer.enabled {
+            let format = self.scene_format();
+            self.visibility_buffer.draw(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                (&scene_view, format),
+                &depth_view,
+                (self.config.width, self.config.height),
+                objects,
+                camera,
+                &self.mesh_pool,
+            );
+        }
+        // the boxes are tested against the depth of everything drawn in the main pass
+        self.occlusion_culling.draw(
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            &depth_view,
+            window_size,
+            (objects, camera),
+            &self.default_uniform_bind_group_layout,
+            &self.static_batches,
+        );
+        if self.depth_pyramid.enabled {
+            self.depth_pyramid.draw(
+                &self.device,
+                &mut encoder,
+                (self.config.width, self.config.height),
+                &depth_view,
+            );
+        }
+        if self.motion_vectors.enabled {
+            self.motion_vectors.draw(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                (self.config.width, self.config.height),
+                objects,
+                camera,
+                &self.mesh_pool,
+            );
+        }
+        if self.ray_traced_shadows.is_some() {
+            let format = self.scene_format();
+            if let Some(shadows) = self.ray_traced_shadows.as_mut() {
+                shadows.draw(
+                    &self.device,
+                    &self.queue,
+                    &mut encoder,
+                    &scene_view,
+                    &depth_view,
+                    format,
+                    objects,
+                    camera,
+                );
+            }
+        }
+        if self.grid.enabled {
+            let format = self.scene_format();
+            self.grid.draw(
+                &self.device,
+                &mut encoder,
+                &scene_view,
+                &depth_view,
+                format,
+                camera,
+            );
+        }
+
+        self.run_frame_graph(
+            Some(POST_PASS),
+            &mut encoder,
+            (&scene_texture, &scene_view),
+            (&frame_texture, &view),
+        );
+        if let (Some(hdr), Some(target)) = (self.hdr.as_mut(), self.hdr_target.as_ref()) {
+            hdr.resolve(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                target,
+                &view,
+                self.config.format,
+            );
+        }
+        self.run_frame_graph(
+            Some(UI_PASS),
+            &mut encoder,
+            (&scene_texture, &scene_view),
+            (&frame_texture, &view),
+        );
+
+        Ok(Some((encoder, view, frame)))
+    }
+
+    /// Runs the custom passes of the frame graph up to the pass of the engine, or all that
+    /// are left if None
+    fn run_frame_graph(
+        &mut self,
+        until: Option<&str>,
+        encoder: &mut wgpu::CommandEncoder,
+        (scene_texture, scene): (&wgpu::Texture, &wgpu::TextureView),
+        (frame_texture, frame): (&wgpu::Texture, &wgpu::TextureView),
+    ) {
+        let scene_format = self.scene_format();
+        let targets = crate::utils::frame_graph::FrameTargets {
+            device: &self.device,
+            queue: &self.queue,
+            encoder,
+            frame_size: (self.config.width, self.config.height),
+            scene_format,
+            frame_format: self.config.format,
+            scene,
+            depth: &self.depth_buffer.1,
+            frame,
+            motion_vectors: self.motion_vectors.view(),
+            depth_pyramid: self.depth_pyramid.view(),
+            shadow_atlas: self.shadow_atlas.view(),
+            visibility_buffer: self.visibility_buffer.view(),
+            scene_texture,
+            depth_texture: &self.depth_buffer.0,
+            frame_texture,
+            motion_vectors_texture: self.motion_vectors.texture(),
+            depth_pyramid_texture: self.depth_pyramid.texture(),
+            shadow_atlas_texture: self.shadow_atlas.texture(),
+            visibility_buffer_texture: self.visibility_buffer.texture(),
+            debug_groups: self.debug_groups,
+        };
+        self.frame_graph.run_until(until, targets);
+    }
+
+    /// Clears the view as given and draws the objects onto it, returns the amount of draw
+    /// calls.
+    ///
+    /// If a profile label is given, the pass is measured by the GPU profiler
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn draw_objects(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        objects: &ObjectStorage,
+        window_size: winit::dpi::PhysicalSize<u32>,
+        camera: &CameraContainer,
+        profile_label: Option<&str>,
+        clear_color: crate::utils::clear::ClearColor,
+    ) -> u32 {
+        let format = self.scene_format();
+        let load = self.clear_load(clear_color, encoder, view, format);
+        self.draw_objects_filtered(
+            encoder,
+            view,
+            depth_view,
+            objects,
+            window_size,
+            camera,
+            profile_label,
+            load,
+            |_| true,
+        )
+    }
+
+    /// Draws the objects that pass the filter onto the view, loading it as given.
+    /// Returns the amount of draw calls
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn draw_objects_filtered(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        objects: &ObjectStorage,
+        window_size: winit::dpi::PhysicalSize<u32>,
+        camera: &CameraContainer,
+        profile_label: Option<&str>,
+        load: wgpu::LoadOp<wgpu::Color>,
+        filter: impl Fn(&crate::Object) -> bool,
+    ) -> u32 {
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render pass"),
+            label: Some(profile_label.unwrap_or("Render pass")),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
+                view,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(self.clear_color),
+                    load,
                     store: wgpu::StoreOp::Store,
                 },
             })],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_buffer.1,
+                view: depth_view,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
+                    load: wgpu::LoadOp::Clear(self.clear_depth),
                     store: wgpu::StoreOp::Store,
                 }),
                 stencil_ops: None,
             }),
-            timestamp_writes: None,
+            timestamp_writes: profile_label.and_then(|label| {
+                self.gpu_profiler
+                    .as_mut()
+                    .and_then(|i| i.render_pass_timestamps(label))
+            }),
             occlusion_query_set: None,
         });
 
-        if let Some(scissor_rect) = self.scissor_rect {
+        let frame_rect = (0, 0, window_size.width, window_size.height);
+        let scissor_rect = self
+            .scissor_rect
             // check if scissor bounds are smaller than the window
-            if scissor_rect.0 + scissor_rect.2 < window_size.width
-                && scissor_rect.1 + scissor_rect.3 < window_size.height
-            {
-                render_pass.set_scissor_rect(
-                    scissor_rect.0,
-                    scissor_rect.1,
-                    scissor_rect.2,
-                    scissor_rect.3,
-                );
-            }
+            .filter(|i| i.0 + i.2 < window_size.width && i.1 + i.3 < window_size.height)
+            .unwrap_or(frame_rect);
+        // the viewport and scissor set on the pass, changed only when the next item needs others
+        let mut clip = (frame_rect, frame_rect);
+        if scissor_rect != frame_rect {
+            render_pass.set_scissor_rect(
+                scissor_rect.0,
+                scissor_rect.1,
+                scissor_rect.2,
+                scissor_rect.3,
+            );
+            clip.1 = scissor_rect;
         }
 
         if let Some(default_data) = self.default_data.as_ref() {
@@ -293,11 +872,83 @@ impl Renderer {
             render_pass.set_pipeline(&default_data.1);
         }
 
-        // sort the object list in descending render order
-        let mut object_list: Vec<_> = objects.iter().collect();
-        object_list.sort_by(|(_, a), (_, b)| a.render_order.cmp(&b.render_order).reverse());
+        self.prepare_draw_list(objects, camera, &filter);
+        // taken for the pass, and put back with its capacity after
+        let draw_list = std::mem::take(&mut self.draw_list);
+
+        let mut draw_calls = 0;
+        // the pooled objects share their buffers, so they are bound once for all in a row
+        let mut pool_bound = false;
+        let mut layer = None;
+
+        for &(render_order, _, item) in draw_list.iter() {
+            if self.debug_groups && layer != Some(render_order) {
+                if layer.is_some() {
+                    render_pass.pop_debug_group();
+                }
+                render_pass.push_debug_group(debug_label(
+                    &mut self.debug_label,
+                    format_args!("Layer {render_order}"),
+                ));
+                layer = Some(render_order);
+            }
+
+            let i = match item {
+                DrawItem::Object(id) => match objects.get_by_id(id) {
+                    Some(i) => i,
+                    None => continue,
+                },
+                DrawItem::Batch(index) => {
+                    let Some(batch) = self.static_batches.batch(index) else {
+                        continue;
+                    };
+                    if self.debug_groups {
+                        render_pass.push_debug_group(debug_label(
+                            &mut self.debug_label,
+                            format_args!("Static Batch \"{}\"", batch.first),
+                        ));
+                    }
+                    let camera_name = batch.camera_effect.as_deref().unwrap_or("main");
+                    if let Some(camera) = camera.get(camera_name) {
+                        render_pass.set_bind_group(1, &camera.uniform_data, &[]);
+                    }
+                    let clipped = set_clip(
+                        &mut render_pass,
+                        &mut clip,
+                        item_clip(camera.get(camera_name), None, window_size, scissor_rect),
+                    );
+                    if clipped && let Some(instance_buffer) = self.static_batches.instance_buffer()
+                    {
+                        pool_bound = false;
+                        render_pass
+                            .set_vertex_buffer(0, batch.vertex_buffers.vertex_buffer.slice(..));
+                        render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+                        render_pass.set_index_buffer(
+                            batch.vertex_buffers.index_buffer.slice(..),
+                            #[cfg(not(feature = "u32"))]
+                            wgpu::IndexFormat::Uint16,
+                            #[cfg(feature = "u32")]
+                            wgpu::IndexFormat::Uint32,
+                        );
+                        if let Some(bind_group) = self.uniform_pool.bind_group() {
+                            render_pass.set_bind_group(
+                                2,
+                                bind_group,
+                                &self.uniform_pool.offsets(&batch.uniform),
+                            );
+                        }
+                        render_pass.set_pipeline(&batch.shader);
+                        render_pass.set_bind_group(0, &batch.texture, &[]);
+                        render_pass.draw_indexed(0..batch.vertex_buffers.length, 0, 0..1);
+                        draw_calls += 1;
+                    }
+                    if self.debug_groups {
+                        render_pass.pop_debug_group();
+                    }
+                    continue;
+                }
+            };
 
-        for (_, i) in object_list {
             if let Some(camera_data) = i.camera_effect.as_ref() {
                 if let Some(camera) = camera.get(camera_data.as_ref()) {
                     render_pass.set_bind_group(1, &camera.uniform_data, &[]);
@@ -308,7 +959,25 @@ impl Renderer {
                 }
             }
 
-            if i.is_visible {
+            let camera_name = i.camera_effect.as_deref().unwrap_or("main");
+            let clipped = set_clip(
+                &mut render_pass,
+                &mut clip,
+                item_clip(
+                    camera.get(camera_name),
+                    i.scissor,
+                    window_size,
+                    scissor_rect,
+                ),
+            );
+
+            if self.debug_groups {
+                render_pass.push_debug_group(debug_label(
+                    &mut self.debug_label,
+                    format_args!("Object \"{}\"", i.name),
+                ));
+            }
+            if i.is_visible && clipped {
                 let vertex_buffer = get_pipeline_vertex_buffer(&i.pipeline.vertex_buffer, objects);
                 let shader = get_pipeline_shader(&i.pipeline.shader, objects);
                 let texture = get_pipeline_texture(&i.pipeline.texture, objects);
@@ -316,54 +985,511 @@ impl Renderer {
 
                 // vertex
                 if let Some(vertex_buffer) = vertex_buffer {
-                    render_pass.set_vertex_buffer(0, vertex_buffer.vertex_buffer.slice(..));
+                    // the meshlets left after culling are drawn instead of all the indices
+                    let (index_buffer, length) = match self.meshlet_culling.culled(i) {
+                        Some(culled) => (&culled.index_buffer, culled.length),
+                        None => (&vertex_buffer.index_buffer, vertex_buffer.length),
+                    };
+
+                    let pooled = crate::utils::mesh_pool::allocation_of(i, objects)
+                        .zip(self.mesh_pool.buffers());
+                    let (first_index, base_vertex) = match pooled {
+                        Some((allocation, (pool_vertices, pool_indices))) => {
+                            if !pool_bound {
+                                render_pass.set_vertex_buffer(0, pool_vertices.slice(..));
+                                render_pass.set_index_buffer(
+                                    pool_indices.slice(..),
+                                    #[cfg(not(feature = "u32"))]
+                                    wgpu::IndexFormat::Uint16,
+                                    #[cfg(feature = "u32")]
+                                    wgpu::IndexFormat::Uint32,
+                                );
+                                pool_bound = true;
+                            }
+                            (allocation.indices().start, allocation.base_vertex())
+                        }
+                        None => {
+                            render_pass.set_vertex_buffer(0, vertex_buffer.vertex_buffer.slice(..));
+                            render_pass.set_index_buffer(
+                                index_buffer.slice(..),
+                                #[cfg(not(feature = "u32"))]
+                                wgpu::IndexFormat::Uint16,
+                                #[cfg(feature = "u32")]
+                                wgpu::IndexFormat::Uint32,
+                            );
+                            pool_bound = false;
+                            (0, 0)
+                        }
+                    };
                     render_pass.set_vertex_buffer(1, i.instance_buffer.slice(..));
-                    render_pass.set_index_buffer(
-                        vertex_buffer.index_buffer.slice(..),
-                        #[cfg(not(feature = "u32"))]
-                        wgpu::IndexFormat::Uint16,
-                        #[cfg(feature = "u32")]
-                        wgpu::IndexFormat::Uint32,
-                    );
 
-                    // shader
-                    if let Some(shader) = shader {
-                        render_pass.set_pipeline(shader);
+                    // uniform
+                    match get_pipeline_uniform_slot(i, objects).zip(self.uniform_pool.bind_group())
+                    {
+                        Some((slot, bind_group)) => {
+                            render_pass.set_bind_group(
+                                2,
+                                bind_group,
+                                &self.uniform_pool.offsets(slot),
+                            );
+                        }
+                        None => {
+                            if let Some(Some(uniform)) = uniform {
+                                render_pass.set_bind_group(2, uniform, &[]);
+                            }
+                        }
                     }
-                    // texture
-                    if let Some(texture) = texture {
-                        render_pass.set_bind_group(0, texture, &[]);
+
+                    let sub_meshes = i.active_sub_meshes();
+                    if sub_meshes.is_empty() {
+                        // shader
+                        if let Some(shader) = shader {
+                            render_pass.set_pipeline(shader);
+                        }
+                        // texture
+                        if let Some(texture) = texture {
+                            render_pass.set_bind_group(0, texture, &[]);
+                        }
+                        if length > 0 {
+                            render_pass.draw_indexed(
+                                first_index..first_index + length,
+                                base_vertex,
+                                0..i.instances.len() as _,
+                            );
+                            draw_calls += 1;
+                        }
                     }
-                    // uniform
-                    if let Some(Some(uniform)) = uniform {
-                        render_pass.set_bind_group(2, uniform, &[]);
+
+                    for sub_mesh in sub_meshes {
+                        let material = sub_mesh.material.and_then(|j| i.materials.get(j));
+
+                        // shader
+                        if let Some(shader) = material.and_then(|j| j.shader.as_ref()).or(shader) {
+                            render_pass.set_pipeline(shader);
+                        }
+                        // texture
+                        if let Some(texture) = material.and_then(|j| j.texture.as_ref()).or(texture)
+                        {
+                            render_pass.set_bind_group(0, texture, &[]);
+                        }
+
+                        // ranges outside of the index buffer are clipped
+                        let start = sub_mesh.indices.start.min(vertex_buffer.length);
+                        let end = sub_mesh.indices.end.min(vertex_buffer.length);
+                        if start < end {
+                            render_pass.draw_indexed(
+                                first_index + start..first_index + end,
+                                base_vertex,
+                                0..i.instances.len() as _,
+                            );
+                            draw_calls += 1;
+                        }
                     }
-                    render_pass.draw_indexed(0..vertex_buffer.length, 0, 0..i.instances.len() as _);
                 }
             }
+            if self.debug_groups {
+                render_pass.pop_debug_group();
+            }
+        }
+        if layer.is_some() {
+            render_pass.pop_debug_group();
         }
         drop(render_pass);
+        self.draw_list = draw_list;
 
-        Ok(Some((encoder, view, frame)))
+        draw_calls
+    }
+
+    /// Fills the draw list with the objects and batches that pass the filter, in descending
+    /// render order. The batched objects are drawn by their batch instead, and the objects the
+    /// spatial index finds outside the view of their camera are left out
+    pub(crate) fn prepare_draw_list(
+        &mut self,
+        objects: &ObjectStorage,
+        camera: &CameraContainer,
+        filter: &impl Fn(&crate::Object) -> bool,
+    ) {
+        self.draw_list.clear();
+        self.in_view.clear();
+        let spatial_index = objects.spatial_index();
+        if let Some(index) = spatial_index {
+            for (camera_name, camera_value) in camera.iter() {
+                index.visit_frustum(camera_value.view_data, &mut self.culling_stack, |name| {
+                    if let Some(object) = objects.get(name.as_ref())
+                        && object.camera_effect.as_deref().unwrap_or("main") == camera_name.as_ref()
+                        && let Some(id) = object.id()
+                    {
+                        self.in_view.insert(id);
+                    }
+                });
+            }
+        }
+        let in_view = |object: &crate::Object| match spatial_index {
+            Some(index)
+                if crate::utils::spatial_index::can_be_culled(object)
+                    && index.contains(object.name.as_ref()) =>
+            {
+                object.id().is_some_and(|id| self.in_view.contains(&id))
+            }
+            _ => true,
+        };
+
+        let items = objects
+            .values()
+            .filter(|i| filter(i) && !self.static_batches.is_batched(i) && in_view(i))
+            .filter_map(|i| Some((i.render_order, DrawItem::Object(i.id()?))))
+            .chain(
+                self.static_batches
+                    .batches()
+                    .enumerate()
+                    .filter(|(_, i)| objects.get(i.first.as_ref()).is_some_and(filter))
+                    .map(|(index, i)| (i.render_order, DrawItem::Batch(index))),
+            );
+        for (sequence, (render_order, item)) in items.enumerate() {
+            self.draw_list.push((render_order, sequence as u32, item));
+        }
+        // the sequence keeps the items of a layer in order, without the buffer of a stable sort
+        self.draw_list
+            .sort_unstable_by_key(|(render_order, sequence, _)| {
+                (std::cmp::Reverse(*render_order), *sequence)
+            });
     }
 
     /// Render the scene.
-    pub(crate) fn render(&mut self, encoder: wgpu::CommandEncoder, frame: wgpu::SurfaceTexture) {
+    pub(crate) fn render(
+        &mut self,
+        mut encoder: wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        frame: FrameTexture,
+    ) {
+        if self.show_stats {
+            self.stats.draw_hud(&mut self.overlay);
+        }
+        self.overlay.draw(
+            &self.device,
+            &mut encoder,
+            view,
+            self.config.format,
+            self.size,
+            self.gpu_profiler.as_mut(),
+        );
+
+        let (scene_texture, scene_view) = match self.hdr_target.as_ref() {
+            Some(target) => (target.texture().clone(), target.view().clone()),
+            None => (frame.texture().clone(), view.clone()),
+        };
+        self.run_frame_graph(
+            None,
+            &mut encoder,
+            (&scene_texture, &scene_view),
+            (frame.texture(), view),
+        );
+
+        if self.window_opacity < 1.0 {
+            self.apply_window_opacity(&mut encoder, view);
+        }
+
+        if let Some(gpu_profiler) = self.gpu_profiler.as_mut() {
+            gpu_profiler.resolve(&mut encoder);
+        }
+        self.frame_history
+            .record(&self.device, &mut encoder, frame.texture());
+        self.frame_recorder
+            .capture(&self.device, &mut encoder, frame.texture());
+
         // submit will accept anything that implements IntoIter
         self.queue.submit(std::iter::once(encoder.finish()));
-        frame.present();
+        if let FrameTexture::Surface(frame) = frame {
+            frame.present();
+        }
+
+        if let Some(gpu_profiler) = self.gpu_profiler.as_mut() {
+            gpu_profiler.after_submit();
+        }
+        self.occlusion_culling.after_submit();
+        self.frame_recorder.after_submit(&self.device);
+        self.frame_graph.after_submit(&self.device);
+    }
+
+    /// Multiplies the whole frame, including its alpha, by the window opacity
+    fn apply_window_opacity(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        let format = self.config.format;
+        if self.opacity_pipeline.as_ref().map(|i| i.0) != Some(format) {
+            let shader = self
+                .device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("Window Opacity Shader"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        crate::utils::default_resources::WINDOW_OPACITY_SHADER.into(),
+                    ),
+                });
+            // the frame is scaled by the blend constant, and the shader output is ignored
+            let blend_component = wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::Constant,
+                operation: wgpu::BlendOperation::Add,
+            };
+
+            let pipeline = self
+                .device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("Window Opacity Pipeline"),
+                    layout: None,
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: Some("vs_main"),
+                        buffers: &[],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: Some("fs_main"),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format,
+                            write_mask: wgpu::ColorWrites::ALL,
+                            blend: Some(wgpu::BlendState {
+                                color: blend_component,
+                                alpha: blend_component,
+                            }),
+                        })],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                    cache: None,
+                });
+            self.opacity_pipeline = Some((format, pipeline));
+        }
+        let Some((_, pipeline)) = self.opacity_pipeline.as_ref() else {
+            return;
+        };
+
+        let opacity = self.window_opacity.clamp(0.0, 1.0) as f64;
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Window Opacity Render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_blend_constant(wgpu::Color {
+            r: opacity,
+            g: opacity,
+            b: opacity,
+            a: opacity,
+        });
+        render_pass.draw(0..3, 0..1);
     }
 
-    /// Sets the background color
-    pub fn set_clear_color(&mut self, r: f64, g: f64, b: f64, a: f64) {
-        self.clear_color = wgpu::Color { r, g, b, a }
+    /// The immediate 2D painter, drawn on top of the scene in pixels from the top left of
+    /// the window. Everything painted is batched into a single draw and cleared after each frame
+    pub fn painter(&mut self) -> &mut crate::utils::overlay::Overlay {
+        &mut self.overlay
+    }
+
+    /// Changes how frames are presented to the window, without restarting.
+    ///
+    /// Returns an error if the window does not support the present mode.
+    pub fn set_present_mode(
+        &mut self,
+        present_mode: wgpu::PresentMode,
+    ) -> Result<(), crate::error::Error> {
+        if let Some(surface) = self.surface.as_ref() {
+            // the auto modes always fall back to a supported mode
+            let is_auto = matches!(
+                present_mode,
+                wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync
+            );
+            if !is_auto
+                && !surface
+                    .get_capabilities(&self.adapter)
+                    .present_modes
+                    .contains(&present_mode)
+            {
+                return Err(crate::error::Error::Custom(format!(
+                    "The present mode {present_mode:?} is not supported by the window"
+                )));
+            }
+
+            self.config.present_mode = present_mode;
+            surface.configure(&self.device, &self.config);
+            if let Some(report) = self.surface_report.as_mut() {
+                report.present_mode = present_mode;
+            }
+        } else {
+            self.config.present_mode = present_mode;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the present mode the window is configured with
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.config.present_mode
+    }
+
+    /// Turns vsync on or off, choosing a supported present mode automatically
+    pub fn set_vsync(&mut self, vsync: bool) {
+        let present_mode = if vsync {
+            wgpu::PresentMode::AutoVsync
+        } else {
+            wgpu::PresentMode::AutoNoVsync
+        };
+        // auto modes are always supported
+        let _ = self.set_present_mode(present_mode);
+    }
+
+    /// Shows or hides the infinite ground grid, it can be configured through [Renderer::grid]
+    pub fn set_show_grid(&mut self, show_grid: bool) {
+        self.grid.enabled = show_grid;
+    }
+
+    /// Sets if the performance HUD should be shown
+    pub fn set_show_stats(&mut self, show_stats: bool) {
+        self.show_stats = show_stats;
+    }
+
+    /// Starts measuring the GPU time of each pass using timestamp queries.
+    ///
+    /// The device must be created with [`wgpu::Features::TIMESTAMP_QUERY`], which can be
+    /// requested through [`crate::WindowDescriptor::features`]. The results are available
+    /// through [`Renderer::gpu_timings`] and are shown in the performance HUD.
+    pub fn enable_gpu_profiling(&mut self) -> Result<(), crate::error::Error> {
+        if !self
+            .device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+        {
+            return Err(crate::error::Error::MissingFeatures(
+                wgpu::Features::TIMESTAMP_QUERY,
+            ));
+        }
+
+        if self.gpu_profiler.is_none() {
+            self.gpu_profiler = Some(crate::utils::gpu_profiler::GpuProfiler::new(
+                &self.device,
+                &self.queue,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Stops measuring the GPU time of the passes
+    pub fn disable_gpu_profiling(&mut self) {
+        self.gpu_profiler = None;
+        self.stats.gpu_timings.clear();
+    }
+
+    /// Returns the GPU time of each measured pass in milliseconds, from the latest available frame
+    pub fn gpu_timings(&self) -> &[(String, f32)] {
+        match self.gpu_profiler.as_ref() {
+            Some(gpu_profiler) => gpu_profiler.timings.as_slice(),
+            None => &[],
+        }
     }
 }
 
+/// An object or a batch of static objects, in the order they are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DrawItem {
+    Object(crate::ObjectId),
+    /// The index of the batch in [crate::utils::static_batching::StaticBatches]
+    Batch(usize),
+}
+
+/// Writes the label of a debug group into the reused string, instead of a new one every frame
+fn debug_label<'a>(label: &'a mut String, args: std::fmt::Arguments) -> &'a str {
+    use std::fmt::Write;
+
+    label.clear();
+    let _ = label.write_fmt(args);
+    label
+}
+
+/// A rectangle of the frame in pixels, as (x, y, width, height)
+type Rect = (u32, u32, u32, u32);
+
+/// The part of the frame covered by both rectangles
+fn intersect(a: Rect, b: Rect) -> Rect {
+    let left = a.0.max(b.0);
+    let top = a.1.max(b.1);
+    let right = (a.0 + a.2).min(b.0 + b.2);
+    let bottom = (a.1 + a.3).min(b.1 + b.3);
+
+    (
+        left,
+        top,
+        right.saturating_sub(left),
+        bottom.saturating_sub(top),
+    )
+}
+
+/// The viewport and scissor of an item: the viewport of its camera, and the scissor of the
+/// pass clipped to the viewport and to the scissor of the object
+pub(crate) fn item_clip(
+    camera: Option<&crate::Camera>,
+    scissor: Option<Rect>,
+    frame_size: winit::dpi::PhysicalSize<u32>,
+    pass_scissor: Rect,
+) -> (Rect, Rect) {
+    let viewport = match camera.and_then(|i| i.viewport) {
+        Some(viewport) => viewport.to_pixels(frame_size),
+        None => (0, 0, frame_size.width, frame_size.height),
+    };
+    let clipped = intersect(pass_scissor, viewport);
+
+    (viewport, scissor.map_or(clipped, |i| intersect(clipped, i)))
+}
+
+/// Sets the viewport and scissor on the pass if they are not already set. Returns false if
+/// nothing drawn with them would be seen
+pub(crate) fn set_clip(
+    render_pass: &mut wgpu::RenderPass,
+    clip: &mut (Rect, Rect),
+    next: (Rect, Rect),
+) -> bool {
+    let (viewport, scissor) = next;
+    if viewport.2 == 0 || viewport.3 == 0 || scissor.2 == 0 || scissor.3 == 0 {
+        return false;
+    }
+
+    if clip.0 != viewport {
+        render_pass.set_viewport(
+            viewport.0 as f32,
+            viewport.1 as f32,
+            viewport.2 as f32,
+            viewport.3 as f32,
+            0.0,
+            1.0,
+        );
+    }
+    if clip.1 != scissor {
+        render_pass.set_scissor_rect(scissor.0, scissor.1, scissor.2, scissor.3);
+    }
+    *clip = next;
+
+    true
+}
+
 // =========================== Extract Pipeline Data ===========================
 macro_rules! gen_pipeline {
     ($function_name:ident, $buffer_type:ty, $buffer_field:ident) => {
-        fn $function_name<'a>(
+        pub(crate) fn $function_name<'a>(
             data: &'a PipelineData<$buffer_type>,
             objects: &'a ObjectStorage,
         ) -> Option<&'a $buffer_type> {
@@ -390,6 +1516,19 @@ gen_pipeline!(
 gen_pipeline!(get_pipeline_shader, crate::Shaders, shader);
 gen_pipeline!(get_pipeline_texture, crate::Textures, texture);
 
+/// Get the slot of the uniform pool the object is drawn with, if any
+fn get_pipeline_uniform_slot<'a>(
+    object: &'a crate::Object,
+    objects: &'a ObjectStorage,
+) -> Option<&'a crate::utils::uniform_pool::UniformSlot> {
+    match &object.pipeline.uniform {
+        PipelineData::Copy(object_id) => objects
+            .get(object_id.as_str())
+            .and_then(|i| get_pipeline_uniform_slot(i, objects)),
+        PipelineData::Data(_) => object.uniform_slot.as_ref(),
+    }
+}
+
 /// Get the pipeline uniform_buffer.
 fn get_pipeline_uniform_buffer<'a>(
     data: &'a PipelineData<Option<crate::UniformBuffers>>,
@@ -407,3 +1546,23 @@ fn get_pipeline_uniform_buffer<'a>(
         PipelineData::Data(data) => Some(data),
     }
 }
+
+/// A renderer without a window for the tests, or `None` where there is no GPU adapter to test
+/// with, such as on most CI machines
+#[cfg(all(test, not(target_arch = "wasm32")))]
+pub(crate) fn test_renderer() -> Option<Renderer> {
+    let settings = crate::WindowDescriptor {
+        width: 64,
+        height: 64,
+        ..Default::default()
+    };
+    let size = winit::dpi::PhysicalSize::new(settings.width, settings.height);
+
+    match pollster::block_on(Renderer::new(size, settings)) {
+        Ok(renderer) => Some(renderer),
+        Err(e) => {
+            eprintln!("Skipped, no renderer could be made: {e}");
+            None
+        }
+    }
+}
@@ -0,0 +1,82 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{Renderer, WindowDescriptor, error::Error};
+
+/// The GPUs the engine can render with on the backends, such as to let the player pick one or
+/// to find the name to give to [WindowDescriptor::adapter_name]. Software adapters are listed
+/// as [wgpu::DeviceType::Cpu]. Always empty on the web, where the browser picks the GPU
+pub fn enumerate_adapters(backends: crate::Backends) -> Vec<wgpu::AdapterInfo> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    })
+    .enumerate_adapters(backends)
+    .iter()
+    .map(|i| i.get_info())
+    .collect();
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = backends;
+        Vec::new()
+    }
+}
+
+/// Picks the adapter of the settings. The first adapter whose name has the
+/// [WindowDescriptor::adapter_name] in it is used, otherwise the one that best fits the
+/// [WindowDescriptor::power_preference].
+///
+/// The `WGPU_ADAPTER_NAME` and `WGPU_POWER_PREF` environment variables replace the settings
+/// when set, so a driver issue can be looked into without rebuilding the game
+pub(crate) async fn request_adapter(
+    instance: &wgpu::Instance,
+    settings: &WindowDescriptor,
+    compatible_surface: Option<&wgpu::Surface<'_>>,
+) -> Result<wgpu::Adapter, Error> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(adapter_name) = std::env::var("WGPU_ADAPTER_NAME")
+        .ok()
+        .or(settings.adapter_name.map(String::from))
+    {
+        let mut adapters = instance.enumerate_adapters(instance_backends(settings));
+        let wanted = adapter_name.to_lowercase();
+        return match adapters.iter().position(|i| {
+            i.get_info().name.to_lowercase().contains(&wanted)
+                && compatible_surface.is_none_or(|surface| i.is_surface_supported(surface))
+        }) {
+            Some(index) => Ok(adapters.swap_remove(index)),
+            None => Err(Error::AdapterNameNotFound(
+                adapter_name,
+                adapters.iter().map(|i| i.get_info().name).collect(),
+            )),
+        };
+    }
+
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::from_env()
+                .unwrap_or(settings.power_preference),
+            compatible_surface,
+            force_fallback_adapter: false,
+        })
+        .await
+        .ok_or(Error::AdapterNotFound)
+}
+
+/// The backends of the settings, or of the `WGPU_BACKEND` environment variable when set, such
+/// as `vulkan`, `dx12`, `metal`, or `gl`
+pub(crate) fn instance_backends(settings: &WindowDescriptor) -> crate::Backends {
+    settings.backends.with_env()
+}
+
+impl Renderer {
+    /// The name, backend, and type of the GPU the engine renders with
+    pub fn adapter_info(&self) -> wgpu::AdapterInfo {
+        self.adapter.get_info()
+    }
+}
@@ -0,0 +1,330 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+/// The parts of the frame the allocations are counted for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Subsystem {
+    /// Building the queued objects, and uploading the loaded and streamed assets
+    Assets,
+    /// Acquiring the frame and recording the object draws
+    PreRender,
+    /// The update loop, the game states, the scheduler, the tasks and the signals
+    Update,
+    /// Rebuilding the changed objects and cameras
+    ObjectUpdate,
+    /// Submitting and presenting the frame
+    Render,
+    /// Anything outside of the frame, such as the background threads and the event loop
+    Other,
+}
+
+impl Subsystem {
+    /// All the subsystems, in the order of the frame
+    pub const ALL: [Subsystem; 6] = [
+        Subsystem::Assets,
+        Subsystem::PreRender,
+        Subsystem::Update,
+        Subsystem::ObjectUpdate,
+        Subsystem::Render,
+        Subsystem::Other,
+    ];
+
+    /// The name of the subsystem, for the reports
+    pub fn name(self) -> &'static str {
+        match self {
+            Subsystem::Assets => "assets",
+            Subsystem::PreRender => "pre render",
+            Subsystem::Update => "update",
+            Subsystem::ObjectUpdate => "object update",
+            Subsystem::Render => "render",
+            Subsystem::Other => "other",
+        }
+    }
+}
+
+/// What a subsystem made in a frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubsystemCounts {
+    /// Heap allocations, including the ones grown in place
+    pub allocations: u64,
+    /// Bytes asked for by the heap allocations
+    pub bytes: u64,
+    /// GPU buffers created
+    pub buffers: u64,
+    /// GPU bind groups created
+    pub bind_groups: u64,
+}
+
+impl SubsystemCounts {
+    /// Returns true if nothing was made
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The counts of the last frame, by subsystem
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocationReport {
+    counts: [SubsystemCounts; Subsystem::ALL.len()],
+}
+
+impl AllocationReport {
+    /// What the subsystem made in the frame
+    pub fn get(&self, subsystem: Subsystem) -> SubsystemCounts {
+        self.counts[subsystem as usize]
+    }
+
+    /// What all the subsystems of the frame made together, without [Subsystem::Other]
+    pub fn frame_total(&self) -> SubsystemCounts {
+        Subsystem::ALL
+            .iter()
+            .filter(|i| **i != Subsystem::Other)
+            .map(|i| self.get(*i))
+            .fold(SubsystemCounts::default(), |total, i| SubsystemCounts {
+                allocations: total.allocations + i.allocations,
+                bytes: total.bytes + i.bytes,
+                buffers: total.buffers + i.buffers,
+                bind_groups: total.bind_groups + i.bind_groups,
+            })
+    }
+
+    /// Returns true if the frame made no GPU resources, and no more heap allocations or bytes
+    /// than the previous frame. wgpu allocates for the commands it records every frame, so a
+    /// steady frame is one that does not grow over the last, rather than one that never allocates
+    pub fn is_steady(&self, previous: &AllocationReport) -> bool {
+        let total = self.frame_total();
+        let previous = previous.frame_total();
+
+        total.buffers == 0
+            && total.bind_groups == 0
+            && total.allocations <= previous.allocations
+            && total.bytes <= previous.bytes
+    }
+
+    /// Panics with both reports if the frame is not steady over the previous one, for tests that
+    /// the frames of a scene that does not change stay steady
+    #[track_caller]
+    pub fn assert_steady(&self, previous: &AllocationReport) {
+        assert!(
+            self.is_steady(previous),
+            "the frame allocated:\n{self}\nafter the previous frame allocated:\n{previous}"
+        );
+    }
+}
+impl std::fmt::Display for AllocationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for subsystem in Subsystem::ALL {
+            let counts = self.get(subsystem);
+            if counts.is_empty() {
+                continue;
+            }
+            writeln!(
+                f,
+                "{}: {} allocations ({} bytes), {} buffers, {} bind groups",
+                subsystem.name(),
+                counts.allocations,
+                counts.bytes,
+                counts.buffers,
+                counts.bind_groups
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "allocation_audit")]
+mod counters {
+    use super::{AllocationReport, Subsystem, SubsystemCounts};
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    const COUNT: usize = Subsystem::ALL.len();
+
+    pub(super) struct Counters {
+        allocations: [AtomicU64; COUNT],
+        bytes: [AtomicU64; COUNT],
+        buffers: [AtomicU64; COUNT],
+        bind_groups: [AtomicU64; COUNT],
+    }
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: AtomicU64 = AtomicU64::new(0);
+    pub(super) static COUNTERS: Counters = Counters {
+        allocations: [ZERO; COUNT],
+        bytes: [ZERO; COUNT],
+        buffers: [ZERO; COUNT],
+        bind_groups: [ZERO; COUNT],
+    };
+
+    thread_local! {
+        static CURRENT: Cell<Subsystem> = const { Cell::new(Subsystem::Other) };
+    }
+
+    fn current() -> usize {
+        // the thread local is gone while the thread is shutting down
+        CURRENT.try_with(|i| i.get()).unwrap_or(Subsystem::Other) as usize
+    }
+
+    pub(super) fn set(subsystem: Subsystem) {
+        CURRENT.with(|i| i.set(subsystem));
+    }
+
+    pub(super) fn allocation(size: usize) {
+        let index = current();
+        COUNTERS.allocations[index].fetch_add(1, Ordering::Relaxed);
+        COUNTERS.bytes[index].fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    pub(super) fn buffer() {
+        COUNTERS.buffers[current()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn bind_group() {
+        COUNTERS.bind_groups[current()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn take() -> AllocationReport {
+        let mut report = AllocationReport::default();
+        for (index, counts) in report.counts.iter_mut().enumerate() {
+            *counts = SubsystemCounts {
+                allocations: COUNTERS.allocations[index].swap(0, Ordering::Relaxed),
+                bytes: COUNTERS.bytes[index].swap(0, Ordering::Relaxed),
+                buffers: COUNTERS.buffers[index].swap(0, Ordering::Relaxed),
+                bind_groups: COUNTERS.bind_groups[index].swap(0, Ordering::Relaxed),
+            };
+        }
+
+        report
+    }
+}
+
+/// A global allocator that counts the heap allocations for the [AllocationReport]. It only
+/// counts with the `allocation_audit` feature, and passes everything to the system allocator.
+///
+/// The report of the last frame is kept in [crate::utils::performance::RenderStats], with the
+/// GPU buffers and bind groups made by the engine counted even without the allocator:
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: blue_engine::utils::allocation_audit::CountingAllocator =
+///     blue_engine::utils::allocation_audit::CountingAllocator;
+///
+/// // after a few frames to warm up
+/// engine.render_frame()?;
+/// let previous = engine.renderer.stats.allocations;
+/// engine.render_frame()?;
+/// engine.renderer.stats.allocations.assert_steady(&previous);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CountingAllocator;
+
+unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+        #[cfg(feature = "allocation_audit")]
+        counters::allocation(layout.size());
+        unsafe { std::alloc::System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: std::alloc::Layout) -> *mut u8 {
+        #[cfg(feature = "allocation_audit")]
+        counters::allocation(layout.size());
+        unsafe { std::alloc::System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+        unsafe { std::alloc::System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: std::alloc::Layout, new_size: usize) -> *mut u8 {
+        #[cfg(feature = "allocation_audit")]
+        counters::allocation(new_size);
+        unsafe { std::alloc::System.realloc(ptr, layout, new_size) }
+    }
+}
+
+/// Counts what is made on this thread from now on under the subsystem
+#[allow(unused_variables)]
+pub(crate) fn enter(subsystem: Subsystem) {
+    #[cfg(feature = "allocation_audit")]
+    counters::set(subsystem);
+}
+
+/// Counts the creation of a GPU buffer under the current subsystem
+pub(crate) fn count_buffer() {
+    #[cfg(feature = "allocation_audit")]
+    counters::buffer();
+}
+
+/// Counts the creation of a GPU bind group under the current subsystem
+pub(crate) fn count_bind_group() {
+    #[cfg(feature = "allocation_audit")]
+    counters::bind_group();
+}
+
+/// Takes the counts since the last time, and starts counting the next frame
+pub(crate) fn take_report() -> AllocationReport {
+    #[cfg(feature = "allocation_audit")]
+    return counters::take();
+
+    #[cfg(not(feature = "allocation_audit"))]
+    AllocationReport::default()
+}
+
+#[cfg(all(test, feature = "allocation_audit"))]
+mod tests {
+    use super::*;
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    // the counters are shared by the whole process, so the frames and the draw list are checked
+    // in the same test instead of ones that could run at the same time
+    #[test]
+    fn frames_of_a_still_scene_are_steady() {
+        let Ok(mut engine) = crate::Engine::new_headless(crate::WindowDescriptor {
+            width: 64,
+            height: 64,
+            ..Default::default()
+        }) else {
+            return;
+        };
+        for index in 0..8 {
+            crate::prelude::primitive_shapes::triangle(
+                format!("triangle {index}"),
+                crate::ObjectSettings::default(),
+                &mut engine.renderer,
+                &mut engine.objects,
+            )
+            .unwrap();
+        }
+        // the passes walk the index for the objects in view
+        engine.objects.set_spatial_index(true);
+
+        // the first frames build the objects and the pipelines
+        for _ in 0..3 {
+            engine.render_frame().unwrap();
+        }
+        let mut previous = engine.renderer.stats.allocations;
+        for _ in 0..5 {
+            engine.render_frame().unwrap();
+            let report = engine.renderer.stats.allocations;
+            report.assert_steady(&previous);
+            previous = report;
+        }
+
+        // the draw list is the part of recording the frame that does not go through wgpu
+        enter(Subsystem::PreRender);
+        take_report();
+        engine
+            .renderer
+            .prepare_draw_list(&engine.objects, &engine.camera, &|_: &crate::Object| true);
+        let report = take_report();
+        enter(Subsystem::Other);
+        assert_eq!(engine.renderer.draw_list.len(), 8);
+        assert!(report.get(Subsystem::PreRender).is_empty(), "{report}");
+    }
+}
@@ -0,0 +1,424 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::{StringBuffer, error::Error};
+
+/// The first bytes of an asset pack
+const PACK_MAGIC: &[u8; 4] = b"BEPK";
+/// The version of the pack format
+const PACK_VERSION: u32 = 1;
+/// The entry is compressed with deflate
+const FLAG_COMPRESSED: u8 = 1;
+/// The size of the header of the pack, before the index
+const HEADER_SIZE: usize = 12;
+/// The size of an entry of the index without its path
+const ENTRY_SIZE: usize = 2 + 8 * 3 + 1;
+
+/// Where the data of a file is in the pack
+#[derive(Debug, Clone, Copy)]
+struct PackEntry {
+    offset: usize,
+    stored_size: usize,
+    size: usize,
+    compressed: bool,
+}
+
+/// Turns the path into the form it is kept as in the pack, with `/` between the parts
+pub(crate) fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut path = path.as_str();
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest;
+    }
+
+    path.trim_start_matches('/').to_string()
+}
+
+/// Collects the files of an [AssetPack] and writes them into a single archive:
+///
+/// ```ignore
+/// let mut pack = AssetPackBuilder::new();
+/// pack.add_file("textures/grass.png", "assets/textures/grass.png")?;
+/// pack.add_file_compressed("shaders/water.wgsl", "assets/shaders/water.wgsl")?;
+/// pack.write("assets.pack")?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AssetPackBuilder {
+    files: Vec<(String, Vec<u8>, bool)>,
+}
+
+impl AssetPackBuilder {
+    /// Creates a new empty pack
+    pub fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+
+    /// Adds the data as a file of the pack, replacing the file if it was already added
+    pub fn add(&mut self, path: impl StringBuffer, data: Vec<u8>) -> &mut Self {
+        self.insert(path, data, false)
+    }
+
+    /// Adds the data compressed with deflate. Best for text such as shaders and models,
+    /// as images like PNG and JPEG are already compressed
+    pub fn add_compressed(&mut self, path: impl StringBuffer, data: Vec<u8>) -> &mut Self {
+        self.insert(path, data, true)
+    }
+
+    /// Adds a file from the disk under the given path of the pack
+    pub fn add_file(
+        &mut self,
+        path: impl StringBuffer,
+        source: impl AsRef<std::path::Path>,
+    ) -> Result<&mut Self, Error> {
+        let data = std::fs::read(source)?;
+        Ok(self.add(path, data))
+    }
+
+    /// Adds a file from the disk compressed, see [AssetPackBuilder::add_compressed]
+    pub fn add_file_compressed(
+        &mut self,
+        path: impl StringBuffer,
+        source: impl AsRef<std::path::Path>,
+    ) -> Result<&mut Self, Error> {
+        let data = std::fs::read(source)?;
+        Ok(self.add_compressed(path, data))
+    }
+
+    fn insert(&mut self, path: impl StringBuffer, data: Vec<u8>, compressed: bool) -> &mut Self {
+        let path = normalize_path(path.as_str());
+        self.files.retain(|(i, _, _)| *i != path);
+        self.files.push((path, data, compressed));
+
+        self
+    }
+
+    /// The amount of files in the pack
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns true if there are no files in the pack
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Writes the pack into its binary form, with the index of the files first. Fails if a
+    /// path is longer than 65535 bytes
+    pub fn build(&self) -> Result<Vec<u8>, Error> {
+        if let Some((path, _, _)) = self.files.iter().find(|i| i.0.len() > u16::MAX as usize) {
+            return Err(Error::AssetPackError(format!(
+                "the path {}... is longer than {} bytes",
+                path.chars().take(32).collect::<String>(),
+                u16::MAX
+            )));
+        }
+
+        let stored = self
+            .files
+            .iter()
+            .map(|(_, data, compressed)| {
+                if *compressed {
+                    Cow::Owned(miniz_oxide::deflate::compress_to_vec(data, 6))
+                } else {
+                    Cow::Borrowed(data.as_slice())
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let index_size = self
+            .files
+            .iter()
+            .map(|(path, _, _)| ENTRY_SIZE + path.len())
+            .sum::<usize>();
+        let mut offset = (HEADER_SIZE + index_size) as u64;
+
+        let mut pack =
+            Vec::with_capacity(offset as usize + stored.iter().map(|i| i.len()).sum::<usize>());
+        pack.extend_from_slice(PACK_MAGIC);
+        pack.extend_from_slice(&PACK_VERSION.to_le_bytes());
+        pack.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+        for ((path, data, compressed), stored) in self.files.iter().zip(stored.iter()) {
+            pack.extend_from_slice(&(path.len() as u16).to_le_bytes());
+            pack.extend_from_slice(path.as_bytes());
+            pack.extend_from_slice(&offset.to_le_bytes());
+            pack.extend_from_slice(&(stored.len() as u64).to_le_bytes());
+            pack.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            pack.push(if *compressed { FLAG_COMPRESSED } else { 0 });
+            offset += stored.len() as u64;
+        }
+        for stored in stored.iter() {
+            pack.extend_from_slice(stored);
+        }
+
+        Ok(pack)
+    }
+
+    /// Writes the pack to a file on the disk
+    pub fn write(&self, path: impl AsRef<std::path::Path>) -> Result<(), Error> {
+        std::fs::write(path, self.build()?)?;
+        Ok(())
+    }
+}
+
+/// A single archive of asset files, with an index of their paths. It can be mounted on the
+/// [crate::utils::asset_server::AssetServer], which then loads the files of the pack in place
+/// of the ones on the disk. The pack can be embedded in the game with
+/// [AssetPack::from_static] and `include_bytes!`, or read from a file next to it.
+///
+/// The packs are made with [AssetPackBuilder]
+#[derive(Debug, Clone)]
+pub struct AssetPack {
+    data: Cow<'static, [u8]>,
+    entries: HashMap<String, PackEntry>,
+}
+
+impl AssetPack {
+    /// Reads a pack from its bytes
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, Error> {
+        Self::new(Cow::Owned(data))
+    }
+
+    /// Reads a pack embedded in the game, such as with `include_bytes!`, without copying it
+    pub fn from_static(data: &'static [u8]) -> Result<Self, Error> {
+        Self::new(Cow::Borrowed(data))
+    }
+
+    /// Reads a pack from a file on the disk
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    fn new(data: Cow<'static, [u8]>) -> Result<Self, Error> {
+        let error = |message: &str| Error::AssetPackError(message.to_string());
+        let read = |offset: usize, size: usize| {
+            data.get(offset..offset + size)
+                .ok_or_else(|| error("the index of the pack is cut short"))
+        };
+        let read_usize = |offset: usize| -> Result<usize, Error> {
+            let bytes = read(offset, 8)?;
+            usize::try_from(u64::from_le_bytes(bytes.try_into().unwrap_or_default()))
+                .map_err(|_| error("a file of the pack is too large"))
+        };
+
+        if read(0, 4)? != PACK_MAGIC {
+            return Err(error("the data is not an asset pack"));
+        }
+        let version = u32::from_le_bytes(read(4, 4)?.try_into().unwrap_or_default());
+        if version != PACK_VERSION {
+            return Err(error("the version of the pack is not supported"));
+        }
+        let count = u32::from_le_bytes(read(8, 4)?.try_into().unwrap_or_default());
+
+        // the count is not trusted for the capacity further than the entries that fit
+        let fit = (data.len() - HEADER_SIZE) / ENTRY_SIZE;
+        let mut entries = HashMap::with_capacity((count as usize).min(fit));
+        let mut cursor = HEADER_SIZE;
+        for _ in 0..count {
+            let path_size = u16::from_le_bytes(read(cursor, 2)?.try_into().unwrap_or_default());
+            cursor += 2;
+            let path = std::str::from_utf8(read(cursor, path_size as usize)?)
+                .map_err(|_| error("a path of the pack is not valid text"))?
+                .to_string();
+            cursor += path_size as usize;
+
+            let entry = PackEntry {
+                offset: read_usize(cursor)?,
+                stored_size: read_usize(cursor + 8)?,
+                size: read_usize(cursor + 16)?,
+                compressed: read(cursor + 24, 1)?[0] & FLAG_COMPRESSED != 0,
+            };
+            cursor += 25;
+
+            if entry.offset.saturating_add(entry.stored_size) > data.len() {
+                return Err(error("a file of the pack is cut short"));
+            }
+            entries.insert(path, entry);
+        }
+
+        Ok(Self { data, entries })
+    }
+
+    /// Returns true if the pack has the file
+    pub fn contains(&self, path: impl StringBuffer) -> bool {
+        self.entries.contains_key(&normalize_path(path.as_str()))
+    }
+
+    /// The paths of all the files in the pack
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|i| i.as_str())
+    }
+
+    /// The amount of files in the pack
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if there are no files in the pack
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Reads a file of the pack, decompressing it if needed
+    pub fn read(&self, path: impl StringBuffer) -> Result<Cow<'_, [u8]>, Error> {
+        let entry = self
+            .entries
+            .get(&normalize_path(path.as_str()))
+            .ok_or_else(|| {
+                Error::AssetPackError(format!("{} is not in the pack", path.as_str()))
+            })?;
+        let stored = &self.data[entry.offset..entry.offset + entry.stored_size];
+        let wrong_size = || {
+            Error::AssetPackError(format!(
+                "{} does not have the size the pack gives for it",
+                path.as_str()
+            ))
+        };
+
+        if entry.compressed {
+            let data = miniz_oxide::inflate::decompress_to_vec_with_limit(stored, entry.size)
+                .map_err(|e| {
+                    Error::AssetPackError(format!(
+                        "{} could not be decompressed: {e}",
+                        path.as_str()
+                    ))
+                })?;
+            match data.len() == entry.size {
+                true => Ok(Cow::Owned(data)),
+                false => Err(wrong_size()),
+            }
+        } else {
+            match stored.len() == entry.size {
+                true => Ok(Cow::Borrowed(stored)),
+                false => Err(wrong_size()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack() -> AssetPackBuilder {
+        let mut pack = AssetPackBuilder::new();
+        pack.add("textures/grass.png", vec![1, 2, 3, 4])
+            .add_compressed("shaders/water.wgsl", b"fn main() {}\n".repeat(40))
+            .add("./empty.bin", Vec::new());
+
+        pack
+    }
+
+    #[test]
+    fn round_trip() {
+        let builder = pack();
+        let data = builder.build().unwrap();
+        let pack = AssetPack::from_bytes(data).unwrap();
+
+        assert_eq!(pack.len(), 3);
+        assert!(pack.contains("textures/grass.png"));
+        assert!(pack.contains("textures\\grass.png"));
+        assert!(pack.contains("/empty.bin"));
+        assert!(!pack.contains("textures"));
+        let mut paths: Vec<_> = pack.paths().collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            ["empty.bin", "shaders/water.wgsl", "textures/grass.png"]
+        );
+
+        assert_eq!(*pack.read("textures/grass.png").unwrap(), [1, 2, 3, 4]);
+        assert!(pack.read("empty.bin").unwrap().is_empty());
+        assert!(pack.read("missing.png").is_err());
+    }
+
+    #[test]
+    fn compressed_entries() {
+        let data = pack().build().unwrap();
+        let water = b"fn main() {}\n".repeat(40);
+        // the repeated text is stored in far less than its size
+        assert!(data.len() < water.len());
+
+        let pack = AssetPack::from_bytes(data).unwrap();
+        let read = pack.read("shaders/water.wgsl").unwrap();
+        assert!(matches!(read, Cow::Owned(_)));
+        assert_eq!(*read, *water);
+    }
+
+    #[test]
+    fn replacing_a_file() {
+        let mut builder = AssetPackBuilder::new();
+        builder.add("a.txt", vec![1]).add("./a.txt", vec![2]);
+        assert_eq!(builder.len(), 1);
+
+        let pack = AssetPack::from_bytes(builder.build().unwrap()).unwrap();
+        assert_eq!(*pack.read("a.txt").unwrap(), [2]);
+    }
+
+    #[test]
+    fn long_paths_are_rejected() {
+        let mut builder = AssetPackBuilder::new();
+        builder.add("a".repeat(u16::MAX as usize), vec![1]);
+        assert!(builder.build().is_ok());
+
+        builder.add("b".repeat(u16::MAX as usize + 1), vec![1]);
+        assert!(builder.build().is_err());
+    }
+
+    #[test]
+    fn truncated_packs() {
+        let data = pack().build().unwrap();
+        for length in 0..data.len() {
+            let Ok(pack) = AssetPack::from_bytes(data[..length].to_vec()) else {
+                continue;
+            };
+            // every file is checked to be inside the data, so any cut fails to open
+            panic!(
+                "a pack cut to {length} bytes opened with {} files",
+                pack.len()
+            );
+        }
+    }
+
+    #[test]
+    fn corrupt_packs() {
+        let data = pack().build().unwrap();
+        let corrupt = |offset: usize, bytes: &[u8]| {
+            let mut data = data.clone();
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            data
+        };
+
+        assert!(AssetPack::from_bytes(corrupt(0, b"NOPE")).is_err());
+        assert!(AssetPack::from_bytes(corrupt(4, &2u32.to_le_bytes())).is_err());
+        // a huge count fails on the missing entries, without reserving room for them
+        assert!(AssetPack::from_bytes(corrupt(8, &u32::MAX.to_le_bytes())).is_err());
+        // a path that is not valid text
+        assert!(AssetPack::from_bytes(corrupt(14, &[0xFF])).is_err());
+
+        // the offset of the first file, past the end of the data
+        let first = HEADER_SIZE + 2 + "textures/grass.png".len();
+        assert!(AssetPack::from_bytes(corrupt(first, &u64::MAX.to_le_bytes())).is_err());
+        assert!(AssetPack::from_bytes(corrupt(first + 8, &u64::MAX.to_le_bytes())).is_err());
+
+        // a size that does not match what is stored
+        let pack = AssetPack::from_bytes(corrupt(first + 16, &5u64.to_le_bytes())).unwrap();
+        assert!(pack.read("textures/grass.png").is_err());
+        assert!(pack.read("empty.bin").is_ok());
+
+        // compressed data that is not deflate, or longer than its size
+        let second = first + ENTRY_SIZE + "shaders/water.wgsl".len();
+        let offset = u64::from_le_bytes(data[second..second + 8].try_into().unwrap()) as usize;
+        let pack = AssetPack::from_bytes(corrupt(offset, &[0xFF; 4])).unwrap();
+        assert!(pack.read("shaders/water.wgsl").is_err());
+        let pack = AssetPack::from_bytes(corrupt(second + 16, &10u64.to_le_bytes())).unwrap();
+        assert!(pack.read("shaders/water.wgsl").is_err());
+        let pack = AssetPack::from_bytes(corrupt(second + 16, &10_000u64.to_le_bytes())).unwrap();
+        assert!(pack.read("shaders/water.wgsl").is_err());
+    }
+}
@@ -0,0 +1,595 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    Mesh, ObjectStorage, Renderer, SamplerSettings, StringBuffer, TextureData, Textures,
+    UnsignedIntType, Vertex,
+    asset_pack::{AssetPack, normalize_path},
+    compressed_textures::{CompressedTexture, read_compressed},
+    error::Error,
+};
+
+/// Registers files embedded in the binary with the [AssetServer], under the paths they are
+/// loaded with. The files are read with `include_bytes!`, relative to the file the macro is
+/// used in, so the game can ship as a single binary or on the web without a file system:
+///
+/// ```ignore
+/// embed_assets!(engine.renderer.assets, {
+///     "textures/grass.png" => "../assets/textures/grass.png",
+///     "shaders/water.wgsl" => "../assets/shaders/water.wgsl",
+/// });
+///
+/// // loaded from the binary instead of the disk
+/// engine.renderer.assets.load_shader("shaders/water.wgsl");
+/// ```
+#[macro_export]
+macro_rules! embed_assets {
+    ($assets:expr, { $($path:literal => $file:literal),* $(,)? }) => {{
+        let assets: &mut $crate::utils::asset_server::AssetServer = &mut $assets;
+        $(assets.embed($path, include_bytes!($file));)*
+    }};
+}
+
+/// Where the files are looked for before the disk
+#[derive(Debug, Clone, Default)]
+struct AssetSources {
+    /// The mounted packs, the last one first when looking for a file
+    packs: Vec<Arc<AssetPack>>,
+    /// The files embedded in the binary, by their path
+    embedded: HashMap<String, &'static [u8]>,
+}
+
+impl AssetSources {
+    /// Reads the file from the last mounted pack that has it, or from the embedded files
+    fn read(&self, path: &str) -> Option<Result<Vec<u8>, Error>> {
+        if let Some(pack) = self.packs.iter().rev().find(|i| i.contains(path)) {
+            return Some(pack.read(path).map(|i| i.into_owned()));
+        }
+
+        self.embedded
+            .get(&normalize_path(path))
+            .map(|i| Ok(i.to_vec()))
+    }
+
+    /// Returns true if a mounted pack or the embedded files have the file
+    #[cfg(target_arch = "wasm32")]
+    fn contains(&self, path: &str) -> bool {
+        self.packs.iter().any(|i| i.contains(path))
+            || self.embedded.contains_key(&normalize_path(path))
+    }
+}
+
+/// A handle to an asset loaded by the [AssetServer]. The handle can be kept and copied right
+/// away, and the asset behind it can be used once it is loaded
+pub struct Handle<T> {
+    id: u64,
+    _marker: std::marker::PhantomData<fn() -> T>,
+}
+impl<T> Handle<T> {
+    fn new(id: u64) -> Self {
+        Self {
+            id,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// The id of the asset in the server
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for Handle<T> {}
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl<T> Eq for Handle<T> {}
+impl<T> std::hash::Hash for Handle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Handle").field(&self.id).finish()
+    }
+}
+
+/// The state of an asset in the [AssetServer]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadState {
+    /// The asset is being read and decoded
+    Loading,
+    /// The asset is ready to be used
+    Loaded,
+    /// The asset failed to load, with the reason
+    Failed(String),
+}
+
+/// An asset as it comes out of the loading thread, before it is uploaded to the GPU
+enum LoadedAsset {
+    Texture(image::DynamicImage, SamplerSettings),
+    CompressedTexture(CompressedTexture, SamplerSettings),
+    Mesh(Vec<Vertex>, Vec<UnsignedIntType>),
+    Shader(String),
+}
+
+/// The work of loading an asset, ran away from the render thread
+type LoadTask = Box<dyn FnOnce() -> Result<LoadedAsset, String> + Send>;
+/// The assets that finished loading, shared with the loading threads
+type FinishedAssets = Arc<Mutex<Vec<(u64, Result<LoadedAsset, String>)>>>;
+
+/// What is set on an object once its asset is loaded
+#[derive(Debug, Clone, Copy)]
+enum AssetBinding {
+    Texture(u64),
+    Mesh(u64),
+    Shader(u64),
+}
+
+/// Loads textures, meshes, and shaders on background threads, so the frames keep going while
+/// the files are read and decoded. On the web, where there are no threads, the loading is
+/// spread over the next frames instead.
+///
+/// Each load returns a [Handle] right away. The objects can be told to use the asset once it
+/// is loaded, and keep what they have, such as the default texture, as a placeholder until
+/// then. The progress can be shown on a loading screen with [AssetServer::progress].
+///
+/// The files can also come from an [AssetPack] mounted with [AssetServer::mount], or be
+/// embedded in the binary with [crate::embed_assets], which are looked in before the disk.
+pub struct AssetServer {
+    next_id: u64,
+    /// The assets that finished loading, waiting to be uploaded to the GPU
+    finished: FinishedAssets,
+    /// The loads waiting for the next frame, on the web
+    #[cfg(target_arch = "wasm32")]
+    tasks: Vec<(u64, LoadTask)>,
+    states: HashMap<u64, LoadState>,
+    textures: HashMap<u64, Textures>,
+    meshes: HashMap<u64, Mesh>,
+    shaders: HashMap<u64, String>,
+    /// The objects waiting for their assets, by the name of the object
+    bindings: Vec<(String, AssetBinding)>,
+    /// The packs and embedded files, shared with the loads in progress
+    sources: Arc<AssetSources>,
+}
+
+impl AssetServer {
+    /// Creates a new empty asset server
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            finished: Arc::new(Mutex::new(Vec::new())),
+            #[cfg(target_arch = "wasm32")]
+            tasks: Vec::new(),
+            states: HashMap::new(),
+            textures: HashMap::new(),
+            meshes: HashMap::new(),
+            shaders: HashMap::new(),
+            bindings: Vec::new(),
+            sources: Arc::new(AssetSources::default()),
+        }
+    }
+
+    /// Mounts the pack, so the paths given to the loads are looked for in it before the disk.
+    /// The packs mounted later are looked in first, so they can replace the files of the
+    /// earlier ones, such as for patches and mods
+    pub fn mount(&mut self, pack: AssetPack) {
+        Arc::make_mut(&mut self.sources).packs.push(Arc::new(pack));
+    }
+
+    /// Removes all the mounted packs
+    pub fn unmount_all(&mut self) {
+        Arc::make_mut(&mut self.sources).packs.clear();
+    }
+
+    /// The amount of mounted packs
+    pub fn mounted_count(&self) -> usize {
+        self.sources.packs.len()
+    }
+
+    /// Registers a file embedded in the binary under the path it is loaded with. The mounted
+    /// packs are looked in before the embedded files, so they can replace them
+    pub fn embed(&mut self, path: impl StringBuffer, data: &'static [u8]) {
+        Arc::make_mut(&mut self.sources)
+            .embedded
+            .insert(normalize_path(path.as_str()), data);
+    }
+
+    /// Returns true if the file is embedded in the binary
+    pub fn is_embedded(&self, path: impl StringBuffer) -> bool {
+        self.sources
+            .embedded
+            .contains_key(&normalize_path(path.as_str()))
+    }
+
+    /// Reads a file right away from the mounted packs or the embedded files, or from the disk
+    /// if none of them have it
+    pub fn read(&self, path: impl AsRef<std::path::Path>) -> Result<Vec<u8>, Error> {
+        let path = path.as_ref();
+        match self.sources.read(&path.to_string_lossy()) {
+            Some(data) => data,
+            None => Ok(std::fs::read(path)?),
+        }
+    }
+
+    /// Starts loading a texture. On the web, the paths that are not in a mounted pack or the
+    /// embedded files are downloaded from the server of the page
+    pub fn load_texture(
+        &mut self,
+        texture_data: TextureData,
+        texture_mode: impl Into<SamplerSettings>,
+    ) -> Handle<Textures> {
+        let texture_mode: SamplerSettings = texture_mode.into();
+        #[cfg(target_arch = "wasm32")]
+        if let TextureData::Path(path) = &texture_data
+            && !self.sources.contains(path)
+        {
+            let path = path.clone();
+            return Handle::new(self.spawn_fetch(path, move |data| {
+                decode_texture(TextureData::Bytes(data), texture_mode)
+            }));
+        }
+
+        let sources = self.sources.clone();
+        Handle::new(self.spawn(Box::new(move || {
+            let texture_data = match texture_data {
+                TextureData::Path(path) => match sources.read(&path) {
+                    Some(data) => TextureData::Bytes(data.map_err(|e| format!("{path}: {e}"))?),
+                    None => TextureData::Path(path),
+                },
+                texture_data => texture_data,
+            };
+
+            decode_texture(texture_data, texture_mode)
+        })))
+    }
+
+    /// Starts loading a mesh from a file. The parser turns the content of the file into the
+    /// vertices and indices, such as for a model format
+    pub fn load_mesh(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        parser: impl FnOnce(&[u8]) -> Result<(Vec<Vertex>, Vec<UnsignedIntType>), Error>
+        + Send
+        + 'static,
+    ) -> Handle<Mesh> {
+        let path = path.as_ref().to_path_buf();
+        #[cfg(target_arch = "wasm32")]
+        if !self.sources.contains(&path.to_string_lossy()) {
+            let path = path.to_string_lossy().into_owned();
+            return Handle::new(self.spawn_fetch(path.clone(), move |data| {
+                let (vertices, indices) = parser(&data).map_err(|e| format!("{path}: {e}"))?;
+                Ok(LoadedAsset::Mesh(vertices, indices))
+            }));
+        }
+
+        let sources = self.sources.clone();
+        Handle::new(self.spawn(Box::new(move || {
+            let data = match sources.read(&path.to_string_lossy()) {
+                Some(data) => data.map_err(|e| e.to_string()),
+                None => std::fs::read(&path).map_err(|e| e.to_string()),
+            }
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+            let (vertices, indices) =
+                parser(&data).map_err(|e| format!("{}: {e}", path.display()))?;
+
+            Ok(LoadedAsset::Mesh(vertices, indices))
+        })))
+    }
+
+    /// Starts loading the source of a shader from a file
+    pub fn load_shader(&mut self, path: impl AsRef<std::path::Path>) -> Handle<String> {
+        let path = path.as_ref().to_path_buf();
+        #[cfg(target_arch = "wasm32")]
+        if !self.sources.contains(&path.to_string_lossy()) {
+            let path = path.to_string_lossy().into_owned();
+            return Handle::new(self.spawn_fetch(path.clone(), move |data| {
+                String::from_utf8(data)
+                    .map(LoadedAsset::Shader)
+                    .map_err(|_| format!("{path}: the shader is not valid text"))
+            }));
+        }
+
+        let sources = self.sources.clone();
+        Handle::new(self.spawn(Box::new(move || {
+            match sources.read(&path.to_string_lossy()) {
+                Some(data) => data.map_err(|e| e.to_string()).and_then(|data| {
+                    String::from_utf8(data).map_err(|_| "the shader is not valid text".to_string())
+                }),
+                None => std::fs::read_to_string(&path).map_err(|e| e.to_string()),
+            }
+            .map(LoadedAsset::Shader)
+            .map_err(|e| format!("{}: {e}", path.display()))
+        })))
+    }
+
+    /// Starts a new load, and returns its id
+    fn next_load(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.states.insert(id, LoadState::Loading);
+
+        id
+    }
+
+    fn spawn(&mut self, task: LoadTask) -> u64 {
+        let id = self.next_load();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let finished = self.finished.clone();
+            rayon::spawn(move || {
+                let result = task();
+                if let Ok(mut finished) = finished.lock() {
+                    finished.push((id, result));
+                }
+            });
+        }
+        #[cfg(target_arch = "wasm32")]
+        self.tasks.push((id, task));
+
+        id
+    }
+
+    /// Downloads the file with the fetch API of the browser, then turns it into the asset
+    #[cfg(target_arch = "wasm32")]
+    fn spawn_fetch(
+        &mut self,
+        path: String,
+        then: impl FnOnce(Vec<u8>) -> Result<LoadedAsset, String> + 'static,
+    ) -> u64 {
+        let id = self.next_load();
+        let finished = self.finished.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let result = crate::utils::web::fetch(&path)
+                .await
+                .map_err(|e| format!("{path}: {e}"))
+                .and_then(then);
+            if let Ok(mut finished) = finished.lock() {
+                finished.push((id, result));
+            }
+        });
+
+        id
+    }
+
+    /// The state of the asset
+    pub fn state<T>(&self, handle: Handle<T>) -> LoadState {
+        self.states
+            .get(&handle.id)
+            .cloned()
+            .unwrap_or_else(|| LoadState::Failed("The asset does not exist".to_string()))
+    }
+
+    /// Returns true if the asset is ready to be used
+    pub fn is_loaded<T>(&self, handle: Handle<T>) -> bool {
+        self.states.get(&handle.id) == Some(&LoadState::Loaded)
+    }
+
+    /// Returns the texture, if it is loaded
+    pub fn texture(&self, handle: Handle<Textures>) -> Option<&Textures> {
+        self.textures.get(&handle.id)
+    }
+
+    /// Returns the mesh, if it is loaded
+    pub fn mesh(&self, handle: Handle<Mesh>) -> Option<&Mesh> {
+        self.meshes.get(&handle.id)
+    }
+
+    /// Returns the source of the shader, if it is loaded
+    pub fn shader(&self, handle: Handle<String>) -> Option<&str> {
+        self.shaders.get(&handle.id).map(|i| i.as_str())
+    }
+
+    /// Sets the texture on the object once it is loaded. The object keeps its current
+    /// texture until then
+    pub fn set_texture_when_loaded(&mut self, object: impl StringBuffer, handle: Handle<Textures>) {
+        self.bindings
+            .push((object.as_string(), AssetBinding::Texture(handle.id)));
+    }
+
+    /// Sets the mesh on the object once it is loaded. The object keeps its current shape
+    /// until then
+    pub fn set_mesh_when_loaded(&mut self, object: impl StringBuffer, handle: Handle<Mesh>) {
+        self.bindings
+            .push((object.as_string(), AssetBinding::Mesh(handle.id)));
+    }
+
+    /// Sets the shader on the object once it is loaded. The object keeps its current shader
+    /// until then, and if the loaded one fails to compile
+    pub fn set_shader_when_loaded(&mut self, object: impl StringBuffer, handle: Handle<String>) {
+        self.bindings
+            .push((object.as_string(), AssetBinding::Shader(handle.id)));
+    }
+
+    /// The amount of assets that are still loading
+    pub fn loading_count(&self) -> usize {
+        self.states
+            .values()
+            .filter(|i| **i == LoadState::Loading)
+            .count()
+    }
+
+    /// The amount of assets that failed to load
+    pub fn failed_count(&self) -> usize {
+        self.states
+            .values()
+            .filter(|i| matches!(i, LoadState::Failed(_)))
+            .count()
+    }
+
+    /// The amount of assets that were requested in total
+    pub fn total_count(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Returns true if any asset is still loading
+    pub fn is_loading(&self) -> bool {
+        self.loading_count() > 0
+    }
+
+    /// How much of the requested assets are done loading, from 0 to 1. The failed ones count
+    /// as done, so a loading screen does not wait for them forever
+    pub fn progress(&self) -> f32 {
+        if self.states.is_empty() {
+            return 1.0;
+        }
+
+        1.0 - self.loading_count() as f32 / self.states.len() as f32
+    }
+
+    fn apply_binding(&self, object: &mut crate::Object, binding: AssetBinding) {
+        match binding {
+            AssetBinding::Texture(id) => {
+                if let Some(texture) = self.textures.get(&id) {
+                    object.set_texture_raw(texture.clone());
+                }
+            }
+            AssetBinding::Mesh(id) => {
+                if let Some(mesh) = self.meshes.get(&id) {
+                    object.set_mesh(mesh.clone());
+                    object.flag_as_changed(true);
+                }
+            }
+            AssetBinding::Shader(id) => {
+                if let Some(source) = self.shaders.get(&id)
+                    && let Err(e) = object.set_shader(source.clone())
+                {
+                    eprintln!("The loaded shader is not used by {}: {e}", object.name);
+                }
+            }
+        }
+    }
+}
+impl Default for AssetServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl std::fmt::Debug for AssetServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AssetServer")
+            .field("states", &self.states)
+            .field("bindings", &self.bindings)
+            .field("packs", &self.sources.packs.len())
+            .field("embedded", &self.sources.embedded.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Turns the data of the texture into an image, or a compressed texture for DDS and KTX2
+fn decode_texture(
+    texture_data: TextureData,
+    texture_mode: SamplerSettings,
+) -> Result<LoadedAsset, String> {
+    if let Some(compressed) = read_compressed(&texture_data) {
+        return compressed
+            .map(|i| LoadedAsset::CompressedTexture(i, texture_mode))
+            .map_err(|e| e.to_string());
+    }
+
+    let image = match texture_data {
+        TextureData::Bytes(data) => {
+            image::load_from_memory(data.as_slice()).map_err(|e| e.to_string())
+        }
+        TextureData::Image(data) => Ok(data),
+        TextureData::Path(path) => image::open(&path).map_err(|e| format!("{path}: {e}")),
+    };
+
+    image.map(|image| LoadedAsset::Texture(image, texture_mode))
+}
+
+impl Renderer {
+    /// Uploads the assets that finished loading, and sets them on the objects waiting for
+    /// them. This is ran at the start of every frame
+    pub fn update_assets(&mut self, objects: &mut ObjectStorage) {
+        #[cfg(target_arch = "wasm32")]
+        for (id, task) in std::mem::take(&mut self.assets.tasks) {
+            let result = task();
+            if let Ok(mut finished) = self.assets.finished.lock() {
+                finished.push((id, result));
+            }
+        }
+
+        let finished = match self.assets.finished.lock() {
+            Ok(mut finished) => std::mem::take(&mut *finished),
+            Err(_) => return,
+        };
+
+        for (id, result) in finished {
+            let state = match result {
+                Ok(LoadedAsset::Texture(image, texture_mode)) => {
+                    match self.build_texture(
+                        format!("Asset {id}"),
+                        TextureData::Image(image),
+                        texture_mode,
+                    ) {
+                        Ok(texture) => {
+                            self.assets.textures.insert(id, texture);
+                            LoadState::Loaded
+                        }
+                        Err(e) => LoadState::Failed(e.to_string()),
+                    }
+                }
+                Ok(LoadedAsset::CompressedTexture(compressed, texture_mode)) => {
+                    match self.build_compressed_texture(
+                        format!("Asset {id}"),
+                        &compressed,
+                        texture_mode,
+                    ) {
+                        Ok(texture) => {
+                            self.assets.textures.insert(id, texture);
+                            LoadState::Loaded
+                        }
+                        Err(e) => LoadState::Failed(e.to_string()),
+                    }
+                }
+                Ok(LoadedAsset::Mesh(vertices, indices)) => {
+                    let mesh = Mesh::new(vertices, indices, self);
+                    self.assets.meshes.insert(id, mesh);
+                    LoadState::Loaded
+                }
+                Ok(LoadedAsset::Shader(source)) => {
+                    self.assets.shaders.insert(id, source);
+                    LoadState::Loaded
+                }
+                Err(e) => LoadState::Failed(e),
+            };
+
+            if let LoadState::Failed(e) = &state {
+                eprintln!("Could not load the asset: {e}");
+            }
+            self.assets.states.insert(id, state);
+        }
+
+        // the bindings are kept until their asset is done, and dropped after
+        let bindings = std::mem::take(&mut self.assets.bindings);
+        for (name, binding) in bindings {
+            let id = match binding {
+                AssetBinding::Texture(id) | AssetBinding::Mesh(id) | AssetBinding::Shader(id) => id,
+            };
+            match self.assets.states.get(&id) {
+                Some(LoadState::Loading) => self.assets.bindings.push((name, binding)),
+                Some(LoadState::Loaded) => {
+                    if let Some(object) = objects.get_mut(&name) {
+                        self.assets.apply_binding(object, binding);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
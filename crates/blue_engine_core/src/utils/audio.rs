@@ -0,0 +1,967 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rodio::{ChannelCount, SampleRate, Source};
+
+use crate::{
+    ObjectId, ObjectStorage, Vector3,
+    error::Error,
+    utils::plugin::{EnginePlugin, PluginContext},
+};
+
+/// The samples of a sound, shared by all of its playbacks
+#[derive(Debug, Clone)]
+pub struct Sound {
+    /// The samples of all the channels, one after another for each frame
+    samples: Arc<[f32]>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl Sound {
+    /// Creates a sound of the samples, where the samples of the channels follow each other
+    /// for each frame
+    pub fn from_samples(samples: impl Into<Arc<[f32]>>, channels: u16, sample_rate: u32) -> Self {
+        Self {
+            samples: samples.into(),
+            channels: channels.max(1),
+            sample_rate: sample_rate.max(1),
+        }
+    }
+
+    /// Decodes a sound from the bytes of a WAV, Ogg Vorbis, MP3, or FLAC file
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Result<Self, Error> {
+        let decoder = rodio::Decoder::try_from(std::io::Cursor::new(bytes.into()))?;
+        let (channels, sample_rate) = (decoder.channels().get(), decoder.sample_rate().get());
+
+        Ok(Self::from_samples(
+            decoder.collect::<Vec<_>>(),
+            channels,
+            sample_rate,
+        ))
+    }
+
+    /// Decodes a sound from a WAV, Ogg Vorbis, MP3, or FLAC file
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    /// The amount of channels
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// The amount of frames in a second
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The amount of frames, each with a sample of every channel
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    /// The length of the sound in seconds
+    pub fn duration(&self) -> f32 {
+        self.frames() as f32 / self.sample_rate as f32
+    }
+}
+
+/// A sound being played
+#[derive(Debug)]
+struct Playback {
+    sound: Sound,
+    /// The frame of the sound that is played next, between the frames when the pitch or the
+    /// sample rate differ from the output
+    cursor: f64,
+    volume: f32,
+    pitch: f32,
+    looping: bool,
+    paused: bool,
+    /// Where the sound is played from, or None to be played as it is
+    position: Option<Vector3>,
+    /// The object whose position the sound is played from
+    follow: Option<ObjectId>,
+    /// The distance until which the sound is at full volume, and after which it gets no
+    /// quieter
+    distance: (f32, f32),
+    /// How blocked the sound is from the listener
+    muffle: Muffle,
+}
+
+/// Muffles the sounds that objects stand between and the listener, quieter and with their
+/// high frequencies cut, as if heard through a wall. See [Audio::set_occlusion]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Occlusion {
+    /// The volume of a blocked sound, 0.5 by default
+    pub gain: f32,
+    /// The frequency in hertz above which a blocked sound is cut, 1000 by default
+    pub cutoff: f32,
+    /// How long in seconds a sound takes to become blocked or clear, 0.1 by default, so it
+    /// does not jump as objects pass in front of it
+    pub fade: f32,
+}
+impl Default for Occlusion {
+    fn default() -> Self {
+        Self {
+            gain: 0.5,
+            cutoff: 1000.0,
+            fade: 0.1,
+        }
+    }
+}
+
+/// The frequency in hertz a sound is cut at while it is clear, above what is heard
+const OPEN_CUTOFF: f32 = 20000.0;
+
+/// How blocked a sound is, with the state of its low pass filter
+#[derive(Debug, Clone, Copy, Default)]
+struct Muffle {
+    /// Whether an object stands between the sound and the listener, checked every frame
+    occluded: bool,
+    /// How far the sound is muffled, from 0 when clear to 1 when blocked
+    amount: f32,
+    /// The last sample out of the filter
+    filtered: f32,
+}
+
+impl Occlusion {
+    /// Moves the muffle a sample towards being blocked or clear, and returns the sample
+    /// through its filter and gain
+    fn apply(&self, muffle: &mut Muffle, sample: f32, sample_rate: f32) -> f32 {
+        // the filter starts from the sound as it is, so it does not click when blocked
+        if muffle.amount <= 0.0 {
+            muffle.filtered = sample;
+        }
+        let target = if muffle.occluded { 1.0 } else { 0.0 };
+        let step = 1.0 / (self.fade.max(0.0) * sample_rate);
+        muffle.amount += (target - muffle.amount).clamp(-step, step);
+        if muffle.amount <= 0.0 {
+            return sample;
+        }
+
+        // the cutoff falls evenly in pitch from above hearing to the one of the occlusion
+        let cutoff =
+            OPEN_CUTOFF * (self.cutoff.clamp(1.0, OPEN_CUTOFF) / OPEN_CUTOFF).powf(muffle.amount);
+        let blend = 1.0 - (-std::f32::consts::TAU * cutoff / sample_rate).exp();
+        muffle.filtered += (sample - muffle.filtered) * blend;
+
+        muffle.filtered * (1.0 + (self.gain.max(0.0) - 1.0) * muffle.amount)
+    }
+}
+
+/// Where the sounds are heard from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Listener {
+    /// The position of the listener
+    pub position: Vector3,
+    /// The direction of the right ear
+    pub right: Vector3,
+}
+impl Default for Listener {
+    fn default() -> Self {
+        Self {
+            position: Vector3::ZERO,
+            right: Vector3::X,
+        }
+    }
+}
+
+/// The playbacks, shared between the engine and the thread of the audio device
+#[derive(Debug)]
+struct MixerState {
+    playbacks: HashMap<u64, Playback>,
+    next_id: u64,
+    volume: f32,
+    listener: Listener,
+    occlusion: Option<Occlusion>,
+}
+
+/// Locks the state, even if a thread panicked while holding it, as the playbacks stay usable
+fn lock(state: &Mutex<MixerState>) -> MutexGuard<'_, MixerState> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A sound that is played, to change or stop it while it plays. The handle can be dropped
+/// without stopping the sound
+#[derive(Debug, Clone)]
+pub struct SoundHandle {
+    id: u64,
+    state: Arc<Mutex<MixerState>>,
+}
+
+impl SoundHandle {
+    /// Changes the playback, if it is still playing
+    fn with(&self, change: impl FnOnce(&mut Playback)) {
+        if let Some(playback) = lock(&self.state).playbacks.get_mut(&self.id) {
+            change(playback);
+        }
+    }
+
+    /// Changes the volume, where 1.0 is the volume of the sound
+    pub fn set_volume(&self, volume: f32) {
+        self.with(|i| i.volume = volume.max(0.0));
+    }
+
+    /// Changes the speed of the playback, which raises the pitch above 1.0 and lowers it below
+    pub fn set_pitch(&self, pitch: f32) {
+        self.with(|i| i.pitch = pitch.max(0.0));
+    }
+
+    /// Should the sound start over once it ends
+    pub fn set_looping(&self, looping: bool) {
+        self.with(|i| i.looping = looping);
+    }
+
+    /// Pauses the sound where it is
+    pub fn pause(&self) {
+        self.with(|i| i.paused = true);
+    }
+
+    /// Continues the sound after it was paused
+    pub fn resume(&self) {
+        self.with(|i| i.paused = false);
+    }
+
+    /// Stops the sound, which can not be resumed
+    pub fn stop(&self) {
+        lock(&self.state).playbacks.remove(&self.id);
+    }
+
+    /// Returns true if the sound has not ended or been stopped, even while paused
+    pub fn is_playing(&self) -> bool {
+        lock(&self.state).playbacks.contains_key(&self.id)
+    }
+
+    /// Returns true if the sound is paused
+    pub fn is_paused(&self) -> bool {
+        lock(&self.state)
+            .playbacks
+            .get(&self.id)
+            .is_some_and(|i| i.paused)
+    }
+
+    /// Plays the sound from the position, panned and quieter with distance from the
+    /// listener, or as it is if None
+    pub fn set_position(&self, position: Option<Vector3>) {
+        self.with(|i| i.position = position);
+    }
+
+    /// Plays the sound from the position of the object, updated every frame, or stops
+    /// following if None
+    pub fn follow(&self, object: Option<ObjectId>) {
+        self.with(|i| i.follow = object);
+    }
+
+    /// Changes the distance until which the sound is at full volume, and the distance after
+    /// which it gets no quieter. Defaults to 1.0 and 100.0
+    pub fn set_distance(&self, min: f32, max: f32) {
+        self.with(|i| i.distance = (min.max(f32::EPSILON), max.max(min)));
+    }
+}
+
+/// Plays sounds through the default audio device, with their volume, pitch, looping, and
+/// position from a listener moved with a camera:
+///
+/// ```ignore
+/// let audio = Audio::new()?;
+/// let step = Sound::load("step.ogg")?;
+/// audio.play_on(&step, player_id).set_volume(0.5);
+/// engine.add_plugin("audio", audio);
+/// ```
+///
+/// As a plugin, the listener follows the camera and the sounds follow their objects. The
+/// device plays until the audio is dropped.
+#[derive(Debug)]
+pub struct Audio {
+    /// The camera the sounds are heard from
+    pub camera: Arc<str>,
+    state: Arc<Mutex<MixerState>>,
+    /// The output of the device, which asks the mixer for the samples from its own thread
+    device: Option<rodio::MixerDeviceSink>,
+}
+
+impl Audio {
+    /// Opens the default audio device with no sounds playing, heard from the main camera
+    pub fn new() -> Result<Self, Error> {
+        let mut device = rodio::DeviceSinkBuilder::open_default_sink()?;
+        // the device is closed with the audio, which is not worth a message
+        device.log_on_drop(false);
+
+        let mut audio = Self::silent();
+        let config = device.config();
+        device.mixer().add(MixerSource {
+            mixer: audio.mixer(),
+            channels: config.channel_count(),
+            sample_rate: config.sample_rate(),
+            block: vec![0.0; MIX_FRAMES * config.channel_count().get() as usize],
+            next: usize::MAX,
+        });
+        audio.device = Some(device);
+
+        Ok(audio)
+    }
+
+    /// Creates the audio without a device, such as for a server. The sounds can be played and
+    /// changed, but do not move on as nothing asks for their samples
+    pub fn silent() -> Self {
+        Self {
+            camera: "main".into(),
+            state: Arc::new(Mutex::new(MixerState {
+                playbacks: HashMap::new(),
+                next_id: 0,
+                volume: 1.0,
+                listener: Listener::default(),
+                occlusion: None,
+            })),
+            device: None,
+        }
+    }
+
+    /// Returns true if the sounds are heard through a device
+    pub fn has_device(&self) -> bool {
+        self.device.is_some()
+    }
+
+    /// The mixer of the sounds, given to the device
+    fn mixer(&self) -> AudioMixer {
+        AudioMixer {
+            state: self.state.clone(),
+        }
+    }
+
+    /// Plays the sound as it is, from the start
+    pub fn play(&self, sound: &Sound) -> SoundHandle {
+        let mut state = lock(&self.state);
+        let id = state.next_id;
+        state.next_id += 1;
+        state.playbacks.insert(
+            id,
+            Playback {
+                sound: sound.clone(),
+                cursor: 0.0,
+                volume: 1.0,
+                pitch: 1.0,
+                looping: false,
+                paused: false,
+                position: None,
+                follow: None,
+                distance: (1.0, 100.0),
+                muffle: Muffle::default(),
+            },
+        );
+
+        SoundHandle {
+            id,
+            state: self.state.clone(),
+        }
+    }
+
+    /// Plays the sound from the position
+    pub fn play_at(&self, sound: &Sound, position: impl Into<Vector3>) -> SoundHandle {
+        let handle = self.play(sound);
+        handle.set_position(Some(position.into()));
+        handle
+    }
+
+    /// Plays the sound from the position of the object, as it moves
+    pub fn play_on(&self, sound: &Sound, object: ObjectId) -> SoundHandle {
+        let handle = self.play(sound);
+        handle.follow(Some(object));
+        handle
+    }
+
+    /// Stops all the sounds
+    pub fn stop_all(&self) {
+        lock(&self.state).playbacks.clear();
+    }
+
+    /// The amount of sounds playing, including the paused ones
+    pub fn playing(&self) -> usize {
+        lock(&self.state).playbacks.len()
+    }
+
+    /// The volume of all the sounds
+    pub fn volume(&self) -> f32 {
+        lock(&self.state).volume
+    }
+
+    /// Changes the volume of all the sounds, where 1.0 is their own volume
+    pub fn set_volume(&self, volume: f32) {
+        lock(&self.state).volume = volume.max(0.0);
+    }
+
+    /// Where the sounds are heard from
+    pub fn listener(&self) -> Listener {
+        lock(&self.state).listener
+    }
+
+    /// Moves the listener, which is done from the camera every frame as a plugin
+    pub fn set_listener(&self, listener: Listener) {
+        lock(&self.state).listener = listener;
+    }
+
+    /// How the sounds blocked from the listener are muffled, if they are
+    pub fn occlusion(&self) -> Option<Occlusion> {
+        lock(&self.state).occlusion
+    }
+
+    /// Muffles the sounds with a position that objects stand between and the listener, or
+    /// stops if None. As a plugin, a ray is cast from the listener to every such sound each
+    /// frame through the boxes of the objects, which is faster with
+    /// [ObjectStorage::set_spatial_index]. The object a sound follows, the objects around
+    /// the listener or the sound, and the hidden objects do not block it
+    pub fn set_occlusion(&self, occlusion: Option<Occlusion>) {
+        let mut state = lock(&self.state);
+        state.occlusion = occlusion;
+        if occlusion.is_none() {
+            state
+                .playbacks
+                .values_mut()
+                .for_each(|i| i.muffle = Muffle::default());
+        }
+    }
+}
+impl EnginePlugin for Audio {
+    fn update(&mut self, ctx: &mut PluginContext) {
+        let mut state = lock(&self.state);
+        if let Some(camera) = ctx.camera.get(self.camera.as_ref()) {
+            let forward = (camera.target - camera.position).normalize_or_zero();
+            let right = forward.cross(camera.up).normalize_or_zero();
+            state.listener = Listener {
+                position: camera.position,
+                right: match right == Vector3::ZERO {
+                    true => Vector3::X,
+                    false => right,
+                },
+            };
+        }
+        let mut positioned = Vec::new();
+        for (id, playback) in state.playbacks.iter_mut() {
+            // the sound keeps the last position of its object once the object is removed
+            if let Some(object) = playback.follow.and_then(|i| ctx.objects.get_by_id(i)) {
+                playback.position = Some(object.position);
+            }
+            if let Some(position) = playback.position {
+                positioned.push((*id, position, playback.follow));
+            }
+        }
+        if state.occlusion.is_none() {
+            return;
+        }
+
+        // the rays are cast without holding the sounds, which the device waits on
+        let listener = state.listener.position;
+        drop(state);
+        let occluded = positioned
+            .into_iter()
+            .map(|(id, position, follow)| {
+                (id, is_occluded(ctx.objects, listener, position, follow))
+            })
+            .collect::<Vec<_>>();
+        let mut state = lock(&self.state);
+        for (id, occluded) in occluded {
+            if let Some(playback) = state.playbacks.get_mut(&id) {
+                playback.muffle.occluded = occluded;
+            }
+        }
+    }
+}
+
+/// Returns true if an object stands between the listener and the sound, other than the
+/// object the sound follows, the objects around either of them, and the hidden objects
+fn is_occluded(
+    objects: &ObjectStorage,
+    listener: Vector3,
+    sound: Vector3,
+    follow: Option<ObjectId>,
+) -> bool {
+    let followed = follow.and_then(|i| objects.name_of(i));
+    objects
+        .objects_on_ray(listener, sound - listener, 1.0)
+        .any(|(name, object, distance)| {
+            // the ray starts inside the objects around the listener, such as the player
+            distance > 0.0
+                && object.is_visible
+                && followed != Some(name.as_str())
+                && !object
+                    .instances_aabb()
+                    .is_some_and(|i| i.contains_point(sound))
+        })
+}
+
+/// Mixes the sounds of an [Audio] into the samples of the device, from its thread
+#[derive(Debug, Clone)]
+struct AudioMixer {
+    state: Arc<Mutex<MixerState>>,
+}
+
+impl AudioMixer {
+    /// Fills the output with the sounds, where the samples of the channels follow each other
+    /// for each frame. The sounds move on by the frames of the output, and are removed once
+    /// they end
+    fn mix(&self, output: &mut [f32], channels: u16, sample_rate: u32) {
+        output.fill(0.0);
+        let channels = channels.max(1) as usize;
+        let mut state = lock(&self.state);
+        let (volume, listener, occlusion) = (state.volume, state.listener, state.occlusion);
+
+        state.playbacks.retain(|_, playback| {
+            if playback.paused {
+                return true;
+            }
+            let sound = &playback.sound;
+            let frames = sound.frames();
+            if frames == 0 {
+                return false;
+            }
+            let source_channels = sound.channels as usize;
+            let step = playback.pitch as f64 * sound.sample_rate as f64 / sample_rate.max(1) as f64;
+            let gain = playback.volume * volume;
+            let gains = spatial_gains(playback, &listener).map(|i| i.map(|j| j * gain));
+
+            for frame in output.chunks_mut(channels) {
+                if playback.cursor >= frames as f64 {
+                    match playback.looping {
+                        true => playback.cursor %= frames as f64,
+                        false => return false,
+                    }
+                }
+                let index = playback.cursor as usize;
+                let next = match index + 1 < frames {
+                    true => index + 1,
+                    false if playback.looping => 0,
+                    false => index,
+                };
+                let blend = playback.cursor.fract() as f32;
+                let sample = |channel: usize| {
+                    let a = sound.samples[index * source_channels + channel];
+                    let b = sound.samples[next * source_channels + channel];
+                    a + (b - a) * blend
+                };
+
+                match (gains, frame.len()) {
+                    // a sound with a position is heard as one channel panned between the ears
+                    (Some([left, right]), length) => {
+                        let mut mono =
+                            (0..source_channels).map(sample).sum::<f32>() / source_channels as f32;
+                        if let Some(occlusion) = occlusion.as_ref() {
+                            mono = occlusion.apply(&mut playback.muffle, mono, sample_rate as f32);
+                        }
+                        frame[0] += mono
+                            * if length == 1 {
+                                (left + right) * 0.5
+                            } else {
+                                left
+                            };
+                        if length > 1 {
+                            frame[1] += mono * right;
+                        }
+                    }
+                    (None, 1) => {
+                        frame[0] += (0..source_channels).map(sample).sum::<f32>()
+                            / source_channels as f32
+                            * gain;
+                    }
+                    (None, _) => {
+                        for (channel, value) in frame.iter_mut().enumerate() {
+                            *value += sample(channel % source_channels) * gain;
+                        }
+                    }
+                }
+                playback.cursor += step;
+            }
+
+            true
+        });
+    }
+}
+
+/// The amount of frames mixed at a time for the device
+const MIX_FRAMES: usize = 256;
+
+/// The sounds of the mixer as an endless source of rodio, mixed a block at a time
+struct MixerSource {
+    mixer: AudioMixer,
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+    block: Vec<f32>,
+    /// The sample of the block that is given next, past its end once it is used up
+    next: usize,
+}
+
+impl Iterator for MixerSource {
+    type Item = rodio::Sample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.block.len() {
+            self.mixer
+                .mix(&mut self.block, self.channels.get(), self.sample_rate.get());
+            self.next = 0;
+        }
+        self.next += 1;
+
+        Some(self.block[self.next - 1])
+    }
+}
+
+impl Source for MixerSource {
+    fn current_span_len(&self) -> Option<usize> {
+        // the channels and sample rate never change
+        None
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+/// The gains of the left and right ear of a sound with a position, quieter with distance
+/// and panned with constant power
+fn spatial_gains(playback: &Playback, listener: &Listener) -> Option<[f32; 2]> {
+    let offset = playback.position? - listener.position;
+    let (min, max) = playback.distance;
+    let distance = offset.length();
+    let attenuation = min / distance.clamp(min, max);
+
+    let pan = match distance > f32::EPSILON {
+        true => (offset / distance).dot(listener.right).clamp(-1.0, 1.0),
+        false => 0.0,
+    };
+    let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+
+    Some([angle.cos() * attenuation, angle.sin() * attenuation])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mixes the frames of the channels at the sample rate of the sounds
+    fn mix(audio: &Audio, frames: usize, channels: u16) -> Vec<f32> {
+        let mut output = vec![0.0; frames * channels as usize];
+        audio.mixer().mix(&mut output, channels, 100);
+        output
+    }
+
+    fn ramp(frames: usize) -> Sound {
+        Sound::from_samples((0..frames).map(|i| i as f32).collect::<Vec<_>>(), 1, 100)
+    }
+
+    fn assert_near(found: &[f32], expected: &[f32]) {
+        assert_eq!(found.len(), expected.len());
+        for (found, expected) in found.iter().zip(expected) {
+            assert!((found - expected).abs() < 1e-4, "{found:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn sounds_are_decoded_from_wav() {
+        let samples = [0i16, 16384, -16384, 32767];
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + samples.len() as u32 * 2).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        // integers, one channel, 8000 frames a second of 2 bytes each, 16 bits
+        for value in [1u16, 1] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&8000u32.to_le_bytes());
+        bytes.extend_from_slice(&16000u32.to_le_bytes());
+        for value in [2u16, 16] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(samples.len() as u32 * 2).to_le_bytes());
+        for sample in samples {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        let sound = Sound::from_bytes(bytes).unwrap();
+        assert_eq!((sound.channels(), sound.sample_rate()), (1, 8000));
+        assert_eq!(sound.frames(), 4);
+        assert_near(&sound.samples, &[0.0, 0.5, -0.5, 32767.0 / 32768.0]);
+
+        assert!(matches!(
+            Sound::from_bytes(b"not a sound".to_vec()),
+            Err(Error::SoundDecodeError(_))
+        ));
+    }
+
+    #[test]
+    fn sounds_are_mixed_with_their_volume() {
+        let audio = Audio::silent();
+        let sound = Sound::from_samples(vec![0.5; 8], 1, 100);
+        audio.play(&sound).set_volume(0.5);
+        audio.play(&sound);
+        audio.set_volume(2.0);
+
+        // a sound of one channel is heard in both
+        assert_near(&mix(&audio, 2, 2), &[1.5; 4]);
+    }
+
+    #[test]
+    fn sounds_end_unless_looping() {
+        let audio = Audio::silent();
+        let once = audio.play(&ramp(4));
+        assert_near(&mix(&audio, 6, 1), &[0.0, 1.0, 2.0, 3.0, 0.0, 0.0]);
+        assert!(!once.is_playing());
+        assert_eq!(audio.playing(), 0);
+
+        let looping = audio.play(&ramp(4));
+        looping.set_looping(true);
+        assert_near(&mix(&audio, 6, 1), &[0.0, 1.0, 2.0, 3.0, 0.0, 1.0]);
+        assert!(looping.is_playing());
+        looping.stop();
+        assert_eq!(audio.playing(), 0);
+    }
+
+    #[test]
+    fn pitch_and_sample_rate_change_the_speed() {
+        let audio = Audio::silent();
+        audio.play(&ramp(8)).set_pitch(0.5);
+        // the frames between the samples are blended
+        assert_near(&mix(&audio, 4, 1), &[0.0, 0.5, 1.0, 1.5]);
+
+        audio.stop_all();
+        audio.play(&ramp(8));
+        let mut output = vec![0.0; 4];
+        audio.mixer().mix(&mut output, 1, 50);
+        assert_near(&output, &[0.0, 2.0, 4.0, 6.0]);
+    }
+
+    #[test]
+    fn paused_sounds_wait() {
+        let audio = Audio::silent();
+        let handle = audio.play(&ramp(4));
+        handle.pause();
+        assert_near(&mix(&audio, 2, 1), &[0.0, 0.0]);
+        assert!(handle.is_paused() && handle.is_playing());
+
+        handle.resume();
+        assert_near(&mix(&audio, 2, 1), &[0.0, 1.0]);
+    }
+
+    #[test]
+    fn positioned_sounds_are_panned_and_quieter_with_distance() {
+        let audio = Audio::silent();
+        let sound = Sound::from_samples(vec![1.0; 8], 1, 100);
+
+        let handle = audio.play_at(&sound, [0.5, 0.0, 0.0]);
+        let near = mix(&audio, 1, 2);
+        // at full volume within the distance, and heard from the right
+        assert!(near[1] > near[0]);
+        assert!((near[0] * near[0] + near[1] * near[1] - 1.0).abs() < 1e-4);
+
+        handle.set_position(Some(Vector3::new(-4.0, 0.0, 0.0)));
+        let far = mix(&audio, 1, 2);
+        assert!(far[0] > far[1]);
+        assert!(((far[0] * far[0] + far[1] * far[1]).sqrt() - 0.25).abs() < 1e-4);
+
+        // the listener is turned around, so the sound is heard from the right
+        audio.set_listener(Listener {
+            position: Vector3::ZERO,
+            right: Vector3::NEG_X,
+        });
+        let turned = mix(&audio, 1, 2);
+        assert!(turned[1] > turned[0]);
+
+        // with no position the sound is heard as it is
+        handle.set_position(None);
+        assert_near(&mix(&audio, 1, 2), &[1.0, 1.0]);
+    }
+
+    #[test]
+    fn the_device_plays_the_sounds() {
+        let Ok(audio) = Audio::new() else {
+            return;
+        };
+        assert!(audio.has_device());
+        let handle = audio.play(&Sound::from_samples(vec![0.0; 480], 1, 48000));
+
+        // the device asks for the samples on its own, which ends the sound
+        for _ in 0..100 {
+            if !handle.is_playing() {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        panic!("the sound did not end");
+    }
+
+    /// Mixes the frames of one channel at 48000 frames a second
+    fn mix_fast(audio: &Audio, frames: usize) -> Vec<f32> {
+        let mut output = vec![0.0; frames];
+        audio.mixer().mix(&mut output, 1, 48000);
+        output
+    }
+
+    fn set_occluded(audio: &Audio, occluded: bool) {
+        for playback in lock(&audio.state).playbacks.values_mut() {
+            playback.muffle.occluded = occluded;
+        }
+    }
+
+    #[test]
+    fn occluded_sounds_are_quieter_and_muffled() {
+        let audio = Audio::silent();
+        audio.set_occlusion(Some(Occlusion {
+            fade: 0.0,
+            ..Default::default()
+        }));
+        // a steady sound and the highest pitch there is, in front of the listener
+        let steady = Sound::from_samples(vec![1.0; 4800], 1, 48000);
+        let high = Sound::from_samples(
+            (0..4800)
+                .map(|i| if i % 2 == 0 { 1.0 } else { -1.0 })
+                .collect::<Vec<_>>(),
+            1,
+            48000,
+        );
+        let centered = std::f32::consts::FRAC_1_SQRT_2;
+
+        let handle = audio.play_at(&steady, [0.0, 0.0, -0.5]);
+        assert_near(&mix_fast(&audio, 4), &[centered; 4]);
+        set_occluded(&audio, true);
+        let blocked = mix_fast(&audio, 480);
+        // the steady sound passes the filter, only quieter
+        assert!(
+            (blocked[479] - centered * 0.5).abs() < 1e-3,
+            "{}",
+            blocked[479]
+        );
+        handle.stop();
+
+        audio.play_at(&high, [0.0, 0.0, -0.5]);
+        let clear = mix_fast(&audio, 64);
+        assert!(clear.iter().all(|i| (i.abs() - centered).abs() < 1e-4));
+        set_occluded(&audio, true);
+        let blocked = mix_fast(&audio, 480);
+        let loudest = blocked[240..].iter().fold(0.0f32, |a, i| a.max(i.abs()));
+        assert!(loudest < 0.05, "{loudest}");
+
+        // the sounds are heard as they are once it is turned off
+        audio.set_occlusion(None);
+        let clear = mix_fast(&audio, 4);
+        assert!(clear.iter().all(|i| (i.abs() - centered).abs() < 1e-4));
+    }
+
+    #[test]
+    fn occlusion_fades_in_and_out() {
+        let audio = Audio::silent();
+        audio.set_occlusion(Some(Occlusion {
+            fade: 0.01,
+            ..Default::default()
+        }));
+        audio.play_at(
+            &Sound::from_samples(vec![1.0; 4800], 1, 48000),
+            [0.0, 0.0, -0.5],
+        );
+        let centered = std::f32::consts::FRAC_1_SQRT_2;
+
+        set_occluded(&audio, true);
+        // the fade takes 480 frames
+        let fading = mix_fast(&audio, 960);
+        assert!(fading[0] > centered * 0.99);
+        assert!(fading.windows(2).all(|i| i[1] <= i[0] + 1e-6));
+        assert!(fading[240] < centered * 0.9 && fading[240] > centered * 0.6);
+        assert!((fading[959] - centered * 0.5).abs() < 1e-3);
+
+        set_occluded(&audio, false);
+        let clearing = mix_fast(&audio, 960);
+        assert!(clearing.windows(2).all(|i| i[1] >= i[0] - 1e-6));
+        assert!((clearing[959] - centered).abs() < 1e-4);
+    }
+
+    #[test]
+    fn sounds_without_a_position_are_not_occluded() {
+        let audio = Audio::silent();
+        audio.set_occlusion(Some(Occlusion {
+            fade: 0.0,
+            ..Default::default()
+        }));
+        audio.play(&Sound::from_samples(vec![1.0; 4800], 1, 48000));
+        set_occluded(&audio, true);
+        assert_near(&mix_fast(&audio, 4), &[1.0; 4]);
+    }
+
+    #[test]
+    fn objects_between_the_listener_and_the_sound_occlude_it() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = ObjectStorage::new();
+        let mut ids = Vec::new();
+        for (name, position) in [("wall", [0.0, 0.0, -5.0]), ("player", [0.0, 0.0, 0.0])] {
+            let (vertices, indices) = crate::primitive_shapes::triangle_data();
+            let mut object = crate::Object::new(
+                name,
+                vertices,
+                indices,
+                crate::ObjectSettings::default(),
+                &mut renderer,
+            )
+            .expect("the triangle is built");
+            object.set_position(position);
+            ids.push(objects.add(name, object));
+        }
+        let listener = Vector3::ZERO;
+
+        assert!(is_occluded(
+            &objects,
+            listener,
+            Vector3::new(0.0, 0.0, -10.0),
+            None
+        ));
+        // the player around the listener does not block, and nothing is to the side
+        assert!(!is_occluded(
+            &objects,
+            listener,
+            Vector3::new(10.0, 0.0, 0.0),
+            None
+        ));
+        assert!(!is_occluded(
+            &objects,
+            listener,
+            Vector3::new(0.0, 0.0, -3.0),
+            None
+        ));
+        // nor does the object the sound comes from
+        assert!(!is_occluded(
+            &objects,
+            listener,
+            Vector3::new(0.0, 0.0, -5.0),
+            Some(ids[0])
+        ));
+
+        objects.set_spatial_index(true);
+        assert!(is_occluded(
+            &objects,
+            listener,
+            Vector3::new(0.0, 0.0, -10.0),
+            None
+        ));
+        objects.get_mut("wall").unwrap().is_visible = false;
+        assert!(!is_occluded(
+            &objects,
+            listener,
+            Vector3::new(0.0, 0.0, -10.0),
+            None
+        ));
+    }
+}
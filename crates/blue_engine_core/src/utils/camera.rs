@@ -6,8 +6,13 @@
 
 use super::default_resources::OPENGL_TO_WGPU_MATRIX;
 use crate::{
-    Matrix4, UniformBuffers, Vector2,
+    Matrix4, ObjectStorage, StringBuffer, UniformBuffers, Vector2,
     prelude::{Renderer, Vector3},
+    utils::{
+        camera_controller::smoothing_factor,
+        collision::{Plane, ray_vs_plane},
+        fog::FogUniform,
+    },
 };
 use winit::dpi::PhysicalSize;
 
@@ -30,6 +35,158 @@ pub enum Projection {
     },
 }
 
+/// Makes a camera follow an object, keeping an offset from it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraFollow {
+    /// Name of the object that is followed
+    pub object: std::sync::Arc<str>,
+    /// The position of the camera relative to the object
+    pub offset: Vector3,
+    /// Time in seconds to catch up with the object, zero to stay on it exactly
+    pub smoothing: f32,
+}
+
+/// The part of the frame a camera draws to, as fractions of its size from the top left, such
+/// as the left half for the first player of a split screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Viewport {
+    /// The left side, from 0 to 1
+    pub x: f32,
+    /// The top side, from 0 to 1
+    pub y: f32,
+    /// The width, from 0 to 1
+    pub width: f32,
+    /// The height, from 0 to 1
+    pub height: f32,
+}
+
+impl Viewport {
+    /// Creates a new viewport from the fractions of the frame
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The rectangle of the viewport in pixels of a frame of the size, as
+    /// (x, y, width, height), clipped to the frame
+    pub fn to_pixels(&self, frame_size: PhysicalSize<u32>) -> (u32, u32, u32, u32) {
+        let (frame_width, frame_height) = (frame_size.width as f32, frame_size.height as f32);
+        let left = (self.x.clamp(0.0, 1.0) * frame_width).round();
+        let top = (self.y.clamp(0.0, 1.0) * frame_height).round();
+        let right = ((self.x + self.width).clamp(0.0, 1.0) * frame_width).round();
+        let bottom = ((self.y + self.height).clamp(0.0, 1.0) * frame_height).round();
+
+        (
+            left as u32,
+            top as u32,
+            (right - left).max(0.0) as u32,
+            (bottom - top).max(0.0) as u32,
+        )
+    }
+}
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new(0.0, 0.0, 1.0, 1.0)
+    }
+}
+
+/// How the content of a camera is scaled when the window is resized, see [ContentScale]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ScalePolicy {
+    /// The content fills the window, stretched if the window has another aspect ratio
+    #[default]
+    Stretch,
+    /// The content is as large as fits the window with its own aspect ratio, with bars on
+    /// the sides that are left
+    Fit,
+    /// The content is scaled by whole numbers only, so each pixel of it is a square of pixels
+    /// of the window, for pixel art. Scaled down to fit if the window is smaller than it
+    IntegerScale,
+}
+
+/// The size a 2D scene is made for, and how it is scaled to the window. The camera keeps the
+/// aspect ratio of the size and sets its viewport to the part of the window the content is
+/// drawn in, again on every resize. See [Camera::set_content_scale]
+///
+/// ```ignore
+/// camera.set_content_scale(Some(ContentScale::new(320.0, 180.0, ScalePolicy::IntegerScale)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContentScale {
+    /// The size the content is made for, in pixels
+    pub size: Vector2,
+    /// How the content is scaled to the window
+    pub policy: ScalePolicy,
+}
+
+impl ContentScale {
+    /// Creates a new content scale for a scene made for the width and height in pixels
+    pub fn new(width: f32, height: f32, policy: ScalePolicy) -> Self {
+        Self {
+            size: Vector2::new(width.max(1.0), height.max(1.0)),
+            policy,
+        }
+    }
+
+    /// The aspect ratio of the content
+    pub fn aspect(&self) -> f32 {
+        self.size.x / self.size.y
+    }
+
+    /// How many pixels of the window a pixel of the content takes, across and down
+    pub fn scale(&self, window_size: PhysicalSize<u32>) -> Vector2 {
+        let window = Vector2::new(window_size.width as f32, window_size.height as f32);
+        let fit = (window / self.size).min_element();
+
+        match self.policy {
+            ScalePolicy::Stretch => window / self.size,
+            ScalePolicy::Fit => Vector2::splat(fit),
+            ScalePolicy::IntegerScale if fit >= 1.0 => Vector2::splat(fit.floor()),
+            ScalePolicy::IntegerScale => Vector2::splat(fit),
+        }
+    }
+
+    /// The part of a window of the size the content is drawn in, centered
+    pub fn viewport(&self, window_size: PhysicalSize<u32>) -> Viewport {
+        if window_size.width == 0 || window_size.height == 0 {
+            return Viewport::default();
+        }
+        let window = Vector2::new(window_size.width as f32, window_size.height as f32);
+        let content = (self.size * self.scale(window_size)).min(window);
+        // on whole pixels, so the pixels of the content line up with the ones of the window
+        let offset = ((window - content) * 0.5).floor();
+
+        Viewport::new(
+            offset.x / window.x,
+            offset.y / window.y,
+            content.x / window.x,
+            content.y / window.y,
+        )
+    }
+}
+
+/// The uniform of a camera as it is sent to the GPU, read as the `CameraUniforms` of the
+/// shaders made with [crate::ShaderBuilder]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    /// The view and projection of the camera
+    pub camera_matrix: Matrix4,
+    /// The position of the camera, in xyz
+    pub position: [f32; 4],
+    /// The fog of the renderer
+    pub fog: FogUniform,
+    /// The time of the shaders in x, see [Renderer::shader_time]
+    pub time: [f32; 4],
+}
+
 /// Container for the camera feature. The settings here are needed for
 /// algebra equations needed for camera vision and movement. Please leave it to the renderer to handle
 #[derive(Debug)]
@@ -54,9 +211,23 @@ pub struct Camera {
     pub(crate) changed: bool,
     /// The uniform data of the camera to be sent to the gpu
     pub uniform_data: UniformBuffers,
+    // the buffer of the uniform data, written in place when the camera changes
+    pub(crate) uniform_buffer: wgpu::Buffer,
+    /// The object followed by the camera, evaluated each frame by the engine
+    pub follow: Option<CameraFollow>,
+    /// The object the camera keeps looking at, evaluated each frame by the engine
+    pub look_at: Option<std::sync::Arc<str>>,
+    /// The internal resolution of the camera relative to the frame, such as 0.5 for a cheap
+    /// reflection camera or 2 for supersampling. Cameras with a scale other than 1 are
+    /// rendered separately and composited onto the frame, below the other cameras
+    pub render_scale: f32,
+    /// The part of the frame the camera draws to, or the whole frame if None. The objects of
+    /// the camera are clipped to it, and its aspect ratio is the one of the viewport
+    pub viewport: Option<Viewport>,
+    /// The size the scene is made for and how it is scaled to the window, which then sets
+    /// the viewport on every resize, see [Camera::set_content_scale]
+    pub content_scale: Option<ContentScale>,
 }
-unsafe impl Send for Camera {}
-unsafe impl Sync for Camera {}
 
 /// Container for Cameras
 ///
@@ -76,9 +247,16 @@ crate::macros::impl_deref_field!(
 impl Camera {
     /// Creates a new camera. this should've been automatically done at the time of creating an engine
     pub fn new(window_size: PhysicalSize<u32>, renderer: &mut Renderer) -> Self {
-        let camera_uniform = renderer.build_uniform_buffer(&[
-            renderer.build_uniform_buffer_part("Camera Uniform", crate::Matrix4::IDENTITY)
-        ]);
+        let uniform_buffer = renderer.build_uniform_buffer_part(
+            "Camera Uniform",
+            CameraUniform {
+                camera_matrix: Matrix4::IDENTITY,
+                position: [0.0; 4],
+                fog: renderer.fog.into(),
+                time: [0.0; 4],
+            },
+        );
+        let camera_uniform = renderer.build_uniform_buffer(std::slice::from_ref(&uniform_buffer));
 
         let mut camera = Self {
             position: Vector3::new(0.0, 0.0, 3.0),
@@ -93,6 +271,12 @@ impl Camera {
             view_data: Matrix4::IDENTITY,
             changed: true,
             uniform_data: camera_uniform.0,
+            uniform_buffer,
+            follow: None,
+            look_at: None,
+            render_scale: 1.0,
+            viewport: None,
+            content_scale: None,
         };
         camera.build_view_projection_matrix();
 
@@ -106,7 +290,11 @@ impl Camera {
 
     /// Builds a projection matrix for camera
     pub fn build_projection_matrix(&self) -> Matrix4 {
-        let aspect = self.resolution.x / self.resolution.y;
+        let viewport = self.viewport.unwrap_or_default();
+        let aspect = match self.content_scale {
+            Some(content_scale) => content_scale.aspect(),
+            None => (self.resolution.x * viewport.width) / (self.resolution.y * viewport.height),
+        };
 
         match self.projection {
             crate::Projection::Perspective { fov } => {
@@ -149,16 +337,16 @@ impl Camera {
         self.changed = true;
     }
 
-    /// This builds a uniform buffer data from camera view data that is sent to the GPU in next frame
+    /// This builds a uniform buffer data from camera view data that is sent to the GPU in next frame.
+    /// It is written every frame, as the time of the shaders in it moves on
     pub fn update_view_projection(&mut self, renderer: &mut Renderer) {
-        if self.changed {
-            let updated_buffer = renderer
-                .build_uniform_buffer(&[renderer
-                    .build_uniform_buffer_part("Camera Uniform", self.camera_uniform_buffer())])
+        let uniform = self.gpu_uniform(renderer);
+        if renderer.write_uniform_buffer_part(&mut self.uniform_buffer, "Camera Uniform", uniform) {
+            self.uniform_data = renderer
+                .build_uniform_buffer(std::slice::from_ref(&self.uniform_buffer))
                 .0;
-            self.uniform_data = updated_buffer;
-            self.changed = false;
         }
+        self.changed = false;
     }
 
     /// This builds a uniform buffer data from camera view data that is sent to the GPU in next frame, and returns the bindgroup
@@ -168,7 +356,7 @@ impl Camera {
     ) -> crate::UniformBuffers {
         let updated_buffer = renderer
             .build_uniform_buffer(&[
-                renderer.build_uniform_buffer_part("Camera Uniform", self.camera_uniform_buffer())
+                renderer.build_uniform_buffer_part("Camera Uniform", self.gpu_uniform(renderer))
             ])
             .0;
 
@@ -180,6 +368,17 @@ impl Camera {
         self.view_data
     }
 
+    /// Returns the whole uniform of the camera, with its position, and the fog and time of
+    /// the shaders of the renderer
+    pub fn gpu_uniform(&self, renderer: &Renderer) -> CameraUniform {
+        CameraUniform {
+            camera_matrix: self.view_data,
+            position: self.position.extend(1.0).to_array(),
+            fog: renderer.fog.into(),
+            time: [renderer.shader_time(), 0.0, 0.0, 0.0],
+        }
+    }
+
     /// Sets the position of camera
     pub fn set_position(&mut self, new_pos: impl Into<Vector3>) {
         self.position = new_pos.into();
@@ -213,6 +412,9 @@ impl Camera {
     /// Sets the aspect ratio of the camera
     pub fn set_resolution(&mut self, window_size: PhysicalSize<u32>) {
         self.resolution = Vector2::new(window_size.width as f32, window_size.height as f32);
+        if let Some(content_scale) = self.content_scale {
+            self.viewport = Some(content_scale.viewport(window_size));
+        }
         self.build_view_projection_matrix();
     }
 
@@ -221,6 +423,129 @@ impl Camera {
         self.projection = projection;
         self.build_view_projection_matrix();
     }
+
+    /// Sets the internal resolution of the camera relative to the frame, see [Camera::render_scale]
+    pub fn set_render_scale(&mut self, render_scale: f32) {
+        self.render_scale = render_scale;
+    }
+
+    /// Sets the part of the frame the camera draws to, see [Camera::viewport]
+    pub fn set_viewport(&mut self, viewport: Option<Viewport>) {
+        self.viewport = viewport;
+        self.build_view_projection_matrix();
+    }
+
+    /// Scales the content to the window by the policy, keeping the aspect ratio of the size
+    /// it is made for. The camera sets its viewport to the part of the window the content is
+    /// drawn in, and again whenever the window is resized, so a viewport set by hand lasts
+    /// until the next resize. An orthographic camera still shows the width of its zoom.
+    ///
+    /// Setting None gives the camera the whole window again
+    pub fn set_content_scale(&mut self, content_scale: Option<ContentScale>) {
+        let window_size = PhysicalSize::new(self.resolution.x as u32, self.resolution.y as u32);
+        self.viewport = match content_scale {
+            Some(content_scale) => Some(content_scale.viewport(window_size)),
+            None if self.content_scale.is_some() => None,
+            None => self.viewport,
+        };
+        self.content_scale = content_scale;
+        self.build_view_projection_matrix();
+    }
+
+    /// The ray from the camera through the point of the window, in pixels from its top left,
+    /// such as the cursor, as (origin, direction) with a direction of length one. The ray
+    /// starts on the near plane. None if the point is outside of the viewport of the camera
+    pub fn cursor_ray(&self, cursor: impl Into<Vector2>) -> Option<(Vector3, Vector3)> {
+        let cursor = cursor.into();
+        let window_size = PhysicalSize::new(self.resolution.x as u32, self.resolution.y as u32);
+        let (x, y, width, height) = self.viewport.unwrap_or_default().to_pixels(window_size);
+        let relative =
+            (cursor - Vector2::new(x as f32, y as f32)) / Vector2::new(width as f32, height as f32);
+        if !relative.cmpge(Vector2::ZERO).all() || !relative.cmple(Vector2::ONE).all() {
+            return None;
+        }
+
+        // the depth of the view is from 0 to 1, and y points up
+        let inverse = self.view_data.inverse();
+        let device = Vector2::new(relative.x * 2.0 - 1.0, 1.0 - relative.y * 2.0);
+        let near = inverse.project_point3(device.extend(0.0));
+        let far = inverse.project_point3(device.extend(1.0));
+        let direction = (far - near).try_normalize()?;
+
+        near.is_finite().then_some((near, direction))
+    }
+
+    /// The point of the plane under the cursor, in pixels of the window, such as the square
+    /// of a board or the place on the ground to move units to. None if the cursor is outside
+    /// of the viewport, or the plane is not in front of the camera there
+    ///
+    /// ```ignore
+    /// if let Some(cursor) = input.cursor()
+    ///     && let Some(point) = camera.cursor_on_plane(cursor, &Plane::GROUND)
+    /// {
+    ///     objects.get_mut("marker").map(|i| i.set_position(point));
+    /// }
+    /// ```
+    pub fn cursor_on_plane(&self, cursor: impl Into<Vector2>, plane: &Plane) -> Option<Vector3> {
+        let (origin, direction) = self.cursor_ray(cursor)?;
+        let distance = ray_vs_plane(origin, direction, plane)?;
+
+        Some(origin + direction * distance)
+    }
+
+    /// Makes the camera follow the object each frame, keeping the offset from its position.
+    /// The camera keeps looking the same way, unless it is also looking at an object
+    pub fn follow(
+        &mut self,
+        object: impl StringBuffer,
+        offset: impl Into<Vector3>,
+        smoothing: f32,
+    ) {
+        self.follow = Some(CameraFollow {
+            object: object.as_arc(),
+            offset: offset.into(),
+            smoothing,
+        });
+    }
+
+    /// Stops following the object
+    pub fn stop_following(&mut self) {
+        self.follow = None;
+    }
+
+    /// Makes the camera look at the position of the object each frame
+    pub fn look_at_object(&mut self, object: impl StringBuffer) {
+        self.look_at = Some(object.as_arc());
+    }
+
+    /// Stops looking at the object
+    pub fn stop_looking_at_object(&mut self) {
+        self.look_at = None;
+    }
+
+    /// Moves the camera by its follow and look at constraints. Objects that do not exist
+    /// are ignored
+    pub fn apply_constraints(&mut self, objects: &ObjectStorage, delta_time: f32) {
+        let mut position = self.position;
+        let mut target = self.target;
+
+        if let Some(follow) = self.follow.as_ref()
+            && let Some(object) = objects.get(follow.object.as_ref())
+        {
+            let goal = object.position + follow.offset;
+            position += (goal - position) * smoothing_factor(follow.smoothing, delta_time);
+            target += position - self.position;
+        }
+        if let Some(object) = self.look_at.as_ref().and_then(|i| objects.get(i.as_ref())) {
+            target = object.position;
+        }
+
+        if position != self.position || target != self.target {
+            self.position = position;
+            self.target = target;
+            self.build_view_projection_matrix();
+        }
+    }
 }
 
 impl CameraContainer {
@@ -283,10 +608,13 @@ impl CameraContainer {
             main_camera.set_near(new_near);
         }
     }
-    /// Sets the aspect ratio of the camera
+    /// Sets the aspect ratio of the camera. The other cameras with a content scale are
+    /// resized too, see [Camera::set_content_scale]
     pub fn set_resolution(&mut self, window_size: PhysicalSize<u32>) {
-        if let Some(main_camera) = self.cameras.get_mut("main") {
-            main_camera.set_resolution(window_size);
+        for (name, camera) in self.cameras.iter_mut() {
+            if name.as_ref() == "main" || camera.content_scale.is_some() {
+                camera.set_resolution(window_size);
+            }
         }
     }
     /// Sets the projection of the camera
@@ -295,6 +623,55 @@ impl CameraContainer {
             main_camera.set_projection(projection);
         }
     }
+    /// Sets the internal resolution of the main camera, see [Camera::render_scale]
+    pub fn set_render_scale(&mut self, render_scale: f32) {
+        if let Some(main_camera) = self.cameras.get_mut("main") {
+            main_camera.set_render_scale(render_scale);
+        }
+    }
+    /// Sets the part of the frame the main camera draws to, see [Camera::viewport]
+    pub fn set_viewport(&mut self, viewport: Option<Viewport>) {
+        if let Some(main_camera) = self.cameras.get_mut("main") {
+            main_camera.set_viewport(viewport);
+        }
+    }
+    /// Sets how the content of the main camera is scaled to the window, see
+    /// [Camera::set_content_scale]
+    pub fn set_content_scale(&mut self, content_scale: Option<ContentScale>) {
+        if let Some(main_camera) = self.cameras.get_mut("main") {
+            main_camera.set_content_scale(content_scale);
+        }
+    }
+    /// The point of the plane under the cursor, seen by the main camera, see
+    /// [Camera::cursor_on_plane]
+    pub fn cursor_on_plane(&self, cursor: impl Into<Vector2>, plane: &Plane) -> Option<Vector3> {
+        self.cameras
+            .get("main")
+            .and_then(|i| i.cursor_on_plane(cursor, plane))
+    }
+    /// Makes the main camera follow the object, see [Camera::follow]
+    pub fn follow(
+        &mut self,
+        object: impl StringBuffer,
+        offset: impl Into<Vector3>,
+        smoothing: f32,
+    ) {
+        if let Some(main_camera) = self.cameras.get_mut("main") {
+            main_camera.follow(object, offset, smoothing);
+        }
+    }
+    /// Makes the main camera look at the object, see [Camera::look_at_object]
+    pub fn look_at_object(&mut self, object: impl StringBuffer) {
+        if let Some(main_camera) = self.cameras.get_mut("main") {
+            main_camera.look_at_object(object);
+        }
+    }
+    /// Moves all the cameras by their follow and look at constraints
+    pub fn apply_constraints(&mut self, objects: &ObjectStorage, delta_time: f32) {
+        for camera in self.cameras.values_mut() {
+            camera.apply_constraints(objects, delta_time);
+        }
+    }
     /// This builds a uniform buffer data from camera view data that is sent to the GPU in next frame
     pub fn update_view_projection(&mut self, renderer: &mut Renderer) {
         if let Some(main_camera) = self.cameras.get_mut("main") {
@@ -328,3 +705,48 @@ impl CameraContainer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Object, ObjectSettings, primitive_shapes::triangle_data};
+
+    #[test]
+    fn following_an_object_moved_by_set_position_and_translate() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = ObjectStorage::new();
+        let (vertices, indices) = triangle_data();
+        let object = Object::new(
+            "player",
+            vertices,
+            indices,
+            ObjectSettings::default(),
+            &mut renderer,
+        )
+        .expect("the triangle is built");
+        objects.add("player", object);
+
+        let mut camera = Camera::new(PhysicalSize::new(800, 600), &mut renderer);
+        camera.follow("player", (0.0, 2.0, 5.0), 0.0);
+        camera.look_at_object("player");
+
+        objects
+            .get_mut("player")
+            .unwrap()
+            .set_position((3.0, 0.0, 0.0));
+        camera.apply_constraints(&objects, 1.0 / 60.0);
+        assert_eq!(camera.position, Vector3::new(3.0, 2.0, 5.0));
+        assert_eq!(camera.target, objects["player"].transform().position);
+
+        objects
+            .get_mut("player")
+            .unwrap()
+            .translate((1.0, 0.0, -1.0));
+        camera.apply_constraints(&objects, 1.0 / 60.0);
+        assert_eq!(camera.position, Vector3::new(4.0, 2.0, 4.0));
+        assert_eq!(camera.target, Vector3::new(4.0, 0.0, -1.0));
+        assert_eq!(camera.target, objects["player"].transform().position);
+    }
+}
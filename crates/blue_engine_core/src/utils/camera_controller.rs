@@ -0,0 +1,362 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{
+    CameraContainer, KeyCode, MouseButton, StringBuffer, Vector3,
+    utils::{
+        gamepad::{GamepadAxis, GamepadButton},
+        input_map::InputMap,
+        winit_input_helper::WinitInputHelper,
+    },
+};
+
+/// Changes smaller than this are not applied to the camera, so it can rest
+const MOVEMENT_EPSILON: f32 = 0.00001;
+
+/// How much of the remaining distance to the goal is covered in this frame, for a smoothing
+/// time in seconds. Zero smoothing reaches the goal immediately
+pub(crate) fn smoothing_factor(smoothing: f32, delta_time: f32) -> f32 {
+    if smoothing <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-delta_time / smoothing).exp()
+    }
+}
+
+/// The direction of a yaw and pitch in degrees, where a yaw of -90 looks down -Z
+fn direction(yaw: f32, pitch: f32) -> Vector3 {
+    let (yaw, pitch) = (yaw.to_radians(), pitch.to_radians());
+    Vector3::new(
+        yaw.cos() * pitch.cos(),
+        pitch.sin(),
+        yaw.sin() * pitch.cos(),
+    )
+}
+
+/// The yaw and pitch in degrees of a direction
+fn yaw_pitch(direction: Vector3) -> (f32, f32) {
+    let direction = direction.normalize_or(Vector3::NEG_Z);
+    (
+        direction.z.atan2(direction.x).to_degrees(),
+        direction.y.clamp(-1.0, 1.0).asin().to_degrees(),
+    )
+}
+
+/// Moves the camera to the position and makes it look at the target, if either moved
+fn apply(camera: &mut CameraContainer, name: &str, position: Vector3, target: Vector3) {
+    if let Some(camera) = camera.get_mut(name)
+        && (!camera.position.abs_diff_eq(position, MOVEMENT_EPSILON)
+            || !camera.target.abs_diff_eq(target, MOVEMENT_EPSILON))
+    {
+        camera.position = position;
+        camera.target = target;
+        camera.build_view_projection_matrix();
+    }
+}
+
+/// A first person camera that flies around with the keyboard, mouse, and gamepad.
+///
+/// The movement is read from the actions of its [InputMap]: `move_forward`, `move_right`,
+/// `move_up`, `fast`, `look_right`, and `look_up`, which are bound to WASD, E and Q,
+/// Shift, and the gamepad sticks by default. It can be added as a signal, or updated
+/// manually with [FlyCamera::update].
+#[derive(Debug, Clone)]
+pub struct FlyCamera {
+    /// Name of the camera that is controlled
+    pub camera: std::sync::Arc<str>,
+    /// The bindings of the movement
+    pub input: InputMap,
+    /// Should the camera be moved
+    pub enabled: bool,
+    /// Movement speed in units per second
+    pub speed: f32,
+    /// Multiplies the speed while the `fast` action is held
+    pub fast_multiplier: f32,
+    /// Degrees turned per pixel of mouse movement
+    pub sensitivity: f32,
+    /// Degrees turned per second with the look actions fully held, such as a gamepad stick
+    pub look_speed: f32,
+    /// Time in seconds to catch up with the movement and turning, zero for none
+    pub smoothing: f32,
+    /// The mouse button that must be held to look around. If `None`, the mouse always turns
+    /// the camera, which is best with a grabbed cursor, see [crate::Window::grab_cursor]
+    pub look_button: Option<MouseButton>,
+    /// The yaw the camera is turning to, in degrees
+    pub yaw: f32,
+    /// The pitch the camera is turning to, in degrees
+    pub pitch: f32,
+    current_yaw: f32,
+    current_pitch: f32,
+    velocity: Vector3,
+    initialized: bool,
+}
+
+impl FlyCamera {
+    /// Creates a new controller of the `main` camera with the default bindings
+    pub fn new() -> Self {
+        let mut input = InputMap::new();
+        input
+            .bind("move_forward", KeyCode::KeyW)
+            .bind_scaled("move_forward", KeyCode::KeyS, -1.0)
+            .bind("move_forward", GamepadAxis::LeftStickY)
+            .bind("move_right", KeyCode::KeyD)
+            .bind_scaled("move_right", KeyCode::KeyA, -1.0)
+            .bind("move_right", GamepadAxis::LeftStickX)
+            .bind("move_up", KeyCode::KeyE)
+            .bind_scaled("move_up", KeyCode::KeyQ, -1.0)
+            .bind("move_up", GamepadButton::RightBumper)
+            .bind_scaled("move_up", GamepadButton::LeftBumper, -1.0)
+            .bind("fast", KeyCode::ShiftLeft)
+            .bind("fast", GamepadButton::LeftThumb)
+            .bind("look_right", GamepadAxis::RightStickX)
+            .bind("look_up", GamepadAxis::RightStickY);
+
+        Self {
+            camera: "main".into(),
+            input,
+            enabled: true,
+            speed: 5.0,
+            fast_multiplier: 3.0,
+            sensitivity: 0.1,
+            look_speed: 120.0,
+            smoothing: 0.05,
+            look_button: Some(MouseButton::Right),
+            yaw: -90.0,
+            pitch: 0.0,
+            current_yaw: -90.0,
+            current_pitch: 0.0,
+            velocity: Vector3::ZERO,
+            initialized: false,
+        }
+    }
+
+    /// Sets the name of the camera that is controlled
+    pub fn with_camera(mut self, camera: impl StringBuffer) -> Self {
+        self.camera = camera.as_arc();
+        self.initialized = false;
+        self
+    }
+
+    /// Reads the input of this frame and moves the camera
+    pub fn update(&mut self, camera: &mut CameraContainer, input: &WinitInputHelper) {
+        let Some(current) = camera.get(self.camera.as_ref()) else {
+            return;
+        };
+        let mut position = current.position;
+        if !self.initialized {
+            (self.yaw, self.pitch) = yaw_pitch(current.target - current.position);
+            (self.current_yaw, self.current_pitch) = (self.yaw, self.pitch);
+            self.initialized = true;
+        }
+        let delta_time = input.delta_time().map(|i| i.as_secs_f32()).unwrap_or(0.0);
+
+        if self.enabled {
+            // looking around
+            if self.look_button.is_none_or(|i| input.mouse_held(i)) {
+                let (x, y) = input.mouse_diff();
+                self.yaw += x * self.sensitivity;
+                self.pitch -= y * self.sensitivity;
+            }
+            self.yaw += self.input.axis_value("look_right", input) * self.look_speed * delta_time;
+            self.pitch += self.input.axis_value("look_up", input) * self.look_speed * delta_time;
+            self.pitch = self.pitch.clamp(-89.0, 89.0);
+        }
+
+        let factor = smoothing_factor(self.smoothing, delta_time);
+        self.current_yaw += (self.yaw - self.current_yaw) * factor;
+        self.current_pitch += (self.pitch - self.current_pitch) * factor;
+        let forward = direction(self.current_yaw, self.current_pitch);
+
+        // moving
+        let mut wish = Vector3::ZERO;
+        if self.enabled {
+            let right = forward.cross(Vector3::Y).normalize_or_zero();
+            wish = forward * self.input.axis_value("move_forward", input)
+                + right * self.input.axis_value("move_right", input)
+                + Vector3::Y * self.input.axis_value("move_up", input);
+            wish = wish.clamp_length_max(1.0) * self.speed;
+            if self.input.pressed("fast", input) {
+                wish *= self.fast_multiplier;
+            }
+        }
+        self.velocity += (wish - self.velocity) * factor;
+        position += self.velocity * delta_time;
+
+        apply(camera, &self.camera, position, position + forward);
+    }
+}
+impl Default for FlyCamera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl crate::Signal for FlyCamera {
+    fn frame(
+        &mut self,
+        _renderer: &mut crate::Renderer,
+        _window: &crate::Window,
+        _objects: &mut crate::ObjectStorage,
+        camera: &mut CameraContainer,
+        input: &crate::InputHelper,
+        _encoder: &mut crate::CommandEncoder,
+        _view: &crate::TextureView,
+    ) {
+        self.update(camera, input);
+    }
+}
+
+/// A camera that orbits around a point, rotated by dragging the mouse, panned by dragging
+/// with the middle button, and zoomed with the scroll wheel.
+///
+/// It can be added as a signal, or updated manually with [OrbitCamera::update].
+#[derive(Debug, Clone)]
+pub struct OrbitCamera {
+    /// Name of the camera that is controlled
+    pub camera: std::sync::Arc<str>,
+    /// Should the camera be moved
+    pub enabled: bool,
+    /// The point the camera orbits around
+    pub target: Vector3,
+    /// The distance the camera is moving to from the target
+    pub distance: f32,
+    /// The closest the camera can zoom in
+    pub min_distance: f32,
+    /// The furthest the camera can zoom out
+    pub max_distance: f32,
+    /// The yaw the camera is rotating to, in degrees
+    pub yaw: f32,
+    /// The pitch the camera is rotating to, in degrees
+    pub pitch: f32,
+    /// The lowest the pitch can go, in degrees
+    pub min_pitch: f32,
+    /// The highest the pitch can go, in degrees
+    pub max_pitch: f32,
+    /// Degrees rotated per pixel of mouse movement
+    pub sensitivity: f32,
+    /// The part of the distance zoomed per line scrolled
+    pub zoom_speed: f32,
+    /// The part of the distance panned per pixel of mouse movement
+    pub pan_speed: f32,
+    /// Time in seconds to catch up with the rotation, panning, and zoom, zero for none
+    pub smoothing: f32,
+    /// The mouse button that rotates the camera while held
+    pub rotate_button: MouseButton,
+    /// The mouse button that pans the camera while held, `None` to disable panning
+    pub pan_button: Option<MouseButton>,
+    current_target: Vector3,
+    current_distance: f32,
+    current_yaw: f32,
+    current_pitch: f32,
+    initialized: bool,
+}
+
+impl OrbitCamera {
+    /// Creates a new controller of the `main` camera that orbits around the given point.
+    /// The distance and angle are taken from the camera when it is first updated
+    pub fn new(target: impl Into<Vector3>) -> Self {
+        let target = target.into();
+
+        Self {
+            camera: "main".into(),
+            enabled: true,
+            target,
+            distance: 3.0,
+            min_distance: 0.5,
+            max_distance: 100.0,
+            yaw: 90.0,
+            pitch: 0.0,
+            min_pitch: -89.0,
+            max_pitch: 89.0,
+            sensitivity: 0.3,
+            zoom_speed: 0.1,
+            pan_speed: 0.002,
+            smoothing: 0.05,
+            rotate_button: MouseButton::Left,
+            pan_button: Some(MouseButton::Middle),
+            current_target: target,
+            current_distance: 3.0,
+            current_yaw: 90.0,
+            current_pitch: 0.0,
+            initialized: false,
+        }
+    }
+
+    /// Sets the name of the camera that is controlled
+    pub fn with_camera(mut self, camera: impl StringBuffer) -> Self {
+        self.camera = camera.as_arc();
+        self.initialized = false;
+        self
+    }
+
+    /// Reads the input of this frame and moves the camera
+    pub fn update(&mut self, camera: &mut CameraContainer, input: &WinitInputHelper) {
+        let Some(current) = camera.get(self.camera.as_ref()) else {
+            return;
+        };
+        if !self.initialized {
+            let offset = current.position - self.target;
+            self.distance = offset.length();
+            (self.yaw, self.pitch) = yaw_pitch(offset);
+            self.current_target = self.target;
+            self.current_distance = self.distance;
+            (self.current_yaw, self.current_pitch) = (self.yaw, self.pitch);
+            self.initialized = true;
+        }
+        let delta_time = input.delta_time().map(|i| i.as_secs_f32()).unwrap_or(0.0);
+
+        if self.enabled {
+            let (x, y) = input.mouse_diff();
+            if input.mouse_held(self.rotate_button) {
+                self.yaw += x * self.sensitivity;
+                self.pitch += y * self.sensitivity;
+            }
+            if self.pan_button.is_some_and(|i| input.mouse_held(i)) {
+                let forward = -direction(self.current_yaw, self.current_pitch);
+                let right = forward.cross(Vector3::Y).normalize_or_zero();
+                let up = right.cross(forward).normalize_or_zero();
+                self.target += (up * y - right * x) * self.pan_speed * self.distance;
+            }
+
+            let (_, scroll) = input.scroll_diff();
+            self.distance *= (1.0 - scroll * self.zoom_speed).max(0.1);
+        }
+        self.pitch = self.pitch.clamp(self.min_pitch, self.max_pitch);
+        self.distance = self
+            .distance
+            .clamp(self.min_distance, self.max_distance.max(self.min_distance));
+
+        let factor = smoothing_factor(self.smoothing, delta_time);
+        self.current_target += (self.target - self.current_target) * factor;
+        self.current_distance += (self.distance - self.current_distance) * factor;
+        self.current_yaw += (self.yaw - self.current_yaw) * factor;
+        self.current_pitch += (self.pitch - self.current_pitch) * factor;
+
+        let position = self.current_target
+            + direction(self.current_yaw, self.current_pitch) * self.current_distance;
+        apply(camera, &self.camera, position, self.current_target);
+    }
+}
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self::new(Vector3::ZERO)
+    }
+}
+
+impl crate::Signal for OrbitCamera {
+    fn frame(
+        &mut self,
+        _renderer: &mut crate::Renderer,
+        _window: &crate::Window,
+        _objects: &mut crate::ObjectStorage,
+        camera: &mut CameraContainer,
+        input: &crate::InputHelper,
+        _encoder: &mut crate::CommandEncoder,
+        _view: &crate::TextureView,
+    ) {
+        self.update(camera, input);
+    }
+}
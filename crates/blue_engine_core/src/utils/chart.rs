@@ -0,0 +1,209 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::utils::overlay::{GLYPH_HEIGHT, Overlay};
+
+/// A rectangle on the screen that data is plotted into, using the painter.
+///
+/// Data is mapped from the ranges of the chart to its area, with the
+/// lowest y value at the bottom.
+#[derive(Debug, Clone)]
+pub struct Chart {
+    /// Position of the plot area in pixels, from the top left of the window
+    pub position: [f32; 2],
+    /// Size of the plot area in pixels
+    pub size: [f32; 2],
+    /// The range of x values shown, from left to right
+    pub x_range: (f32, f32),
+    /// The range of y values shown, from bottom to top
+    pub y_range: (f32, f32),
+    /// Amount of labeled ticks on each axis
+    pub ticks: u32,
+    /// Color of the axes and ticks
+    pub axis_color: [f32; 4],
+    /// Color of the labels
+    pub label_color: [f32; 4],
+    /// Scale of the label text
+    pub label_scale: f32,
+}
+
+impl Chart {
+    /// Creates a new chart with ranges of 0 to 1 on both axes
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            position: [x, y],
+            size: [width, height],
+            x_range: (0.0, 1.0),
+            y_range: (0.0, 1.0),
+            ticks: 5,
+            axis_color: [0.8, 0.8, 0.8, 1.0],
+            label_color: [1.0, 1.0, 1.0, 1.0],
+            label_scale: 2.0,
+        }
+    }
+
+    /// Sets the ranges to fit all the points
+    pub fn fit(&mut self, points: &[[f32; 2]]) -> &mut Self {
+        if let Some(first) = points.first() {
+            let (mut min, mut max) = (*first, *first);
+            for point in points {
+                min = [min[0].min(point[0]), min[1].min(point[1])];
+                max = [max[0].max(point[0]), max[1].max(point[1])];
+            }
+            self.x_range = (min[0], max[0]);
+            self.y_range = (min[1], max[1]);
+        }
+
+        self
+    }
+
+    /// Converts a point of the data into a position on the screen
+    pub fn to_screen(&self, point: [f32; 2]) -> [f32; 2] {
+        [
+            self.position[0] + normalize(point[0], self.x_range) * self.size[0],
+            self.position[1] + (1.0 - normalize(point[1], self.y_range)) * self.size[1],
+        ]
+    }
+
+    /// Paints the x and y axes along the bottom and left of the chart, with labeled ticks
+    pub fn axes(&self, painter: &mut Overlay) {
+        const TICK_LENGTH: f32 = 4.0;
+        let [x, y] = self.position;
+        let [width, height] = self.size;
+        let bottom = y + height;
+        let label_height = GLYPH_HEIGHT as f32 * self.label_scale;
+
+        painter.line([x, bottom], [x + width, bottom], 1.0, self.axis_color);
+        painter.line([x, y], [x, bottom], 1.0, self.axis_color);
+
+        let ticks = self.ticks.max(1);
+        for i in 0..=ticks {
+            let t = i as f32 / ticks as f32;
+
+            // x axis
+            let tick_x = x + t * width;
+            let label = format_value(lerp(self.x_range, t));
+            painter.line(
+                [tick_x, bottom],
+                [tick_x, bottom + TICK_LENGTH],
+                1.0,
+                self.axis_color,
+            );
+            painter.text(
+                tick_x - Overlay::text_width(&label, self.label_scale) / 2.0,
+                bottom + TICK_LENGTH * 2.0,
+                self.label_scale,
+                &label,
+                self.label_color,
+            );
+
+            // y axis
+            let tick_y = bottom - t * height;
+            let label = format_value(lerp(self.y_range, t));
+            painter.line([x - TICK_LENGTH, tick_y], [x, tick_y], 1.0, self.axis_color);
+            painter.text(
+                x - TICK_LENGTH * 2.0 - Overlay::text_width(&label, self.label_scale),
+                tick_y - label_height / 2.0,
+                self.label_scale,
+                &label,
+                self.label_color,
+            );
+        }
+    }
+
+    /// Paints a title centered above the chart
+    pub fn title(&self, painter: &mut Overlay, title: &str) {
+        let label_height = GLYPH_HEIGHT as f32 * self.label_scale;
+        painter.text(
+            self.position[0] + (self.size[0] - Overlay::text_width(title, self.label_scale)) / 2.0,
+            self.position[1] - label_height * 2.0,
+            self.label_scale,
+            title,
+            self.label_color,
+        );
+    }
+
+    /// Paints a line through the points, in the order given
+    pub fn line_chart(
+        &self,
+        painter: &mut Overlay,
+        points: &[[f32; 2]],
+        thickness: f32,
+        color: [f32; 4],
+    ) {
+        let points: Vec<_> = points.iter().map(|i| self.to_screen(*i)).collect();
+        painter.polyline(&points, thickness, color);
+    }
+
+    /// Paints a dot at each of the points
+    pub fn scatter_chart(
+        &self,
+        painter: &mut Overlay,
+        points: &[[f32; 2]],
+        radius: f32,
+        color: [f32; 4],
+    ) {
+        for point in points {
+            painter.circle(self.to_screen(*point), radius, color);
+        }
+    }
+
+    /// Paints a bar for each value, spread evenly across the width of the chart.
+    ///
+    /// The bars start from zero, or the edge of the y range if zero is not in it.
+    pub fn bar_chart(&self, painter: &mut Overlay, values: &[f32], color: [f32; 4]) {
+        if values.is_empty() {
+            return;
+        }
+        // space between the bars, as a fraction of the slot of each bar
+        const GAP: f32 = 0.2;
+
+        let slot = self.size[0] / values.len() as f32;
+        let base = self.to_screen([
+            0.0,
+            0.0f32.clamp(
+                self.y_range.0.min(self.y_range.1),
+                self.y_range.0.max(self.y_range.1),
+            ),
+        ])[1];
+
+        for (index, value) in values.iter().enumerate() {
+            let top = self.to_screen([0.0, *value])[1];
+            painter.rect(
+                self.position[0] + index as f32 * slot + slot * GAP / 2.0,
+                top.min(base),
+                slot * (1.0 - GAP),
+                (top - base).abs(),
+                color,
+            );
+        }
+    }
+}
+
+/// Position of a value in a range, from 0 to 1 and clamped
+fn normalize(value: f32, range: (f32, f32)) -> f32 {
+    let length = range.1 - range.0;
+    if length == 0.0 {
+        return 0.5;
+    }
+
+    ((value - range.0) / length).clamp(0.0, 1.0)
+}
+
+fn lerp(range: (f32, f32), t: f32) -> f32 {
+    range.0 + (range.1 - range.0) * t
+}
+
+/// Formats a label with fewer decimals for bigger values
+fn format_value(value: f32) -> String {
+    if value.abs() >= 100.0 || value.fract() == 0.0 {
+        format!("{value:.0}")
+    } else if value.abs() >= 10.0 {
+        format!("{value:.1}")
+    } else {
+        format!("{value:.2}")
+    }
+}
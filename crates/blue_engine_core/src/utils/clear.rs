@@ -0,0 +1,219 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use wgpu::util::DeviceExt;
+
+use super::default_resources::CLEAR_GRADIENT_SHADER;
+use crate::Renderer;
+
+/// What the frame is filled with before the objects are drawn
+///
+/// ```ignore
+/// renderer.set_clear_color(ClearColor::VerticalGradient {
+///     top: wgpu::Color { r: 0.3, g: 0.5, b: 0.9, a: 1.0 },
+///     bottom: wgpu::Color::WHITE,
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClearColor {
+    /// A single color
+    Color(wgpu::Color),
+    /// A gradient from the top of the frame to its bottom
+    VerticalGradient {
+        /// The color at the top
+        top: wgpu::Color,
+        /// The color at the bottom
+        bottom: wgpu::Color,
+    },
+    /// The frame is not cleared, and the objects are drawn over what is already there. Useful
+    /// for windows drawn over others, or to draw over the passes of the frame graph before the
+    /// main pass
+    Keep,
+}
+
+impl ClearColor {
+    /// A single color from its channels
+    pub fn rgba(r: f64, g: f64, b: f64, a: f64) -> Self {
+        Self::Color(wgpu::Color { r, g, b, a })
+    }
+}
+
+impl From<wgpu::Color> for ClearColor {
+    fn from(color: wgpu::Color) -> Self {
+        Self::Color(color)
+    }
+}
+
+/// The pipeline of the gradient, and the format it was built for
+#[derive(Debug, Default)]
+pub(crate) struct ClearGradient {
+    pipeline: Option<(
+        wgpu::TextureFormat,
+        wgpu::BindGroupLayout,
+        wgpu::RenderPipeline,
+    )>,
+}
+
+impl ClearGradient {
+    /// Drops the pipeline, made again for the new device when a gradient is drawn
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.pipeline = None;
+    }
+
+    /// Fills the view with the gradient
+    fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+        (top, bottom): (wgpu::Color, wgpu::Color),
+    ) {
+        if self.pipeline.as_ref().map(|i| i.0) != Some(format) {
+            let (layout, pipeline) = Self::build_pipeline(device, format);
+            self.pipeline = Some((format, layout, pipeline));
+        }
+        let Some((_, layout, pipeline)) = self.pipeline.as_ref() else {
+            return;
+        };
+
+        let color = |i: wgpu::Color| [i.r as f32, i.g as f32, i.b as f32, i.a as f32];
+        crate::utils::allocation_audit::count_buffer();
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Clear Gradient Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[color(top), color(bottom)]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        crate::utils::allocation_audit::count_bind_group();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Clear Gradient Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Clear Gradient Render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(bottom),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn build_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline) {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Clear Gradient Shader"),
+            source: wgpu::ShaderSource::Wgsl(CLEAR_GRADIENT_SHADER.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Clear Gradient Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Clear Gradient Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Clear Gradient Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    write_mask: wgpu::ColorWrites::ALL,
+                    blend: None,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+                unclipped_depth: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        (bind_group_layout, pipeline)
+    }
+}
+
+impl Renderer {
+    /// Sets what the frame is cleared to before the objects are drawn. Takes a
+    /// [wgpu::Color], or a [ClearColor] for a gradient or to not clear at all
+    pub fn set_clear_color(&mut self, clear_color: impl Into<ClearColor>) {
+        self.clear_color = clear_color.into();
+    }
+
+    /// Sets the depth the depth buffer is cleared to before the objects are drawn, 1.0 by
+    /// default. Lower values hide the objects that are further away
+    pub fn set_clear_depth(&mut self, depth: f32) {
+        self.clear_depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// Clears the view as given, and returns how the pass that draws over it should load it.
+    /// Gradients are drawn in their own pass, after which the view is loaded
+    pub(crate) fn clear_load(
+        &mut self,
+        clear_color: ClearColor,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::LoadOp<wgpu::Color> {
+        match clear_color {
+            ClearColor::Color(color) => wgpu::LoadOp::Clear(color),
+            ClearColor::VerticalGradient { top, bottom } => {
+                self.clear_gradient
+                    .draw(&self.device, encoder, view, format, (top, bottom));
+                wgpu::LoadOp::Load
+            }
+            ClearColor::Keep => wgpu::LoadOp::Load,
+        }
+    }
+}
@@ -0,0 +1,30 @@
+// A vertical gradient that fills the target before the objects are drawn, with a full screen
+// triangle
+
+struct ClearGradient {
+    top: vec4<f32>,
+    bottom: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> gradient: ClearGradient;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // 0 at the top of the target and 1 at the bottom
+    @location(0) height: f32,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.height = uv.y;
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return mix(gradient.top, gradient.bottom, clamp(input.height, 0.0, 1.0));
+}
@@ -0,0 +1,593 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{Object, Quaternion, Vector3};
+
+/// A box aligned with the axes of the world
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Aabb {
+    /// The corner with the smallest coordinates
+    pub min: Vector3,
+    /// The corner with the largest coordinates
+    pub max: Vector3,
+}
+
+impl Aabb {
+    /// Creates the box between the corners, in any order
+    pub fn new(a: impl Into<Vector3>, b: impl Into<Vector3>) -> Self {
+        let (a, b) = (a.into(), b.into());
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    /// The smallest box around the points, None if there are none
+    pub fn from_points(points: impl IntoIterator<Item = Vector3>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Self::new(first, first), |aabb, i| Self {
+            min: aabb.min.min(i),
+            max: aabb.max.max(i),
+        }))
+    }
+
+    /// The middle of the box
+    pub fn center(&self) -> Vector3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Half the size of the box on each axis
+    pub fn half_extents(&self) -> Vector3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// The smallest box around both boxes
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    /// Returns true if the point is inside or on the box
+    pub fn contains_point(&self, point: impl Into<Vector3>) -> bool {
+        let point = point.into();
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    /// Returns true if the boxes overlap or touch
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        aabb_vs_aabb(self, other)
+    }
+
+    /// The point of the box closest to the point
+    pub fn closest_point(&self, point: impl Into<Vector3>) -> Vector3 {
+        point.into().clamp(self.min, self.max)
+    }
+}
+
+/// A box turned by a rotation
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Obb {
+    /// The middle of the box
+    pub center: Vector3,
+    /// Half the size of the box along each of its axes
+    pub half_extents: Vector3,
+    /// The rotation of the box
+    pub rotation: Quaternion,
+}
+
+impl Obb {
+    /// Creates the box of the center, half size, and rotation
+    pub fn new(
+        center: impl Into<Vector3>,
+        half_extents: impl Into<Vector3>,
+        rotation: Quaternion,
+    ) -> Self {
+        Self {
+            center: center.into(),
+            half_extents: half_extents.into(),
+            rotation,
+        }
+    }
+
+    /// The directions of the sides of the box in the world
+    pub fn axes(&self) -> [Vector3; 3] {
+        [
+            self.rotation * Vector3::X,
+            self.rotation * Vector3::Y,
+            self.rotation * Vector3::Z,
+        ]
+    }
+
+    /// The smallest box aligned with the axes around this one
+    pub fn aabb(&self) -> Aabb {
+        let [x, y, z] = self.axes();
+        let half_extents = x.abs() * self.half_extents.x
+            + y.abs() * self.half_extents.y
+            + z.abs() * self.half_extents.z;
+
+        Aabb {
+            min: self.center - half_extents,
+            max: self.center + half_extents,
+        }
+    }
+
+    /// Returns true if the point is inside or on the box
+    pub fn contains_point(&self, point: impl Into<Vector3>) -> bool {
+        let local = self.rotation.inverse() * (point.into() - self.center);
+        local.abs().cmple(self.half_extents).all()
+    }
+
+    /// Returns true if the boxes overlap or touch
+    pub fn intersects(&self, other: &Obb) -> bool {
+        obb_vs_obb(self, other)
+    }
+}
+impl From<Aabb> for Obb {
+    fn from(aabb: Aabb) -> Self {
+        Self {
+            center: aabb.center(),
+            half_extents: aabb.half_extents(),
+            rotation: Quaternion::IDENTITY,
+        }
+    }
+}
+
+/// An endless flat surface, such as the ground or a table, made of the points whose distance
+/// along the normal is the same
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Plane {
+    /// The direction the plane faces, of length one
+    pub normal: Vector3,
+    /// The distance of the plane from the origin along the normal
+    pub distance: f32,
+}
+
+impl Plane {
+    /// The ground, the plane of X and Z through the origin, facing up
+    pub const GROUND: Self = Self {
+        normal: Vector3::Y,
+        distance: 0.0,
+    };
+
+    /// Creates the plane through the point, facing the normal
+    pub fn new(normal: impl Into<Vector3>, point: impl Into<Vector3>) -> Self {
+        let normal = normal.into().normalize_or(Vector3::Y);
+        Self {
+            normal,
+            distance: normal.dot(point.into()),
+        }
+    }
+
+    /// The plane of X and Z at the height, facing up
+    pub fn horizontal(height: f32) -> Self {
+        Self {
+            normal: Vector3::Y,
+            distance: height,
+        }
+    }
+
+    /// How far the point is in front of the plane, negative if it is behind
+    pub fn signed_distance(&self, point: impl Into<Vector3>) -> f32 {
+        self.normal.dot(point.into()) - self.distance
+    }
+
+    /// The point on the plane closest to the given one
+    pub fn project_point(&self, point: impl Into<Vector3>) -> Vector3 {
+        let point = point.into();
+        point - self.normal * self.signed_distance(point)
+    }
+}
+
+/// Returns true if the boxes overlap or touch
+pub fn aabb_vs_aabb(a: &Aabb, b: &Aabb) -> bool {
+    a.min.cmple(b.max).all() && b.min.cmple(a.max).all()
+}
+
+/// How far along the ray it enters the box, 0 if it starts inside, or None if it misses.
+/// The distance is in lengths of the direction
+pub fn ray_vs_aabb(
+    origin: impl Into<Vector3>,
+    direction: impl Into<Vector3>,
+    aabb: &Aabb,
+) -> Option<f32> {
+    let (origin, direction) = (origin.into(), direction.into());
+    // the ray is clipped by the slabs between the sides of each axis
+    let inverse = direction.recip();
+    let first = (aabb.min - origin) * inverse;
+    let second = (aabb.max - origin) * inverse;
+    let near = first.min(second).max_element();
+    let far = first.max(second).min_element();
+
+    (far >= near.max(0.0) && !near.is_nan()).then_some(near.max(0.0))
+}
+
+/// How far along the ray it enters the box, 0 if it starts inside, or None if it misses
+pub fn ray_vs_obb(
+    origin: impl Into<Vector3>,
+    direction: impl Into<Vector3>,
+    obb: &Obb,
+) -> Option<f32> {
+    // the ray is turned into the space of the box, where it is aligned with the axes
+    let inverse = obb.rotation.inverse();
+    let origin = inverse * (origin.into() - obb.center);
+    let direction = inverse * direction.into();
+
+    ray_vs_aabb(
+        origin,
+        direction,
+        &Aabb::new(-obb.half_extents, obb.half_extents),
+    )
+}
+
+/// How far along the ray it meets the plane, from either side, or None if it runs along the
+/// plane or away from it. The distance is in lengths of the direction
+pub fn ray_vs_plane(
+    origin: impl Into<Vector3>,
+    direction: impl Into<Vector3>,
+    plane: &Plane,
+) -> Option<f32> {
+    let (origin, direction) = (origin.into(), direction.into());
+    let speed = plane.normal.dot(direction);
+    if speed.abs() < f32::EPSILON {
+        return None;
+    }
+    let distance = -plane.signed_distance(origin) / speed;
+
+    (distance >= 0.0).then_some(distance)
+}
+
+/// Returns true if the sphere overlaps or touches the box
+pub fn sphere_vs_aabb(center: impl Into<Vector3>, radius: f32, aabb: &Aabb) -> bool {
+    let center = center.into();
+    aabb.closest_point(center).distance_squared(center) <= radius * radius
+}
+
+/// Returns true if the sphere overlaps or touches the box
+pub fn sphere_vs_obb(center: impl Into<Vector3>, radius: f32, obb: &Obb) -> bool {
+    let local = obb.rotation.inverse() * (center.into() - obb.center);
+    sphere_vs_aabb(
+        local,
+        radius,
+        &Aabb::new(-obb.half_extents, obb.half_extents),
+    )
+}
+
+/// Returns true if the spheres overlap or touch
+pub fn sphere_vs_sphere(
+    center: impl Into<Vector3>,
+    radius: f32,
+    other_center: impl Into<Vector3>,
+    other_radius: f32,
+) -> bool {
+    let reach = radius + other_radius;
+    center.into().distance_squared(other_center.into()) <= reach * reach
+}
+
+/// Returns true if the point is inside or on the box
+pub fn point_in_aabb(point: impl Into<Vector3>, aabb: &Aabb) -> bool {
+    aabb.contains_point(point)
+}
+
+/// Returns true if the point is inside or on the box
+pub fn point_in_obb(point: impl Into<Vector3>, obb: &Obb) -> bool {
+    obb.contains_point(point)
+}
+
+/// Returns true if the boxes overlap or touch, by looking for an axis that separates them
+/// among their sides and the crossings of their sides
+pub fn obb_vs_obb(a: &Obb, b: &Obb) -> bool {
+    let axes_a = a.axes();
+    let axes_b = b.axes();
+    let offset = b.center - a.center;
+
+    let separates = |axis: Vector3| {
+        // the crossing of parallel sides has no direction, and can not separate
+        if axis.length_squared() < 1e-8 {
+            return false;
+        }
+        let reach = |axes: &[Vector3; 3], half_extents: Vector3| {
+            axes[0].dot(axis).abs() * half_extents.x
+                + axes[1].dot(axis).abs() * half_extents.y
+                + axes[2].dot(axis).abs() * half_extents.z
+        };
+        offset.dot(axis).abs() > reach(&axes_a, a.half_extents) + reach(&axes_b, b.half_extents)
+    };
+
+    let face_axes = axes_a.iter().chain(axes_b.iter()).copied();
+    let edge_axes = axes_a
+        .iter()
+        .flat_map(|i| axes_b.iter().map(move |j| i.cross(*j)));
+    !face_axes.chain(edge_axes).any(separates)
+}
+
+impl Object {
+    /// The box aligned with the axes of the world around the object, with its current
+    /// position, rotation, and scale. The instances are not included. None if the object
+    /// has no vertices
+    pub fn aabb(&self) -> Option<Aabb> {
+        let local = self.local_aabb()?;
+        let transform = self.transformation_matrix();
+        let corners = (0..8).map(|i| {
+            let corner = Vector3::new(
+                if i & 1 == 0 { local.min.x } else { local.max.x },
+                if i & 2 == 0 { local.min.y } else { local.max.y },
+                if i & 4 == 0 { local.min.z } else { local.max.z },
+            );
+            transform.transform_point3(corner)
+        });
+
+        Aabb::from_points(corners)
+    }
+
+    /// The box around the object turned with it, fitting closer than [Object::aabb] when the
+    /// object is rotated. None if the object has no vertices
+    pub fn obb(&self) -> Option<Obb> {
+        let local = self.local_aabb()?;
+        let transform = self.transformation_matrix();
+
+        Some(Obb {
+            center: transform.transform_point3(local.center()),
+            half_extents: local.half_extents() * self.size.abs(),
+            rotation: self.rotation_quaternion,
+        })
+    }
+
+    /// The box aligned with the axes of the world around all the instances of the object,
+    /// which is where it is drawn. None if the object has no vertices or no instances
+    pub fn instances_aabb(&self) -> Option<Aabb> {
+        let local = self.local_aabb()?;
+        let transform = self.transformation_matrix();
+        let corners = (0..8)
+            .map(|i| {
+                Vector3::new(
+                    if i & 1 == 0 { local.min.x } else { local.max.x },
+                    if i & 2 == 0 { local.min.y } else { local.max.y },
+                    if i & 4 == 0 { local.min.z } else { local.max.z },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        Aabb::from_points(self.instances.iter().flat_map(|instance| {
+            let matrix = instance.build().model * transform;
+            corners.iter().map(move |i| matrix.transform_point3(*i))
+        }))
+    }
+
+    /// The box around the vertices of the object, or of its shared mesh
+    pub(crate) fn local_aabb(&self) -> Option<Aabb> {
+        let vertices = match self.mesh.as_ref() {
+            Some(mesh) => mesh.vertices.as_slice(),
+            None => self.vertices.as_slice(),
+        };
+
+        Aabb::from_points(vertices.iter().map(|i| Vector3::from(i.position)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Vector3::splat(-1.0), Vector3::splat(1.0))
+    }
+
+    #[test]
+    fn aabb_overlaps_and_misses() {
+        let a = unit_box();
+        assert!(a.intersects(&Aabb::new([0.5, 0.5, 0.5], [2.0, 2.0, 2.0])));
+        assert!(!a.intersects(&Aabb::new([1.5, 0.0, 0.0], [2.0, 1.0, 1.0])));
+        // overlapping on two axes is not enough
+        assert!(!a.intersects(&Aabb::new([-0.5, 3.0, -0.5], [0.5, 4.0, 0.5])));
+    }
+
+    #[test]
+    fn touching_boxes_intersect() {
+        let a = unit_box();
+        let side = Aabb::new([1.0, -1.0, -1.0], [3.0, 1.0, 1.0]);
+        let corner = Aabb::new([1.0, 1.0, 1.0], [2.0, 2.0, 2.0]);
+        assert!(a.intersects(&side));
+        assert!(a.intersects(&corner));
+        assert!(Obb::from(a).intersects(&Obb::from(side)));
+        assert!(a.contains_point([1.0, 0.0, -1.0]));
+    }
+
+    #[test]
+    fn ray_hits_and_misses_a_box() {
+        let a = unit_box();
+        assert_eq!(
+            ray_vs_aabb([-5.0, 0.0, 0.0], [1.0, 0.0, 0.0], &a),
+            Some(4.0)
+        );
+        // the distance is in lengths of the direction
+        assert_eq!(
+            ray_vs_aabb([-5.0, 0.0, 0.0], [2.0, 0.0, 0.0], &a),
+            Some(2.0)
+        );
+        assert_eq!(ray_vs_aabb([-5.0, 0.0, 0.0], [-1.0, 0.0, 0.0], &a), None);
+        assert_eq!(ray_vs_aabb([-5.0, 2.0, 0.0], [1.0, 0.0, 0.0], &a), None);
+        let diagonal = ray_vs_aabb([-3.0, -3.0, -3.0], [1.0, 1.0, 1.0], &a).unwrap();
+        assert!((diagonal - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ray_starting_inside_a_box_hits_at_zero() {
+        let a = unit_box();
+        assert_eq!(ray_vs_aabb(Vector3::ZERO, [0.0, 1.0, 0.0], &a), Some(0.0));
+        assert_eq!(
+            ray_vs_aabb([0.5, 0.5, 0.5], [-1.0, 0.0, 0.0], &a),
+            Some(0.0)
+        );
+
+        let obb = Obb::new(
+            [3.0, 0.0, 0.0],
+            [1.0, 1.0, 1.0],
+            Quaternion::from_rotation_y(0.7),
+        );
+        assert_eq!(
+            ray_vs_obb([3.0, 0.0, 0.0], [0.0, 0.0, 1.0], &obb),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn ray_along_a_box_side_without_reaching_it() {
+        let a = unit_box();
+        // the direction has zero on two axes, which must not turn into a NaN hit
+        assert_eq!(ray_vs_aabb([0.0, 5.0, 0.0], [1.0, 0.0, 0.0], &a), None);
+        assert_eq!(
+            ray_vs_aabb([-5.0, 0.0, 0.0], [1.0, 0.0, 0.0], &a),
+            Some(4.0)
+        );
+    }
+
+    #[test]
+    fn ray_hits_a_rotated_box() {
+        // turned by 45 degrees, the corner reaches out to the square root of two
+        let obb = Obb::new(
+            Vector3::ZERO,
+            [1.0, 1.0, 1.0],
+            Quaternion::from_rotation_y(std::f32::consts::FRAC_PI_4),
+        );
+        let hit = ray_vs_obb([-5.0, 0.0, 0.0], [1.0, 0.0, 0.0], &obb).unwrap();
+        assert!((hit - (5.0 - std::f32::consts::SQRT_2)).abs() < 1e-5);
+        assert_eq!(ray_vs_obb([-5.0, 1.5, 0.0], [1.0, 0.0, 0.0], &obb), None);
+    }
+
+    #[test]
+    fn ray_meets_a_plane_from_either_side() {
+        let ground = Plane::GROUND;
+        assert_eq!(
+            ray_vs_plane([0.0, 4.0, 0.0], [0.0, -2.0, 0.0], &ground),
+            Some(2.0)
+        );
+        assert_eq!(
+            ray_vs_plane([0.0, -4.0, 0.0], [0.0, 1.0, 0.0], &ground),
+            Some(4.0)
+        );
+        assert_eq!(
+            ray_vs_plane([0.0, 4.0, 0.0], [0.0, 1.0, 0.0], &ground),
+            None
+        );
+    }
+
+    #[test]
+    fn ray_parallel_to_a_plane_misses() {
+        let ground = Plane::horizontal(1.0);
+        assert_eq!(
+            ray_vs_plane([0.0, 2.0, 0.0], [1.0, 0.0, 0.0], &ground),
+            None
+        );
+        // even when it runs on the plane
+        assert_eq!(
+            ray_vs_plane([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], &ground),
+            None
+        );
+    }
+
+    #[test]
+    fn plane_distances_and_projection() {
+        let plane = Plane::new([0.0, 0.0, 2.0], [0.0, 0.0, 3.0]);
+        assert_eq!(plane.normal, Vector3::Z);
+        assert_eq!(plane.signed_distance([1.0, 1.0, 5.0]), 2.0);
+        assert_eq!(plane.signed_distance([1.0, 1.0, 0.0]), -3.0);
+        assert_eq!(
+            plane.project_point([1.0, 2.0, 7.0]),
+            Vector3::new(1.0, 2.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn spheres_against_boxes_and_spheres() {
+        let a = unit_box();
+        assert!(sphere_vs_aabb([2.0, 0.0, 0.0], 1.0, &a));
+        assert!(!sphere_vs_aabb([2.0, 2.0, 0.0], 1.0, &a));
+        assert!(sphere_vs_sphere(Vector3::ZERO, 1.0, [2.0, 0.0, 0.0], 1.0));
+        assert!(!sphere_vs_sphere(Vector3::ZERO, 1.0, [2.1, 0.0, 0.0], 1.0));
+
+        let obb = Obb::new(
+            Vector3::ZERO,
+            [1.0, 1.0, 1.0],
+            Quaternion::from_rotation_z(std::f32::consts::FRAC_PI_4),
+        );
+        // the corner of the turned box reaches a sphere the box itself would not
+        assert!(sphere_vs_obb([0.0, 1.8, 0.0], 0.5, &obb));
+        assert!(!sphere_vs_aabb([0.0, 1.8, 0.0], 0.5, &a));
+    }
+
+    #[test]
+    fn rotated_boxes_are_separated_by_an_edge_axis() {
+        // the boxes overlap on all the axes of their sides but are apart along a crossing
+        // of their edges
+        let a = Obb::new(
+            Vector3::ZERO,
+            [1.0, 1.0, 1.0],
+            Quaternion::from_rotation_x(std::f32::consts::FRAC_PI_4),
+        );
+        let b = Obb::new(
+            [0.0, 2.1, 2.1],
+            [1.0, 1.0, 1.0],
+            Quaternion::from_rotation_z(std::f32::consts::FRAC_PI_4),
+        );
+        assert!(a.aabb().intersects(&b.aabb()));
+        assert!(!a.intersects(&b));
+        assert!(a.intersects(&Obb {
+            center: [0.0, 1.5, 1.5].into(),
+            ..b
+        }));
+    }
+
+    #[test]
+    fn obb_bounds_and_points() {
+        let obb = Obb::new(
+            [1.0, 0.0, 0.0],
+            [2.0, 1.0, 1.0],
+            Quaternion::from_rotation_z(std::f32::consts::FRAC_PI_2),
+        );
+        let aabb = obb.aabb();
+        assert!(aabb.min.abs_diff_eq(Vector3::new(0.0, -2.0, -1.0), 1e-5));
+        assert!(aabb.max.abs_diff_eq(Vector3::new(2.0, 2.0, 1.0), 1e-5));
+        assert!(obb.contains_point([1.0, 1.9, 0.0]));
+        assert!(!obb.contains_point([2.9, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn object_bounds_follow_its_transform() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let (vertices, indices) = crate::primitive_shapes::triangle_data();
+        let mut object = Object::new(
+            "triangle",
+            vertices,
+            indices,
+            crate::ObjectSettings::default(),
+            &mut renderer,
+        )
+        .unwrap();
+        let local = object.aabb().unwrap();
+
+        object.set_position([10.0, 0.0, 0.0]);
+        let moved = object.aabb().unwrap();
+        assert!(moved.min.abs_diff_eq(local.min + Vector3::X * 10.0, 1e-5));
+        assert!(moved.max.abs_diff_eq(local.max + Vector3::X * 10.0, 1e-5));
+
+        let obb = object.obb().unwrap();
+        assert!(obb.aabb().min.abs_diff_eq(moved.min, 1e-5));
+        assert!(ray_vs_aabb([10.0, 0.0, -5.0], [0.0, 0.0, 1.0], &moved).is_some());
+
+        object.translate([0.0, 2.0, 0.0]);
+        assert_eq!(object.position, Vector3::new(10.0, 2.0, 0.0));
+        let raised = object.aabb().unwrap();
+        assert!(raised.min.abs_diff_eq(moved.min + Vector3::Y * 2.0, 1e-5));
+    }
+}
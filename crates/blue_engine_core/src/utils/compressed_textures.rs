@@ -0,0 +1,715 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{Renderer, SamplerSettings, StringBuffer, Textures, error::Error};
+
+/// The first bytes of a DDS file
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+/// The first bytes of a KTX2 file
+const KTX2_IDENTIFIER: &[u8; 12] = &[
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// The families of block compressed formats a GPU can sample from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureCompression {
+    /// BC1 to BC7, also known as DXT and S3TC. Supported by most desktop GPUs
+    Bc,
+    /// ETC2 and EAC. Supported by most mobile GPUs
+    Etc2,
+    /// ASTC with any block size. Supported by most newer mobile GPUs
+    Astc,
+}
+impl TextureCompression {
+    /// All the families, in the order they are preferred in
+    pub const ALL: [TextureCompression; 3] = [Self::Bc, Self::Astc, Self::Etc2];
+
+    /// The feature the device needs to sample from the family
+    pub fn feature(&self) -> wgpu::Features {
+        match self {
+            Self::Bc => wgpu::Features::TEXTURE_COMPRESSION_BC,
+            Self::Etc2 => wgpu::Features::TEXTURE_COMPRESSION_ETC2,
+            Self::Astc => wgpu::Features::TEXTURE_COMPRESSION_ASTC,
+        }
+    }
+
+    /// The features of all the families together
+    pub fn all_features() -> wgpu::Features {
+        Self::ALL
+            .iter()
+            .fold(wgpu::Features::empty(), |features, i| {
+                features | i.feature()
+            })
+    }
+}
+
+/// A texture stored in its GPU format, read from a DDS or KTX2 file. The texture is uploaded
+/// as is without decoding, so block compressed textures take a fraction of the memory of
+/// the decoded image on the GPU
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedTexture {
+    /// The format of the texture on the GPU
+    pub format: wgpu::TextureFormat,
+    /// The width of the largest level
+    pub width: u32,
+    /// The height of the largest level
+    pub height: u32,
+    /// The data of each mip level, from the largest to the smallest
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedTexture {
+    /// Returns true if the data starts like a DDS or KTX2 file
+    pub fn is_container(data: &[u8]) -> bool {
+        data.starts_with(DDS_MAGIC) || data.starts_with(KTX2_IDENTIFIER)
+    }
+
+    /// Returns true if the extension of the path is of a DDS or KTX2 file
+    pub fn is_container_path(path: impl AsRef<std::path::Path>) -> bool {
+        path.as_ref()
+            .extension()
+            .and_then(|i| i.to_str())
+            .is_some_and(|i| i.eq_ignore_ascii_case("dds") || i.eq_ignore_ascii_case("ktx2"))
+    }
+
+    /// Reads a DDS or KTX2 file, depending on its first bytes
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        if data.starts_with(DDS_MAGIC) {
+            Self::from_dds(data)
+        } else if data.starts_with(KTX2_IDENTIFIER) {
+            Self::from_ktx2(data)
+        } else {
+            Err(Error::CompressedTextureError(
+                "the data is not of a DDS or KTX2 file",
+            ))
+        }
+    }
+
+    /// Reads a DDS or KTX2 file from the disk
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Reads a DDS file, with the BC1 to BC7 formats or uncompressed 8 bit RGBA
+    pub fn from_dds(data: &[u8]) -> Result<Self, Error> {
+        if !data.starts_with(DDS_MAGIC) || read_u32(data, 4)? != 124 {
+            return Err(Error::CompressedTextureError("the DDS header is not valid"));
+        }
+
+        let flags = read_u32(data, 8)?;
+        let height = read_u32(data, 12)?;
+        let width = read_u32(data, 16)?;
+        let level_count = if flags & 0x20000 != 0 {
+            read_u32(data, 28)?.max(1)
+        } else {
+            1
+        };
+        let pixel_flags = read_u32(data, 80)?;
+        let four_cc = data.get(84..88).unwrap_or_default();
+        let cube_map = read_u32(data, 112)? & 0x200 != 0;
+        if cube_map {
+            return Err(Error::CompressedTextureError(
+                "cube map DDS textures are not supported",
+            ));
+        }
+
+        let mut offset: usize = 128;
+        let format = match four_cc {
+            b"DXT1" => wgpu::TextureFormat::Bc1RgbaUnormSrgb,
+            b"DXT2" | b"DXT3" => wgpu::TextureFormat::Bc2RgbaUnormSrgb,
+            b"DXT4" | b"DXT5" => wgpu::TextureFormat::Bc3RgbaUnormSrgb,
+            b"ATI1" | b"BC4U" => wgpu::TextureFormat::Bc4RUnorm,
+            b"BC4S" => wgpu::TextureFormat::Bc4RSnorm,
+            b"ATI2" | b"BC5U" => wgpu::TextureFormat::Bc5RgUnorm,
+            b"BC5S" => wgpu::TextureFormat::Bc5RgSnorm,
+            b"DX10" => {
+                offset += 20;
+                if read_u32(data, 140)? > 1 {
+                    return Err(Error::CompressedTextureError(
+                        "array DDS textures are not supported",
+                    ));
+                }
+                dxgi_format(read_u32(data, 128)?)?
+            }
+            // uncompressed, told apart by the position of the red channel
+            _ if pixel_flags & 0x40 != 0 && read_u32(data, 88)? == 32 => {
+                match read_u32(data, 92)? {
+                    0x0000_00FF => wgpu::TextureFormat::Rgba8UnormSrgb,
+                    0x00FF_0000 => wgpu::TextureFormat::Bgra8UnormSrgb,
+                    _ => {
+                        return Err(Error::CompressedTextureError(
+                            "the pixel format of the DDS texture is not supported",
+                        ));
+                    }
+                }
+            }
+            _ => {
+                return Err(Error::CompressedTextureError(
+                    "the pixel format of the DDS texture is not supported",
+                ));
+            }
+        };
+
+        check_level_count(width, height, level_count)?;
+        let mut levels = Vec::with_capacity(level_count as usize);
+        for level in 0..level_count {
+            let size = level_size(format, width, height, level)?;
+            let level_data = offset
+                .checked_add(size)
+                .and_then(|end| data.get(offset..end))
+                .ok_or(Error::CompressedTextureError("the DDS file is cut short"))?;
+            levels.push(level_data.to_vec());
+            offset += size;
+        }
+
+        Self::new(format, width, height, levels)
+    }
+
+    /// Reads a KTX2 file, with the BCn, ETC2, EAC, ASTC formats or uncompressed 8 bit RGBA.
+    /// Basis Universal textures need to be transcoded to one of these beforehand, such as
+    /// with the `basisu` tool
+    pub fn from_ktx2(data: &[u8]) -> Result<Self, Error> {
+        if !data.starts_with(KTX2_IDENTIFIER) {
+            return Err(Error::CompressedTextureError(
+                "the KTX2 header is not valid",
+            ));
+        }
+
+        let vk_format = read_u32(data, 12)?;
+        let width = read_u32(data, 20)?;
+        let height = read_u32(data, 24)?;
+        let depth = read_u32(data, 28)?;
+        let layer_count = read_u32(data, 32)?;
+        let face_count = read_u32(data, 36)?;
+        let level_count = read_u32(data, 40)?.max(1);
+        let supercompression = read_u32(data, 44)?;
+
+        if depth > 1 || layer_count > 1 || face_count != 1 {
+            return Err(Error::CompressedTextureError(
+                "only 2D KTX2 textures are supported, without layers or faces",
+            ));
+        }
+        match supercompression {
+            0 => {}
+            1 => {
+                return Err(Error::CompressedTextureError(
+                    "Basis Universal KTX2 textures must be transcoded first",
+                ));
+            }
+            _ => {
+                return Err(Error::CompressedTextureError(
+                    "the supercompression of the KTX2 texture is not supported",
+                ));
+            }
+        }
+        // UASTC is stored with an undefined format, the same as other Basis textures
+        if vk_format == 0 {
+            return Err(Error::CompressedTextureError(
+                "Basis Universal KTX2 textures must be transcoded first",
+            ));
+        }
+        let format = vk_format_to_wgpu(vk_format)?;
+
+        check_level_count(width, height, level_count)?;
+        let mut levels = Vec::with_capacity(level_count as usize);
+        for level in 0..level_count {
+            let index = 80 + level as usize * 24;
+            let cut_short = || Error::CompressedTextureError("the KTX2 file is cut short");
+            let offset = usize::try_from(read_u64(data, index)?).map_err(|_| cut_short())?;
+            let length = usize::try_from(read_u64(data, index + 8)?).map_err(|_| cut_short())?;
+            if length != level_size(format, width, height, level)? {
+                return Err(Error::CompressedTextureError(
+                    "the size of a KTX2 level does not match its format",
+                ));
+            }
+            let level_data = offset
+                .checked_add(length)
+                .and_then(|end| data.get(offset..end))
+                .ok_or_else(cut_short)?;
+            levels.push(level_data.to_vec());
+        }
+
+        Self::new(format, width, height, levels)
+    }
+
+    fn new(
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        levels: Vec<Vec<u8>>,
+    ) -> Result<Self, Error> {
+        let (block_width, block_height) = format.block_dimensions();
+        check_level_count(width, height, levels.len() as u32)?;
+        if !width.is_multiple_of(block_width) || !height.is_multiple_of(block_height) {
+            return Err(Error::CompressedTextureError(
+                "the size of the texture is not a multiple of its block size",
+            ));
+        }
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    /// The family of the format, if it is block compressed
+    pub fn compression(&self) -> Option<TextureCompression> {
+        let features = self.format.required_features();
+        TextureCompression::ALL
+            .into_iter()
+            .find(|i| features.contains(i.feature()))
+    }
+
+    /// The features the device needs to use the texture
+    pub fn required_features(&self) -> wgpu::Features {
+        self.format.required_features()
+    }
+
+    /// The bytes the texture takes on the GPU
+    pub fn size_in_bytes(&self) -> usize {
+        self.levels.iter().map(|i| i.len()).sum()
+    }
+}
+
+/// Checks the size of the texture and its amount of levels, before anything is allocated for
+/// the levels
+fn check_level_count(width: u32, height: u32, level_count: u32) -> Result<(), Error> {
+    if width == 0 || height == 0 {
+        return Err(Error::CompressedTextureError("the texture has no size"));
+    }
+    if level_count > crate::utils::mipmaps::mip_level_count(width, height) {
+        return Err(Error::CompressedTextureError(
+            "the texture has more mip levels than its size allows",
+        ));
+    }
+
+    Ok(())
+}
+
+/// The size of the level in bytes, with the blocks tightly packed
+fn level_size(
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    level: u32,
+) -> Result<usize, Error> {
+    let (rows, bytes_per_row) = level_layout(format, width, height, level)?;
+    (rows as usize)
+        .checked_mul(bytes_per_row as usize)
+        .ok_or(Error::CompressedTextureError("the texture is too large"))
+}
+
+/// The amount of block rows of the level, and the bytes of each row
+fn level_layout(
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    level: u32,
+) -> Result<(u32, u32), Error> {
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_copy_size(None).unwrap_or(4);
+    let (Some(width), Some(height)) = (width.checked_shr(level), height.checked_shr(level)) else {
+        return Err(Error::CompressedTextureError(
+            "the texture has more mip levels than its size allows",
+        ));
+    };
+
+    let bytes_per_row = width
+        .max(1)
+        .div_ceil(block_width)
+        .checked_mul(block_size)
+        .ok_or(Error::CompressedTextureError("the texture is too large"))?;
+    Ok((height.max(1).div_ceil(block_height), bytes_per_row))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, Error> {
+    data.get(offset..offset + 4)
+        .map(|i| u32::from_le_bytes([i[0], i[1], i[2], i[3]]))
+        .ok_or(Error::CompressedTextureError("the header is cut short"))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, Error> {
+    Ok(read_u32(data, offset)? as u64 | (read_u32(data, offset + 4)? as u64) << 32)
+}
+
+/// The format of a DDS file with the DX10 header
+fn dxgi_format(format: u32) -> Result<wgpu::TextureFormat, Error> {
+    use wgpu::TextureFormat as F;
+
+    Ok(match format {
+        28 => F::Rgba8Unorm,
+        29 => F::Rgba8UnormSrgb,
+        87 => F::Bgra8Unorm,
+        91 => F::Bgra8UnormSrgb,
+        71 => F::Bc1RgbaUnorm,
+        72 => F::Bc1RgbaUnormSrgb,
+        74 => F::Bc2RgbaUnorm,
+        75 => F::Bc2RgbaUnormSrgb,
+        77 => F::Bc3RgbaUnorm,
+        78 => F::Bc3RgbaUnormSrgb,
+        80 => F::Bc4RUnorm,
+        81 => F::Bc4RSnorm,
+        83 => F::Bc5RgUnorm,
+        84 => F::Bc5RgSnorm,
+        95 => F::Bc6hRgbUfloat,
+        96 => F::Bc6hRgbFloat,
+        98 => F::Bc7RgbaUnorm,
+        99 => F::Bc7RgbaUnormSrgb,
+        _ => {
+            return Err(Error::CompressedTextureError(
+                "the DXGI format of the DDS texture is not supported",
+            ));
+        }
+    })
+}
+
+/// The format of a KTX2 file, given as a Vulkan format
+fn vk_format_to_wgpu(format: u32) -> Result<wgpu::TextureFormat, Error> {
+    use wgpu::{AstcBlock, AstcChannel, TextureFormat as F};
+
+    // the ASTC formats come in pairs of unorm and sRGB, one pair for each block size
+    if (157..=184).contains(&format) {
+        let blocks = [
+            AstcBlock::B4x4,
+            AstcBlock::B5x4,
+            AstcBlock::B5x5,
+            AstcBlock::B6x5,
+            AstcBlock::B6x6,
+            AstcBlock::B8x5,
+            AstcBlock::B8x6,
+            AstcBlock::B8x8,
+            AstcBlock::B10x5,
+            AstcBlock::B10x6,
+            AstcBlock::B10x8,
+            AstcBlock::B10x10,
+            AstcBlock::B12x10,
+            AstcBlock::B12x12,
+        ];
+        let index = format - 157;
+        return Ok(F::Astc {
+            block: blocks[index as usize / 2],
+            channel: if index.is_multiple_of(2) {
+                AstcChannel::Unorm
+            } else {
+                AstcChannel::UnormSrgb
+            },
+        });
+    }
+
+    Ok(match format {
+        37 => F::Rgba8Unorm,
+        43 => F::Rgba8UnormSrgb,
+        44 => F::Bgra8Unorm,
+        50 => F::Bgra8UnormSrgb,
+        131 | 133 => F::Bc1RgbaUnorm,
+        132 | 134 => F::Bc1RgbaUnormSrgb,
+        135 => F::Bc2RgbaUnorm,
+        136 => F::Bc2RgbaUnormSrgb,
+        137 => F::Bc3RgbaUnorm,
+        138 => F::Bc3RgbaUnormSrgb,
+        139 => F::Bc4RUnorm,
+        140 => F::Bc4RSnorm,
+        141 => F::Bc5RgUnorm,
+        142 => F::Bc5RgSnorm,
+        143 => F::Bc6hRgbUfloat,
+        144 => F::Bc6hRgbFloat,
+        145 => F::Bc7RgbaUnorm,
+        146 => F::Bc7RgbaUnormSrgb,
+        147 => F::Etc2Rgb8Unorm,
+        148 => F::Etc2Rgb8UnormSrgb,
+        149 => F::Etc2Rgb8A1Unorm,
+        150 => F::Etc2Rgb8A1UnormSrgb,
+        151 => F::Etc2Rgba8Unorm,
+        152 => F::Etc2Rgba8UnormSrgb,
+        153 => F::EacR11Unorm,
+        154 => F::EacR11Snorm,
+        155 => F::EacRg11Unorm,
+        156 => F::EacRg11Snorm,
+        _ => {
+            return Err(Error::CompressedTextureError(
+                "the Vulkan format of the KTX2 texture is not supported",
+            ));
+        }
+    })
+}
+
+impl Renderer {
+    /// The compressed format family best supported by the device. Useful to choose which
+    /// version of a texture to load when they are shipped in more than one format
+    pub fn texture_compression(&self) -> Option<TextureCompression> {
+        let features = self.device.features();
+        TextureCompression::ALL
+            .into_iter()
+            .find(|i| features.contains(i.feature()))
+    }
+
+    /// Creates a new texture for objects out of a [CompressedTexture], uploading its levels
+    /// as they are. Fails if the device can not sample from the format of the texture
+    pub fn build_compressed_texture(
+        &mut self,
+        name: impl StringBuffer,
+        compressed: &CompressedTexture,
+        texture_mode: impl Into<SamplerSettings>,
+    ) -> Result<Textures, Error> {
+        let missing = compressed.required_features() - self.device.features();
+        if !missing.is_empty() {
+            return Err(Error::MissingFeatures(missing));
+        }
+
+        let format = compressed.format;
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(name.as_str()),
+            size: wgpu::Extent3d {
+                width: compressed.width,
+                height: compressed.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: compressed.levels.len().max(1) as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (level, data) in compressed.levels.iter().enumerate() {
+            let level = level as u32;
+            let (rows, bytes_per_row) =
+                level_layout(format, compressed.width, compressed.height, level)?;
+            // the copy covers whole blocks, even where the level is smaller than one
+            let size = wgpu::Extent3d {
+                width: (compressed.width >> level).max(1),
+                height: (compressed.height >> level).max(1),
+                depth_or_array_layers: 1,
+            }
+            .physical_size(format);
+
+            self.queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: level,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                data,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(rows),
+                },
+                size,
+            );
+        }
+
+        // the blocks can not be downscaled, so they are only counted
+        let textures = self.build_texture_from_gpu(name, &texture, texture_mode);
+        self.track_texture(&textures, &texture);
+        Ok(textures)
+    }
+}
+
+/// Reads the texture data as a [CompressedTexture], if it is of a DDS or KTX2 file
+pub(crate) fn read_compressed(
+    texture_data: &crate::TextureData,
+) -> Option<Result<CompressedTexture, Error>> {
+    match texture_data {
+        crate::TextureData::Bytes(data) if CompressedTexture::is_container(data) => {
+            Some(CompressedTexture::from_bytes(data))
+        }
+        crate::TextureData::Path(path) if CompressedTexture::is_container_path(path) => {
+            Some(CompressedTexture::open(path))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// A DDS file with the four character code, and the levels filled with their index
+    fn dds(four_cc: &[u8; 4], width: u32, height: u32, levels: &[usize]) -> Vec<u8> {
+        let mut data = vec![0; 128];
+        data[..4].copy_from_slice(DDS_MAGIC);
+        write_u32(&mut data, 4, 124);
+        write_u32(&mut data, 8, 0x1007 | 0x20000);
+        write_u32(&mut data, 12, height);
+        write_u32(&mut data, 16, width);
+        write_u32(&mut data, 28, levels.len() as u32);
+        write_u32(&mut data, 76, 32);
+        write_u32(&mut data, 80, 0x4);
+        data[84..88].copy_from_slice(four_cc);
+        for (level, size) in levels.iter().enumerate() {
+            data.extend(std::iter::repeat_n(level as u8, *size));
+        }
+
+        data
+    }
+
+    /// A KTX2 file of 8 bit sRGB RGBA, with the levels filled with their index
+    fn ktx2(width: u32, height: u32, levels: &[usize]) -> Vec<u8> {
+        let mut data = vec![0; 80 + levels.len() * 24];
+        data[..12].copy_from_slice(KTX2_IDENTIFIER);
+        write_u32(&mut data, 12, 43);
+        write_u32(&mut data, 16, 1);
+        write_u32(&mut data, 20, width);
+        write_u32(&mut data, 24, height);
+        write_u32(&mut data, 36, 1);
+        write_u32(&mut data, 40, levels.len() as u32);
+        for (level, size) in levels.iter().enumerate() {
+            let index = 80 + level * 24;
+            let offset = data.len() as u64;
+            data[index..index + 8].copy_from_slice(&offset.to_le_bytes());
+            data[index + 8..index + 16].copy_from_slice(&(*size as u64).to_le_bytes());
+            data[index + 16..index + 24].copy_from_slice(&(*size as u64).to_le_bytes());
+            data.extend(std::iter::repeat_n(level as u8, *size));
+        }
+
+        data
+    }
+
+    #[test]
+    fn dds_with_mip_levels() {
+        // 8 by 8 is two by two blocks of 8 bytes, then a block for each smaller level
+        let texture = CompressedTexture::from_bytes(&dds(b"DXT1", 8, 8, &[32, 8, 8, 8])).unwrap();
+        assert_eq!(texture.format, wgpu::TextureFormat::Bc1RgbaUnormSrgb);
+        assert_eq!((texture.width, texture.height), (8, 8));
+        assert_eq!(texture.levels.len(), 4);
+        assert_eq!(texture.levels[0], [0; 32]);
+        assert_eq!(texture.levels[3], [3; 8]);
+        assert_eq!(texture.size_in_bytes(), 56);
+        assert_eq!(texture.compression(), Some(TextureCompression::Bc));
+
+        let texture = CompressedTexture::from_bytes(&dds(b"DXT5", 4, 4, &[16])).unwrap();
+        assert_eq!(texture.format, wgpu::TextureFormat::Bc3RgbaUnormSrgb);
+        assert_eq!(texture.levels, [vec![0; 16]]);
+    }
+
+    #[test]
+    fn dds_with_dx10_and_uncompressed_formats() {
+        let mut data = dds(b"DX10", 4, 4, &[]);
+        data.extend_from_slice(&[0; 20]);
+        write_u32(&mut data, 28, 1);
+        write_u32(&mut data, 128, 99);
+        write_u32(&mut data, 140, 1);
+        data.extend_from_slice(&[7; 16]);
+        let texture = CompressedTexture::from_dds(&data).unwrap();
+        assert_eq!(texture.format, wgpu::TextureFormat::Bc7RgbaUnormSrgb);
+        assert_eq!(texture.levels, [vec![7; 16]]);
+
+        let mut data = dds(&[0; 4], 2, 2, &[16, 4]);
+        write_u32(&mut data, 80, 0x41);
+        write_u32(&mut data, 88, 32);
+        write_u32(&mut data, 92, 0x00FF_0000);
+        let texture = CompressedTexture::from_dds(&data).unwrap();
+        assert_eq!(texture.format, wgpu::TextureFormat::Bgra8UnormSrgb);
+        assert_eq!(texture.compression(), None);
+        assert_eq!(texture.levels.len(), 2);
+    }
+
+    #[test]
+    fn ktx2_with_mip_levels() {
+        let texture = CompressedTexture::from_bytes(&ktx2(4, 2, &[32, 8, 4])).unwrap();
+        assert_eq!(texture.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!((texture.width, texture.height), (4, 2));
+        assert_eq!(texture.levels, [vec![0; 32], vec![1; 8], vec![2; 4]]);
+    }
+
+    #[test]
+    fn truncated_files() {
+        for data in [dds(b"DXT1", 8, 8, &[32, 8, 8, 8]), ktx2(4, 4, &[64, 16, 4])] {
+            for length in 0..data.len() {
+                assert!(CompressedTexture::from_bytes(&data[..length]).is_err());
+            }
+        }
+        assert!(CompressedTexture::from_bytes(b"not a texture").is_err());
+    }
+
+    #[test]
+    fn corrupt_dds_headers() {
+        let valid = dds(b"DXT1", 8, 8, &[32, 8, 8, 8]);
+        let corrupt = |offset: usize, value: u32| {
+            let mut data = valid.clone();
+            write_u32(&mut data, offset, value);
+            CompressedTexture::from_dds(&data)
+        };
+
+        // more levels than the size allows, which is checked before allocating for them
+        assert!(corrupt(28, 5).is_err());
+        assert!(corrupt(28, u32::MAX).is_err());
+        // sizes whose levels would not fit in memory
+        assert!(corrupt(16, u32::MAX).is_err());
+        assert!(corrupt(12, u32::MAX).is_err());
+        assert!(corrupt(16, 0).is_err());
+        assert!(corrupt(4, 123).is_err());
+        assert!(corrupt(112, 0x200).is_err());
+        assert!(corrupt(84, u32::from_le_bytes(*b"NOPE")).is_err());
+
+        let mut uncompressed = valid.clone();
+        write_u32(&mut uncompressed, 84, 0);
+        write_u32(&mut uncompressed, 80, 0x40);
+        write_u32(&mut uncompressed, 88, 32);
+        write_u32(&mut uncompressed, 92, 0x0000_FF00);
+        assert!(CompressedTexture::from_dds(&uncompressed).is_err());
+    }
+
+    #[test]
+    fn corrupt_ktx2_headers() {
+        let valid = ktx2(4, 4, &[64, 16, 4]);
+        let corrupt = |offset: usize, value: u64| {
+            let mut data = valid.clone();
+            data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+            CompressedTexture::from_ktx2(&data)
+        };
+        let corrupt_u32 = |offset: usize, value: u32| {
+            let mut data = valid.clone();
+            write_u32(&mut data, offset, value);
+            CompressedTexture::from_ktx2(&data)
+        };
+
+        assert!(corrupt_u32(40, 4).is_err());
+        assert!(corrupt_u32(40, u32::MAX).is_err());
+        assert!(corrupt_u32(20, u32::MAX).is_err());
+        assert!(corrupt_u32(24, 0).is_err());
+        assert!(corrupt_u32(12, 0).is_err());
+        assert!(corrupt_u32(12, 1_000).is_err());
+        assert!(corrupt_u32(32, 2).is_err());
+        assert!(corrupt_u32(36, 6).is_err());
+        assert!(corrupt_u32(44, 1).is_err());
+        assert!(corrupt_u32(44, 2).is_err());
+
+        // level offsets and lengths past the end of the file, or past the end of memory
+        assert!(corrupt(80, u64::MAX).is_err());
+        assert!(corrupt(80, u64::MAX - 63).is_err());
+        assert!(corrupt(80, valid.len() as u64).is_err());
+        assert!(corrupt(88, 65).is_err());
+        assert!(corrupt(88, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn level_sizes_do_not_overflow() {
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        assert_eq!(level_layout(format, 8, 4, 1).unwrap(), (2, 16));
+        assert_eq!(level_layout(format, 8, 4, 3).unwrap(), (1, 4));
+        assert!(level_layout(format, 8, 4, 32).is_err());
+        assert!(level_layout(format, u32::MAX, 1, 0).is_err());
+        assert_eq!(
+            level_size(wgpu::TextureFormat::Bc1RgbaUnorm, 5, 5, 0).unwrap(),
+            32
+        );
+        assert!(level_size(format, 1 << 30, 1, 0).is_err());
+        assert_eq!(
+            level_size(format, 1 << 20, 1 << 20, 0).ok(),
+            (1usize << 20).checked_mul(1 << 22)
+        );
+    }
+}
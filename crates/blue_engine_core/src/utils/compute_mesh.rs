@@ -0,0 +1,422 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use wgpu::util::DeviceExt;
+
+use super::default_resources::COMPUTE_MESH_SHADER;
+use crate::{
+    ObjectStorage, Renderer, StringBuffer, UnsignedIntType, Vertex, VertexBuffers, error::Error,
+    objects::ShaderBuilder,
+};
+
+/// The placeholder the bindings and helpers of the compute meshes are substituted for
+const COMPUTE_MESH_PLACEHOLDER: &str = "//@COMPUTE_MESH";
+
+/// The uniforms of a compute mesh, laid out as `ComputeMeshInfo` in the shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ComputeMeshInfo {
+    time: f32,
+    delta_time: f32,
+    frame: u32,
+    vertex_count: u32,
+}
+
+/// The vertices of an object written by a compute shader before every frame, such as the
+/// waves of an ocean or the surface of marching cubes, and drawn without going through the
+/// CPU. See [Renderer::add_compute_mesh].
+///
+/// The source needs a `@compute fn cs_main`, and writes the vertices with
+/// `write_vertex(index, position, texture_coordinates, normal)`. The `mesh` uniform holds
+/// `time`, `delta_time`, `frame`, and `vertex_count`, and the parameters given with
+/// [ComputeMesh::set_parameters] are at `@group(0) @binding(2)`
+///
+/// ```ignore
+/// @compute @workgroup_size(64)
+/// fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+///     let x = f32(id.x % 64u) / 63.0;
+///     let z = f32(id.x / 64u) / 63.0;
+///     let y = sin(x * 10.0 + mesh.time) * 0.1;
+///     write_vertex(id.x, vec3<f32>(x, y, z), vec2<f32>(x, z), vec3<f32>(0.0, 1.0, 0.0));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ComputeMesh {
+    /// Should the vertices be written every frame. They keep their last values while disabled
+    pub enabled: bool,
+    /// The amount of workgroups dispatched, by default enough for one invocation for every
+    /// vertex with a workgroup size of 64
+    pub workgroups: (u32, u32, u32),
+    shader: String,
+    vertex_count: u32,
+    indices: Vec<UnsignedIntType>,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    info_buffer: wgpu::Buffer,
+    /// The parameters, kept to upload them again on a new device
+    parameters: Option<(Vec<u8>, wgpu::Buffer)>,
+    /// The pipeline and its bind group, made again when the parameters are first set
+    pipeline: Option<(wgpu::ComputePipeline, wgpu::BindGroup)>,
+}
+
+impl ComputeMesh {
+    /// The amount of vertices written by the shader
+    pub fn vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
+    /// The buffer the vertices are written to, which can also be written by other compute
+    /// passes
+    pub fn vertex_buffer(&self) -> &wgpu::Buffer {
+        &self.vertex_buffer
+    }
+
+    /// Sets the parameters given to the shader at `@group(0) @binding(2)`, as a uniform
+    pub fn set_parameters<T: bytemuck::Pod>(&mut self, renderer: &Renderer, parameters: T) {
+        let data = bytemuck::bytes_of(&parameters).to_vec();
+        match self.parameters.as_mut() {
+            Some((old, buffer)) if old.len() == data.len() => {
+                renderer.queue.write_buffer(buffer, 0, &data);
+                *old = data;
+            }
+            _ => {
+                let buffer = build_parameters_buffer(&renderer.device, &data);
+                self.parameters = Some((data, buffer));
+                self.pipeline = None;
+            }
+        }
+    }
+
+    /// Makes the buffers again on the device, with the last vertices lost
+    fn build_buffers(&mut self, device: &wgpu::Device) {
+        let (vertex_buffer, index_buffer, info_buffer) =
+            build_buffers(device, self.vertex_count, &self.indices);
+        self.vertex_buffer = vertex_buffer;
+        self.index_buffer = index_buffer;
+        self.info_buffer = info_buffer;
+        if let Some((data, buffer)) = self.parameters.as_mut() {
+            *buffer = build_parameters_buffer(device, data);
+        }
+        self.pipeline = None;
+    }
+
+    /// The buffers the object is drawn with
+    fn vertex_buffers(&self) -> VertexBuffers {
+        VertexBuffers {
+            vertex_buffer: self.vertex_buffer.clone(),
+            index_buffer: self.index_buffer.clone(),
+            length: self.indices.len() as u32,
+        }
+    }
+
+    fn build_pipeline(&self, device: &wgpu::Device) -> (wgpu::ComputePipeline, wgpu::BindGroup) {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compute Mesh Shader"),
+            source: wgpu::ShaderSource::Wgsl(self.shader.as_str().into()),
+        });
+
+        let buffer_entry = |binding: u32, ty: wgpu::BufferBindingType| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let mut layout_entries = vec![
+            buffer_entry(0, wgpu::BufferBindingType::Storage { read_only: false }),
+            buffer_entry(1, wgpu::BufferBindingType::Uniform),
+        ];
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: self.vertex_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: self.info_buffer.as_entire_binding(),
+            },
+        ];
+        if let Some((_, buffer)) = self.parameters.as_ref() {
+            layout_entries.push(buffer_entry(2, wgpu::BufferBindingType::Uniform));
+            entries.push(wgpu::BindGroupEntry {
+                binding: 2,
+                resource: buffer.as_entire_binding(),
+            });
+        }
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Compute Mesh Bind Group Layout"),
+            entries: &layout_entries,
+        });
+        crate::utils::allocation_audit::count_bind_group();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Compute Mesh Bind Group"),
+            layout: &bind_group_layout,
+            entries: &entries,
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Compute Mesh Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Compute Mesh Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        (pipeline, bind_group)
+    }
+}
+
+/// The compute meshes of the objects, dispatched before the shadows and the main pass
+#[derive(Debug)]
+pub struct ComputeMeshes {
+    meshes: std::collections::HashMap<std::sync::Arc<str>, ComputeMesh>,
+    started: crate::time::Instant,
+    last_frame: Option<crate::time::Instant>,
+    frame: u32,
+}
+
+impl ComputeMeshes {
+    /// Creates a new empty list of compute meshes
+    pub fn new() -> Self {
+        Self {
+            meshes: std::collections::HashMap::new(),
+            started: crate::time::Instant::now(),
+            last_frame: None,
+            frame: 0,
+        }
+    }
+
+    /// The amount of compute meshes
+    pub fn len(&self) -> usize {
+        self.meshes.len()
+    }
+
+    /// Returns true if there are no compute meshes
+    pub fn is_empty(&self) -> bool {
+        self.meshes.is_empty()
+    }
+
+    /// Restarts the time and the frame count given to the shaders
+    pub fn restart(&mut self) {
+        self.started = crate::time::Instant::now();
+        self.last_frame = None;
+        self.frame = 0;
+    }
+
+    /// Runs the shaders of the compute meshes of the objects in the storage
+    pub(crate) fn dispatch(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        objects: &ObjectStorage,
+    ) {
+        if self.meshes.is_empty() {
+            return;
+        }
+
+        let now = crate::time::Instant::now();
+        let delta_time = self.last_frame.map_or(0.0, |i| (now - i).as_secs_f32());
+        self.last_frame = Some(now);
+
+        for (name, mesh) in self.meshes.iter_mut() {
+            if !mesh.enabled || !objects.contains_key(name.as_ref()) {
+                continue;
+            }
+
+            let info = ComputeMeshInfo {
+                time: (now - self.started).as_secs_f32(),
+                delta_time,
+                frame: self.frame,
+                vertex_count: mesh.vertex_count,
+            };
+            queue.write_buffer(&mesh.info_buffer, 0, bytemuck::bytes_of(&info));
+
+            if mesh.pipeline.is_none() {
+                mesh.pipeline = Some(mesh.build_pipeline(device));
+            }
+            let Some((pipeline, bind_group)) = mesh.pipeline.as_ref() else {
+                continue;
+            };
+
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Compute Mesh Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.set_bind_group(0, bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                mesh.workgroups.0,
+                mesh.workgroups.1,
+                mesh.workgroups.2,
+            );
+        }
+
+        self.frame = self.frame.wrapping_add(1);
+    }
+}
+impl Default for ComputeMeshes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Makes the vertex, index, and info buffers of a compute mesh
+fn build_buffers(
+    device: &wgpu::Device,
+    vertex_count: u32,
+    indices: &[UnsignedIntType],
+) -> (wgpu::Buffer, wgpu::Buffer, wgpu::Buffer) {
+    crate::utils::allocation_audit::count_buffer();
+    let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Compute Mesh Vertex Buffer"),
+        size: (vertex_count.max(1) as usize * std::mem::size_of::<Vertex>()) as u64,
+        usage: wgpu::BufferUsages::VERTEX
+            | wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    crate::utils::allocation_audit::count_buffer();
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Compute Mesh Index Buffer"),
+        contents: bytemuck::cast_slice(indices),
+        usage: wgpu::BufferUsages::INDEX,
+    });
+
+    crate::utils::allocation_audit::count_buffer();
+    let info_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Compute Mesh Info"),
+        size: std::mem::size_of::<ComputeMeshInfo>() as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    (vertex_buffer, index_buffer, info_buffer)
+}
+
+fn build_parameters_buffer(device: &wgpu::Device, data: &[u8]) -> wgpu::Buffer {
+    crate::utils::allocation_audit::count_buffer();
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Compute Mesh Parameters"),
+        contents: data,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    })
+}
+
+impl Renderer {
+    /// Draws the object with vertices written by the compute shader before every frame,
+    /// instead of its own, see [ComputeMesh]. The indices are given as they stay the same,
+    /// or are every three vertices in a row making a triangle if None. Replaces the compute
+    /// mesh the object had.
+    ///
+    /// Returns an error mapped to the lines of the source if the shader fails to compile, or
+    /// if there are more vertices than the indices can refer to. The objects with a compute
+    /// mesh are not merged by the static batching, nor culled by meshlets
+    pub fn add_compute_mesh(
+        &mut self,
+        objects: &mut ObjectStorage,
+        object: impl StringBuffer,
+        source: impl Into<String>,
+        vertex_count: u32,
+        indices: Option<Vec<UnsignedIntType>>,
+    ) -> Result<&mut ComputeMesh, Error> {
+        let Some(object_value) = objects.get_mut(object.as_str()) else {
+            return Err(Error::Custom(format!(
+                "The object {} does not exist",
+                object.as_str()
+            )));
+        };
+
+        let indices = match indices {
+            Some(indices) => indices,
+            None => {
+                if vertex_count as u64 > UnsignedIntType::MAX as u64 + 1 {
+                    return Err(Error::Custom(format!(
+                        "{vertex_count} vertices are more than the indices can refer to"
+                    )));
+                }
+                (0..vertex_count).map(|i| i as UnsignedIntType).collect()
+            }
+        };
+
+        // the helpers go after the source, so the lines of the source stay the same
+        let mut shader_builder = ShaderBuilder::new(String::new(), None);
+        shader_builder.configs.push((
+            COMPUTE_MESH_PLACEHOLDER.to_string(),
+            Box::new(|_| COMPUTE_MESH_SHADER.to_string()),
+        ));
+        shader_builder.set_shader(format!("{}\n{COMPUTE_MESH_PLACEHOLDER}", source.into()));
+        shader_builder.validate()?;
+
+        let (vertex_buffer, index_buffer, info_buffer) =
+            build_buffers(&self.device, vertex_count, &indices);
+        let mesh = ComputeMesh {
+            enabled: true,
+            workgroups: (vertex_count.div_ceil(64).max(1), 1, 1),
+            shader: shader_builder.shader,
+            vertex_count,
+            indices,
+            vertex_buffer,
+            index_buffer,
+            info_buffer,
+            parameters: None,
+            pipeline: None,
+        };
+
+        object_value.gpu_vertices = Some(mesh.vertex_buffers());
+        object_value.changed = true;
+
+        let name = object_value.name.clone();
+        self.compute_meshes.meshes.insert(name.clone(), mesh);
+        Ok(self
+            .compute_meshes
+            .meshes
+            .get_mut(&name)
+            .expect("the compute mesh was just added"))
+    }
+
+    /// Returns the compute mesh of the object, to change its settings or parameters
+    pub fn compute_mesh(&mut self, object: impl StringBuffer) -> Option<&mut ComputeMesh> {
+        self.compute_meshes.meshes.get_mut(object.as_str())
+    }
+
+    /// Removes the compute mesh of the object, which is then drawn with its own vertices again.
+    /// Returns false if it had none
+    pub fn remove_compute_mesh(
+        &mut self,
+        objects: &mut ObjectStorage,
+        object: impl StringBuffer,
+    ) -> bool {
+        if let Some(object_value) = objects.get_mut(object.as_str()) {
+            object_value.gpu_vertices = None;
+            object_value.changed = true;
+        }
+
+        self.compute_meshes.meshes.remove(object.as_str()).is_some()
+    }
+
+    /// Makes the buffers of the compute meshes again on the new device, and gives them to
+    /// their objects. The vertices are written again with the next frame
+    pub(crate) fn recover_compute_meshes(&mut self, objects: &mut ObjectStorage) {
+        for (name, mesh) in self.compute_meshes.meshes.iter_mut() {
+            mesh.build_buffers(&self.device);
+            if let Some(object) = objects.get_mut(name.as_ref()) {
+                object.gpu_vertices = Some(mesh.vertex_buffers());
+            }
+        }
+    }
+}
@@ -0,0 +1,35 @@
+// Added after the source of the compute meshes
+
+struct ComputeMeshInfo {
+    time: f32,
+    delta_time: f32,
+    frame: u32,
+    vertex_count: u32,
+};
+
+// the vertices of the mesh, 8 floats each: the position, texture coordinates, and normal
+@group(0) @binding(0)
+var<storage, read_write> vertices: array<f32>;
+
+@group(0) @binding(1)
+var<uniform> mesh: ComputeMeshInfo;
+
+fn write_vertex(index: u32, position: vec3<f32>, texture_coordinates: vec2<f32>, normal: vec3<f32>) {
+    if index >= mesh.vertex_count {
+        return;
+    }
+    let offset = index * 8u;
+    vertices[offset] = position.x;
+    vertices[offset + 1u] = position.y;
+    vertices[offset + 2u] = position.z;
+    vertices[offset + 3u] = texture_coordinates.x;
+    vertices[offset + 4u] = texture_coordinates.y;
+    vertices[offset + 5u] = normal.x;
+    vertices[offset + 6u] = normal.y;
+    vertices[offset + 7u] = normal.z;
+}
+
+fn read_position(index: u32) -> vec3<f32> {
+    let offset = index * 8u;
+    return vec3<f32>(vertices[offset], vertices[offset + 1u], vertices[offset + 2u]);
+}
@@ -7,6 +7,78 @@
 /// The default shader file code
 pub const DEFAULT_SHADER: &str = include_str!("./default_shader.wgsl");
 
+/// The default shader of the objects with quantized vertices, see
+/// [crate::utils::vertex_quantization]
+pub const QUANTIZED_SHADER: &str = include_str!("./quantized_shader.wgsl");
+
+/// The default shader of the objects that read their vertices from a storage buffer, see
+/// [crate::utils::vertex_pulling]
+pub const VERTEX_PULLING_SHADER: &str = include_str!("./vertex_pulling_shader.wgsl");
+
+/// The default shader of the objects animated by a skinned crowd, see
+/// [crate::utils::skinned_instancing]
+pub const SKINNED_INSTANCING_SHADER: &str = include_str!("./skinned_instancing.wgsl");
+
+/// The default shader of the objects with morph targets, see [crate::utils::morph_targets]
+pub const MORPH_TARGET_SHADER: &str = include_str!("./morph_target_shader.wgsl");
+
+/// The bindings and helpers added to the shaders of the compute meshes
+pub const COMPUTE_MESH_SHADER: &str = include_str!("./compute_mesh.wgsl");
+
+/// The shader used to draw the screen space overlay
+pub const OVERLAY_SHADER: &str = include_str!("./overlay_shader.wgsl");
+
+/// The compute shader used for image analysis
+pub const IMAGE_ANALYSIS_SHADER: &str = include_str!("./image_analysis.wgsl");
+
+/// The compute shaders used for image processing
+pub const IMAGE_PROCESSING_SHADER: &str = include_str!("./image_processing.wgsl");
+
+/// The shader used to map the HDR scene into the range of the window
+pub const HDR_TONEMAPPING_SHADER: &str = include_str!("./hdr_tonemapping.wgsl");
+
+/// The shader that fills the background with a vertical gradient
+pub const CLEAR_GRADIENT_SHADER: &str = include_str!("./clear_gradient.wgsl");
+
+/// The shader used to draw the infinite ground grid
+pub const GRID_SHADER: &str = include_str!("./grid.wgsl");
+
+/// The shader used to downscale the frames kept in the frame history
+pub const FRAME_HISTORY_SHADER: &str = include_str!("./frame_history.wgsl");
+
+/// The uniforms and entry points added to the shaders of the shader sandbox
+pub const SHADER_SANDBOX_SHADER: &str = include_str!("./shader_sandbox.wgsl");
+
+/// The shader used to fade the frame by the opacity of the window
+pub const WINDOW_OPACITY_SHADER: &str = include_str!("./window_opacity.wgsl");
+
+/// The shader that draws the motion of the objects since the previous frame
+pub const MOTION_VECTORS_SHADER: &str = include_str!("./motion_vectors.wgsl");
+
+/// The compute shader that reduces the depth of the scene into the depth pyramid
+pub const DEPTH_PYRAMID_SHADER: &str = include_str!("./depth_pyramid.wgsl");
+
+/// The shader that draws the boxes of the objects for the occlusion queries
+pub const OCCLUSION_CULLING_SHADER: &str = include_str!("./occlusion_culling.wgsl");
+
+/// The shader that draws the depth of the objects into the shadow atlas
+pub const SHADOW_ATLAS_SHADER: &str = include_str!("./shadow_atlas.wgsl");
+
+/// The shader that draws the object, instance and triangle of each pixel
+pub const VISIBILITY_BUFFER_SHADER: &str = include_str!("./visibility_buffer.wgsl");
+
+/// The shader that resolves the visibility buffer into the colors of the scene
+pub const VISIBILITY_RESOLVE_SHADER: &str = include_str!("./visibility_resolve.wgsl");
+
+/// The shader that darkens the scene where the rays towards the light are blocked
+pub const RAY_TRACED_SHADOWS_SHADER: &str = include_str!("./ray_traced_shadows.wgsl");
+
+/// The shader that samples the page cache of a virtual texture
+pub const VIRTUAL_TEXTURE_SHADER: &str = include_str!("./virtual_texture.wgsl");
+
+/// The shader that draws the normals or the depth of an object into a baked sprite
+pub const SPRITE_BAKING_SHADER: &str = include_str!("./sprite_baking.wgsl");
+
 /// The default texture thats loaded for each object
 pub const DEFAULT_TEXTURE: &[u8] = &[
     137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0,
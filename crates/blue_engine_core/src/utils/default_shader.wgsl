@@ -2,6 +2,10 @@
 
 //@CAMERA_STRUCT
 
+//@FOG
+
+//@VARIATION
+
 struct TransformationUniforms {
     transform_matrix: mat4x4<f32>,
 };
@@ -16,6 +20,7 @@ struct VertexInput {
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) texture_coordinates: vec2<f32>,
+    //@FOG_OUTPUT
 };
 
 struct InstanceInput {
@@ -38,12 +43,16 @@ fn vs_main(input: VertexInput, instance: InstanceInput) -> VertexOutput {
     var out: VertexOutput;
     out.texture_coordinates = input.texture_coordinates;
     //@CAMERA_VERTEX
+    //@FOG_VERTEX
     return out;
 }
 
 // Fragment Stage
 struct FragmentUniforms {
     color: vec4<f32>,
+    random: f32,
+    time_offset: f32,
+    seed: u32,
 };
 @group(2) @binding(1)
 var<uniform> fragment_uniforms: FragmentUniforms;
@@ -57,8 +66,8 @@ var sampler_diffuse: sampler;
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
     if fragment_uniforms.color.w == 0.0 {
-        return textureSample(texture_diffuse, sampler_diffuse, input.texture_coordinates);
+        return apply_fog(textureSample(texture_diffuse, sampler_diffuse, input.texture_coordinates), input.fog_distance);
     } else {
-        return textureSample(texture_diffuse, sampler_diffuse, input.texture_coordinates) * fragment_uniforms.color;
+        return apply_fog(textureSample(texture_diffuse, sampler_diffuse, input.texture_coordinates) * fragment_uniforms.color, input.fog_distance);
     }
 }
\ No newline at end of file
@@ -0,0 +1,288 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use super::default_resources::DEPTH_PYRAMID_SHADER;
+
+/// The format of the depth pyramid, the nearest depth on X and the farthest on Y
+pub const DEPTH_PYRAMID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Float;
+/// The size of the groups of the compute shader on each axis
+const WORKGROUP_SIZE: u32 = 8;
+
+/// The texture of the pyramid, with the views of each of its levels
+#[derive(Debug)]
+struct PyramidTarget {
+    size: (u32, u32),
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    levels: Vec<wgpu::TextureView>,
+    /// The bind groups of the levels after the first, each reading the level before
+    bind_groups: Vec<wgpu::BindGroup>,
+}
+
+/// The pipelines of the first level and of the ones after, with their layouts
+#[derive(Debug)]
+struct PyramidPipelines {
+    copy_layout: wgpu::BindGroupLayout,
+    copy: wgpu::ComputePipeline,
+    reduce_layout: wgpu::BindGroupLayout,
+    reduce: wgpu::ComputePipeline,
+}
+
+/// A hierarchical depth buffer (Hi-Z) of the scene, made after the main pass of each frame.
+/// The first level is the size of the frame, and each level after is half the size of the
+/// one before, down to a single texel. A texel holds the nearest and the farthest depth of
+/// the part of the scene it covers, in a texture of [DEPTH_PYRAMID_FORMAT].
+///
+/// The farthest depth tells if something is hidden behind the scene with a single read,
+/// such as the bounds of an object for occlusion culling, and the nearest lets rays skip the
+/// empty space in screen space reflections. The pyramid is read through
+/// [DepthPyramid::view], or as [crate::utils::frame_graph::DEPTH_PYRAMID] by the passes of
+/// the frame graph, with `textureLoad` and the level.
+#[derive(Debug)]
+pub struct DepthPyramid {
+    /// Should the pyramid be made
+    pub enabled: bool,
+    target: Option<PyramidTarget>,
+    pipelines: Option<PyramidPipelines>,
+}
+
+impl DepthPyramid {
+    /// Creates the depth pyramid, disabled
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            target: None,
+            pipelines: None,
+        }
+    }
+
+    /// Drops the pyramid and its pipelines, so they are made again on the new device
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.target = None;
+        self.pipelines = None;
+    }
+
+    /// The view of all the levels of the latest pyramid, if it is enabled
+    pub fn view(&self) -> Option<&wgpu::TextureView> {
+        match self.enabled {
+            true => self.target.as_ref().map(|i| &i.view),
+            false => None,
+        }
+    }
+
+    /// The texture of the latest pyramid, if it is enabled
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        match self.enabled {
+            true => self.target.as_ref().map(|i| &i.texture),
+            false => None,
+        }
+    }
+
+    /// The amount of levels of the pyramid, 0 before it is first made
+    pub fn mip_count(&self) -> u32 {
+        self.target
+            .as_ref()
+            .map(|i| i.texture.mip_level_count())
+            .unwrap_or(0)
+    }
+
+    /// Makes the pyramid from the depth of the scene
+    pub(crate) fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        size: (u32, u32),
+        depth: &wgpu::TextureView,
+    ) {
+        if self.pipelines.is_none() {
+            self.pipelines = Some(Self::build_pipelines(device));
+        }
+        let Some(pipelines) = self.pipelines.as_ref() else {
+            return;
+        };
+        if self.target.as_ref().map(|i| i.size) != Some(size) {
+            self.target = Some(create_target(device, pipelines, size));
+        }
+        let Some(target) = self.target.as_ref() else {
+            return;
+        };
+        // the depth buffer may be made again without changing size, so the first level is
+        // bound to it every frame
+        crate::utils::allocation_audit::count_bind_group();
+        let copy_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Depth Pyramid Copy Bind Group"),
+            layout: &pipelines.copy_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(depth),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&target.levels[0]),
+                },
+            ],
+        });
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Depth Pyramid Pass"),
+            timestamp_writes: None,
+        });
+        let bind_groups = std::iter::once(&copy_bind_group).chain(target.bind_groups.iter());
+        for (level, bind_group) in bind_groups.enumerate() {
+            let width = (target.size.0 >> level).max(1);
+            let height = (target.size.1 >> level).max(1);
+            compute_pass.set_pipeline(match level {
+                0 => &pipelines.copy,
+                _ => &pipelines.reduce,
+            });
+            compute_pass.set_bind_group(0, bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                width.div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+    }
+
+    fn build_pipelines(device: &wgpu::Device) -> PyramidPipelines {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Depth Pyramid Shader"),
+            source: wgpu::ShaderSource::Wgsl(DEPTH_PYRAMID_SHADER.into()),
+        });
+
+        let level_entry = wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: DEPTH_PYRAMID_FORMAT,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let source_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let copy_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Depth Pyramid Copy Bind Group Layout"),
+            entries: &[source_entry(0, wgpu::TextureSampleType::Depth), level_entry],
+        });
+        let reduce_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Depth Pyramid Reduce Bind Group Layout"),
+            entries: &[
+                source_entry(1, wgpu::TextureSampleType::Float { filterable: false }),
+                level_entry,
+            ],
+        });
+
+        let pipeline = |layout: &wgpu::BindGroupLayout, entry_point, label| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            })
+        };
+        let copy = pipeline(&copy_layout, "copy_depth", "Depth Pyramid Copy Pipeline");
+        let reduce = pipeline(&reduce_layout, "reduce", "Depth Pyramid Reduce Pipeline");
+
+        PyramidPipelines {
+            copy_layout,
+            copy,
+            reduce_layout,
+            reduce,
+        }
+    }
+}
+impl Default for DepthPyramid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Creates the texture with every level down to a single texel, and the bind groups that
+/// reduce them
+fn create_target(
+    device: &wgpu::Device,
+    pipelines: &PyramidPipelines,
+    size: (u32, u32),
+) -> PyramidTarget {
+    let size = (size.0.max(1), size.1.max(1));
+    let mip_level_count = 32 - size.0.max(size.1).leading_zeros();
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Depth Pyramid"),
+        size: wgpu::Extent3d {
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: DEPTH_PYRAMID_FORMAT,
+        usage: wgpu::TextureUsages::STORAGE_BINDING
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let levels = (0..mip_level_count)
+        .map(|level| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Depth Pyramid Level"),
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let bind_groups = levels
+        .windows(2)
+        .map(|pair| {
+            crate::utils::allocation_audit::count_bind_group();
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Depth Pyramid Reduce Bind Group"),
+                layout: &pipelines.reduce_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&pair[0]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&pair[1]),
+                    },
+                ],
+            })
+        })
+        .collect();
+
+    PyramidTarget {
+        size,
+        texture,
+        view,
+        levels,
+        bind_groups,
+    }
+}
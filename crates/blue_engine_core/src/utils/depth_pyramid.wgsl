@@ -0,0 +1,50 @@
+// The depth pyramid, each level half the size of the one before. A texel keeps the nearest
+// depth of the texels it covers in red, and the farthest in green
+
+@group(0) @binding(0)
+var scene_depth: texture_depth_2d;
+@group(0) @binding(1)
+var previous_level: texture_2d<f32>;
+@group(0) @binding(2)
+var level: texture_storage_2d<rg32float, write>;
+
+// copies the depth of the scene to the first level
+@compute @workgroup_size(8, 8)
+fn copy_depth(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(level);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    let depth = textureLoad(scene_depth, vec2<i32>(id.xy), 0);
+    textureStore(level, vec2<i32>(id.xy), vec4<f32>(depth, depth, 0.0, 0.0));
+}
+
+// reduces 2x2 texels of the level before into one
+@compute @workgroup_size(8, 8)
+fn reduce(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(level);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    let previous_size = vec2<i32>(textureDimensions(previous_level));
+    let start = vec2<i32>(id.xy) * 2;
+    // the last row or column of an odd level has no texel of its own below, so the texels at
+    // the edge cover it as well
+    let on_edge = id.xy == size - 1u;
+    let odd = (previous_size & vec2<i32>(1)) == vec2<i32>(1);
+    let extra = select(vec2<i32>(0), vec2<i32>(1), on_edge & odd);
+
+    var nearest = 1.0;
+    var farthest = 0.0;
+    for (var y = 0; y <= 1 + extra.y; y++) {
+        for (var x = 0; x <= 1 + extra.x; x++) {
+            let coords = min(start + vec2<i32>(x, y), previous_size - 1);
+            let depth = textureLoad(previous_level, coords, 0).rg;
+            nearest = min(nearest, depth.r);
+            farthest = max(farthest, depth.g);
+        }
+    }
+    textureStore(level, vec2<i32>(id.xy), vec4<f32>(nearest, farthest, 0.0, 0.0));
+}
@@ -0,0 +1,416 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    CameraContainer, Object, ObjectStorage, PipelineData, Renderer, TextureData,
+    utils::default_resources::DEFAULT_TEXTURE,
+};
+
+/// Flags the device as lost when the driver loses it. Every call on a lost device fails, so
+/// its errors are ignored instead of panicking until the device is made again
+pub(crate) fn watch_device(device: &wgpu::Device) -> Arc<AtomicBool> {
+    let device_lost = Arc::new(AtomicBool::new(false));
+
+    let lost = device_lost.clone();
+    device.set_device_lost_callback(move |reason, message| {
+        // the device is destroyed on purpose when it is replaced, or when the engine closes
+        if reason == wgpu::DeviceLostReason::Unknown {
+            eprintln!("The GPU device was lost: {message}");
+            lost.store(true, Ordering::Release);
+        }
+    });
+
+    let lost = device_lost.clone();
+    device.on_uncaptured_error(Box::new(move |error| {
+        if !lost.load(Ordering::Acquire) {
+            panic!("wgpu error: {error}");
+        }
+    }));
+
+    device_lost
+}
+
+impl Renderer {
+    /// Returns true if the GPU device was lost, such as when the driver crashed or was
+    /// updated. The engine makes it again before the next frame, see [Renderer::recover_device]
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Acquire)
+    }
+
+    /// Makes the device again after it was lost, with the same settings, and everything the
+    /// engine made on it from what is kept on the CPU: the vertices, shaders, and textures
+    /// set with [Object::set_texture] of the objects, and the uniforms of the cameras. The
+    /// surface of the window is configured for the new device.
+    ///
+    /// This is done by the engine before the frame, and the signals are told with
+    /// [crate::Signal::device_recovered]. The uniform buffers written with
+    /// [Object::set_uniform_buffer], and the textures and shaders of the materials made with
+    /// [Object::add_textured_material] and [Object::set_material_shader] are made again from
+    /// what they were made of. The uniform buffers put in [Object::uniform_buffers] by hand are
+    /// made again empty, and the textures set with [Object::set_texture_raw], the textures and
+    /// shaders set on the materials by hand, and the handles to meshes kept outside of the
+    /// objects are of the lost device, so they must be made again there.
+    ///
+    /// It is not available on the web, where the GPU can not be waited on without returning to
+    /// the browser, the same as why [crate::Engine::new_async] is used there. The frame would
+    /// have to wait for the new adapter and device, so a lost device is only flagged, its errors
+    /// are ignored, and the page has to be loaded again
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn recover_device(
+        &mut self,
+        objects: &mut ObjectStorage,
+        camera: &mut CameraContainer,
+    ) -> Result<(), crate::error::Error> {
+        let adapter = pollster::block_on(crate::utils::adapters::request_adapter(
+            &self.instance,
+            &self.device_settings,
+            self.surface.as_ref(),
+        ))?;
+        let (device, queue) =
+            pollster::block_on(Self::request_device(&adapter, &self.device_settings))?;
+        self.device_lost = watch_device(&device);
+        self.adapter = adapter;
+        self.device = device;
+        self.queue = queue;
+        (
+            self.texture_bind_group_layout,
+            self.default_uniform_bind_group_layout,
+        ) = Self::build_bind_group_layouts(&self.device);
+
+        self.clear_device_resources();
+        match self.surface.take() {
+            Some(surface) => self.attach_surface(surface, self.device_settings.transparent),
+            None => {
+                if self.offscreen_target.is_some() {
+                    self.offscreen_target = Some(self.build_offscreen_target());
+                }
+                self.depth_buffer =
+                    Self::build_depth_buffer("Depth Buffer", &self.device, &self.config);
+            }
+        }
+        self.build_default_data();
+        self.recover_compute_meshes(objects);
+
+        // the objects that share a mesh are given the same new mesh
+        let mut meshes = std::collections::HashMap::<usize, crate::Mesh>::new();
+        for object in objects.values_mut() {
+            if let Some(mesh) = object.mesh.take() {
+                let new_mesh = meshes.entry(mesh.id()).or_insert_with(|| {
+                    crate::Mesh::new_with_sub_meshes(
+                        mesh.vertices.clone(),
+                        mesh.indices.clone(),
+                        mesh.sub_meshes.clone(),
+                        self,
+                    )
+                });
+                object.mesh = Some(new_mesh.clone());
+            }
+            object.recover_device(self);
+        }
+        self.recover_skinned_crowds(objects);
+        self.recover_morph_targets(objects);
+
+        for camera_value in camera.values_mut() {
+            camera_value.uniform_buffer =
+                self.build_uniform_buffer_part("Camera Uniform", camera_value.gpu_uniform(self));
+            camera_value.uniform_data = self
+                .build_uniform_buffer(std::slice::from_ref(&camera_value.uniform_buffer))
+                .0;
+        }
+
+        Ok(())
+    }
+
+    /// Drops the caches and targets made on the lost device, keeping the settings
+    fn clear_device_resources(&mut self) {
+        self.overlay.clear_device_resources();
+        self.frame_recorder.clear_device_resources();
+        self.frame_history.clear_device_resources();
+        self.texture_streaming.clear_device_resources();
+        self.pipeline_cache.clear_device_resources();
+        self.static_batches.clear_device_resources();
+        self.meshlet_culling.clear_device_resources();
+        self.motion_vectors.clear_device_resources();
+        self.depth_pyramid.clear_device_resources();
+        self.occlusion_culling.clear_device_resources();
+        self.shadow_atlas.clear_device_resources();
+        self.visibility_buffer.clear_device_resources();
+        self.frame_graph.clear_device_resources();
+        self.grid.clear_device_resources();
+        self.clear_gradient.clear_device_resources();
+        self.uniform_pool = crate::utils::uniform_pool::UniformPool::new();
+        self.mesh_pool = crate::utils::mesh_pool::MeshPool::new();
+        self.mipmaps = crate::utils::mipmaps::MipmapGenerator::default();
+        self.camera_targets = crate::utils::render_scale::CameraTargets::default();
+        self.opacity_pipeline = None;
+        self.hdr_target = None;
+        if let Some(hdr) = self.hdr.as_mut() {
+            hdr.clear_device_resources();
+        }
+
+        // the new device may lack the features the old one had
+        if self.gpu_profiler.is_some() {
+            self.gpu_profiler = None;
+            if self.enable_gpu_profiling().is_err() {
+                eprintln!("The new device can not profile the GPU");
+            }
+        }
+        if self.supports_ray_tracing() {
+            if let Some(shadows) = self.ray_traced_shadows.as_mut() {
+                shadows.clear_device_resources();
+            }
+        } else if self.ray_traced_shadows.take().is_some() {
+            eprintln!("The new device can not trace the shadows");
+        }
+    }
+}
+
+impl Object {
+    /// Makes the buffers, texture, and shader of the object again on the new device
+    #[cfg(not(target_arch = "wasm32"))]
+    fn recover_device(&mut self, renderer: &mut Renderer) {
+        self.uniform_slot = None;
+        self.mesh_allocation = None;
+
+        // the uniforms added by hand are made again at the same size, with the bytes they were
+        // written with if they were kept, or empty to be written again
+        let mut uniform_sources = Vec::with_capacity(self.uniform_sources.len());
+        let custom_uniforms = self
+            .uniform_buffers
+            .iter()
+            .skip(2)
+            .map(|i| {
+                crate::utils::allocation_audit::count_buffer();
+                match self.uniform_sources.iter().find(|(j, _)| j == i) {
+                    Some((_, bytes)) => {
+                        let buffer = wgpu::util::DeviceExt::create_buffer_init(
+                            &renderer.device,
+                            &wgpu::util::BufferInitDescriptor {
+                                label: Some("Uniform Buffer"),
+                                contents: bytes,
+                                usage: i.usage(),
+                            },
+                        );
+                        uniform_sources.push((buffer.clone(), bytes.clone()));
+                        buffer
+                    }
+                    None => renderer.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("Uniform Buffer"),
+                        size: i.size(),
+                        usage: i.usage(),
+                        mapped_at_creation: false,
+                    }),
+                }
+            })
+            .collect::<Vec<_>>();
+        self.uniform_sources = uniform_sources;
+        self.uniform_buffers = vec![
+            renderer.build_uniform_buffer_part("Transformation Matrix", crate::Matrix4::IDENTITY),
+            renderer.build_uniform_buffer_part(
+                "Color",
+                crate::utils::shader_variation::FragmentUniform::new(self.color, self.variation),
+            ),
+        ];
+        self.uniform_buffers.extend(custom_uniforms);
+
+        let instances = self
+            .instances
+            .iter()
+            .map(crate::Instance::build)
+            .collect::<Vec<_>>();
+        self.instance_buffer = renderer.build_instance(instances);
+        self.back_instance_buffer = None;
+
+        if let PipelineData::Data(_) = self.pipeline.texture {
+            let texture = match self.texture_source.as_ref() {
+                Some((name, texture_data, texture_mode)) => {
+                    renderer.build_texture(name.as_ref(), texture_data.clone(), *texture_mode)
+                }
+                None => renderer.build_texture(
+                    "Default Texture",
+                    TextureData::Bytes(DEFAULT_TEXTURE.to_vec()),
+                    crate::prelude::TextureMode::Clamp,
+                ),
+            };
+            match texture {
+                Ok(texture) => self.pipeline.texture = PipelineData::Data(texture),
+                Err(e) => eprintln!("The texture of {} could not be made again: {e}", self.name),
+            }
+        }
+
+        // the shaders of the materials are built for the uniform layout made by the update
+        self.update(renderer);
+
+        for material in self.materials.iter_mut() {
+            let texture_source = material
+                .texture_source
+                .take()
+                .filter(|(texture, ..)| material.texture.as_ref() == Some(texture));
+            material.texture = None;
+            if let Some((_, texture_data, texture_mode)) = texture_source {
+                match renderer.build_texture(
+                    material.name.as_ref(),
+                    texture_data.clone(),
+                    texture_mode,
+                ) {
+                    Ok(texture) => {
+                        material.texture = Some(texture.clone());
+                        material.texture_source = Some((texture, texture_data, texture_mode));
+                    }
+                    Err(e) => eprintln!(
+                        "The texture of the material {} could not be made again: {e}",
+                        material.name
+                    ),
+                }
+            }
+
+            let shader_source = material
+                .shader_source
+                .take()
+                .filter(|(shader, _)| material.shader.as_ref() == Some(shader));
+            material.shader = shader_source.map(|(_, source)| {
+                let shader = renderer.build_shader(
+                    material.name.as_ref(),
+                    source.clone(),
+                    Some(&self.uniform_layout),
+                    self.shader_settings,
+                );
+                material.shader_source = Some((shader.clone(), source));
+                shader
+            });
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::{
+        Material, ObjectSettings, primitive_shapes::triangle_data,
+        utils::default_resources::DEFAULT_SHADER,
+    };
+
+    fn triangle(renderer: &mut Renderer) -> Object {
+        let (vertices, indices) = triangle_data();
+        Object::new(
+            "triangle",
+            vertices,
+            indices,
+            ObjectSettings::default(),
+            renderer,
+        )
+        .expect("the triangle is built")
+    }
+
+    #[test]
+    fn uniform_buffers_are_made_again_with_their_bytes() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut camera = CameraContainer::new(winit::dpi::PhysicalSize::new(64, 64), &mut renderer);
+        let mut object = triangle(&mut renderer);
+
+        let value = [1.0f32, 2.0, 3.0, 4.0];
+        object
+            .set_uniform_buffer(2, "Light", value, &mut renderer)
+            .unwrap();
+        assert!(
+            object
+                .set_uniform_buffer(0, "Transformation", value, &mut renderer)
+                .is_err()
+        );
+        assert!(
+            object
+                .set_uniform_buffer(4, "Past the end", value, &mut renderer)
+                .is_err()
+        );
+        // written again in place, keeping only the latest bytes
+        let value = [5.0f32, 6.0, 7.0, 8.0];
+        object
+            .set_uniform_buffer(2, "Light", value, &mut renderer)
+            .unwrap();
+        let by_hand = renderer.build_uniform_buffer_part("By Hand", value);
+        object.uniform_buffers.push(by_hand.clone());
+        let kept = object.uniform_buffers[2].clone();
+        assert_eq!(
+            object.uniform_sources,
+            vec![(kept.clone(), bytemuck::bytes_of(&value).to_vec())]
+        );
+
+        let mut objects = ObjectStorage::new();
+        let id = objects.add("triangle", object);
+        renderer.recover_device(&mut objects, &mut camera).unwrap();
+
+        let object = objects.get_by_id(id).unwrap();
+        assert_eq!(object.uniform_buffers.len(), 4);
+        assert_ne!(object.uniform_buffers[2], kept);
+        assert_ne!(object.uniform_buffers[3], by_hand);
+        assert_eq!(object.uniform_buffers[3].size(), by_hand.size());
+        // the bytes follow the buffer made again, and the one added by hand has none
+        assert_eq!(
+            object.uniform_sources,
+            vec![(
+                object.uniform_buffers[2].clone(),
+                bytemuck::bytes_of(&value).to_vec()
+            )]
+        );
+    }
+
+    #[test]
+    fn materials_are_made_again_from_their_sources() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut camera = CameraContainer::new(winit::dpi::PhysicalSize::new(64, 64), &mut renderer);
+        let mut object = triangle(&mut renderer);
+
+        let sourced = object
+            .add_textured_material(
+                "Sourced",
+                TextureData::Bytes(DEFAULT_TEXTURE.to_vec()),
+                crate::prelude::TextureMode::Clamp,
+                &mut renderer,
+            )
+            .unwrap();
+        object
+            .set_material_shader(sourced, DEFAULT_SHADER.to_string(), &mut renderer)
+            .unwrap();
+        let (texture, shader) = {
+            let material = &object.materials[sourced];
+            (material.texture.clone(), material.shader.clone())
+        };
+
+        // a texture set by hand has nothing to be made again from
+        let mut material = Material::new("By Hand");
+        material.texture = Some(
+            renderer
+                .build_texture(
+                    "By Hand",
+                    TextureData::Bytes(DEFAULT_TEXTURE.to_vec()),
+                    crate::prelude::TextureMode::Clamp,
+                )
+                .unwrap(),
+        );
+        let by_hand = object.add_material(material);
+
+        let mut objects = ObjectStorage::new();
+        let id = objects.add("triangle", object);
+        renderer.recover_device(&mut objects, &mut camera).unwrap();
+
+        let object = objects.get_by_id(id).unwrap();
+        let material = &object.materials[sourced];
+        assert!(material.texture.is_some());
+        assert!(material.shader.is_some());
+        assert_ne!(material.texture, texture);
+        assert_ne!(material.shader, shader);
+        assert!(object.materials[by_hand].texture.is_none());
+    }
+}
@@ -0,0 +1,187 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{Renderer, SamplerSettings, StringBuffer, Textures, error::Error};
+
+/// A rectangle of pixels in a texture, from its top left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureRegion {
+    /// The left edge of the region
+    pub x: u32,
+    /// The top edge of the region
+    pub y: u32,
+    /// The width of the region
+    pub width: u32,
+    /// The height of the region
+    pub height: u32,
+}
+impl TextureRegion {
+    /// Creates a new region
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The region covering a whole texture of the size
+    pub fn full(width: u32, height: u32) -> Self {
+        Self::new(0, 0, width, height)
+    }
+}
+
+/// A texture for objects whose pixels are written from the CPU in place, such as for video
+/// frames, procedural textures, or minimaps. Writing to it does not rebuild the texture, so
+/// the objects using it show the new pixels on the next frame.
+///
+/// The texture has a single mip level, as it is expected to change often
+#[derive(Debug, Clone)]
+pub struct DynamicTexture {
+    texture: wgpu::Texture,
+    textures: Textures,
+}
+
+impl DynamicTexture {
+    /// The texture for objects, to be set with [crate::Object::set_texture_raw]
+    pub fn textures(&self) -> Textures {
+        self.textures.clone()
+    }
+
+    /// The texture on the GPU
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// The width of the texture
+    pub fn width(&self) -> u32 {
+        self.texture.width()
+    }
+
+    /// The height of the texture
+    pub fn height(&self) -> u32 {
+        self.texture.height()
+    }
+
+    /// Writes the pixels to the region of the texture, as tightly packed 8 bit RGBA rows
+    pub fn write_pixels(
+        &self,
+        renderer: &Renderer,
+        region: TextureRegion,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        renderer.write_texture_pixels(&self.texture, region, data)
+    }
+
+    /// Writes the pixels of the whole texture, as tightly packed 8 bit RGBA rows
+    pub fn write_all(&self, renderer: &Renderer, data: &[u8]) -> Result<(), Error> {
+        self.write_pixels(
+            renderer,
+            TextureRegion::full(self.width(), self.height()),
+            data,
+        )
+    }
+}
+
+impl Renderer {
+    /// Creates a new texture whose pixels can be written each frame, see [DynamicTexture].
+    /// It starts fully transparent
+    pub fn build_dynamic_texture(
+        &self,
+        name: impl StringBuffer,
+        width: u32,
+        height: u32,
+        texture_mode: impl Into<SamplerSettings>,
+    ) -> DynamicTexture {
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(name.as_str()),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let textures = self.build_texture_from_gpu(name, &texture, texture_mode);
+
+        DynamicTexture { texture, textures }
+    }
+
+    /// Writes the pixels to the region of the first mip level of the texture, in place. The
+    /// rows of the data are tightly packed in the format of the texture, which must not be
+    /// block compressed, and must be created with [wgpu::TextureUsages::COPY_DST]
+    pub fn write_texture_pixels(
+        &self,
+        texture: &wgpu::Texture,
+        region: TextureRegion,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let format = texture.format();
+        let pixel_size = match (format.block_dimensions(), format.block_copy_size(None)) {
+            ((1, 1), Some(size)) => size,
+            _ => {
+                return Err(Error::TextureWriteError(
+                    "the format of the texture can not be written to by pixels",
+                ));
+            }
+        };
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_DST) {
+            return Err(Error::TextureWriteError(
+                "the texture was not created with COPY_DST",
+            ));
+        }
+        if region.width == 0 || region.height == 0 {
+            return Ok(());
+        }
+        if region.x.saturating_add(region.width) > texture.width()
+            || region.y.saturating_add(region.height) > texture.height()
+        {
+            return Err(Error::TextureWriteError(
+                "the region is outside of the texture",
+            ));
+        }
+        let bytes_per_row = region.width * pixel_size;
+        if data.len() != (bytes_per_row * region.height) as usize {
+            return Err(Error::TextureWriteError(
+                "the size of the data does not match the region",
+            ));
+        }
+
+        self.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: region.x,
+                    y: region.y,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(region.height),
+            },
+            wgpu::Extent3d {
+                width: region.width,
+                height: region.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        Ok(())
+    }
+}
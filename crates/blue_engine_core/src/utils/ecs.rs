@@ -0,0 +1,223 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{
+    Mesh, ObjectId, ObjectSettings, ObjectStorage, Renderer, Textures, Transform, Vector4,
+    error::Error, objects::Object,
+};
+
+/// The component of an entity that gives it a shared mesh to be drawn with. Together with a
+/// [Transform], it makes the entity drawn by an object
+#[derive(Debug, Clone)]
+pub struct MeshHandle(pub Mesh);
+
+/// The component of an entity that colors and textures its object
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialHandle {
+    /// The color of the object
+    pub color: Vector4,
+    /// The texture of the object, or the default texture if None
+    pub texture: Option<Textures>,
+}
+impl MaterialHandle {
+    /// A material of the color and the default texture
+    pub fn new(color: impl Into<Vector4>) -> Self {
+        Self {
+            color: color.into(),
+            texture: None,
+        }
+    }
+
+    /// Changes the texture of the material
+    pub fn with_texture(mut self, texture: Textures) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+}
+
+/// An entity with an object, and what was last given to it
+#[derive(Debug)]
+struct SyncedEntity {
+    object: ObjectId,
+    transform: Transform,
+    mesh: Mesh,
+    material: Option<MaterialHandle>,
+    seen: u64,
+}
+
+/// Keeps the objects of the engine in step with the entities of an ECS, such as hecs or
+/// bevy_ecs, without depending on either. Each entity with a [Transform] and a [MeshHandle]
+/// is drawn by an object of its own, that is made when the entity first shows up, updated
+/// when its components change, and removed once the entity is no longer given.
+///
+/// The entities are given by their id as a number, which every ECS has:
+///
+/// ```ignore
+/// let mut sync = EcsSync::new();
+///
+/// engine.update_loop(move |renderer, _, objects, _, _, _| {
+///     let mut query = world.query::<(&Transform, &MeshHandle, Option<&MaterialHandle>)>();
+///     let entities = query
+///         .iter()
+///         .map(|(entity, (transform, mesh, material))| (entity.to_bits().get(), transform, mesh, material));
+///     sync.sync(entities, objects, renderer).unwrap();
+/// })?;
+/// ```
+#[derive(Debug)]
+pub struct EcsSync {
+    /// The settings the objects of the entities are made with
+    pub settings: ObjectSettings,
+    /// The start of the names of the objects, followed by the id of the entity
+    pub prefix: Arc<str>,
+    entities: HashMap<u64, SyncedEntity>,
+    frame: u64,
+}
+
+impl EcsSync {
+    /// Creates the sync with no entities, whose objects are named `entity_` and their id
+    pub fn new() -> Self {
+        Self {
+            settings: ObjectSettings::default(),
+            prefix: "entity_".into(),
+            entities: HashMap::new(),
+            frame: 0,
+        }
+    }
+
+    /// The object that draws the entity
+    pub fn object_of(&self, entity: u64) -> Option<ObjectId> {
+        self.entities.get(&entity).map(|i| i.object)
+    }
+
+    /// The amount of entities that have an object
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Returns true if no entities have an object
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Makes, updates, and removes the objects to match the entities. The objects of the
+    /// entities that are not given are removed, so all of them must be given every time
+    pub fn sync<'a>(
+        &mut self,
+        entities: impl IntoIterator<
+            Item = (
+                u64,
+                &'a Transform,
+                &'a MeshHandle,
+                Option<&'a MaterialHandle>,
+            ),
+        >,
+        objects: &mut ObjectStorage,
+        renderer: &mut Renderer,
+    ) -> Result<(), Error> {
+        self.frame += 1;
+
+        for (entity, transform, mesh, material) in entities {
+            let kept = self
+                .entities
+                .get(&entity)
+                .is_some_and(|i| objects.contains_id(i.object) && mesh.0.ptr_eq(&i.mesh));
+            if !kept {
+                // the mesh is shared, so a new one needs a new object
+                if let Some(old) = self.entities.remove(&entity) {
+                    objects.remove_by_id(old.object);
+                }
+                let name = format!("{}{entity}", self.prefix);
+                let mut object =
+                    Object::new_from_mesh(name.as_str(), &mesh.0, self.settings.clone(), renderer)?;
+                object.set_transform(*transform);
+                let id = objects.add(name.as_str(), object);
+
+                self.entities.insert(
+                    entity,
+                    SyncedEntity {
+                        object: id,
+                        transform: *transform,
+                        mesh: mesh.0.clone(),
+                        material: None,
+                        seen: self.frame,
+                    },
+                );
+            }
+            let Some(synced) = self.entities.get_mut(&entity) else {
+                continue;
+            };
+            synced.seen = self.frame;
+
+            let Some(object) = objects.get_by_id_mut(synced.object) else {
+                continue;
+            };
+            if synced.transform != *transform {
+                object.set_transform(*transform);
+                synced.transform = *transform;
+            }
+            if synced.material.as_ref() != material {
+                apply_material(object, material, renderer)?;
+                synced.material = material.cloned();
+            }
+        }
+
+        let frame = self.frame;
+        self.entities.retain(|_, synced| {
+            let seen = synced.seen == frame;
+            if !seen {
+                objects.remove_by_id(synced.object);
+            }
+            seen
+        });
+
+        Ok(())
+    }
+
+    /// Removes the objects of all the entities
+    pub fn clear(&mut self, objects: &mut ObjectStorage) {
+        for (_, synced) in self.entities.drain() {
+            objects.remove_by_id(synced.object);
+        }
+    }
+}
+impl Default for EcsSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Gives the object the color and texture of the material, or the defaults without one
+fn apply_material(
+    object: &mut Object,
+    material: Option<&MaterialHandle>,
+    renderer: &mut Renderer,
+) -> Result<(), Error> {
+    let color = material
+        .map(|i| i.color)
+        .unwrap_or(crate::utils::default_resources::DEFAULT_COLOR);
+    object.set_color(color.x, color.y, color.z, color.w);
+
+    match material.and_then(|i| i.texture.clone()) {
+        Some(texture) => {
+            object.set_texture_raw(texture);
+        }
+        None => {
+            object.set_texture(
+                "Default Texture",
+                crate::TextureData::Bytes(
+                    crate::utils::default_resources::DEFAULT_TEXTURE.to_vec(),
+                ),
+                crate::TextureMode::Clamp,
+                renderer,
+            )?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,158 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{CameraContainer, ObjectStorage, Renderer, SignalStorage, Window};
+
+/// The default amount of ticks that can run in a single frame before the rest are dropped
+pub const DEFAULT_MAX_TICKS_PER_FRAME: u32 = 8;
+
+/// The callback of [crate::Engine::update_loop_fixed]. The last argument is the tick length in
+/// seconds for the fixed update, and the interpolation alpha for the render update.
+#[allow(clippy::type_complexity)]
+pub type FixedUpdateFunction = Box<
+    dyn 'static
+        + FnMut(
+            &mut Renderer,
+            &mut Window,
+            &mut ObjectStorage,
+            &crate::utils::winit_input_helper::WinitInputHelper,
+            &mut CameraContainer,
+            &mut SignalStorage,
+            f32,
+        ),
+>;
+
+/// Splits the time between frames into ticks of a fixed length, so the logic runs at the
+/// same rate regardless of the frame rate
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    /// The amount of ticks per second
+    pub tick_rate: f32,
+    /// The amount of ticks that can run in a single frame. If the logic falls further behind,
+    /// such as after a long stall, the remaining time is dropped instead of catching up
+    pub max_ticks_per_frame: u32,
+    accumulator: f32,
+    last_frame: Option<crate::time::Instant>,
+    ticks: u64,
+}
+
+impl FixedTimestep {
+    /// Creates a new timestep with the given amount of ticks per second
+    pub fn new(tick_rate: f32) -> Self {
+        Self {
+            tick_rate,
+            max_ticks_per_frame: DEFAULT_MAX_TICKS_PER_FRAME,
+            accumulator: 0.0,
+            last_frame: None,
+            ticks: 0,
+        }
+    }
+
+    /// The length of a tick in seconds
+    pub fn tick_length(&self) -> f32 {
+        1.0 / self.tick_rate.max(f32::EPSILON)
+    }
+
+    /// How far the time is between the last tick and the next one, from 0 to 1. Rendering
+    /// the objects between their previous and current state by this keeps the motion smooth
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.tick_length()).clamp(0.0, 1.0)
+    }
+
+    /// The amount of ticks that have run in total
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Adds the time since the last frame, and returns the amount of ticks to run
+    pub(crate) fn advance(&mut self) -> u32 {
+        let now = crate::time::Instant::now();
+        let elapsed = self
+            .last_frame
+            .map(|last_frame| (now - last_frame).as_secs_f32())
+            .unwrap_or_default();
+        self.last_frame = Some(now);
+
+        self.advance_by(elapsed)
+    }
+
+    /// Adds the elapsed seconds, and returns the amount of ticks to run
+    fn advance_by(&mut self, elapsed: f32) -> u32 {
+        self.accumulator += elapsed;
+
+        let tick_length = self.tick_length();
+        let mut ticks = 0;
+        while self.accumulator >= tick_length && ticks < self.max_ticks_per_frame {
+            self.accumulator -= tick_length;
+            ticks += 1;
+        }
+        if ticks == self.max_ticks_per_frame {
+            self.accumulator = self.accumulator.min(tick_length);
+        }
+
+        self.ticks += ticks as u64;
+        ticks
+    }
+}
+
+/// The callbacks and timestep of [crate::Engine::update_loop_fixed]
+pub struct FixedUpdate {
+    /// Splits the frames into ticks
+    pub timestep: FixedTimestep,
+    /// Ran once for every tick, with the tick length in seconds
+    pub fixed_function: FixedUpdateFunction,
+    /// Ran once every frame, with the interpolation alpha
+    pub render_function: FixedUpdateFunction,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_at_the_tick_rate() {
+        let mut timestep = FixedTimestep::new(4.0);
+        assert_eq!(timestep.tick_length(), 0.25);
+
+        assert_eq!(timestep.advance_by(0.125), 0);
+        assert_eq!(timestep.alpha(), 0.5);
+        assert_eq!(timestep.advance_by(0.125), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.advance_by(0.5), 2);
+        assert_eq!(timestep.ticks(), 3);
+    }
+
+    #[test]
+    fn leftover_time_carries_over() {
+        let mut timestep = FixedTimestep::new(4.0);
+
+        // three frames of 0.375 are 1.125 seconds, which is four ticks and a half
+        let ticks: u32 = (0..3).map(|_| timestep.advance_by(0.375)).sum();
+        assert_eq!(ticks, 4);
+        assert_eq!(timestep.alpha(), 0.5);
+    }
+
+    #[test]
+    fn stalls_are_capped() {
+        let mut timestep = FixedTimestep::new(4.0);
+        timestep.max_ticks_per_frame = 3;
+
+        // ten seconds would be forty ticks, only three run and at most one tick is kept
+        assert_eq!(timestep.advance_by(10.0), 3);
+        assert!(timestep.alpha() <= 1.0);
+        assert_eq!(timestep.advance_by(0.0), 1);
+        assert_eq!(timestep.advance_by(0.0), 0);
+        assert_eq!(timestep.ticks(), 4);
+    }
+
+    #[test]
+    fn zero_tick_rate() {
+        let mut timestep = FixedTimestep::new(0.0);
+        assert!(timestep.tick_length().is_finite());
+        assert_eq!(timestep.advance_by(1.0), 0);
+        assert!(timestep.alpha() < 1e-6);
+    }
+}
@@ -0,0 +1,98 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{Renderer, Vector4};
+
+/// The override constant declared by the `//@FOG` placeholder, set to false for the objects
+/// that opt out of the fog
+pub const FOG_OVERRIDE: &str = "fog_enabled";
+
+/// How the fog thickens with the distance from the camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FogMode {
+    /// No fog before `start`, and nothing but fog after `end`
+    Linear {
+        /// The distance the fog starts at
+        start: f32,
+        /// The distance the fog covers everything at
+        end: f32,
+    },
+    /// The fog thickens by `exp(-density * distance)`, without ever covering everything
+    Exponential {
+        /// How thick the fog is, usually a small number such as 0.05
+        density: f32,
+    },
+}
+
+/// The fog applied to the objects affected by a camera, by their distance from it
+///
+/// The default shaders apply it, and custom shaders can with the `//@FOG`, `//@FOG_OUTPUT`
+/// and `//@FOG_VERTEX` placeholders, see [crate::ShaderBuilder]. The objects can opt out
+/// with [crate::ShaderSettings::fog]
+///
+/// ```ignore
+/// renderer.set_fog(Some(Fog::linear(Vector4::new(0.7, 0.7, 0.8, 1.0), 10.0, 50.0)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    /// The color the objects fade to, the alpha is not used
+    pub color: Vector4,
+    /// How the fog thickens
+    pub mode: FogMode,
+}
+
+impl Fog {
+    /// Fog between the two distances from the camera
+    pub fn linear(color: impl Into<Vector4>, start: f32, end: f32) -> Self {
+        Self {
+            color: color.into(),
+            mode: FogMode::Linear { start, end },
+        }
+    }
+
+    /// Fog that thickens exponentially with the distance from the camera
+    pub fn exponential(color: impl Into<Vector4>, density: f32) -> Self {
+        Self {
+            color: color.into(),
+            mode: FogMode::Exponential { density },
+        }
+    }
+}
+
+/// The fog as it is laid out in the uniform of the cameras
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FogUniform {
+    /// The color of the fog
+    pub color: [f32; 4],
+    /// The mode, 0 for no fog, 1 for linear and 2 for exponential, followed by the start,
+    /// the end, and the density
+    pub parameters: [f32; 4],
+}
+
+impl From<Option<Fog>> for FogUniform {
+    fn from(fog: Option<Fog>) -> Self {
+        let Some(fog) = fog else {
+            return Self::default();
+        };
+
+        Self {
+            color: fog.color.to_array(),
+            parameters: match fog.mode {
+                FogMode::Linear { start, end } => [1.0, start, end, 0.0],
+                FogMode::Exponential { density } => [2.0, 0.0, 0.0, density],
+            },
+        }
+    }
+}
+
+impl Renderer {
+    /// Sets the fog of the objects affected by the cameras, or removes it with None. The
+    /// cameras pick it up when their uniforms are written for the next frame
+    pub fn set_fog(&mut self, fog: Option<Fog>) {
+        self.fog = fog;
+    }
+}
@@ -0,0 +1,254 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{Renderer, definition::BufferMapping, error::Error};
+
+/// A buffer being read back from the GPU, without waiting for it. See
+/// [Renderer::read_buffer_async]
+#[derive(Debug)]
+pub struct PendingRead {
+    buffer: wgpu::Buffer,
+    /// Taken once the data is read
+    mapping: Option<BufferMapping>,
+}
+
+impl PendingRead {
+    /// Returns true once the data can be taken without waiting
+    pub fn is_ready(&self) -> bool {
+        self.mapping.as_ref().is_none_or(|i| i.result().is_some())
+    }
+
+    /// Takes the data if the GPU is done with the buffer, or returns `None` while it is not.
+    /// Call it again on a later frame until it returns the data, which is given only once
+    pub fn poll(&mut self, renderer: &Renderer) -> Option<Result<Vec<u8>, Error>> {
+        // moves the mapping along when the engine is not rendering, such as in headless mode
+        renderer.device.poll(wgpu::Maintain::Poll);
+
+        let Some(mapping) = self.mapping.as_ref() else {
+            return Some(Err(Error::Custom(
+                "The data of the buffer was already taken".to_string(),
+            )));
+        };
+        let result = mapping.result()?;
+        self.mapping = None;
+
+        Some(result.map(|()| {
+            let data = self.buffer.slice(..).get_mapped_range().to_vec();
+            self.buffer.unmap();
+            data
+        }))
+    }
+
+    /// Blocks until the GPU is done with the buffer, and takes the data. The browsers can not
+    /// block, so there it gives an error if the data is not ready yet
+    pub fn wait(mut self, renderer: &Renderer) -> Result<Vec<u8>, Error> {
+        renderer.device.poll(wgpu::Maintain::Wait);
+
+        self.poll(renderer).unwrap_or(Err(Error::Custom(
+            "The buffer is still being read, poll it on a later frame".to_string(),
+        )))
+    }
+}
+
+/// A texture being read back from the GPU into an image, without waiting for it. See
+/// [Renderer::read_texture_async] and [Renderer::capture_frame_async]
+///
+/// ```ignore
+/// // once, such as on a key press
+/// pending = Some(renderer.capture_frame_async()?);
+///
+/// // every frame after
+/// if let Some(capture) = pending.as_mut()
+///     && let Some(image) = capture.poll(renderer)
+/// {
+///     image?.save("screenshot.png")?;
+///     pending = None;
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PendingImage {
+    readback: TextureReadback,
+    read: PendingRead,
+}
+
+impl PendingImage {
+    /// Returns true once the image can be taken without waiting
+    pub fn is_ready(&self) -> bool {
+        self.read.is_ready()
+    }
+
+    /// Takes the image if the GPU is done with the texture, or returns `None` while it is not
+    pub fn poll(&mut self, renderer: &Renderer) -> Option<Result<image::RgbaImage, Error>> {
+        Some(
+            self.read
+                .poll(renderer)?
+                .and_then(|data| self.readback.to_image(&data)),
+        )
+    }
+
+    /// Blocks until the GPU is done with the texture, and takes the image
+    pub fn wait(self, renderer: &Renderer) -> Result<image::RgbaImage, Error> {
+        let data = self.read.wait(renderer)?;
+        self.readback.to_image(&data)
+    }
+}
+
+/// A copy of a texture into a buffer that can be read by the CPU
+#[derive(Debug)]
+pub(crate) struct TextureReadback {
+    pub buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    swap_channels: bool,
+}
+
+impl TextureReadback {
+    /// Records a copy of the first layer of the texture into a new buffer.
+    ///
+    /// The texture must have [`wgpu::TextureUsages::COPY_SRC`] and be in an 8 bit RGBA or BGRA format.
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) -> Result<Self, Error> {
+        let swap_channels = match texture.format() {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => {
+                return Err(Error::Custom(format!(
+                    "Reading textures of format {format:?} is not supported"
+                )));
+            }
+        };
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            return Err(Error::Custom(
+                "The texture can not be read as it does not have the COPY_SRC usage".to_string(),
+            ));
+        }
+
+        let width = texture.width();
+        let height = texture.height();
+        // rows of the copy must be aligned
+        let padded_bytes_per_row = (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        crate::utils::allocation_audit::count_buffer();
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Texture Readback Buffer"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        Ok(Self {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            swap_channels,
+        })
+    }
+
+    /// Turns the data read from the buffer into an image, removing the row padding
+    pub(crate) fn to_image(&self, data: &[u8]) -> Result<image::RgbaImage, Error> {
+        let unpadded_bytes_per_row = (self.width * 4) as usize;
+
+        let mut pixels = Vec::with_capacity(unpadded_bytes_per_row * self.height as usize);
+        for row in data.chunks_exact(self.padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+        }
+        if self.swap_channels {
+            pixels.chunks_exact_mut(4).for_each(|i| i.swap(0, 2));
+        }
+
+        image::RgbaImage::from_raw(self.width, self.height, pixels).ok_or(Error::Custom(
+            "The texture data does not match its size".to_string(),
+        ))
+    }
+}
+
+impl Renderer {
+    /// Starts reading the content of a buffer back to the CPU, and returns right away. The
+    /// data is taken from the returned [PendingRead] once the GPU is done with the buffer.
+    ///
+    /// The buffer must have been created with [`wgpu::BufferUsages::MAP_READ`]
+    pub fn read_buffer_async(&self, buffer: &wgpu::Buffer) -> PendingRead {
+        PendingRead {
+            buffer: buffer.clone(),
+            mapping: Some(BufferMapping::read(buffer)),
+        }
+    }
+
+    /// Starts reading the first layer of a texture back from the GPU, and returns right away.
+    /// The image is taken from the returned [PendingImage] on a later frame.
+    ///
+    /// The texture must have [`wgpu::TextureUsages::COPY_SRC`] and be in an 8 bit RGBA or BGRA format.
+    pub fn read_texture_async(&self, texture: &wgpu::Texture) -> Result<PendingImage, Error> {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Texture Readback Encoder"),
+            });
+        let readback = TextureReadback::new(&self.device, &mut encoder, texture)?;
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        Ok(PendingImage {
+            read: self.read_buffer_async(&readback.buffer),
+            readback,
+        })
+    }
+
+    /// Reads the first layer of a texture back from the GPU into an image, blocking until it
+    /// is read. See [Renderer::read_texture_async] to read it without waiting.
+    ///
+    /// The texture must have [`wgpu::TextureUsages::COPY_SRC`] and be in an 8 bit RGBA or BGRA format.
+    pub fn read_texture(&self, texture: &wgpu::Texture) -> Result<image::RgbaImage, Error> {
+        self.read_texture_async(texture)?.wait(self)
+    }
+
+    /// Starts reading the last rendered frame of the offscreen target, and returns right away.
+    ///
+    /// Only available in headless mode, see [crate::Engine::new_headless].
+    pub fn capture_frame_async(&self) -> Result<PendingImage, Error> {
+        match self.offscreen_target.as_ref() {
+            Some((texture, _)) => self.read_texture_async(texture),
+            None => Err(Error::Custom(
+                "There is no offscreen target to capture, the renderer is not headless".to_string(),
+            )),
+        }
+    }
+
+    /// Reads the last rendered frame of the offscreen target into an image, blocking until it
+    /// is read. See [Renderer::capture_frame_async] to read it without waiting.
+    ///
+    /// Only available in headless mode, see [crate::Engine::new_headless].
+    pub fn capture_frame(&self) -> Result<image::RgbaImage, Error> {
+        self.capture_frame_async()?.wait(self)
+    }
+}
@@ -0,0 +1,995 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::Error;
+use crate::utils::pass_export::{ExportedTexture, PendingExport, check_format};
+
+/// The pass that draws the objects, the grid, and the scaled cameras onto the scene
+pub const MAIN_PASS: &str = "main";
+/// The pass that tonemaps the scene onto the frame in HDR
+pub const POST_PASS: &str = "post";
+/// The pass that draws the overlay and the performance HUD onto the frame
+pub const UI_PASS: &str = "ui";
+
+/// The color texture the scene is drawn to, which is the frame itself without HDR
+pub const SCENE: &str = "scene";
+/// The depth texture of the scene
+pub const DEPTH: &str = "depth";
+/// The texture presented to the window
+pub const FRAME: &str = "frame";
+/// The motion vectors of the scene, when [crate::utils::motion_vectors::MotionVectors] is enabled
+pub const MOTION_VECTORS: &str = "motion_vectors";
+/// The depth pyramid of the scene, when [crate::utils::depth_pyramid::DepthPyramid] is enabled
+pub const DEPTH_PYRAMID: &str = "depth_pyramid";
+/// The shadow maps of the lights, when [crate::utils::shadow_atlas::ShadowAtlas] is enabled
+pub const SHADOW_ATLAS: &str = "shadow_atlas";
+/// The object, instance and triangle of each pixel, when
+/// [crate::utils::visibility_buffer::VisibilityBuffer] is enabled
+pub const VISIBILITY_BUFFER: &str = "visibility_buffer";
+
+/// The size of a texture created by the frame graph
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureSize {
+    /// The size of the frame
+    Frame,
+    /// The size of the frame multiplied by a scale, such as 0.5 for half resolution
+    Scaled(f32),
+    /// A size in pixels that does not follow the frame, such as for shadow maps
+    Fixed(u32, u32),
+}
+
+/// Describes a texture created by the frame graph
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureDesc {
+    /// The size of the texture
+    pub size: TextureSize,
+    /// The format of the texture
+    pub format: wgpu::TextureFormat,
+    /// How the texture is used by the passes
+    pub usage: wgpu::TextureUsages,
+}
+impl TextureDesc {
+    /// A texture of the size of the frame, that can be rendered to and sampled
+    pub fn new(format: wgpu::TextureFormat) -> Self {
+        Self {
+            size: TextureSize::Frame,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        }
+    }
+
+    /// Changes the size of the texture
+    pub fn with_size(mut self, size: TextureSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Changes how the texture is used
+    pub fn with_usage(mut self, usage: wgpu::TextureUsages) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    fn resolve(&self, frame_size: (u32, u32)) -> TextureKey {
+        let (width, height) = match self.size {
+            TextureSize::Frame => frame_size,
+            TextureSize::Scaled(scale) => (
+                (frame_size.0 as f32 * scale).round() as u32,
+                (frame_size.1 as f32 * scale).round() as u32,
+            ),
+            TextureSize::Fixed(width, height) => (width, height),
+        };
+
+        TextureKey {
+            width: width.max(1),
+            height: height.max(1),
+            format: self.format,
+            usage: self.usage,
+        }
+    }
+}
+
+/// Describes a buffer created by the frame graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferDesc {
+    /// The size of the buffer in bytes
+    pub size: u64,
+    /// How the buffer is used by the passes
+    pub usage: wgpu::BufferUsages,
+}
+
+/// A texture on the GPU, as the passes of the graph can share it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct TextureKey {
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+}
+
+/// A custom pass of the frame graph
+pub trait FramePass: Send + Sync {
+    /// Declares what the pass reads and writes. Called when the pass is added
+    fn setup(&mut self, builder: &mut PassBuilder);
+
+    /// Records the pass into the frame
+    fn execute(&mut self, context: &mut PassContext);
+}
+
+/// Collects the textures and buffers a pass uses, and where it goes among the other passes
+#[derive(Debug, Default)]
+pub struct PassBuilder {
+    reads: Vec<Arc<str>>,
+    writes: Vec<Arc<str>>,
+    after: Vec<Arc<str>>,
+    before: Vec<Arc<str>>,
+    textures: Vec<(Arc<str>, TextureDesc)>,
+    buffers: Vec<(Arc<str>, BufferDesc)>,
+    exports: Vec<Arc<str>>,
+}
+
+impl PassBuilder {
+    /// Creates a texture that the pass writes, and the passes after it can read
+    pub fn create_texture(&mut self, name: impl crate::StringBuffer, desc: TextureDesc) {
+        self.textures.push((name.as_arc(), desc));
+        self.writes.push(name.as_arc());
+    }
+
+    /// Creates a buffer that the pass writes, and the passes after it can read
+    pub fn create_buffer(&mut self, name: impl crate::StringBuffer, desc: BufferDesc) {
+        self.buffers.push((name.as_arc(), desc));
+        self.writes.push(name.as_arc());
+    }
+
+    /// The pass reads the texture or buffer, so it runs after the passes that write it
+    pub fn read(&mut self, name: impl crate::StringBuffer) {
+        self.reads.push(name.as_arc());
+    }
+
+    /// The pass writes the texture or buffer, so it runs before the passes that read it
+    pub fn write(&mut self, name: impl crate::StringBuffer) {
+        self.writes.push(name.as_arc());
+    }
+
+    /// The pass writes the texture, and it is read back to the CPU after the pass every
+    /// frame, see [FrameGraph::exported]. The passes after it can still read it as well
+    pub fn export(&mut self, name: impl crate::StringBuffer) {
+        self.exports.push(name.as_arc());
+        self.writes.push(name.as_arc());
+    }
+
+    /// The pass runs after the other pass
+    pub fn after(&mut self, pass: impl crate::StringBuffer) {
+        self.after.push(pass.as_arc());
+    }
+
+    /// The pass runs before the other pass
+    pub fn before(&mut self, pass: impl crate::StringBuffer) {
+        self.before.push(pass.as_arc());
+    }
+}
+
+/// What a pass is given to record itself with
+pub struct PassContext<'a> {
+    /// The device, for creating pipelines and bind groups
+    pub device: &'a wgpu::Device,
+    /// The queue, for writing buffers and textures
+    pub queue: &'a wgpu::Queue,
+    /// The encoder of the frame, to record the pass into
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    /// The size of the frame in pixels
+    pub frame_size: (u32, u32),
+    /// The format of the scene texture
+    pub scene_format: wgpu::TextureFormat,
+    /// The format of the frame texture
+    pub frame_format: wgpu::TextureFormat,
+    pub(crate) scene: &'a wgpu::TextureView,
+    pub(crate) depth: &'a wgpu::TextureView,
+    pub(crate) frame: &'a wgpu::TextureView,
+    pub(crate) motion_vectors: Option<&'a wgpu::TextureView>,
+    pub(crate) depth_pyramid: Option<&'a wgpu::TextureView>,
+    pub(crate) shadow_atlas: Option<&'a wgpu::TextureView>,
+    pub(crate) visibility_buffer: Option<&'a wgpu::TextureView>,
+    textures: &'a HashMap<Arc<str>, usize>,
+    physical_textures: &'a [(TextureKey, wgpu::Texture, wgpu::TextureView)],
+    buffers: &'a HashMap<Arc<str>, wgpu::Buffer>,
+}
+
+impl PassContext<'_> {
+    /// The view of a texture of the graph, including [SCENE], [DEPTH], [FRAME], and
+    /// [MOTION_VECTORS], [DEPTH_PYRAMID], [SHADOW_ATLAS] and [VISIBILITY_BUFFER] if enabled
+    pub fn view(&self, name: &str) -> Option<&wgpu::TextureView> {
+        match name {
+            SCENE => Some(self.scene),
+            DEPTH => Some(self.depth),
+            FRAME => Some(self.frame),
+            MOTION_VECTORS => self.motion_vectors,
+            DEPTH_PYRAMID => self.depth_pyramid,
+            SHADOW_ATLAS => self.shadow_atlas,
+            VISIBILITY_BUFFER => self.visibility_buffer,
+            _ => self
+                .textures
+                .get(name)
+                .map(|i| &self.physical_textures[*i].2),
+        }
+    }
+
+    /// A texture created by the graph
+    pub fn texture(&self, name: &str) -> Option<&wgpu::Texture> {
+        self.textures
+            .get(name)
+            .map(|i| &self.physical_textures[*i].1)
+    }
+
+    /// A buffer created by the graph
+    pub fn buffer(&self, name: &str) -> Option<&wgpu::Buffer> {
+        self.buffers.get(name)
+    }
+}
+
+struct PassNode {
+    name: Arc<str>,
+    /// None for the passes of the engine
+    pass: Option<Box<dyn FramePass>>,
+    builder: PassBuilder,
+}
+
+/// The passes of a frame, ordered by the textures and buffers they read and write. Custom
+/// passes go around the passes of the engine, [MAIN_PASS], [POST_PASS] and [UI_PASS], by
+/// reading and writing [SCENE], [DEPTH] and [FRAME], or with [PassBuilder::before] and
+/// [PassBuilder::after].
+///
+/// The writers of a texture run before the readers added after them, and in the order they
+/// were added among each other, so a pass that writes a texture the engine reads, such as
+/// [SCENE], is placed before the reader with [PassBuilder::before]. The textures the passes create are made by the graph when first needed and
+/// kept between frames, and the ones that are never in use at the same time share the same
+/// memory, so a texture that must keep its content until the next frame is best made by the
+/// pass itself:
+///
+/// ```ignore
+/// struct Outline;
+/// impl FramePass for Outline {
+///     fn setup(&mut self, builder: &mut PassBuilder) {
+///         builder.read(SCENE);
+///         builder.read(DEPTH);
+///         builder.write(FRAME);
+///         builder.before(UI_PASS);
+///     }
+///
+///     fn execute(&mut self, context: &mut PassContext) {
+///         let frame = context.view(FRAME).unwrap();
+///         // record a render pass into context.encoder
+///     }
+/// }
+///
+/// engine.renderer.frame_graph.add_pass("outline", Outline)?;
+/// ```
+///
+/// A texture a pass writes can be tagged with [PassBuilder::export], or with
+/// [FrameGraph::export] for the passes of the engine, to read it back to the CPU for analysis.
+/// The copy is read without stalling the frame, so [FrameGraph::exported] gives the latest
+/// one that is done, converted from its format.
+pub struct FrameGraph {
+    nodes: Vec<PassNode>,
+    /// The indices of the nodes in the order they run
+    order: Vec<usize>,
+    /// The next node of the order to run this frame
+    cursor: usize,
+    /// The textures created by the passes, mapped to the physical texture they use
+    textures: HashMap<Arc<str>, usize>,
+    physical_textures: Vec<(TextureKey, wgpu::Texture, wgpu::TextureView)>,
+    buffers: HashMap<Arc<str>, wgpu::Buffer>,
+    /// The frame size the textures were made for, or None if they must be made again
+    allocated_for: Option<(u32, u32)>,
+    /// The pass of the engine the renderer is recording, whose exports are copied after it
+    builtin_running: Option<usize>,
+    pending_exports: Vec<PendingExport>,
+    exports: HashMap<Arc<str>, ExportedTexture>,
+}
+
+impl std::fmt::Debug for FrameGraph {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrameGraph")
+            .field("passes", &self.pass_names())
+            .field("textures", &self.physical_textures.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl FrameGraph {
+    /// Creates the graph with only the passes of the engine
+    pub fn new() -> Self {
+        let builtin = |name: &str, reads: &[&str], writes: &[&str]| {
+            let mut builder = PassBuilder::default();
+            reads.iter().for_each(|i| builder.read(*i));
+            writes.iter().for_each(|i| builder.write(*i));
+            PassNode {
+                name: name.into(),
+                pass: None,
+                builder,
+            }
+        };
+
+        let mut graph = Self {
+            nodes: vec![
+                builtin(
+                    MAIN_PASS,
+                    &[],
+                    &[
+                        SCENE,
+                        DEPTH,
+                        MOTION_VECTORS,
+                        DEPTH_PYRAMID,
+                        SHADOW_ATLAS,
+                        VISIBILITY_BUFFER,
+                    ],
+                ),
+                builtin(POST_PASS, &[SCENE], &[FRAME]),
+                builtin(UI_PASS, &[], &[FRAME]),
+            ],
+            order: Vec::new(),
+            cursor: 0,
+            textures: HashMap::new(),
+            physical_textures: Vec::new(),
+            buffers: HashMap::new(),
+            allocated_for: None,
+            builtin_running: None,
+            pending_exports: Vec::new(),
+            exports: HashMap::new(),
+        };
+        graph.order = graph.sort().unwrap_or_default();
+
+        graph
+    }
+
+    /// Drops the textures and buffers of the passes, they are made again before the next frame. The
+    /// passes themselves are kept
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.textures.clear();
+        self.physical_textures.clear();
+        self.buffers.clear();
+        self.allocated_for = None;
+        self.pending_exports.clear();
+    }
+
+    /// Adds a custom pass, and places it among the others. Returns an error and leaves the
+    /// graph as it was if the pass reads something no pass writes, or runs in a circle
+    pub fn add_pass(
+        &mut self,
+        name: impl crate::StringBuffer,
+        mut pass: impl FramePass + 'static,
+    ) -> Result<(), Error> {
+        if self.has_pass(name.as_str()) {
+            return Err(Error::FrameGraphError(format!(
+                "there is already a pass named {}",
+                name.as_str()
+            )));
+        }
+
+        let mut builder = PassBuilder::default();
+        pass.setup(&mut builder);
+        for (name, desc) in builder.textures.iter() {
+            if builder.exports.contains(name) {
+                check_format(desc.format)?;
+            }
+        }
+        self.nodes.push(PassNode {
+            name: name.as_arc(),
+            pass: Some(Box::new(pass)),
+            builder,
+        });
+
+        match self.sort() {
+            Ok(order) => {
+                self.order = order;
+                self.allocated_for = None;
+                Ok(())
+            }
+            Err(e) => {
+                self.nodes.pop();
+                Err(e)
+            }
+        }
+    }
+
+    /// Removes a custom pass. The passes of the engine can not be removed
+    pub fn remove_pass(&mut self, name: &str) -> bool {
+        let Some(index) = self
+            .nodes
+            .iter()
+            .position(|i| i.pass.is_some() && i.name.as_ref() == name)
+        else {
+            return false;
+        };
+
+        self.nodes.remove(index);
+        self.exports
+            .retain(|name, _| self.nodes.iter().any(|i| i.builder.exports.contains(name)));
+        self.order = self.sort().unwrap_or_default();
+        self.allocated_for = None;
+        true
+    }
+
+    /// Tags a texture the pass writes for export, including the passes of the engine, such as
+    /// [DEPTH] of [MAIN_PASS]. The 8 bit RGBA and BGRA, the 16 and 32 bit float, and the
+    /// [crate::DEPTH_FORMAT] formats can be exported
+    pub fn export(&mut self, pass: &str, texture: impl crate::StringBuffer) -> Result<(), Error> {
+        let Some(node) = self.nodes.iter_mut().find(|i| i.name.as_ref() == pass) else {
+            return Err(Error::FrameGraphError(format!(
+                "there is no pass named {pass} to export from"
+            )));
+        };
+        if !node.builder.writes.contains(&texture.as_arc()) {
+            return Err(Error::FrameGraphError(format!(
+                "the pass {pass} does not write {}",
+                texture.as_str()
+            )));
+        }
+        if let Some((_, desc)) = node
+            .builder
+            .textures
+            .iter()
+            .find(|i| i.0.as_ref() == texture.as_str())
+        {
+            check_format(desc.format)?;
+        }
+
+        if !node.builder.exports.contains(&texture.as_arc()) {
+            node.builder.exports.push(texture.as_arc());
+            self.allocated_for = None;
+        }
+        Ok(())
+    }
+
+    /// The latest export of the texture that has been read back
+    pub fn exported(&self, texture: &str) -> Option<&ExportedTexture> {
+        self.exports.get(texture)
+    }
+
+    /// Returns true if the graph has a pass with the name, including the passes of the engine
+    pub fn has_pass(&self, name: &str) -> bool {
+        self.nodes.iter().any(|i| i.name.as_ref() == name)
+    }
+
+    /// The names of the passes, in the order they run
+    pub fn pass_names(&self) -> Vec<&str> {
+        self.order
+            .iter()
+            .map(|i| self.nodes[*i].name.as_ref())
+            .collect()
+    }
+
+    /// The amount of textures on the GPU made for the passes, after the sharing
+    pub fn texture_count(&self) -> usize {
+        self.physical_textures.len()
+    }
+
+    /// Orders the nodes so the writers of everything run before its readers
+    fn sort(&self) -> Result<Vec<usize>, Error> {
+        let count = self.nodes.len();
+        let index_of = |name: &str| self.nodes.iter().position(|i| i.name.as_ref() == name);
+        let mut edges = vec![Vec::new(); count];
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut before = Vec::new();
+            for (others, is_after) in [(&node.builder.after, true), (&node.builder.before, false)] {
+                for other in others.iter() {
+                    let Some(other) = index_of(other) else {
+                        return Err(Error::FrameGraphError(format!(
+                            "the pass {} is placed around {other}, which is not in the graph",
+                            node.name
+                        )));
+                    };
+                    if is_after {
+                        edges[other].push(index);
+                    } else {
+                        edges[index].push(other);
+                        before.push(other);
+                    }
+                }
+            }
+            // the passes this one is placed before do not count as writing before it
+            let writes = |i: usize, name: &Arc<str>| {
+                i != index && !before.contains(&i) && self.nodes[i].builder.writes.contains(name)
+            };
+
+            // a read waits for the writers added before the pass, or for all of them if none
+            for read in node.builder.reads.iter() {
+                let writers: Vec<_> = (0..count).filter(|i| writes(*i, read)).collect();
+                if writers.is_empty() {
+                    return Err(Error::FrameGraphError(format!(
+                        "the pass {} reads {read}, which no pass writes",
+                        node.name
+                    )));
+                }
+                let earlier: Vec<_> = writers.iter().copied().filter(|i| *i < index).collect();
+                let writers = if earlier.is_empty() { writers } else { earlier };
+                writers.into_iter().for_each(|i| edges[i].push(index));
+            }
+
+            // the writers of the same thing run in the order they were added
+            for write in node.builder.writes.iter() {
+                if let Some(previous) = (0..index).rev().find(|i| writes(*i, write)) {
+                    edges[previous].push(index);
+                }
+            }
+        }
+
+        // Kahn's algorithm, taking the earliest added pass first to keep the order stable
+        let mut incoming = vec![0; count];
+        edges.iter().flatten().for_each(|i| incoming[*i] += 1);
+        let mut ready: Vec<usize> = (0..count).filter(|i| incoming[*i] == 0).collect();
+        let mut order = Vec::with_capacity(count);
+        while !ready.is_empty() {
+            let next = ready.remove(0);
+            order.push(next);
+            for to in edges[next].iter() {
+                incoming[*to] -= 1;
+                if incoming[*to] == 0 {
+                    let at = ready.partition_point(|i| i < to);
+                    ready.insert(at, *to);
+                }
+            }
+        }
+
+        if order.len() != count {
+            return Err(Error::FrameGraphError(
+                "the passes depend on each other in a circle".to_string(),
+            ));
+        }
+
+        Ok(order)
+    }
+
+    /// Makes the textures and buffers of the passes, sharing the textures that are not in use
+    /// at the same time, and keeping the ones that still fit from before
+    fn allocate(&mut self, device: &wgpu::Device, frame_size: (u32, u32)) {
+        // the first and last place in the order each texture is used
+        let mut lifetimes: Vec<(Arc<str>, TextureKey, usize, usize)> = Vec::new();
+        for (position, index) in self.order.iter().enumerate() {
+            let builder = &self.nodes[*index].builder;
+            for (name, desc) in builder.textures.iter() {
+                if !lifetimes.iter().any(|i| i.0 == *name) {
+                    let mut key = desc.resolve(frame_size);
+                    // the exported textures are copied to the buffers they are read back from
+                    if self.nodes.iter().any(|i| i.builder.exports.contains(name)) {
+                        key.usage |= wgpu::TextureUsages::COPY_SRC;
+                    }
+                    lifetimes.push((name.clone(), key, position, position));
+                }
+            }
+            for name in builder.reads.iter().chain(builder.writes.iter()) {
+                if let Some(lifetime) = lifetimes.iter_mut().find(|i| i.0 == *name) {
+                    lifetime.3 = position;
+                }
+            }
+        }
+
+        let mut spare = std::mem::take(&mut self.physical_textures);
+        // the place in the order after which each physical texture is free again
+        let mut free_after: Vec<usize> = Vec::new();
+        self.textures.clear();
+        for (name, key, first, last) in lifetimes {
+            let shared = (0..self.physical_textures.len())
+                .find(|i| self.physical_textures[*i].0 == key && free_after[*i] < first);
+            let index = match shared {
+                Some(index) => {
+                    free_after[index] = last;
+                    index
+                }
+                None => {
+                    let physical = match spare.iter().position(|i| i.0 == key) {
+                        Some(position) => spare.swap_remove(position),
+                        None => {
+                            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                                label: Some(&name),
+                                size: wgpu::Extent3d {
+                                    width: key.width,
+                                    height: key.height,
+                                    depth_or_array_layers: 1,
+                                },
+                                mip_level_count: 1,
+                                sample_count: 1,
+                                dimension: wgpu::TextureDimension::D2,
+                                format: key.format,
+                                usage: key.usage,
+                                view_formats: &[],
+                            });
+                            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                            (key, texture, view)
+                        }
+                    };
+                    self.physical_textures.push(physical);
+                    free_after.push(last);
+                    self.physical_textures.len() - 1
+                }
+            };
+            self.textures.insert(name, index);
+        }
+
+        let mut buffers = std::mem::take(&mut self.buffers);
+        for (name, desc) in self.nodes.iter().flat_map(|i| i.builder.buffers.iter()) {
+            let buffer = match buffers.remove(name) {
+                Some(buffer) if buffer.size() == desc.size && buffer.usage() == desc.usage => {
+                    buffer
+                }
+                _ => {
+                    crate::utils::allocation_audit::count_buffer();
+                    device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some(name),
+                        size: desc.size,
+                        usage: desc.usage,
+                        mapped_at_creation: false,
+                    })
+                }
+            };
+            self.buffers.insert(name.clone(), buffer);
+        }
+
+        self.allocated_for = Some(frame_size);
+    }
+
+    /// Starts the frame, making the textures again if the frame changed size
+    pub(crate) fn begin_frame(&mut self, device: &wgpu::Device, frame_size: (u32, u32)) {
+        self.cursor = 0;
+        if self.allocated_for != Some(frame_size) {
+            self.allocate(device, frame_size);
+        }
+    }
+
+    /// Runs the custom passes up to the pass of the engine, or all that are left if None.
+    /// The pass of the engine itself is recorded by the renderer after
+    pub(crate) fn run_until(&mut self, builtin: Option<&str>, mut targets: FrameTargets) {
+        // the pass of the engine from the call before has been recorded by now
+        if let Some(index) = self.builtin_running.take() {
+            self.record_exports(index, &mut targets);
+        }
+
+        while let Some(index) = self.order.get(self.cursor).copied() {
+            self.cursor += 1;
+
+            let node = &mut self.nodes[index];
+            let Some(pass) = node.pass.as_mut() else {
+                if builtin.is_some_and(|i| i == node.name.as_ref()) {
+                    self.builtin_running = Some(index);
+                    return;
+                }
+                continue;
+            };
+
+            if targets.debug_groups {
+                targets.encoder.push_debug_group(&node.name);
+            }
+            let mut context = PassContext {
+                device: targets.device,
+                queue: targets.queue,
+                encoder: &mut *targets.encoder,
+                frame_size: targets.frame_size,
+                scene_format: targets.scene_format,
+                frame_format: targets.frame_format,
+                scene: targets.scene,
+                depth: targets.depth,
+                frame: targets.frame,
+                motion_vectors: targets.motion_vectors,
+                depth_pyramid: targets.depth_pyramid,
+                shadow_atlas: targets.shadow_atlas,
+                visibility_buffer: targets.visibility_buffer,
+                textures: &self.textures,
+                physical_textures: &self.physical_textures,
+                buffers: &self.buffers,
+            };
+            pass.execute(&mut context);
+            self.record_exports(index, &mut targets);
+            if targets.debug_groups {
+                targets.encoder.pop_debug_group();
+            }
+        }
+    }
+
+    /// Copies the textures the pass exports. A texture is only copied again once its copy
+    /// before has been read back
+    fn record_exports(&mut self, index: usize, targets: &mut FrameTargets) {
+        let node = &self.nodes[index];
+        for name in node.builder.exports.iter() {
+            if self.pending_exports.iter().any(|i| i.name == *name) {
+                continue;
+            }
+            let texture = match targets.texture(name) {
+                Some(texture) => texture,
+                None => match self.textures.get(name) {
+                    Some(index) => &self.physical_textures[*index].1,
+                    None => continue,
+                },
+            };
+
+            if let Some(export) = PendingExport::new(
+                targets.device,
+                targets.encoder,
+                name.clone(),
+                node.name.clone(),
+                texture,
+            ) {
+                self.pending_exports.push(export);
+            }
+        }
+    }
+
+    /// Starts reading back the exports of the frame, must be called after it is submitted,
+    /// and keeps the ones that are done
+    pub(crate) fn after_submit(&mut self, device: &wgpu::Device) {
+        if self.pending_exports.is_empty() {
+            return;
+        }
+        self.pending_exports.iter_mut().for_each(|i| i.start());
+        device.poll(wgpu::Maintain::Poll);
+
+        self.pending_exports.retain(|export| match export.finish() {
+            Some(Ok(texture)) => {
+                self.exports.insert(export.name.clone(), texture);
+                false
+            }
+            Some(Err(e)) => {
+                eprintln!("Could not export {}: {e}", export.name);
+                false
+            }
+            None => true,
+        });
+    }
+}
+impl Default for FrameGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The textures of the engine that the custom passes of the frame are given
+pub(crate) struct FrameTargets<'a, 'b> {
+    pub(crate) device: &'a wgpu::Device,
+    pub(crate) queue: &'a wgpu::Queue,
+    pub(crate) encoder: &'b mut wgpu::CommandEncoder,
+    pub(crate) frame_size: (u32, u32),
+    pub(crate) scene_format: wgpu::TextureFormat,
+    pub(crate) frame_format: wgpu::TextureFormat,
+    pub(crate) scene: &'a wgpu::TextureView,
+    pub(crate) depth: &'a wgpu::TextureView,
+    pub(crate) frame: &'a wgpu::TextureView,
+    pub(crate) motion_vectors: Option<&'a wgpu::TextureView>,
+    pub(crate) depth_pyramid: Option<&'a wgpu::TextureView>,
+    pub(crate) shadow_atlas: Option<&'a wgpu::TextureView>,
+    pub(crate) visibility_buffer: Option<&'a wgpu::TextureView>,
+    pub(crate) scene_texture: &'a wgpu::Texture,
+    pub(crate) depth_texture: &'a wgpu::Texture,
+    pub(crate) frame_texture: &'a wgpu::Texture,
+    pub(crate) motion_vectors_texture: Option<&'a wgpu::Texture>,
+    pub(crate) depth_pyramid_texture: Option<&'a wgpu::Texture>,
+    pub(crate) shadow_atlas_texture: Option<&'a wgpu::Texture>,
+    pub(crate) visibility_buffer_texture: Option<&'a wgpu::Texture>,
+    /// Should the custom passes be wrapped in debug groups of their names
+    pub(crate) debug_groups: bool,
+}
+
+impl<'a> FrameTargets<'a, '_> {
+    /// The texture of the engine with the name
+    fn texture(&self, name: &str) -> Option<&'a wgpu::Texture> {
+        match name {
+            SCENE => Some(self.scene_texture),
+            DEPTH => Some(self.depth_texture),
+            FRAME => Some(self.frame_texture),
+            MOTION_VECTORS => self.motion_vectors_texture,
+            DEPTH_PYRAMID => self.depth_pyramid_texture,
+            SHADOW_ATLAS => self.shadow_atlas_texture,
+            VISIBILITY_BUFFER => self.visibility_buffer_texture,
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pass that only declares what it uses
+    struct Declared(fn(&mut PassBuilder));
+    impl FramePass for Declared {
+        fn setup(&mut self, builder: &mut PassBuilder) {
+            (self.0)(builder)
+        }
+        fn execute(&mut self, _context: &mut PassContext) {}
+    }
+
+    fn position(graph: &FrameGraph, pass: &str) -> usize {
+        graph
+            .pass_names()
+            .iter()
+            .position(|i| *i == pass)
+            .expect("the pass is in the graph")
+    }
+
+    #[test]
+    fn builtin_order() {
+        let graph = FrameGraph::new();
+        assert_eq!(graph.pass_names(), [MAIN_PASS, POST_PASS, UI_PASS]);
+        assert!(graph.has_pass(MAIN_PASS));
+        assert!(!graph.has_pass("outline"));
+    }
+
+    #[test]
+    fn readers_run_after_writers() {
+        let mut graph = FrameGraph::new();
+        // added in the reverse order they have to run in
+        graph
+            .add_pass(
+                "composite",
+                Declared(|builder| {
+                    builder.read("blurred");
+                    builder.write(SCENE);
+                }),
+            )
+            .expect_err("nothing writes the blurred texture yet");
+        graph
+            .add_pass(
+                "blur",
+                Declared(|builder| {
+                    builder.read("mask");
+                    builder.create_texture(
+                        "blurred",
+                        TextureDesc::new(wgpu::TextureFormat::Rgba8Unorm),
+                    );
+                }),
+            )
+            .expect_err("nothing writes the mask yet");
+        graph
+            .add_pass(
+                "mask",
+                Declared(|builder| {
+                    builder.read(DEPTH);
+                    builder.create_texture("mask", TextureDesc::new(wgpu::TextureFormat::R8Unorm));
+                }),
+            )
+            .unwrap();
+        graph
+            .add_pass(
+                "blur",
+                Declared(|builder| {
+                    builder.read("mask");
+                    builder.create_texture(
+                        "blurred",
+                        TextureDesc::new(wgpu::TextureFormat::Rgba8Unorm),
+                    );
+                }),
+            )
+            .unwrap();
+        graph
+            .add_pass(
+                "composite",
+                Declared(|builder| {
+                    builder.read("blurred");
+                    builder.write(SCENE);
+                    builder.before(POST_PASS);
+                }),
+            )
+            .unwrap();
+
+        let order = graph.pass_names();
+        assert_eq!(order.len(), 6);
+        assert!(position(&graph, MAIN_PASS) < position(&graph, "mask"));
+        assert!(position(&graph, "mask") < position(&graph, "blur"));
+        assert!(position(&graph, "blur") < position(&graph, "composite"));
+        assert!(position(&graph, "composite") < position(&graph, POST_PASS));
+    }
+
+    #[test]
+    fn after_and_before() {
+        let mut graph = FrameGraph::new();
+        graph
+            .add_pass("late", Declared(|builder| builder.after(UI_PASS)))
+            .unwrap();
+        graph
+            .add_pass("early", Declared(|builder| builder.before(MAIN_PASS)))
+            .unwrap();
+        graph
+            .add_pass(
+                "between",
+                Declared(|builder| {
+                    builder.after(MAIN_PASS);
+                    builder.before(POST_PASS);
+                }),
+            )
+            .unwrap();
+
+        assert_eq!(
+            graph.pass_names(),
+            ["early", MAIN_PASS, "between", POST_PASS, UI_PASS, "late"]
+        );
+
+        graph
+            .add_pass("lost", Declared(|builder| builder.after("missing")))
+            .expect_err("the pass it is placed after is not in the graph");
+        assert!(!graph.has_pass("lost"));
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let mut graph = FrameGraph::new();
+        graph
+            .add_pass("first", Declared(|builder| builder.after(MAIN_PASS)))
+            .unwrap();
+        graph
+            .add_pass(
+                "second",
+                Declared(|builder| {
+                    builder.after("first");
+                    builder.before(MAIN_PASS);
+                }),
+            )
+            .expect_err("the passes run in a circle");
+        graph
+            .add_pass(
+                "reads_own_output",
+                Declared(|builder| {
+                    builder.read(FRAME);
+                    builder.before(POST_PASS);
+                    builder.after(UI_PASS);
+                }),
+            )
+            .expect_err("the pass is placed before a pass that runs before it");
+
+        // the graph is left as it was, the ready passes run in the order they were added
+        assert_eq!(graph.pass_names(), [MAIN_PASS, POST_PASS, UI_PASS, "first"]);
+    }
+
+    #[test]
+    fn writers_of_the_same_texture_keep_their_order() {
+        let mut graph = FrameGraph::new();
+        graph
+            .add_pass("particles", Declared(|builder| builder.write(SCENE)))
+            .unwrap();
+        graph
+            .add_pass(
+                "decals",
+                Declared(|builder| {
+                    builder.write(SCENE);
+                    builder.before(POST_PASS);
+                }),
+            )
+            .unwrap();
+        // reads the scene after the first two writers, and so runs after them
+        graph
+            .add_pass("bloom", Declared(|builder| builder.read(SCENE)))
+            .unwrap();
+
+        assert_eq!(
+            graph.pass_names(),
+            [
+                MAIN_PASS,
+                "particles",
+                "decals",
+                POST_PASS,
+                UI_PASS,
+                "bloom"
+            ]
+        );
+    }
+
+    #[test]
+    fn adding_and_removing() {
+        let mut graph = FrameGraph::new();
+        graph
+            .add_pass("outline", Declared(|builder| builder.read(SCENE)))
+            .unwrap();
+        graph
+            .add_pass("outline", Declared(|builder| builder.read(SCENE)))
+            .expect_err("the name is taken");
+
+        assert!(graph.remove_pass("outline"));
+        assert!(!graph.remove_pass("outline"));
+        assert!(!graph.remove_pass(MAIN_PASS));
+        assert_eq!(graph.pass_names(), [MAIN_PASS, POST_PASS, UI_PASS]);
+    }
+}
@@ -0,0 +1,334 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use super::default_resources::FRAME_HISTORY_SHADER;
+use crate::{Renderer, error::Error};
+
+/// Keeps downscaled copies of the last presented frames on the GPU, so they can be dumped
+/// when something goes wrong, such as right after a visual glitch.
+///
+/// The frames are only read back when they are requested, so keeping them costs a small copy
+/// on the GPU each frame.
+#[derive(Debug)]
+pub struct FrameHistory {
+    /// The amount of frames kept. Zero disables the history
+    pub capacity: usize,
+    /// The size of the kept frames relative to the presented ones, from 0 to 1
+    pub scale: f32,
+    /// The kept frames, oldest first
+    frames: std::collections::VecDeque<wgpu::Texture>,
+    /// A copy of the frame that can be sampled, for frames that can not be sampled themselves
+    source: Option<(wgpu::Texture, wgpu::TextureView)>,
+    sampler: Option<wgpu::Sampler>,
+    /// The layout, pipeline and the format it was built for
+    pipeline: Option<(
+        wgpu::TextureFormat,
+        wgpu::BindGroupLayout,
+        wgpu::RenderPipeline,
+    )>,
+}
+
+impl FrameHistory {
+    /// Creates a new disabled history
+    pub fn new() -> Self {
+        Self {
+            capacity: 0,
+            scale: 0.25,
+            frames: std::collections::VecDeque::new(),
+            source: None,
+            sampler: None,
+            pipeline: None,
+        }
+    }
+
+    /// Drops the kept frames and the pipeline, which were made on the lost device
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.frames.clear();
+        self.source = None;
+        self.sampler = None;
+        self.pipeline = None;
+    }
+
+    /// The amount of frames that are currently kept
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns true if no frames are kept
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Drops all the kept frames
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.source = None;
+    }
+
+    /// Copies a downscaled version of the frame into the history
+    pub(crate) fn record(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) {
+        if self.capacity == 0 {
+            if !self.frames.is_empty() {
+                self.clear();
+            }
+            return;
+        }
+
+        // the frame is copied first if it can not be sampled, such as most window surfaces
+        let source_view = if texture
+            .usage()
+            .contains(wgpu::TextureUsages::TEXTURE_BINDING)
+        {
+            texture.create_view(&wgpu::TextureViewDescriptor::default())
+        } else if texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            let source_matches = self.source.as_ref().is_some_and(|(source, _)| {
+                source.size() == texture.size() && source.format() == texture.format()
+            });
+            if !source_matches {
+                let source = device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Frame History Source"),
+                    size: texture.size(),
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: texture.format(),
+                    usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                });
+                let view = source.create_view(&wgpu::TextureViewDescriptor::default());
+                self.source = Some((source, view));
+            }
+            let Some((source, view)) = self.source.as_ref() else {
+                return;
+            };
+
+            encoder.copy_texture_to_texture(
+                texture.as_image_copy(),
+                source.as_image_copy(),
+                texture.size(),
+            );
+            view.clone()
+        } else {
+            eprintln!(
+                "The frame history is disabled, as the frames can not be copied on this window"
+            );
+            self.capacity = 0;
+            return;
+        };
+
+        // the kept frames use the same color space as the frame, so they look the same
+        let format = if texture.format().is_srgb() {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        let scale = self.scale.clamp(0.01, 1.0);
+        let size = wgpu::Extent3d {
+            width: ((texture.width() as f32 * scale) as u32).max(1),
+            height: ((texture.height() as f32 * scale) as u32).max(1),
+            depth_or_array_layers: 1,
+        };
+
+        while self.frames.len() > self.capacity {
+            self.frames.pop_front();
+        }
+        // the oldest frame is reused once the history is full
+        let reused = if self.frames.len() == self.capacity {
+            self.frames
+                .pop_front()
+                .filter(|i| i.size() == size && i.format() == format)
+        } else {
+            None
+        };
+        let frame = reused.unwrap_or_else(|| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Frame History Frame"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            })
+        });
+
+        if self.pipeline.as_ref().map(|i| i.0) != Some(format) {
+            let (layout, pipeline) = Self::build_pipeline(device, format);
+            self.pipeline = Some((format, layout, pipeline));
+        }
+        let Some((_, layout, pipeline)) = self.pipeline.as_ref() else {
+            return;
+        };
+        let sampler = self.sampler.get_or_insert_with(|| {
+            device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("Frame History Sampler"),
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            })
+        });
+
+        crate::utils::allocation_audit::count_bind_group();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Frame History Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&source_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        });
+        let frame_view = frame.create_view(&wgpu::TextureViewDescriptor::default());
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Frame History Render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &frame_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        self.frames.push_back(frame);
+    }
+
+    fn build_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline) {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Frame History Shader"),
+            source: wgpu::ShaderSource::Wgsl(FRAME_HISTORY_SHADER.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Frame History Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Frame History Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Frame History Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    write_mask: wgpu::ColorWrites::ALL,
+                    blend: None,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        (bind_group_layout, pipeline)
+    }
+}
+impl Default for FrameHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    /// Starts keeping the last `capacity` presented frames, downscaled by `scale`.
+    /// See [FrameHistory]
+    pub fn enable_frame_history(&mut self, capacity: usize, scale: f32) {
+        self.frame_history.capacity = capacity;
+        self.frame_history.scale = scale;
+    }
+
+    /// Stops keeping the frames, and drops the kept ones
+    pub fn disable_frame_history(&mut self) {
+        self.frame_history.capacity = 0;
+        self.frame_history.clear();
+    }
+
+    /// Reads the kept frames back from the GPU, oldest first. This waits for the GPU
+    pub fn frame_history_images(&self) -> Result<Vec<image::RgbaImage>, Error> {
+        self.frame_history
+            .frames
+            .iter()
+            .map(|i| self.read_texture(i))
+            .collect()
+    }
+
+    /// Writes the kept frames into the directory as `history_000.png`, oldest first.
+    /// The directory is created if needed. Returns the amount of frames written
+    pub fn dump_frame_history(
+        &self,
+        directory: impl AsRef<std::path::Path>,
+    ) -> Result<usize, Error> {
+        std::fs::create_dir_all(directory.as_ref())?;
+
+        let images = self.frame_history_images()?;
+        for (index, image) in images.iter().enumerate() {
+            image.save_with_format(
+                directory.as_ref().join(format!("history_{index:03}.png")),
+                image::ImageFormat::Png,
+            )?;
+        }
+
+        Ok(images.len())
+    }
+}
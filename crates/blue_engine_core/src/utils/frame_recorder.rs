@@ -0,0 +1,231 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{Renderer, error::Error, utils::frame_capture::TextureReadback};
+
+/// The most frames that can wait for their readback before the renderer waits for the GPU
+pub const MAX_PENDING_CAPTURES: usize = 8;
+
+/// How the recorded frames are written to files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// Each frame is written as a PNG image
+    Png,
+    /// Each frame is written as tightly packed 8 bit RGBA pixels, without a header
+    Raw,
+}
+
+/// An ongoing recording of frames into a directory
+#[derive(Debug, Clone)]
+struct Recording {
+    directory: std::path::PathBuf,
+    format: FrameFormat,
+    next_frame: u64,
+}
+
+/// A frame that is copied to a buffer and is being read back
+#[derive(Debug)]
+struct PendingCapture {
+    readback: TextureReadback,
+    path: std::path::PathBuf,
+    format: FrameFormat,
+    mapping: Option<crate::definition::BufferMapping>,
+}
+
+/// Captures presented frames into files, for screenshots and frame sequences.
+///
+/// The frames are copied into buffers as they are rendered, and written once the GPU
+/// is done with them, without stalling the frame.
+#[derive(Debug, Default)]
+pub struct FrameRecorder {
+    screenshots: Vec<std::path::PathBuf>,
+    recording: Option<Recording>,
+    pending: Vec<PendingCapture>,
+}
+
+impl FrameRecorder {
+    /// Creates a new recorder that captures nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops the captures still being read back from the lost device. The recording goes on
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Returns true if a frame sequence is being recorded
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Returns true if there are frames waiting to be captured or written
+    pub fn is_busy(&self) -> bool {
+        !self.screenshots.is_empty() || !self.pending.is_empty()
+    }
+
+    /// Copies the frame texture for every requested capture
+    pub(crate) fn capture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) {
+        let mut targets: Vec<_> = self
+            .screenshots
+            .drain(..)
+            .map(|path| {
+                let format = match path.extension().and_then(|i| i.to_str()) {
+                    Some("raw") | Some("rgba") => FrameFormat::Raw,
+                    _ => FrameFormat::Png,
+                };
+                (path, format)
+            })
+            .collect();
+        if let Some(recording) = self.recording.as_mut() {
+            let extension = match recording.format {
+                FrameFormat::Png => "png",
+                FrameFormat::Raw => "rgba",
+            };
+            targets.push((
+                recording
+                    .directory
+                    .join(format!("frame_{:06}.{extension}", recording.next_frame)),
+                recording.format,
+            ));
+            recording.next_frame += 1;
+        }
+
+        for (path, format) in targets {
+            match TextureReadback::new(device, encoder, texture) {
+                Ok(readback) => self.pending.push(PendingCapture {
+                    readback,
+                    path,
+                    format,
+                    mapping: None,
+                }),
+                Err(e) => eprintln!("Could not capture the frame to {}: {e}", path.display()),
+            }
+        }
+    }
+
+    /// Starts reading back the captured frames, must be called after the frame is submitted.
+    /// Finished frames are written, and if too many are waiting, waits for the GPU
+    pub(crate) fn after_submit(&mut self, device: &wgpu::Device) {
+        for capture in self.pending.iter_mut().filter(|i| i.mapping.is_none()) {
+            capture.mapping = Some(crate::definition::BufferMapping::read(
+                &capture.readback.buffer,
+            ));
+        }
+
+        self.write_finished(device, self.pending.len() > MAX_PENDING_CAPTURES);
+    }
+
+    /// Writes the frames whose readback is done. If `wait` is true, waits for all of them
+    pub(crate) fn write_finished(&mut self, device: &wgpu::Device, wait: bool) {
+        if self.pending.is_empty() {
+            return;
+        }
+        device.poll(if wait {
+            wgpu::Maintain::Wait
+        } else {
+            wgpu::Maintain::Poll
+        });
+
+        self.pending.retain(|capture| {
+            let Some(mapping) = capture.mapping.as_ref() else {
+                return true;
+            };
+
+            let result = match mapping.result() {
+                Some(Ok(())) => {
+                    let data = capture.readback.buffer.slice(..).get_mapped_range();
+                    let result =
+                        write_frame(&capture.readback, &data, &capture.path, capture.format);
+                    drop(data);
+                    capture.readback.buffer.unmap();
+
+                    result
+                }
+                Some(Err(e)) => Err(e),
+                None => return true,
+            };
+
+            if let Err(e) = result {
+                eprintln!(
+                    "Could not write the frame to {}: {e}",
+                    capture.path.display()
+                );
+            }
+            false
+        });
+    }
+}
+
+/// Writes a captured frame to a file
+fn write_frame(
+    readback: &TextureReadback,
+    data: &[u8],
+    path: &std::path::Path,
+    format: FrameFormat,
+) -> Result<(), Error> {
+    let image = readback.to_image(data)?;
+
+    match format {
+        FrameFormat::Png => image.save_with_format(path, image::ImageFormat::Png)?,
+        FrameFormat::Raw => std::fs::write(path, image.as_raw())?,
+    }
+
+    Ok(())
+}
+
+impl Renderer {
+    /// Saves the next presented frame to the given path.
+    ///
+    /// The frame is written once the GPU is done with it, usually a frame or two later.
+    /// Paths ending with `.raw` or `.rgba` are written as raw RGBA pixels, anything else as PNG.
+    pub fn screenshot(&mut self, path: impl AsRef<std::path::Path>) {
+        self.frame_recorder
+            .screenshots
+            .push(path.as_ref().to_path_buf());
+    }
+
+    /// Starts writing every presented frame into the directory, named `frame_000000.png`
+    /// or `frame_000000.rgba` by their order. The directory is created if needed.
+    pub fn start_recording(
+        &mut self,
+        directory: impl AsRef<std::path::Path>,
+        format: FrameFormat,
+    ) -> Result<(), Error> {
+        std::fs::create_dir_all(directory.as_ref())?;
+        self.frame_recorder.recording = Some(Recording {
+            directory: directory.as_ref().to_path_buf(),
+            format,
+            next_frame: 0,
+        });
+
+        Ok(())
+    }
+
+    /// Stops recording, and waits for the recorded frames to be written.
+    /// Returns the amount of frames that were recorded
+    pub fn stop_recording(&mut self) -> u64 {
+        let recorded = self
+            .frame_recorder
+            .recording
+            .take()
+            .map(|i| i.next_frame)
+            .unwrap_or(0);
+        self.frame_recorder.write_finished(&self.device, true);
+
+        recorded
+    }
+
+    /// Waits for all the captured frames to be written
+    pub fn flush_captures(&mut self) {
+        self.frame_recorder.write_finished(&self.device, true);
+    }
+}
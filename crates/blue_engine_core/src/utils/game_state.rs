@@ -0,0 +1,449 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{
+    CameraContainer, CommandEncoder, ObjectStorage, Renderer, SignalStorage, TextureView, Window,
+};
+
+/// The engine given to the hooks of a [GameState]
+pub struct StateContext<'a> {
+    /// The renderer of the engine
+    pub renderer: &'a mut Renderer,
+    /// The window of the engine
+    pub window: &'a mut Window,
+    /// The objects of the engine
+    pub objects: &'a mut ObjectStorage,
+    /// The input of this frame
+    pub input: &'a crate::utils::winit_input_helper::WinitInputHelper,
+    /// The cameras of the engine
+    pub camera: &'a mut CameraContainer,
+    /// The signals of the engine
+    pub signals: &'a mut SignalStorage,
+    commands: &'a mut Vec<StateCommand>,
+}
+
+impl StateContext<'_> {
+    /// Puts the state on top of the current one, which is paused until it is popped
+    pub fn push(&mut self, state: impl GameState + 'static) {
+        self.commands.push(StateCommand::Push(Box::new(state)));
+    }
+
+    /// Removes the current state, and resumes the one below it
+    pub fn pop(&mut self) {
+        self.commands.push(StateCommand::Pop);
+    }
+
+    /// Replaces the current state with another
+    pub fn switch(&mut self, state: impl GameState + 'static) {
+        self.commands.push(StateCommand::Switch(Box::new(state)));
+    }
+
+    /// Removes all the states
+    pub fn clear(&mut self) {
+        self.commands.push(StateCommand::Clear);
+    }
+}
+
+/// A screen or mode of the game, such as a menu, the gameplay, or a pause screen. The states
+/// are kept in the [StateStack], and only the one on top is updated
+#[allow(unused_variables)]
+pub trait GameState {
+    /// Ran when the state is put on the stack
+    fn enter(&mut self, ctx: &mut StateContext) {}
+
+    /// Ran when the state is taken off the stack
+    fn exit(&mut self, ctx: &mut StateContext) {}
+
+    /// Ran when another state is pushed on top of this one
+    fn pause(&mut self, ctx: &mut StateContext) {}
+
+    /// Ran when the state on top of this one is popped
+    fn resume(&mut self, ctx: &mut StateContext) {}
+
+    /// Ran every frame while the state is on top, after the update loop
+    fn update(&mut self, ctx: &mut StateContext) {}
+
+    /// Ran before the frame is rendered, while the state is on top or visible below a
+    /// transparent state
+    fn draw(&mut self, ctx: &mut StateContext, encoder: &mut CommandEncoder, view: &TextureView) {}
+
+    /// Should the states below keep drawing while this one is on top, such as for a pause
+    /// screen over the gameplay
+    fn is_transparent(&self) -> bool {
+        false
+    }
+}
+
+/// A change to the stack, applied between the hooks
+enum StateCommand<S = Box<dyn GameState>> {
+    Push(S),
+    Pop,
+    Switch(S),
+    Clear,
+}
+
+/// The hooks ran on the states when the stack changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transition {
+    Enter,
+    Exit,
+    Pause,
+    Resume,
+}
+
+/// Runs the transitions of the states of type `S`, and holds the changes they make
+trait Transitions<S> {
+    /// The changes to the stack that are not applied yet
+    fn commands(&mut self) -> &mut Vec<StateCommand<S>>;
+
+    /// Runs the hook of the transition on the state
+    fn run(&mut self, state: &mut S, transition: Transition);
+}
+
+impl Transitions<Box<dyn GameState>> for StateContext<'_> {
+    fn commands(&mut self) -> &mut Vec<StateCommand> {
+        self.commands
+    }
+
+    fn run(&mut self, state: &mut Box<dyn GameState>, transition: Transition) {
+        match transition {
+            Transition::Enter => state.enter(self),
+            Transition::Exit => state.exit(self),
+            Transition::Pause => state.pause(self),
+            Transition::Resume => state.resume(self),
+        }
+    }
+}
+
+/// The stack of [GameState]s, managed by the engine. The changes to the stack are applied at
+/// the next frame, and from inside the hooks right after the hook returns:
+///
+/// ```ignore
+/// struct Menu;
+/// impl GameState for Menu {
+///     fn update(&mut self, ctx: &mut StateContext) {
+///         if ctx.input.key_pressed(KeyCode::Enter) {
+///             ctx.switch(Gameplay::default());
+///         }
+///     }
+/// }
+///
+/// engine.states.push(Menu);
+/// ```
+pub struct StateStack {
+    states: Vec<Box<dyn GameState>>,
+    commands: Vec<StateCommand>,
+}
+
+impl StateStack {
+    /// Creates a new stack without states
+    pub fn new() -> Self {
+        Self {
+            states: Vec::new(),
+            commands: Vec::new(),
+        }
+    }
+
+    /// Puts the state on top of the current one, which is paused until it is popped
+    pub fn push(&mut self, state: impl GameState + 'static) {
+        self.commands.push(StateCommand::Push(Box::new(state)));
+    }
+
+    /// Removes the current state, and resumes the one below it
+    pub fn pop(&mut self) {
+        self.commands.push(StateCommand::Pop);
+    }
+
+    /// Replaces the current state with another
+    pub fn switch(&mut self, state: impl GameState + 'static) {
+        self.commands.push(StateCommand::Switch(Box::new(state)));
+    }
+
+    /// Removes all the states
+    pub fn clear(&mut self) {
+        self.commands.push(StateCommand::Clear);
+    }
+
+    /// The amount of states on the stack, without the changes that are not applied yet
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Returns true if there are no states on the stack
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Applies the changes to the stack, including the ones made by the hooks they ran
+    fn apply<S>(states: &mut Vec<S>, ctx: &mut impl Transitions<S>) {
+        loop {
+            let commands = std::mem::take(ctx.commands());
+            if commands.is_empty() {
+                break;
+            }
+
+            for command in commands {
+                match command {
+                    StateCommand::Push(mut state) => {
+                        if let Some(top) = states.last_mut() {
+                            ctx.run(top, Transition::Pause);
+                        }
+                        ctx.run(&mut state, Transition::Enter);
+                        states.push(state);
+                    }
+                    StateCommand::Pop => {
+                        if let Some(mut top) = states.pop() {
+                            ctx.run(&mut top, Transition::Exit);
+                            if let Some(top) = states.last_mut() {
+                                ctx.run(top, Transition::Resume);
+                            }
+                        }
+                    }
+                    StateCommand::Switch(mut state) => {
+                        if let Some(mut top) = states.pop() {
+                            ctx.run(&mut top, Transition::Exit);
+                        }
+                        ctx.run(&mut state, Transition::Enter);
+                        states.push(state);
+                    }
+                    StateCommand::Clear => {
+                        while let Some(mut top) = states.pop() {
+                            ctx.run(&mut top, Transition::Exit);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Applies the changes to the stack and updates the state on top
+    pub(crate) fn update(
+        &mut self,
+        renderer: &mut Renderer,
+        window: &mut Window,
+        objects: &mut ObjectStorage,
+        input: &crate::utils::winit_input_helper::WinitInputHelper,
+        camera: &mut CameraContainer,
+        signals: &mut SignalStorage,
+    ) {
+        if self.states.is_empty() && self.commands.is_empty() {
+            return;
+        }
+
+        let mut ctx = StateContext {
+            renderer,
+            window,
+            objects,
+            input,
+            camera,
+            signals,
+            commands: &mut self.commands,
+        };
+        Self::apply(&mut self.states, &mut ctx);
+
+        if let Some(top) = self.states.last_mut() {
+            top.update(&mut ctx);
+        }
+        Self::apply(&mut self.states, &mut ctx);
+    }
+
+    /// Draws the state on top, and the ones below it that are visible through it
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn draw(
+        &mut self,
+        renderer: &mut Renderer,
+        window: &mut Window,
+        objects: &mut ObjectStorage,
+        input: &crate::utils::winit_input_helper::WinitInputHelper,
+        camera: &mut CameraContainer,
+        signals: &mut SignalStorage,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+    ) {
+        if self.states.is_empty() {
+            return;
+        }
+
+        let mut ctx = StateContext {
+            renderer,
+            window,
+            objects,
+            input,
+            camera,
+            signals,
+            commands: &mut self.commands,
+        };
+
+        // the states are drawn from the lowest visible one up, so the top is drawn last
+        let first_visible = self
+            .states
+            .iter()
+            .rposition(|i| !i.is_transparent())
+            .unwrap_or(0);
+        for state in self.states[first_visible..].iter_mut() {
+            state.draw(&mut ctx, encoder, view);
+        }
+        Self::apply(&mut self.states, &mut ctx);
+    }
+}
+impl Default for StateStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl std::fmt::Debug for StateStack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StateStack")
+            .field("states", &self.states.len())
+            .field("pending", &self.commands.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A state told apart by its name, which changes the stack from its hooks by the
+    /// commands it was given
+    struct Named {
+        name: &'static str,
+        on_enter: Vec<StateCommand<Named>>,
+    }
+
+    fn named(name: &'static str) -> Named {
+        Named {
+            name,
+            on_enter: Vec::new(),
+        }
+    }
+
+    /// Writes the transitions to a log in place of the engine
+    #[derive(Default)]
+    struct Log {
+        commands: Vec<StateCommand<Named>>,
+        ran: Vec<String>,
+    }
+
+    impl Transitions<Named> for Log {
+        fn commands(&mut self) -> &mut Vec<StateCommand<Named>> {
+            &mut self.commands
+        }
+
+        fn run(&mut self, state: &mut Named, transition: Transition) {
+            self.ran.push(format!("{} {transition:?}", state.name));
+            if transition == Transition::Enter {
+                self.commands.append(&mut state.on_enter);
+            }
+        }
+    }
+
+    /// Applies the commands to the states, and returns the transitions that ran
+    fn apply(states: &mut Vec<Named>, commands: Vec<StateCommand<Named>>) -> Vec<String> {
+        let mut log = Log {
+            commands,
+            ..Default::default()
+        };
+        StateStack::apply(states, &mut log);
+        assert!(log.commands.is_empty());
+
+        log.ran
+    }
+
+    fn names(states: &[Named]) -> Vec<&'static str> {
+        states.iter().map(|i| i.name).collect()
+    }
+
+    #[test]
+    fn push_pauses_and_pop_resumes() {
+        let mut states = Vec::new();
+
+        assert_eq!(
+            apply(&mut states, vec![StateCommand::Push(named("game"))]),
+            ["game Enter"]
+        );
+        assert_eq!(
+            apply(&mut states, vec![StateCommand::Push(named("pause"))]),
+            ["game Pause", "pause Enter"]
+        );
+        assert_eq!(names(&states), ["game", "pause"]);
+
+        assert_eq!(
+            apply(&mut states, vec![StateCommand::Pop]),
+            ["pause Exit", "game Resume"]
+        );
+        assert_eq!(names(&states), ["game"]);
+
+        assert_eq!(apply(&mut states, vec![StateCommand::Pop]), ["game Exit"]);
+        assert!(states.is_empty());
+        assert!(apply(&mut states, vec![StateCommand::Pop]).is_empty());
+    }
+
+    #[test]
+    fn switch_replaces_the_top() {
+        let mut states = Vec::new();
+        apply(
+            &mut states,
+            vec![
+                StateCommand::Push(named("game")),
+                StateCommand::Push(named("menu")),
+            ],
+        );
+
+        assert_eq!(
+            apply(&mut states, vec![StateCommand::Switch(named("options"))]),
+            ["menu Exit", "options Enter"]
+        );
+        assert_eq!(names(&states), ["game", "options"]);
+
+        // switching an empty stack only enters the state
+        let mut states = Vec::new();
+        assert_eq!(
+            apply(&mut states, vec![StateCommand::Switch(named("menu"))]),
+            ["menu Enter"]
+        );
+    }
+
+    #[test]
+    fn clear_exits_from_the_top() {
+        let mut states = Vec::new();
+        apply(
+            &mut states,
+            vec![
+                StateCommand::Push(named("game")),
+                StateCommand::Push(named("pause")),
+                StateCommand::Push(named("options")),
+            ],
+        );
+
+        assert_eq!(
+            apply(&mut states, vec![StateCommand::Clear]),
+            ["options Exit", "pause Exit", "game Exit"]
+        );
+        assert!(states.is_empty());
+    }
+
+    #[test]
+    fn changes_from_hooks_are_applied_after_them() {
+        let mut states = Vec::new();
+        let mut loading = named("loading");
+        loading.on_enter = vec![StateCommand::Switch(named("game"))];
+        let mut splash = named("splash");
+        splash.on_enter = vec![StateCommand::Push(loading)];
+
+        assert_eq!(
+            apply(&mut states, vec![StateCommand::Push(splash)]),
+            [
+                "splash Enter",
+                "splash Pause",
+                "loading Enter",
+                "loading Exit",
+                "game Enter"
+            ]
+        );
+        assert_eq!(names(&states), ["splash", "game"]);
+    }
+}
@@ -0,0 +1,354 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+/// The id of a gamepad, which stays the same if the gamepad is reconnected
+pub type GamepadId = usize;
+
+/// The default deadzone of the axes
+pub const DEFAULT_DEADZONE: f32 = 0.1;
+
+/// Buttons of a gamepad, named by their position on a standard layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    /// The bottom face button, A on Xbox controllers
+    South,
+    /// The right face button, B on Xbox controllers
+    East,
+    /// The top face button, Y on Xbox controllers
+    North,
+    /// The left face button, X on Xbox controllers
+    West,
+    #[doc(hidden)]
+    LeftBumper,
+    #[doc(hidden)]
+    LeftTrigger,
+    #[doc(hidden)]
+    RightBumper,
+    #[doc(hidden)]
+    RightTrigger,
+    #[doc(hidden)]
+    Select,
+    #[doc(hidden)]
+    Start,
+    /// The button in the middle, such as the Xbox or PS button
+    Mode,
+    /// Pressing the left stick
+    LeftThumb,
+    /// Pressing the right stick
+    RightThumb,
+    #[doc(hidden)]
+    DPadUp,
+    #[doc(hidden)]
+    DPadDown,
+    #[doc(hidden)]
+    DPadLeft,
+    #[doc(hidden)]
+    DPadRight,
+}
+
+/// Axes of a gamepad, with values from -1 to 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    #[doc(hidden)]
+    LeftStickX,
+    #[doc(hidden)]
+    LeftStickY,
+    #[doc(hidden)]
+    RightStickX,
+    #[doc(hidden)]
+    RightStickY,
+    /// The left trigger, on gamepads that report it as an axis
+    LeftZ,
+    /// The right trigger, on gamepads that report it as an axis
+    RightZ,
+}
+
+/// Something that happened to a gamepad
+#[derive(Debug, Clone, PartialEq)]
+pub enum GamepadEvent {
+    /// A gamepad was connected, with its name
+    Connected(GamepadId, String),
+    /// A gamepad was disconnected
+    Disconnected(GamepadId),
+    /// A button was pressed
+    ButtonPressed(GamepadId, GamepadButton),
+    /// A button was released
+    ButtonReleased(GamepadId, GamepadButton),
+    /// An axis moved to a new value, before applying the deadzone
+    AxisChanged(GamepadId, GamepadAxis, f32),
+}
+
+/// The state of a single gamepad
+#[derive(Debug, Clone, Default)]
+pub struct GamepadState {
+    /// The name of the gamepad, as reported by the system
+    pub name: String,
+    /// Is the gamepad connected
+    pub connected: bool,
+    held: std::collections::HashSet<GamepadButton>,
+    pressed: std::collections::HashSet<GamepadButton>,
+    released: std::collections::HashSet<GamepadButton>,
+    axes: std::collections::HashMap<GamepadAxis, f32>,
+}
+
+impl GamepadState {
+    /// Returns true if the button was pressed in this frame
+    pub fn button_pressed(&self, button: GamepadButton) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    /// Returns true if the button was released in this frame
+    pub fn button_released(&self, button: GamepadButton) -> bool {
+        self.released.contains(&button)
+    }
+
+    /// Returns true if the button is held down
+    pub fn button_held(&self, button: GamepadButton) -> bool {
+        self.held.contains(&button)
+    }
+
+    /// Returns the raw value of the axis, without the deadzone
+    pub fn axis_raw(&self, axis: GamepadAxis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or(0.0)
+    }
+}
+
+/// The state of all the gamepads that have been connected, and the events of this frame.
+///
+/// Gamepads are read when the `gamepad` feature is enabled. Without it, events can still be
+/// sent with [Gamepads::process_event] from another source.
+#[derive(Debug, Clone)]
+pub struct Gamepads {
+    gamepads: std::collections::HashMap<GamepadId, GamepadState>,
+    events: Vec<GamepadEvent>,
+    /// Axis values closer to zero than this are read as zero
+    pub deadzone: f32,
+}
+
+impl Gamepads {
+    /// Creates a new state without any gamepads
+    pub fn new() -> Self {
+        Self {
+            gamepads: std::collections::HashMap::new(),
+            events: Vec::new(),
+            deadzone: DEFAULT_DEADZONE,
+        }
+    }
+
+    /// Returns the gamepads that are connected
+    pub fn connected(&self) -> impl Iterator<Item = (GamepadId, &GamepadState)> {
+        self.gamepads
+            .iter()
+            .filter(|(_, i)| i.connected)
+            .map(|(id, i)| (*id, i))
+    }
+
+    /// Returns the first connected gamepad, useful for single player games
+    pub fn first(&self) -> Option<&GamepadState> {
+        self.connected().min_by_key(|(id, _)| *id).map(|(_, i)| i)
+    }
+
+    /// Returns the state of a gamepad, if it has ever been connected
+    pub fn get(&self, id: GamepadId) -> Option<&GamepadState> {
+        self.gamepads.get(&id)
+    }
+
+    /// Returns the events of this frame, in the order they happened
+    pub fn events(&self) -> &[GamepadEvent] {
+        &self.events
+    }
+
+    /// Returns the value of the axis of a gamepad with the deadzone applied, rescaled so that
+    /// the edge of the deadzone is zero
+    pub fn axis(&self, id: GamepadId, axis: GamepadAxis) -> f32 {
+        let value = self.get(id).map(|i| i.axis_raw(axis)).unwrap_or(0.0);
+        let deadzone = self.deadzone.clamp(0.0, 0.99);
+
+        if value.abs() <= deadzone {
+            0.0
+        } else {
+            value.signum() * (value.abs() - deadzone) / (1.0 - deadzone)
+        }
+    }
+
+    /// Returns the two axes of a stick with the deadzone applied to their length, this keeps
+    /// diagonal movement smooth
+    pub fn stick(&self, id: GamepadId, x: GamepadAxis, y: GamepadAxis) -> (f32, f32) {
+        let Some(gamepad) = self.get(id) else {
+            return (0.0, 0.0);
+        };
+        let (x, y) = (gamepad.axis_raw(x), gamepad.axis_raw(y));
+        let length = (x * x + y * y).sqrt();
+        let deadzone = self.deadzone.clamp(0.0, 0.99);
+
+        if length <= deadzone {
+            (0.0, 0.0)
+        } else {
+            let scale = ((length - deadzone) / (1.0 - deadzone)).min(1.0) / length;
+            (x * scale, y * scale)
+        }
+    }
+
+    /// Applies an event to the state of the gamepads
+    pub fn process_event(&mut self, event: GamepadEvent) {
+        match &event {
+            GamepadEvent::Connected(id, name) => {
+                let gamepad = self.gamepads.entry(*id).or_default();
+                gamepad.name.clone_from(name);
+                gamepad.connected = true;
+            }
+            GamepadEvent::Disconnected(id) => {
+                if let Some(gamepad) = self.gamepads.get_mut(id) {
+                    gamepad.connected = false;
+                    gamepad.held.clear();
+                    gamepad.axes.clear();
+                }
+            }
+            GamepadEvent::ButtonPressed(id, button) => {
+                let gamepad = self.gamepads.entry(*id).or_default();
+                gamepad.connected = true;
+                if gamepad.held.insert(*button) {
+                    gamepad.pressed.insert(*button);
+                }
+            }
+            GamepadEvent::ButtonReleased(id, button) => {
+                let gamepad = self.gamepads.entry(*id).or_default();
+                if gamepad.held.remove(button) {
+                    gamepad.released.insert(*button);
+                }
+            }
+            GamepadEvent::AxisChanged(id, axis, value) => {
+                let gamepad = self.gamepads.entry(*id).or_default();
+                gamepad.connected = true;
+                gamepad.axes.insert(*axis, value.clamp(-1.0, 1.0));
+            }
+        }
+
+        self.events.push(event);
+    }
+
+    /// Clears the events and the pressed and released buttons of the last frame
+    pub(crate) fn step(&mut self) {
+        self.events.clear();
+        for gamepad in self.gamepads.values_mut() {
+            gamepad.pressed.clear();
+            gamepad.released.clear();
+        }
+    }
+}
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads the gamepads from the system, does nothing unless the `gamepad` feature is enabled
+#[derive(Default)]
+pub struct GamepadBackend {
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+impl GamepadBackend {
+    /// Connects to the gamepads of the system, if supported
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "gamepad")]
+            gilrs: match gilrs::Gilrs::new() {
+                Ok(gilrs) => Some(gilrs),
+                Err(e) => {
+                    eprintln!("Could not read the gamepads: {e}");
+                    None
+                }
+            },
+        }
+    }
+
+    /// Sends the new events of the gamepads to the state, including the ones already
+    /// connected the first time it is called
+    #[allow(unused_variables)]
+    pub(crate) fn poll(&mut self, gamepads: &mut Gamepads) {
+        #[cfg(feature = "gamepad")]
+        if let Some(gilrs) = self.gilrs.as_mut() {
+            // gamepads that were connected before the engine started do not send an event
+            let known: Vec<_> = gilrs
+                .gamepads()
+                .filter(|(id, _)| gamepads.get(usize::from(*id)).is_none())
+                .map(|(id, gamepad)| (usize::from(id), gamepad.name().to_string()))
+                .collect();
+            for (id, name) in known {
+                gamepads.process_event(GamepadEvent::Connected(id, name));
+            }
+
+            while let Some(gilrs::Event { id, event, .. }) = gilrs.next_event() {
+                let gamepad_id = usize::from(id);
+                let event = match event {
+                    gilrs::EventType::Connected => Some(GamepadEvent::Connected(
+                        gamepad_id,
+                        gilrs.gamepad(id).name().to_string(),
+                    )),
+                    gilrs::EventType::Disconnected => Some(GamepadEvent::Disconnected(gamepad_id)),
+                    gilrs::EventType::ButtonPressed(button, _) => {
+                        convert_button(button).map(|i| GamepadEvent::ButtonPressed(gamepad_id, i))
+                    }
+                    gilrs::EventType::ButtonReleased(button, _) => {
+                        convert_button(button).map(|i| GamepadEvent::ButtonReleased(gamepad_id, i))
+                    }
+                    gilrs::EventType::AxisChanged(axis, value, _) => {
+                        convert_axis(axis).map(|i| GamepadEvent::AxisChanged(gamepad_id, i, value))
+                    }
+                    _ => None,
+                };
+
+                if let Some(event) = event {
+                    gamepads.process_event(event);
+                }
+            }
+        }
+    }
+}
+impl std::fmt::Debug for GamepadBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GamepadBackend").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "gamepad")]
+fn convert_button(button: gilrs::Button) -> Option<GamepadButton> {
+    Some(match button {
+        gilrs::Button::South => GamepadButton::South,
+        gilrs::Button::East => GamepadButton::East,
+        gilrs::Button::North => GamepadButton::North,
+        gilrs::Button::West => GamepadButton::West,
+        gilrs::Button::LeftTrigger => GamepadButton::LeftBumper,
+        gilrs::Button::LeftTrigger2 => GamepadButton::LeftTrigger,
+        gilrs::Button::RightTrigger => GamepadButton::RightBumper,
+        gilrs::Button::RightTrigger2 => GamepadButton::RightTrigger,
+        gilrs::Button::Select => GamepadButton::Select,
+        gilrs::Button::Start => GamepadButton::Start,
+        gilrs::Button::Mode => GamepadButton::Mode,
+        gilrs::Button::LeftThumb => GamepadButton::LeftThumb,
+        gilrs::Button::RightThumb => GamepadButton::RightThumb,
+        gilrs::Button::DPadUp => GamepadButton::DPadUp,
+        gilrs::Button::DPadDown => GamepadButton::DPadDown,
+        gilrs::Button::DPadLeft => GamepadButton::DPadLeft,
+        gilrs::Button::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+#[cfg(feature = "gamepad")]
+fn convert_axis(axis: gilrs::Axis) -> Option<GamepadAxis> {
+    Some(match axis {
+        gilrs::Axis::LeftStickX => GamepadAxis::LeftStickX,
+        gilrs::Axis::LeftStickY => GamepadAxis::LeftStickY,
+        gilrs::Axis::RightStickX => GamepadAxis::RightStickX,
+        gilrs::Axis::RightStickY => GamepadAxis::RightStickY,
+        gilrs::Axis::LeftZ => GamepadAxis::LeftZ,
+        gilrs::Axis::RightZ => GamepadAxis::RightZ,
+        _ => return None,
+    })
+}
@@ -0,0 +1,172 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+/// The maximum amount of passes that can be measured in a frame
+pub const MAX_PROFILED_PASSES: u32 = 32;
+
+/// Measures the GPU time of render and compute passes using timestamp queries.
+///
+/// The results arrive a frame or more late, as they are read back without
+/// waiting for the GPU.
+#[derive(Debug)]
+pub struct GpuProfiler {
+    /// The query set that timestamps are written to
+    pub query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// The passes measured in the current frame
+    labels: Vec<String>,
+    /// The passes whose timestamps are in the readback buffer
+    pending_labels: Vec<String>,
+    resolved: bool,
+    /// The readback buffer, while it is being mapped
+    mapping: Option<crate::definition::BufferMapping>,
+    timestamp_period: f32,
+    /// The latest results, the label of each pass and its time in milliseconds
+    pub timings: Vec<(String, f32)>,
+}
+
+impl GpuProfiler {
+    /// Creates a new profiler. The device must have [`wgpu::Features::TIMESTAMP_QUERY`] enabled
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let size = (MAX_PROFILED_PASSES * 2) as u64 * wgpu::QUERY_SIZE as u64;
+
+        Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("GPU Profiler Query Set"),
+                ty: wgpu::QueryType::Timestamp,
+                count: MAX_PROFILED_PASSES * 2,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("GPU Profiler Resolve Buffer"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("GPU Profiler Readback Buffer"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            labels: Vec::new(),
+            pending_labels: Vec::new(),
+            resolved: false,
+            mapping: None,
+            timestamp_period: queue.get_timestamp_period(),
+            timings: Vec::new(),
+        }
+    }
+
+    /// Reserves timestamps for a render pass with the given label.
+    ///
+    /// Returns `None` if the frame already has [`MAX_PROFILED_PASSES`] measured passes.
+    pub fn render_pass_timestamps(
+        &mut self,
+        label: &str,
+    ) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        let index = self.reserve(label)?;
+
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(index),
+            end_of_pass_write_index: Some(index + 1),
+        })
+    }
+
+    /// Reserves timestamps for a compute pass with the given label.
+    ///
+    /// Returns `None` if the frame already has [`MAX_PROFILED_PASSES`] measured passes.
+    pub fn compute_pass_timestamps(
+        &mut self,
+        label: &str,
+    ) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        let index = self.reserve(label)?;
+
+        Some(wgpu::ComputePassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(index),
+            end_of_pass_write_index: Some(index + 1),
+        })
+    }
+
+    fn reserve(&mut self, label: &str) -> Option<u32> {
+        if self.labels.len() as u32 >= MAX_PROFILED_PASSES {
+            return None;
+        }
+        self.labels.push(label.to_string());
+
+        Some((self.labels.len() as u32 - 1) * 2)
+    }
+
+    /// Collects the results of the previous frames, if they arrived, and starts a new frame
+    pub(crate) fn begin_frame(&mut self, device: &wgpu::Device) {
+        if let Some(mapping) = self.mapping.as_ref() {
+            device.poll(wgpu::Maintain::Poll);
+
+            match mapping.result() {
+                Some(Ok(())) => {
+                    let data = self.readback_buffer.slice(..).get_mapped_range();
+                    let timestamps: &[u64] = bytemuck::cast_slice(&data);
+
+                    self.timings = self
+                        .pending_labels
+                        .iter()
+                        .enumerate()
+                        .map(|(index, label)| {
+                            let ticks =
+                                timestamps[index * 2 + 1].saturating_sub(timestamps[index * 2]);
+                            (
+                                label.clone(),
+                                ticks as f32 * self.timestamp_period / 1_000_000.0,
+                            )
+                        })
+                        .collect();
+                    drop(data);
+
+                    self.readback_buffer.unmap();
+                    self.mapping = None;
+                }
+                Some(Err(_)) => {
+                    self.mapping = None;
+                }
+                None => {}
+            }
+        }
+
+        self.labels.clear();
+    }
+
+    /// Copies the timestamps of this frame for reading, unless the previous ones are still being read
+    pub(crate) fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.mapping.is_some() || self.labels.is_empty() {
+            return;
+        }
+
+        let count = self.labels.len() as u32 * 2;
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            count as u64 * wgpu::QUERY_SIZE as u64,
+        );
+
+        self.pending_labels = self.labels.clone();
+        self.resolved = true;
+    }
+
+    /// Starts reading back the resolved timestamps, must be called after the frame is submitted
+    pub(crate) fn after_submit(&mut self) {
+        if self.resolved {
+            self.resolved = false;
+            self.mapping = Some(crate::definition::BufferMapping::read(
+                &self.readback_buffer,
+            ));
+        }
+    }
+}
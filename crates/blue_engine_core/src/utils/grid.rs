@@ -0,0 +1,239 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use wgpu::util::DeviceExt;
+
+use super::default_resources::GRID_SHADER;
+use crate::{CameraContainer, Vector4};
+
+/// The data of the grid sent to the GPU
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GridUniform {
+    view_projection: [[f32; 4]; 4],
+    inverse_view_projection: [[f32; 4]; 4],
+    camera_position: [f32; 4],
+    line_color: [f32; 4],
+    major_line_color: [f32; 4],
+    x_axis_color: [f32; 4],
+    z_axis_color: [f32; 4],
+    settings: [f32; 4],
+}
+
+/// An infinite editor style grid on the ground (XZ) plane, that fades with distance.
+///
+/// It is drawn after the objects and is hidden behind them.
+#[derive(Debug)]
+pub struct Grid {
+    /// Should the grid be drawn
+    pub enabled: bool,
+    /// Size of each cell of the grid in world units
+    pub cell_size: f32,
+    /// Amount of cells between each major line
+    pub major_line_every: u32,
+    /// Distance from the camera where the grid fully fades out
+    pub fade_distance: f32,
+    /// Width of the lines in pixels
+    pub line_width: f32,
+    /// Color of the lines
+    pub line_color: Vector4,
+    /// Color of the major lines
+    pub major_line_color: Vector4,
+    /// Color of the line along the X axis
+    pub x_axis_color: Vector4,
+    /// Color of the line along the Z axis
+    pub z_axis_color: Vector4,
+    /// The camera the grid is viewed from
+    pub camera: std::sync::Arc<str>,
+    /// The layout, pipeline and the format it was built for
+    pipeline: Option<(
+        wgpu::TextureFormat,
+        wgpu::BindGroupLayout,
+        wgpu::RenderPipeline,
+    )>,
+}
+
+impl Grid {
+    /// Creates a new disabled grid with default settings
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            cell_size: 1.0,
+            major_line_every: 10,
+            fade_distance: 50.0,
+            line_width: 1.0,
+            line_color: Vector4::new(0.5, 0.5, 0.5, 0.4),
+            major_line_color: Vector4::new(0.6, 0.6, 0.6, 0.7),
+            x_axis_color: Vector4::new(0.9, 0.2, 0.2, 1.0),
+            z_axis_color: Vector4::new(0.2, 0.4, 0.9, 1.0),
+            camera: "main".into(),
+            pipeline: None,
+        }
+    }
+
+    /// Drops the pipeline, to be made again on the new device
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.pipeline = None;
+    }
+
+    /// Draws the grid onto the view, testing against the depth of the scene
+    pub(crate) fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+        camera: &CameraContainer,
+    ) {
+        let Some(camera) = camera.get(self.camera.as_ref()) else {
+            return;
+        };
+
+        if self.pipeline.as_ref().map(|i| i.0) != Some(format) {
+            let (layout, pipeline) = Self::build_pipeline(device, format);
+            self.pipeline = Some((format, layout, pipeline));
+        }
+        let Some((_, layout, pipeline)) = self.pipeline.as_ref() else {
+            return;
+        };
+
+        let uniform = GridUniform {
+            view_projection: camera.view_data.to_cols_array_2d(),
+            inverse_view_projection: camera.view_data.inverse().to_cols_array_2d(),
+            camera_position: camera.position.extend(1.0).to_array(),
+            line_color: self.line_color.to_array(),
+            major_line_color: self.major_line_color.to_array(),
+            x_axis_color: self.x_axis_color.to_array(),
+            z_axis_color: self.z_axis_color.to_array(),
+            settings: [
+                self.cell_size.max(f32::EPSILON),
+                self.major_line_every.max(1) as f32,
+                self.fade_distance.max(f32::EPSILON),
+                self.line_width,
+            ],
+        };
+        crate::utils::allocation_audit::count_buffer();
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Grid Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        crate::utils::allocation_audit::count_bind_group();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Grid Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Grid Render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn build_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline) {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Grid Shader"),
+            source: wgpu::ShaderSource::Wgsl(GRID_SHADER.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Grid Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Grid Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Grid Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    write_mask: wgpu::ColorWrites::ALL,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+                unclipped_depth: false,
+            },
+            // the grid is transparent, so it does not hide what is drawn after it
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: crate::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        (bind_group_layout, pipeline)
+    }
+}
+impl Default for Grid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
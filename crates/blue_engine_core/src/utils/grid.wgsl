@@ -0,0 +1,94 @@
+// Infinite ground grid on the XZ plane, drawn on top of the scene with a full screen triangle
+
+struct GridUniform {
+    view_projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    camera_position: vec4<f32>,
+    line_color: vec4<f32>,
+    major_line_color: vec4<f32>,
+    x_axis_color: vec4<f32>,
+    z_axis_color: vec4<f32>,
+    // cell size, cells per major line, fade distance, line width in pixels
+    settings: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> grid: GridUniform;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) clip: vec2<f32>,
+};
+
+struct FragmentOutput {
+    @builtin(frag_depth) depth: f32,
+    @location(0) color: vec4<f32>,
+};
+
+fn unproject(point: vec3<f32>) -> vec3<f32> {
+    let world = grid.inverse_view_projection * vec4<f32>(point, 1.0);
+    return world.xyz / world.w;
+}
+
+// coverage of the lines of a grid with the given cell size, from 0 to 1
+fn grid_lines(position: vec2<f32>, cell_size: f32, line_width: f32) -> f32 {
+    let coordinates = position / cell_size;
+    let derivative = max(fwidth(coordinates), vec2<f32>(0.00001));
+    let distance = abs(fract(coordinates - 0.5) - 0.5) / derivative;
+
+    return 1.0 - min(min(distance.x, distance.y) / line_width, 1.0);
+}
+
+// coverage of a single line at zero, from 0 to 1
+fn axis_line(value: f32, line_width: f32) -> f32 {
+    let derivative = max(fwidth(value), 0.00001);
+
+    return 1.0 - min(abs(value) / derivative / line_width, 1.0);
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let clip = uv * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(clip, 0.0, 1.0);
+    out.clip = clip;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let near_point = unproject(vec3<f32>(in.clip, 0.0));
+    let far_point = unproject(vec3<f32>(in.clip, 1.0));
+
+    // where the view ray hits the ground
+    let denominator = far_point.y - near_point.y;
+    let t = select(-1.0, -near_point.y / denominator, abs(denominator) > 0.000001);
+    let world = near_point + (far_point - near_point) * clamp(t, 0.0, 1.0);
+
+    let cell_size = grid.settings.x;
+    let line_width = grid.settings.w;
+    let minor = grid_lines(world.xz, cell_size, line_width);
+    let major = grid_lines(world.xz, cell_size * grid.settings.y, line_width);
+    let x_axis = axis_line(world.z, line_width * 1.5);
+    let z_axis = axis_line(world.x, line_width * 1.5);
+
+    var color = grid.line_color * vec4<f32>(1.0, 1.0, 1.0, minor);
+    color = mix(color, grid.major_line_color, major);
+    color = mix(color, grid.x_axis_color, x_axis);
+    color = mix(color, grid.z_axis_color, z_axis);
+
+    let fade = 1.0 - clamp(distance(world, grid.camera_position.xyz) / grid.settings.z, 0.0, 1.0);
+    color.a = color.a * fade;
+
+    let clip_position = grid.view_projection * vec4<f32>(world, 1.0);
+
+    var out: FragmentOutput;
+    out.depth = clamp(clip_position.z / clip_position.w, 0.0, 1.0);
+    out.color = color;
+
+    if t <= 0.0 || t >= 1.0 || color.a <= 0.001 {
+        discard;
+    }
+    return out;
+}
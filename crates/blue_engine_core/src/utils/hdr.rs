@@ -0,0 +1,440 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use wgpu::util::DeviceExt;
+
+use super::default_resources::HDR_TONEMAPPING_SHADER;
+use crate::{Renderer, SamplerSettings, StringBuffer, Textures};
+
+/// The format the scene is rendered to when HDR is enabled
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// How the colors of the HDR scene are brought into the range of the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tonemapping {
+    /// The colors are clamped, so everything brighter than white is cut off
+    Clamp,
+    /// Keeps the colors of the dark parts, and slowly brings the bright parts towards white
+    Reinhard,
+    /// The filmic curve of ACES, with more contrast and saturation than Reinhard
+    #[default]
+    Aces,
+}
+impl Tonemapping {
+    fn mode(&self) -> u32 {
+        match self {
+            Self::Clamp => 0,
+            Self::Reinhard => 1,
+            Self::Aces => 2,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct TonemapParams {
+    exposure: f32,
+    mode: u32,
+    output: u32,
+    paper_white: f32,
+}
+
+/// The texture the scene of a window is rendered to before it is tonemapped
+#[derive(Debug)]
+pub(crate) struct HdrTarget {
+    size: (u32, u32),
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+impl HdrTarget {
+    /// The view the scene is drawn to
+    pub(crate) fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// The texture the scene is drawn to
+    pub(crate) fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+}
+
+/// The settings and the tonemapping pass of the HDR scene
+#[derive(Debug)]
+pub struct Hdr {
+    /// How the colors are brought into the range of the window
+    pub tonemapping: Tonemapping,
+    /// The scene is multiplied by this before it is tonemapped, 1.0 keeps it as is
+    pub exposure: f32,
+    /// How bright the white of the scene is in nits, on windows with HDR output. The brighter
+    /// parts of the scene go past it instead of being tonemapped
+    pub paper_white: f32,
+    /// The color space of the window, set when its surface is made
+    pub(crate) output: crate::utils::surface_format::OutputColor,
+    layout: Option<wgpu::BindGroupLayout>,
+    params: Option<wgpu::Buffer>,
+    /// The pipeline and the format it was built for
+    pipeline: Option<(wgpu::TextureFormat, wgpu::RenderPipeline)>,
+}
+
+impl Hdr {
+    /// Creates the settings with the given tonemapping and no exposure change
+    pub fn new(tonemapping: Tonemapping) -> Self {
+        Self {
+            tonemapping,
+            exposure: 1.0,
+            paper_white: 200.0,
+            output: crate::utils::surface_format::OutputColor::Sdr,
+            layout: None,
+            params: None,
+            pipeline: None,
+        }
+    }
+
+    /// Drops the pipeline and its buffer, keeping the settings of the tonemapping
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.layout = None;
+        self.params = None;
+        self.pipeline = None;
+    }
+
+    fn layout(&mut self, device: &wgpu::Device) -> &wgpu::BindGroupLayout {
+        self.layout.get_or_insert_with(|| {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Tonemapping Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            })
+        })
+    }
+
+    /// Returns the view of the target to render the scene to, rebuilding it when the size
+    /// changed
+    pub(crate) fn scene_view(
+        &mut self,
+        device: &wgpu::Device,
+        target: &mut Option<HdrTarget>,
+        width: u32,
+        height: u32,
+    ) -> wgpu::TextureView {
+        let size = (width.max(1), height.max(1));
+        if let Some(target) = target.as_ref()
+            && target.size == size
+        {
+            return target.view.clone();
+        }
+
+        let params = self
+            .params
+            .get_or_insert_with(|| {
+                crate::utils::allocation_audit::count_buffer();
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Tonemapping Params"),
+                    contents: bytemuck::bytes_of(&TonemapParams {
+                        exposure: 1.0,
+                        mode: 0,
+                        output: 0,
+                        paper_white: 0.0,
+                    }),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                })
+            })
+            .clone();
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("HDR Scene Target"),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        crate::utils::allocation_audit::count_bind_group();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Tonemapping Bind Group"),
+            layout: self.layout(device),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+        });
+
+        *target = Some(HdrTarget {
+            size,
+            texture,
+            view: view.clone(),
+            bind_group,
+        });
+
+        view
+    }
+
+    /// Tonemaps the scene of the target onto the view of the window
+    pub(crate) fn resolve(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &HdrTarget,
+        view: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+    ) {
+        if self.pipeline.as_ref().map(|i| i.0) != Some(format) {
+            let pipeline = self.build_pipeline(device, format);
+            self.pipeline = Some((format, pipeline));
+        }
+        let (Some((_, pipeline)), Some(params)) = (self.pipeline.as_ref(), self.params.as_ref())
+        else {
+            return;
+        };
+
+        queue.write_buffer(
+            params,
+            0,
+            bytemuck::bytes_of(&TonemapParams {
+                exposure: self.exposure.max(0.0),
+                mode: self.tonemapping.mode(),
+                output: self.output.mode(),
+                paper_white: self.paper_white.max(0.0),
+            }),
+        );
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Tonemapping Render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &target.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn build_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Tonemapping Shader"),
+            source: wgpu::ShaderSource::Wgsl(HDR_TONEMAPPING_SHADER.into()),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Tonemapping Pipeline Layout"),
+            bind_group_layouts: &[self.layout(device)],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Tonemapping Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    write_mask: wgpu::ColorWrites::ALL,
+                    blend: None,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+}
+
+/// Converts the float to the bits of a half float, rounding to the nearest
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7F_FFFF;
+
+    // infinity and NaN
+    if exponent == 0xFF {
+        return sign | 0x7C00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1F {
+        return sign | 0x7C00;
+    }
+    if exponent <= 0 {
+        // too small for the half float, even as a subnormal
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let rounding = (mantissa >> (shift - 1)) & 1;
+        return sign | ((mantissa >> shift) + rounding) as u16;
+    }
+
+    // the rounding can carry into the exponent, which is still correct
+    let half = (exponent as u32) << 10 | mantissa >> 13;
+    sign | (half + ((mantissa >> 12) & 1)) as u16
+}
+
+impl Renderer {
+    /// The format the objects are rendered to, which is [HDR_FORMAT] when HDR is enabled
+    pub fn scene_format(&self) -> wgpu::TextureFormat {
+        if self.hdr.is_some() {
+            HDR_FORMAT
+        } else {
+            self.config.format
+        }
+    }
+
+    /// Returns true if the scene is rendered in HDR, set by [crate::WindowDescriptor::hdr]
+    pub fn is_hdr(&self) -> bool {
+        self.hdr.is_some()
+    }
+
+    /// The settings of the HDR scene, if HDR is enabled. The tonemapping and the exposure can
+    /// be changed at any time
+    pub fn hdr(&mut self) -> Option<&mut Hdr> {
+        self.hdr.as_mut()
+    }
+
+    /// Creates a new texture for objects out of a high dynamic range image, such as the ones
+    /// loaded from `.hdr` and `.exr` files. The colors are kept above 1.0, in a
+    /// [wgpu::TextureFormat::Rgba16Float] texture, or [wgpu::TextureFormat::Rgba32Float] when
+    /// the device has [wgpu::Features::FLOAT32_FILTERABLE]
+    pub fn build_texture_hdr(
+        &mut self,
+        name: impl StringBuffer,
+        image: &image::DynamicImage,
+        texture_mode: impl Into<SamplerSettings>,
+    ) -> Textures {
+        let rgba = self
+            .fit_texture(
+                name.as_str(),
+                image::DynamicImage::ImageRgba32F(image.to_rgba32f()),
+            )
+            .into_rgba32f();
+        let (width, height) = rgba.dimensions();
+
+        let full_precision = self
+            .device
+            .features()
+            .contains(wgpu::Features::FLOAT32_FILTERABLE);
+        let (format, data): (_, Vec<u8>) = if full_precision {
+            (
+                wgpu::TextureFormat::Rgba32Float,
+                bytemuck::cast_slice(rgba.as_raw()).to_vec(),
+            )
+        } else {
+            let half: Vec<u16> = rgba.as_raw().iter().map(|i| f32_to_f16(*i)).collect();
+            (
+                wgpu::TextureFormat::Rgba16Float,
+                bytemuck::cast_slice(&half).to_vec(),
+            )
+        };
+        let pixel_size = format.block_copy_size(None).unwrap_or(8);
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(name.as_str()),
+            size,
+            mip_level_count: crate::utils::mipmaps::mip_level_count(width, height),
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        self.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &data,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(pixel_size * width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+        self.generate_mipmaps(&texture);
+
+        let textures = self.build_texture_from_gpu(name, &texture, texture_mode);
+        self.track_texture(&textures, &texture);
+        textures
+    }
+}
+
+/// Returns true if the image holds colors outside of the 0 to 1 range, as floats
+pub(crate) fn is_hdr_image(image: &image::DynamicImage) -> bool {
+    matches!(
+        image,
+        image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_)
+    )
+}
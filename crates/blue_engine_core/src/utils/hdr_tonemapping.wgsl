@@ -0,0 +1,79 @@
+// Maps the HDR scene into the range of the window with a full screen triangle
+
+struct TonemapParams {
+    exposure: f32,
+    // 0 for clamping, 1 for Reinhard, 2 for ACES
+    mode: u32,
+    // 0 for SDR, 1 for scRGB, 2 for HDR10
+    output: u32,
+    // the brightness of white in nits, for the HDR outputs
+    paper_white: f32,
+};
+
+@group(0) @binding(0)
+var scene_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> params: TonemapParams;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (vec3<f32>(1.0) + color);
+}
+
+// the fit of the ACES filmic curve by Krzysztof Narkowicz
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return (color * (a * color + b)) / (color * (c * color + d) + e);
+}
+
+// the ST 2084 curve of HDR10, from the brightness over 10000 nits
+fn pq(color: vec3<f32>) -> vec3<f32> {
+    let m1 = 0.1593017578125;
+    let m2 = 78.84375;
+    let c1 = 0.8359375;
+    let c2 = 18.8515625;
+    let c3 = 18.6875;
+    let y = pow(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(m1));
+    return pow((vec3<f32>(c1) + c2 * y) / (vec3<f32>(1.0) + c3 * y), vec3<f32>(m2));
+}
+
+// the primaries of Rec. 709, which the scene is in, moved to the ones of Rec. 2020
+fn rec709_to_rec2020(color: vec3<f32>) -> vec3<f32> {
+    return mat3x3<f32>(
+        vec3<f32>(0.6274, 0.0691, 0.0164),
+        vec3<f32>(0.3293, 0.9195, 0.0880),
+        vec3<f32>(0.0433, 0.0114, 0.8956),
+    ) * color;
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let scene = textureLoad(scene_texture, vec2<i32>(position.xy), 0);
+    let color = max(scene.rgb * params.exposure, vec3<f32>(0.0));
+    let alpha = clamp(scene.a, 0.0, 1.0);
+
+    // the HDR outputs keep the bright parts, so the scene is only scaled to the paper white
+    if params.output == 1u {
+        return vec4<f32>(color * params.paper_white / 80.0, alpha);
+    } else if params.output == 2u {
+        return vec4<f32>(pq(rec709_to_rec2020(color) * params.paper_white / 10000.0), alpha);
+    }
+
+    var mapped = color;
+    if params.mode == 1u {
+        mapped = reinhard(color);
+    } else if params.mode == 2u {
+        mapped = aces(color);
+    }
+
+    return vec4<f32>(clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0)), alpha);
+}
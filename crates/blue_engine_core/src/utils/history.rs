@@ -0,0 +1,601 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{
+    Material, Object, ObjectStorage, PipelineData, Renderer, StringBuffer, Textures, Transform,
+    Vector4,
+};
+
+/// The default amount of commands that can be undone
+pub const DEFAULT_HISTORY_LIMIT: usize = 256;
+
+/// A change to the scene that can be applied and reverted.
+///
+/// Commands are applied once when executed, and after that are only reverted and
+/// applied again by undo and redo, so they must leave the scene as they found it.
+pub trait Command {
+    /// Makes the change
+    fn apply(&mut self, objects: &mut ObjectStorage, renderer: &mut Renderer);
+    /// Reverts the change made by [`Command::apply`]
+    fn revert(&mut self, objects: &mut ObjectStorage, renderer: &mut Renderer);
+    /// A short description of the change, such as for an Edit menu
+    fn label(&self) -> &str;
+}
+
+/// Changes the position, rotation, and scale of an object
+#[derive(Debug, Clone)]
+pub struct SetTransform {
+    /// Name of the object
+    pub object: String,
+    /// The transform before the change
+    pub before: Transform,
+    /// The transform after the change
+    pub after: Transform,
+}
+impl SetTransform {
+    /// Creates a new transform change
+    pub fn new(object: impl StringBuffer, before: Transform, after: Transform) -> Self {
+        Self {
+            object: object.as_string(),
+            before,
+            after,
+        }
+    }
+}
+impl Command for SetTransform {
+    fn apply(&mut self, objects: &mut ObjectStorage, _renderer: &mut Renderer) {
+        if let Some(object) = objects.get_mut(&self.object) {
+            object.set_transform(self.after);
+        }
+    }
+    fn revert(&mut self, objects: &mut ObjectStorage, _renderer: &mut Renderer) {
+        if let Some(object) = objects.get_mut(&self.object) {
+            object.set_transform(self.before);
+        }
+    }
+    fn label(&self) -> &str {
+        "Transform"
+    }
+}
+
+/// Changes the color of an object
+#[derive(Debug, Clone)]
+pub struct SetColor {
+    /// Name of the object
+    pub object: String,
+    /// The color before the change
+    pub before: Vector4,
+    /// The color after the change
+    pub after: Vector4,
+}
+impl SetColor {
+    /// Creates a new color change
+    pub fn new(object: impl StringBuffer, before: Vector4, after: Vector4) -> Self {
+        Self {
+            object: object.as_string(),
+            before,
+            after,
+        }
+    }
+}
+impl Command for SetColor {
+    fn apply(&mut self, objects: &mut ObjectStorage, _renderer: &mut Renderer) {
+        if let Some(object) = objects.get_mut(&self.object) {
+            object.set_color(self.after.x, self.after.y, self.after.z, self.after.w);
+        }
+    }
+    fn revert(&mut self, objects: &mut ObjectStorage, _renderer: &mut Renderer) {
+        if let Some(object) = objects.get_mut(&self.object) {
+            object.set_color(self.before.x, self.before.y, self.before.z, self.before.w);
+        }
+    }
+    fn label(&self) -> &str {
+        "Color"
+    }
+}
+
+/// Replaces the texture of an object. The command holds the texture that is not in use,
+/// and swaps it with the object's on every apply and revert.
+#[derive(Debug)]
+pub struct SetTexture {
+    /// Name of the object
+    pub object: String,
+    /// The texture that is swapped in next
+    pub texture: PipelineData<Textures>,
+}
+impl SetTexture {
+    /// Creates a new texture change to the given texture
+    pub fn new(object: impl StringBuffer, texture: PipelineData<Textures>) -> Self {
+        Self {
+            object: object.as_string(),
+            texture,
+        }
+    }
+
+    fn swap(&mut self, objects: &mut ObjectStorage) {
+        if let Some(object) = objects.get_mut(&self.object) {
+            std::mem::swap(&mut object.pipeline.texture, &mut self.texture);
+            object.changed = true;
+        }
+    }
+}
+impl Command for SetTexture {
+    fn apply(&mut self, objects: &mut ObjectStorage, _renderer: &mut Renderer) {
+        self.swap(objects);
+    }
+    fn revert(&mut self, objects: &mut ObjectStorage, _renderer: &mut Renderer) {
+        self.swap(objects);
+    }
+    fn label(&self) -> &str {
+        "Texture"
+    }
+}
+
+/// Replaces one of the materials of an object. Like [`SetTexture`], the material that
+/// is not in use is held by the command.
+#[derive(Debug)]
+pub struct SetMaterial {
+    /// Name of the object
+    pub object: String,
+    /// Index of the material in the object's materials
+    pub index: usize,
+    /// The material that is swapped in next
+    pub material: Material,
+}
+impl SetMaterial {
+    /// Creates a new material change to the given material
+    pub fn new(object: impl StringBuffer, index: usize, material: Material) -> Self {
+        Self {
+            object: object.as_string(),
+            index,
+            material,
+        }
+    }
+
+    fn swap(&mut self, objects: &mut ObjectStorage) {
+        if let Some(material) = objects
+            .get_mut(&self.object)
+            .and_then(|object| object.materials.get_mut(self.index))
+        {
+            std::mem::swap(material, &mut self.material);
+        }
+    }
+}
+impl Command for SetMaterial {
+    fn apply(&mut self, objects: &mut ObjectStorage, _renderer: &mut Renderer) {
+        self.swap(objects);
+    }
+    fn revert(&mut self, objects: &mut ObjectStorage, _renderer: &mut Renderer) {
+        self.swap(objects);
+    }
+    fn label(&self) -> &str {
+        "Material"
+    }
+}
+
+/// Adds an object to the storage. The object is held by the command while it is undone.
+pub struct AddObject {
+    /// Name of the object in the storage
+    pub name: String,
+    object: Option<Object>,
+}
+impl AddObject {
+    /// Creates a new addition of the object
+    pub fn new(name: impl StringBuffer, object: Object) -> Self {
+        Self {
+            name: name.as_string(),
+            object: Some(object),
+        }
+    }
+}
+impl Command for AddObject {
+    fn apply(&mut self, objects: &mut ObjectStorage, _renderer: &mut Renderer) {
+        if let Some(object) = self.object.take() {
+            objects.insert(self.name.clone(), object);
+        }
+    }
+    fn revert(&mut self, objects: &mut ObjectStorage, _renderer: &mut Renderer) {
+        self.object = objects.remove(&self.name);
+    }
+    fn label(&self) -> &str {
+        "Add Object"
+    }
+}
+
+/// Removes an object from the storage. The object is held by the command until it is undone.
+pub struct RemoveObject {
+    /// Name of the object in the storage
+    pub name: String,
+    object: Option<Object>,
+}
+impl RemoveObject {
+    /// Creates a new removal of the object
+    pub fn new(name: impl StringBuffer) -> Self {
+        Self {
+            name: name.as_string(),
+            object: None,
+        }
+    }
+}
+impl Command for RemoveObject {
+    fn apply(&mut self, objects: &mut ObjectStorage, _renderer: &mut Renderer) {
+        self.object = objects.remove(&self.name);
+    }
+    fn revert(&mut self, objects: &mut ObjectStorage, _renderer: &mut Renderer) {
+        if let Some(object) = self.object.take() {
+            objects.insert(self.name.clone(), object);
+        }
+    }
+    fn label(&self) -> &str {
+        "Remove Object"
+    }
+}
+
+/// Several commands that are undone and redone together
+pub struct CommandGroup {
+    /// Description of the group
+    pub label: String,
+    /// The commands in the order they were applied
+    pub commands: Vec<Box<dyn Command>>,
+}
+impl CommandGroup {
+    /// Creates a new empty group
+    pub fn new(label: impl StringBuffer) -> Self {
+        Self {
+            label: label.as_string(),
+            commands: Vec::new(),
+        }
+    }
+}
+impl Command for CommandGroup {
+    fn apply(&mut self, objects: &mut ObjectStorage, renderer: &mut Renderer) {
+        for command in self.commands.iter_mut() {
+            command.apply(objects, renderer);
+        }
+    }
+    fn revert(&mut self, objects: &mut ObjectStorage, renderer: &mut Renderer) {
+        for command in self.commands.iter_mut().rev() {
+            command.revert(objects, renderer);
+        }
+    }
+    fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+/// Records the changes made to the scene, so they can be undone and redone
+pub struct CommandHistory {
+    undo_stack: std::collections::VecDeque<Box<dyn Command>>,
+    redo_stack: Vec<Box<dyn Command>>,
+    /// The commands of the group that is being recorded
+    group: Option<CommandGroup>,
+    /// The amount of commands that can be undone, the oldest ones are dropped past it
+    pub limit: usize,
+}
+
+impl CommandHistory {
+    /// Creates a new empty history
+    pub fn new() -> Self {
+        Self {
+            undo_stack: std::collections::VecDeque::new(),
+            redo_stack: Vec::new(),
+            group: None,
+            limit: DEFAULT_HISTORY_LIMIT,
+        }
+    }
+
+    /// Applies the command and records it
+    pub fn execute(
+        &mut self,
+        mut command: impl Command + 'static,
+        objects: &mut ObjectStorage,
+        renderer: &mut Renderer,
+    ) {
+        command.apply(objects, renderer);
+        self.record(command);
+    }
+
+    /// Records a command whose change has already been made, such as an object moved by dragging
+    pub fn record(&mut self, command: impl Command + 'static) {
+        self.redo_stack.clear();
+
+        if let Some(group) = self.group.as_mut() {
+            group.commands.push(Box::new(command));
+            return;
+        }
+
+        self.undo_stack.push_back(Box::new(command));
+        while self.undo_stack.len() > self.limit {
+            self.undo_stack.pop_front();
+        }
+    }
+
+    /// Changes the transform of an object and records it
+    pub fn set_transform(
+        &mut self,
+        object: impl StringBuffer,
+        transform: Transform,
+        objects: &mut ObjectStorage,
+        renderer: &mut Renderer,
+    ) {
+        let Some(before) = objects.get(object.as_str()).map(|i| i.transform()) else {
+            eprintln!("Object {} does not exist", object.as_str());
+            return;
+        };
+
+        self.execute(
+            SetTransform::new(object, before, transform),
+            objects,
+            renderer,
+        );
+    }
+
+    /// Changes the color of an object and records it
+    pub fn set_color(
+        &mut self,
+        object: impl StringBuffer,
+        color: Vector4,
+        objects: &mut ObjectStorage,
+        renderer: &mut Renderer,
+    ) {
+        let Some(before) = objects.get(object.as_str()).map(|i| i.color) else {
+            eprintln!("Object {} does not exist", object.as_str());
+            return;
+        };
+
+        self.execute(SetColor::new(object, before, color), objects, renderer);
+    }
+
+    /// Adds an object to the storage and records it
+    pub fn add_object(
+        &mut self,
+        name: impl StringBuffer,
+        object: Object,
+        objects: &mut ObjectStorage,
+        renderer: &mut Renderer,
+    ) {
+        self.execute(AddObject::new(name, object), objects, renderer);
+    }
+
+    /// Removes an object from the storage and records it
+    pub fn remove_object(
+        &mut self,
+        name: impl StringBuffer,
+        objects: &mut ObjectStorage,
+        renderer: &mut Renderer,
+    ) {
+        if !objects.contains_key(name.as_str()) {
+            eprintln!("Object {} does not exist", name.as_str());
+            return;
+        }
+
+        self.execute(RemoveObject::new(name), objects, renderer);
+    }
+
+    /// Starts grouping the recorded commands, so they are undone together as one.
+    /// Does nothing if a group is already being recorded.
+    pub fn begin_group(&mut self, label: impl StringBuffer) {
+        if self.group.is_none() {
+            self.group = Some(CommandGroup::new(label));
+        }
+    }
+
+    /// Stops grouping and records the group, unless it is empty
+    pub fn end_group(&mut self) {
+        if let Some(group) = self.group.take()
+            && !group.commands.is_empty()
+        {
+            self.record(group);
+        }
+    }
+
+    /// Reverts the last command. Returns false if there was nothing to undo
+    pub fn undo(&mut self, objects: &mut ObjectStorage, renderer: &mut Renderer) -> bool {
+        match self.step_back() {
+            Some(command) => {
+                command.revert(objects, renderer);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies the last undone command again. Returns false if there was nothing to redo
+    pub fn redo(&mut self, objects: &mut ObjectStorage, renderer: &mut Renderer) -> bool {
+        match self.step_forward() {
+            Some(command) => {
+                command.apply(objects, renderer);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the last command over to be redone, and returns it to be reverted
+    fn step_back(&mut self) -> Option<&mut Box<dyn Command>> {
+        self.end_group();
+
+        let command = self.undo_stack.pop_back()?;
+        self.redo_stack.push(command);
+        self.redo_stack.last_mut()
+    }
+
+    /// Moves the last undone command back to be undone, and returns it to be applied again
+    fn step_forward(&mut self) -> Option<&mut Box<dyn Command>> {
+        let command = self.redo_stack.pop()?;
+        self.undo_stack.push_back(command);
+        self.undo_stack.back_mut()
+    }
+
+    /// Returns true if there is a command to undo
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// Returns true if there is a command to redo
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// The label of the command that is undone next
+    pub fn undo_label(&self) -> Option<&str> {
+        self.undo_stack.back().map(|i| i.label())
+    }
+
+    /// The label of the command that is redone next
+    pub fn redo_label(&self) -> Option<&str> {
+        self.redo_stack.last().map(|i| i.label())
+    }
+
+    /// Forgets all the recorded commands
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.group = None;
+    }
+}
+impl Default for CommandHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A command that changes nothing, told apart by its label
+    struct Labeled(&'static str);
+    impl Command for Labeled {
+        fn apply(&mut self, _objects: &mut ObjectStorage, _renderer: &mut Renderer) {}
+        fn revert(&mut self, _objects: &mut ObjectStorage, _renderer: &mut Renderer) {}
+        fn label(&self) -> &str {
+            self.0
+        }
+    }
+
+    #[test]
+    fn empty_history() {
+        let mut history = CommandHistory::new();
+        assert!(!history.can_undo());
+        assert!(!history.can_redo());
+        assert_eq!(history.undo_label(), None);
+        assert!(history.step_back().is_none());
+        assert!(history.step_forward().is_none());
+    }
+
+    #[test]
+    fn undo_and_redo_labels() {
+        let mut history = CommandHistory::new();
+        history.record(Labeled("first"));
+        history.record(Labeled("second"));
+        assert_eq!(history.undo_label(), Some("second"));
+
+        assert_eq!(
+            history.step_back().map(|i| i.label().to_string()),
+            Some("second".into())
+        );
+        assert!(history.can_undo());
+        assert!(history.can_redo());
+        assert_eq!(history.undo_label(), Some("first"));
+        assert_eq!(history.redo_label(), Some("second"));
+
+        history.step_back();
+        assert!(!history.can_undo());
+        assert_eq!(history.redo_label(), Some("first"));
+
+        assert_eq!(
+            history.step_forward().map(|i| i.label().to_string()),
+            Some("first".into())
+        );
+        assert_eq!(history.undo_label(), Some("first"));
+        assert_eq!(history.redo_label(), Some("second"));
+    }
+
+    #[test]
+    fn new_command_clears_redo() {
+        let mut history = CommandHistory::new();
+        history.record(Labeled("first"));
+        history.record(Labeled("second"));
+        history.step_back();
+        assert!(history.can_redo());
+
+        history.record(Labeled("third"));
+        assert!(!history.can_redo());
+        assert_eq!(history.redo_label(), None);
+        assert_eq!(history.undo_label(), Some("third"));
+        history.step_back();
+        assert_eq!(history.undo_label(), Some("first"));
+    }
+
+    #[test]
+    fn groups_are_undone_as_one() {
+        let mut history = CommandHistory::new();
+        history.record(Labeled("before"));
+        history.begin_group("drag");
+        history.record(Labeled("move"));
+        history.record(Labeled("rotate"));
+        // a second begin while grouping keeps the first group
+        history.begin_group("ignored");
+        history.record(Labeled("scale"));
+        history.end_group();
+        assert_eq!(history.undo_label(), Some("drag"));
+
+        history.step_back();
+        assert_eq!(history.redo_label(), Some("drag"));
+        assert_eq!(history.undo_label(), Some("before"));
+    }
+
+    #[test]
+    fn empty_groups_are_dropped() {
+        let mut history = CommandHistory::new();
+        history.begin_group("nothing");
+        history.end_group();
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn undo_ends_the_open_group() {
+        let mut history = CommandHistory::new();
+        history.begin_group("open");
+        history.record(Labeled("inside"));
+        assert!(!history.can_undo());
+
+        assert_eq!(
+            history.step_back().map(|i| i.label().to_string()),
+            Some("open".into())
+        );
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn limit_drops_the_oldest() {
+        let mut history = CommandHistory::new();
+        history.limit = 2;
+        history.record(Labeled("first"));
+        history.record(Labeled("second"));
+        history.record(Labeled("third"));
+
+        history.step_back();
+        history.step_back();
+        assert!(!history.can_undo());
+        assert_eq!(history.redo_label(), Some("second"));
+    }
+
+    #[test]
+    fn clear_forgets_everything() {
+        let mut history = CommandHistory::new();
+        history.record(Labeled("first"));
+        history.record(Labeled("second"));
+        history.step_back();
+        history.begin_group("open");
+        history.clear();
+        assert!(!history.can_undo());
+        assert!(!history.can_redo());
+        history.end_group();
+        assert!(!history.can_undo());
+    }
+}
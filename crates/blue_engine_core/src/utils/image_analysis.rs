@@ -0,0 +1,310 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use wgpu::util::DeviceExt;
+
+use super::default_resources::IMAGE_ANALYSIS_SHADER;
+use crate::{Renderer, Vector4};
+
+/// The amount of bins in the luminance histogram
+pub const HISTOGRAM_BINS: usize = 256;
+/// The luminance below which pixels are counted as black, in the first bin
+pub const BLACK_LUMINANCE: f32 = 0.00001;
+// min and max of rgba and luminance
+const MIN_MAX_VALUES: usize = 10;
+
+/// The result of analyzing a texture
+#[derive(Debug, Clone)]
+pub struct ImageAnalysisResult {
+    /// Amount of pixels in each luminance bin. The first bin holds the black pixels, and the
+    /// rest split the log2 of the luminance evenly between `min_log` and `max_log`, with the
+    /// pixels outside of the range in the bins at its ends. See
+    /// [ImageAnalysisResult::bin_luminance]
+    pub histogram: [u32; HISTOGRAM_BINS],
+    /// The log2 luminance at the start of the second bin
+    pub min_log: f32,
+    /// The log2 luminance at the end of the last bin
+    pub max_log: f32,
+    /// The geometric mean of the luminance of the pixels that are not black, estimated from
+    /// the histogram. This is the average used for auto exposure, as a few bright pixels do not
+    /// outweigh the rest of the image
+    pub average_luminance: f32,
+    /// The lowest luminance of the texture
+    pub min_luminance: f32,
+    /// The highest luminance of the texture
+    pub max_luminance: f32,
+    /// The lowest value of each channel
+    pub min: Vector4,
+    /// The highest value of each channel
+    pub max: Vector4,
+    /// Amount of pixels analyzed
+    pub pixel_count: u32,
+}
+
+impl ImageAnalysisResult {
+    /// The luminance at the middle of the bin, zero for the first one
+    pub fn bin_luminance(&self, bin: usize) -> f32 {
+        match bin {
+            0 => 0.0,
+            _ => {
+                let position = (bin as f32 - 0.5) / (HISTOGRAM_BINS - 1) as f32;
+                (self.min_log + position * (self.max_log - self.min_log)).exp2()
+            }
+        }
+    }
+}
+
+/// The settings of the shader, laid out as `AnalysisSettings`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct AnalysisSettings {
+    min_log: f32,
+    log_range: f32,
+    padding: [f32; 2],
+}
+
+/// Compute utilities for analyzing any texture on the GPU, such as histogram,
+/// average luminance, and min/max of the channels.
+///
+/// The texture must be created with [`wgpu::TextureUsages::TEXTURE_BINDING`] and have a float format.
+/// The luminance is binned by its log2, so the HDR textures are covered up to `2^max_log`
+#[derive(Debug)]
+pub struct ImageAnalysis {
+    /// The log2 of the lowest luminance the histogram tells apart, -10 by default
+    pub min_log: f32,
+    /// The log2 of the highest luminance the histogram tells apart, 10 by default
+    pub max_log: f32,
+    /// The layout of the bind group used by the compute shader
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    /// The compute pipeline
+    pub pipeline: wgpu::ComputePipeline,
+}
+
+impl ImageAnalysis {
+    /// Creates the compute pipeline for analysis
+    pub fn new(renderer: &Renderer) -> Self {
+        let device = &renderer.device;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Image Analysis Shader"),
+            source: wgpu::ShaderSource::Wgsl(IMAGE_ANALYSIS_SHADER.into()),
+        });
+
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Image Analysis Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                storage_entry(1),
+                storage_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Image Analysis Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Image Analysis Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        Self {
+            min_log: -10.0,
+            max_log: 10.0,
+            bind_group_layout,
+            pipeline,
+        }
+    }
+
+    /// Analyzes the texture and waits for the result
+    pub fn analyze(
+        &self,
+        renderer: &Renderer,
+        texture: &wgpu::Texture,
+    ) -> Result<ImageAnalysisResult, crate::error::Error> {
+        let device = &renderer.device;
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        crate::utils::allocation_audit::count_buffer();
+        let histogram_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Image Analysis Histogram Buffer"),
+            contents: bytemuck::cast_slice(&[0u32; HISTOGRAM_BINS]),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+        let mut min_max = [u32::MAX; MIN_MAX_VALUES];
+        min_max[MIN_MAX_VALUES / 2..].fill(0);
+        crate::utils::allocation_audit::count_buffer();
+        let min_max_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Image Analysis Min Max Buffer"),
+            contents: bytemuck::cast_slice(&min_max),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+
+        let settings = AnalysisSettings {
+            min_log: self.min_log,
+            log_range: (self.max_log - self.min_log).max(f32::EPSILON),
+            padding: [0.0; 2],
+        };
+        crate::utils::allocation_audit::count_buffer();
+        let settings_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Image Analysis Settings Buffer"),
+            contents: bytemuck::bytes_of(&settings),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let histogram_size = histogram_buffer.size();
+        crate::utils::allocation_audit::count_buffer();
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Image Analysis Staging Buffer"),
+            size: histogram_size + min_max_buffer.size(),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        crate::utils::allocation_audit::count_bind_group();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Image Analysis Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: histogram_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: min_max_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: settings_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Image Analysis Encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Image Analysis Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                texture.width().div_ceil(16),
+                texture.height().div_ceil(16),
+                1,
+            );
+        }
+        encoder.copy_buffer_to_buffer(&histogram_buffer, 0, &staging_buffer, 0, histogram_size);
+        encoder.copy_buffer_to_buffer(
+            &min_max_buffer,
+            0,
+            &staging_buffer,
+            histogram_size,
+            min_max_buffer.size(),
+        );
+        renderer.queue.submit(std::iter::once(encoder.finish()));
+
+        let data = renderer.read_buffer(&staging_buffer)?;
+        let values: &[u32] = bytemuck::cast_slice(&data);
+
+        let mut histogram = [0u32; HISTOGRAM_BINS];
+        histogram.copy_from_slice(&values[..HISTOGRAM_BINS]);
+        let min_max = &values[HISTOGRAM_BINS..];
+        let as_float = |index: usize| f32::from_bits(min_max[index]);
+
+        let mut result = ImageAnalysisResult {
+            histogram,
+            min_log: self.min_log,
+            max_log: self.max_log,
+            average_luminance: 0.0,
+            min_luminance: as_float(4),
+            max_luminance: as_float(9),
+            min: Vector4::new(as_float(0), as_float(1), as_float(2), as_float(3)),
+            max: Vector4::new(as_float(5), as_float(6), as_float(7), as_float(8)),
+            pixel_count: texture.width() * texture.height(),
+        };
+
+        // the mean of the log2 luminance, leaving out the black pixels whose log has no bound
+        let lit = histogram[1..].iter().sum::<u32>();
+        if lit > 0 {
+            let log_sum = (1..HISTOGRAM_BINS)
+                .map(|bin| result.bin_luminance(bin).log2() * histogram[bin] as f32)
+                .sum::<f32>();
+            result.average_luminance = (log_sum / lit as f32).exp2();
+        }
+
+        Ok(result)
+    }
+
+    /// Returns the luminance histogram of the texture
+    pub fn histogram(
+        &self,
+        renderer: &Renderer,
+        texture: &wgpu::Texture,
+    ) -> Result<[u32; HISTOGRAM_BINS], crate::error::Error> {
+        Ok(self.analyze(renderer, texture)?.histogram)
+    }
+
+    /// Returns the geometric mean of the luminance of the texture
+    pub fn average_luminance(
+        &self,
+        renderer: &Renderer,
+        texture: &wgpu::Texture,
+    ) -> Result<f32, crate::error::Error> {
+        Ok(self.analyze(renderer, texture)?.average_luminance)
+    }
+
+    /// Returns the lowest and highest value of each channel of the texture
+    pub fn min_max(
+        &self,
+        renderer: &Renderer,
+        texture: &wgpu::Texture,
+    ) -> Result<(Vector4, Vector4), crate::error::Error> {
+        let result = self.analyze(renderer, texture)?;
+        Ok((result.min, result.max))
+    }
+}
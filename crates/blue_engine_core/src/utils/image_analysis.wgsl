@@ -0,0 +1,57 @@
+// Image analysis compute shader, gathers a log2 luminance histogram and min/max of the texture
+
+@group(0) @binding(0)
+var input_texture: texture_2d<f32>;
+
+// the first bin holds the black pixels, and the other 255 split the log2 luminance between
+// the min and max log of the settings
+@group(0) @binding(1)
+var<storage, read_write> histogram: array<atomic<u32>, 256>;
+
+// min of rgba and luminance, followed by max of rgba and luminance.
+// The values are stored as the bits of positive floats, which keep their order as u32
+@group(0) @binding(2)
+var<storage, read_write> min_max: array<atomic<u32>, 10>;
+
+struct AnalysisSettings {
+    min_log: f32,
+    log_range: f32,
+    padding: vec2<f32>,
+}
+
+@group(0) @binding(3)
+var<uniform> settings: AnalysisSettings;
+
+// luminance below this is counted as black
+const BLACK: f32 = 0.00001;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+fn luminance_bin(value: f32) -> u32 {
+    if value < BLACK {
+        return 0u;
+    }
+    let position = clamp((log2(value) - settings.min_log) / settings.log_range, 0.0, 1.0);
+    return 1u + min(u32(position * 255.0), 254u);
+}
+
+@compute @workgroup_size(16, 16)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dimensions = textureDimensions(input_texture);
+    if id.x >= dimensions.x || id.y >= dimensions.y {
+        return;
+    }
+
+    let color = max(textureLoad(input_texture, vec2<i32>(id.xy), 0), vec4<f32>(0.0));
+    let value = luminance(color.rgb);
+
+    atomicAdd(&histogram[luminance_bin(value)], 1u);
+
+    let values = array<f32, 5>(color.r, color.g, color.b, color.a, value);
+    for (var i = 0u; i < 5u; i++) {
+        atomicMin(&min_max[i], bitcast<u32>(values[i]));
+        atomicMax(&min_max[i + 5u], bitcast<u32>(values[i]));
+    }
+}
@@ -0,0 +1,343 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use wgpu::util::DeviceExt;
+
+use super::default_resources::IMAGE_PROCESSING_SHADER;
+use crate::Renderer;
+
+/// The largest amount of pixels the blur samples on each side
+pub const MAX_BLUR_RADIUS: u32 = 64;
+
+/// The parameters of the kernels, laid out as `ProcessingParams` in the shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ProcessingParams {
+    radius: i32,
+    sigma: f32,
+    threshold: f32,
+    direction: u32,
+}
+
+/// The operations of [ImageProcessing]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageKernel {
+    /// Blurs the texture, with the standard deviation in pixels
+    GaussianBlur(f32),
+    /// Finds the edges of the texture. The result is the strength of the edges, in grayscale
+    Sobel,
+    /// Halves the size of the texture, averaging each 2x2 block of pixels
+    Downsample,
+    /// Turns the pixels white if their luminance reaches the value, and black otherwise
+    Threshold(f32),
+}
+
+/// Compute kernels for processing any texture on the GPU, such as blurring or finding edges.
+///
+/// The input must be created with [`wgpu::TextureUsages::TEXTURE_BINDING`] and have a float
+/// format. The result is a new `Rgba8Unorm` texture with linear values, which can be used as the
+/// input of the next kernel, read back with [Renderer::read_texture], or given to objects with
+/// [Renderer::build_texture_from_gpu].
+#[derive(Debug)]
+pub struct ImageProcessing {
+    /// The layout of the bind group used by the compute shaders
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    gaussian_blur: wgpu::ComputePipeline,
+    sobel: wgpu::ComputePipeline,
+    downsample: wgpu::ComputePipeline,
+    threshold: wgpu::ComputePipeline,
+}
+
+impl ImageProcessing {
+    /// Creates the compute pipelines of the kernels
+    pub fn new(renderer: &Renderer) -> Self {
+        let device = &renderer.device;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Image Processing Shader"),
+            source: wgpu::ShaderSource::Wgsl(IMAGE_PROCESSING_SHADER.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Image Processing Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Image Processing Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = |entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Image Processing Pipeline"),
+                layout: Some(&layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            })
+        };
+
+        Self {
+            gaussian_blur: pipeline("cs_gaussian_blur"),
+            sobel: pipeline("cs_sobel"),
+            downsample: pipeline("cs_downsample"),
+            threshold: pipeline("cs_threshold"),
+            bind_group_layout,
+        }
+    }
+
+    /// Runs the kernel on the texture and returns the result. The work is submitted right
+    /// away, but it does not wait for the GPU
+    pub fn apply(
+        &self,
+        renderer: &Renderer,
+        kernel: ImageKernel,
+        texture: &wgpu::Texture,
+    ) -> wgpu::Texture {
+        let mut encoder = renderer
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Image Processing Encoder"),
+            });
+        let output = self.record(&renderer.device, &mut encoder, kernel, texture);
+        renderer.queue.submit(std::iter::once(encoder.finish()));
+
+        output
+    }
+
+    /// Runs the kernels one after another on the texture, and returns the final result
+    pub fn apply_all(
+        &self,
+        renderer: &Renderer,
+        kernels: &[ImageKernel],
+        texture: &wgpu::Texture,
+    ) -> wgpu::Texture {
+        let mut encoder = renderer
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Image Processing Encoder"),
+            });
+        let mut output = texture.clone();
+        for kernel in kernels.iter() {
+            output = self.record(&renderer.device, &mut encoder, *kernel, &output);
+        }
+        renderer.queue.submit(std::iter::once(encoder.finish()));
+
+        output
+    }
+
+    /// Records the kernel into the encoder and returns the texture it writes into, such as
+    /// for running it as part of a frame
+    pub fn record(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        kernel: ImageKernel,
+        texture: &wgpu::Texture,
+    ) -> wgpu::Texture {
+        let (width, height) = match kernel {
+            ImageKernel::Downsample => (
+                texture.width().div_ceil(2).max(1),
+                texture.height().div_ceil(2).max(1),
+            ),
+            _ => (texture.width(), texture.height()),
+        };
+        let output = Self::build_output(device, width, height);
+
+        match kernel {
+            ImageKernel::GaussianBlur(sigma) => {
+                let radius = ((sigma * 3.0).ceil() as u32).min(MAX_BLUR_RADIUS) as i32;
+                let params = |direction| ProcessingParams {
+                    radius,
+                    sigma,
+                    threshold: 0.0,
+                    direction,
+                };
+
+                // the blur is separable, so it runs once for each direction
+                let horizontal = Self::build_output(device, width, height);
+                self.dispatch(
+                    device,
+                    encoder,
+                    &self.gaussian_blur,
+                    texture,
+                    &horizontal,
+                    params(0),
+                );
+                self.dispatch(
+                    device,
+                    encoder,
+                    &self.gaussian_blur,
+                    &horizontal,
+                    &output,
+                    params(1),
+                );
+            }
+            ImageKernel::Sobel | ImageKernel::Downsample | ImageKernel::Threshold(_) => {
+                let pipeline = match kernel {
+                    ImageKernel::Sobel => &self.sobel,
+                    ImageKernel::Downsample => &self.downsample,
+                    _ => &self.threshold,
+                };
+                let threshold = match kernel {
+                    ImageKernel::Threshold(threshold) => threshold,
+                    _ => 0.0,
+                };
+
+                self.dispatch(
+                    device,
+                    encoder,
+                    pipeline,
+                    texture,
+                    &output,
+                    ProcessingParams {
+                        radius: 0,
+                        sigma: 0.0,
+                        threshold,
+                        direction: 0,
+                    },
+                );
+            }
+        }
+
+        output
+    }
+
+    fn build_output(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Image Processing Output"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        })
+    }
+
+    fn dispatch(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::ComputePipeline,
+        input: &wgpu::Texture,
+        output: &wgpu::Texture,
+        params: ProcessingParams,
+    ) {
+        let input_view = input.create_view(&wgpu::TextureViewDescriptor::default());
+        let output_view = output.create_view(&wgpu::TextureViewDescriptor::default());
+        crate::utils::allocation_audit::count_buffer();
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Image Processing Params Buffer"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        crate::utils::allocation_audit::count_bind_group();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Image Processing Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&input_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&output_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Image Processing Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        compute_pass.dispatch_workgroups(
+            output.width().div_ceil(8),
+            output.height().div_ceil(8),
+            1,
+        );
+    }
+
+    /// Blurs the texture, with the standard deviation in pixels
+    pub fn gaussian_blur(
+        &self,
+        renderer: &Renderer,
+        texture: &wgpu::Texture,
+        sigma: f32,
+    ) -> wgpu::Texture {
+        self.apply(renderer, ImageKernel::GaussianBlur(sigma), texture)
+    }
+
+    /// Returns the strength of the edges of the texture, in grayscale
+    pub fn sobel(&self, renderer: &Renderer, texture: &wgpu::Texture) -> wgpu::Texture {
+        self.apply(renderer, ImageKernel::Sobel, texture)
+    }
+
+    /// Returns the texture at half its size
+    pub fn downsample(&self, renderer: &Renderer, texture: &wgpu::Texture) -> wgpu::Texture {
+        self.apply(renderer, ImageKernel::Downsample, texture)
+    }
+
+    /// Returns white where the luminance of the texture reaches the threshold, and black
+    /// elsewhere
+    pub fn threshold(
+        &self,
+        renderer: &Renderer,
+        texture: &wgpu::Texture,
+        threshold: f32,
+    ) -> wgpu::Texture {
+        self.apply(renderer, ImageKernel::Threshold(threshold), texture)
+    }
+}
@@ -0,0 +1,108 @@
+// Image processing compute shaders, each entry point reads the input texture and writes the
+// result into the output texture. The values are kept linear
+
+struct ProcessingParams {
+    // the amount of pixels sampled on each side by the blur
+    radius: i32,
+    sigma: f32,
+    threshold: f32,
+    // 0 for the horizontal pass of the blur, 1 for the vertical one
+    direction: u32,
+};
+
+@group(0) @binding(0)
+var input_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var output_texture: texture_storage_2d<rgba8unorm, write>;
+
+@group(0) @binding(2)
+var<uniform> params: ProcessingParams;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// loads a pixel, clamping the coordinates to the edges of the texture
+fn load(position: vec2<i32>) -> vec4<f32> {
+    let dimensions = vec2<i32>(textureDimensions(input_texture));
+    return textureLoad(input_texture, clamp(position, vec2<i32>(0), dimensions - 1), 0);
+}
+
+fn outside(id: vec3<u32>) -> bool {
+    let dimensions = textureDimensions(output_texture);
+    return id.x >= dimensions.x || id.y >= dimensions.y;
+}
+
+// a single pass of the separable gaussian blur, along the direction of the params
+@compute @workgroup_size(8, 8)
+fn cs_gaussian_blur(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside(id) {
+        return;
+    }
+
+    let step = select(vec2<i32>(1, 0), vec2<i32>(0, 1), params.direction == 1u);
+    let sigma = max(params.sigma, 0.0001);
+    var color = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var i = -params.radius; i <= params.radius; i++) {
+        let weight = exp(-f32(i * i) / (2.0 * sigma * sigma));
+        color += load(vec2<i32>(id.xy) + step * i) * weight;
+        total += weight;
+    }
+
+    textureStore(output_texture, id.xy, color / total);
+}
+
+// the magnitude of the edges, from the sobel operator over the luminance
+@compute @workgroup_size(8, 8)
+fn cs_sobel(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside(id) {
+        return;
+    }
+
+    let position = vec2<i32>(id.xy);
+    var samples: array<f32, 9>;
+    for (var y = 0; y < 3; y++) {
+        for (var x = 0; x < 3; x++) {
+            samples[y * 3 + x] = luminance(load(position + vec2<i32>(x - 1, y - 1)).rgb);
+        }
+    }
+
+    let gradient_x = (samples[2] + 2.0 * samples[5] + samples[8])
+        - (samples[0] + 2.0 * samples[3] + samples[6]);
+    let gradient_y = (samples[6] + 2.0 * samples[7] + samples[8])
+        - (samples[0] + 2.0 * samples[1] + samples[2]);
+    let magnitude = clamp(length(vec2<f32>(gradient_x, gradient_y)), 0.0, 1.0);
+
+    textureStore(output_texture, id.xy, vec4<f32>(vec3<f32>(magnitude), 1.0));
+}
+
+// halves the size of the texture, averaging each 2x2 block of pixels
+@compute @workgroup_size(8, 8)
+fn cs_downsample(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside(id) {
+        return;
+    }
+
+    let position = vec2<i32>(id.xy) * 2;
+    let color = load(position)
+        + load(position + vec2<i32>(1, 0))
+        + load(position + vec2<i32>(0, 1))
+        + load(position + vec2<i32>(1, 1));
+
+    textureStore(output_texture, id.xy, color * 0.25);
+}
+
+// white where the luminance reaches the threshold, black elsewhere. The alpha is kept
+@compute @workgroup_size(8, 8)
+fn cs_threshold(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside(id) {
+        return;
+    }
+
+    let color = load(vec2<i32>(id.xy));
+    let value = select(0.0, 1.0, luminance(color.rgb) >= params.threshold);
+
+    textureStore(output_texture, id.xy, vec4<f32>(vec3<f32>(value), color.a));
+}
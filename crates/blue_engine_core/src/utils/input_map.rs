@@ -0,0 +1,844 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{
+    KeyCode, MouseButton, StringBuffer,
+    utils::{
+        gamepad::{GamepadAxis, GamepadButton, GamepadEvent, GamepadId},
+        winit_input_helper::WinitInputHelper,
+    },
+};
+
+/// The default amount an axis must be moved for its action to count as pressed
+pub const DEFAULT_PRESS_THRESHOLD: f32 = 0.5;
+
+/// A physical input that an action can be bound to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputSource {
+    /// A key, by its physical position in the US layout
+    Key(KeyCode),
+    /// A mouse button
+    Mouse(MouseButton),
+    /// A gamepad button
+    GamepadButton(GamepadButton),
+    /// A gamepad axis
+    GamepadAxis(GamepadAxis),
+}
+impl From<KeyCode> for InputSource {
+    fn from(value: KeyCode) -> Self {
+        Self::Key(value)
+    }
+}
+impl From<MouseButton> for InputSource {
+    fn from(value: MouseButton) -> Self {
+        Self::Mouse(value)
+    }
+}
+impl From<GamepadButton> for InputSource {
+    fn from(value: GamepadButton) -> Self {
+        Self::GamepadButton(value)
+    }
+}
+impl From<GamepadAxis> for InputSource {
+    fn from(value: GamepadAxis) -> Self {
+        Self::GamepadAxis(value)
+    }
+}
+
+/// An input bound to an action
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputBinding {
+    /// The physical input
+    pub source: InputSource,
+    /// Multiplies the value of the input in [`InputMap::axis_value`]. A negative scale makes a
+    /// key move the axis in the other direction, or inverts an axis.
+    pub scale: f32,
+}
+
+/// Turns the names of the variants into their value and back, for the text form of the inputs
+macro_rules! named_variants {
+    ($type:ty, $from_name:ident, [$($variant:ident),* $(,)?]) => {
+        fn $from_name(name: &str) -> Option<$type> {
+            match name {
+                $(stringify!($variant) => Some(<$type>::$variant),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+named_variants!(
+    KeyCode,
+    key_from_name,
+    [
+        Backquote,
+        Backslash,
+        BracketLeft,
+        BracketRight,
+        Comma,
+        Digit0,
+        Digit1,
+        Digit2,
+        Digit3,
+        Digit4,
+        Digit5,
+        Digit6,
+        Digit7,
+        Digit8,
+        Digit9,
+        Equal,
+        IntlBackslash,
+        IntlRo,
+        IntlYen,
+        KeyA,
+        KeyB,
+        KeyC,
+        KeyD,
+        KeyE,
+        KeyF,
+        KeyG,
+        KeyH,
+        KeyI,
+        KeyJ,
+        KeyK,
+        KeyL,
+        KeyM,
+        KeyN,
+        KeyO,
+        KeyP,
+        KeyQ,
+        KeyR,
+        KeyS,
+        KeyT,
+        KeyU,
+        KeyV,
+        KeyW,
+        KeyX,
+        KeyY,
+        KeyZ,
+        Minus,
+        Period,
+        Quote,
+        Semicolon,
+        Slash,
+        AltLeft,
+        AltRight,
+        Backspace,
+        CapsLock,
+        ContextMenu,
+        ControlLeft,
+        ControlRight,
+        Enter,
+        SuperLeft,
+        SuperRight,
+        ShiftLeft,
+        ShiftRight,
+        Space,
+        Tab,
+        Convert,
+        KanaMode,
+        Lang1,
+        Lang2,
+        Lang3,
+        Lang4,
+        Lang5,
+        NonConvert,
+        Delete,
+        End,
+        Help,
+        Home,
+        Insert,
+        PageDown,
+        PageUp,
+        ArrowDown,
+        ArrowLeft,
+        ArrowRight,
+        ArrowUp,
+        NumLock,
+        Numpad0,
+        Numpad1,
+        Numpad2,
+        Numpad3,
+        Numpad4,
+        Numpad5,
+        Numpad6,
+        Numpad7,
+        Numpad8,
+        Numpad9,
+        NumpadAdd,
+        NumpadBackspace,
+        NumpadClear,
+        NumpadClearEntry,
+        NumpadComma,
+        NumpadDecimal,
+        NumpadDivide,
+        NumpadEnter,
+        NumpadEqual,
+        NumpadHash,
+        NumpadMemoryAdd,
+        NumpadMemoryClear,
+        NumpadMemoryRecall,
+        NumpadMemoryStore,
+        NumpadMemorySubtract,
+        NumpadMultiply,
+        NumpadParenLeft,
+        NumpadParenRight,
+        NumpadStar,
+        NumpadSubtract,
+        Escape,
+        Fn,
+        FnLock,
+        PrintScreen,
+        ScrollLock,
+        Pause,
+        BrowserBack,
+        BrowserFavorites,
+        BrowserForward,
+        BrowserHome,
+        BrowserRefresh,
+        BrowserSearch,
+        BrowserStop,
+        Eject,
+        LaunchApp1,
+        LaunchApp2,
+        LaunchMail,
+        MediaPlayPause,
+        MediaSelect,
+        MediaStop,
+        MediaTrackNext,
+        MediaTrackPrevious,
+        Power,
+        Sleep,
+        AudioVolumeDown,
+        AudioVolumeMute,
+        AudioVolumeUp,
+        WakeUp,
+        Meta,
+        Hyper,
+        Turbo,
+        Abort,
+        Resume,
+        Suspend,
+        Again,
+        Copy,
+        Cut,
+        Find,
+        Open,
+        Paste,
+        Props,
+        Select,
+        Undo,
+        Hiragana,
+        Katakana,
+        F1,
+        F2,
+        F3,
+        F4,
+        F5,
+        F6,
+        F7,
+        F8,
+        F9,
+        F10,
+        F11,
+        F12,
+        F13,
+        F14,
+        F15,
+        F16,
+        F17,
+        F18,
+        F19,
+        F20,
+        F21,
+        F22,
+        F23,
+        F24,
+        F25,
+        F26,
+        F27,
+        F28,
+        F29,
+        F30,
+        F31,
+        F32,
+        F33,
+        F34,
+        F35,
+    ]
+);
+named_variants!(
+    GamepadButton,
+    gamepad_button_from_name,
+    [
+        South,
+        East,
+        North,
+        West,
+        LeftBumper,
+        LeftTrigger,
+        RightBumper,
+        RightTrigger,
+        Select,
+        Start,
+        Mode,
+        LeftThumb,
+        RightThumb,
+        DPadUp,
+        DPadDown,
+        DPadLeft,
+        DPadRight,
+    ]
+);
+named_variants!(
+    GamepadAxis,
+    gamepad_axis_from_name,
+    [
+        LeftStickX,
+        LeftStickY,
+        RightStickX,
+        RightStickY,
+        LeftZ,
+        RightZ
+    ]
+);
+
+/// The inputs are written as their kind and name, such as `Key:Space`, `Mouse:Left`, or
+/// `GamepadAxis:LeftStickX`
+impl std::fmt::Display for InputSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Key(key) => write!(f, "Key:{key:?}"),
+            Self::Mouse(MouseButton::Other(button)) => write!(f, "Mouse:{button}"),
+            Self::Mouse(button) => write!(f, "Mouse:{button:?}"),
+            Self::GamepadButton(button) => write!(f, "GamepadButton:{button:?}"),
+            Self::GamepadAxis(axis) => write!(f, "GamepadAxis:{axis:?}"),
+        }
+    }
+}
+impl std::str::FromStr for InputSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, name) = s
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| format!("the input {s} has no kind"))?;
+
+        let source = match kind {
+            "Key" => key_from_name(name).map(Self::Key),
+            "Mouse" => match name {
+                "Left" => Some(MouseButton::Left),
+                "Right" => Some(MouseButton::Right),
+                "Middle" => Some(MouseButton::Middle),
+                "Back" => Some(MouseButton::Back),
+                "Forward" => Some(MouseButton::Forward),
+                other => other.parse().ok().map(MouseButton::Other),
+            }
+            .map(Self::Mouse),
+            "GamepadButton" => gamepad_button_from_name(name).map(Self::GamepadButton),
+            "GamepadAxis" => gamepad_axis_from_name(name).map(Self::GamepadAxis),
+            _ => None,
+        };
+
+        source.ok_or_else(|| format!("the input {s} is not known"))
+    }
+}
+
+/// The bindings are written as their input, followed by their scale if it is not 1, such as
+/// `GamepadAxis:LeftStickY*-1`
+impl std::fmt::Display for InputBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.scale == 1.0 {
+            write!(f, "{}", self.source)
+        } else {
+            write!(f, "{}*{}", self.source, self.scale)
+        }
+    }
+}
+impl std::str::FromStr for InputBinding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (source, scale) = match s.split_once('*') {
+            Some((source, scale)) => (
+                source,
+                scale
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("the scale of {s} is not a number"))?,
+            ),
+            None => (s, 1.0),
+        };
+
+        Ok(Self {
+            source: source.parse()?,
+            scale,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for InputBinding {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for InputBinding {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = <String as serde::Deserialize>::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Maps named actions, such as `"jump"` or `"move_forward"`, to keys, mouse buttons, and
+/// gamepad inputs, so the game logic does not depend on the physical inputs.
+///
+/// An action can have many bindings, and is active if any of them is.
+#[derive(Debug, Clone)]
+pub struct InputMap {
+    actions: std::collections::HashMap<String, Vec<InputBinding>>,
+    /// The gamepad that is read. If `None`, all the connected gamepads are read
+    pub gamepad: Option<GamepadId>,
+    /// The amount an axis must be moved for its action to count as pressed
+    pub press_threshold: f32,
+}
+
+impl InputMap {
+    /// Creates a new map without any actions
+    pub fn new() -> Self {
+        Self {
+            actions: std::collections::HashMap::new(),
+            gamepad: None,
+            press_threshold: DEFAULT_PRESS_THRESHOLD,
+        }
+    }
+
+    /// Binds an input to the action, creating the action if it does not exist
+    pub fn bind(&mut self, action: impl StringBuffer, source: impl Into<InputSource>) -> &mut Self {
+        self.bind_scaled(action, source, 1.0)
+    }
+
+    /// Binds an input to the action, with a scale for its value in [`InputMap::axis_value`]
+    pub fn bind_scaled(
+        &mut self,
+        action: impl StringBuffer,
+        source: impl Into<InputSource>,
+        scale: f32,
+    ) -> &mut Self {
+        let binding = InputBinding {
+            source: source.into(),
+            scale,
+        };
+        let bindings = self.actions.entry(action.as_string()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+
+        self
+    }
+
+    /// Removes an input from the action
+    pub fn unbind(
+        &mut self,
+        action: impl StringBuffer,
+        source: impl Into<InputSource>,
+    ) -> &mut Self {
+        let source = source.into();
+        if let Some(bindings) = self.actions.get_mut(action.as_str()) {
+            bindings.retain(|i| i.source != source);
+        }
+
+        self
+    }
+
+    /// Replaces an input of the action with another, keeping its scale.
+    /// Returns false if the action did not have the old input.
+    pub fn rebind(
+        &mut self,
+        action: impl StringBuffer,
+        old: impl Into<InputSource>,
+        new: impl Into<InputSource>,
+    ) -> bool {
+        let old = old.into();
+        match self
+            .actions
+            .get_mut(action.as_str())
+            .and_then(|i| i.iter_mut().find(|i| i.source == old))
+        {
+            Some(binding) => {
+                binding.source = new.into();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces all the bindings of the action
+    pub fn set_bindings(&mut self, action: impl StringBuffer, bindings: Vec<InputBinding>) {
+        self.actions.insert(action.as_string(), bindings);
+    }
+
+    /// Returns the bindings of the action
+    pub fn bindings(&self, action: impl StringBuffer) -> &[InputBinding] {
+        self.actions
+            .get(action.as_str())
+            .map(|i| i.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Removes the action and all of its bindings
+    pub fn remove_action(&mut self, action: impl StringBuffer) {
+        self.actions.remove(action.as_str());
+    }
+
+    /// Returns the names of all the actions
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.actions.keys().map(|i| i.as_str())
+    }
+
+    /// Returns the first key, mouse button, or gamepad button that was pressed in this frame.
+    ///
+    /// This is useful for a controls menu that waits for the player to press the new input
+    /// of an action.
+    pub fn capture(&self, input: &WinitInputHelper) -> Option<InputSource> {
+        if let Some(key) = input.keys_pressed().first() {
+            return Some(InputSource::Key(*key));
+        }
+        if let Some(button) = input.mouse_buttons_pressed().first() {
+            return Some(InputSource::Mouse(*button));
+        }
+
+        input
+            .gamepads()
+            .events()
+            .iter()
+            .find_map(|event| match event {
+                GamepadEvent::ButtonPressed(id, button) if self.reads_gamepad(*id) => {
+                    Some(InputSource::GamepadButton(*button))
+                }
+                _ => None,
+            })
+    }
+
+    /// Returns true while any input of the action is held down, or its axes are moved past
+    /// the press threshold
+    pub fn pressed(&self, action: impl StringBuffer, input: &WinitInputHelper) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| match binding.source {
+                InputSource::GamepadAxis(axis) => {
+                    self.axis(input, axis) * binding.scale.signum() >= self.press_threshold
+                }
+                source => self.source_held(source, input),
+            })
+    }
+
+    /// Returns true if any button of the action was pressed in this frame.
+    /// Axes are not counted, as they have no press of their own.
+    pub fn just_pressed(&self, action: impl StringBuffer, input: &WinitInputHelper) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| match binding.source {
+                InputSource::Key(key) => input.key_pressed(key),
+                InputSource::Mouse(button) => input.mouse_pressed(button),
+                InputSource::GamepadButton(button) => self
+                    .gamepads(input)
+                    .any(|(_, gamepad)| gamepad.button_pressed(button)),
+                InputSource::GamepadAxis(_) => false,
+            })
+    }
+
+    /// Returns true if any button of the action was released in this frame.
+    /// Axes are not counted, as they have no release of their own.
+    pub fn just_released(&self, action: impl StringBuffer, input: &WinitInputHelper) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| match binding.source {
+                InputSource::Key(key) => input.key_released(key),
+                InputSource::Mouse(button) => input.mouse_released(button),
+                InputSource::GamepadButton(button) => self
+                    .gamepads(input)
+                    .any(|(_, gamepad)| gamepad.button_released(button)),
+                InputSource::GamepadAxis(_) => false,
+            })
+    }
+
+    /// Returns the value of the action between -1 and 1. Held buttons count as 1 and axes as
+    /// their value with the deadzone applied, each multiplied by the scale of its binding.
+    pub fn axis_value(&self, action: impl StringBuffer, input: &WinitInputHelper) -> f32 {
+        self.bindings(action)
+            .iter()
+            .map(|binding| match binding.source {
+                InputSource::GamepadAxis(axis) => self.axis(input, axis) * binding.scale,
+                source if self.source_held(source, input) => binding.scale,
+                _ => 0.0,
+            })
+            .sum::<f32>()
+            .clamp(-1.0, 1.0)
+    }
+
+    fn source_held(&self, source: InputSource, input: &WinitInputHelper) -> bool {
+        match source {
+            InputSource::Key(key) => input.key_held(key),
+            InputSource::Mouse(button) => input.mouse_held(button),
+            InputSource::GamepadButton(button) => self
+                .gamepads(input)
+                .any(|(_, gamepad)| gamepad.button_held(button)),
+            InputSource::GamepadAxis(_) => false,
+        }
+    }
+
+    /// The value of the axis on the read gamepads furthest from zero
+    fn axis(&self, input: &WinitInputHelper, axis: GamepadAxis) -> f32 {
+        self.gamepads(input)
+            .map(|(id, _)| input.gamepads().axis(id, axis))
+            .fold(
+                0.0,
+                |value: f32, i| {
+                    if i.abs() > value.abs() { i } else { value }
+                },
+            )
+    }
+
+    fn reads_gamepad(&self, id: GamepadId) -> bool {
+        self.gamepad.is_none_or(|i| i == id)
+    }
+
+    fn gamepads<'a>(
+        &'a self,
+        input: &'a WinitInputHelper,
+    ) -> impl Iterator<Item = (GamepadId, &'a crate::utils::gamepad::GamepadState)> {
+        input
+            .gamepads()
+            .connected()
+            .filter(|(id, _)| self.reads_gamepad(*id))
+    }
+}
+impl Default for InputMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::event::{DeviceId, ElementState, WindowEvent};
+
+    fn mouse(state: ElementState, button: MouseButton) -> WindowEvent {
+        WindowEvent::MouseInput {
+            device_id: DeviceId::dummy(),
+            state,
+            button,
+        }
+    }
+
+    /// Steps the input to the next frame with the events, reading the axes without a deadzone
+    fn frame(input: &mut WinitInputHelper, events: &[WindowEvent], gamepad: &[GamepadEvent]) {
+        input.step_with_window_events(events);
+        input.gamepads_mut().deadzone = 0.0;
+        for event in gamepad {
+            input.gamepads_mut().process_event(event.clone());
+        }
+    }
+
+    #[test]
+    fn binding_and_unbinding() {
+        let mut map = InputMap::new();
+        map.bind("jump", KeyCode::Space)
+            .bind("jump", GamepadButton::South)
+            .bind("jump", KeyCode::Space);
+        assert_eq!(map.bindings("jump").len(), 2);
+        assert_eq!(map.bindings("jump")[0].scale, 1.0);
+        assert!(map.bindings("missing").is_empty());
+
+        map.unbind("jump", KeyCode::Space);
+        assert_eq!(
+            map.bindings("jump"),
+            &[InputBinding {
+                source: InputSource::GamepadButton(GamepadButton::South),
+                scale: 1.0
+            }]
+        );
+
+        map.remove_action("jump");
+        assert_eq!(map.actions().count(), 0);
+    }
+
+    #[test]
+    fn rebinding_keeps_the_scale() {
+        let mut map = InputMap::new();
+        map.bind_scaled("move_x", KeyCode::KeyA, -1.0);
+
+        assert!(map.rebind("move_x", KeyCode::KeyA, KeyCode::ArrowLeft));
+        assert_eq!(
+            map.bindings("move_x"),
+            &[InputBinding {
+                source: InputSource::Key(KeyCode::ArrowLeft),
+                scale: -1.0
+            }]
+        );
+        assert!(!map.rebind("move_x", KeyCode::KeyA, KeyCode::KeyQ));
+        assert!(!map.rebind("missing", KeyCode::KeyA, KeyCode::KeyQ));
+    }
+
+    #[test]
+    fn bindings_as_text() {
+        let binding: InputBinding = "GamepadAxis:LeftStickY*-1".parse().unwrap();
+        assert_eq!(
+            binding.source,
+            InputSource::GamepadAxis(GamepadAxis::LeftStickY)
+        );
+        assert_eq!(binding.scale, -1.0);
+        assert_eq!(binding.to_string(), "GamepadAxis:LeftStickY*-1");
+
+        for text in ["Key:Space", "Mouse:Left", "Mouse:7", "GamepadButton:DPadUp"] {
+            let binding: InputBinding = text.parse().unwrap();
+            assert_eq!(binding.scale, 1.0);
+            assert_eq!(binding.to_string(), text);
+        }
+
+        assert!("Space".parse::<InputBinding>().is_err());
+        assert!("Key:Nothing".parse::<InputBinding>().is_err());
+        assert!("Key:Space*fast".parse::<InputBinding>().is_err());
+    }
+
+    #[test]
+    fn mouse_press_hold_and_release() {
+        let mut map = InputMap::new();
+        map.bind("fire", MouseButton::Left);
+        let mut input = WinitInputHelper::new();
+
+        frame(
+            &mut input,
+            &[mouse(ElementState::Pressed, MouseButton::Left)],
+            &[],
+        );
+        assert!(map.just_pressed("fire", &input));
+        assert!(map.pressed("fire", &input));
+        assert_eq!(
+            map.capture(&input),
+            Some(InputSource::Mouse(MouseButton::Left))
+        );
+
+        frame(&mut input, &[], &[]);
+        assert!(!map.just_pressed("fire", &input));
+        assert!(map.pressed("fire", &input));
+        assert_eq!(map.capture(&input), None);
+
+        frame(
+            &mut input,
+            &[mouse(ElementState::Released, MouseButton::Left)],
+            &[],
+        );
+        assert!(map.just_released("fire", &input));
+        assert!(!map.pressed("fire", &input));
+    }
+
+    #[test]
+    fn buttons_compose_an_axis() {
+        let mut map = InputMap::new();
+        map.bind_scaled("move_x", GamepadButton::DPadLeft, -1.0)
+            .bind("move_x", GamepadButton::DPadRight);
+        let mut input = WinitInputHelper::new();
+
+        frame(
+            &mut input,
+            &[],
+            &[GamepadEvent::ButtonPressed(0, GamepadButton::DPadLeft)],
+        );
+        assert_eq!(map.axis_value("move_x", &input), -1.0);
+        assert!(map.just_pressed("move_x", &input));
+
+        frame(
+            &mut input,
+            &[],
+            &[GamepadEvent::ButtonPressed(0, GamepadButton::DPadRight)],
+        );
+        assert_eq!(map.axis_value("move_x", &input), 0.0);
+
+        frame(
+            &mut input,
+            &[],
+            &[GamepadEvent::ButtonReleased(0, GamepadButton::DPadLeft)],
+        );
+        assert_eq!(map.axis_value("move_x", &input), 1.0);
+        assert!(map.just_released("move_x", &input));
+    }
+
+    #[test]
+    fn axes_are_scaled_and_clamped() {
+        let mut map = InputMap::new();
+        map.bind_scaled("forward", GamepadAxis::LeftStickY, -1.0);
+        let mut input = WinitInputHelper::new();
+
+        frame(
+            &mut input,
+            &[],
+            &[GamepadEvent::AxisChanged(0, GamepadAxis::LeftStickY, -0.25)],
+        );
+        assert_eq!(map.axis_value("forward", &input), 0.25);
+        assert!(!map.pressed("forward", &input));
+        // axes have no press of their own
+        assert!(!map.just_pressed("forward", &input));
+
+        frame(
+            &mut input,
+            &[],
+            &[GamepadEvent::AxisChanged(0, GamepadAxis::LeftStickY, -0.75)],
+        );
+        assert_eq!(map.axis_value("forward", &input), 0.75);
+        assert!(map.pressed("forward", &input));
+
+        // the axis past the threshold in the other direction is not a press
+        frame(
+            &mut input,
+            &[],
+            &[GamepadEvent::AxisChanged(0, GamepadAxis::LeftStickY, 0.75)],
+        );
+        assert!(!map.pressed("forward", &input));
+
+        map.bind("forward", GamepadButton::North);
+        frame(
+            &mut input,
+            &[],
+            &[
+                GamepadEvent::AxisChanged(0, GamepadAxis::LeftStickY, -0.75),
+                GamepadEvent::ButtonPressed(0, GamepadButton::North),
+            ],
+        );
+        assert_eq!(map.axis_value("forward", &input), 1.0);
+    }
+
+    #[test]
+    fn only_the_chosen_gamepad_is_read() {
+        let mut map = InputMap::new();
+        map.bind("jump", GamepadButton::South);
+        map.gamepad = Some(1);
+        let mut input = WinitInputHelper::new();
+
+        frame(
+            &mut input,
+            &[],
+            &[GamepadEvent::ButtonPressed(0, GamepadButton::South)],
+        );
+        assert!(!map.pressed("jump", &input));
+        assert_eq!(map.capture(&input), None);
+
+        frame(
+            &mut input,
+            &[],
+            &[GamepadEvent::ButtonPressed(1, GamepadButton::South)],
+        );
+        assert!(map.pressed("jump", &input));
+        assert_eq!(
+            map.capture(&input),
+            Some(InputSource::GamepadButton(GamepadButton::South))
+        );
+    }
+}
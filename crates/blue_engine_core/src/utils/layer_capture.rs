@@ -0,0 +1,188 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{CameraContainer, ObjectStorage, Renderer, error::Error};
+
+/// What [Renderer::capture_layers] renders, and at which size
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerCapture {
+    /// The width of the image in pixels
+    pub width: u32,
+    /// The height of the image in pixels
+    pub height: u32,
+    /// The image is rendered at this many times its size and averaged down, for smooth edges.
+    /// 1 renders it at its size
+    pub supersampling: u32,
+    /// The render orders of the objects to include, or `None` for all of them
+    pub layers: Option<Vec<usize>>,
+    /// The names of the objects to include, or `None` for all of them
+    pub objects: Option<Vec<String>>,
+}
+impl LayerCapture {
+    /// Captures all objects at the given size, with 4 times supersampling
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            supersampling: 4,
+            layers: None,
+            objects: None,
+        }
+    }
+
+    /// Only includes the objects with these render orders
+    pub fn with_layers(mut self, layers: impl IntoIterator<Item = usize>) -> Self {
+        self.layers = Some(layers.into_iter().collect());
+        self
+    }
+
+    /// Only includes the objects with these names
+    pub fn with_objects(mut self, objects: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.objects = Some(objects.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Returns true if the object is in the capture
+    fn includes(&self, object: &crate::Object) -> bool {
+        self.layers
+            .as_ref()
+            .is_none_or(|i| i.contains(&object.render_order))
+            && self
+                .objects
+                .as_ref()
+                .is_none_or(|i| i.iter().any(|name| name.as_str() == object.name.as_ref()))
+    }
+}
+
+/// Averages each block of `factor` by `factor` pixels into one. The colors are weighed by
+/// their alpha, so the edges against the transparent background do not darken
+fn downsample(image: &image::RgbaImage, factor: u32) -> image::RgbaImage {
+    if factor <= 1 {
+        return image.clone();
+    }
+
+    image::RgbaImage::from_fn(image.width() / factor, image.height() / factor, |x, y| {
+        let mut color = [0.0f32; 3];
+        let mut alpha = 0.0f32;
+        for dy in 0..factor {
+            for dx in 0..factor {
+                let pixel = image.get_pixel(x * factor + dx, y * factor + dy).0;
+                let weight = pixel[3] as f32 / 255.0;
+                for channel in 0..3 {
+                    color[channel] += pixel[channel] as f32 * weight;
+                }
+                alpha += weight;
+            }
+        }
+
+        let samples = (factor * factor) as f32;
+        let channel = |i: usize| {
+            if alpha > 0.0 {
+                (color[i] / alpha).round().clamp(0.0, 255.0) as u8
+            } else {
+                0
+            }
+        };
+        image::Rgba([
+            channel(0),
+            channel(1),
+            channel(2),
+            (alpha / samples * 255.0).round().clamp(0.0, 255.0) as u8,
+        ])
+    })
+}
+
+impl Renderer {
+    /// Renders only the chosen layers and objects against a transparent background into an
+    /// image of any size, such as for marketing images or baking sprites out of 3D models.
+    ///
+    /// The main camera is used, with its aspect ratio changed to the one of the image for the
+    /// capture. This waits for the GPU, so it is meant for tools rather than every frame. The
+    /// scene must not be rendered in HDR, as the image is read in 8 bits
+    pub fn capture_layers(
+        &mut self,
+        objects: &ObjectStorage,
+        camera: &mut CameraContainer,
+        capture: &LayerCapture,
+    ) -> Result<image::RgbaImage, Error> {
+        let format = self.scene_format();
+        if self.is_hdr() {
+            return Err(Error::Custom(format!(
+                "The layers can not be captured from a scene of format {format:?}"
+            )));
+        }
+
+        let supersampling = capture.supersampling.max(1);
+        let size = winit::dpi::PhysicalSize::new(
+            capture.width.max(1) * supersampling,
+            capture.height.max(1) * supersampling,
+        );
+        let max_size = self.device.limits().max_texture_dimension_2d;
+        if size.width > max_size || size.height > max_size {
+            return Err(Error::Custom(format!(
+                "The capture of {}x{} pixels is larger than the largest texture of {max_size}",
+                size.width, size.height
+            )));
+        }
+
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Layer Capture Target"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_config = wgpu::SurfaceConfiguration {
+            width: size.width,
+            height: size.height,
+            ..self.config.clone()
+        };
+        let depth_buffer =
+            Renderer::build_depth_buffer("Layer Capture Depth", &self.device, &depth_config);
+
+        // the camera is given the aspect ratio of the capture, and set back after it
+        let previous_resolution = camera.get("main").map(|i| i.resolution);
+        camera.set_resolution(size);
+        camera.update_view_projection(self);
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Layer Capture Encoder"),
+            });
+        self.draw_objects_filtered(
+            &mut encoder,
+            &view,
+            &depth_buffer.1,
+            objects,
+            size,
+            camera,
+            None,
+            wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+            |i| capture.includes(i),
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        if let Some(resolution) = previous_resolution {
+            camera.set_resolution(winit::dpi::PhysicalSize::new(
+                resolution.x as u32,
+                resolution.y as u32,
+            ));
+            camera.update_view_projection(self);
+        }
+
+        let image = self.read_texture(&texture)?;
+        Ok(downsample(&image, supersampling))
+    }
+}
@@ -0,0 +1,422 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use crate::{Object, ObjectStorage, PipelineData, Renderer, UnsignedIntType, Vertex};
+
+/// The vertices the pool starts with, doubled each time it runs out
+const INITIAL_VERTICES: u32 = 4096;
+/// The indices the pool starts with, doubled each time it runs out
+const INITIAL_INDICES: u32 = 8192;
+/// The size of a vertex in the buffer
+const VERTEX_SIZE: u64 = std::mem::size_of::<Vertex>() as u64;
+/// The size of an index in the buffer
+const INDEX_SIZE: u64 = std::mem::size_of::<UnsignedIntType>() as u64;
+/// The indices of a mesh start at a multiple of this, as the buffer is written in 4 bytes
+const INDEX_ALIGNMENT: u32 = (wgpu::COPY_BUFFER_ALIGNMENT / INDEX_SIZE) as u32;
+
+/// The free ranges of one of the buffers, in vertices or indices
+#[derive(Debug, Default)]
+struct RangeAllocator {
+    /// The free ranges before the end, sorted and never next to each other
+    free: Vec<Range<u32>>,
+    /// Where the ranges in use end
+    end: u32,
+}
+
+impl RangeAllocator {
+    /// Takes the first free range that fits, or the space after the end
+    fn allocate(&mut self, size: u32) -> Range<u32> {
+        if let Some(index) = self.free.iter().position(|i| i.len() as u32 >= size) {
+            let start = self.free[index].start;
+            self.free[index].start += size;
+            if self.free[index].is_empty() {
+                self.free.remove(index);
+            }
+            return start..start + size;
+        }
+
+        let start = self.end;
+        self.end += size;
+        start..self.end
+    }
+
+    /// Gives the range back, merging it with the free ranges next to it
+    fn release(&mut self, range: Range<u32>) {
+        if range.is_empty() {
+            return;
+        }
+        let mut index = self.free.partition_point(|i| i.start < range.start);
+        self.free.insert(index, range);
+
+        if index + 1 < self.free.len() && self.free[index].end == self.free[index + 1].start {
+            let next = self.free.remove(index + 1);
+            self.free[index].end = next.end;
+        }
+        if index > 0 && self.free[index - 1].end == self.free[index].start {
+            let current = self.free.remove(index);
+            index -= 1;
+            self.free[index].end = current.end;
+        }
+        // the free space at the end is not kept as a range
+        if index + 1 == self.free.len() && self.free[index].end == self.end {
+            self.end = self.free[index].start;
+            self.free.pop();
+        }
+    }
+
+    /// The amount in use
+    fn used(&self) -> u32 {
+        self.end - self.free.iter().map(|i| i.len() as u32).sum::<u32>()
+    }
+}
+
+/// The ranges of both buffers, shared with the allocations to be given back when dropped
+#[derive(Debug, Default)]
+struct PoolRanges {
+    vertices: RangeAllocator,
+    indices: RangeAllocator,
+    allocations: usize,
+}
+
+/// The place of a mesh in the [MeshPool], given back to the pool when dropped
+#[derive(Debug)]
+pub struct MeshAllocation {
+    vertices: Range<u32>,
+    indices: Range<u32>,
+    index_count: u32,
+    ranges: Arc<Mutex<PoolRanges>>,
+}
+
+impl MeshAllocation {
+    /// The first vertex of the mesh, added to its indices when drawn
+    pub fn base_vertex(&self) -> i32 {
+        self.vertices.start as i32
+    }
+
+    /// The amount of vertices the mesh has room for in the pool
+    pub fn vertex_count(&self) -> u32 {
+        self.vertices.len() as u32
+    }
+
+    /// The indices of the mesh in the index buffer of the pool
+    pub fn indices(&self) -> Range<u32> {
+        self.indices.start..self.indices.start + self.index_count
+    }
+
+    /// The size of the ranges of the mesh in the buffers of the pool, in bytes
+    pub fn size(&self) -> u64 {
+        self.vertices.len() as u64 * VERTEX_SIZE + self.indices.len() as u64 * INDEX_SIZE
+    }
+
+    /// Returns true if the vertices and indices fit in the ranges of the allocation
+    fn fits(&self, vertices: usize, indices: usize) -> bool {
+        vertices <= self.vertices.len() && indices <= self.indices.len()
+    }
+}
+impl Drop for MeshAllocation {
+    fn drop(&mut self) {
+        if let Ok(mut ranges) = self.ranges.lock() {
+            ranges.vertices.release(self.vertices.clone());
+            ranges.indices.release(self.indices.clone());
+            ranges.allocations -= 1;
+        }
+    }
+}
+
+/// One vertex buffer and one index buffer shared by the pooled objects, each mesh in ranges
+/// of its own and drawn with its base vertex. The objects of the pool are drawn without
+/// binding buffers in between, and have their meshes in the same buffers for drawing many
+/// of them in a single call later on, see [Object::set_pooled].
+///
+/// The buffers double in size when they run out, copying the meshes over. The ranges of the
+/// removed meshes are reused by the next ones that fit in them
+#[derive(Debug)]
+pub struct MeshPool {
+    vertex_buffer: Option<wgpu::Buffer>,
+    index_buffer: Option<wgpu::Buffer>,
+    vertex_capacity: u32,
+    index_capacity: u32,
+    ranges: Arc<Mutex<PoolRanges>>,
+}
+
+impl MeshPool {
+    /// Creates an empty pool, the buffers are made when the first mesh is added
+    pub fn new() -> Self {
+        Self {
+            vertex_buffer: None,
+            index_buffer: None,
+            vertex_capacity: 0,
+            index_capacity: 0,
+            ranges: Arc::new(Mutex::new(PoolRanges::default())),
+        }
+    }
+
+    /// The amount of vertices the vertex buffer has room for
+    pub fn vertex_capacity(&self) -> u32 {
+        self.vertex_capacity
+    }
+
+    /// The amount of indices the index buffer has room for
+    pub fn index_capacity(&self) -> u32 {
+        self.index_capacity
+    }
+
+    /// The amount of vertices and indices in use
+    pub fn used(&self) -> (u32, u32) {
+        self.ranges
+            .lock()
+            .map(|i| (i.vertices.used(), i.indices.used()))
+            .unwrap_or_default()
+    }
+
+    /// The amount of meshes in the pool
+    pub fn len(&self) -> usize {
+        self.ranges
+            .lock()
+            .map(|i| i.allocations)
+            .unwrap_or_default()
+    }
+
+    /// Returns true if there are no meshes in the pool
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The vertex and index buffers of the pool
+    pub fn buffers(&self) -> Option<(&wgpu::Buffer, &wgpu::Buffer)> {
+        self.vertex_buffer.as_ref().zip(self.index_buffer.as_ref())
+    }
+}
+impl Default for MeshPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Get the allocation of the pool the object is drawn with, if any
+pub(crate) fn allocation_of<'a>(
+    object: &'a Object,
+    objects: &'a ObjectStorage,
+) -> Option<&'a MeshAllocation> {
+    match &object.pipeline.vertex_buffer {
+        PipelineData::Copy(object_id) => objects
+            .get(object_id.as_str())
+            .and_then(|i| allocation_of(i, objects)),
+        PipelineData::Data(_) => object.mesh_allocation.as_ref(),
+    }
+}
+
+impl Renderer {
+    /// Writes the mesh to the pool, in the place of the previous allocation if it still fits
+    pub(crate) fn allocate_mesh(
+        &mut self,
+        vertices: &[Vertex],
+        indices: &[UnsignedIntType],
+        previous: Option<MeshAllocation>,
+    ) -> MeshAllocation {
+        let padded_indices = indices.len().next_multiple_of(INDEX_ALIGNMENT as usize);
+        let allocation = match previous {
+            Some(mut allocation) if allocation.fits(vertices.len(), padded_indices) => {
+                allocation.index_count = indices.len() as u32;
+                allocation
+            }
+            previous => {
+                // the old ranges are given back first, so they can be reused if they grow
+                drop(previous);
+                let ranges = self.mesh_pool.ranges.clone();
+                let (vertex_range, index_range) = match ranges.lock() {
+                    Ok(mut ranges) => {
+                        ranges.allocations += 1;
+                        (
+                            ranges.vertices.allocate(vertices.len() as u32),
+                            ranges.indices.allocate(padded_indices as u32),
+                        )
+                    }
+                    Err(_) => (0..0, 0..0),
+                };
+
+                MeshAllocation {
+                    vertices: vertex_range,
+                    indices: index_range,
+                    index_count: indices.len() as u32,
+                    ranges,
+                }
+            }
+        };
+        self.grow_mesh_pool(allocation.vertices.end, allocation.indices.end);
+
+        let (Some(vertex_buffer), Some(index_buffer)) = (
+            self.mesh_pool.vertex_buffer.as_ref(),
+            self.mesh_pool.index_buffer.as_ref(),
+        ) else {
+            return allocation;
+        };
+        if !vertices.is_empty() {
+            self.queue.write_buffer(
+                vertex_buffer,
+                allocation.vertices.start as u64 * VERTEX_SIZE,
+                bytemuck::cast_slice(vertices),
+            );
+        }
+        if !indices.is_empty() {
+            let mut padded = indices.to_vec();
+            padded.resize(padded_indices, 0);
+            self.queue.write_buffer(
+                index_buffer,
+                allocation.indices.start as u64 * INDEX_SIZE,
+                bytemuck::cast_slice(padded.as_slice()),
+            );
+        }
+
+        allocation
+    }
+
+    /// Grows the buffers of the pool until they fit the amount of vertices and indices, and
+    /// copies the meshes to the new ones
+    fn grow_mesh_pool(&mut self, vertices: u32, indices: u32) {
+        let pool = &mut self.mesh_pool;
+        if pool.vertex_buffer.is_none() || vertices > pool.vertex_capacity {
+            let capacity = vertices
+                .max(pool.vertex_capacity * 2)
+                .max(INITIAL_VERTICES)
+                .next_power_of_two();
+            pool.vertex_buffer = Some(grow_buffer(
+                &self.device,
+                &self.queue,
+                pool.vertex_buffer.as_ref(),
+                capacity as u64 * VERTEX_SIZE,
+                wgpu::BufferUsages::VERTEX,
+                "Mesh Pool Vertex Buffer",
+            ));
+            pool.vertex_capacity = capacity;
+        }
+        if pool.index_buffer.is_none() || indices > pool.index_capacity {
+            let capacity = indices
+                .max(pool.index_capacity * 2)
+                .max(INITIAL_INDICES)
+                .next_power_of_two();
+            pool.index_buffer = Some(grow_buffer(
+                &self.device,
+                &self.queue,
+                pool.index_buffer.as_ref(),
+                capacity as u64 * INDEX_SIZE,
+                wgpu::BufferUsages::INDEX,
+                "Mesh Pool Index Buffer",
+            ));
+            pool.index_capacity = capacity;
+        }
+    }
+}
+
+/// Makes a buffer of the size, with the contents of the old buffer at its start
+fn grow_buffer(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    old_buffer: Option<&wgpu::Buffer>,
+    size: u64,
+    usage: wgpu::BufferUsages,
+    label: &str,
+) -> wgpu::Buffer {
+    crate::utils::allocation_audit::count_buffer();
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size,
+        usage: usage | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    if let Some(old_buffer) = old_buffer {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mesh Pool Grow Encoder"),
+        });
+        encoder.copy_buffer_to_buffer(old_buffer, 0, &buffer, 0, old_buffer.size());
+        queue.submit(Some(encoder.finish()));
+    }
+
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The free ranges the allocator should have for the slots in use, found one slot at a time
+    fn model_free(used: &[bool]) -> (Vec<Range<u32>>, u32) {
+        let end = used.iter().rposition(|i| *i).map_or(0, |i| i as u32 + 1);
+        let mut free: Vec<Range<u32>> = Vec::new();
+        for slot in 0..end {
+            if used[slot as usize] {
+                continue;
+            }
+            match free.last_mut() {
+                Some(last) if last.end == slot => last.end += 1,
+                _ => free.push(slot..slot + 1),
+            }
+        }
+
+        (free, end)
+    }
+
+    #[test]
+    fn allocator_matches_brute_force_model() {
+        // xorshift, so the test is the same on every run without a dependency
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut random = move |below: u32| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % below as u64) as u32
+        };
+
+        let mut allocator = RangeAllocator::default();
+        let mut used = Vec::new();
+        let mut live: Vec<Range<u32>> = Vec::new();
+
+        for _ in 0..5000 {
+            if live.is_empty() || random(2) == 0 {
+                let size = random(40);
+                let (free, end) = model_free(&used);
+                let expected = free
+                    .iter()
+                    .find(|i| i.len() as u32 >= size && size > 0)
+                    .map_or(end, |i| i.start);
+
+                let range = allocator.allocate(size);
+                assert_eq!(range.len() as u32, size);
+                if size > 0 {
+                    assert_eq!(range.start, expected, "not the first free range that fits");
+                }
+                if used.len() < range.end as usize {
+                    used.resize(range.end as usize, false);
+                }
+                for slot in range.clone() {
+                    assert!(!used[slot as usize], "{range:?} overlaps a range in use");
+                    used[slot as usize] = true;
+                }
+                live.push(range);
+            } else {
+                let range = live.swap_remove(random(live.len() as u32) as usize);
+                for slot in range.clone() {
+                    used[slot as usize] = false;
+                }
+                allocator.release(range);
+            }
+
+            let (free, end) = model_free(&used);
+            assert_eq!(allocator.free, free, "the free ranges are not fully merged");
+            assert_eq!(allocator.end, end);
+            assert_eq!(allocator.used(), used.iter().filter(|i| **i).count() as u32);
+        }
+
+        for range in live.drain(..) {
+            allocator.release(range);
+        }
+        assert!(allocator.free.is_empty());
+        assert_eq!(allocator.end, 0);
+    }
+}
@@ -0,0 +1,722 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{
+    CameraContainer, Matrix4, Object, ObjectStorage, Renderer, UnsignedIntType, Vector3, Vector4,
+    Vertex, error::Error,
+};
+
+const MESHLETS_MAGIC: &[u8; 4] = b"BEML";
+const MESHLETS_VERSION: u32 = 1;
+
+/// The most vertices in a meshlet, the limit most mesh shading hardware is fastest with
+pub const MAX_MESHLET_VERTICES: usize = 64;
+/// The most triangles in a meshlet
+pub const MAX_MESHLET_TRIANGLES: usize = 124;
+
+/// A small cluster of triangles, with the bounds it is culled by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Meshlet {
+    /// The first index of the meshlet in [Meshlets::indices]
+    pub index_offset: u32,
+    /// The amount of indices of the meshlet
+    pub index_count: u32,
+    /// The center of the sphere around the meshlet
+    pub center: Vector3,
+    /// The radius of the sphere around the meshlet
+    pub radius: f32,
+    /// The average direction the triangles of the meshlet face
+    pub cone_axis: Vector3,
+    /// The sine of the widest angle of a triangle from the cone axis, or above one if the
+    /// triangles face too many ways for the meshlet to be culled as facing away
+    pub cone_cutoff: f32,
+}
+
+/// A mesh split into meshlets, which are culled on their own so only the parts of a very high
+/// polygon mesh that can be seen are drawn.
+///
+/// Building the meshlets is slow for large meshes, so they are best built offline and saved
+/// with [Meshlets::to_bytes], then loaded with [Meshlets::from_bytes]. They are set on objects
+/// with [Object::set_meshlets], and culled by the renderer before each frame, see
+/// [MeshletCulling]
+#[derive(Debug, Clone)]
+pub struct Meshlets {
+    /// The vertices of the mesh
+    pub vertices: Vec<Vertex>,
+    /// The indices of the mesh, with the triangles of each meshlet next to each other
+    pub indices: Vec<UnsignedIntType>,
+    /// The meshlets of the mesh
+    pub meshlets: Vec<Meshlet>,
+}
+
+impl Meshlets {
+    /// Splits the mesh into meshlets of up to [MAX_MESHLET_VERTICES] vertices and
+    /// [MAX_MESHLET_TRIANGLES] triangles
+    pub fn build(vertices: &[Vertex], indices: &[UnsignedIntType]) -> Self {
+        Self::build_with_limits(
+            vertices,
+            indices,
+            MAX_MESHLET_VERTICES,
+            MAX_MESHLET_TRIANGLES,
+        )
+    }
+
+    /// Splits the mesh into meshlets of up to the given amount of vertices and triangles.
+    ///
+    /// The triangles are taken in the order of the indices, so meshes with nearby triangles
+    /// next to each other in the indices, as most exporters write them, give tighter meshlets
+    pub fn build_with_limits(
+        vertices: &[Vertex],
+        indices: &[UnsignedIntType],
+        max_vertices: usize,
+        max_triangles: usize,
+    ) -> Self {
+        let max_vertices = max_vertices.max(3);
+        let max_triangles = max_triangles.max(1);
+
+        let mut meshlets = Self {
+            vertices: vertices.to_vec(),
+            indices: Vec::with_capacity(indices.len()),
+            meshlets: Vec::new(),
+        };
+        let mut meshlet_vertices = Vec::with_capacity(max_vertices);
+        let mut start = 0;
+
+        for triangle in indices.chunks_exact(3) {
+            // triangles pointing outside of the vertices can not be drawn
+            if triangle.iter().any(|i| *i as usize >= vertices.len()) {
+                continue;
+            }
+
+            let new_vertices = triangle
+                .iter()
+                .enumerate()
+                .filter(|(position, i)| {
+                    !meshlet_vertices.contains(*i) && !triangle[..*position].contains(*i)
+                })
+                .count();
+            let triangle_count = (meshlets.indices.len() - start) / 3;
+            if meshlet_vertices.len() + new_vertices > max_vertices
+                || triangle_count == max_triangles
+            {
+                meshlets.finish_meshlet(start);
+                start = meshlets.indices.len();
+                meshlet_vertices.clear();
+            }
+
+            for index in triangle {
+                if !meshlet_vertices.contains(index) {
+                    meshlet_vertices.push(*index);
+                }
+            }
+            meshlets.indices.extend_from_slice(triangle);
+        }
+        if meshlets.indices.len() > start {
+            meshlets.finish_meshlet(start);
+        }
+
+        meshlets
+    }
+
+    /// Adds the meshlet of the indices from the start to the end, with its bounds
+    fn finish_meshlet(&mut self, start: usize) {
+        let indices = &self.indices[start..];
+        let position =
+            |index: UnsignedIntType| Vector3::from(self.vertices[index as usize].position);
+
+        let (min, max) = indices.iter().fold(
+            (Vector3::splat(f32::MAX), Vector3::splat(f32::MIN)),
+            |(min, max), i| (min.min(position(*i)), max.max(position(*i))),
+        );
+        let center = (min + max) * 0.5;
+        let radius = indices
+            .iter()
+            .map(|i| position(*i).distance(center))
+            .fold(0.0, f32::max);
+
+        let normals: Vec<Vector3> = indices
+            .chunks_exact(3)
+            .map(|i| (position(i[1]) - position(i[0])).cross(position(i[2]) - position(i[0])))
+            .filter(|i| i.length_squared() > 0.0)
+            .collect();
+        let cone_axis = normals.iter().sum::<Vector3>().normalize_or_zero();
+        let min_dot = normals
+            .iter()
+            .map(|i| i.normalize().dot(cone_axis))
+            .fold(1.0, f32::min);
+        // a cone of half a sphere or more always has some triangles facing the camera
+        let cone_cutoff = if cone_axis == Vector3::ZERO || min_dot <= 0.0 {
+            2.0
+        } else {
+            (1.0 - min_dot * min_dot).sqrt()
+        };
+
+        self.meshlets.push(Meshlet {
+            index_offset: start as u32,
+            index_count: indices.len() as u32,
+            center,
+            radius,
+            cone_axis,
+            cone_cutoff,
+        });
+    }
+
+    /// Writes the meshlets to bytes, to be read back with [Meshlets::from_bytes]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(
+            20 + self.vertices.len() * std::mem::size_of::<Vertex>()
+                + self.indices.len() * 4
+                + self.meshlets.len() * 40,
+        );
+        data.extend_from_slice(MESHLETS_MAGIC);
+        data.extend_from_slice(&MESHLETS_VERSION.to_le_bytes());
+        data.extend_from_slice(&(self.vertices.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(self.indices.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(self.meshlets.len() as u32).to_le_bytes());
+
+        for vertex in self.vertices.iter() {
+            for value in vertex
+                .position
+                .iter()
+                .chain(&vertex.uv)
+                .chain(&vertex.normal)
+            {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        for index in self.indices.iter() {
+            data.extend_from_slice(&(*index as u32).to_le_bytes());
+        }
+        for meshlet in self.meshlets.iter() {
+            data.extend_from_slice(&meshlet.index_offset.to_le_bytes());
+            data.extend_from_slice(&meshlet.index_count.to_le_bytes());
+            for value in meshlet
+                .center
+                .to_array()
+                .into_iter()
+                .chain([meshlet.radius])
+                .chain(meshlet.cone_axis.to_array())
+                .chain([meshlet.cone_cutoff])
+            {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        data
+    }
+
+    /// Reads the meshlets written by [Meshlets::to_bytes]
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let mut cursor = 0;
+        let mut read_u32 = || -> Result<u32, Error> {
+            let bytes = data
+                .get(cursor..cursor + 4)
+                .ok_or(Error::MeshletError("the data is cut short"))?;
+            cursor += 4;
+            Ok(u32::from_le_bytes(bytes.try_into().unwrap_or_default()))
+        };
+
+        if read_u32()?.to_le_bytes() != *MESHLETS_MAGIC {
+            return Err(Error::MeshletError("the data is not meshlets"));
+        }
+        if read_u32()? != MESHLETS_VERSION {
+            return Err(Error::MeshletError(
+                "the version of the meshlets is not supported",
+            ));
+        }
+        let vertex_count = read_u32()? as usize;
+        let index_count = read_u32()? as usize;
+        let meshlet_count = read_u32()? as usize;
+
+        // the counts are checked against the data before anything is allocated for them
+        let size = 20 + vertex_count * 32 + index_count * 4 + meshlet_count * 40;
+        if data.len() < size {
+            return Err(Error::MeshletError("the data is cut short"));
+        }
+        let mut read_f32s = |values: &mut [f32]| -> Result<(), Error> {
+            for value in values.iter_mut() {
+                *value = f32::from_bits(read_u32()?);
+            }
+            Ok(())
+        };
+
+        let mut vertices = Vec::with_capacity(vertex_count);
+        for _ in 0..vertex_count {
+            let mut values = [0.0; 8];
+            read_f32s(&mut values)?;
+            vertices.push(Vertex {
+                position: [values[0], values[1], values[2]],
+                uv: [values[3], values[4]],
+                normal: [values[5], values[6], values[7]],
+            });
+        }
+
+        let mut indices = Vec::with_capacity(index_count);
+        for _ in 0..index_count {
+            let index = read_u32()?;
+            if index as usize >= vertex_count || index > UnsignedIntType::MAX as u32 {
+                return Err(Error::MeshletError("an index is outside of the vertices"));
+            }
+            indices.push(index as UnsignedIntType);
+        }
+
+        let mut meshlets = Vec::with_capacity(meshlet_count);
+        for _ in 0..meshlet_count {
+            let index_offset = read_u32()?;
+            let index_count = read_u32()?;
+            if index_offset as usize + index_count as usize > indices.len() {
+                return Err(Error::MeshletError("a meshlet is outside of the indices"));
+            }
+            let mut values = [0.0; 8];
+            for value in values.iter_mut() {
+                *value = f32::from_bits(read_u32()?);
+            }
+
+            meshlets.push(Meshlet {
+                index_offset,
+                index_count,
+                center: Vector3::new(values[0], values[1], values[2]),
+                radius: values[3],
+                cone_axis: Vector3::new(values[4], values[5], values[6]),
+                cone_cutoff: values[7],
+            });
+        }
+
+        Ok(Self {
+            vertices,
+            indices,
+            meshlets,
+        })
+    }
+}
+
+/// The culled indices of an object with meshlets
+#[derive(Debug)]
+pub(crate) struct CulledMeshlets {
+    meshlets: Arc<Meshlets>,
+    pub(crate) index_buffer: wgpu::Buffer,
+    pub(crate) length: u32,
+    /// The meshlets the index buffer was last written with
+    visible: Vec<u32>,
+}
+
+/// Culls the meshlets of the objects set with [Object::set_meshlets] against their camera
+/// before each frame, so only the meshlets inside the view and facing the camera are drawn.
+///
+/// This is the work the task stage of a mesh shading pipeline would do. wgpu does not expose
+/// mesh and task shaders yet, see [Renderer::supports_mesh_shading], so the meshlets are culled
+/// on the CPU and their indices written to a buffer the object is drawn with. The buffer is
+/// only written when the meshlets in view change.
+#[derive(Debug)]
+pub struct MeshletCulling {
+    enabled: bool,
+    culled: HashMap<Arc<str>, CulledMeshlets>,
+    visible_meshlets: usize,
+    total_meshlets: usize,
+    // kept between frames so culling does not allocate
+    visible: Vec<u32>,
+    indices: Vec<UnsignedIntType>,
+    instances: Vec<(Matrix4, Matrix4, f32)>,
+}
+
+impl MeshletCulling {
+    /// Creates the culling, enabled
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            culled: HashMap::new(),
+            visible_meshlets: 0,
+            total_meshlets: 0,
+            visible: Vec::new(),
+            indices: Vec::new(),
+            instances: Vec::new(),
+        }
+    }
+
+    /// Drops the culled index buffers, which are made again by the next culling
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.culled.clear();
+    }
+
+    /// Turns the culling on or off. When off the objects draw all their meshlets
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.culled.clear();
+            self.visible_meshlets = 0;
+            self.total_meshlets = 0;
+        }
+    }
+
+    /// Returns true if the meshlets are culled
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The amount of meshlets drawn in the last frame
+    pub fn visible_meshlets(&self) -> usize {
+        self.visible_meshlets
+    }
+
+    /// The amount of meshlets of all the objects in the last frame
+    pub fn total_meshlets(&self) -> usize {
+        self.total_meshlets
+    }
+
+    /// The culled indices the object is drawn with instead of its own, if any
+    pub(crate) fn culled(&self, object: &Object) -> Option<&CulledMeshlets> {
+        if self.culled.is_empty() {
+            return None;
+        }
+
+        self.culled.get(&object.name)
+    }
+}
+impl Default for MeshletCulling {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The planes of the view, facing inwards, from a view projection matrix
+pub(crate) fn frustum_planes(view_projection: Matrix4) -> [Vector4; 6] {
+    let (x, y, z, w) = (
+        view_projection.row(0),
+        view_projection.row(1),
+        view_projection.row(2),
+        view_projection.row(3),
+    );
+
+    [w + x, w - x, w + y, w - y, z, w - z].map(|i| i / i.truncate().length().max(f32::MIN_POSITIVE))
+}
+
+impl Renderer {
+    /// Returns true if the device can run mesh and task shaders. Always false for now, as
+    /// wgpu does not expose them, and the meshlets are culled on the CPU instead
+    pub fn supports_mesh_shading(&self) -> bool {
+        false
+    }
+
+    /// Culls the meshlets of the objects against their cameras, and writes the indices of the
+    /// ones left to their buffers
+    pub(crate) fn cull_meshlets(&mut self, objects: &ObjectStorage, camera: &CameraContainer) {
+        let culling = &mut self.meshlet_culling;
+        if !culling.enabled {
+            return;
+        }
+
+        culling.culled.retain(|name, _| {
+            objects.get(name.as_ref()).is_some_and(|i| {
+                i.meshlets.is_some()
+                    && i.gpu_vertices.is_none()
+                    && i.is_visible
+                    && i.active_sub_meshes().is_empty()
+            })
+        });
+        culling.visible_meshlets = 0;
+        culling.total_meshlets = 0;
+
+        for object in objects.values() {
+            let Some(meshlets) = object.meshlets.as_ref() else {
+                continue;
+            };
+            let camera_name = object.camera_effect.as_deref().unwrap_or("main");
+            let Some(camera) = camera.get(camera_name) else {
+                continue;
+            };
+            // the vertices written on the GPU are not the ones of the meshlets
+            if !object.is_visible
+                || object.gpu_vertices.is_some()
+                || !object.active_sub_meshes().is_empty()
+            {
+                continue;
+            }
+
+            let planes = frustum_planes(camera.view_data);
+            // meshlets facing away are only hidden when the back faces are culled
+            let cone_sign = match (
+                object.shader_settings.cull_mode,
+                object.shader_settings.front_face,
+            ) {
+                (Some(wgpu::Face::Back), wgpu::FrontFace::Ccw) => Some(1.0),
+                (Some(wgpu::Face::Back), wgpu::FrontFace::Cw) => Some(-1.0),
+                _ => None,
+            };
+            let transform = object.translation_matrix
+                * Matrix4::from_quat(object.rotation_quaternion)
+                * object.scale_matrix;
+
+            // the matrix, normal matrix, and largest scale of each instance
+            culling.instances.clear();
+            culling.instances.extend(object.instances.iter().map(|i| {
+                let matrix = i.build().model * transform;
+                let scale = matrix
+                    .x_axis
+                    .length()
+                    .max(matrix.y_axis.length())
+                    .max(matrix.z_axis.length());
+                (matrix, matrix.inverse().transpose(), scale)
+            }));
+
+            culling.visible.clear();
+            for (index, meshlet) in meshlets.meshlets.iter().enumerate() {
+                let is_visible = culling
+                    .instances
+                    .iter()
+                    .any(|(matrix, normal_matrix, scale)| {
+                        let center = matrix.transform_point3(meshlet.center);
+                        let radius = meshlet.radius * scale;
+
+                        if planes
+                            .iter()
+                            .any(|i| i.truncate().dot(center) + i.w < -radius)
+                        {
+                            return false;
+                        }
+
+                        match cone_sign {
+                            Some(sign) => {
+                                let axis = normal_matrix
+                                    .transform_vector3(meshlet.cone_axis)
+                                    .normalize_or_zero()
+                                    * sign;
+                                let to_center = center - camera.position;
+                                to_center.dot(axis)
+                                    < meshlet.cone_cutoff * to_center.length() + radius
+                            }
+                            None => true,
+                        }
+                    });
+                if is_visible {
+                    culling.visible.push(index as u32);
+                }
+            }
+            culling.visible_meshlets += culling.visible.len();
+            culling.total_meshlets += meshlets.meshlets.len();
+
+            let entry = match culling.culled.get_mut(&object.name) {
+                Some(entry) if Arc::ptr_eq(&entry.meshlets, meshlets) => entry,
+                _ => {
+                    // the indices are copied in whole words, so an odd amount is padded
+                    let size =
+                        (meshlets.indices.len() + 1) * std::mem::size_of::<UnsignedIntType>();
+                    crate::utils::allocation_audit::count_buffer();
+                    let index_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("Meshlet Index Buffer"),
+                        size: (size as u64).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+                        usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    });
+
+                    culling
+                        .culled
+                        .entry(object.name.clone())
+                        .insert_entry(CulledMeshlets {
+                            meshlets: meshlets.clone(),
+                            index_buffer,
+                            length: 0,
+                            visible: vec![u32::MAX],
+                        })
+                        .into_mut()
+                }
+            };
+            if entry.visible == culling.visible {
+                continue;
+            }
+
+            culling.indices.clear();
+            for index in culling.visible.iter() {
+                let meshlet = &meshlets.meshlets[*index as usize];
+                let start = meshlet.index_offset as usize;
+                culling.indices.extend_from_slice(
+                    &meshlets.indices[start..start + meshlet.index_count as usize],
+                );
+            }
+            entry.length = culling.indices.len() as u32;
+            let mut bytes = bytemuck::cast_slice::<_, u8>(&culling.indices).len();
+            if bytes % wgpu::COPY_BUFFER_ALIGNMENT as usize != 0 {
+                culling.indices.push(0);
+                bytes = bytemuck::cast_slice::<_, u8>(&culling.indices).len();
+            }
+            if bytes > 0 {
+                self.queue.write_buffer(
+                    &entry.index_buffer,
+                    0,
+                    bytemuck::cast_slice(&culling.indices),
+                );
+            }
+            entry.visible.clone_from(&culling.visible);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A flat grid of quads facing +Z, with the triangles of each row next to each other
+    fn grid(size: usize) -> (Vec<Vertex>, Vec<UnsignedIntType>) {
+        let vertices = (0..=size)
+            .flat_map(|y| {
+                (0..=size).map(move |x| Vertex {
+                    position: [x as f32, y as f32, 0.0],
+                    uv: [0.0, 0.0],
+                    normal: [0.0, 0.0, 1.0],
+                })
+            })
+            .collect();
+        let indices = (0..size)
+            .flat_map(|y| {
+                (0..size).flat_map(move |x| {
+                    let corner = (y * (size + 1) + x) as UnsignedIntType;
+                    let above = corner + (size + 1) as UnsignedIntType;
+                    [corner, corner + 1, above + 1, corner, above + 1, above]
+                })
+            })
+            .collect();
+
+        (vertices, indices)
+    }
+
+    /// Checks the meshlets are within the limits, cover the indices in order, and their
+    /// spheres hold their vertices
+    fn check_meshlets(meshlets: &Meshlets, max_vertices: usize, max_triangles: usize) {
+        let mut next = 0;
+        for meshlet in meshlets.meshlets.iter() {
+            assert_eq!(meshlet.index_offset, next);
+            assert!(meshlet.index_count > 0 && meshlet.index_count % 3 == 0);
+            next += meshlet.index_count;
+
+            let range = meshlet.index_offset as usize
+                ..(meshlet.index_offset + meshlet.index_count) as usize;
+            let indices = &meshlets.indices[range];
+            let mut unique = indices.to_vec();
+            unique.sort();
+            unique.dedup();
+            assert!(unique.len() <= max_vertices, "{} vertices", unique.len());
+            assert!(indices.len() / 3 <= max_triangles);
+
+            for index in unique {
+                let position = Vector3::from(meshlets.vertices[index as usize].position);
+                assert!(position.distance(meshlet.center) <= meshlet.radius + 1e-4);
+            }
+        }
+        assert_eq!(next as usize, meshlets.indices.len());
+    }
+
+    #[test]
+    fn default_limits() {
+        let (vertices, indices) = grid(32);
+        let meshlets = Meshlets::build(&vertices, &indices);
+
+        check_meshlets(&meshlets, MAX_MESHLET_VERTICES, MAX_MESHLET_TRIANGLES);
+        assert_eq!(meshlets.indices, indices);
+        // every triangle faces +Z, so the cones are as narrow as they get
+        for meshlet in meshlets.meshlets.iter() {
+            assert!(meshlet.cone_axis.distance(Vector3::Z) < 1e-5);
+            assert!(meshlet.cone_cutoff < 1e-3);
+        }
+    }
+
+    #[test]
+    fn vertex_and_triangle_limits() {
+        let (vertices, indices) = grid(12);
+        for (max_vertices, max_triangles) in [(3, 124), (4, 2), (16, 124), (64, 7), (256, 1)] {
+            let meshlets =
+                Meshlets::build_with_limits(&vertices, &indices, max_vertices, max_triangles);
+            check_meshlets(&meshlets, max_vertices, max_triangles);
+            assert_eq!(meshlets.indices, indices);
+        }
+
+        // a single triangle per meshlet
+        let meshlets = Meshlets::build_with_limits(&vertices, &indices, 3, 1);
+        assert_eq!(meshlets.meshlets.len(), indices.len() / 3);
+
+        // limits too small for a triangle are raised to fit one
+        let meshlets = Meshlets::build_with_limits(&vertices, &indices, 0, 0);
+        assert_eq!(meshlets.meshlets.len(), indices.len() / 3);
+    }
+
+    #[test]
+    fn triangles_outside_of_the_vertices_are_skipped() {
+        let (vertices, mut indices) = grid(2);
+        let count = indices.len();
+        indices.extend_from_slice(&[0, 1, 200]);
+        // and the indices that do not make a whole triangle
+        indices.push(0);
+
+        let meshlets = Meshlets::build(&vertices, &indices);
+        assert_eq!(meshlets.indices, indices[..count]);
+        check_meshlets(&meshlets, MAX_MESHLET_VERTICES, MAX_MESHLET_TRIANGLES);
+
+        let empty = Meshlets::build(&vertices, &[]);
+        assert!(empty.meshlets.is_empty() && empty.indices.is_empty());
+    }
+
+    #[test]
+    fn triangles_facing_away_from_each_other_can_not_be_culled_by_the_cone() {
+        let vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]].map(|position| Vertex {
+            position,
+            uv: [0.0, 0.0],
+            normal: [0.0, 0.0, 1.0],
+        });
+        let meshlets = Meshlets::build(&vertices, &[0, 1, 2, 0, 2, 1]);
+        assert_eq!(meshlets.meshlets.len(), 1);
+        assert!(meshlets.meshlets[0].cone_cutoff > 1.0);
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let (vertices, indices) = grid(9);
+        let meshlets = Meshlets::build_with_limits(&vertices, &indices, 32, 40);
+
+        let read = Meshlets::from_bytes(&meshlets.to_bytes()).expect("the meshlets are read");
+        assert_eq!(
+            bytemuck::cast_slice::<Vertex, u8>(&read.vertices),
+            bytemuck::cast_slice::<Vertex, u8>(&meshlets.vertices)
+        );
+        assert_eq!(read.indices, meshlets.indices);
+        assert_eq!(read.meshlets, meshlets.meshlets);
+    }
+
+    #[test]
+    fn corrupt_bytes() {
+        let (vertices, indices) = grid(3);
+        let data = Meshlets::build(&vertices, &indices).to_bytes();
+
+        for length in 0..data.len() {
+            assert!(Meshlets::from_bytes(&data[..length]).is_err());
+        }
+
+        let mut wrong_magic = data.clone();
+        wrong_magic[0] = b'X';
+        assert!(Meshlets::from_bytes(&wrong_magic).is_err());
+
+        let mut wrong_version = data.clone();
+        wrong_version[4] = 9;
+        assert!(Meshlets::from_bytes(&wrong_version).is_err());
+
+        // counts far larger than the data are rejected before allocating for them
+        let mut huge_count = data.clone();
+        huge_count[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Meshlets::from_bytes(&huge_count).is_err());
+
+        let vertex_bytes = vertices.len() * 32;
+        let mut bad_index = data.clone();
+        bad_index[20 + vertex_bytes..24 + vertex_bytes]
+            .copy_from_slice(&(vertices.len() as u32).to_le_bytes());
+        assert!(Meshlets::from_bytes(&bad_index).is_err());
+
+        let meshlet_start = 20 + vertex_bytes + indices.len() * 4;
+        let mut bad_meshlet = data.clone();
+        bad_meshlet[meshlet_start + 4..meshlet_start + 8]
+            .copy_from_slice(&(indices.len() as u32 + 3).to_le_bytes());
+        assert!(Meshlets::from_bytes(&bad_meshlet).is_err());
+    }
+}
@@ -0,0 +1,421 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use image::GenericImageView;
+
+use super::default_resources::FRAME_HISTORY_SHADER;
+use crate::{Renderer, SamplerSettings, StringBuffer, TextureData, Textures, error::Error};
+
+/// The default amount of texture data uploaded each frame while streaming, 4 MiB
+pub const DEFAULT_STREAM_BYTES_PER_FRAME: usize = 4 * 1024 * 1024;
+/// The levels of a streamed texture up to this size are uploaded right away
+const STREAM_PRELOAD_SIZE: u32 = 128;
+
+/// The amount of mip levels of a full chain for the size, down to 1x1
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// The pipeline that fills each mip level from the one before it
+#[derive(Debug, Default)]
+pub(crate) struct MipmapGenerator {
+    sampler: Option<wgpu::Sampler>,
+    /// The layout, pipeline and the format it was built for
+    pipeline: Option<(
+        wgpu::TextureFormat,
+        wgpu::BindGroupLayout,
+        wgpu::RenderPipeline,
+    )>,
+}
+
+/// A texture whose levels are still being uploaded
+#[derive(Debug)]
+struct TextureStream {
+    texture: wgpu::Texture,
+    /// The levels left to upload, largest first, so the next one is at the end
+    levels: Vec<(u32, image::RgbaImage)>,
+    /// The rows of the next level that are already uploaded
+    uploaded_rows: u32,
+}
+
+/// Uploads large textures over several frames, smallest level first, so they show up blurry
+/// right away and get sharper in place of stalling the frame. See
+/// [Renderer::build_texture_streamed]
+#[derive(Debug)]
+pub struct TextureStreaming {
+    /// The amount of bytes uploaded each frame, shared by all the streamed textures
+    pub bytes_per_frame: usize,
+    streams: Vec<TextureStream>,
+}
+
+impl TextureStreaming {
+    /// Creates a new streaming queue
+    pub fn new() -> Self {
+        Self {
+            bytes_per_frame: DEFAULT_STREAM_BYTES_PER_FRAME,
+            streams: Vec::new(),
+        }
+    }
+
+    /// Stops streaming the textures, whose objects are given their textures again instead
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.streams.clear();
+    }
+
+    /// The amount of textures that are still being uploaded
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Returns true if no texture is being uploaded
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+}
+impl Default for TextureStreaming {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    /// Fills all the mip levels of the texture from its first level. The texture must be
+    /// created with [`wgpu::TextureUsages::RENDER_ATTACHMENT`] and a renderable format
+    pub fn generate_mipmaps(&mut self, texture: &wgpu::Texture) {
+        if texture.mip_level_count() < 2 {
+            return;
+        }
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Mipmap Encoder"),
+            });
+        for level in 1..texture.mip_level_count() {
+            self.blit_mip_level(&mut encoder, texture, level - 1, level);
+        }
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    /// Draws one level of the texture into another, scaling it to fit
+    fn blit_mip_level(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        source_level: u32,
+        target_level: u32,
+    ) {
+        let format = texture.format();
+        if self.mipmaps.pipeline.as_ref().map(|i| i.0) != Some(format) {
+            let (layout, pipeline) = Self::build_mipmap_pipeline(&self.device, format);
+            self.mipmaps.pipeline = Some((format, layout, pipeline));
+        }
+        let Some((_, layout, pipeline)) = self.mipmaps.pipeline.as_ref() else {
+            return;
+        };
+        let sampler = self.mipmaps.sampler.get_or_insert_with(|| {
+            self.device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("Mipmap Sampler"),
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            })
+        });
+
+        let level_view = |level| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Mipmap Level View"),
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        };
+        let source_view = level_view(source_level);
+        let target_view = level_view(target_level);
+
+        crate::utils::allocation_audit::count_bind_group();
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Mipmap Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&source_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Mipmap Render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn build_mipmap_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline) {
+        // the levels are copied with the same full screen triangle as the frame history
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mipmap Shader"),
+            source: wgpu::ShaderSource::Wgsl(FRAME_HISTORY_SHADER.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Mipmap Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mipmap Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mipmap Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    write_mask: wgpu::ColorWrites::ALL,
+                    blend: None,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        (bind_group_layout, pipeline)
+    }
+
+    /// Creates a new texture like [Renderer::build_texture], but uploads it over the next
+    /// frames, smallest mip level first. The texture can be used right away, and gets sharper
+    /// as the larger levels arrive. This avoids the stall of uploading large images at once
+    pub fn build_texture_streamed(
+        &mut self,
+        name: impl StringBuffer,
+        texture_data: TextureData,
+        texture_mode: impl Into<SamplerSettings>,
+    ) -> Result<Textures, Error> {
+        // compressed textures come with their levels, and are small enough to upload at once
+        if let Some(compressed) = crate::utils::compressed_textures::read_compressed(&texture_data)
+        {
+            return self.build_compressed_texture(name, &compressed?, texture_mode);
+        }
+
+        let img = match texture_data {
+            TextureData::Bytes(data) => image::load_from_memory(data.as_slice())?,
+            TextureData::Image(data) => data,
+            TextureData::Path(path) => image::open(path)?,
+        };
+        if crate::utils::hdr::is_hdr_image(&img) {
+            return Ok(self.build_texture_hdr(name, &img, texture_mode));
+        }
+        let img = self.fit_texture(name.as_str(), img);
+        let dimensions = img.dimensions();
+        let level_count = mip_level_count(dimensions.0, dimensions.1);
+
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(name.as_str()),
+            size: wgpu::Extent3d {
+                width: dimensions.0,
+                height: dimensions.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        // the levels are made on the CPU with a fast box filter, so they can be uploaded
+        // separately
+        let mut levels = vec![(0, img.to_rgba8())];
+        for level in 1..level_count {
+            let previous = &levels[level as usize - 1].1;
+            let smaller = image::imageops::thumbnail(
+                previous,
+                (previous.width() / 2).max(1),
+                (previous.height() / 2).max(1),
+            );
+            levels.push((level, smaller));
+        }
+
+        // the small levels are uploaded right away, and the larger ones are filled from them
+        // until their turn comes
+        while let Some((level, image)) = levels.last() {
+            if image.width().max(image.height()) > STREAM_PRELOAD_SIZE {
+                break;
+            }
+            write_level_rows(&self.queue, &texture, *level, image, 0, image.height());
+            levels.pop();
+        }
+        if let Some((largest_remaining, _)) = levels.last() {
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Texture Streaming Encoder"),
+                });
+            for level in (0..=*largest_remaining).rev() {
+                self.blit_mip_level(&mut encoder, &texture, level + 1, level);
+            }
+            self.queue.submit(std::iter::once(encoder.finish()));
+
+            self.texture_streaming.streams.push(TextureStream {
+                texture: texture.clone(),
+                levels,
+                uploaded_rows: 0,
+            });
+        }
+
+        let textures = self.build_texture_from_gpu(name, &texture, texture_mode);
+        self.track_texture(&textures, &texture);
+        Ok(textures)
+    }
+
+    /// Uploads the next part of the streamed textures, ran once every frame
+    pub fn stream_textures(&mut self) {
+        if self.texture_streaming.streams.is_empty() {
+            return;
+        }
+
+        let mut budget = self.texture_streaming.bytes_per_frame.max(1);
+        let mut streams = std::mem::take(&mut self.texture_streaming.streams);
+        let mut encoder = None;
+        for stream in streams.iter_mut() {
+            while budget > 0
+                && let Some((level, image)) = stream.levels.last()
+            {
+                let row_size = image.width() as usize * 4;
+                let rows =
+                    ((budget / row_size).max(1) as u32).min(image.height() - stream.uploaded_rows);
+                write_level_rows(
+                    &self.queue,
+                    &stream.texture,
+                    *level,
+                    image,
+                    stream.uploaded_rows,
+                    rows,
+                );
+                stream.uploaded_rows += rows;
+                budget = budget.saturating_sub(rows as usize * row_size);
+
+                if stream.uploaded_rows == image.height() {
+                    let level = *level;
+                    stream.levels.pop();
+                    stream.uploaded_rows = 0;
+
+                    // the next level is filled again from the sharper one
+                    if level > 0 && !stream.levels.is_empty() {
+                        let encoder = encoder.get_or_insert_with(|| {
+                            self.device
+                                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                                    label: Some("Texture Streaming Encoder"),
+                                })
+                        });
+                        self.blit_mip_level(encoder, &stream.texture, level, level - 1);
+                    }
+                }
+            }
+        }
+        streams.retain(|i| !i.levels.is_empty());
+        self.texture_streaming.streams = streams;
+
+        if let Some(encoder) = encoder {
+            self.queue.submit(std::iter::once(encoder.finish()));
+        }
+    }
+}
+
+/// Writes a range of rows of the image into the level of the texture
+fn write_level_rows(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    level: u32,
+    image: &image::RgbaImage,
+    first_row: u32,
+    rows: u32,
+) {
+    let row_size = image.width() as usize * 4;
+    let start = first_row as usize * row_size;
+    let end = start + rows as usize * row_size;
+
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture,
+            mip_level: level,
+            origin: wgpu::Origin3d {
+                x: 0,
+                y: first_row,
+                z: 0,
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        &image.as_raw()[start..end],
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(image.width() * 4),
+            rows_per_image: Some(rows),
+        },
+        wgpu::Extent3d {
+            width: image.width(),
+            height: rows,
+            depth_or_array_layers: 1,
+        },
+    );
+}
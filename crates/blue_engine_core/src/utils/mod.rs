@@ -1,8 +1,147 @@
+/// listing and picking the GPUs the engine renders with
+pub mod adapters;
+/// counting the allocations made in each frame
+pub mod allocation_audit;
+/// archives of assets that the asset server can load from
+pub mod asset_pack;
+/// loading textures, meshes, and shaders in the background
+pub mod asset_server;
+/// playing sounds in space through the default audio device
+#[cfg(feature = "audio")]
+pub mod audio;
 /// The camera utilities
 pub mod camera;
+/// ready made fly and orbit camera controls
+pub mod camera_controller;
+/// plotting helpers drawn with the painter
+pub mod chart;
+/// what the frame is cleared to before the objects are drawn: a color, a gradient, or nothing
+pub mod clear;
+/// intersection tests of boxes, spheres, rays, and points, without a physics engine
+pub mod collision;
+/// DDS and KTX2 textures uploaded in their block compressed formats
+pub mod compressed_textures;
+/// vertices of objects written by compute shaders every frame, without the CPU
+pub mod compute_mesh;
 /// Input wrapping
 mod current_input;
 /// default resources used in the engine
 pub mod default_resources;
+/// hierarchical depth of the scene for occlusion culling and screen space effects
+pub mod depth_pyramid;
+/// making the GPU device again after it is lost, with everything made on it
+pub mod device_recovery;
+/// textures written from CPU data in place, such as for video
+pub mod dynamic_texture;
+/// objects driven by the components of the entities of an ECS
+#[cfg(feature = "ecs")]
+pub mod ecs;
+/// fixed rate logic updates separate from the frame rate
+pub mod fixed_timestep;
+/// distance fog applied to the objects affected by the cameras
+pub mod fog;
+/// reading rendered frames and textures back from the GPU
+pub mod frame_capture;
+/// passes that declare their textures, ordered and given attachments by the engine
+pub mod frame_graph;
+/// ring buffer of the last presented frames for debugging
+pub mod frame_history;
+/// screenshots and frame sequence recording of the presented frames
+pub mod frame_recorder;
+/// stack of game states such as menus, gameplay, and pause screens
+pub mod game_state;
+/// gamepad and controller input
+pub mod gamepad;
+/// GPU time measurement of passes using timestamp queries
+pub mod gpu_profiler;
+/// infinite editor style ground grid
+pub mod grid;
+/// HDR rendering with tonemapping, and high dynamic range textures
+pub mod hdr;
+/// undo and redo of the changes made to the scene
+pub mod history;
+/// compute based histogram and luminance analysis of textures
+pub mod image_analysis;
+/// compute kernels for blurring, edge detection, and more on textures
+pub mod image_processing;
+/// named actions bound to keys, mouse buttons, and gamepad inputs
+pub mod input_map;
+/// chosen layers and objects rendered against a transparent background into an image
+pub mod layer_capture;
+/// the shared vertex and index buffers the meshes of the pooled objects are suballocated in
+pub mod mesh_pool;
+/// splitting high polygon meshes into clusters culled on their own
+pub mod meshlets;
+/// mipmap generation and streamed texture uploads
+pub mod mipmaps;
+/// blend shapes that move the vertices of an object by weights, such as for faces
+pub mod morph_targets;
+/// the motion of each pixel since the previous frame, for temporal effects
+pub mod motion_vectors;
+/// skipping the objects hidden behind others, found by GPU occlusion queries
+pub mod occlusion_culling;
+/// screen space drawing on top of the scene
+pub mod overlay;
+/// textures of the frame graph passes read back to the CPU for analysis
+pub mod pass_export;
+/// frame statistics and the performance HUD
+pub mod performance;
+/// the 2D physics of the objects, stepped at a fixed rate
+#[cfg(feature = "physics2d")]
+pub mod physics2d;
+/// the 3D physics of the objects, with colliders made from their meshes and raycasts
+#[cfg(feature = "physics3d")]
+pub mod physics3d;
+/// sharing the render pipelines of the objects
+pub mod pipeline_cache;
+/// reusable features that hook into the loop of the engine
+pub mod plugin;
+/// presets that set the quality of the shadows, passes, and textures at once
+pub mod quality;
+/// shadows traced with ray queries on devices with ray tracing
+pub mod ray_traced_shadows;
+/// functions that record custom passes into the encoder of the frame
+pub mod render_hooks;
+/// cameras rendered at a different internal resolution
+pub mod render_scale;
+/// change tracking and encoding of object state for networking, sent through any transport
+pub mod replication;
+/// delayed and repeating callbacks that follow the time scale
+pub mod scheduler;
+/// settings of the player kept between runs, applied by the engine
+pub mod settings;
+/// Shadertoy style full screen shaders for quick experiments
+pub mod shader_sandbox;
+/// per object seeds and time offsets that keep shared materials from animating in lockstep
+pub mod shader_variation;
+/// the shadow maps of many lights packed into one texture
+pub mod shadow_atlas;
+/// crowds of animated characters drawn as the instances of one object
+pub mod skinned_instancing;
+/// a tree of the boxes of the objects for finding them by region, view, or ray
+pub mod spatial_index;
+/// sprite sheets rendered from objects, with their normals and depth
+pub mod sprite_baking;
+/// merging the static objects into fewer draw calls
+pub mod static_batching;
+/// the format and color space of the window surface, negotiated with the platform
+pub mod surface_format;
+/// async tasks that wait for frames, time, and assets
+pub mod tasks;
+/// caps on the size of the loaded textures and on the GPU memory they take
+pub mod texture_budget;
+/// one shared uniform buffer for the transformation and color of the objects
+pub mod uniform_pool;
+/// shaders that read the vertices from a storage buffer by their index
+pub mod vertex_pulling;
+/// smaller vertices made of normalized integers and octahedral normals
+pub mod vertex_quantization;
+/// textures far larger than their GPU memory, streamed in pages
+pub mod virtual_texture;
+/// an experimental renderer that draws the IDs of the triangles first and shades them after
+pub mod visibility_buffer;
+/// the canvas and the downloads of the engine on the web
+#[cfg(target_arch = "wasm32")]
+pub mod web;
 /// input helper
 pub mod winit_input_helper;
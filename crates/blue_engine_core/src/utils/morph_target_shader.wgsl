@@ -0,0 +1,121 @@
+// blocks
+
+//@CAMERA_STRUCT
+
+//@FOG
+
+//@VARIATION
+
+struct TransformationUniforms {
+    transform_matrix: mat4x4<f32>,
+};
+@group(2) @binding(0)
+var<uniform> transform_uniform: TransformationUniforms;
+
+struct MorphInfo {
+    vertex_count: u32,
+    target_count: u32,
+};
+@group(2) @binding(2)
+var<uniform> morph: MorphInfo;
+
+// how far each vertex moves from the base mesh in each target, one target after the other
+struct MorphDelta {
+    position: vec4<f32>,
+    normal: vec4<f32>,
+};
+@group(2) @binding(3)
+var<storage, read> morph_deltas: array<MorphDelta>;
+
+@group(2) @binding(4)
+var<storage, read> morph_weights: array<f32>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) texture_coordinates: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coordinates: vec2<f32>,
+    @location(1) normal: vec3<f32>,
+    //@FOG_OUTPUT
+};
+
+struct InstanceInput {
+    @location(3) model_matrix_0: vec4<f32>,
+    @location(4) model_matrix_1: vec4<f32>,
+    @location(5) model_matrix_2: vec4<f32>,
+    @location(6) model_matrix_3: vec4<f32>,
+};
+
+// the vertex moved by every target, as much as its weight
+fn morph_vertex(vertex: VertexInput, vertex_index: u32) -> VertexInput {
+    var out = vertex;
+    if vertex_index >= morph.vertex_count {
+        return out;
+    }
+
+    for (var i = 0u; i < morph.target_count; i += 1u) {
+        let weight = morph_weights[i];
+        if weight == 0.0 {
+            continue;
+        }
+        let delta = morph_deltas[i * morph.vertex_count + vertex_index];
+        out.position += delta.position.xyz * weight;
+        out.normal += delta.normal.xyz * weight;
+    }
+    if dot(out.normal, out.normal) > 0.0 {
+        out.normal = normalize(out.normal);
+    }
+    return out;
+}
+
+// Vertex Stage
+@vertex
+fn vs_main(
+    vertex: VertexInput,
+    instance: InstanceInput,
+    @builtin(vertex_index) vertex_index: u32,
+) -> VertexOutput {
+    let input = morph_vertex(vertex, vertex_index);
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    var out: VertexOutput;
+    out.texture_coordinates = input.texture_coordinates;
+    out.normal = input.normal;
+    //@CAMERA_VERTEX
+    //@FOG_VERTEX
+    return out;
+}
+
+// Fragment Stage
+struct FragmentUniforms {
+    color: vec4<f32>,
+    random: f32,
+    time_offset: f32,
+    seed: u32,
+};
+@group(2) @binding(1)
+var<uniform> fragment_uniforms: FragmentUniforms;
+
+@group(0) @binding(0)
+var texture_diffuse: texture_2d<f32>;
+
+@group(0) @binding(1)
+var sampler_diffuse: sampler;
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    if fragment_uniforms.color.w == 0.0 {
+        return apply_fog(textureSample(texture_diffuse, sampler_diffuse, input.texture_coordinates), input.fog_distance);
+    } else {
+        return apply_fog(textureSample(texture_diffuse, sampler_diffuse, input.texture_coordinates) * fragment_uniforms.color, input.fog_distance);
+    }
+}
@@ -0,0 +1,357 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use wgpu::util::DeviceExt;
+
+use super::default_resources::{DEFAULT_SHADER, MORPH_TARGET_SHADER};
+use crate::{ObjectStorage, Renderer, StringBuffer, Vector3, error::Error, objects::ShaderBuilder};
+
+/// A shape the vertices of an object can be blended towards, such as a smile or a blink, as
+/// how far each vertex moves from the base mesh. These are the morph targets of glTF
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MorphTarget {
+    /// How far each vertex moves
+    pub positions: Vec<Vector3>,
+    /// How far the normal of each vertex moves, or empty if the normals stay as they are
+    pub normals: Vec<Vector3>,
+}
+
+impl MorphTarget {
+    /// Creates a new target from the displacement of the positions and of the normals
+    pub fn new(positions: Vec<Vector3>, normals: Vec<Vector3>) -> Self {
+        Self { positions, normals }
+    }
+
+    /// A target that moves the positions alone
+    pub fn from_positions(positions: Vec<Vector3>) -> Self {
+        Self {
+            positions,
+            normals: Vec::new(),
+        }
+    }
+}
+
+/// The uniforms of a morphed object, laid out as `MorphInfo` in the shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct MorphInfo {
+    vertex_count: u32,
+    target_count: u32,
+    padding: [u32; 2],
+}
+
+/// The displacement of one vertex in one target, laid out as `MorphDelta` in the shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct MorphDelta {
+    position: [f32; 4],
+    normal: [f32; 4],
+}
+
+/// The morph targets of an object and the weight of each, blended in the vertex shader. See
+/// [Renderer::add_morph_targets].
+///
+/// The weights are written to the GPU before the frame when they change, so they can be
+/// animated every frame without making any buffers
+///
+/// ```ignore
+/// let face = renderer.add_morph_targets(&mut objects, "face", vec![smile, blink])?;
+/// face.set_weight(0, 0.8);
+/// ```
+#[derive(Debug)]
+pub struct MorphedObject {
+    targets: Vec<MorphTarget>,
+    weights: Vec<f32>,
+    vertex_count: u32,
+    weights_changed: bool,
+    /// The info, displacements, and weights, bound from binding 2 of the object
+    buffers: [wgpu::Buffer; 3],
+}
+
+impl MorphedObject {
+    /// The targets of the object
+    pub fn targets(&self) -> &[MorphTarget] {
+        &self.targets
+    }
+
+    /// The weight of each target, zero for the targets that do not move the vertices
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    /// Sets how much of the target is blended in, usually from 0 to 1. Nothing changes if
+    /// there is no such target
+    pub fn set_weight(&mut self, target: usize, weight: f32) {
+        if let Some(value) = self.weights.get_mut(target)
+            && *value != weight
+        {
+            *value = weight;
+            self.weights_changed = true;
+        }
+    }
+
+    /// Sets the weights of the targets in order, such as from a glTF animation. The targets
+    /// after the given weights are left as they are
+    pub fn set_weights(&mut self, weights: &[f32]) {
+        for (target, weight) in weights.iter().enumerate() {
+            self.set_weight(target, *weight);
+        }
+    }
+
+    /// Gives the buffers to the object, in place of the ones it had
+    fn bind(&self, object: &mut crate::Object) {
+        if object.uniform_buffers.len() >= 5 {
+            object.uniform_buffers.splice(2..5, self.buffers.clone());
+            object.changed = true;
+        }
+    }
+}
+
+/// Makes the info, displacements, and weights buffers of a morphed object
+fn build_buffers(
+    device: &wgpu::Device,
+    targets: &[MorphTarget],
+    weights: &[f32],
+    vertex_count: u32,
+) -> [wgpu::Buffer; 3] {
+    let info = MorphInfo {
+        vertex_count,
+        target_count: targets.len() as u32,
+        padding: [0; 2],
+    };
+    crate::utils::allocation_audit::count_buffer();
+    let info = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Morph Info"),
+        contents: bytemuck::bytes_of(&info),
+        usage: wgpu::BufferUsages::UNIFORM,
+    });
+
+    let deltas = targets
+        .iter()
+        .flat_map(|target| {
+            target
+                .positions
+                .iter()
+                .enumerate()
+                .map(|(index, position)| {
+                    let normal = target.normals.get(index).copied().unwrap_or(Vector3::ZERO);
+                    MorphDelta {
+                        position: position.extend(0.0).to_array(),
+                        normal: normal.extend(0.0).to_array(),
+                    }
+                })
+        })
+        .collect::<Vec<_>>();
+    crate::utils::allocation_audit::count_buffer();
+    let deltas = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Morph Deltas"),
+        contents: bytemuck::cast_slice(&deltas),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+
+    crate::utils::allocation_audit::count_buffer();
+    let weights = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Morph Weights"),
+        contents: bytemuck::cast_slice(weights),
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+    });
+
+    [info, deltas, weights]
+}
+
+/// The objects with morph targets, whose changed weights are written before every frame
+#[derive(Debug)]
+pub struct MorphedObjects {
+    objects: std::collections::HashMap<std::sync::Arc<str>, MorphedObject>,
+}
+
+impl MorphedObjects {
+    /// Creates a new empty list of morphed objects
+    pub fn new() -> Self {
+        Self {
+            objects: std::collections::HashMap::new(),
+        }
+    }
+
+    /// The amount of morphed objects
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Returns true if there are no morphed objects
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Writes the weights that changed since the last frame
+    pub(crate) fn update(&mut self, queue: &wgpu::Queue) {
+        for morphed in self.objects.values_mut() {
+            if morphed.weights_changed {
+                queue.write_buffer(
+                    &morphed.buffers[2],
+                    0,
+                    bytemuck::cast_slice(&morphed.weights),
+                );
+                morphed.weights_changed = false;
+            }
+        }
+    }
+}
+impl Default for MorphedObjects {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    /// Gives the object morph targets, which move its vertices by their weights in the vertex
+    /// shader, such as for the faces of imported glTF models. The targets are for each vertex
+    /// of the object, or of its shared mesh, and start with a weight of zero. Replaces the
+    /// targets the object had.
+    ///
+    /// The object gets the info, displacements, and weights at bindings 2 to 4 of its
+    /// uniforms, so its own uniforms start from binding 5, and it is no longer quantized or
+    /// pulling its vertices. The default shader is swapped for [MORPH_TARGET_SHADER], which
+    /// passes the blended normal on to the fragment stage. The shadows and the motion vectors
+    /// see the object without its targets.
+    ///
+    /// Returns an error if the device can not read storage buffers in the vertex stage, if
+    /// the object is animated by a skinned crowd, or if a target does not have as many
+    /// positions as there are vertices
+    pub fn add_morph_targets(
+        &mut self,
+        objects: &mut ObjectStorage,
+        object: impl StringBuffer,
+        targets: Vec<MorphTarget>,
+    ) -> Result<&mut MorphedObject, Error> {
+        if !self.supports_vertex_pulling() {
+            return Err(Error::Custom(
+                "The device can not read storage buffers in the vertex stage".to_string(),
+            ));
+        }
+        let Some(object_value) = objects.get_mut(object.as_str()) else {
+            return Err(Error::Custom(format!(
+                "The object {} does not exist",
+                object.as_str()
+            )));
+        };
+        if object_value.shader_settings.skinned_instancing {
+            return Err(Error::Custom(format!(
+                "{} is animated by a skinned crowd, so it can not have morph targets",
+                object_value.name
+            )));
+        }
+
+        let vertex_count = match object_value.mesh.as_ref() {
+            Some(mesh) => mesh.vertices.len(),
+            None => object_value.vertices.len(),
+        };
+        if targets.is_empty() {
+            return Err(Error::Custom(format!(
+                "No morph targets were given to {}",
+                object_value.name
+            )));
+        }
+        if let Some(target) = targets.iter().find(|i| {
+            i.positions.len() != vertex_count
+                || (!i.normals.is_empty() && i.normals.len() != vertex_count)
+        }) {
+            return Err(Error::Custom(format!(
+                "A morph target has {} positions and {} normals for the {vertex_count} vertices of {}",
+                target.positions.len(),
+                target.normals.len(),
+                object_value.name
+            )));
+        }
+
+        let weights = vec![0.0; targets.len()];
+        let morphed = MorphedObject {
+            buffers: build_buffers(&self.device, &targets, &weights, vertex_count as u32),
+            vertex_count: vertex_count as u32,
+            weights_changed: false,
+            targets,
+            weights,
+        };
+
+        // the buffers of the previous targets are replaced in place
+        if !object_value.shader_settings.morph_targets {
+            object_value.set_quantized(false, self);
+            object_value.set_vertex_pulling(false, self);
+            object_value.shader_settings.morph_targets = true;
+            let at = 2.min(object_value.uniform_buffers.len());
+            object_value
+                .uniform_buffers
+                .splice(at..at, morphed.buffers.clone());
+
+            if object_value.shader_builder.source == DEFAULT_SHADER {
+                object_value.shader_builder = ShaderBuilder::new(
+                    MORPH_TARGET_SHADER.to_string(),
+                    object_value.camera_effect.clone(),
+                );
+            }
+        }
+        morphed.bind(object_value);
+
+        let name = object_value.name.clone();
+        self.morphed_objects.objects.insert(name.clone(), morphed);
+        Ok(self
+            .morphed_objects
+            .objects
+            .get_mut(&name)
+            .expect("the morphed object was just added"))
+    }
+
+    /// Returns the morph targets of the object, to set their weights
+    pub fn morphed_object(&mut self, object: impl StringBuffer) -> Option<&mut MorphedObject> {
+        self.morphed_objects.objects.get_mut(object.as_str())
+    }
+
+    /// Removes the morph targets of the object, which is then drawn as its base mesh with the
+    /// default shader. Returns false if it had none
+    pub fn remove_morph_targets(
+        &mut self,
+        objects: &mut ObjectStorage,
+        object: impl StringBuffer,
+    ) -> bool {
+        if let Some(object_value) = objects.get_mut(object.as_str())
+            && object_value.shader_settings.morph_targets
+        {
+            object_value.shader_settings.morph_targets = false;
+            if object_value.uniform_buffers.len() >= 5 {
+                object_value.uniform_buffers.drain(2..5);
+            }
+            if object_value.shader_builder.source == MORPH_TARGET_SHADER {
+                object_value.shader_builder = ShaderBuilder::new(
+                    DEFAULT_SHADER.to_string(),
+                    object_value.camera_effect.clone(),
+                );
+            }
+            object_value.changed = true;
+        }
+
+        self.morphed_objects
+            .objects
+            .remove(object.as_str())
+            .is_some()
+    }
+
+    /// Makes the buffers of the morphed objects again on the new device, and gives them to
+    /// their objects in place of the empty ones made by their recovery
+    pub(crate) fn recover_morph_targets(&mut self, objects: &mut ObjectStorage) {
+        for (name, morphed) in self.morphed_objects.objects.iter_mut() {
+            morphed.buffers = build_buffers(
+                &self.device,
+                &morphed.targets,
+                &morphed.weights,
+                morphed.vertex_count,
+            );
+            morphed.weights_changed = false;
+            if let Some(object) = objects.get_mut(name.as_ref()) {
+                morphed.bind(object);
+            }
+        }
+    }
+}
@@ -0,0 +1,359 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::sync::Arc;
+
+use super::default_resources::MOTION_VECTORS_SHADER;
+use crate::{
+    CameraContainer, InstanceRaw, Matrix4, ObjectStorage, Vertex,
+    render::get_pipeline_vertex_buffer,
+};
+
+/// The format of the motion vectors, the motion in texture coordinates on X and Y
+pub const MOTION_VECTORS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+/// The data of an object sent to the GPU, one for each object at its own offset
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct MotionUniform {
+    view_projection: [[f32; 4]; 4],
+    transform: [f32; 16],
+    previous_view_projection: [[f32; 4]; 4],
+    previous_transform: [f32; 16],
+}
+/// The distance between the uniforms of the objects, which is a multiple of the offset
+/// alignment of every device
+const UNIFORM_STRIDE: u64 = 256;
+
+/// The motion of each pixel of the scene since the previous frame, for temporal antialiasing,
+/// motion blur and temporal upscaling. The objects are drawn to a texture of
+/// [MOTION_VECTORS_FORMAT] with their transform of this frame and the one before, see
+/// [crate::Object::previous_transformation_matrix].
+///
+/// The texture is the size of the frame and is read through [MotionVectors::view], or as
+/// [crate::utils::frame_graph::MOTION_VECTORS] by the passes of the frame graph. The motion
+/// of the instances is not tracked, only of the objects and the camera.
+#[derive(Debug)]
+pub struct MotionVectors {
+    /// Should the motion vectors be drawn
+    pub enabled: bool,
+    /// The camera the motion is measured from
+    pub camera: Arc<str>,
+    /// The motion vectors and their own depth, with the size they were made for
+    target: Option<(
+        (u32, u32),
+        wgpu::Texture,
+        wgpu::TextureView,
+        wgpu::TextureView,
+    )>,
+    pipeline: Option<(wgpu::BindGroupLayout, wgpu::RenderPipeline)>,
+    /// The uniforms of the objects, their bind group, and how many objects fit
+    uniforms: Option<(wgpu::Buffer, wgpu::BindGroup, usize)>,
+    previous_view_projection: Option<Matrix4>,
+}
+
+impl MotionVectors {
+    /// Creates the motion vectors, disabled
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            camera: "main".into(),
+            target: None,
+            pipeline: None,
+            uniforms: None,
+            previous_view_projection: None,
+        }
+    }
+
+    /// Drops the target, pipeline, and uniforms, which are made again on the next frame
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.target = None;
+        self.pipeline = None;
+        self.uniforms = None;
+        self.previous_view_projection = None;
+    }
+
+    /// The motion vectors of the latest frame, if they are enabled
+    pub fn view(&self) -> Option<&wgpu::TextureView> {
+        match self.enabled {
+            true => self.target.as_ref().map(|i| &i.2),
+            false => None,
+        }
+    }
+
+    /// The texture of the motion vectors of the latest frame, if they are enabled
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        match self.enabled {
+            true => self.target.as_ref().map(|i| &i.1),
+            false => None,
+        }
+    }
+
+    /// Draws the motion of the visible objects of the camera, and of those without a camera
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        size: (u32, u32),
+        objects: &ObjectStorage,
+        camera: &CameraContainer,
+        mesh_pool: &crate::utils::mesh_pool::MeshPool,
+    ) {
+        let Some(camera) = camera.get(self.camera.as_ref()) else {
+            return;
+        };
+        let view_projection = camera.view_data;
+        let previous_view_projection = self
+            .previous_view_projection
+            .replace(view_projection)
+            .unwrap_or(view_projection);
+
+        if self.target.as_ref().map(|i| i.0) != Some(size) {
+            let (texture, view) =
+                create_target(device, size, MOTION_VECTORS_FORMAT, "Motion Vectors");
+            let (_, depth_view) =
+                create_target(device, size, crate::DEPTH_FORMAT, "Motion Vectors Depth");
+            self.target = Some((size, texture, view, depth_view));
+        }
+
+        let drawn = objects
+            .values()
+            .filter(|i| {
+                // the quantized vertices can not be read with the layout of the motion vectors
+                i.is_visible
+                    && !i.shader_settings.quantized_vertices
+                    && i.camera_effect
+                        .as_deref()
+                        .is_none_or(|i| i == self.camera.as_ref())
+            })
+            .filter_map(|i| {
+                Some((
+                    i,
+                    get_pipeline_vertex_buffer(&i.pipeline.vertex_buffer, objects)?,
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let uniforms = drawn
+            .iter()
+            .map(|(object, _)| {
+                // the objects without a camera are drawn on the screen as they are
+                let (view_projection, previous_view_projection) = match object.camera_effect {
+                    Some(_) => (view_projection, previous_view_projection),
+                    None => (Matrix4::IDENTITY, Matrix4::IDENTITY),
+                };
+                let transform = object.transformation_matrix();
+
+                MotionUniform {
+                    view_projection: view_projection.to_cols_array_2d(),
+                    transform: transform.to_cols_array(),
+                    previous_view_projection: previous_view_projection.to_cols_array_2d(),
+                    previous_transform: object.previous_transformation_matrix().to_cols_array(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if self.pipeline.is_none() {
+            self.pipeline = Some(Self::build_pipeline(device));
+        }
+        let Some((layout, pipeline)) = self.pipeline.as_ref() else {
+            return;
+        };
+
+        if self.uniforms.as_ref().is_none_or(|i| i.2 < uniforms.len()) {
+            let capacity = uniforms.len().next_power_of_two().max(64);
+            crate::utils::allocation_audit::count_buffer();
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Motion Vectors Uniform Buffer"),
+                size: capacity as u64 * UNIFORM_STRIDE,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            crate::utils::allocation_audit::count_bind_group();
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Motion Vectors Bind Group"),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<MotionUniform>() as u64),
+                    }),
+                }],
+            });
+            self.uniforms = Some((buffer, bind_group, capacity));
+        }
+        let (Some((buffer, bind_group, _)), Some((_, _, view, depth_view))) =
+            (self.uniforms.as_ref(), self.target.as_ref())
+        else {
+            return;
+        };
+        if !uniforms.is_empty() {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(uniforms.as_slice()));
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Motion Vectors Render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(pipeline);
+        for (index, (object, vertex_buffer)) in drawn.iter().enumerate() {
+            if vertex_buffer.length == 0 {
+                continue;
+            }
+            render_pass.set_bind_group(0, bind_group, &[index as u32 * UNIFORM_STRIDE as u32]);
+            let pooled =
+                crate::utils::mesh_pool::allocation_of(object, objects).zip(mesh_pool.buffers());
+            let (vertices, indices, first_index, base_vertex) = match pooled {
+                Some((allocation, (vertices, indices))) => (
+                    vertices,
+                    indices,
+                    allocation.indices().start,
+                    allocation.base_vertex(),
+                ),
+                None => (
+                    &vertex_buffer.vertex_buffer,
+                    &vertex_buffer.index_buffer,
+                    0,
+                    0,
+                ),
+            };
+            render_pass.set_vertex_buffer(0, vertices.slice(..));
+            render_pass.set_vertex_buffer(1, object.instance_buffer.slice(..));
+            render_pass.set_index_buffer(
+                indices.slice(..),
+                #[cfg(not(feature = "u32"))]
+                wgpu::IndexFormat::Uint16,
+                #[cfg(feature = "u32")]
+                wgpu::IndexFormat::Uint32,
+            );
+            render_pass.draw_indexed(
+                first_index..first_index + vertex_buffer.length,
+                base_vertex,
+                0..object.instances.len() as _,
+            );
+        }
+    }
+
+    fn build_pipeline(device: &wgpu::Device) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline) {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Motion Vectors Shader"),
+            source: wgpu::ShaderSource::Wgsl(MOTION_VECTORS_SHADER.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Motion Vectors Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(
+                        std::mem::size_of::<MotionUniform>() as u64
+                    ),
+                },
+                count: None,
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Motion Vectors Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Motion Vectors Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc(), InstanceRaw::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: MOTION_VECTORS_FORMAT,
+                    write_mask: wgpu::ColorWrites::ALL,
+                    blend: None,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            // the faces are not culled, as the objects may cull either, and the depth keeps
+            // the closest surface anyway
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: crate::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        (bind_group_layout, pipeline)
+    }
+}
+impl Default for MotionVectors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Creates a texture of the size that can be rendered to, sampled and copied, with its view
+fn create_target(
+    device: &wgpu::Device,
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+    label: &str,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size.0.max(1),
+            height: size.1.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    (texture, view)
+}
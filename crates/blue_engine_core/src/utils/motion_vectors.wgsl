@@ -0,0 +1,54 @@
+// Motion vectors, the distance each pixel moved on the screen since the previous frame.
+// The objects are drawn with their transform of this and the previous frame
+
+struct MotionUniform {
+    view_projection: mat4x4<f32>,
+    transform: mat4x4<f32>,
+    previous_view_projection: mat4x4<f32>,
+    previous_transform: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> motion: MotionUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(3) model_matrix_0: vec4<f32>,
+    @location(4) model_matrix_1: vec4<f32>,
+    @location(5) model_matrix_2: vec4<f32>,
+    @location(6) model_matrix_3: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) current: vec4<f32>,
+    @location(1) previous: vec4<f32>,
+};
+
+@vertex
+fn vs_main(input: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let position = vec4<f32>(input.position, 1.0);
+
+    var out: VertexOutput;
+    out.current = motion.view_projection * model_matrix * (motion.transform * position);
+    out.previous = motion.previous_view_projection * model_matrix
+        * (motion.previous_transform * position);
+    out.position = out.current;
+    return out;
+}
+
+// the motion in texture coordinates, from where the pixel was to where it is
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec2<f32> {
+    let current = input.current.xy / input.current.w;
+    let previous = input.previous.xy / input.previous.w;
+    return (current - previous) * vec2<f32>(0.5, -0.5);
+}
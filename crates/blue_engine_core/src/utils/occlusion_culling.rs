@@ -0,0 +1,360 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::default_resources::OCCLUSION_CULLING_SHADER;
+use crate::{CameraContainer, Object, ObjectStorage, StringBuffer, Vector3};
+
+/// The most objects queried in a frame, the largest query set every device can make. The
+/// objects after them are always drawn
+pub const MAX_OCCLUSION_QUERIES: usize = 4096;
+
+/// The box of an object, laid out as `BoxInput` in the shader, one instance for each query
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct QueryBox {
+    minimum: [f32; 3],
+    maximum: [f32; 3],
+}
+
+/// The query set, the buffer it is resolved to, the buffer it is read back from, and how
+/// many queries they hold
+type Queries = (wgpu::QuerySet, wgpu::Buffer, wgpu::Buffer, u32);
+
+/// Skips drawing the objects hidden behind others. After the main pass, the box around each
+/// object is drawn against the depth of the scene inside an occlusion query, and the objects
+/// whose box had no samples pass the depth test are left out of the main pass from then on,
+/// until their box is seen again.
+///
+/// The results are read back without waiting for the GPU, so they are a frame or more late,
+/// and an object coming out from behind another shows up that much later. Only the objects
+/// with a camera are queried, and never the ones drawn by a static batch, animated on the
+/// GPU, or around the camera. The queries themselves cost a draw call for each object, so
+/// this pays off in dense scenes such as interiors, with many objects behind walls
+#[derive(Debug)]
+pub struct OcclusionCulling {
+    /// Should the hidden objects be skipped
+    pub enabled: bool,
+    /// The objects that were hidden in the latest results
+    occluded: Arc<HashSet<Arc<str>>>,
+    pipeline: Option<wgpu::RenderPipeline>,
+    queries: Option<Queries>,
+    /// The instance buffer of the boxes, and how many boxes fit
+    boxes: Option<(wgpu::Buffer, u32)>,
+    /// The objects of the queries being read back, in order
+    pending: Vec<Arc<str>>,
+    resolved: bool,
+    mapping: Option<crate::definition::BufferMapping>,
+}
+
+impl OcclusionCulling {
+    /// Creates the occlusion culling, disabled
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            occluded: Arc::new(HashSet::new()),
+            pipeline: None,
+            queries: None,
+            boxes: None,
+            pending: Vec::new(),
+            resolved: false,
+            mapping: None,
+        }
+    }
+
+    /// Drops the pipeline and buffers, and shows every object until the next results
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.occluded = Arc::new(HashSet::new());
+        self.pipeline = None;
+        self.queries = None;
+        self.boxes = None;
+        self.pending.clear();
+        self.resolved = false;
+        self.mapping = None;
+    }
+
+    /// Returns true if the object was hidden behind others in the latest results
+    pub fn is_occluded(&self, object: impl StringBuffer) -> bool {
+        self.occluded.contains(object.as_str())
+    }
+
+    /// The amount of objects that were hidden in the latest results
+    pub fn occluded_count(&self) -> usize {
+        self.occluded.len()
+    }
+
+    /// The objects left out of the main pass
+    pub(crate) fn occluded(&self) -> Arc<HashSet<Arc<str>>> {
+        self.occluded.clone()
+    }
+
+    /// Collects the results of the previous frames, if they arrived
+    pub(crate) fn begin_frame(&mut self, device: &wgpu::Device) {
+        if !self.enabled {
+            if !self.occluded.is_empty() {
+                self.occluded = Arc::new(HashSet::new());
+            }
+            return;
+        }
+        let (Some(mapping), Some((_, _, readback, _))) =
+            (self.mapping.as_ref(), self.queries.as_ref())
+        else {
+            return;
+        };
+        device.poll(wgpu::Maintain::Poll);
+
+        match mapping.result() {
+            Some(Ok(())) => {
+                let data = readback.slice(..).get_mapped_range();
+                let samples: &[u64] = bytemuck::cast_slice(&data);
+                self.occluded = Arc::new(
+                    self.pending
+                        .iter()
+                        .zip(samples)
+                        .filter(|(_, samples)| **samples == 0)
+                        .map(|(name, _)| name.clone())
+                        .collect(),
+                );
+                drop(data);
+
+                readback.unmap();
+                self.mapping = None;
+            }
+            Some(Err(_)) => self.mapping = None,
+            None => {}
+        }
+    }
+
+    /// Draws the boxes of the objects against the depth of the scene, each in its own query,
+    /// unless the previous queries are still being read
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        depth_view: &wgpu::TextureView,
+        frame_size: winit::dpi::PhysicalSize<u32>,
+        (objects, camera): (&ObjectStorage, &CameraContainer),
+        camera_layout: &wgpu::BindGroupLayout,
+        static_batches: &crate::utils::static_batching::StaticBatches,
+    ) {
+        if !self.enabled || self.mapping.is_some() {
+            return;
+        }
+
+        let mut names = Vec::new();
+        let mut boxes = Vec::new();
+        for object in objects.values() {
+            if names.len() >= MAX_OCCLUSION_QUERIES {
+                break;
+            }
+            let Some(camera_name) = object.camera_effect.as_deref() else {
+                continue;
+            };
+            let Some(camera) = camera.get(camera_name) else {
+                continue;
+            };
+            if !is_queried(object) || static_batches.is_batched(object) {
+                continue;
+            }
+            let Some(aabb) = object.instances_aabb() else {
+                continue;
+            };
+            // the box would be clipped by the near plane, and seen from inside
+            let margin = Vector3::splat(camera.near);
+            if camera.position.cmpge(aabb.min - margin).all()
+                && camera.position.cmple(aabb.max + margin).all()
+            {
+                continue;
+            }
+
+            names.push((object.name.clone(), camera_name));
+            boxes.push(QueryBox {
+                minimum: aabb.min.to_array(),
+                maximum: aabb.max.to_array(),
+            });
+        }
+        if names.is_empty() {
+            self.occluded = Arc::new(HashSet::new());
+            return;
+        }
+        let count = names.len() as u32;
+
+        if self.boxes.as_ref().is_none_or(|i| i.1 < count) {
+            let capacity = count.next_power_of_two();
+            crate::utils::allocation_audit::count_buffer();
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Occlusion Query Boxes"),
+                size: capacity as u64 * std::mem::size_of::<QueryBox>() as u64,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            self.boxes = Some((buffer, capacity));
+        }
+        if self.queries.as_ref().is_none_or(|i| i.3 < count) {
+            self.queries = Some(build_queries(device, count.next_power_of_two()));
+        }
+        let pipeline = self
+            .pipeline
+            .get_or_insert_with(|| build_pipeline(device, camera_layout));
+        let (Some((box_buffer, _)), Some((query_set, resolve_buffer, readback, _))) =
+            (self.boxes.as_ref(), self.queries.as_ref())
+        else {
+            return;
+        };
+        queue.write_buffer(box_buffer, 0, bytemuck::cast_slice(&boxes));
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Occlusion Queries"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: Some(query_set),
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_vertex_buffer(0, box_buffer.slice(..));
+
+            let frame_rect = (0, 0, frame_size.width, frame_size.height);
+            let mut clip = (frame_rect, frame_rect);
+            for (index, (_, camera_name)) in names.iter().enumerate() {
+                let index = index as u32;
+                let camera = camera.get(*camera_name);
+                if let Some(camera) = camera {
+                    render_pass.set_bind_group(0, &camera.uniform_data, &[]);
+                }
+
+                // a box outside of the viewport passes no samples, as its object is not seen
+                let clipped = crate::render::set_clip(
+                    &mut render_pass,
+                    &mut clip,
+                    crate::render::item_clip(camera, None, frame_size, frame_rect),
+                );
+                render_pass.begin_occlusion_query(index);
+                if clipped {
+                    render_pass.draw(0..36, index..index + 1);
+                }
+                render_pass.end_occlusion_query();
+            }
+        }
+
+        encoder.resolve_query_set(query_set, 0..count, resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            resolve_buffer,
+            0,
+            readback,
+            0,
+            count as u64 * wgpu::QUERY_SIZE as u64,
+        );
+
+        self.pending = names.into_iter().map(|(name, _)| name).collect();
+        self.resolved = true;
+    }
+
+    /// Starts reading back the resolved queries, must be called after the frame is submitted
+    pub(crate) fn after_submit(&mut self) {
+        if self.resolved
+            && let Some((_, _, readback, _)) = self.queries.as_ref()
+        {
+            self.resolved = false;
+            self.mapping = Some(crate::definition::BufferMapping::read(readback));
+        }
+    }
+}
+impl Default for OcclusionCulling {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The objects whose vertices stay inside the box of their vertices on the CPU
+fn is_queried(object: &Object) -> bool {
+    object.is_visible
+        && object.gpu_vertices.is_none()
+        && !object.shader_settings.skinned_instancing
+        && !object.shader_settings.morph_targets
+        && !object.instances.is_empty()
+}
+
+/// Creates the query set with the buffers its results are copied through
+fn build_queries(device: &wgpu::Device, count: u32) -> Queries {
+    let size = count as u64 * wgpu::QUERY_SIZE as u64;
+
+    (
+        device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Occlusion Query Set"),
+            ty: wgpu::QueryType::Occlusion,
+            count,
+        }),
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Occlusion Query Resolve Buffer"),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        }),
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Occlusion Query Readback Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }),
+        count,
+    )
+}
+
+/// The boxes are drawn from both sides without writing anything, only tested against the depth
+fn build_pipeline(
+    device: &wgpu::Device,
+    camera_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Occlusion Culling Shader"),
+        source: wgpu::ShaderSource::Wgsl(OCCLUSION_CULLING_SHADER.into()),
+    });
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Occlusion Culling Pipeline Layout"),
+        bind_group_layouts: &[camera_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Occlusion Culling Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<QueryBox>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+            }],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: None,
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: crate::DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
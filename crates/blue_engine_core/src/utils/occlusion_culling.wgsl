@@ -0,0 +1,34 @@
+// Occlusion queries, the boxes around the objects drawn against the depth of the scene.
+// The samples that pass the depth test are counted by the query of each box
+
+struct CameraUniforms {
+    camera_matrix: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera_uniform: CameraUniforms;
+
+struct BoxInput {
+    @location(0) minimum: vec3<f32>,
+    @location(1) maximum: vec3<f32>,
+};
+
+// the corners of the twelve triangles of a box, with the bits of the corner picking the
+// largest side on x, y, and z
+const CORNERS = array<u32, 36>(
+    0u, 2u, 6u, 0u, 6u, 4u,
+    1u, 5u, 7u, 1u, 7u, 3u,
+    0u, 4u, 5u, 0u, 5u, 1u,
+    2u, 3u, 7u, 2u, 7u, 6u,
+    0u, 1u, 3u, 0u, 3u, 2u,
+    4u, 6u, 7u, 4u, 7u, 5u,
+);
+
+@vertex
+fn vs_main(input: BoxInput, @builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    var corners = CORNERS;
+    let corner = corners[vertex_index % 36u];
+    let largest = vec3<bool>((corner & 1u) != 0u, (corner & 2u) != 0u, (corner & 4u) != 0u);
+    let position = select(input.minimum, input.maximum, largest);
+
+    return camera_uniform.camera_matrix * vec4<f32>(position, 1.0);
+}
@@ -0,0 +1,441 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use wgpu::util::DeviceExt;
+
+use super::default_resources::OVERLAY_SHADER;
+
+/// Width of a glyph in the built-in bitmap font, in font pixels
+pub const GLYPH_WIDTH: u32 = 3;
+/// Height of a glyph in the built-in bitmap font, in font pixels
+pub const GLYPH_HEIGHT: u32 = 5;
+
+/// Vertex used by the overlay, is in pixel space until it is drawn
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OverlayVertex {
+    /// Position of the vertex in pixels, from the top left of the window
+    pub position: [f32; 2],
+    /// Color of the vertex
+    pub color: [f32; 4],
+}
+impl OverlayVertex {
+    pub(crate) fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<OverlayVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+/// A screen space painter that is drawn on top of everything else in the frame.
+///
+/// Everything added to it is in pixels, starting from the top left of the window,
+/// and is cleared after each frame.
+#[derive(Debug)]
+pub struct Overlay {
+    /// The vertices queued for this frame
+    pub vertices: Vec<OverlayVertex>,
+    /// The indices queued for this frame
+    pub indices: Vec<u32>,
+    /// The pipeline and the format it was built for
+    pipeline: Option<(wgpu::TextureFormat, wgpu::RenderPipeline)>,
+}
+
+impl Overlay {
+    /// Creates a new empty overlay
+    pub fn new() -> Self {
+        Self {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            pipeline: None,
+        }
+    }
+
+    /// Drops the pipeline, which is made again when the overlay is next drawn
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.pipeline = None;
+    }
+
+    /// Removes everything that is queued
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    /// Returns true if nothing is queued to be drawn
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Queues a filled rectangle
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) {
+        let start = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&[
+            OverlayVertex {
+                position: [x, y],
+                color,
+            },
+            OverlayVertex {
+                position: [x + width, y],
+                color,
+            },
+            OverlayVertex {
+                position: [x + width, y + height],
+                color,
+            },
+            OverlayVertex {
+                position: [x, y + height],
+                color,
+            },
+        ]);
+        self.indices
+            .extend_from_slice(&[start, start + 2, start + 1, start, start + 3, start + 2]);
+    }
+
+    /// Queues the outline of a rectangle, the outline is drawn inside the rectangle
+    pub fn rect_outline(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        thickness: f32,
+        color: [f32; 4],
+    ) {
+        let thickness = thickness.min(width / 2.0).min(height / 2.0);
+
+        self.rect(x, y, width, thickness, color);
+        self.rect(x, y + height - thickness, width, thickness, color);
+        self.rect(x, y + thickness, thickness, height - thickness * 2.0, color);
+        self.rect(
+            x + width - thickness,
+            y + thickness,
+            thickness,
+            height - thickness * 2.0,
+            color,
+        );
+    }
+
+    /// Queues a filled triangle from three points
+    pub fn triangle(&mut self, a: [f32; 2], b: [f32; 2], c: [f32; 2], color: [f32; 4]) {
+        let start = self.vertices.len() as u32;
+        self.vertices.extend(
+            [a, b, c]
+                .into_iter()
+                .map(|position| OverlayVertex { position, color }),
+        );
+        self.indices
+            .extend_from_slice(&[start, start + 1, start + 2]);
+    }
+
+    /// Queues a line between two points with the given thickness
+    pub fn line(&mut self, from: [f32; 2], to: [f32; 2], thickness: f32, color: [f32; 4]) {
+        let direction = [to[0] - from[0], to[1] - from[1]];
+        let length = (direction[0] * direction[0] + direction[1] * direction[1]).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        // offset along the normal of the line, for each side
+        let normal = [
+            -direction[1] / length * thickness / 2.0,
+            direction[0] / length * thickness / 2.0,
+        ];
+
+        let start = self.vertices.len() as u32;
+        self.vertices.extend(
+            [
+                [from[0] + normal[0], from[1] + normal[1]],
+                [to[0] + normal[0], to[1] + normal[1]],
+                [to[0] - normal[0], to[1] - normal[1]],
+                [from[0] - normal[0], from[1] - normal[1]],
+            ]
+            .into_iter()
+            .map(|position| OverlayVertex { position, color }),
+        );
+        self.indices
+            .extend_from_slice(&[start, start + 2, start + 1, start, start + 3, start + 2]);
+    }
+
+    /// Queues connected lines through the points
+    pub fn polyline(&mut self, points: &[[f32; 2]], thickness: f32, color: [f32; 4]) {
+        for pair in points.windows(2) {
+            self.line(pair[0], pair[1], thickness, color);
+        }
+    }
+
+    /// Queues a filled circle
+    pub fn circle(&mut self, center: [f32; 2], radius: f32, color: [f32; 4]) {
+        let segments = circle_segments(radius);
+
+        let start = self.vertices.len() as u32;
+        self.vertices.push(OverlayVertex {
+            position: center,
+            color,
+        });
+        self.vertices.extend((0..segments).map(|i| {
+            let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+            OverlayVertex {
+                position: [
+                    center[0] + angle.cos() * radius,
+                    center[1] + angle.sin() * radius,
+                ],
+                color,
+            }
+        }));
+        for i in 0..segments {
+            self.indices
+                .extend_from_slice(&[start, start + 1 + i, start + 1 + (i + 1) % segments]);
+        }
+    }
+
+    /// Queues the outline of a circle with the given thickness
+    pub fn circle_outline(
+        &mut self,
+        center: [f32; 2],
+        radius: f32,
+        thickness: f32,
+        color: [f32; 4],
+    ) {
+        let segments = circle_segments(radius);
+        let points: Vec<_> = (0..=segments)
+            .map(|i| {
+                let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+                [
+                    center[0] + angle.cos() * radius,
+                    center[1] + angle.sin() * radius,
+                ]
+            })
+            .collect();
+
+        self.polyline(&points, thickness, color);
+    }
+
+    /// Queues a line of text using the built-in bitmap font and returns its width in pixels.
+    ///
+    /// The font only contains digits, latin letters and a few symbols, lowercase letters are
+    /// drawn as uppercase and unknown characters are drawn as blank space.
+    pub fn text(&mut self, x: f32, y: f32, scale: f32, text: &str, color: [f32; 4]) -> f32 {
+        let mut cursor = x;
+        for character in text.chars() {
+            let glyph = glyph(character);
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        self.rect(
+                            cursor + column as f32 * scale,
+                            y + row as f32 * scale,
+                            scale,
+                            scale,
+                            color,
+                        );
+                    }
+                }
+            }
+            cursor += (GLYPH_WIDTH + 1) as f32 * scale;
+        }
+
+        cursor - x
+    }
+
+    /// Width in pixels of a text when drawn with the given scale
+    pub fn text_width(text: &str, scale: f32) -> f32 {
+        text.chars().count() as f32 * (GLYPH_WIDTH + 1) as f32 * scale
+    }
+
+    /// Draws everything queued onto the view and clears the queue
+    pub(crate) fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+        size: winit::dpi::PhysicalSize<u32>,
+        gpu_profiler: Option<&mut crate::utils::gpu_profiler::GpuProfiler>,
+    ) {
+        if self.is_empty() || size.width == 0 || size.height == 0 {
+            self.clear();
+            return;
+        }
+
+        if self.pipeline.as_ref().map(|i| i.0) != Some(format) {
+            self.pipeline = Some((format, Self::build_pipeline(device, format)));
+        }
+
+        // convert from pixels to normalized device coordinates
+        let vertices = self
+            .vertices
+            .iter()
+            .map(|i| OverlayVertex {
+                position: [
+                    i.position[0] / size.width as f32 * 2.0 - 1.0,
+                    1.0 - i.position[1] / size.height as f32 * 2.0,
+                ],
+                color: i.color,
+            })
+            .collect::<Vec<_>>();
+
+        crate::utils::allocation_audit::count_buffer();
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Overlay Vertex Buffer"),
+            contents: bytemuck::cast_slice(vertices.as_slice()),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        crate::utils::allocation_audit::count_buffer();
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Overlay Index Buffer"),
+            contents: bytemuck::cast_slice(self.indices.as_slice()),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        if let Some((_, pipeline)) = self.pipeline.as_ref() {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Overlay Render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: gpu_profiler
+                    .and_then(|i| i.render_pass_timestamps("Overlay Pass")),
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..self.indices.len() as u32, 0, 0..1);
+        }
+
+        self.clear();
+    }
+
+    fn build_pipeline(device: &wgpu::Device, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Overlay Shader"),
+            source: wgpu::ShaderSource::Wgsl(OVERLAY_SHADER.into()),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overlay Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overlay Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[OverlayVertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    write_mask: wgpu::ColorWrites::ALL,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+                unclipped_depth: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+}
+impl Default for Overlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Amount of segments used for a circle, more for bigger circles
+fn circle_segments(radius: f32) -> u32 {
+    (radius / 2.0).clamp(12.0, 96.0) as u32
+}
+
+/// Returns the rows of a glyph in the built-in 3x5 bitmap font
+#[rustfmt::skip]
+fn glyph(character: char) -> [u8; GLYPH_HEIGHT as usize] {
+    match character.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        _ => [0b000, 0b000, 0b000, 0b000, 0b000],
+    }
+}
@@ -0,0 +1,281 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::sync::Arc;
+
+use crate::{definition::BufferMapping, error::Error};
+
+/// The pixels of an exported texture, converted from the format of the texture
+#[derive(Debug, Clone)]
+pub enum ExportedData {
+    /// The 8 bit RGBA and BGRA formats, in RGBA order
+    Color(image::RgbaImage),
+    /// The 16 and 32 bit float formats, with the channels of each pixel next to each other
+    Float {
+        /// The amount of channels of a pixel, from 1 to 4
+        channels: u32,
+        /// The channels of the pixels, row by row
+        values: Vec<f32>,
+    },
+    /// The depth of each pixel, row by row
+    Depth(Vec<f32>),
+}
+
+/// A texture that a pass of the frame graph tagged for export, read back from the GPU
+#[derive(Debug, Clone)]
+pub struct ExportedTexture {
+    /// The pass that wrote the texture
+    pub pass: Arc<str>,
+    /// The width in pixels
+    pub width: u32,
+    /// The height in pixels
+    pub height: u32,
+    /// The format of the texture on the GPU
+    pub format: wgpu::TextureFormat,
+    /// The pixels of the texture
+    pub data: ExportedData,
+}
+
+impl ExportedTexture {
+    /// Converts the pixels to an image. Floats are clamped between 0 and 1, a single channel
+    /// or the depth is gray, and two channels are red and green
+    pub fn to_image(&self) -> image::RgbaImage {
+        let to_u8 = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let pixels = match &self.data {
+            ExportedData::Color(image) => return image.clone(),
+            ExportedData::Float { channels, values } => values
+                .chunks_exact((*channels).max(1) as usize)
+                .flat_map(|i| match i {
+                    [gray] => [to_u8(*gray), to_u8(*gray), to_u8(*gray), 255],
+                    [r, g] => [to_u8(*r), to_u8(*g), 0, 255],
+                    [r, g, b] => [to_u8(*r), to_u8(*g), to_u8(*b), 255],
+                    [r, g, b, a, ..] => [to_u8(*r), to_u8(*g), to_u8(*b), to_u8(*a)],
+                    [] => [0, 0, 0, 255],
+                })
+                .collect(),
+            ExportedData::Depth(values) => values
+                .iter()
+                .flat_map(|i| [to_u8(*i), to_u8(*i), to_u8(*i), 255])
+                .collect(),
+        };
+
+        image::RgbaImage::from_raw(self.width, self.height, pixels)
+            .unwrap_or_else(|| image::RgbaImage::new(self.width, self.height))
+    }
+}
+
+/// Returns an error if the texture of the format can not be exported
+pub(crate) fn check_format(format: wgpu::TextureFormat) -> Result<(), Error> {
+    match layout_of(format) {
+        Some(_) => Ok(()),
+        None => Err(Error::FrameGraphError(format!(
+            "textures of format {format:?} can not be exported"
+        ))),
+    }
+}
+
+/// How the pixels of a format are laid out
+#[derive(Debug, Clone, Copy)]
+enum Layout {
+    Unorm8 { swap_channels: bool },
+    Float16(u32),
+    Float32(u32),
+    Depth32,
+}
+
+fn layout_of(format: wgpu::TextureFormat) -> Option<Layout> {
+    use wgpu::TextureFormat as Format;
+    Some(match format {
+        Format::Rgba8Unorm | Format::Rgba8UnormSrgb => Layout::Unorm8 {
+            swap_channels: false,
+        },
+        Format::Bgra8Unorm | Format::Bgra8UnormSrgb => Layout::Unorm8 {
+            swap_channels: true,
+        },
+        Format::R16Float => Layout::Float16(1),
+        Format::Rg16Float => Layout::Float16(2),
+        Format::Rgba16Float => Layout::Float16(4),
+        Format::R32Float => Layout::Float32(1),
+        Format::Rg32Float => Layout::Float32(2),
+        Format::Rgba32Float => Layout::Float32(4),
+        Format::Depth32Float => Layout::Depth32,
+        _ => return None,
+    })
+}
+
+/// The part of the texture that is copied, only the depth of depth textures
+fn aspect_of(format: wgpu::TextureFormat) -> wgpu::TextureAspect {
+    match format.is_depth_stencil_format() {
+        true => wgpu::TextureAspect::DepthOnly,
+        false => wgpu::TextureAspect::All,
+    }
+}
+
+/// A copy of an exported texture that is being read back
+#[derive(Debug)]
+pub(crate) struct PendingExport {
+    pub(crate) name: Arc<str>,
+    pass: Arc<str>,
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    format: wgpu::TextureFormat,
+    mapping: Option<BufferMapping>,
+}
+
+impl PendingExport {
+    /// Records a copy of the texture into a new buffer. Returns None if its format can not be
+    /// exported, or it can not be copied
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        name: Arc<str>,
+        pass: Arc<str>,
+        texture: &wgpu::Texture,
+    ) -> Option<Self> {
+        let format = texture.format();
+        layout_of(format)?;
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            return None;
+        }
+        let aspect = aspect_of(format);
+        let block_size = format.block_copy_size(Some(aspect))?;
+
+        let width = texture.width();
+        let height = texture.height();
+        let padded_bytes_per_row = (width * block_size)
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        crate::utils::allocation_audit::count_buffer();
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pass Export Buffer"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        Some(Self {
+            name,
+            pass,
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            format,
+            mapping: None,
+        })
+    }
+
+    /// Starts reading the buffer back, once the frame it was recorded in is submitted
+    pub(crate) fn start(&mut self) {
+        if self.mapping.is_none() {
+            self.mapping = Some(BufferMapping::read(&self.buffer));
+        }
+    }
+
+    /// The finished export, the error of the readback, or None while it is still being read
+    pub(crate) fn finish(&self) -> Option<Result<ExportedTexture, Error>> {
+        match self.mapping.as_ref()?.result()? {
+            Ok(()) => {
+                let data = self.buffer.slice(..).get_mapped_range();
+                let texture = self.convert(&data);
+                drop(data);
+                self.buffer.unmap();
+                Some(Ok(texture))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    /// Removes the row padding, and converts the pixels of the format
+    fn convert(&self, data: &[u8]) -> ExportedTexture {
+        let block_size = self
+            .format
+            .block_copy_size(Some(aspect_of(self.format)))
+            .unwrap_or(4);
+        let unpadded = (self.width * block_size) as usize;
+        let mut bytes = Vec::with_capacity(unpadded * self.height as usize);
+        for row in data.chunks_exact(self.padded_bytes_per_row as usize) {
+            bytes.extend_from_slice(&row[..unpadded]);
+        }
+
+        let floats = |bytes: &[u8]| {
+            bytes
+                .chunks_exact(4)
+                .map(|i| f32::from_le_bytes([i[0], i[1], i[2], i[3]]))
+                .collect::<Vec<_>>()
+        };
+        let data = match layout_of(self.format) {
+            Some(Layout::Unorm8 { swap_channels }) => {
+                if swap_channels {
+                    bytes.chunks_exact_mut(4).for_each(|i| i.swap(0, 2));
+                }
+                ExportedData::Color(
+                    image::RgbaImage::from_raw(self.width, self.height, bytes)
+                        .unwrap_or_else(|| image::RgbaImage::new(self.width, self.height)),
+                )
+            }
+            Some(Layout::Float16(channels)) => ExportedData::Float {
+                channels,
+                values: bytes
+                    .chunks_exact(2)
+                    .map(|i| f16_to_f32(u16::from_le_bytes([i[0], i[1]])))
+                    .collect(),
+            },
+            Some(Layout::Float32(channels)) => ExportedData::Float {
+                channels,
+                values: floats(&bytes),
+            },
+            Some(Layout::Depth32) | None => ExportedData::Depth(floats(&bytes)),
+        };
+
+        ExportedTexture {
+            pass: self.pass.clone(),
+            width: self.width,
+            height: self.height,
+            format: self.format,
+            data,
+        }
+    }
+}
+
+/// Converts the bits of a half precision float
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
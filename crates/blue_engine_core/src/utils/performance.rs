@@ -0,0 +1,256 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{
+    ObjectStorage, PipelineData,
+    utils::{allocation_audit::AllocationReport, overlay::Overlay},
+};
+
+/// The amount of frames kept for the frame time graph
+pub const FRAME_TIME_HISTORY: usize = 120;
+
+/// Time spent in each stage of the frame, in milliseconds
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StageTimings {
+    /// Acquiring the frame and recording the object draws
+    pub pre_render: f32,
+    /// Running the update loop and the signals
+    pub update: f32,
+    /// Rebuilding the changed objects and cameras
+    pub object_update: f32,
+    /// Submitting and presenting the frame
+    pub render: f32,
+}
+
+/// How evenly the frames are spaced, for diagnosing stutter
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FramePacingStats {
+    /// The time each frame is given by the frame pacing, in milliseconds
+    pub target_frame_time: Option<f32>,
+    /// The standard deviation of the recent frame times, in milliseconds
+    pub jitter: f32,
+    /// The largest difference of a recent frame time from the target, in milliseconds
+    pub max_deviation: f32,
+    /// Frames that took more than one and a half times the target, in total
+    pub missed_frames: u64,
+}
+
+/// Statistics about the frames rendered by the engine
+#[derive(Debug, Clone)]
+pub struct RenderStats {
+    /// Frames per second, averaged over the frame time history
+    pub fps: f32,
+    /// Time it took for the last frame, in milliseconds
+    pub frame_time: f32,
+    /// The time of the recent frames, in milliseconds, oldest first
+    pub frame_time_history: std::collections::VecDeque<f32>,
+    /// Amount of draw calls in the last frame
+    pub draw_calls: u32,
+    /// Amount of objects in the storage
+    pub object_count: usize,
+    /// Bytes used by the vertex, index, instance, and uniform buffers of the objects
+    pub buffer_memory: u64,
+    /// Bytes used by the textures loaded for the objects, see
+    /// [crate::utils::texture_budget::TextureBudget]
+    pub texture_memory: u64,
+    /// Time spent in each stage of the last frame
+    pub timings: StageTimings,
+    /// GPU time of each measured pass in milliseconds, when GPU profiling is enabled
+    pub gpu_timings: Vec<(String, f32)>,
+    /// How evenly the frames are spaced
+    pub pacing: FramePacingStats,
+    /// The heap allocations and GPU resources made in the last frame, by subsystem. Only
+    /// counted with the `allocation_audit` feature
+    pub allocations: AllocationReport,
+    last_frame: Option<crate::time::Instant>,
+}
+
+impl RenderStats {
+    /// Creates a new empty statistics
+    pub fn new() -> Self {
+        Self {
+            fps: 0.0,
+            frame_time: 0.0,
+            frame_time_history: std::collections::VecDeque::with_capacity(FRAME_TIME_HISTORY),
+            draw_calls: 0,
+            object_count: 0,
+            buffer_memory: 0,
+            texture_memory: 0,
+            timings: StageTimings::default(),
+            gpu_timings: Vec::new(),
+            pacing: FramePacingStats::default(),
+            allocations: AllocationReport::default(),
+            last_frame: None,
+        }
+    }
+
+    /// Marks the start of a new frame, and updates the frame time and FPS
+    pub(crate) fn begin_frame(&mut self) {
+        let now = crate::time::Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            self.frame_time = (now - last_frame).as_secs_f32() * 1000.0;
+
+            if self.frame_time_history.len() == FRAME_TIME_HISTORY {
+                self.frame_time_history.pop_front();
+            }
+            self.frame_time_history.push_back(self.frame_time);
+
+            let average =
+                self.frame_time_history.iter().sum::<f32>() / self.frame_time_history.len() as f32;
+            self.fps = if average > 0.0 { 1000.0 / average } else { 0.0 };
+
+            self.pacing.jitter = (self
+                .frame_time_history
+                .iter()
+                .map(|i| (i - average).powi(2))
+                .sum::<f32>()
+                / self.frame_time_history.len() as f32)
+                .sqrt();
+            if let Some(target) = self.pacing.target_frame_time {
+                self.pacing.max_deviation = self
+                    .frame_time_history
+                    .iter()
+                    .map(|i| (i - target).abs())
+                    .fold(0.0, f32::max);
+                if self.frame_time > target * 1.5 {
+                    self.pacing.missed_frames += 1;
+                }
+            } else {
+                self.pacing.max_deviation = 0.0;
+            }
+        }
+        self.last_frame = Some(now);
+    }
+
+    /// Counts the objects and the memory used by their buffers
+    pub(crate) fn collect_objects(&mut self, objects: &ObjectStorage) {
+        // shared meshes are only counted once
+        let mut meshes = std::collections::HashSet::new();
+
+        self.object_count = objects.len();
+        self.buffer_memory = objects
+            .values()
+            .map(|i| {
+                let vertex_buffer = match (&i.pipeline.vertex_buffer, &i.mesh, &i.mesh_allocation) {
+                    // only the part of the shared buffers of the pool the object has
+                    (PipelineData::Data(_), _, Some(allocation)) => allocation.size(),
+                    (PipelineData::Data(_), Some(mesh), _) if !meshes.insert(mesh.id()) => 0,
+                    (PipelineData::Data(data), _, _) => {
+                        data.vertex_buffer.size() + data.index_buffer.size()
+                    }
+                    (PipelineData::Copy(_), _, _) => 0,
+                };
+                let uniform_buffers = i.uniform_buffers.iter().map(|j| j.size()).sum::<u64>();
+
+                let instance_buffers = i.instance_buffer.size()
+                    + i.back_instance_buffer.as_ref().map_or(0, |j| j.size());
+
+                vertex_buffer + instance_buffers + uniform_buffers
+            })
+            .sum();
+    }
+
+    /// Queues the performance HUD onto the overlay
+    pub fn draw_hud(&self, overlay: &mut Overlay) {
+        const SCALE: f32 = 2.0;
+        const PADDING: f32 = 8.0;
+        const LINE_HEIGHT: f32 = 14.0;
+        const GRAPH_HEIGHT: f32 = 48.0;
+        // frame times above this are clipped in the graph
+        const GRAPH_MAX_TIME: f32 = 50.0;
+
+        let mut lines = vec![
+            format!("FPS: {:.1}", self.fps),
+            format!("FRAME: {:.2} MS", self.frame_time),
+            format!("JITTER: {:.2} MS", self.pacing.jitter),
+            format!("DRAW CALLS: {}", self.draw_calls),
+            format!("OBJECTS: {}", self.object_count),
+            format!("BUFFERS: {}", format_bytes(self.buffer_memory)),
+            format!("TEXTURES: {}", format_bytes(self.texture_memory)),
+            format!("PRE RENDER: {:.2} MS", self.timings.pre_render),
+            format!("UPDATE: {:.2} MS", self.timings.update),
+            format!("OBJECT UPDATE: {:.2} MS", self.timings.object_update),
+            format!("RENDER: {:.2} MS", self.timings.render),
+        ];
+        #[cfg(feature = "allocation_audit")]
+        {
+            let allocations = self.allocations.frame_total();
+            lines.push(format!(
+                "ALLOCATIONS: {} ({})",
+                allocations.allocations,
+                format_bytes(allocations.bytes)
+            ));
+        }
+        lines.extend(
+            self.gpu_timings
+                .iter()
+                .map(|(label, time)| format!("GPU {label}: {time:.2} MS")),
+        );
+
+        let graph_width = FRAME_TIME_HISTORY as f32 * 2.0;
+        let width = lines
+            .iter()
+            .map(|i| Overlay::text_width(i, SCALE))
+            .fold(graph_width, f32::max)
+            + PADDING * 2.0;
+        let height = lines.len() as f32 * LINE_HEIGHT + GRAPH_HEIGHT + PADDING * 3.0;
+
+        overlay.rect(0.0, 0.0, width, height, [0.0, 0.0, 0.0, 0.6]);
+        for (index, line) in lines.iter().enumerate() {
+            overlay.text(
+                PADDING,
+                PADDING + index as f32 * LINE_HEIGHT,
+                SCALE,
+                line,
+                [1.0, 1.0, 1.0, 1.0],
+            );
+        }
+
+        // frame time graph
+        let graph_top = height - PADDING - GRAPH_HEIGHT;
+        overlay.rect(
+            PADDING,
+            graph_top,
+            graph_width,
+            GRAPH_HEIGHT,
+            [1.0, 1.0, 1.0, 0.1],
+        );
+        for (index, frame_time) in self.frame_time_history.iter().enumerate() {
+            let bar_height = (frame_time / GRAPH_MAX_TIME).min(1.0) * GRAPH_HEIGHT;
+            let color = if *frame_time <= 1000.0 / 60.0 {
+                [0.2, 0.9, 0.2, 1.0]
+            } else if *frame_time <= 1000.0 / 30.0 {
+                [0.9, 0.9, 0.2, 1.0]
+            } else {
+                [0.9, 0.2, 0.2, 1.0]
+            };
+
+            overlay.rect(
+                PADDING + index as f32 * 2.0,
+                graph_top + GRAPH_HEIGHT - bar_height,
+                2.0,
+                bar_height,
+                color,
+            );
+        }
+    }
+}
+impl Default for RenderStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Formats bytes into a human readable form
+fn format_bytes(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.2} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{:.2} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{bytes} B")
+    }
+}
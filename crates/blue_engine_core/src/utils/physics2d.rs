@@ -0,0 +1,720 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+pub use rapier2d;
+use rapier2d::prelude::{
+    ActiveEvents, ColliderBuilder, ColliderHandle, ColliderSet, ContactPair, PhysicsWorld,
+    RigidBodyBuilder, RigidBodyHandle, RigidBodySet, Rotation, SoftBodySet, SoftBodyTearEvent,
+};
+
+use crate::{
+    ObjectStorage, StringBuffer, Vector2,
+    utils::{
+        fixed_timestep::FixedTimestep,
+        plugin::{EnginePlugin, PluginContext},
+    },
+};
+
+/// The default amount of physics steps per second
+pub const DEFAULT_PHYSICS_RATE: f32 = 60.0;
+
+/// Whether a collision started or stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionKind {
+    /// The bodies started touching in this step
+    Started,
+    /// The bodies stopped touching in this step
+    Stopped,
+}
+
+/// A collision of the object, given to its callbacks
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollisionEvent {
+    /// The object the callback is for
+    pub object: Arc<str>,
+    /// The object it collided with
+    pub other: Arc<str>,
+    /// Whether the collision started or stopped
+    pub kind: CollisionKind,
+    /// The direction from the object towards the other, along the deepest contact, or between
+    /// their centers for sensors. Zero when stopped
+    pub normal: Vector2,
+}
+
+/// The callback of the collisions of an object
+pub type CollisionCallback = Box<dyn FnMut(&CollisionEvent)>;
+
+/// The body of an object and the collider that gives it its shape
+#[derive(Debug, Clone, Copy)]
+struct Link {
+    body: RigidBodyHandle,
+    collider: ColliderHandle,
+}
+
+/// The collisions that started and stopped in a step, with the direction from the first
+/// collider towards the second
+#[derive(Default)]
+struct CollisionQueue(Mutex<Vec<(ColliderHandle, ColliderHandle, CollisionKind, Vector2)>>);
+
+impl rapier2d::pipeline::EventHandler for CollisionQueue {
+    fn handle_collision_event(
+        &self,
+        _bodies: &RigidBodySet,
+        colliders: &ColliderSet,
+        event: rapier2d::prelude::CollisionEvent,
+        contact_pair: Option<&ContactPair>,
+    ) {
+        let (first, second) = (event.collider1(), event.collider2());
+        let normal = match event.started() {
+            true => contact_normal(contact_pair, first)
+                .or_else(|| {
+                    let offset =
+                        colliders.get(second)?.translation() - colliders.get(first)?.translation();
+                    Some(from_rapier(offset).normalize_or_zero())
+                })
+                .unwrap_or(Vector2::ZERO),
+            false => Vector2::ZERO,
+        };
+        let kind = match event.started() {
+            true => CollisionKind::Started,
+            false => CollisionKind::Stopped,
+        };
+
+        if let Ok(mut queue) = self.0.lock() {
+            queue.push((first, second, kind, normal));
+        }
+    }
+
+    fn handle_contact_force_event(
+        &self,
+        _dt: f32,
+        _bodies: &RigidBodySet,
+        _colliders: &ColliderSet,
+        _contact_pair: &ContactPair,
+        _total_force_magnitude: f32,
+    ) {
+    }
+
+    fn handle_soft_body_tear_event(&self, _soft_bodies: &SoftBodySet, _event: &SoftBodyTearEvent) {}
+}
+
+/// The normal of the deepest contact of the pair, pointing away from the collider
+fn contact_normal(contact_pair: Option<&ContactPair>, from: ColliderHandle) -> Option<Vector2> {
+    let contact_pair = contact_pair?;
+    let (manifold, _) = contact_pair.find_deepest_contact()?;
+    let normal = from_rapier(manifold.data.normal);
+
+    Some(match contact_pair.collider1 == from {
+        true => normal,
+        false => -normal,
+    })
+}
+
+/// A 2D physics simulation of the objects on X and Y, run by [rapier2d]. The bodies are
+/// linked to the objects by name, and their positions and rotations around Z are written to
+/// the objects after every step, keeping the Z of the objects.
+///
+/// The bodies and colliders are described with the builders of rapier, and the
+/// [PhysicsWorld] is kept in [Physics2D::world] for what is not wrapped here, such as joints.
+/// The simulation steps at a fixed rate when added as a plugin, or by calling
+/// [Physics2D::step] in [crate::Engine::update_loop_fixed]:
+///
+/// ```ignore
+/// use blue_engine::utils::physics2d::rapier2d::prelude::{ColliderBuilder, RigidBodyBuilder};
+///
+/// let mut physics = Physics2D::new();
+/// physics.add_body("ball", RigidBodyBuilder::dynamic(), ColliderBuilder::ball(0.5), &engine.objects);
+/// physics.add_body("ground", RigidBodyBuilder::fixed(), ColliderBuilder::cuboid(5.0, 0.5), &engine.objects);
+/// physics.on_collision("ball", |event| println!("{} hit {}", event.object, event.other));
+///
+/// engine.add_plugin("physics", physics);
+/// ```
+pub struct Physics2D {
+    /// The acceleration of the dynamic bodies
+    pub gravity: Vector2,
+    /// The rate the simulation steps at when it is a plugin
+    pub timestep: FixedTimestep,
+    /// The bodies, colliders, and joints of the simulation
+    pub world: PhysicsWorld,
+    links: HashMap<Arc<str>, Link>,
+    objects: HashMap<ColliderHandle, Arc<str>>,
+    callbacks: HashMap<Arc<str>, Vec<CollisionCallback>>,
+    collisions: CollisionQueue,
+}
+
+impl std::fmt::Debug for Physics2D {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Physics2D")
+            .field("gravity", &self.gravity)
+            .field("bodies", &self.links.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Physics2D {
+    /// Creates an empty simulation with the gravity of the earth pulling down on Y
+    pub fn new() -> Self {
+        Self {
+            gravity: Vector2::new(0.0, -9.81),
+            timestep: FixedTimestep::new(DEFAULT_PHYSICS_RATE),
+            world: PhysicsWorld::new(),
+            links: HashMap::new(),
+            objects: HashMap::new(),
+            callbacks: HashMap::new(),
+            collisions: CollisionQueue::default(),
+        }
+    }
+
+    /// Adds a body with the collider for the object, starting at its position and its rotation
+    /// around Z. A body the object had before is replaced. The collisions of the collider are
+    /// given to the callbacks of [Physics2D::on_collision]
+    pub fn add_body(
+        &mut self,
+        object: impl StringBuffer,
+        body: RigidBodyBuilder,
+        collider: ColliderBuilder,
+        objects: &ObjectStorage,
+    ) -> RigidBodyHandle {
+        let mut body = body.build();
+        if let Some(linked) = objects.get(object.as_str()) {
+            body.set_translation(to_rapier(linked.position.truncate()), false);
+            body.set_rotation(Rotation::new(linked.rotation.z), false);
+        }
+        let mut collider = collider.build();
+        collider.set_active_events(collider.active_events() | ActiveEvents::COLLISION_EVENTS);
+
+        self.remove_body(object.as_str());
+        let (body, collider) = self.world.insert(body, collider);
+        let name = object.as_arc();
+        self.objects.insert(collider, name.clone());
+        self.links.insert(name, Link { body, collider });
+
+        body
+    }
+
+    /// Removes the body of the object with its collider, and returns it
+    pub fn remove_body(&mut self, object: &str) -> Option<rapier2d::prelude::RigidBody> {
+        let link = self.links.remove(object)?;
+        self.objects.remove(&link.collider);
+
+        self.world.remove_body(link.body)
+    }
+
+    /// The body of the object
+    pub fn body(&self, object: &str) -> Option<&rapier2d::prelude::RigidBody> {
+        self.world.bodies.get(self.links.get(object)?.body)
+    }
+
+    /// The body of the object, to be changed
+    pub fn body_mut(&mut self, object: &str) -> Option<&mut rapier2d::prelude::RigidBody> {
+        self.world.bodies.get_mut(self.links.get(object)?.body)
+    }
+
+    /// The collider of the body of the object
+    pub fn collider(&self, object: &str) -> Option<&rapier2d::prelude::Collider> {
+        self.world.colliders.get(self.links.get(object)?.collider)
+    }
+
+    /// The collider of the body of the object, to be changed
+    pub fn collider_mut(&mut self, object: &str) -> Option<&mut rapier2d::prelude::Collider> {
+        self.world
+            .colliders
+            .get_mut(self.links.get(object)?.collider)
+    }
+
+    /// The object the collider is the shape of
+    pub fn object_of(&self, collider: ColliderHandle) -> Option<&Arc<str>> {
+        self.objects.get(&collider)
+    }
+
+    /// The amount of bodies
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    /// Returns true if there are no bodies
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// Returns true if the bodies of the objects are touching, or overlapping when one of them
+    /// is a sensor
+    pub fn is_touching(&self, object: &str, other: &str) -> bool {
+        let (Some(first), Some(second)) = (self.links.get(object), self.links.get(other)) else {
+            return false;
+        };
+
+        self.world
+            .intersection_pair(first.collider, second.collider)
+            == Some(true)
+            || self
+                .world
+                .contact_pair(first.collider, second.collider)
+                .is_some_and(|i| i.has_any_active_contact())
+    }
+
+    /// Adds a callback for the collisions of the object that start and stop
+    pub fn on_collision(
+        &mut self,
+        object: impl StringBuffer,
+        callback: impl FnMut(&CollisionEvent) + 'static,
+    ) {
+        self.callbacks
+            .entry(object.as_arc())
+            .or_default()
+            .push(Box::new(callback));
+    }
+
+    /// Removes the collision callbacks of the object
+    pub fn clear_callbacks(&mut self, object: &str) {
+        self.callbacks.remove(object);
+    }
+
+    /// Moves the simulation forward by the time in seconds, and writes the positions and
+    /// rotations of the bodies to their objects. The bodies of the objects that were removed
+    /// are removed too
+    pub fn step(&mut self, delta_time: f32, objects: &mut ObjectStorage) {
+        let removed = self
+            .links
+            .keys()
+            .filter(|i| !objects.contains_key(i.as_ref()))
+            .cloned()
+            .collect::<Vec<_>>();
+        for name in removed {
+            self.remove_body(name.as_ref());
+        }
+
+        self.world.gravity = to_rapier(self.gravity);
+        self.world.integration_parameters.dt = delta_time;
+        self.world.step_with_events(&(), &self.collisions);
+
+        for (name, link) in self.links.iter() {
+            let Some(body) = self.world.bodies.get(link.body) else {
+                continue;
+            };
+            if body.is_fixed() || body.is_sleeping() {
+                continue;
+            }
+            if let Some(object) = objects.get_mut(name.as_ref()) {
+                let translation = body.translation();
+                object.set_position((translation.x, translation.y, object.position.z));
+                object.set_rotation((
+                    object.rotation.x,
+                    object.rotation.y,
+                    body.rotation().angle(),
+                ));
+            }
+        }
+        self.report();
+    }
+
+    /// Calls the callbacks of the collisions that started and stopped in the step
+    fn report(&mut self) {
+        let collisions = match self.collisions.0.get_mut() {
+            Ok(collisions) => std::mem::take(collisions),
+            Err(_) => return,
+        };
+        if self.callbacks.is_empty() {
+            return;
+        }
+
+        for (first, second, kind, normal) in collisions {
+            // the colliders of the bodies removed since are no longer linked to objects
+            let (Some(first), Some(second)) = (self.objects.get(&first), self.objects.get(&second))
+            else {
+                continue;
+            };
+            for (object, other, normal) in [(first, second, normal), (second, first, -normal)] {
+                let Some(callbacks) = self.callbacks.get_mut(object) else {
+                    continue;
+                };
+                let event = CollisionEvent {
+                    object: object.clone(),
+                    other: other.clone(),
+                    kind,
+                    normal,
+                };
+                callbacks.iter_mut().for_each(|i| i(&event));
+            }
+        }
+    }
+}
+impl Default for Physics2D {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EnginePlugin for Physics2D {
+    fn update(&mut self, ctx: &mut PluginContext) {
+        let tick_length = self.timestep.tick_length();
+        for _ in 0..self.timestep.advance() {
+            self.step(tick_length, ctx.objects);
+        }
+    }
+}
+
+/// The vector of the engine as the one of rapier, which is of another version of glam
+fn to_rapier(vector: impl Into<Vector2>) -> rapier2d::math::Vector {
+    let vector = vector.into();
+    rapier2d::math::Vector::new(vector.x, vector.y)
+}
+
+/// The vector of rapier as the one of the engine
+fn from_rapier(vector: rapier2d::math::Vector) -> Vector2 {
+    Vector2::new(vector.x, vector.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Object, ObjectSettings, Renderer, primitive_shapes::triangle_data};
+    use rapier2d::prelude::CoefficientCombineRule;
+    use std::{cell::RefCell, rc::Rc};
+
+    const TICK: f32 = 1.0 / DEFAULT_PHYSICS_RATE;
+
+    /// The objects of the names, at the positions
+    fn objects(renderer: &mut Renderer, placed: &[(&str, [f32; 3])]) -> ObjectStorage {
+        let mut objects = ObjectStorage::new();
+        for (name, position) in placed {
+            let (vertices, indices) = triangle_data();
+            let mut object = Object::new(
+                *name,
+                vertices,
+                indices,
+                ObjectSettings::default(),
+                renderer,
+            )
+            .expect("the triangle is built");
+            object.set_position(*position);
+            objects.add(*name, object);
+        }
+        objects
+    }
+
+    fn ground(physics: &mut Physics2D, objects: &ObjectStorage) {
+        physics.add_body(
+            "ground",
+            RigidBodyBuilder::fixed(),
+            ColliderBuilder::cuboid(10.0, 0.5),
+            objects,
+        );
+    }
+
+    #[test]
+    fn bodies_are_integrated_at_the_fixed_step() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = objects(
+            &mut renderer,
+            &[
+                ("ball", [0.0, 0.0, 3.0]),
+                ("platform", [10.0, 0.0, 0.0]),
+                ("wall", [-10.0, 0.0, 0.0]),
+            ],
+        );
+        let mut physics = Physics2D::new();
+        physics.add_body(
+            "ball",
+            RigidBodyBuilder::dynamic(),
+            ColliderBuilder::ball(0.5),
+            &objects,
+        );
+        physics.add_body(
+            "platform",
+            RigidBodyBuilder::kinematic_velocity_based().linvel(to_rapier([0.0, 2.0])),
+            ColliderBuilder::ball(0.5),
+            &objects,
+        );
+        physics.add_body(
+            "wall",
+            RigidBodyBuilder::fixed(),
+            ColliderBuilder::ball(0.5),
+            &objects,
+        );
+        assert_eq!(physics.len(), 3);
+
+        for _ in 0..60 {
+            physics.step(TICK, &mut objects);
+        }
+
+        // the bodies start at the positions of their objects, and fall about half of the
+        // gravity in a second
+        let ball = physics.body("ball").unwrap();
+        assert!((ball.linvel().y - physics.gravity.y).abs() < 1e-3);
+        assert!(
+            (ball.translation().y + 4.9).abs() < 0.1,
+            "{}",
+            ball.translation().y
+        );
+        let object = objects.get("ball").unwrap();
+        assert!((object.position.y - ball.translation().y).abs() < 1e-6);
+        assert_eq!(object.position.x, 0.0);
+        assert_eq!(object.position.z, 3.0);
+
+        // the gravity does not pull the kinematic and fixed bodies
+        let platform = objects.get("platform").unwrap();
+        assert!((platform.position.y - 2.0).abs() < 1e-3);
+        assert_eq!(platform.position.x, 10.0);
+        assert_eq!(
+            from_rapier(physics.body("wall").unwrap().translation()),
+            Vector2::new(-10.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn rotations_are_written_around_z() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = objects(&mut renderer, &[("wheel", [0.0, 0.0, 0.0])]);
+        objects
+            .get_mut("wheel")
+            .unwrap()
+            .set_rotation((0.5, 0.0, 0.25));
+        let mut physics = Physics2D::new();
+        physics.gravity = Vector2::ZERO;
+        physics.add_body(
+            "wheel",
+            RigidBodyBuilder::dynamic().angvel(1.0),
+            ColliderBuilder::ball(0.5),
+            &objects,
+        );
+
+        for _ in 0..30 {
+            physics.step(TICK, &mut objects);
+        }
+        let angle = physics.body("wheel").unwrap().rotation().angle();
+        assert!((angle - 0.75).abs() < 1e-3, "{angle}");
+        let wheel = objects.get("wheel").unwrap();
+        assert!((wheel.rotation.z - angle).abs() < 1e-6);
+        assert_eq!(wheel.rotation.x, 0.5);
+    }
+
+    #[test]
+    fn a_falling_body_comes_to_rest_on_the_ground() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = objects(
+            &mut renderer,
+            &[("ball", [0.0, 3.0, 0.0]), ("ground", [0.0, 0.0, 0.0])],
+        );
+        let mut physics = Physics2D::new();
+        physics.add_body(
+            "ball",
+            RigidBodyBuilder::dynamic(),
+            ColliderBuilder::ball(0.5),
+            &objects,
+        );
+        ground(&mut physics, &objects);
+
+        for _ in 0..180 {
+            physics.step(TICK, &mut objects);
+        }
+        let ball = physics.body("ball").unwrap();
+        // the top of the ground is at 0.5
+        assert!(
+            (ball.translation().y - 1.0).abs() < 0.02,
+            "{}",
+            ball.translation().y
+        );
+        assert!(ball.linvel().length() < 0.2);
+        assert!(physics.is_touching("ground", "ball"));
+    }
+
+    #[test]
+    fn bouncy_bodies_bounce_back_up() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = objects(
+            &mut renderer,
+            &[
+                ("bouncy", [-2.0, 3.0, 0.0]),
+                ("dull", [2.0, 3.0, 0.0]),
+                ("ground", [0.0, 0.0, 0.0]),
+            ],
+        );
+        let mut physics = Physics2D::new();
+        physics.add_body(
+            "bouncy",
+            RigidBodyBuilder::dynamic(),
+            ColliderBuilder::ball(0.5)
+                .restitution(1.0)
+                .restitution_combine_rule(CoefficientCombineRule::Max),
+            &objects,
+        );
+        physics.add_body(
+            "dull",
+            RigidBodyBuilder::dynamic(),
+            ColliderBuilder::ball(0.5),
+            &objects,
+        );
+        ground(&mut physics, &objects);
+
+        // the bodies fall 1.5 units in about 0.55 seconds
+        let mut highest_after_bounce = f32::MIN;
+        let mut bounced = false;
+        for _ in 0..90 {
+            physics.step(TICK, &mut objects);
+            let bouncy = physics.body("bouncy").unwrap();
+            bounced |= bouncy.linvel().y > 0.0;
+            if bounced {
+                highest_after_bounce = highest_after_bounce.max(bouncy.translation().y);
+            }
+        }
+        assert!(bounced);
+        assert!(highest_after_bounce > 2.5, "{highest_after_bounce}");
+        assert!(physics.body("dull").unwrap().translation().y < 1.1);
+    }
+
+    #[test]
+    fn colliding_bodies_keep_their_momentum() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = objects(
+            &mut renderer,
+            &[("heavy", [-1.0, 0.0, 0.0]), ("light", [1.0, 0.0, 0.0])],
+        );
+        let mut physics = Physics2D::new();
+        physics.gravity = Vector2::ZERO;
+        physics.add_body(
+            "heavy",
+            RigidBodyBuilder::dynamic().linvel(to_rapier([2.0, 0.0])),
+            ColliderBuilder::ball(0.5).mass(3.0),
+            &objects,
+        );
+        physics.add_body(
+            "light",
+            RigidBodyBuilder::dynamic().linvel(to_rapier([-2.0, 0.0])),
+            ColliderBuilder::ball(0.5).mass(1.0),
+            &objects,
+        );
+
+        for _ in 0..60 {
+            physics.step(TICK, &mut objects);
+        }
+        let (heavy, light) = (
+            physics.body("heavy").unwrap(),
+            physics.body("light").unwrap(),
+        );
+        let momentum = heavy.linvel() * heavy.mass() + light.linvel() * light.mass();
+        assert!(from_rapier(momentum).abs_diff_eq(Vector2::new(4.0, 0.0), 1e-2));
+        // without restitution the bodies move on together
+        assert!((heavy.linvel().x - 1.0).abs() < 1e-2);
+        assert!((light.linvel().x - 1.0).abs() < 1e-2);
+        assert!(light.translation().x - heavy.translation().x > 0.95);
+    }
+
+    #[test]
+    fn collision_events_start_and_stop() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = objects(
+            &mut renderer,
+            &[("player", [-3.0, 0.0, 0.0]), ("trigger", [0.0, 0.0, 0.0])],
+        );
+        let mut physics = Physics2D::new();
+        physics.gravity = Vector2::ZERO;
+        physics.add_body(
+            "player",
+            RigidBodyBuilder::dynamic().linvel(to_rapier([3.0, 0.0])),
+            ColliderBuilder::cuboid(0.5, 0.5),
+            &objects,
+        );
+        physics.add_body(
+            "trigger",
+            RigidBodyBuilder::fixed(),
+            ColliderBuilder::ball(1.0).sensor(true),
+            &objects,
+        );
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        for name in ["player", "trigger"] {
+            let events = events.clone();
+            physics.on_collision(name, move |event| events.borrow_mut().push(event.clone()));
+        }
+
+        let mut touched = false;
+        for _ in 0..120 {
+            physics.step(TICK, &mut objects);
+            touched |= physics.is_touching("player", "trigger");
+        }
+        assert!(touched);
+        assert!(!physics.is_touching("player", "trigger"));
+        // the sensor lets the body pass through without slowing it
+        let player = physics.body("player").unwrap();
+        assert!(from_rapier(player.linvel()).abs_diff_eq(Vector2::new(3.0, 0.0), 1e-4));
+        assert!(player.translation().x > 2.0);
+
+        let events = events.borrow();
+        let mut kinds = events
+            .iter()
+            .map(|i| {
+                (
+                    i.kind == CollisionKind::Stopped,
+                    i.object.as_ref(),
+                    i.other.as_ref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        kinds.sort();
+        assert_eq!(
+            kinds,
+            [
+                (false, "player", "trigger"),
+                (false, "trigger", "player"),
+                (true, "player", "trigger"),
+                (true, "trigger", "player"),
+            ]
+        );
+        for event in events.iter() {
+            let expected = match (event.kind, event.object.as_ref()) {
+                (CollisionKind::Stopped, _) => Vector2::ZERO,
+                (_, "player") => Vector2::X,
+                _ => Vector2::NEG_X,
+            };
+            assert!(event.normal.abs_diff_eq(expected, 1e-3), "{event:?}");
+        }
+    }
+
+    #[test]
+    fn bodies_of_removed_objects_are_dropped() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = objects(
+            &mut renderer,
+            &[("ball", [0.0, 1.0, 0.0]), ("ground", [0.0, 0.0, 0.0])],
+        );
+        let mut physics = Physics2D::new();
+        let ball = physics.add_body(
+            "ball",
+            RigidBodyBuilder::dynamic(),
+            ColliderBuilder::ball(0.5),
+            &objects,
+        );
+        ground(&mut physics, &objects);
+        physics.step(TICK, &mut objects);
+        assert!(physics.is_touching("ball", "ground"));
+
+        objects.remove("ball");
+        physics.step(TICK, &mut objects);
+        assert_eq!(physics.len(), 1);
+        assert!(physics.body("ball").is_none());
+        assert!(physics.world.bodies.get(ball).is_none());
+        assert_eq!(physics.world.colliders.len(), 1);
+        assert!(!physics.is_touching("ball", "ground"));
+    }
+}
@@ -0,0 +1,775 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub use rapier3d;
+use rapier3d::{
+    geometry::TriMeshBuilderError,
+    prelude::{
+        ColliderBuilder, ColliderHandle, PhysicsWorld, QueryFilter, Ray, RigidBodyBuilder,
+        RigidBodyHandle, Rotation,
+    },
+};
+
+use crate::{
+    Object, ObjectStorage, Quaternion, StringBuffer, Transform, Vector3,
+    utils::{
+        fixed_timestep::FixedTimestep,
+        plugin::{EnginePlugin, PluginContext},
+    },
+};
+
+/// The default amount of physics steps per second
+pub const DEFAULT_PHYSICS_RATE: f32 = 60.0;
+
+/// The convex collider around the vertices of the object, scaled by its size. None if rapier
+/// can not make a hull of them, such as when there are too few
+pub fn convex_hull(object: &Object) -> Option<ColliderBuilder> {
+    let (vertices, _) = geometry_of(object);
+    let points = vertices
+        .iter()
+        .map(|i| to_rapier(Vector3::from(i.position) * object.size))
+        .collect::<Vec<_>>();
+
+    ColliderBuilder::convex_hull(&points)
+}
+
+/// The collider of the triangles of the object, scaled by its size. Two triangle meshes do
+/// not collide with each other, so they are best for the fixed bodies such as a level
+pub fn trimesh(object: &Object) -> Result<ColliderBuilder, TriMeshBuilderError> {
+    let (vertices, indices) = geometry_of(object);
+    let vertices = vertices
+        .iter()
+        .map(|i| to_rapier(Vector3::from(i.position) * object.size))
+        .collect();
+    let triangles = indices
+        .chunks_exact(3)
+        .map(|i| [i[0] as u32, i[1] as u32, i[2] as u32])
+        .collect();
+
+    ColliderBuilder::trimesh(vertices, triangles)
+}
+
+/// The vertices and indices the object is drawn with
+fn geometry_of(object: &Object) -> (&[crate::Vertex], &[crate::UnsignedIntType]) {
+    match object.mesh.as_ref() {
+        Some(mesh) => (mesh.vertices.as_slice(), mesh.indices.as_slice()),
+        None => (object.vertices.as_slice(), object.indices.as_slice()),
+    }
+}
+
+/// The closest hit of a ray, see [Physics3D::raycast]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RayHit {
+    /// The object that was hit
+    pub object: Arc<str>,
+    /// How far along the ray the hit is
+    pub distance: f32,
+    /// Where the ray hit
+    pub point: Vector3,
+    /// The direction the surface faces where it was hit
+    pub normal: Vector3,
+}
+
+/// The body of an object and the collider that gives it its shape
+#[derive(Debug, Clone, Copy)]
+struct Link {
+    body: RigidBodyHandle,
+    collider: ColliderHandle,
+}
+
+/// A 3D physics simulation of the objects, run by [rapier3d], with convex hulls and triangle
+/// meshes made from the vertices of the objects by [convex_hull] and [trimesh]. The bodies
+/// are linked to the objects by name, and their positions and rotations are written to the
+/// objects after every step.
+///
+/// The bodies and colliders are described with the builders of rapier, and the
+/// [PhysicsWorld] is kept in [Physics3D::world] for what is not wrapped here, such as joints.
+/// The simulation steps at a fixed rate when added as a plugin, or by calling
+/// [Physics3D::step] in [crate::Engine::update_loop_fixed]:
+///
+/// ```ignore
+/// use blue_engine::utils::physics3d::rapier3d::prelude::RigidBodyBuilder;
+///
+/// let mut physics = Physics3D::new();
+/// let level = trimesh(engine.objects.get("level").unwrap()).unwrap();
+/// physics.add_body("level", RigidBodyBuilder::fixed(), level, &engine.objects);
+/// let crate_collider = convex_hull(engine.objects.get("crate").unwrap()).unwrap();
+/// physics.add_body("crate", RigidBodyBuilder::dynamic(), crate_collider, &engine.objects);
+///
+/// if let Some(hit) = physics.raycast((0.0, 10.0, 0.0), (0.0, -1.0, 0.0), 100.0) {
+///     println!("the ground is {} below", hit.distance);
+/// }
+/// engine.add_plugin("physics", physics);
+/// ```
+pub struct Physics3D {
+    /// The acceleration of the dynamic bodies
+    pub gravity: Vector3,
+    /// The rate the simulation steps at when it is a plugin
+    pub timestep: FixedTimestep,
+    /// The bodies, colliders, and joints of the simulation
+    pub world: PhysicsWorld,
+    links: HashMap<Arc<str>, Link>,
+    objects: HashMap<ColliderHandle, Arc<str>>,
+}
+
+impl std::fmt::Debug for Physics3D {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Physics3D")
+            .field("gravity", &self.gravity)
+            .field("bodies", &self.links.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Physics3D {
+    /// Creates an empty simulation with the gravity of the earth pulling down on Y
+    pub fn new() -> Self {
+        Self {
+            gravity: Vector3::new(0.0, -9.81, 0.0),
+            timestep: FixedTimestep::new(DEFAULT_PHYSICS_RATE),
+            world: PhysicsWorld::new(),
+            links: HashMap::new(),
+            objects: HashMap::new(),
+        }
+    }
+
+    /// Adds a body with the collider for the object, starting at its position and rotation.
+    /// A body the object had before is replaced
+    pub fn add_body(
+        &mut self,
+        object: impl StringBuffer,
+        body: RigidBodyBuilder,
+        collider: ColliderBuilder,
+        objects: &ObjectStorage,
+    ) -> RigidBodyHandle {
+        let mut body = body.build();
+        if let Some(linked) = objects.get(object.as_str()) {
+            body.set_translation(to_rapier(linked.position), false);
+            body.set_rotation(to_rapier_rotation(linked.rotation_quaternion), false);
+        }
+
+        self.remove_body(object.as_str());
+        let (body, collider) = self.world.insert(body, collider);
+        let name = object.as_arc();
+        self.objects.insert(collider, name.clone());
+        self.links.insert(name, Link { body, collider });
+
+        body
+    }
+
+    /// Removes the body of the object with its collider, and returns it
+    pub fn remove_body(&mut self, object: &str) -> Option<rapier3d::prelude::RigidBody> {
+        let link = self.links.remove(object)?;
+        self.objects.remove(&link.collider);
+
+        self.world.remove_body(link.body)
+    }
+
+    /// The body of the object
+    pub fn body(&self, object: &str) -> Option<&rapier3d::prelude::RigidBody> {
+        self.world.bodies.get(self.links.get(object)?.body)
+    }
+
+    /// The body of the object, to be changed
+    pub fn body_mut(&mut self, object: &str) -> Option<&mut rapier3d::prelude::RigidBody> {
+        self.world.bodies.get_mut(self.links.get(object)?.body)
+    }
+
+    /// The collider of the body of the object
+    pub fn collider(&self, object: &str) -> Option<&rapier3d::prelude::Collider> {
+        self.world.colliders.get(self.links.get(object)?.collider)
+    }
+
+    /// The collider of the body of the object, to be changed
+    pub fn collider_mut(&mut self, object: &str) -> Option<&mut rapier3d::prelude::Collider> {
+        self.world
+            .colliders
+            .get_mut(self.links.get(object)?.collider)
+    }
+
+    /// The object the collider is the shape of
+    pub fn object_of(&self, collider: ColliderHandle) -> Option<&Arc<str>> {
+        self.objects.get(&collider)
+    }
+
+    /// The amount of bodies
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    /// Returns true if there are no bodies
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// The closest body the ray hits within the distance, such as for picking objects with
+    /// the mouse or checking the ground below. The bodies are found where they were at the
+    /// last step, so the ones added or moved by hand since are found after the next step, or
+    /// after [PhysicsWorld::detect_collisions]
+    pub fn raycast(
+        &self,
+        origin: impl Into<Vector3>,
+        direction: impl Into<Vector3>,
+        max_distance: f32,
+    ) -> Option<RayHit> {
+        let origin = origin.into();
+        let direction = direction.into().normalize_or_zero();
+        if direction == Vector3::ZERO {
+            return None;
+        }
+
+        let linked =
+            |collider, _: &rapier3d::prelude::Collider| self.objects.contains_key(&collider);
+        let (collider, hit) = self.world.cast_ray_and_get_normal(
+            &Ray::new(to_rapier(origin), to_rapier(direction)),
+            max_distance,
+            true,
+            QueryFilter::default().predicate(&linked),
+        )?;
+
+        Some(RayHit {
+            object: self.objects.get(&collider)?.clone(),
+            distance: hit.time_of_impact,
+            point: origin + direction * hit.time_of_impact,
+            normal: from_rapier(hit.normal),
+        })
+    }
+
+    /// Moves the simulation forward by the time in seconds, and writes the positions and
+    /// rotations of the bodies to their objects. The bodies of the objects that were removed
+    /// are removed too
+    pub fn step(&mut self, delta_time: f32, objects: &mut ObjectStorage) {
+        let removed = self
+            .links
+            .keys()
+            .filter(|i| !objects.contains_key(i.as_ref()))
+            .cloned()
+            .collect::<Vec<_>>();
+        for name in removed {
+            self.remove_body(name.as_ref());
+        }
+
+        self.world.gravity = to_rapier(self.gravity);
+        self.world.integration_parameters.dt = delta_time;
+        self.world.step();
+
+        for (name, link) in self.links.iter() {
+            let Some(body) = self.world.bodies.get(link.body) else {
+                continue;
+            };
+            if body.is_fixed() || body.is_sleeping() {
+                continue;
+            }
+            if let Some(object) = objects.get_mut(name.as_ref()) {
+                let scale = object.size;
+                object.set_transform(Transform {
+                    position: from_rapier(body.translation()),
+                    rotation: from_rapier_rotation(*body.rotation()),
+                    scale,
+                });
+            }
+        }
+    }
+}
+impl Default for Physics3D {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EnginePlugin for Physics3D {
+    fn update(&mut self, ctx: &mut PluginContext) {
+        let tick_length = self.timestep.tick_length();
+        for _ in 0..self.timestep.advance() {
+            self.step(tick_length, ctx.objects);
+        }
+    }
+}
+
+/// The vector of the engine as the one of rapier, which is of another version of glam
+fn to_rapier(vector: impl Into<Vector3>) -> rapier3d::math::Vector {
+    let vector = vector.into();
+    rapier3d::math::Vector::new(vector.x, vector.y, vector.z)
+}
+
+/// The vector of rapier as the one of the engine
+fn from_rapier(vector: rapier3d::math::Vector) -> Vector3 {
+    Vector3::new(vector.x, vector.y, vector.z)
+}
+
+/// The rotation of the engine as the one of rapier
+fn to_rapier_rotation(rotation: Quaternion) -> Rotation {
+    Rotation::from_xyzw(rotation.x, rotation.y, rotation.z, rotation.w)
+}
+
+/// The rotation of rapier as the one of the engine
+fn from_rapier_rotation(rotation: Rotation) -> Quaternion {
+    Quaternion::from_xyzw(rotation.x, rotation.y, rotation.z, rotation.w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ObjectSettings, Renderer,
+        primitive_shapes::{cube_data, triangle_data},
+    };
+    use rapier3d::prelude::CoefficientCombineRule;
+
+    const TICK: f32 = 1.0 / DEFAULT_PHYSICS_RATE;
+
+    /// The objects of the names, at the positions
+    fn objects(renderer: &mut Renderer, placed: &[(&str, [f32; 3])]) -> ObjectStorage {
+        let mut objects = ObjectStorage::new();
+        for (name, position) in placed {
+            let (vertices, indices) = triangle_data();
+            let mut object = Object::new(
+                *name,
+                vertices,
+                indices,
+                ObjectSettings::default(),
+                renderer,
+            )
+            .expect("the triangle is built");
+            object.set_position(*position);
+            objects.add(*name, object);
+        }
+        objects
+    }
+
+    fn ground() -> ColliderBuilder {
+        ColliderBuilder::cuboid(10.0, 0.5, 10.0)
+    }
+
+    /// A square of two triangles on X and Z, facing up
+    fn floor() -> ColliderBuilder {
+        ColliderBuilder::trimesh(
+            [
+                [-5.0, 0.0, -5.0],
+                [5.0, 0.0, -5.0],
+                [5.0, 0.0, 5.0],
+                [-5.0, 0.0, 5.0],
+            ]
+            .map(to_rapier)
+            .to_vec(),
+            vec![[0, 2, 1], [0, 3, 2]],
+        )
+        .expect("the floor is built")
+    }
+
+    /// A fixed body at the position, for the bodies without objects
+    fn fixed_at(position: [f32; 3]) -> RigidBodyBuilder {
+        RigidBodyBuilder::fixed().translation(to_rapier(position))
+    }
+
+    #[test]
+    fn rays_hit_the_closest_body() {
+        let objects = ObjectStorage::new();
+        let mut physics = Physics3D::new();
+        physics.add_body(
+            "near",
+            fixed_at([0.0, 0.0, -5.0]),
+            ColliderBuilder::ball(1.0),
+            &objects,
+        );
+        physics.add_body(
+            "far",
+            fixed_at([0.0, 0.0, -10.0]),
+            ColliderBuilder::cuboid(3.0, 3.0, 1.0),
+            &objects,
+        );
+        // the colliders that are not of an object are not hit
+        physics.world.insert(
+            fixed_at([0.0, 0.0, -2.0]),
+            ColliderBuilder::cuboid(5.0, 5.0, 0.1),
+        );
+        physics.world.detect_collisions(&(), &());
+
+        let hit = physics
+            .raycast(Vector3::ZERO, [0.0, 0.0, -1.0], 100.0)
+            .unwrap();
+        assert_eq!(hit.object.as_ref(), "near");
+        assert!((hit.distance - 4.0).abs() < 1e-3);
+        assert!(hit.point.abs_diff_eq(Vector3::new(0.0, 0.0, -4.0), 1e-3));
+        assert!(hit.normal.abs_diff_eq(Vector3::Z, 1e-3));
+
+        // the direction does not need to be of length one
+        let hit = physics
+            .raycast([0.0, 2.0, 0.0], [0.0, 0.0, -7.0], 100.0)
+            .unwrap();
+        assert_eq!(hit.object.as_ref(), "far");
+        assert!((hit.distance - 9.0).abs() < 1e-3);
+        assert!(hit.normal.abs_diff_eq(Vector3::Z, 1e-3));
+    }
+
+    #[test]
+    fn rays_miss_past_the_bodies_and_the_distance() {
+        let objects = ObjectStorage::new();
+        let mut physics = Physics3D::new();
+        physics.add_body(
+            "ball",
+            fixed_at([0.0, 0.0, -5.0]),
+            ColliderBuilder::ball(1.0),
+            &objects,
+        );
+        physics.world.detect_collisions(&(), &());
+
+        assert!(
+            physics
+                .raycast(Vector3::ZERO, [0.0, 0.0, -1.0], 3.9)
+                .is_none()
+        );
+        assert!(
+            physics
+                .raycast([1.1, 0.0, 0.0], [0.0, 0.0, -1.0], 100.0)
+                .is_none()
+        );
+        assert!(
+            physics
+                .raycast(Vector3::ZERO, [0.0, 0.0, 1.0], 100.0)
+                .is_none()
+        );
+        assert!(
+            physics
+                .raycast(Vector3::ZERO, Vector3::ZERO, 100.0)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn rays_hit_rotated_boxes_hulls_and_meshes() {
+        let objects = ObjectStorage::new();
+        let mut physics = Physics3D::new();
+        physics.add_body(
+            "turned",
+            fixed_at([10.0, 0.0, 0.0]).rotation(to_rapier([0.0, std::f32::consts::FRAC_PI_4, 0.0])),
+            ColliderBuilder::cuboid(1.0, 1.0, 1.0),
+            &objects,
+        );
+        // a pyramid with its tip up
+        let pyramid = [
+            [-1.0, 0.0, -1.0],
+            [1.0, 0.0, -1.0],
+            [1.0, 0.0, 1.0],
+            [-1.0, 0.0, 1.0],
+            [0.0, 2.0, 0.0],
+        ]
+        .map(to_rapier);
+        physics.add_body(
+            "pyramid",
+            fixed_at([0.0, 0.0, 10.0]),
+            ColliderBuilder::convex_hull(&pyramid).unwrap(),
+            &objects,
+        );
+        physics.add_body("floor", fixed_at([0.0, -3.0, 0.0]), floor(), &objects);
+        physics.world.detect_collisions(&(), &());
+
+        // the corner of the turned box reaches out to the square root of two
+        let hit = physics
+            .raycast([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], 100.0)
+            .unwrap();
+        assert_eq!(hit.object.as_ref(), "turned");
+        assert!((hit.distance - (10.0 - std::f32::consts::SQRT_2)).abs() < 1e-2);
+
+        let hit = physics
+            .raycast([0.0, 5.0, 10.0], [0.0, -1.0, 0.0], 100.0)
+            .unwrap();
+        assert_eq!(hit.object.as_ref(), "pyramid");
+        assert!((hit.distance - 3.0).abs() < 1e-2);
+
+        let hit = physics
+            .raycast([2.0, 0.0, 1.0], [0.0, -1.0, 0.0], 100.0)
+            .unwrap();
+        assert_eq!(hit.object.as_ref(), "floor");
+        assert!((hit.distance - 3.0).abs() < 1e-3);
+        assert!(hit.normal.abs_diff_eq(Vector3::Y, 1e-3));
+        // the floor is hit from below too
+        let hit = physics
+            .raycast([2.0, -6.0, 1.0], [0.0, 1.0, 0.0], 100.0)
+            .unwrap();
+        assert!((hit.distance - 3.0).abs() < 1e-3);
+        // and missed past its edge
+        assert!(
+            physics
+                .raycast([6.0, 0.0, 0.0], [0.0, -1.0, 0.0], 100.0)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn colliders_are_made_from_the_vertices_of_the_objects() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let (vertices, indices) = cube_data();
+        let mut cube = Object::new(
+            "cube",
+            vertices,
+            indices,
+            ObjectSettings::default(),
+            &mut renderer,
+        )
+        .expect("the cube is built");
+        cube.set_scale((2.0, 1.0, 1.0));
+        let mut objects = objects(&mut renderer, &[("triangle", [0.0, 0.0, -5.0])]);
+        objects.add("cube", cube);
+        objects
+            .get_mut("cube")
+            .unwrap()
+            .set_position((0.0, 5.0, 0.0));
+
+        let mut physics = Physics3D::new();
+        let hull = convex_hull(objects.get("cube").unwrap()).expect("the cube has a volume");
+        physics.add_body("cube", RigidBodyBuilder::fixed(), hull, &objects);
+        let triangle = objects.get("triangle").unwrap();
+        let mesh = trimesh(triangle).expect("the triangle is a mesh");
+        physics.add_body("triangle", RigidBodyBuilder::fixed(), mesh, &objects);
+        physics.step(TICK, &mut objects);
+
+        // the hull is scaled with the object, and placed at its position
+        let size = objects.get("cube").unwrap().size;
+        let hit = physics
+            .raycast([-10.0, 5.0, 0.0], [1.0, 0.0, 0.0], 100.0)
+            .unwrap();
+        assert_eq!(hit.object.as_ref(), "cube");
+        assert!(
+            (hit.distance - (10.0 - size.x)).abs() < 1e-3,
+            "{}",
+            hit.distance
+        );
+
+        let hit = physics
+            .raycast([0.0, 0.0, 0.0], [0.0, 0.0, -1.0], 100.0)
+            .unwrap();
+        assert_eq!(hit.object.as_ref(), "triangle");
+        assert!((hit.distance - 5.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn boxes_stack_on_the_ground() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = objects(
+            &mut renderer,
+            &[
+                ("ground", [0.0, 0.0, 0.0]),
+                ("bottom", [0.0, 1.2, 0.0]),
+                ("middle", [0.1, 2.4, 0.0]),
+                ("top", [0.0, 3.6, -0.1]),
+            ],
+        );
+        let mut physics = Physics3D::new();
+        physics.add_body("ground", RigidBodyBuilder::fixed(), ground(), &objects);
+        for name in ["bottom", "middle", "top"] {
+            physics.add_body(
+                name,
+                RigidBodyBuilder::dynamic(),
+                ColliderBuilder::cuboid(0.5, 0.5, 0.5),
+                &objects,
+            );
+        }
+
+        for _ in 0..240 {
+            physics.step(TICK, &mut objects);
+        }
+        for (name, height) in [("bottom", 1.0), ("middle", 2.0), ("top", 3.0)] {
+            let body = physics.body(name).unwrap();
+            let position = from_rapier(body.translation());
+            assert!(
+                (position.y - height).abs() < 0.05,
+                "{name} is at {position}"
+            );
+            assert!(
+                body.linvel().length() < 0.2,
+                "{name} moves at {}",
+                body.linvel()
+            );
+            assert!(
+                objects
+                    .get(name)
+                    .unwrap()
+                    .position
+                    .abs_diff_eq(position, 1e-6)
+            );
+        }
+        // the boxes do not slide off each other
+        assert!((physics.body("middle").unwrap().translation().x - 0.1).abs() < 0.05);
+        assert!((physics.body("top").unwrap().translation().z + 0.1).abs() < 0.05);
+    }
+
+    #[test]
+    fn rotations_are_written_to_the_objects() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = objects(&mut renderer, &[("spinner", [1.0, 2.0, 3.0])]);
+        objects
+            .get_mut("spinner")
+            .unwrap()
+            .set_rotation((0.0, 0.0, 0.5));
+        let mut physics = Physics3D::new();
+        physics.gravity = Vector3::ZERO;
+        physics.add_body(
+            "spinner",
+            RigidBodyBuilder::dynamic().angvel(to_rapier([0.0, 1.0, 0.0])),
+            ColliderBuilder::ball(0.5),
+            &objects,
+        );
+        // the body starts with the rotation of the object
+        let start = from_rapier_rotation(*physics.body("spinner").unwrap().rotation());
+        assert!(start.abs_diff_eq(Quaternion::from_rotation_z(0.5), 1e-6));
+
+        for _ in 0..30 {
+            physics.step(TICK, &mut objects);
+        }
+        let expected = Quaternion::from_rotation_y(0.5) * Quaternion::from_rotation_z(0.5);
+        let body = from_rapier_rotation(*physics.body("spinner").unwrap().rotation());
+        assert!(body.abs_diff_eq(expected, 1e-3), "{body}");
+        let spinner = objects.get("spinner").unwrap();
+        assert!(spinner.rotation_quaternion.abs_diff_eq(body, 1e-6));
+        assert!(
+            spinner
+                .position
+                .abs_diff_eq(Vector3::new(1.0, 2.0, 3.0), 1e-4)
+        );
+        assert_eq!(spinner.size, Vector3::ONE);
+    }
+
+    #[test]
+    fn bouncy_bodies_bounce_back_up() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = objects(
+            &mut renderer,
+            &[
+                ("ground", [0.0, 0.0, 0.0]),
+                ("bouncy", [-2.0, 3.0, 0.0]),
+                ("dull", [2.0, 3.0, 0.0]),
+            ],
+        );
+        let mut physics = Physics3D::new();
+        physics.add_body("ground", RigidBodyBuilder::fixed(), ground(), &objects);
+        physics.add_body(
+            "bouncy",
+            RigidBodyBuilder::dynamic(),
+            ColliderBuilder::ball(0.5)
+                .restitution(1.0)
+                .restitution_combine_rule(CoefficientCombineRule::Max),
+            &objects,
+        );
+        physics.add_body(
+            "dull",
+            RigidBodyBuilder::dynamic(),
+            ColliderBuilder::ball(0.5),
+            &objects,
+        );
+
+        let mut highest_after_bounce = f32::MIN;
+        let mut bounced = false;
+        for _ in 0..90 {
+            physics.step(TICK, &mut objects);
+            let bouncy = physics.body("bouncy").unwrap();
+            bounced |= bouncy.linvel().y > 0.0;
+            if bounced {
+                highest_after_bounce = highest_after_bounce.max(bouncy.translation().y);
+            }
+        }
+        assert!(bounced);
+        assert!(highest_after_bounce > 2.5, "{highest_after_bounce}");
+        let dull = physics.body("dull").unwrap().translation();
+        assert!((dull.y - 1.0).abs() < 0.05, "{}", dull.y);
+    }
+
+    #[test]
+    fn bodies_rest_on_a_triangle_mesh() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = objects(
+            &mut renderer,
+            &[
+                ("floor", [0.0, 0.0, 0.0]),
+                ("ball", [1.0, 2.0, 1.0]),
+                ("box", [-1.0, 2.0, -1.0]),
+            ],
+        );
+        let mut physics = Physics3D::new();
+        physics.add_body("floor", RigidBodyBuilder::fixed(), floor(), &objects);
+        physics.add_body(
+            "ball",
+            RigidBodyBuilder::dynamic(),
+            ColliderBuilder::ball(0.5),
+            &objects,
+        );
+        physics.add_body(
+            "box",
+            RigidBodyBuilder::dynamic(),
+            ColliderBuilder::cuboid(0.5, 0.5, 0.5),
+            &objects,
+        );
+
+        for _ in 0..180 {
+            physics.step(TICK, &mut objects);
+        }
+        for name in ["ball", "box"] {
+            let body = physics.body(name).unwrap();
+            let position = from_rapier(body.translation());
+            assert!((position.y - 0.5).abs() < 0.05, "{name} is at {position}");
+            assert!(
+                body.linvel().length() < 0.2,
+                "{name} moves at {}",
+                body.linvel()
+            );
+        }
+    }
+
+    #[test]
+    fn bodies_of_removed_objects_are_dropped() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut objects = objects(
+            &mut renderer,
+            &[("ball", [0.0, 1.0, 0.0]), ("ground", [0.0, 0.0, 0.0])],
+        );
+        let mut physics = Physics3D::new();
+        let ball = physics.add_body(
+            "ball",
+            RigidBodyBuilder::dynamic(),
+            ColliderBuilder::ball(0.5),
+            &objects,
+        );
+        physics.add_body("ground", RigidBodyBuilder::fixed(), ground(), &objects);
+        physics.step(TICK, &mut objects);
+        assert_eq!(
+            physics
+                .raycast([0.0, 5.0, 0.0], [0.0, -1.0, 0.0], 10.0)
+                .unwrap()
+                .object
+                .as_ref(),
+            "ball"
+        );
+
+        objects.remove("ball");
+        physics.step(TICK, &mut objects);
+        assert_eq!(physics.len(), 1);
+        assert!(physics.body("ball").is_none());
+        assert!(physics.world.bodies.get(ball).is_none());
+        assert_eq!(
+            physics
+                .raycast([0.0, 5.0, 0.0], [0.0, -1.0, 0.0], 10.0)
+                .unwrap()
+                .object
+                .as_ref(),
+            "ground"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::HashMap;
+
+use crate::{ShaderSettings, Shaders};
+
+/// Everything a render pipeline is built from
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct PipelineKey {
+    pub(crate) source: String,
+    pub(crate) settings: ShaderSettings,
+    pub(crate) uniform_layout: Option<wgpu::BindGroupLayout>,
+    pub(crate) format: wgpu::TextureFormat,
+}
+
+/// Keeps the render pipelines and uniform bind group layouts built by the renderer, so the
+/// objects that use the same shader and settings share them instead of building their own.
+///
+/// The pipelines are kept until [PipelineCache::clear] is called, which is worth doing after
+/// many shaders that are no longer used were built, such as when leaving a level
+#[derive(Debug, Default)]
+pub struct PipelineCache {
+    pipelines: HashMap<PipelineKey, Shaders>,
+    layouts: HashMap<Vec<wgpu::BindGroupLayoutEntry>, wgpu::BindGroupLayout>,
+    hits: u64,
+}
+
+impl PipelineCache {
+    /// Creates a new empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the pipelines and layouts of the lost device
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.pipelines.clear();
+        self.layouts.clear();
+    }
+
+    /// The amount of render pipelines in the cache
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    /// Returns true if there are no render pipelines in the cache
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    /// The amount of bind group layouts in the cache
+    pub fn layout_count(&self) -> usize {
+        self.layouts.len()
+    }
+
+    /// How many times a pipeline was taken from the cache instead of being built
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Removes all the pipelines and layouts. The objects keep the ones they already have
+    pub fn clear(&mut self) {
+        self.pipelines.clear();
+        self.layouts.clear();
+    }
+
+    // the pipelines and layouts are hashed by their handle, which does not change
+    #[allow(clippy::mutable_key_type)]
+    pub(crate) fn pipeline(&mut self, key: &PipelineKey) -> Option<Shaders> {
+        let pipeline = self.pipelines.get(key).cloned();
+        if pipeline.is_some() {
+            self.hits += 1;
+        }
+
+        pipeline
+    }
+
+    #[allow(clippy::mutable_key_type)]
+    pub(crate) fn insert_pipeline(&mut self, key: PipelineKey, pipeline: Shaders) {
+        self.pipelines.insert(key, pipeline);
+    }
+
+    /// Returns the layout with the entries, building it if it is not in the cache yet
+    pub(crate) fn layout(
+        &mut self,
+        device: &wgpu::Device,
+        entries: &[wgpu::BindGroupLayoutEntry],
+    ) -> wgpu::BindGroupLayout {
+        if let Some(layout) = self.layouts.get(entries) {
+            return layout.clone();
+        }
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("uniform dynamic bind group layout"),
+            entries,
+        });
+        self.layouts.insert(entries.to_vec(), layout.clone());
+
+        layout
+    }
+}
@@ -0,0 +1,165 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use downcast::{Any, downcast};
+
+use crate::{CameraContainer, Engine, ObjectStorage, Renderer, SignalStorage, Window};
+
+/// The engine given to the hooks of an [EnginePlugin]
+pub struct PluginContext<'a> {
+    /// The renderer of the engine
+    pub renderer: &'a mut Renderer,
+    /// The window of the engine
+    pub window: &'a mut Window,
+    /// The objects of the engine
+    pub objects: &'a mut ObjectStorage,
+    /// The input of this frame
+    pub input: &'a crate::utils::winit_input_helper::WinitInputHelper,
+    /// The cameras of the engine
+    pub camera: &'a mut CameraContainer,
+    /// The signals of the engine
+    pub signals: &'a mut SignalStorage,
+}
+
+/// A feature packaged to be added to any engine, such as physics, audio, or a UI library.
+/// The plugins are ran in the order they were added, see [Engine::add_plugin]
+///
+/// ```ignore
+/// struct Spin;
+/// impl EnginePlugin for Spin {
+///     fn update(&mut self, ctx: &mut PluginContext) {
+///         if let Some(cube) = ctx.objects.get_mut("cube") {
+///             cube.set_rotation((0.0, 1.0, 0.0));
+///         }
+///     }
+/// }
+///
+/// engine.add_plugin("spin", Spin);
+/// ```
+#[allow(unused_variables)]
+pub trait EnginePlugin: Any {
+    /// Ran once when the plugin is added, with the whole engine to add objects, signals, or
+    /// other plugins to. The window may not be created yet
+    fn setup(&mut self, engine: &mut Engine) {}
+
+    /// Ran every frame after the update loop and the game states
+    fn update(&mut self, ctx: &mut PluginContext) {}
+
+    /// Ran every frame before the frame is recorded, after the queued objects are built
+    fn pre_render(&mut self, ctx: &mut PluginContext) {}
+
+    /// Ran every frame after the frame is submitted and presented
+    fn post_render(&mut self, ctx: &mut PluginContext) {}
+}
+// the plugins can be fetched back as their own type, like the signals
+downcast!(dyn EnginePlugin);
+
+/// The hook of the plugins that is ran
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PluginHook {
+    Update,
+    PreRender,
+    PostRender,
+}
+
+/// The plugins of the engine, with their names
+#[derive(Default)]
+pub struct PluginStorage {
+    plugins: Vec<(String, Box<dyn EnginePlugin>)>,
+}
+
+impl std::fmt::Debug for PluginStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.plugins.iter().map(|i| &i.0))
+            .finish()
+    }
+}
+
+impl PluginStorage {
+    /// Creates an empty storage
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if a plugin with the name was added
+    pub fn has_plugin(&self, name: impl crate::StringBuffer) -> bool {
+        self.plugins.iter().any(|i| i.0 == name.as_str())
+    }
+
+    /// The names of the plugins, in the order they run
+    pub fn names(&self) -> Vec<&str> {
+        self.plugins.iter().map(|i| i.0.as_str()).collect()
+    }
+
+    /// Gets a plugin as its own type
+    pub fn get_plugin<T: 'static>(
+        &mut self,
+        name: impl crate::StringBuffer,
+    ) -> Option<Result<&mut T, downcast::TypeMismatch>> {
+        self.plugins
+            .iter_mut()
+            .find(|i| i.0 == name.as_str())
+            .map(|i| i.1.downcast_mut::<T>())
+    }
+
+    /// Removes a plugin, returns true if there was one with the name
+    pub fn remove_plugin(&mut self, name: impl crate::StringBuffer) -> bool {
+        let length = self.plugins.len();
+        self.plugins.retain(|i| i.0 != name.as_str());
+        self.plugins.len() != length
+    }
+
+    /// Runs the hook of every plugin
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn run(
+        &mut self,
+        hook: PluginHook,
+        renderer: &mut Renderer,
+        window: &mut Window,
+        objects: &mut ObjectStorage,
+        input: &crate::utils::winit_input_helper::WinitInputHelper,
+        camera: &mut CameraContainer,
+        signals: &mut SignalStorage,
+    ) {
+        if self.plugins.is_empty() {
+            return;
+        }
+
+        let mut ctx = PluginContext {
+            renderer,
+            window,
+            objects,
+            input,
+            camera,
+            signals,
+        };
+        for (_, plugin) in self.plugins.iter_mut() {
+            match hook {
+                PluginHook::Update => plugin.update(&mut ctx),
+                PluginHook::PreRender => plugin.pre_render(&mut ctx),
+                PluginHook::PostRender => plugin.post_render(&mut ctx),
+            }
+        }
+    }
+}
+
+impl Engine {
+    /// Adds a plugin after the others and runs its setup. A plugin with the same name is
+    /// replaced
+    pub fn add_plugin(&mut self, name: impl crate::StringBuffer, mut plugin: impl EnginePlugin) {
+        self.plugins.remove_plugin(name.clone());
+        plugin.setup(self);
+        self.plugins
+            .plugins
+            .push((name.as_string(), Box::new(plugin)));
+    }
+
+    /// Removes a plugin, returns true if there was one with the name
+    pub fn remove_plugin(&mut self, name: impl crate::StringBuffer) -> bool {
+        self.plugins.remove_plugin(name)
+    }
+}
@@ -0,0 +1,186 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{CameraContainer, Renderer, SamplerSettings};
+
+/// A level of quality, from the fastest to the best looking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QualityPreset {
+    /// For weak devices, at a lower internal resolution and without the extra passes
+    Low,
+    /// Smaller shadows and a few of the extra passes
+    Medium,
+    /// The full resolution with all the extra passes
+    High,
+    /// Supersampled, with the largest shadows and ray traced shadows where supported
+    Ultra,
+}
+
+impl QualityPreset {
+    /// All the presets, from the fastest to the best looking
+    pub const ALL: [QualityPreset; 4] = [Self::Low, Self::Medium, Self::High, Self::Ultra];
+
+    /// The settings of the preset
+    pub fn settings(self) -> QualitySettings {
+        match self {
+            Self::Low => QualitySettings {
+                shadow_atlas_size: 1024,
+                shadow_resolution_scale: 0.25,
+                ray_traced_shadows: false,
+                render_scale: 0.75,
+                motion_vectors: false,
+                depth_pyramid: false,
+                anisotropy: 1,
+                lod_bias: 1.0,
+            },
+            Self::Medium => QualitySettings {
+                shadow_atlas_size: 2048,
+                shadow_resolution_scale: 0.5,
+                ray_traced_shadows: false,
+                render_scale: 1.0,
+                motion_vectors: false,
+                depth_pyramid: true,
+                anisotropy: 4,
+                lod_bias: 0.0,
+            },
+            Self::High => QualitySettings {
+                shadow_atlas_size: 4096,
+                shadow_resolution_scale: 1.0,
+                ray_traced_shadows: false,
+                render_scale: 1.0,
+                motion_vectors: true,
+                depth_pyramid: true,
+                anisotropy: 8,
+                lod_bias: 0.0,
+            },
+            Self::Ultra => QualitySettings {
+                shadow_atlas_size: 8192,
+                shadow_resolution_scale: 2.0,
+                ray_traced_shadows: true,
+                render_scale: 1.5,
+                motion_vectors: true,
+                depth_pyramid: true,
+                anisotropy: 16,
+                lod_bias: 0.0,
+            },
+        }
+    }
+
+    /// The name of the preset, as read by [QualityPreset::from_name]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Ultra => "ultra",
+        }
+    }
+
+    /// The preset of the name, in any case
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|i| i.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+impl From<QualityPreset> for QualitySettings {
+    fn from(preset: QualityPreset) -> Self {
+        preset.settings()
+    }
+}
+
+/// How the renderer trades speed for looks, set together from a [QualityPreset] or one by
+/// one. The engine draws without MSAA, so the edges are smoothed by drawing the main camera
+/// at a higher internal resolution instead, see [QualitySettings::render_scale]. The default
+/// is how the renderer starts, which leaves the textures as they are made
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QualitySettings {
+    /// The size of the shadow atlas on each side, see [crate::utils::shadow_atlas::ShadowAtlas]
+    pub shadow_atlas_size: u32,
+    /// Multiplies the resolution of the shadows of every light
+    pub shadow_resolution_scale: f32,
+    /// Should the shadows be ray traced, on devices that support it
+    pub ray_traced_shadows: bool,
+    /// The internal resolution of the main camera, above 1.0 to supersample, see
+    /// [crate::Camera::render_scale]
+    pub render_scale: f32,
+    /// Should the motion vectors be drawn, for the temporal effects
+    pub motion_vectors: bool,
+    /// Should the depth pyramid be made, for occlusion culling and screen space effects
+    pub depth_pyramid: bool,
+    /// The least anisotropic filtering of the textures, see [SamplerSettings::anisotropy]
+    pub anisotropy: u16,
+    /// The amount of the largest mipmap levels the textures skip, to save memory and
+    /// bandwidth
+    pub lod_bias: f32,
+}
+impl Default for QualitySettings {
+    fn default() -> Self {
+        Self {
+            shadow_atlas_size: crate::utils::shadow_atlas::DEFAULT_ATLAS_SIZE,
+            shadow_resolution_scale: 1.0,
+            ray_traced_shadows: false,
+            render_scale: 1.0,
+            motion_vectors: false,
+            depth_pyramid: false,
+            anisotropy: 1,
+            lod_bias: 0.0,
+        }
+    }
+}
+
+impl QualitySettings {
+    /// The sampler with the anisotropy and the mipmap levels of the quality
+    pub fn sampler(&self, sampler: SamplerSettings) -> SamplerSettings {
+        SamplerSettings {
+            anisotropy: sampler.anisotropy.max(self.anisotropy),
+            lod_min_clamp: sampler.lod_min_clamp + self.lod_bias.max(0.0),
+            ..sampler
+        }
+    }
+}
+
+impl Renderer {
+    /// The quality that was last applied
+    pub fn quality(&self) -> &QualitySettings {
+        &self.quality
+    }
+
+    /// Changes the quality of the rendering at once, while running. The shadows, passes and
+    /// internal resolution change from the next frame on, while the anisotropy and the
+    /// mipmap levels apply to the textures made after this
+    ///
+    /// ```ignore
+    /// renderer.apply_quality(QualityPreset::Low, camera);
+    /// ```
+    pub fn apply_quality(
+        &mut self,
+        quality: impl Into<QualitySettings>,
+        camera: &mut CameraContainer,
+    ) {
+        let quality = quality.into();
+
+        self.shadow_atlas.size = quality
+            .shadow_atlas_size
+            .min(self.device.limits().max_texture_dimension_2d);
+        self.shadow_atlas.resolution_scale = quality.shadow_resolution_scale;
+        if quality.ray_traced_shadows && self.supports_ray_tracing() {
+            if self.ray_traced_shadows.is_none() {
+                // the device supports ray tracing, so this can not fail
+                let _ = self.enable_ray_traced_shadows();
+            }
+        } else {
+            self.disable_ray_traced_shadows();
+        }
+        self.motion_vectors.enabled = quality.motion_vectors;
+        self.depth_pyramid.enabled = quality.depth_pyramid;
+        camera.set_render_scale(quality.render_scale);
+
+        self.quality = quality;
+    }
+}
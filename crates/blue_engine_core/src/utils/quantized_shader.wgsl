@@ -0,0 +1,108 @@
+// blocks
+
+//@CAMERA_STRUCT
+
+//@FOG
+
+//@VARIATION
+
+struct TransformationUniforms {
+    transform_matrix: mat4x4<f32>,
+};
+@group(2) @binding(0)
+var<uniform> transform_uniform: TransformationUniforms;
+
+struct QuantizationUniforms {
+    position_offset: vec4<f32>,
+    position_scale: vec4<f32>,
+    // the offset in xy, and the scale in zw
+    uv_offset_scale: vec4<f32>,
+};
+@group(2) @binding(2)
+var<uniform> quantization: QuantizationUniforms;
+
+struct QuantizedVertexInput {
+    @location(0) position: vec4<f32>,
+    @location(1) texture_coordinates: vec2<f32>,
+    @location(2) normal: vec2<f32>,
+};
+
+struct VertexInput {
+    position: vec3<f32>,
+    texture_coordinates: vec2<f32>,
+    normal: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coordinates: vec2<f32>,
+    //@FOG_OUTPUT
+};
+
+struct InstanceInput {
+    @location(3) model_matrix_0: vec4<f32>,
+    @location(4) model_matrix_1: vec4<f32>,
+    @location(5) model_matrix_2: vec4<f32>,
+    @location(6) model_matrix_3: vec4<f32>,
+};
+
+fn decode_octahedral(encoded: vec2<f32>) -> vec3<f32> {
+    var normal = vec3<f32>(encoded, 1.0 - abs(encoded.x) - abs(encoded.y));
+    let fold = max(-normal.z, 0.0);
+    normal.x += select(fold, -fold, normal.x >= 0.0);
+    normal.y += select(fold, -fold, normal.y >= 0.0);
+    return normalize(normal);
+}
+
+fn dequantize(quantized: QuantizedVertexInput) -> VertexInput {
+    var vertex: VertexInput;
+    vertex.position = quantization.position_offset.xyz
+        + quantization.position_scale.xyz * quantized.position.xyz;
+    vertex.texture_coordinates = quantization.uv_offset_scale.xy
+        + quantization.uv_offset_scale.zw * quantized.texture_coordinates;
+    vertex.normal = decode_octahedral(quantized.normal);
+    return vertex;
+}
+
+// Vertex Stage
+@vertex
+fn vs_main(quantized: QuantizedVertexInput, instance: InstanceInput) -> VertexOutput {
+    let input = dequantize(quantized);
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    var out: VertexOutput;
+    out.texture_coordinates = input.texture_coordinates;
+    //@CAMERA_VERTEX
+    //@FOG_VERTEX
+    return out;
+}
+
+// Fragment Stage
+struct FragmentUniforms {
+    color: vec4<f32>,
+    random: f32,
+    time_offset: f32,
+    seed: u32,
+};
+@group(2) @binding(1)
+var<uniform> fragment_uniforms: FragmentUniforms;
+
+@group(0) @binding(0)
+var texture_diffuse: texture_2d<f32>;
+
+@group(0) @binding(1)
+var sampler_diffuse: sampler;
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    if fragment_uniforms.color.w == 0.0 {
+        return apply_fog(textureSample(texture_diffuse, sampler_diffuse, input.texture_coordinates), input.fog_distance);
+    } else {
+        return apply_fog(textureSample(texture_diffuse, sampler_diffuse, input.texture_coordinates) * fragment_uniforms.color, input.fog_distance);
+    }
+}
@@ -0,0 +1,508 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+
+use super::default_resources::RAY_TRACED_SHADOWS_SHADER;
+use crate::{
+    CameraContainer, Matrix4, Object, ObjectStorage, PipelineData, Renderer, Vector3, Vertex,
+};
+
+/// The features the device must be created with to trace rays
+pub const RAY_TRACING_FEATURES: wgpu::Features =
+    wgpu::Features::EXPERIMENTAL_RAY_TRACING_ACCELERATION_STRUCTURE
+        .union(wgpu::Features::EXPERIMENTAL_RAY_QUERY);
+
+/// The data of the shadows sent to the GPU
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowUniform {
+    inverse_view_projection: [[f32; 4]; 4],
+    camera_position: [f32; 4],
+    light: [f32; 4],
+    settings: [f32; 4],
+}
+
+/// Shadows found by tracing a ray from every pixel of the scene towards a directional light,
+/// on devices with ray tracing. They are sharp and exact at any distance, unlike shadow maps,
+/// at the cost of building the acceleration structures of the objects.
+///
+/// Each mesh gets a bottom level acceleration structure (BLAS) when first seen, which is
+/// shared by the objects and instances that use the same vertex buffer, and built again when
+/// the buffer is replaced. The top level (TLAS) is built each frame from their transforms.
+/// Objects of other cameras neither cast nor receive the shadows.
+///
+/// ```ignore
+/// renderer.enable_ray_traced_shadows()?;
+/// if let Some(shadows) = renderer.ray_traced_shadows() {
+///     shadows.light_direction = Vector3::new(0.5, 1.0, 0.3);
+/// }
+/// ```
+pub struct RayTracedShadows {
+    /// The direction towards the light
+    pub light_direction: Vector3,
+    /// How dark the shadows are, from 0 for none to 1 for black
+    pub strength: f32,
+    /// How far from the surface the rays start for every unit of distance from the camera,
+    /// which keeps the surfaces from shadowing themselves
+    pub bias: f32,
+    /// The longest distance that shadows are cast over
+    pub max_distance: f32,
+    /// The camera the shadows are traced for
+    pub camera: Arc<str>,
+    /// The acceleration structure of each vertex buffer, and the frame it was last used at
+    blas: HashMap<wgpu::Buffer, (wgpu::Blas, u64)>,
+    tlas: Option<wgpu::TlasPackage>,
+    /// The amount of instances written to the TLAS last frame
+    instance_count: usize,
+    frame: u64,
+    uniform_buffer: Option<wgpu::Buffer>,
+    /// The layout, pipeline and the format it was built for
+    pipeline: Option<(
+        wgpu::TextureFormat,
+        wgpu::BindGroupLayout,
+        wgpu::RenderPipeline,
+    )>,
+    /// The bind group and the depth view it was built for
+    bind_group: Option<(wgpu::TextureView, wgpu::BindGroup)>,
+}
+
+impl RayTracedShadows {
+    /// Creates the shadows of a light above the scene
+    pub fn new() -> Self {
+        Self {
+            light_direction: Vector3::new(0.3, 1.0, 0.2),
+            strength: 0.6,
+            bias: 0.001,
+            max_distance: 1000.0,
+            camera: "main".into(),
+            blas: HashMap::new(),
+            tlas: None,
+            instance_count: 0,
+            frame: 0,
+            uniform_buffer: None,
+            pipeline: None,
+            bind_group: None,
+        }
+    }
+
+    /// Drops the acceleration structures and the pipeline, keeping the settings of the light
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.blas.clear();
+        self.tlas = None;
+        self.instance_count = 0;
+        self.uniform_buffer = None;
+        self.pipeline = None;
+        self.bind_group = None;
+    }
+
+    /// The amount of meshes that have an acceleration structure
+    pub fn mesh_count(&self) -> usize {
+        self.blas.len()
+    }
+
+    /// The amount of instances in the scene the rays were traced against last frame
+    pub fn instance_count(&self) -> usize {
+        self.instance_count
+    }
+
+    /// Builds the acceleration structures of the visible objects, and darkens the parts of
+    /// the view that are in shadow
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+        objects: &ObjectStorage,
+        camera: &CameraContainer,
+    ) {
+        let Some(camera) = camera.get(self.camera.as_ref()) else {
+            return;
+        };
+        self.frame += 1;
+
+        // the meshes without an acceleration structure yet, with the buffers they are built from
+        let mut pending = Vec::new();
+        let mut instance_count = 0;
+        for object in casters(objects, &self.camera) {
+            let Some((vertex_buffer, source)) = vertex_source(object, objects) else {
+                continue;
+            };
+            instance_count += object.instances.len();
+
+            if let Some((_, frame)) = self.blas.get_mut(vertex_buffer) {
+                *frame = self.frame;
+                continue;
+            }
+            let size = wgpu::BlasTriangleGeometrySizeDescriptor {
+                vertex_format: wgpu::VertexFormat::Float32x3,
+                vertex_count: source.vertices.len() as u32,
+                #[cfg(not(feature = "u32"))]
+                index_format: Some(wgpu::IndexFormat::Uint16),
+                #[cfg(feature = "u32")]
+                index_format: Some(wgpu::IndexFormat::Uint32),
+                index_count: Some(source.indices.len() as u32),
+                flags: wgpu::AccelerationStructureGeometryFlags::OPAQUE,
+            };
+            let blas = device.create_blas(
+                &wgpu::CreateBlasDescriptor {
+                    label: Some("Shadow BLAS"),
+                    flags: wgpu::AccelerationStructureFlags::PREFER_FAST_TRACE,
+                    update_mode: wgpu::AccelerationStructureUpdateMode::Build,
+                },
+                wgpu::BlasGeometrySizeDescriptors::Triangles {
+                    descriptors: vec![size.clone()],
+                },
+            );
+
+            crate::utils::allocation_audit::count_buffer();
+            let vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Shadow BLAS Vertex Buffer"),
+                contents: bytemuck::cast_slice(source.vertices.as_slice()),
+                usage: wgpu::BufferUsages::BLAS_INPUT,
+            });
+            crate::utils::allocation_audit::count_buffer();
+            let indices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Shadow BLAS Index Buffer"),
+                contents: bytemuck::cast_slice(source.indices.as_slice()),
+                usage: wgpu::BufferUsages::BLAS_INPUT,
+            });
+
+            self.blas
+                .insert(vertex_buffer.clone(), (blas.clone(), self.frame));
+            pending.push((blas, size, vertices, indices));
+        }
+        // the meshes that are gone or were replaced
+        let frame = self.frame;
+        self.blas.retain(|_, (_, last_frame)| *last_frame == frame);
+
+        if instance_count == 0 {
+            self.instance_count = 0;
+            return;
+        }
+
+        let capacity = self.tlas.as_ref().map(|i| i.get().len()).unwrap_or(0);
+        if capacity < instance_count {
+            let tlas = device.create_tlas(&wgpu::CreateTlasDescriptor {
+                label: Some("Shadow TLAS"),
+                max_instances: instance_count.next_power_of_two().max(16) as u32,
+                flags: wgpu::AccelerationStructureFlags::PREFER_FAST_TRACE,
+                update_mode: wgpu::AccelerationStructureUpdateMode::Build,
+            });
+            self.tlas = Some(wgpu::TlasPackage::new(tlas));
+            self.bind_group = None;
+        }
+        self.write_instances(objects, instance_count);
+        let Some(tlas) = self.tlas.as_ref() else {
+            return;
+        };
+
+        let blas_entries = pending
+            .iter()
+            .map(|(blas, size, vertices, indices)| wgpu::BlasBuildEntry {
+                blas,
+                geometry: wgpu::BlasGeometries::TriangleGeometries(vec![
+                    wgpu::BlasTriangleGeometry {
+                        size,
+                        vertex_buffer: vertices,
+                        first_vertex: 0,
+                        vertex_stride: std::mem::size_of::<Vertex>() as u64,
+                        index_buffer: Some(indices),
+                        first_index: Some(0),
+                        transform_buffer: None,
+                        transform_buffer_offset: None,
+                    },
+                ]),
+            })
+            .collect::<Vec<_>>();
+        encoder.build_acceleration_structures(blas_entries.iter(), std::iter::once(tlas));
+
+        let uniform = ShadowUniform {
+            inverse_view_projection: camera.view_data.inverse().to_cols_array_2d(),
+            camera_position: camera.position.extend(1.0).to_array(),
+            light: self
+                .light_direction
+                .normalize_or(Vector3::Y)
+                .extend(self.strength.clamp(0.0, 1.0))
+                .to_array(),
+            settings: [self.bias.max(0.0), self.max_distance.max(0.0), 0.0, 0.0],
+        };
+        let uniform_buffer = self.uniform_buffer.get_or_insert_with(|| {
+            crate::utils::allocation_audit::count_buffer();
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Shadow Uniform Buffer"),
+                size: std::mem::size_of::<ShadowUniform>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        });
+        queue.write_buffer(uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+
+        if self.pipeline.as_ref().map(|i| i.0) != Some(format) {
+            let (layout, pipeline) = Self::build_pipeline(device, format);
+            self.pipeline = Some((format, layout, pipeline));
+            self.bind_group = None;
+        }
+        let Some((_, layout, pipeline)) = self.pipeline.as_ref() else {
+            return;
+        };
+
+        if self.bind_group.as_ref().map(|i| &i.0) != Some(depth_view) {
+            crate::utils::allocation_audit::count_bind_group();
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Shadow Bind Group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(depth_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: tlas.as_binding(),
+                    },
+                ],
+            });
+            self.bind_group = Some((depth_view.clone(), bind_group));
+        }
+        let Some((_, bind_group)) = self.bind_group.as_ref() else {
+            return;
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Writes the transform of each instance of the objects to the TLAS
+    fn write_instances(&mut self, objects: &ObjectStorage, instance_count: usize) {
+        let casters = casters(objects, &self.camera)
+            .filter_map(|i| {
+                let (vertex_buffer, _) = vertex_source(i, objects)?;
+                Some((i, &self.blas.get(vertex_buffer)?.0))
+            })
+            .collect::<Vec<_>>();
+        let Some(tlas) = self.tlas.as_mut() else {
+            return;
+        };
+        let capacity = tlas.get().len();
+        let Some(slots) =
+            tlas.get_mut_slice(0..capacity.min(instance_count.max(self.instance_count)))
+        else {
+            return;
+        };
+
+        let mut slots = slots.iter_mut();
+        for (object, blas) in casters {
+            let transform = object.translation_matrix
+                * Matrix4::from_quat(object.rotation_quaternion)
+                * object.scale_matrix;
+            for instance in object.instances.iter() {
+                let Some(slot) = slots.next() else {
+                    break;
+                };
+                let matrix = (instance.build().model * transform).transpose();
+                let mut rows = [0.0; 12];
+                rows.copy_from_slice(&matrix.to_cols_array()[..12]);
+                *slot = Some(wgpu::TlasInstance::new(blas, rows, 0, 0xFF));
+            }
+        }
+        // the instances of last frame that are gone
+        slots.for_each(|i| *i = None);
+        self.instance_count = instance_count;
+    }
+
+    fn build_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline) {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(RAY_TRACED_SHADOWS_SHADER.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shadow Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::AccelerationStructure,
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    write_mask: wgpu::ColorWrites::ALL,
+                    // the scene is multiplied by the light that reaches it, keeping its alpha
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::Src,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        (bind_group_layout, pipeline)
+    }
+}
+impl Default for RayTracedShadows {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl std::fmt::Debug for RayTracedShadows {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RayTracedShadows")
+            .field("light_direction", &self.light_direction)
+            .field("strength", &self.strength)
+            .field("bias", &self.bias)
+            .field("max_distance", &self.max_distance)
+            .field("camera", &self.camera)
+            .field("meshes", &self.blas.len())
+            .field("instance_count", &self.instance_count)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The visible objects of the camera that can be traced against. The pooled meshes share
+/// their vertex buffer, which the acceleration structures are kept by, so they are left out
+fn casters<'a>(objects: &'a ObjectStorage, camera: &'a str) -> impl Iterator<Item = &'a Object> {
+    objects.values().filter(move |i| {
+        i.is_visible
+            && crate::utils::mesh_pool::allocation_of(i, objects).is_none()
+            && i.camera_effect.as_deref().unwrap_or("main") == camera
+            && i.indices.len() >= 3
+            && i.indices.len() % 3 == 0
+    })
+}
+
+/// The vertex buffer the object is drawn with, and the object that owns it
+fn vertex_source<'a>(
+    object: &'a Object,
+    objects: &'a ObjectStorage,
+) -> Option<(&'a wgpu::Buffer, &'a Object)> {
+    match &object.pipeline.vertex_buffer {
+        PipelineData::Copy(object_id) => objects
+            .get(object_id.as_str())
+            .and_then(|i| vertex_source(i, objects)),
+        PipelineData::Data(data) => Some((&data.vertex_buffer, object)),
+    }
+}
+
+impl Renderer {
+    /// Returns true if the device was created with [RAY_TRACING_FEATURES], which can be
+    /// requested through [`crate::WindowDescriptor::features`] where the adapter has them
+    pub fn supports_ray_tracing(&self) -> bool {
+        self.device.features().contains(RAY_TRACING_FEATURES)
+    }
+
+    /// Shadows the scene with rays traced towards a light, configured through
+    /// [Renderer::ray_traced_shadows]. Needs [RAY_TRACING_FEATURES]
+    pub fn enable_ray_traced_shadows(&mut self) -> Result<(), crate::error::Error> {
+        if !self.supports_ray_tracing() {
+            return Err(crate::error::Error::MissingFeatures(
+                RAY_TRACING_FEATURES - self.device.features(),
+            ));
+        }
+
+        if self.ray_traced_shadows.is_none() {
+            self.ray_traced_shadows = Some(RayTracedShadows::new());
+        }
+
+        Ok(())
+    }
+
+    /// Stops tracing the shadows, and frees their acceleration structures
+    pub fn disable_ray_traced_shadows(&mut self) {
+        self.ray_traced_shadows = None;
+    }
+
+    /// The settings of the ray traced shadows, if they are enabled
+    pub fn ray_traced_shadows(&mut self) -> Option<&mut RayTracedShadows> {
+        self.ray_traced_shadows.as_mut()
+    }
+}
@@ -0,0 +1,56 @@
+// Ray traced shadows, drawn with a full screen triangle over the scene. Each pixel casts a ray
+// towards the light, and the scene is multiplied by the result to darken what is in shadow
+
+struct ShadowUniform {
+    inverse_view_projection: mat4x4<f32>,
+    camera_position: vec4<f32>,
+    // the direction towards the light, and how dark the shadows are
+    light: vec4<f32>,
+    // the offset of the rays from the surface, and their length
+    settings: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> shadow: ShadowUniform;
+@group(0) @binding(1)
+var scene_depth: texture_depth_2d;
+@group(0) @binding(2)
+var scene_objects: acceleration_structure;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let depth = textureLoad(scene_depth, vec2<i32>(position.xy), 0);
+    // nothing was drawn here
+    if depth >= 1.0 {
+        return vec4<f32>(1.0);
+    }
+
+    let size = vec2<f32>(textureDimensions(scene_depth));
+    let clip = vec2<f32>(position.x / size.x * 2.0 - 1.0, 1.0 - position.y / size.y * 2.0);
+    let world = shadow.inverse_view_projection * vec4<f32>(clip, depth, 1.0);
+    let point = world.xyz / world.w;
+
+    // the depth is less precise further away, so the rays start further from the surface
+    let bias = shadow.settings.x * max(distance(point, shadow.camera_position.xyz), 1.0);
+    var query: ray_query;
+    rayQueryInitialize(&query, scene_objects, RayDesc(
+        RAY_FLAG_TERMINATE_ON_FIRST_HIT,
+        0xFFu,
+        bias,
+        shadow.settings.y,
+        point,
+        normalize(shadow.light.xyz),
+    ));
+    rayQueryProceed(&query);
+
+    let intersection = rayQueryGetCommittedIntersection(&query);
+    if intersection.kind == RAY_QUERY_INTERSECTION_NONE {
+        return vec4<f32>(1.0);
+    }
+    return vec4<f32>(vec3<f32>(1.0 - shadow.light.w), 1.0);
+}
@@ -0,0 +1,149 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use super::frame_graph::{FramePass, MAIN_PASS, POST_PASS, PassBuilder, PassContext, UI_PASS};
+use crate::{Engine, Renderer, error::Error};
+
+/// Where a render hook is recorded in the frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RenderStage {
+    /// Before the objects are drawn, given the scene texture. The main pass clears it after
+    BeforeMainPass,
+    /// After the objects and the grid are drawn, given the scene texture
+    AfterMainPass,
+    /// After the scene is tonemapped to the frame, given the frame texture
+    AfterPostProcessing,
+    /// After the overlay is drawn, given the frame texture
+    AfterUi,
+}
+
+/// The resources of the engine a render hook is given, next to the encoder and view
+pub struct RenderResources<'a> {
+    /// The device, for creating pipelines and bind groups
+    pub device: &'a wgpu::Device,
+    /// The queue, for writing buffers and textures
+    pub queue: &'a wgpu::Queue,
+    /// The depth buffer of the scene
+    pub depth: &'a wgpu::TextureView,
+    /// The size of the frame in pixels
+    pub frame_size: (u32, u32),
+    /// The format of the view the hook is given
+    pub format: wgpu::TextureFormat,
+    /// The motion vectors of the scene, if they are enabled
+    pub motion_vectors: Option<&'a wgpu::TextureView>,
+}
+
+/// The function of a render hook
+pub type RenderHookFn =
+    Box<dyn FnMut(&mut wgpu::CommandEncoder, &wgpu::TextureView, &RenderResources) + Send + Sync>;
+
+/// A render hook as a pass of the frame graph
+struct RenderHook {
+    stage: RenderStage,
+    hook: RenderHookFn,
+}
+
+impl FramePass for RenderHook {
+    fn setup(&mut self, builder: &mut PassBuilder) {
+        match self.stage {
+            RenderStage::BeforeMainPass => builder.before(MAIN_PASS),
+            RenderStage::AfterMainPass => {
+                builder.after(MAIN_PASS);
+                builder.before(POST_PASS);
+            }
+            RenderStage::AfterPostProcessing => {
+                builder.after(POST_PASS);
+                builder.before(UI_PASS);
+            }
+            RenderStage::AfterUi => builder.after(UI_PASS),
+        }
+    }
+
+    fn execute(&mut self, context: &mut PassContext) {
+        let (view, format) = match self.stage {
+            RenderStage::BeforeMainPass | RenderStage::AfterMainPass => {
+                (context.scene, context.scene_format)
+            }
+            RenderStage::AfterPostProcessing | RenderStage::AfterUi => {
+                (context.frame, context.frame_format)
+            }
+        };
+        let resources = RenderResources {
+            device: context.device,
+            queue: context.queue,
+            depth: context.depth,
+            frame_size: context.frame_size,
+            format,
+            motion_vectors: context.motion_vectors,
+        };
+
+        (self.hook)(context.encoder, view, &resources);
+    }
+}
+
+impl Renderer {
+    /// Records the function into the encoder of every frame at the stage, for passes the
+    /// engine can not express. The hooks of the same stage run in the order they were added.
+    ///
+    /// Returns the name of the hook in [Renderer::frame_graph], which it can be removed with.
+    pub fn add_render_hook(
+        &mut self,
+        stage: RenderStage,
+        hook: impl FnMut(&mut wgpu::CommandEncoder, &wgpu::TextureView, &RenderResources)
+        + Send
+        + Sync
+        + 'static,
+    ) -> Result<String, Error> {
+        let name = (0..)
+            .map(|i| format!("render_hook_{i}"))
+            .find(|i| !self.frame_graph.has_pass(i))
+            .unwrap_or_default();
+
+        self.frame_graph.add_pass(
+            name.as_str(),
+            RenderHook {
+                stage,
+                hook: Box::new(hook),
+            },
+        )?;
+
+        Ok(name)
+    }
+
+    /// Removes a render hook by the name it was added with
+    pub fn remove_render_hook(&mut self, name: &str) -> bool {
+        self.frame_graph.remove_pass(name)
+    }
+}
+
+impl Engine {
+    /// Records the function into the encoder of every frame at the stage, see
+    /// [Renderer::add_render_hook].
+    ///
+    /// ```ignore
+    /// engine.add_render_hook(RenderStage::AfterMainPass, |encoder, view, resources| {
+    ///     let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+    ///         color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+    ///             view,
+    ///             resolve_target: None,
+    ///             ops: wgpu::Operations::default(),
+    ///         })],
+    ///         ..Default::default()
+    ///     });
+    ///     // draw with pipelines made from resources.device
+    /// })?;
+    /// ```
+    pub fn add_render_hook(
+        &mut self,
+        stage: RenderStage,
+        hook: impl FnMut(&mut wgpu::CommandEncoder, &wgpu::TextureView, &RenderResources)
+        + Send
+        + Sync
+        + 'static,
+    ) -> Result<String, Error> {
+        self.renderer.add_render_hook(stage, hook)
+    }
+}
@@ -0,0 +1,279 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use super::default_resources::FRAME_HISTORY_SHADER;
+use crate::{CameraContainer, ObjectStorage, Renderer};
+
+/// The smallest difference from 1 for a render scale to be rendered separately
+const RENDER_SCALE_EPSILON: f32 = 0.001;
+
+/// The textures a scaled camera is rendered to before it is composited onto the frame
+#[derive(Debug)]
+struct CameraTarget {
+    color: wgpu::Texture,
+    depth: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+/// The internal textures of the cameras that render at a different resolution than the frame,
+/// see [crate::Camera::render_scale]
+#[derive(Debug, Default)]
+pub(crate) struct CameraTargets {
+    targets: std::collections::HashMap<std::sync::Arc<str>, CameraTarget>,
+    sampler: Option<wgpu::Sampler>,
+    /// The layout, pipeline and the format it was built for
+    pipeline: Option<(
+        wgpu::TextureFormat,
+        wgpu::BindGroupLayout,
+        wgpu::RenderPipeline,
+    )>,
+}
+
+impl Renderer {
+    /// Draws the objects of each camera with a render scale into its own texture, and
+    /// composites them onto the frame, which is cleared first. Returns the names of the
+    /// scaled cameras and the amount of draw calls, so the rest of the objects can be drawn
+    /// on top of them.
+    pub(crate) fn draw_scaled_cameras(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        objects: &ObjectStorage,
+        camera: &CameraContainer,
+    ) -> (Vec<std::sync::Arc<str>>, u32) {
+        let mut scaled = camera
+            .iter()
+            .filter(|(_, i)| (i.render_scale - 1.0).abs() > RENDER_SCALE_EPSILON)
+            .map(|(name, i)| (name.clone(), i.render_scale.clamp(0.01, 4.0)))
+            .collect::<Vec<_>>();
+        // the main camera is composited first, and the rest in a stable order
+        scaled.sort_by(|(a, _), (b, _)| (a.as_ref() != "main", a).cmp(&(b.as_ref() != "main", b)));
+        self.camera_targets
+            .targets
+            .retain(|name, _| scaled.iter().any(|(i, _)| i == name));
+        if scaled.is_empty() {
+            return (Vec::new(), 0);
+        }
+
+        let format = self.scene_format();
+        if self.camera_targets.pipeline.as_ref().map(|i| i.0) != Some(format) {
+            let (layout, pipeline) = Self::build_camera_composite_pipeline(&self.device, format);
+            self.camera_targets.pipeline = Some((format, layout, pipeline));
+            self.camera_targets.targets.clear();
+        }
+
+        let mut draw_calls = 0;
+        for (index, (name, scale)) in scaled.iter().enumerate() {
+            let size = winit::dpi::PhysicalSize::new(
+                ((self.config.width as f32 * scale) as u32).max(1),
+                ((self.config.height as f32 * scale) as u32).max(1),
+            );
+            let (color_view, depth_view, bind_group) = self.camera_target(name, size);
+
+            if self.debug_groups {
+                encoder.push_debug_group(&format!("Camera \"{name}\""));
+            }
+            draw_calls += self.draw_objects_filtered(
+                encoder,
+                &color_view,
+                &depth_view,
+                objects,
+                size,
+                camera,
+                None,
+                wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                |i| i.camera_effect.as_deref().unwrap_or("main") == name.as_ref(),
+            );
+            if self.debug_groups {
+                encoder.pop_debug_group();
+            }
+
+            // the first camera is drawn over the clear of the frame
+            let load = if index == 0 {
+                self.clear_load(self.clear_color, encoder, view, format)
+            } else {
+                wgpu::LoadOp::Load
+            };
+            let Some((_, _, pipeline)) = self.camera_targets.pipeline.as_ref() else {
+                continue;
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Camera Composite Render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        (
+            scaled.into_iter().map(|(name, _)| name).collect(),
+            draw_calls,
+        )
+    }
+
+    /// Returns the color view, depth view and composite bind group of the camera,
+    /// rebuilding them if the size changed
+    fn camera_target(
+        &mut self,
+        name: &std::sync::Arc<str>,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> (wgpu::TextureView, wgpu::TextureView, wgpu::BindGroup) {
+        let matches = self
+            .camera_targets
+            .targets
+            .get(name)
+            .is_some_and(|i| i.color.width() == size.width && i.color.height() == size.height);
+
+        if !matches {
+            let color = self.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Camera Target"),
+                size: wgpu::Extent3d {
+                    width: size.width,
+                    height: size.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.scene_format(),
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let color_view = color.create_view(&wgpu::TextureViewDescriptor::default());
+
+            let mut config = self.config.clone();
+            config.width = size.width;
+            config.height = size.height;
+            let depth = Self::build_depth_buffer("Camera Target Depth", &self.device, &config).1;
+
+            let sampler = self.camera_targets.sampler.get_or_insert_with(|| {
+                self.device.create_sampler(&wgpu::SamplerDescriptor {
+                    label: Some("Camera Target Sampler"),
+                    mag_filter: wgpu::FilterMode::Linear,
+                    min_filter: wgpu::FilterMode::Linear,
+                    ..Default::default()
+                })
+            });
+            let Some((_, layout, _)) = self.camera_targets.pipeline.as_ref() else {
+                unreachable!("the composite pipeline is built before the targets");
+            };
+            crate::utils::allocation_audit::count_bind_group();
+            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Camera Target Bind Group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&color_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+            });
+
+            self.camera_targets.targets.insert(
+                name.clone(),
+                CameraTarget {
+                    color,
+                    depth,
+                    bind_group,
+                },
+            );
+        }
+
+        let target = &self.camera_targets.targets[name];
+        (
+            target
+                .color
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+            target.depth.clone(),
+            target.bind_group.clone(),
+        )
+    }
+
+    fn build_camera_composite_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline) {
+        // the layers are copied with the same full screen triangle as the frame history
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Camera Composite Shader"),
+            source: wgpu::ShaderSource::Wgsl(FRAME_HISTORY_SHADER.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Camera Composite Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Camera Composite Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Camera Composite Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    write_mask: wgpu::ColorWrites::ALL,
+                    // the layers are cleared to transparent, so their colors are premultiplied
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        (bind_group_layout, pipeline)
+    }
+}
@@ -0,0 +1,773 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::io::{Read, Write};
+
+use crate::{
+    ObjectStorage, Quaternion, Transform, Vector3, Vector4,
+    error::Error,
+    utils::{
+        fixed_timestep::FixedTimestep,
+        plugin::{EnginePlugin, PluginContext},
+    },
+};
+
+/// The default amount a value must change to be sent again
+pub const DEFAULT_REPLICATION_TOLERANCE: f32 = 0.0001;
+/// The default amount of times the changes are sent in a second
+pub const DEFAULT_REPLICATION_RATE: f32 = 20.0;
+/// The default largest message a [StreamTransport] accepts, in bytes
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+const HAS_TRANSFORM: u8 = 1;
+const HAS_VISIBILITY: u8 = 1 << 1;
+const HAS_COLOR: u8 = 1 << 2;
+const IS_VISIBLE: u8 = 1 << 3;
+
+/// The changes of a single object. Fields that did not change are `None`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ObjectDelta {
+    /// Name of the object in the storage
+    pub name: String,
+    /// The new position, rotation, and scale
+    pub transform: Option<Transform>,
+    /// The new visibility
+    pub is_visible: Option<bool>,
+    /// The new color
+    pub color: Option<Vector4>,
+}
+
+impl ObjectDelta {
+    /// Returns true if nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.transform.is_none() && self.is_visible.is_none() && self.color.is_none()
+    }
+}
+
+/// The changes of the scene since the last time they were collected
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SceneDelta {
+    /// The objects that changed or were added
+    pub objects: Vec<ObjectDelta>,
+    /// Names of the objects that were removed
+    pub removed: Vec<String>,
+}
+
+impl SceneDelta {
+    /// Returns true if nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty() && self.removed.is_empty()
+    }
+
+    /// Applies the changes to the objects of the storage.
+    ///
+    /// Objects that do not exist are skipped, and removed objects are only removed if
+    /// `remove` is true, as the networking layer may want to handle them itself.
+    pub fn apply(&self, objects: &mut ObjectStorage, remove: bool) {
+        for delta in self.objects.iter() {
+            let Some(object) = objects.get_mut(&delta.name) else {
+                continue;
+            };
+
+            if let Some(transform) = delta.transform {
+                object.set_transform(transform);
+            }
+            if let Some(is_visible) = delta.is_visible {
+                object.set_visibility(is_visible);
+            }
+            if let Some(color) = delta.color {
+                object.set_color(color.x, color.y, color.z, color.w);
+            }
+        }
+
+        if remove {
+            for name in self.removed.iter() {
+                objects.remove(name);
+            }
+        }
+    }
+
+    /// Encodes the changes into a compact little endian binary form, where only the
+    /// changed fields are written
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(&(self.objects.len() as u32).to_le_bytes());
+        for delta in self.objects.iter() {
+            write_name(&mut bytes, &delta.name);
+
+            let mut flags = 0;
+            if delta.transform.is_some() {
+                flags |= HAS_TRANSFORM;
+            }
+            if let Some(is_visible) = delta.is_visible {
+                flags |= HAS_VISIBILITY;
+                if is_visible {
+                    flags |= IS_VISIBLE;
+                }
+            }
+            if delta.color.is_some() {
+                flags |= HAS_COLOR;
+            }
+            bytes.push(flags);
+
+            if let Some(transform) = delta.transform {
+                write_floats(&mut bytes, &transform.position.to_array());
+                write_floats(&mut bytes, &transform.rotation.to_array());
+                write_floats(&mut bytes, &transform.scale.to_array());
+            }
+            if let Some(color) = delta.color {
+                write_floats(&mut bytes, &color.to_array());
+            }
+        }
+
+        bytes.extend_from_slice(&(self.removed.len() as u32).to_le_bytes());
+        for name in self.removed.iter() {
+            write_name(&mut bytes, name);
+        }
+
+        bytes
+    }
+
+    /// Decodes the changes from the form written by [`SceneDelta::to_bytes`]. Data that ends
+    /// early, or goes on after the changes, is an error
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader { bytes };
+        let mut delta = Self::default();
+
+        for _ in 0..reader.u32()? {
+            let name = reader.name()?;
+            let flags = reader.take(1)?[0];
+
+            let transform = if flags & HAS_TRANSFORM != 0 {
+                Some(Transform {
+                    position: Vector3::from_array(reader.floats()?),
+                    rotation: Quaternion::from_array(reader.floats()?),
+                    scale: Vector3::from_array(reader.floats()?),
+                })
+            } else {
+                None
+            };
+            let color = if flags & HAS_COLOR != 0 {
+                Some(Vector4::from_array(reader.floats()?))
+            } else {
+                None
+            };
+
+            delta.objects.push(ObjectDelta {
+                name,
+                transform,
+                is_visible: (flags & HAS_VISIBILITY != 0).then_some(flags & IS_VISIBLE != 0),
+                color,
+            });
+        }
+
+        for _ in 0..reader.u32()? {
+            delta.removed.push(reader.name()?);
+        }
+        if !reader.bytes.is_empty() {
+            return Err(Error::ReplicationDecodeError(
+                "the data goes on after the changes",
+            ));
+        }
+
+        Ok(delta)
+    }
+}
+
+/// The state of an object as it was last sent
+#[derive(Debug, Clone, Copy)]
+struct ReplicatedState {
+    transform: Transform,
+    is_visible: bool,
+    color: Vector4,
+}
+
+/// Tracks the changes of the objects, so only what changed since the last collection is sent.
+///
+/// Each connection that receives the changes separately should have its own tracker.
+#[derive(Debug, Clone)]
+pub struct ReplicationTracker {
+    sent: std::collections::HashMap<String, ReplicatedState>,
+    /// The amount a value must change to be sent again
+    pub tolerance: f32,
+}
+
+impl ReplicationTracker {
+    /// Creates a new tracker that has not sent anything yet
+    pub fn new() -> Self {
+        Self {
+            sent: std::collections::HashMap::new(),
+            tolerance: DEFAULT_REPLICATION_TOLERANCE,
+        }
+    }
+
+    /// Returns the changes of the objects since the last call, and marks them as sent.
+    /// Objects that were not sent before are sent in full.
+    pub fn collect(&mut self, objects: &ObjectStorage) -> SceneDelta {
+        let mut delta = SceneDelta::default();
+
+        for (name, object) in objects.iter() {
+            let state = ReplicatedState {
+                transform: object.transform(),
+                is_visible: object.is_visible,
+                color: object.color,
+            };
+
+            let object_delta = match self.sent.get(name) {
+                Some(sent) => ObjectDelta {
+                    name: name.clone(),
+                    transform: (!self.transform_eq(&sent.transform, &state.transform))
+                        .then_some(state.transform),
+                    is_visible: (sent.is_visible != state.is_visible).then_some(state.is_visible),
+                    color: (!sent.color.abs_diff_eq(state.color, self.tolerance))
+                        .then_some(state.color),
+                },
+                None => ObjectDelta {
+                    name: name.clone(),
+                    transform: Some(state.transform),
+                    is_visible: Some(state.is_visible),
+                    color: Some(state.color),
+                },
+            };
+
+            if !object_delta.is_empty() {
+                // only the sent values are stored, so slow changes below the tolerance
+                // still add up and are sent eventually
+                let sent = self.sent.entry(name.clone()).or_insert(state);
+                if let Some(transform) = object_delta.transform {
+                    sent.transform = transform;
+                }
+                if let Some(is_visible) = object_delta.is_visible {
+                    sent.is_visible = is_visible;
+                }
+                if let Some(color) = object_delta.color {
+                    sent.color = color;
+                }
+
+                delta.objects.push(object_delta);
+            }
+        }
+
+        self.sent.retain(|name, _| {
+            let exists = objects.contains_key(name);
+            if !exists {
+                delta.removed.push(name.clone());
+            }
+            exists
+        });
+
+        delta
+    }
+
+    /// Forgets what was sent, so the next collection sends all the objects in full,
+    /// such as when a client reconnects
+    pub fn reset(&mut self) {
+        self.sent.clear();
+    }
+
+    fn transform_eq(&self, a: &Transform, b: &Transform) -> bool {
+        a.position.abs_diff_eq(b.position, self.tolerance)
+            && a.rotation.abs_diff_eq(b.rotation, self.tolerance)
+            && a.scale.abs_diff_eq(b.scale, self.tolerance)
+    }
+}
+impl Default for ReplicationTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Carries the encoded changes between the two sides, such as over a socket or the channel
+/// of a networking library. The changes build on the ones before, so the messages must
+/// arrive whole and in order
+pub trait ReplicationTransport {
+    /// Sends a message to the other side
+    fn send(&mut self, message: &[u8]) -> std::io::Result<()>;
+
+    /// The next message from the other side, or None if there is none yet
+    fn receive(&mut self) -> std::io::Result<Option<Vec<u8>>>;
+}
+
+/// A transport between two sides of the same process, for testing and local play
+#[derive(Debug)]
+pub struct ChannelTransport {
+    sender: std::sync::mpsc::Sender<Vec<u8>>,
+    receiver: std::sync::mpsc::Receiver<Vec<u8>>,
+}
+
+impl ChannelTransport {
+    /// Creates both sides of the channel
+    pub fn pair() -> (Self, Self) {
+        let (sender_a, receiver_a) = std::sync::mpsc::channel();
+        let (sender_b, receiver_b) = std::sync::mpsc::channel();
+
+        (
+            Self {
+                sender: sender_a,
+                receiver: receiver_b,
+            },
+            Self {
+                sender: sender_b,
+                receiver: receiver_a,
+            },
+        )
+    }
+}
+impl ReplicationTransport for ChannelTransport {
+    fn send(&mut self, message: &[u8]) -> std::io::Result<()> {
+        self.sender
+            .send(message.to_vec())
+            .map_err(|_| std::io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn receive(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        match self.receiver.try_recv() {
+            Ok(message) => Ok(Some(message)),
+            Err(std::sync::mpsc::TryRecvError::Empty) => Ok(None),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+        }
+    }
+}
+
+/// A transport over a stream such as a [std::net::TcpStream], where each message is sent
+/// after its length. The stream should be set to not block, so the frame does not wait for
+/// the messages.
+///
+/// A length above [StreamTransport::max_message_size] is an error, as the other side either
+/// is not sending the same framing, or would have this side hold gigabytes waiting for it
+#[derive(Debug)]
+pub struct StreamTransport<S: Read + Write> {
+    /// The stream the messages go through
+    pub stream: S,
+    /// The largest message that is sent or received, in bytes
+    pub max_message_size: usize,
+    /// The bytes read that do not make a whole message yet
+    received: Vec<u8>,
+}
+
+impl<S: Read + Write> StreamTransport<S> {
+    /// Sends and receives the messages through the stream
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            received: Vec::new(),
+        }
+    }
+
+    /// Takes the first message out of the received bytes, if it is whole
+    fn take_message(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        let Some(length) = self.received.get(0..4) else {
+            return Ok(None);
+        };
+        let length = u32::from_le_bytes([length[0], length[1], length[2], length[3]]) as usize;
+        if length > self.max_message_size {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "a message of {length} bytes is larger than the limit of {} bytes",
+                    self.max_message_size
+                ),
+            ));
+        }
+        if self.received.len() < 4 + length {
+            return Ok(None);
+        }
+
+        let message = self.received[4..4 + length].to_vec();
+        self.received.drain(..4 + length);
+        Ok(Some(message))
+    }
+}
+impl<S: Read + Write> ReplicationTransport for StreamTransport<S> {
+    fn send(&mut self, message: &[u8]) -> std::io::Result<()> {
+        if message.len() > self.max_message_size || message.len() > u32::MAX as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "a message of {} bytes is larger than the limit of {} bytes",
+                    message.len(),
+                    self.max_message_size
+                ),
+            ));
+        }
+
+        let mut framed = Vec::with_capacity(4 + message.len());
+        framed.extend_from_slice(&(message.len() as u32).to_le_bytes());
+        framed.extend_from_slice(message);
+        self.stream.write_all(&framed)?;
+        self.stream.flush()
+    }
+
+    fn receive(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        if let Some(message) = self.take_message()? {
+            return Ok(Some(message));
+        }
+
+        let mut buffer = [0u8; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                Ok(length) => {
+                    self.received.extend_from_slice(&buffer[..length]);
+                    if let Some(message) = self.take_message()? {
+                        return Ok(Some(message));
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(None),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Sends the changes of the objects through a transport at a fixed rate, and applies the
+/// changes received from the other side. The side that owns the objects sends, the others
+/// receive, and a side may do both for the objects each owns:
+///
+/// ```ignore
+/// let stream = std::net::TcpStream::connect("127.0.0.1:4000")?;
+/// stream.set_nonblocking(true)?;
+///
+/// let mut replicator = Replicator::new(StreamTransport::new(stream));
+/// replicator.send = false;
+/// engine.add_plugin("replication", replicator);
+/// ```
+///
+/// When sending fails, everything is sent in full on the next tick, so the other side
+/// catches up once the transport works again.
+#[derive(Debug)]
+pub struct Replicator<T: ReplicationTransport> {
+    /// The transport the changes go through
+    pub transport: T,
+    /// Tracks what was sent
+    pub tracker: ReplicationTracker,
+    /// How often the changes are sent
+    pub timestep: FixedTimestep,
+    /// Should the changes of the objects be sent
+    pub send: bool,
+    /// Should the received changes be applied
+    pub receive: bool,
+    /// Should the objects removed on the other side be removed here
+    pub apply_removals: bool,
+    /// The last error of the transport or of decoding, cleared when it works again
+    last_error: Option<String>,
+}
+
+impl<T: ReplicationTransport> Replicator<T> {
+    /// Sends and receives through the transport, [DEFAULT_REPLICATION_RATE] times a second
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            tracker: ReplicationTracker::new(),
+            timestep: FixedTimestep::new(DEFAULT_REPLICATION_RATE),
+            send: true,
+            receive: true,
+            apply_removals: true,
+            last_error: None,
+        }
+    }
+
+    /// The last error of the transport or of decoding a message, if it has not worked since
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Sends the changes since the last tick, if there are any
+    pub fn send_changes(&mut self, objects: &ObjectStorage) {
+        let delta = self.tracker.collect(objects);
+        if delta.is_empty() {
+            return;
+        }
+
+        match self.transport.send(&delta.to_bytes()) {
+            Ok(()) => self.last_error = None,
+            Err(e) => {
+                self.tracker.reset();
+                self.last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// Applies all the changes that arrived
+    pub fn receive_changes(&mut self, objects: &mut ObjectStorage) {
+        loop {
+            let message = match self.transport.receive() {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(e) => {
+                    self.last_error = Some(e.to_string());
+                    break;
+                }
+            };
+
+            match SceneDelta::from_bytes(&message) {
+                Ok(delta) => {
+                    delta.apply(objects, self.apply_removals);
+                    self.last_error = None;
+                }
+                Err(e) => self.last_error = Some(e.to_string()),
+            }
+        }
+    }
+}
+
+impl<T: ReplicationTransport + 'static> EnginePlugin for Replicator<T> {
+    fn update(&mut self, ctx: &mut PluginContext) {
+        if self.receive {
+            self.receive_changes(ctx.objects);
+        }
+        // the changes pile up between the ticks, and are sent once
+        if self.timestep.advance() > 0 && self.send {
+            self.send_changes(ctx.objects);
+        }
+    }
+}
+
+fn write_name(bytes: &mut Vec<u8>, name: &str) {
+    bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(name.as_bytes());
+}
+
+fn write_floats(bytes: &mut Vec<u8>, floats: &[f32]) {
+    for float in floats {
+        bytes.extend_from_slice(&float.to_le_bytes());
+    }
+}
+
+/// Reads the encoded changes, failing if they end early
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < length {
+            return Err(Error::ReplicationDecodeError("the data ended early"));
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn name(&mut self) -> Result<String, Error> {
+        let length = self.u32()? as usize;
+        String::from_utf8(self.take(length)?.to_vec())
+            .map_err(|_| Error::ReplicationDecodeError("an object name is not valid UTF-8"))
+    }
+
+    fn floats<const N: usize>(&mut self) -> Result<[f32; N], Error> {
+        let mut floats = [0.0; N];
+        for float in floats.iter_mut() {
+            let bytes = self.take(4)?;
+            *float = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+
+        Ok(floats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stream that gives out the queued chunks one read at a time, and would block after
+    #[derive(Default)]
+    struct ChunkedStream {
+        chunks: std::collections::VecDeque<Vec<u8>>,
+        written: Vec<u8>,
+    }
+
+    impl Read for ChunkedStream {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            let Some(mut chunk) = self.chunks.pop_front() else {
+                return Err(std::io::ErrorKind::WouldBlock.into());
+            };
+            let length = chunk.len().min(buffer.len());
+            buffer[..length].copy_from_slice(&chunk[..length]);
+            if length < chunk.len() {
+                self.chunks.push_front(chunk.split_off(length));
+            }
+
+            Ok(length)
+        }
+    }
+    impl Write for ChunkedStream {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.written.extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn scene_delta() -> SceneDelta {
+        SceneDelta {
+            objects: vec![
+                ObjectDelta {
+                    name: "player".into(),
+                    transform: Some(Transform {
+                        position: Vector3::new(1.0, -2.5, 3.0),
+                        rotation: Quaternion::from_xyzw(0.0, 0.6, 0.0, 0.8),
+                        scale: Vector3::new(1.0, 2.0, 1.0),
+                    }),
+                    is_visible: Some(false),
+                    color: Some(Vector4::new(0.25, 0.5, 0.75, 1.0)),
+                },
+                ObjectDelta {
+                    name: "ünïcode".into(),
+                    transform: None,
+                    is_visible: Some(true),
+                    color: None,
+                },
+                ObjectDelta {
+                    name: String::new(),
+                    transform: None,
+                    is_visible: None,
+                    color: Some(Vector4::ZERO),
+                },
+            ],
+            removed: vec!["enemy".into(), String::new()],
+        }
+    }
+
+    #[test]
+    fn scene_delta_round_trips() {
+        let delta = scene_delta();
+        assert_eq!(SceneDelta::from_bytes(&delta.to_bytes()).unwrap(), delta);
+
+        let empty = SceneDelta::default();
+        assert_eq!(SceneDelta::from_bytes(&empty.to_bytes()).unwrap(), empty);
+    }
+
+    #[test]
+    fn truncated_scene_delta_is_an_error() {
+        let bytes = scene_delta().to_bytes();
+        for length in 0..bytes.len() {
+            assert!(
+                SceneDelta::from_bytes(&bytes[..length]).is_err(),
+                "decoded {length} of {} bytes",
+                bytes.len()
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_scene_delta_is_an_error() {
+        let mut trailing = scene_delta().to_bytes();
+        trailing.push(0);
+        assert!(SceneDelta::from_bytes(&trailing).is_err());
+
+        // counts and lengths far larger than the data
+        let mut huge_count = u32::MAX.to_le_bytes().to_vec();
+        huge_count.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(SceneDelta::from_bytes(&huge_count).is_err());
+
+        let mut invalid_name = 1u32.to_le_bytes().to_vec();
+        invalid_name.extend_from_slice(&1u32.to_le_bytes());
+        invalid_name.push(0xff);
+        invalid_name.push(0);
+        invalid_name.extend_from_slice(&0u32.to_le_bytes());
+        assert!(SceneDelta::from_bytes(&invalid_name).is_err());
+    }
+
+    fn framed(message: &[u8]) -> Vec<u8> {
+        let mut transport = StreamTransport::new(ChunkedStream::default());
+        transport.send(message).unwrap();
+        transport.stream.written
+    }
+
+    #[test]
+    fn split_frames_are_joined() {
+        let frame = framed(b"hello");
+        let mut transport = StreamTransport::new(ChunkedStream::default());
+        // split inside the length, and again inside the message
+        for chunk in [&frame[..2], &frame[2..6], &frame[6..]] {
+            transport.stream.chunks.push_back(chunk.to_vec());
+        }
+
+        assert_eq!(transport.receive().unwrap(), Some(b"hello".to_vec()));
+        assert_eq!(transport.receive().unwrap(), None);
+    }
+
+    #[test]
+    fn partial_frames_wait_for_the_rest() {
+        let frame = framed(b"hello");
+        let mut transport = StreamTransport::new(ChunkedStream::default());
+
+        transport.stream.chunks.push_back(frame[..3].to_vec());
+        assert_eq!(transport.receive().unwrap(), None);
+        transport.stream.chunks.push_back(frame[3..7].to_vec());
+        assert_eq!(transport.receive().unwrap(), None);
+        transport.stream.chunks.push_back(frame[7..].to_vec());
+        assert_eq!(transport.receive().unwrap(), Some(b"hello".to_vec()));
+    }
+
+    #[test]
+    fn concatenated_frames_are_split() {
+        let mut bytes = framed(b"first");
+        bytes.extend(framed(b""));
+        bytes.extend(framed(b"third"));
+        let mut transport = StreamTransport::new(ChunkedStream::default());
+        transport.stream.chunks.push_back(bytes);
+
+        assert_eq!(transport.receive().unwrap(), Some(b"first".to_vec()));
+        assert_eq!(transport.receive().unwrap(), Some(Vec::new()));
+        assert_eq!(transport.receive().unwrap(), Some(b"third".to_vec()));
+        assert_eq!(transport.receive().unwrap(), None);
+    }
+
+    #[test]
+    fn oversized_length_is_an_error() {
+        let mut transport = StreamTransport::new(ChunkedStream::default());
+        transport.max_message_size = 8;
+        transport
+            .stream
+            .chunks
+            .push_back(u32::MAX.to_le_bytes().to_vec());
+
+        let error = transport.receive().unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(transport.send(&[0; 9]).is_err());
+        assert!(transport.send(&[0; 8]).is_ok());
+    }
+
+    #[test]
+    fn closed_stream_is_an_error() {
+        struct Closed;
+        impl Read for Closed {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Ok(0)
+            }
+        }
+        impl Write for Closed {
+            fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+                Ok(bytes.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut transport = StreamTransport::new(Closed);
+        let error = transport.receive().unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,453 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{CameraContainer, ObjectStorage, Renderer, SignalStorage, Window};
+
+/// A handle to a timer of the [Scheduler], used to cancel it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerId(u64);
+
+/// The engine given to the callbacks of the [Scheduler]
+pub struct SchedulerContext<'a> {
+    /// The renderer of the engine
+    pub renderer: &'a mut Renderer,
+    /// The window of the engine
+    pub window: &'a mut Window,
+    /// The objects of the engine
+    pub objects: &'a mut ObjectStorage,
+    /// The input of this frame
+    pub input: &'a crate::utils::winit_input_helper::WinitInputHelper,
+    /// The cameras of the engine
+    pub camera: &'a mut CameraContainer,
+    /// The signals of the engine
+    pub signals: &'a mut SignalStorage,
+    /// The scheduler itself, to add or cancel timers from inside a callback
+    pub scheduler: &'a mut Scheduler,
+    /// The timer that is running the callback
+    pub timer: TimerId,
+}
+
+/// The callback of a timer, ran once or on every repeat
+enum TimerCallback {
+    Once(Box<dyn FnOnce(&mut SchedulerContext)>),
+    Repeat(Box<dyn FnMut(&mut SchedulerContext)>),
+}
+impl TimerCallback {
+    /// Runs the callback, and gives it back if it repeats
+    fn run(self, context: &mut SchedulerContext) -> Option<Self> {
+        match self {
+            Self::Once(callback) => {
+                callback(context);
+                None
+            }
+            Self::Repeat(mut callback) => {
+                callback(context);
+                Some(Self::Repeat(callback))
+            }
+        }
+    }
+}
+
+struct Timer {
+    id: TimerId,
+    /// The scheduler time the timer is due at, in seconds
+    due: f64,
+    /// The time between the repeats, for repeating timers
+    interval: f32,
+    callback: TimerCallback,
+}
+
+/// Runs callbacks after a delay or on a fixed interval, in place of counting the time by hand
+/// in the update loop. The timers are checked every frame, after the update loop.
+///
+/// The time of the scheduler follows [Scheduler::time_scale], and stops while it is paused, so
+/// the timers of the game slow down and stop with it.
+pub struct Scheduler {
+    /// How fast the time of the scheduler passes compared to the real time. 1.0 is real time
+    pub time_scale: f32,
+    /// Stops the time of the scheduler, and so all of its timers
+    pub paused: bool,
+    time: f64,
+    next_id: u64,
+    timers: Vec<Timer>,
+    /// The timers that are due this frame and did not run yet, the next one last
+    due: Vec<Timer>,
+    /// The timer whose callback is running, and if it was cancelled by it
+    running: Option<(TimerId, bool)>,
+}
+
+impl Scheduler {
+    /// Creates a new scheduler without timers
+    pub fn new() -> Self {
+        Self {
+            time_scale: 1.0,
+            paused: false,
+            time: 0.0,
+            next_id: 0,
+            timers: Vec::new(),
+            due: Vec::new(),
+            running: None,
+        }
+    }
+
+    fn push(&mut self, delay: f32, interval: f32, callback: TimerCallback) -> TimerId {
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+        self.timers.push(Timer {
+            id,
+            due: self.time + delay.max(0.0) as f64,
+            interval,
+            callback,
+        });
+
+        id
+    }
+
+    /// Runs the callback once, after the delay in seconds
+    pub fn after(
+        &mut self,
+        delay: f32,
+        callback: impl FnOnce(&mut SchedulerContext) + 'static,
+    ) -> TimerId {
+        self.push(delay, 0.0, TimerCallback::Once(Box::new(callback)))
+    }
+
+    /// Runs the callback every interval in seconds, starting one interval from now.
+    /// If more than one interval passes in a frame, such as after a stall, it only runs once
+    pub fn every(
+        &mut self,
+        interval: f32,
+        callback: impl FnMut(&mut SchedulerContext) + 'static,
+    ) -> TimerId {
+        // a zero interval would run the callback forever in a single frame
+        let interval = interval.max(f32::EPSILON);
+        self.push(
+            interval,
+            interval,
+            TimerCallback::Repeat(Box::new(callback)),
+        )
+    }
+
+    /// Stops the timer from running again. Returns false if it already finished
+    pub fn cancel(&mut self, timer: TimerId) -> bool {
+        if let Some((running, cancelled)) = self.running.as_mut()
+            && *running == timer
+        {
+            *cancelled = true;
+            return true;
+        }
+
+        let count = self.len();
+        self.timers.retain(|i| i.id != timer);
+        self.due.retain(|i| i.id != timer);
+        self.len() != count
+    }
+
+    /// Returns true if the timer will still run
+    pub fn is_scheduled(&self, timer: TimerId) -> bool {
+        self.timers().any(|i| i.id == timer)
+            || self
+                .running
+                .is_some_and(|(running, cancelled)| running == timer && !cancelled)
+    }
+
+    /// The seconds left until the timer runs next
+    pub fn remaining(&self, timer: TimerId) -> Option<f32> {
+        self.timers()
+            .find(|i| i.id == timer)
+            .map(|i| (i.due - self.time).max(0.0) as f32)
+    }
+
+    /// Cancels all the timers
+    pub fn clear(&mut self) {
+        self.timers.clear();
+        self.due.clear();
+        if let Some((_, cancelled)) = self.running.as_mut() {
+            *cancelled = true;
+        }
+    }
+
+    /// The amount of timers that will still run
+    pub fn len(&self) -> usize {
+        self.timers.len() + self.due.len()
+    }
+
+    /// Returns true if there are no timers
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The timers that did not run yet, including the ones due this frame
+    fn timers(&self) -> impl Iterator<Item = &Timer> {
+        self.timers.iter().chain(self.due.iter())
+    }
+
+    /// The time of the scheduler in seconds, scaled and without the paused time
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Moves the time forward and runs the timers that are due, in the order they are due
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn advance(
+        &mut self,
+        delta_time: f32,
+        renderer: &mut Renderer,
+        window: &mut Window,
+        objects: &mut ObjectStorage,
+        input: &crate::utils::winit_input_helper::WinitInputHelper,
+        camera: &mut CameraContainer,
+        signals: &mut SignalStorage,
+    ) {
+        self.run_due(delta_time, |scheduler, timer, callback| {
+            callback.run(&mut SchedulerContext {
+                renderer,
+                window,
+                objects,
+                input,
+                camera,
+                signals,
+                scheduler,
+                timer,
+            })
+        });
+    }
+
+    /// Moves the time forward and gives the timers that are due to `run`, in the order they
+    /// are due. Timers cancelled by an earlier one in the same frame do not run
+    fn run_due(
+        &mut self,
+        delta_time: f32,
+        mut run: impl FnMut(&mut Self, TimerId, TimerCallback) -> Option<TimerCallback>,
+    ) {
+        if self.paused {
+            return;
+        }
+        self.time += (delta_time * self.time_scale.max(0.0)) as f64;
+
+        let (mut due, timers): (Vec<_>, Vec<_>) = std::mem::take(&mut self.timers)
+            .into_iter()
+            .partition(|i| i.due <= self.time);
+        self.timers = timers;
+        // the next one is popped from the end
+        due.sort_by(|a, b| b.due.total_cmp(&a.due).then(b.id.0.cmp(&a.id.0)));
+        self.due = due;
+
+        while let Some(timer) = self.due.pop() {
+            let Timer {
+                id,
+                mut due,
+                interval,
+                callback,
+            } = timer;
+
+            self.running = Some((id, false));
+            let callback = run(self, id, callback);
+
+            let cancelled = self.running.take().is_some_and(|i| i.1);
+            if let Some(callback) = callback
+                && !cancelled
+            {
+                // the missed repeats are dropped, so the next one is always in the future
+                due += interval as f64;
+                if due <= self.time {
+                    due = self.time + interval as f64;
+                }
+                self.timers.push(Timer {
+                    id,
+                    due,
+                    interval,
+                    callback,
+                });
+            }
+        }
+    }
+}
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl std::fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("time_scale", &self.time_scale)
+            .field("paused", &self.paused)
+            .field("time", &self.time)
+            .field("timers", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Moves the scheduler forward and returns the timers that ran, in order. `run` is called
+    /// with each timer in place of its callback
+    fn step_with(
+        scheduler: &mut Scheduler,
+        delta_time: f32,
+        mut run: impl FnMut(&mut Scheduler, TimerId),
+    ) -> Vec<TimerId> {
+        let mut ran = Vec::new();
+        scheduler.run_due(delta_time, |scheduler, timer, callback| {
+            ran.push(timer);
+            run(scheduler, timer);
+            match callback {
+                TimerCallback::Once(_) => None,
+                TimerCallback::Repeat(_) => Some(callback),
+            }
+        });
+
+        ran
+    }
+
+    fn step(scheduler: &mut Scheduler, delta_time: f32) -> Vec<TimerId> {
+        step_with(scheduler, delta_time, |_, _| {})
+    }
+
+    #[test]
+    fn after_runs_once_when_due() {
+        let mut scheduler = Scheduler::new();
+        let timer = scheduler.after(0.25, |_| {});
+
+        assert!(step(&mut scheduler, 0.125).is_empty());
+        assert_eq!(scheduler.remaining(timer), Some(0.125));
+
+        assert_eq!(step(&mut scheduler, 0.125), [timer]);
+        assert!(!scheduler.is_scheduled(timer));
+        assert!(scheduler.is_empty());
+
+        assert!(step(&mut scheduler, 1.0).is_empty());
+    }
+
+    #[test]
+    fn due_timers_run_in_order() {
+        let mut scheduler = Scheduler::new();
+        let third = scheduler.after(0.75, |_| {});
+        let first = scheduler.after(0.25, |_| {});
+        let repeat = scheduler.every(0.5, |_| {});
+        // due at the same time as the repeat, but added after it
+        let second = scheduler.after(0.5, |_| {});
+
+        assert_eq!(step(&mut scheduler, 1.0), [first, repeat, second, third]);
+    }
+
+    #[test]
+    fn every_runs_on_each_interval() {
+        let mut scheduler = Scheduler::new();
+        let timer = scheduler.every(0.5, |_| {});
+
+        let ran: usize = (0..4).map(|_| step(&mut scheduler, 0.25).len()).sum();
+        assert_eq!(ran, 2);
+        assert!(scheduler.is_scheduled(timer));
+        assert_eq!(scheduler.remaining(timer), Some(0.5));
+    }
+
+    #[test]
+    fn every_catches_up_once() {
+        let mut scheduler = Scheduler::new();
+        let timer = scheduler.every(0.25, |_| {});
+
+        // a stall over several intervals runs the timer once, and the next one is a whole
+        // interval away instead of right after
+        assert_eq!(step(&mut scheduler, 1.125), [timer]);
+        assert_eq!(scheduler.remaining(timer), Some(0.25));
+
+        assert!(step(&mut scheduler, 0.125).is_empty());
+        assert_eq!(step(&mut scheduler, 0.125), [timer]);
+    }
+
+    #[test]
+    fn cancelling_by_id() {
+        let mut scheduler = Scheduler::new();
+        let once = scheduler.after(0.25, |_| {});
+        let repeat = scheduler.every(0.25, |_| {});
+        let kept = scheduler.after(0.25, |_| {});
+
+        assert!(scheduler.cancel(once));
+        assert!(scheduler.cancel(repeat));
+        assert!(!scheduler.cancel(repeat));
+        assert!(!scheduler.is_scheduled(once));
+        assert!(scheduler.is_scheduled(kept));
+        assert_eq!(scheduler.len(), 1);
+
+        assert_eq!(step(&mut scheduler, 1.0), [kept]);
+        assert!(!scheduler.cancel(kept));
+    }
+
+    #[test]
+    fn cancelling_from_the_callback() {
+        let mut scheduler = Scheduler::new();
+        let timer = scheduler.every(0.25, |_| {});
+
+        let mut count = 0;
+        for _ in 0..8 {
+            step_with(&mut scheduler, 0.25, |scheduler, running| {
+                count += 1;
+                if count == 2 {
+                    assert!(scheduler.cancel(running));
+                    assert!(!scheduler.is_scheduled(running));
+                }
+            });
+        }
+        assert_eq!(count, 2);
+        assert!(!scheduler.is_scheduled(timer));
+    }
+
+    #[test]
+    fn cancelling_a_sibling_due_in_the_same_frame() {
+        let mut scheduler = Scheduler::new();
+        let first = scheduler.after(0.25, |_| {});
+        let sibling = scheduler.every(0.5, |_| {});
+        let last = scheduler.after(0.5, |_| {});
+
+        let ran = step_with(&mut scheduler, 1.0, |scheduler, running| {
+            if running == first {
+                assert!(scheduler.is_scheduled(sibling));
+                assert_eq!(scheduler.remaining(sibling), Some(0.0));
+                assert!(scheduler.cancel(sibling));
+                assert!(!scheduler.is_scheduled(sibling));
+                assert!(!scheduler.cancel(sibling));
+                assert_eq!(scheduler.len(), 1);
+            }
+        });
+        assert_eq!(ran, [first, last]);
+        assert!(!scheduler.is_scheduled(sibling));
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn clearing_from_the_callback() {
+        let mut scheduler = Scheduler::new();
+        let first = scheduler.every(0.25, |_| {});
+        scheduler.after(0.25, |_| {});
+        scheduler.after(2.0, |_| {});
+
+        let ran = step_with(&mut scheduler, 0.25, |scheduler, _| scheduler.clear());
+        assert_eq!(ran, [first]);
+        assert!(scheduler.is_empty());
+        assert!(!scheduler.is_scheduled(first));
+        assert!(step(&mut scheduler, 5.0).is_empty());
+    }
+
+    #[test]
+    fn paused_and_scaled_time() {
+        let mut scheduler = Scheduler::new();
+        let timer = scheduler.after(0.5, |_| {});
+
+        scheduler.paused = true;
+        assert!(step(&mut scheduler, 1.0).is_empty());
+        assert_eq!(scheduler.time(), 0.0);
+
+        scheduler.paused = false;
+        scheduler.time_scale = 0.5;
+        assert!(step(&mut scheduler, 0.5).is_empty());
+        assert_eq!(scheduler.time(), 0.25);
+        assert_eq!(step(&mut scheduler, 0.5), [timer]);
+    }
+}
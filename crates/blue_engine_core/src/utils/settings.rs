@@ -0,0 +1,390 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    CameraContainer, Renderer, StringBuffer, Window, WindowDescriptor,
+    utils::{
+        input_map::{InputBinding, InputMap},
+        quality::QualityPreset,
+    },
+};
+#[cfg(feature = "serde")]
+use crate::error::Error;
+
+/// The name of the settings file, in the directory of [settings_directory]
+pub const SETTINGS_FILE_NAME: &str = "settings.toml";
+
+/// The directory the settings of the app are kept in, following the platform:
+/// `%APPDATA%` on Windows, `~/Library/Application Support` on macOS, and `$XDG_CONFIG_HOME`
+/// or `~/.config` elsewhere. Returns `None` on platforms without a file system for them
+pub fn settings_directory(app_name: impl StringBuffer) -> Option<PathBuf> {
+    #[cfg(any(target_arch = "wasm32", target_os = "android", target_os = "ios"))]
+    let base: Option<PathBuf> = None;
+
+    #[cfg(target_os = "windows")]
+    let base = std::env::var_os("APPDATA").map(PathBuf::from);
+
+    #[cfg(target_os = "macos")]
+    let base = std::env::var_os("HOME")
+        .map(|i| PathBuf::from(i).join("Library").join("Application Support"));
+
+    #[cfg(not(any(
+        target_arch = "wasm32",
+        target_os = "android",
+        target_os = "ios",
+        target_os = "windows",
+        target_os = "macos"
+    )))]
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|i| !i.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|i| PathBuf::from(i).join(".config")));
+
+    base.map(|i| i.join(app_name.as_str()))
+}
+
+/// The settings the player can change, kept between the runs of the game
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct Settings {
+    /// The size of the window
+    pub resolution: (u32, u32),
+    /// Should the window be borderless fullscreen
+    pub fullscreen: bool,
+    /// Should the frames wait for the display to refresh
+    pub vsync: bool,
+    /// The volume between 0 and 1, which the game gives to its audio, such as with the
+    /// `set_volume` of the audio module
+    pub volume: f32,
+    /// The quality of the rendering, or None to leave it as the game sets it
+    pub quality: Option<QualityPreset>,
+    /// The inputs of each action, see [Settings::apply_bindings]
+    pub bindings: BTreeMap<String, Vec<InputBinding>>,
+    /// Any other settings of the game, by their name
+    pub values: BTreeMap<String, String>,
+}
+
+impl Settings {
+    /// Takes the settings out of the descriptor the engine was created with
+    pub fn from_descriptor(descriptor: &WindowDescriptor) -> Self {
+        Self {
+            resolution: (descriptor.width, descriptor.height),
+            vsync: matches!(
+                descriptor.present_mode,
+                wgpu::PresentMode::AutoVsync | wgpu::PresentMode::Fifo
+            ),
+            ..Default::default()
+        }
+    }
+
+    /// Keeps the bindings of all the actions of the map
+    pub fn set_bindings_from(&mut self, map: &InputMap) {
+        self.bindings = map
+            .actions()
+            .map(|i| (i.to_string(), map.bindings(i).to_vec()))
+            .collect();
+    }
+
+    /// Replaces the bindings of the map with the kept ones. Actions without kept bindings are
+    /// left as they are
+    pub fn apply_bindings(&self, map: &mut InputMap) {
+        for (action, bindings) in self.bindings.iter() {
+            map.set_bindings(action.as_str(), bindings.clone());
+        }
+    }
+
+    /// Returns the value of another setting
+    pub fn value(&self, name: impl StringBuffer) -> Option<&str> {
+        self.values.get(name.as_str()).map(|i| i.as_str())
+    }
+
+    /// Sets the value of another setting
+    pub fn set_value(&mut self, name: impl StringBuffer, value: impl ToString) {
+        self.values.insert(name.as_string(), value.to_string());
+    }
+
+    /// Reads the settings from the TOML written by [Settings::to_toml]. The settings missing
+    /// from the text take their default
+    #[cfg(feature = "serde")]
+    pub fn from_toml(text: &str) -> Result<Self, Error> {
+        toml::from_str(text).map_err(|e| Error::SettingsError(e.to_string()))
+    }
+
+    /// Writes the settings as TOML, with the bindings as `action = ["Key:Space"]` in the
+    /// `[bindings]` table and the other values in the `[values]` table
+    #[cfg(feature = "serde")]
+    pub fn to_toml(&self) -> Result<String, Error> {
+        toml::to_string(self).map_err(|e| Error::SettingsError(e.to_string()))
+    }
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            resolution: (800, 600),
+            fullscreen: false,
+            vsync: false,
+            volume: 1.0,
+            quality: None,
+            bindings: BTreeMap::new(),
+            values: BTreeMap::new(),
+        }
+    }
+}
+/// A setting that was changed, see [SettingsStore::changes]
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingsChange {
+    /// The size of the window changed, and the window was resized
+    Resolution(u32, u32),
+    /// The window went in or out of fullscreen
+    Fullscreen(bool),
+    /// The vsync was turned on or off
+    Vsync(bool),
+    /// The volume changed
+    Volume(f32),
+    /// The quality preset changed, and was applied to the renderer
+    Quality(QualityPreset),
+    /// The bindings of the action changed
+    Bindings(String),
+    /// Another setting changed, by its name
+    Value(String),
+}
+
+/// Keeps the [Settings] of the game and saves them to the disk. The changes to the settings
+/// are applied by the engine after the update loop, resizing the window and changing the
+/// vsync, fullscreen and quality, and are listed in [SettingsStore::changes] until the next frame.
+/// Saving and loading them needs the `serde` feature:
+///
+/// ```ignore
+/// engine.settings.set_app_name("my_game");
+/// engine.settings.load()?;
+///
+/// // later, such as in an options menu
+/// engine.settings.settings.vsync = true;
+/// engine.settings.save()?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct SettingsStore {
+    /// The current settings, which can be changed at any time
+    pub settings: Settings,
+    /// The settings as they were last applied
+    applied: Settings,
+    path: Option<PathBuf>,
+    changes: Vec<SettingsChange>,
+}
+
+impl SettingsStore {
+    /// Creates a new store with the settings, which are taken as already applied
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            applied: settings.clone(),
+            settings,
+            path: None,
+            changes: Vec::new(),
+        }
+    }
+
+    /// Keeps the settings in the [settings_directory] of the app
+    pub fn set_app_name(&mut self, app_name: impl StringBuffer) {
+        self.path = settings_directory(app_name).map(|i| i.join(SETTINGS_FILE_NAME));
+    }
+
+    /// Keeps the settings in the file at the path
+    pub fn set_path(&mut self, path: impl AsRef<Path>) {
+        self.path = Some(path.as_ref().to_path_buf());
+    }
+
+    /// The file the settings are kept in
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Reads the settings from their file. Returns false if there is no file yet, such as on
+    /// the first run, in which case the settings are left as they are
+    #[cfg(feature = "serde")]
+    pub fn load(&mut self) -> Result<bool, Error> {
+        let Some(path) = self.path.as_ref() else {
+            return Ok(false);
+        };
+
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                self.settings = Settings::from_toml(&text)?;
+                Ok(true)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the settings to their file, creating its directory if needed
+    #[cfg(feature = "serde")]
+    pub fn save(&self) -> Result<(), Error> {
+        let path = self.path.as_ref().ok_or_else(|| {
+            Error::SettingsError("there is no file to save the settings to".to_string())
+        })?;
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(path, self.settings.to_toml()?)?;
+
+        Ok(())
+    }
+
+    /// The settings that were changed and applied at the end of the last frame
+    pub fn changes(&self) -> &[SettingsChange] {
+        &self.changes
+    }
+
+    /// Applies the settings that changed since the last time, and lists them as changes
+    pub(crate) fn apply(
+        &mut self,
+        renderer: &mut Renderer,
+        window: &mut Window,
+        camera: &mut CameraContainer,
+    ) {
+        self.changes.clear();
+        if self.settings == self.applied {
+            return;
+        }
+        let (settings, applied) = (&self.settings, &self.applied);
+
+        if settings.fullscreen != applied.fullscreen {
+            window.set_fullscreen_borderless(settings.fullscreen);
+            self.changes
+                .push(SettingsChange::Fullscreen(settings.fullscreen));
+        }
+        if settings.resolution != applied.resolution {
+            let (width, height) = settings.resolution;
+            crate::window::resize_window(renderer, window, camera, width, height);
+            self.changes.push(SettingsChange::Resolution(width, height));
+        }
+        if settings.vsync != applied.vsync {
+            renderer.set_vsync(settings.vsync);
+            self.changes.push(SettingsChange::Vsync(settings.vsync));
+        }
+        if settings.volume != applied.volume {
+            self.changes.push(SettingsChange::Volume(settings.volume));
+        }
+        if let (Some(quality), true) = (settings.quality, settings.quality != applied.quality) {
+            renderer.apply_quality(quality, camera);
+            self.changes.push(SettingsChange::Quality(quality));
+        }
+
+        for (action, bindings) in settings.bindings.iter() {
+            if applied.bindings.get(action) != Some(bindings) {
+                self.changes.push(SettingsChange::Bindings(action.clone()));
+            }
+        }
+        for action in applied.bindings.keys() {
+            if !settings.bindings.contains_key(action) {
+                self.changes.push(SettingsChange::Bindings(action.clone()));
+            }
+        }
+        for (name, value) in settings.values.iter() {
+            if applied.values.get(name) != Some(value) {
+                self.changes.push(SettingsChange::Value(name.clone()));
+            }
+        }
+        for name in applied.values.keys() {
+            if !settings.values.contains_key(name) {
+                self.changes.push(SettingsChange::Value(name.clone()));
+            }
+        }
+
+        self.applied = self.settings.clone();
+    }
+}
+impl Default for SettingsStore {
+    fn default() -> Self {
+        Self::new(Settings::default())
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    fn settings() -> Settings {
+        let mut settings = Settings {
+            resolution: (1920, 1080),
+            fullscreen: true,
+            vsync: true,
+            volume: 0.35,
+            quality: Some(QualityPreset::High),
+            ..Default::default()
+        };
+        settings.bindings.insert(
+            "jump".into(),
+            vec![
+                "Key:Space".parse().unwrap(),
+                "GamepadButton:South".parse().unwrap(),
+            ],
+        );
+        settings
+            .bindings
+            .insert("an action = with, symbols".into(), Vec::new());
+        settings.set_value(
+            "player name",
+            "first line\nsecond line = \"quoted\", # not a comment",
+        );
+        settings.set_value("empty", "");
+        settings.set_value("padded", "  spaces around  ");
+
+        settings
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let settings = settings();
+        let text = settings.to_toml().unwrap();
+        assert_eq!(Settings::from_toml(&text).unwrap(), settings);
+
+        let settings = Settings::default();
+        let text = settings.to_toml().unwrap();
+        assert_eq!(Settings::from_toml(&text).unwrap(), settings);
+    }
+
+    #[test]
+    fn missing_settings_take_their_default() {
+        let settings = Settings::from_toml("vsync = true\n[values]\nname = \"player\"").unwrap();
+        assert!(settings.vsync);
+        assert_eq!(settings.value("name"), Some("player"));
+        assert_eq!(settings.resolution, Settings::default().resolution);
+        assert!(settings.bindings.is_empty());
+    }
+
+    #[test]
+    fn malformed_settings_are_an_error() {
+        assert!(Settings::from_toml("volume = \"loud\"").is_err());
+        assert!(Settings::from_toml("[bindings]\njump = [\"Key:NotAKey\"]").is_err());
+        assert!(Settings::from_toml("resolution = [").is_err());
+    }
+
+    #[test]
+    fn settings_are_saved_and_loaded() {
+        let directory =
+            std::env::temp_dir().join(format!("blue_engine_settings_{}", std::process::id()));
+        let mut store = SettingsStore::default();
+        store.set_path(directory.join(SETTINGS_FILE_NAME));
+        assert!(!store.load().unwrap());
+
+        store.settings = settings();
+        store.save().unwrap();
+        let mut loaded = SettingsStore::default();
+        loaded.set_path(directory.join(SETTINGS_FILE_NAME));
+        assert!(loaded.load().unwrap());
+        assert_eq!(loaded.settings, store.settings);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,235 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use super::default_resources::SHADER_SANDBOX_SHADER;
+use crate::{MouseButton, error::Error, objects::ShaderBuilder};
+
+/// The placeholder the sandbox uniforms and entry points are substituted for
+const SANDBOX_PLACEHOLDER: &str = "//@SHADER_SANDBOX";
+
+/// The uniforms of the sandbox, laid out as `SandboxUniforms` in the shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SandboxUniforms {
+    mouse: [f32; 4],
+    resolution: [f32; 2],
+    time: f32,
+    delta_time: f32,
+    frame: u32,
+    _padding: [u32; 3],
+}
+
+/// A Shadertoy style full screen shader, for quick WGSL experiments inside the engine.
+///
+/// The source only needs a `fn main_image(frag_coord: vec2<f32>) -> vec4<f32>`, which is
+/// called for every pixel with its position in pixels from the top left. The `sandbox`
+/// uniform holds `mouse`, `resolution`, `time`, `delta_time`, and `frame`. The shader is drawn
+/// over the scene once added as a signal:
+///
+/// ```ignore
+/// engine.signals.add_signal("sandbox", Box::new(ShaderSandbox::new(source)?));
+/// ```
+pub struct ShaderSandbox {
+    /// Should the shader be drawn
+    pub enabled: bool,
+    shader_builder: ShaderBuilder,
+    started: crate::time::Instant,
+    frame: u32,
+    /// The uniform buffer, its bind group, the pipeline and the format it was built for
+    pipeline: Option<(
+        wgpu::TextureFormat,
+        wgpu::Buffer,
+        wgpu::BindGroup,
+        wgpu::RenderPipeline,
+    )>,
+}
+
+impl ShaderSandbox {
+    /// Creates a new sandbox with the shader source. Returns an error mapped to the lines of
+    /// the source if it fails to compile
+    pub fn new(source: impl Into<String>) -> Result<Self, Error> {
+        let mut sandbox = Self {
+            enabled: true,
+            shader_builder: ShaderBuilder::new(String::new(), None),
+            started: crate::time::Instant::now(),
+            frame: 0,
+            pipeline: None,
+        };
+        sandbox.shader_builder.configs.push((
+            SANDBOX_PLACEHOLDER.to_string(),
+            Box::new(|_| SHADER_SANDBOX_SHADER.to_string()),
+        ));
+        sandbox.set_source(source)?;
+
+        Ok(sandbox)
+    }
+
+    /// Replaces the shader source, such as when the file is edited. The old shader is kept if
+    /// the new one fails to compile
+    pub fn set_source(&mut self, source: impl Into<String>) -> Result<(), Error> {
+        let old_source = std::mem::take(&mut self.shader_builder.source);
+
+        // the sandbox code goes after the source, so the lines of the source stay the same
+        self.shader_builder
+            .set_shader(format!("{}\n{SANDBOX_PLACEHOLDER}", source.into()));
+        if let Err(e) = self.shader_builder.validate() {
+            self.shader_builder.set_shader(old_source);
+            return Err(e);
+        }
+        self.pipeline = None;
+
+        Ok(())
+    }
+
+    /// Restarts the time and the frame count
+    pub fn restart(&mut self) {
+        self.started = crate::time::Instant::now();
+        self.frame = 0;
+    }
+
+    fn build_pipeline(
+        &self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> (wgpu::Buffer, wgpu::BindGroup, wgpu::RenderPipeline) {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shader Sandbox Shader"),
+            source: wgpu::ShaderSource::Wgsl(self.shader_builder.shader.as_str().into()),
+        });
+
+        crate::utils::allocation_audit::count_buffer();
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shader Sandbox Uniforms"),
+            size: std::mem::size_of::<SandboxUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shader Sandbox Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        crate::utils::allocation_audit::count_bind_group();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shader Sandbox Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shader Sandbox Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shader Sandbox Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    write_mask: wgpu::ColorWrites::ALL,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        (uniform_buffer, bind_group, pipeline)
+    }
+}
+
+impl crate::Signal for ShaderSandbox {
+    fn frame(
+        &mut self,
+        renderer: &mut crate::Renderer,
+        _window: &crate::Window,
+        _objects: &mut crate::ObjectStorage,
+        _camera: &mut crate::CameraContainer,
+        input: &crate::InputHelper,
+        encoder: &mut crate::CommandEncoder,
+        view: &crate::TextureView,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let format = renderer.config.format;
+        if self.pipeline.as_ref().map(|i| i.0) != Some(format) {
+            let (buffer, bind_group, pipeline) = self.build_pipeline(&renderer.device, format);
+            self.pipeline = Some((format, buffer, bind_group, pipeline));
+        }
+        let Some((_, buffer, bind_group, pipeline)) = self.pipeline.as_ref() else {
+            return;
+        };
+
+        let cursor = input.cursor().unwrap_or((0.0, 0.0));
+        let uniforms = SandboxUniforms {
+            mouse: [
+                cursor.0,
+                cursor.1,
+                input.mouse_held(MouseButton::Left) as u32 as f32,
+                input.mouse_held(MouseButton::Right) as u32 as f32,
+            ],
+            resolution: [renderer.config.width as f32, renderer.config.height as f32],
+            time: self.started.elapsed().as_secs_f32(),
+            delta_time: input.delta_time().map(|i| i.as_secs_f32()).unwrap_or(0.0),
+            frame: self.frame,
+            _padding: [0; 3],
+        };
+        renderer
+            .queue
+            .write_buffer(buffer, 0, bytemuck::bytes_of(&uniforms));
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shader Sandbox Render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        self.frame = self.frame.wrapping_add(1);
+    }
+}
@@ -0,0 +1,35 @@
+// The uniforms and entry points of the shader sandbox, added after the user's main_image
+
+struct SandboxUniforms {
+    // the cursor position in pixels, and if the left and right buttons are held
+    mouse: vec4<f32>,
+    // the size of the frame in pixels
+    resolution: vec2<f32>,
+    // seconds since the sandbox started
+    time: f32,
+    // seconds since the last frame
+    delta_time: f32,
+    // the amount of frames rendered since the sandbox started
+    frame: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> sandbox: SandboxUniforms;
+
+struct SandboxOutput {
+    @builtin(position) position: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> SandboxOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: SandboxOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: SandboxOutput) -> @location(0) vec4<f32> {
+    return main_image(in.position.xy);
+}
@@ -0,0 +1,110 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{Renderer, Vector4};
+
+/// The seed and time offset of an object, given to its shader next to its color, so the
+/// objects sharing a material, such as waving flags or flickering lights, do not animate in
+/// lockstep. See [crate::Object::set_variation].
+///
+/// The shaders read them with the helpers of the `//@VARIATION` placeholder:
+/// `object_time()` is the time of the shaders plus the offset of the object, and
+/// `instance_random(instance_index)` is a number from 0 to 1 that differs for each object
+/// and each of its instances. The default shaders declare the `fragment_uniforms` they need
+///
+/// ```ignore
+/// let sway = sin(object_time() * 2.0 + instance_random(instance_index) * 6.28);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ShaderVariation {
+    /// The seed the random numbers of the shader are made from
+    pub seed: u32,
+    /// The seconds the object is ahead of the time of the shaders
+    pub time_offset: f32,
+}
+
+impl ShaderVariation {
+    /// Creates a new variation
+    pub fn new(seed: u32, time_offset: f32) -> Self {
+        Self { seed, time_offset }
+    }
+
+    /// A variation with a random seed, and a random time offset of up to the given seconds
+    pub fn random(max_time_offset: f32) -> Self {
+        use std::sync::atomic::{AtomicU32, Ordering};
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+        let nanos = crate::time::SystemTime::now()
+            .duration_since(crate::time::UNIX_EPOCH)
+            .map(|i| i.subsec_nanos())
+            .unwrap_or_default();
+        let seed = hash(nanos ^ hash(COUNTER.fetch_add(1, Ordering::Relaxed)));
+
+        Self {
+            seed,
+            time_offset: unit(hash(seed)) * max_time_offset.max(0.0),
+        }
+    }
+
+    /// The seed as a number from 0 to 1, the same one the shaders see as `random`
+    pub fn random_value(&self) -> f32 {
+        unit(hash(self.seed))
+    }
+}
+
+/// The color and variation of an object, laid out as `FragmentUniforms` in the default
+/// shaders. Shaders declaring only the color still work
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FragmentUniform {
+    /// The color of the object
+    pub color: [f32; 4],
+    /// The seed of the variation as a number from 0 to 1
+    pub random: f32,
+    /// The seconds the object is ahead of the time of the shaders
+    pub time_offset: f32,
+    /// The seed of the variation
+    pub seed: u32,
+    padding: u32,
+}
+
+impl FragmentUniform {
+    /// The uniform of the color and variation
+    pub fn new(color: Vector4, variation: ShaderVariation) -> Self {
+        Self {
+            color: color.to_array(),
+            random: variation.random_value(),
+            time_offset: variation.time_offset,
+            seed: variation.seed,
+            padding: 0,
+        }
+    }
+}
+
+/// The PCG hash, the same as `hash_u32` of the shaders
+pub(crate) fn hash(value: u32) -> u32 {
+    let state = value.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+/// A hash as a number from 0 to 1
+fn unit(hash: u32) -> f32 {
+    hash as f32 / u32::MAX as f32
+}
+
+impl Renderer {
+    /// The seconds since the time of the shaders started, given to them with the uniforms of
+    /// the cameras
+    pub fn shader_time(&self) -> f32 {
+        self.shader_clock.elapsed().as_secs_f32()
+    }
+
+    /// Starts the time of the shaders from zero again
+    pub fn restart_shader_time(&mut self) {
+        self.shader_clock = crate::time::Instant::now();
+    }
+}
@@ -0,0 +1,881 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::sync::Arc;
+
+use super::default_resources::SHADOW_ATLAS_SHADER;
+use crate::{
+    InstanceRaw, Matrix4, ObjectStorage, StringBuffer, Vector3, Vertex,
+    render::get_pipeline_vertex_buffer,
+};
+
+/// The size of the atlas on each side when it is created
+pub const DEFAULT_ATLAS_SIZE: u32 = 4096;
+/// The smallest a tile is made when the atlas is full
+pub const MIN_TILE_SIZE: u32 = 16;
+/// The distance between the uniforms of the tiles and of the objects, which is a multiple of
+/// the offset alignment of every device
+const UNIFORM_STRIDE: u64 = 256;
+/// The directions and up vectors of the faces of the cube around a point light
+const CUBE_FACES: [(Vector3, Vector3); 6] = [
+    (Vector3::X, Vector3::NEG_Y),
+    (Vector3::NEG_X, Vector3::NEG_Y),
+    (Vector3::Y, Vector3::Z),
+    (Vector3::NEG_Y, Vector3::NEG_Z),
+    (Vector3::Z, Vector3::NEG_Y),
+    (Vector3::NEG_Z, Vector3::NEG_Y),
+];
+
+/// The shape of the light a shadow map is drawn for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadowLightKind {
+    /// A cone of light, with the full angle of the cone in radians
+    Spot {
+        /// The direction the light points at
+        direction: Vector3,
+        /// The angle of the cone
+        angle: f32,
+    },
+    /// Light in every direction, with a tile for each face of a cube around it, in the order
+    /// of +X, -X, +Y, -Y, +Z and -Z
+    Point,
+}
+
+/// A light that casts shadows through a tile of the [ShadowAtlas]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowLight {
+    /// The position of the light
+    pub position: Vector3,
+    /// The shape of the light
+    pub kind: ShadowLightKind,
+    /// The farthest distance the light casts shadows over
+    pub range: f32,
+    /// The size of each of its tiles, rounded up to a power of two. They are made smaller
+    /// when the tiles of all the lights do not fit in the atlas
+    pub resolution: u32,
+}
+
+impl ShadowLight {
+    /// Creates a spot light pointing at the direction, with a cone of the angle in radians
+    pub fn spot(
+        position: impl Into<Vector3>,
+        direction: impl Into<Vector3>,
+        angle: f32,
+        range: f32,
+    ) -> Self {
+        Self {
+            position: position.into(),
+            kind: ShadowLightKind::Spot {
+                direction: direction.into(),
+                angle,
+            },
+            range,
+            resolution: 1024,
+        }
+    }
+
+    /// Creates a point light, shining in every direction
+    pub fn point(position: impl Into<Vector3>, range: f32) -> Self {
+        Self {
+            position: position.into(),
+            kind: ShadowLightKind::Point,
+            range,
+            resolution: 512,
+        }
+    }
+
+    /// Changes the size of the tiles of the light
+    pub fn with_resolution(mut self, resolution: u32) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    /// The transform from the world to each tile of the light
+    pub fn view_projections(&self) -> Vec<Matrix4> {
+        let near = (self.range * 0.001).max(0.01);
+        let far = self.range.max(near * 2.0);
+        match self.kind {
+            ShadowLightKind::Spot { direction, angle } => {
+                let direction = direction.normalize_or(Vector3::NEG_Z);
+                let up = match direction.y.abs() > 0.99 {
+                    true => Vector3::Z,
+                    false => Vector3::Y,
+                };
+                let projection = Matrix4::perspective_rh(
+                    angle.clamp(0.01, std::f32::consts::PI - 0.01),
+                    1.0,
+                    near,
+                    far,
+                );
+                vec![projection * Matrix4::look_to_rh(self.position, direction, up)]
+            }
+            ShadowLightKind::Point => {
+                let projection =
+                    Matrix4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, near, far);
+                CUBE_FACES
+                    .iter()
+                    .map(|(direction, up)| {
+                        projection * Matrix4::look_to_rh(self.position, *direction, *up)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// The place of a shadow map in the atlas, and the transform from the world into it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowTile {
+    /// The corner of the tile in the atlas, in texels
+    pub offset: (u32, u32),
+    /// The size of the tile on each side, in texels
+    pub size: u32,
+    /// The transform from the world to the clip space of the tile
+    pub view_projection: Matrix4,
+}
+
+/// A tile as the shaders read it from [ShadowAtlas::tile_buffer]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TileData {
+    view_projection: [[f32; 4]; 4],
+    /// The corner and size of the tile in texture coordinates of the atlas
+    rect: [f32; 4],
+}
+
+/// The uniform buffers of the tiles and the objects, their bind groups, and how many fit
+#[derive(Debug)]
+struct Uniforms {
+    tiles: (wgpu::Buffer, wgpu::BindGroup, usize),
+    objects: (wgpu::Buffer, wgpu::BindGroup, usize),
+}
+
+/// The shadow maps of many spot and point lights packed into a single depth texture, each
+/// light drawn into its own tiles with a viewport. One texture and one bind group serve all
+/// the lights, however many there are, and their tiles are packed again every frame as the
+/// lights are added, removed or change resolution.
+///
+/// The tiles are squares of a power of two, and are all halved when they do not fit, down
+/// to [MIN_TILE_SIZE]. The lights whose tiles still do not fit have no shadows. The visible
+/// objects of the camera cast shadows, and the atlas is drawn before the main pass so its
+/// objects can read it.
+///
+/// The atlas is read through [ShadowAtlas::view], or as
+/// [crate::utils::frame_graph::SHADOW_ATLAS] by the passes of the frame graph. The tiles are
+/// in [ShadowAtlas::tile_buffer], starting at [ShadowAtlas::first_tile] of each light, as
+/// an array of:
+///
+/// ```wgsl
+/// struct ShadowTile {
+///     view_projection: mat4x4<f32>,
+///     // the corner and size of the tile in texture coordinates of the atlas
+///     rect: vec4<f32>,
+/// };
+/// ```
+///
+/// ```ignore
+/// renderer.shadow_atlas.enabled = true;
+/// renderer.shadow_atlas.set_light("lamp", ShadowLight::point((0.0, 3.0, 0.0), 20.0));
+/// renderer.shadow_atlas.set_light(
+///     "torch",
+///     ShadowLight::spot((2.0, 2.0, 0.0), (0.0, -1.0, 0.0), 0.8, 15.0).with_resolution(2048),
+/// );
+/// ```
+#[derive(Debug)]
+pub struct ShadowAtlas {
+    /// Should the shadow maps be drawn
+    pub enabled: bool,
+    /// The size of the atlas on each side, rounded up to a power of two
+    pub size: u32,
+    /// Multiplies the resolution of every light, such as 0.5 for half the size
+    pub resolution_scale: f32,
+    /// The camera whose objects cast shadows
+    pub camera: Arc<str>,
+    lights: Vec<(Arc<str>, ShadowLight)>,
+    /// The tiles of all the lights, and the range of each light in them
+    tiles: Vec<ShadowTile>,
+    light_tiles: Vec<(Arc<str>, std::ops::Range<usize>)>,
+    /// The depth texture, its view, and its size
+    target: Option<(u32, wgpu::Texture, wgpu::TextureView)>,
+    /// The layouts of the tiles and the objects, and the pipeline
+    pipeline: Option<(
+        wgpu::BindGroupLayout,
+        wgpu::BindGroupLayout,
+        wgpu::RenderPipeline,
+    )>,
+    uniforms: Option<Uniforms>,
+    /// The tiles for the shaders, and how many fit
+    tile_buffer: Option<(wgpu::Buffer, usize)>,
+}
+
+impl ShadowAtlas {
+    /// Creates the atlas with no lights, disabled
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            size: DEFAULT_ATLAS_SIZE,
+            resolution_scale: 1.0,
+            camera: "main".into(),
+            lights: Vec::new(),
+            tiles: Vec::new(),
+            light_tiles: Vec::new(),
+            target: None,
+            pipeline: None,
+            uniforms: None,
+            tile_buffer: None,
+        }
+    }
+
+    /// Drops the atlas, its pipeline, and its buffers. The lights are kept
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.target = None;
+        self.pipeline = None;
+        self.uniforms = None;
+        self.tile_buffer = None;
+    }
+
+    /// Adds the light, or replaces the light of the same name
+    pub fn set_light(&mut self, name: impl StringBuffer, light: ShadowLight) {
+        let name = name.as_arc();
+        match self.lights.iter_mut().find(|i| i.0 == name) {
+            Some(existing) => existing.1 = light,
+            None => self.lights.push((name, light)),
+        }
+    }
+
+    /// Removes the light, returns it if there was one
+    pub fn remove_light(&mut self, name: impl StringBuffer) -> Option<ShadowLight> {
+        let index = self.lights.iter().position(|i| *i.0 == *name.as_str())?;
+        Some(self.lights.remove(index).1)
+    }
+
+    /// The light of the name, to change it
+    pub fn light_mut(&mut self, name: impl StringBuffer) -> Option<&mut ShadowLight> {
+        self.lights
+            .iter_mut()
+            .find(|i| *i.0 == *name.as_str())
+            .map(|i| &mut i.1)
+    }
+
+    /// The lights with their names, in the order they were added
+    pub fn lights(&self) -> impl Iterator<Item = (&str, &ShadowLight)> {
+        self.lights
+            .iter()
+            .map(|(name, light)| (name.as_ref(), light))
+    }
+
+    /// The tiles of the light in the latest frame, empty if they did not fit
+    pub fn tiles(&self, name: impl StringBuffer) -> &[ShadowTile] {
+        self.tile_range(name.as_str())
+            .map(|i| &self.tiles[i])
+            .unwrap_or_default()
+    }
+
+    /// The index of the first tile of the light in [ShadowAtlas::tile_buffer]
+    pub fn first_tile(&self, name: impl StringBuffer) -> Option<usize> {
+        self.tile_range(name.as_str()).map(|i| i.start)
+    }
+
+    /// The depth of the latest frame, if the atlas is enabled
+    pub fn view(&self) -> Option<&wgpu::TextureView> {
+        match self.enabled {
+            true => self.target.as_ref().map(|i| &i.2),
+            false => None,
+        }
+    }
+
+    /// The depth texture of the latest frame, if the atlas is enabled
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        match self.enabled {
+            true => self.target.as_ref().map(|i| &i.1),
+            false => None,
+        }
+    }
+
+    /// The storage buffer of the tiles of all the lights, if the atlas is enabled
+    pub fn tile_buffer(&self) -> Option<&wgpu::Buffer> {
+        match self.enabled {
+            true => self.tile_buffer.as_ref().map(|i| &i.0),
+            false => None,
+        }
+    }
+
+    fn tile_range(&self, name: &str) -> Option<std::ops::Range<usize>> {
+        self.light_tiles
+            .iter()
+            .find(|i| *i.0 == *name)
+            .map(|i| i.1.clone())
+            .filter(|i| !i.is_empty())
+    }
+
+    /// Places the tiles of the lights in the atlas, largest first
+    fn pack(&mut self) {
+        let size = self.size.max(MIN_TILE_SIZE).next_power_of_two();
+        let lights = self
+            .lights
+            .iter()
+            .map(|(name, light)| {
+                let resolution = ((light.resolution as f32 * self.resolution_scale) as u32)
+                    .clamp(MIN_TILE_SIZE, size)
+                    .next_power_of_two();
+                (name.clone(), resolution, light.view_projections())
+            })
+            .collect::<Vec<_>>();
+
+        // the tiles are halved until they fit, or can not be made smaller
+        let area = |shift: u32| {
+            lights
+                .iter()
+                .map(|(_, resolution, faces)| {
+                    let tile = (resolution >> shift).max(MIN_TILE_SIZE) as u64;
+                    tile * tile * faces.len() as u64
+                })
+                .sum::<u64>()
+        };
+        let mut shift = 0;
+        while area(shift) > size as u64 * size as u64 && (size >> shift) > MIN_TILE_SIZE {
+            shift += 1;
+        }
+
+        let mut order = (0..lights.len()).collect::<Vec<_>>();
+        order.sort_by_key(|i| std::cmp::Reverse(lights[*i].1));
+
+        // squares of powers of two placed from largest to smallest along a Z-order curve
+        // fill the atlas without gaps, as each starts at a multiple of its own area
+        let mut placed = vec![0..0; lights.len()];
+        self.tiles.clear();
+        let mut used = 0u64;
+        for index in order {
+            let (_, resolution, faces) = &lights[index];
+            let tile = (resolution >> shift).max(MIN_TILE_SIZE);
+            let tile_area = tile as u64 * tile as u64;
+            if used + tile_area * faces.len() as u64 > size as u64 * size as u64 {
+                continue;
+            }
+
+            let start = self.tiles.len();
+            for view_projection in faces {
+                let (x, y) = morton_decode(used / tile_area);
+                self.tiles.push(ShadowTile {
+                    offset: (x * tile, y * tile),
+                    size: tile,
+                    view_projection: *view_projection,
+                });
+                used += tile_area;
+            }
+            placed[index] = start..self.tiles.len();
+        }
+
+        self.light_tiles = lights
+            .into_iter()
+            .zip(placed)
+            .map(|((name, _, _), range)| (name, range))
+            .collect();
+    }
+
+    /// Packs the tiles and draws the depth of the objects of the camera into them
+    pub(crate) fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        objects: &ObjectStorage,
+        mesh_pool: &crate::utils::mesh_pool::MeshPool,
+    ) {
+        self.pack();
+        let size = self.size.max(MIN_TILE_SIZE).next_power_of_two();
+        if self.target.as_ref().map(|i| i.0) != Some(size) {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Shadow Atlas"),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: crate::DEPTH_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            self.target = Some((size, texture, view));
+        }
+
+        let casters = objects
+            .values()
+            .filter(|i| {
+                // the quantized vertices can not be read with the layout of the shadows
+                i.is_visible
+                    && !i.shader_settings.quantized_vertices
+                    && i.camera_effect.as_deref() == Some(self.camera.as_ref())
+            })
+            .filter_map(|i| {
+                Some((
+                    i,
+                    get_pipeline_vertex_buffer(&i.pipeline.vertex_buffer, objects)?,
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let tile_data = self
+            .tiles
+            .iter()
+            .map(|i| TileData {
+                view_projection: i.view_projection.to_cols_array_2d(),
+                rect: [
+                    i.offset.0 as f32 / size as f32,
+                    i.offset.1 as f32 / size as f32,
+                    i.size as f32 / size as f32,
+                    i.size as f32 / size as f32,
+                ],
+            })
+            .collect::<Vec<_>>();
+        if self
+            .tile_buffer
+            .as_ref()
+            .is_none_or(|i| i.1 < tile_data.len())
+        {
+            let capacity = tile_data.len().next_power_of_two().max(16);
+            crate::utils::allocation_audit::count_buffer();
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Shadow Atlas Tile Buffer"),
+                size: capacity as u64 * std::mem::size_of::<TileData>() as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            self.tile_buffer = Some((buffer, capacity));
+        }
+        if let (Some((buffer, _)), false) = (self.tile_buffer.as_ref(), tile_data.is_empty()) {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(tile_data.as_slice()));
+        }
+
+        if self.pipeline.is_none() {
+            self.pipeline = Some(Self::build_pipeline(device));
+        }
+        let Some((tile_layout, object_layout, pipeline)) = self.pipeline.as_ref() else {
+            return;
+        };
+        let grow = |current: Option<&(wgpu::Buffer, wgpu::BindGroup, usize)>,
+                    needed: usize,
+                    layout: &wgpu::BindGroupLayout,
+                    label: &str| {
+            if current.is_some_and(|i| i.2 >= needed) {
+                return None;
+            }
+            let capacity = needed.next_power_of_two().max(64);
+            crate::utils::allocation_audit::count_buffer();
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: capacity as u64 * UNIFORM_STRIDE,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            crate::utils::allocation_audit::count_bind_group();
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<Matrix4>() as u64),
+                    }),
+                }],
+            });
+            Some((buffer, bind_group, capacity))
+        };
+        let tiles = grow(
+            self.uniforms.as_ref().map(|i| &i.tiles),
+            self.tiles.len(),
+            tile_layout,
+            "Shadow Atlas Tile Uniforms",
+        );
+        let casters_uniforms = grow(
+            self.uniforms.as_ref().map(|i| &i.objects),
+            casters.len(),
+            object_layout,
+            "Shadow Atlas Object Uniforms",
+        );
+        match (self.uniforms.as_mut(), tiles, casters_uniforms) {
+            (Some(uniforms), tiles, objects) => {
+                if let Some(tiles) = tiles {
+                    uniforms.tiles = tiles;
+                }
+                if let Some(objects) = objects {
+                    uniforms.objects = objects;
+                }
+            }
+            (None, Some(tiles), Some(objects)) => {
+                self.uniforms = Some(Uniforms { tiles, objects });
+            }
+            _ => {}
+        }
+        let (Some(uniforms), Some((_, _, depth_view))) =
+            (self.uniforms.as_ref(), self.target.as_ref())
+        else {
+            return;
+        };
+
+        let write_strided = |buffer: &wgpu::Buffer, matrices: &mut dyn Iterator<Item = Matrix4>| {
+            let mut data = Vec::new();
+            for matrix in matrices {
+                data.extend_from_slice(bytemuck::bytes_of(&matrix.to_cols_array()));
+                data.resize(data.len().next_multiple_of(UNIFORM_STRIDE as usize), 0);
+            }
+            if !data.is_empty() {
+                queue.write_buffer(buffer, 0, data.as_slice());
+            }
+        };
+        write_strided(
+            &uniforms.tiles.0,
+            &mut self.tiles.iter().map(|i| i.view_projection),
+        );
+        write_strided(
+            &uniforms.objects.0,
+            &mut casters
+                .iter()
+                .map(|(object, _)| object.transformation_matrix()),
+        );
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Atlas Render pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(pipeline);
+        for (tile_index, tile) in self.tiles.iter().enumerate() {
+            render_pass.set_viewport(
+                tile.offset.0 as f32,
+                tile.offset.1 as f32,
+                tile.size as f32,
+                tile.size as f32,
+                0.0,
+                1.0,
+            );
+            render_pass.set_scissor_rect(tile.offset.0, tile.offset.1, tile.size, tile.size);
+            render_pass.set_bind_group(
+                0,
+                &uniforms.tiles.1,
+                &[tile_index as u32 * UNIFORM_STRIDE as u32],
+            );
+
+            for (index, (object, vertex_buffer)) in casters.iter().enumerate() {
+                if vertex_buffer.length == 0 {
+                    continue;
+                }
+                render_pass.set_bind_group(
+                    1,
+                    &uniforms.objects.1,
+                    &[index as u32 * UNIFORM_STRIDE as u32],
+                );
+                let pooled = crate::utils::mesh_pool::allocation_of(object, objects)
+                    .zip(mesh_pool.buffers());
+                let (vertices, indices, first_index, base_vertex) = match pooled {
+                    Some((allocation, (vertices, indices))) => (
+                        vertices,
+                        indices,
+                        allocation.indices().start,
+                        allocation.base_vertex(),
+                    ),
+                    None => (
+                        &vertex_buffer.vertex_buffer,
+                        &vertex_buffer.index_buffer,
+                        0,
+                        0,
+                    ),
+                };
+                render_pass.set_vertex_buffer(0, vertices.slice(..));
+                render_pass.set_vertex_buffer(1, object.instance_buffer.slice(..));
+                render_pass.set_index_buffer(
+                    indices.slice(..),
+                    #[cfg(not(feature = "u32"))]
+                    wgpu::IndexFormat::Uint16,
+                    #[cfg(feature = "u32")]
+                    wgpu::IndexFormat::Uint32,
+                );
+                render_pass.draw_indexed(
+                    first_index..first_index + vertex_buffer.length,
+                    base_vertex,
+                    0..object.instances.len() as _,
+                );
+            }
+        }
+    }
+
+    fn build_pipeline(
+        device: &wgpu::Device,
+    ) -> (
+        wgpu::BindGroupLayout,
+        wgpu::BindGroupLayout,
+        wgpu::RenderPipeline,
+    ) {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Atlas Shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADOW_ATLAS_SHADER.into()),
+        });
+
+        let uniform_layout = |label| {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some(label),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<Matrix4>() as u64
+                        ),
+                    },
+                    count: None,
+                }],
+            })
+        };
+        let tile_layout = uniform_layout("Shadow Atlas Tile Bind Group Layout");
+        let object_layout = uniform_layout("Shadow Atlas Object Bind Group Layout");
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Atlas Pipeline Layout"),
+            bind_group_layouts: &[&tile_layout, &object_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Atlas Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc(), InstanceRaw::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: None,
+            // both faces cast shadows, as the objects may cull either
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: crate::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                // keeps the surfaces from shadowing themselves
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        (tile_layout, object_layout, pipeline)
+    }
+}
+impl Default for ShadowAtlas {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The position of the index along a Z-order curve
+fn morton_decode(index: u64) -> (u32, u32) {
+    let compact = |mut value: u64| {
+        value &= 0x5555_5555_5555_5555;
+        value = (value | (value >> 1)) & 0x3333_3333_3333_3333;
+        value = (value | (value >> 2)) & 0x0F0F_0F0F_0F0F_0F0F;
+        value = (value | (value >> 4)) & 0x00FF_00FF_00FF_00FF;
+        value = (value | (value >> 8)) & 0x0000_FFFF_0000_FFFF;
+        value = (value | (value >> 16)) & 0x0000_0000_FFFF_FFFF;
+        value as u32
+    };
+
+    (compact(index), compact(index >> 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks the tiles are inside the atlas, are powers of two, and do not overlap
+    fn check_tiles(atlas: &ShadowAtlas) {
+        let size = atlas.size.next_power_of_two();
+        for (index, tile) in atlas.tiles.iter().enumerate() {
+            assert!(tile.size.is_power_of_two() && tile.size >= MIN_TILE_SIZE);
+            assert!(tile.offset.0 + tile.size <= size && tile.offset.1 + tile.size <= size);
+
+            for other in atlas.tiles[index + 1..].iter() {
+                let apart = tile.offset.0 + tile.size <= other.offset.0
+                    || other.offset.0 + other.size <= tile.offset.0
+                    || tile.offset.1 + tile.size <= other.offset.1
+                    || other.offset.1 + other.size <= tile.offset.1;
+                assert!(apart, "{tile:?} overlaps {other:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn morton_order() {
+        let positions: Vec<_> = (0..8).map(morton_decode).collect();
+        assert_eq!(
+            positions,
+            [
+                (0, 0),
+                (1, 0),
+                (0, 1),
+                (1, 1),
+                (2, 0),
+                (3, 0),
+                (2, 1),
+                (3, 1)
+            ]
+        );
+        assert_eq!(
+            morton_decode(u32::MAX as u64),
+            (u16::MAX as u32, u16::MAX as u32)
+        );
+    }
+
+    #[test]
+    fn tiles_of_spot_and_point_lights() {
+        let mut atlas = ShadowAtlas::new();
+        atlas.size = 2048;
+        atlas.set_light(
+            "torch",
+            ShadowLight::spot(Vector3::ZERO, Vector3::NEG_Y, 0.8, 10.0),
+        );
+        atlas.set_light("lamp", ShadowLight::point(Vector3::Y, 10.0));
+        atlas.pack();
+
+        assert_eq!(atlas.tiles("torch").len(), 1);
+        assert_eq!(atlas.tiles("torch")[0].size, 1024);
+        assert_eq!(atlas.tiles("lamp").len(), 6);
+        assert!(atlas.tiles("lamp").iter().all(|i| i.size == 512));
+        assert_eq!(atlas.first_tile("torch"), Some(0));
+        assert_eq!(atlas.first_tile("lamp"), Some(1));
+        assert!(atlas.tiles("missing").is_empty());
+        check_tiles(&atlas);
+    }
+
+    #[test]
+    fn tiles_are_halved_until_they_fit() {
+        let mut atlas = ShadowAtlas::new();
+        atlas.size = 1024;
+        for i in 0..4 {
+            atlas.set_light(
+                format!("spot {i}"),
+                ShadowLight::spot(Vector3::ZERO, Vector3::X, 1.0, 5.0).with_resolution(1024),
+            );
+        }
+        atlas.pack();
+
+        // four tiles of 1024 only fit in 1024 at half the size
+        for i in 0..4 {
+            assert_eq!(atlas.tiles(format!("spot {i}"))[0].size, 512);
+        }
+        check_tiles(&atlas);
+
+        atlas.resolution_scale = 0.25;
+        atlas.pack();
+        assert_eq!(atlas.tiles("spot 0")[0].size, 256);
+        check_tiles(&atlas);
+    }
+
+    #[test]
+    fn lights_that_do_not_fit_have_no_tiles() {
+        let mut atlas = ShadowAtlas::new();
+        atlas.size = MIN_TILE_SIZE * 2;
+        atlas.set_light("lamp", ShadowLight::point(Vector3::ZERO, 5.0));
+        atlas.set_light(
+            "torch",
+            ShadowLight::spot(Vector3::ZERO, Vector3::X, 1.0, 5.0),
+        );
+        atlas.pack();
+
+        // the six faces of the point light can not fit in four of the smallest tiles
+        assert!(atlas.tiles("lamp").is_empty());
+        assert_eq!(atlas.first_tile("lamp"), None);
+        assert_eq!(atlas.tiles("torch").len(), 1);
+        assert_eq!(atlas.tiles("torch")[0].size, MIN_TILE_SIZE);
+        check_tiles(&atlas);
+    }
+
+    #[test]
+    fn mixed_resolutions_pack_without_gaps() {
+        let mut atlas = ShadowAtlas::new();
+        atlas.size = 4096;
+        let mut seed = 7u32;
+        for i in 0..40 {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let resolution = 16 << ((seed >> 28) % 7);
+            let light = match seed & 1 {
+                0 => ShadowLight::point(Vector3::ZERO, 5.0),
+                _ => ShadowLight::spot(Vector3::ZERO, Vector3::X, 1.0, 5.0),
+            };
+            atlas.set_light(format!("light {i}"), light.with_resolution(resolution));
+        }
+        atlas.pack();
+        check_tiles(&atlas);
+
+        // the lights were placed largest first, so the used area is one run from the start
+        let used: u64 = atlas
+            .tiles
+            .iter()
+            .map(|i| i.size as u64 * i.size as u64)
+            .sum();
+        let last_end = atlas
+            .tiles
+            .iter()
+            .map(|i| {
+                let tile_area = i.size as u64 * i.size as u64;
+                let index = (0..)
+                    .find(|index| {
+                        morton_decode(*index) == (i.offset.0 / i.size, i.offset.1 / i.size)
+                    })
+                    .unwrap();
+                (index + 1) * tile_area
+            })
+            .max()
+            .unwrap_or_default();
+        assert_eq!(used, last_end);
+    }
+
+    #[test]
+    fn replacing_and_removing_lights() {
+        let mut atlas = ShadowAtlas::new();
+        atlas.set_light("lamp", ShadowLight::point(Vector3::ZERO, 5.0));
+        atlas.set_light(
+            "lamp",
+            ShadowLight::spot(Vector3::ZERO, Vector3::X, 1.0, 5.0),
+        );
+        assert_eq!(atlas.lights().count(), 1);
+        atlas.light_mut("lamp").unwrap().resolution = 64;
+        atlas.pack();
+        assert_eq!(atlas.tiles("lamp").len(), 1);
+        assert_eq!(atlas.tiles("lamp")[0].size, 64);
+
+        assert!(atlas.remove_light("lamp").is_some());
+        assert!(atlas.remove_light("lamp").is_none());
+        atlas.pack();
+        assert!(atlas.tiles.is_empty());
+        assert!(atlas.tiles("lamp").is_empty());
+    }
+}
@@ -0,0 +1,36 @@
+// The depth of the objects seen from a light, drawn into the tile of the light in the shadow
+// atlas. There is no fragment stage, only the depth is kept
+
+struct TileUniform {
+    view_projection: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> tile: TileUniform;
+
+struct CasterUniform {
+    transform: mat4x4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> caster: CasterUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(3) model_matrix_0: vec4<f32>,
+    @location(4) model_matrix_1: vec4<f32>,
+    @location(5) model_matrix_2: vec4<f32>,
+    @location(6) model_matrix_3: vec4<f32>,
+};
+
+@vertex
+fn vs_main(input: VertexInput, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    return tile.view_projection * model_matrix * (caster.transform * vec4<f32>(input.position, 1.0));
+}
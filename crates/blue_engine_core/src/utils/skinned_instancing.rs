@@ -0,0 +1,439 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use wgpu::util::DeviceExt;
+
+use super::default_resources::{DEFAULT_SHADER, SKINNED_INSTANCING_SHADER};
+use crate::{Matrix4, ObjectStorage, Renderer, StringBuffer, error::Error, objects::ShaderBuilder};
+
+/// The bones a vertex follows, and how much it follows each. The weights are normalized when
+/// the crowd is added
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkinWeights {
+    /// The indices of the bones
+    pub joints: [u32; 4],
+    /// The weight of each bone, from 0 to 1
+    pub weights: [f32; 4],
+}
+
+impl SkinWeights {
+    /// A vertex that follows a single bone
+    pub fn single(joint: u32) -> Self {
+        Self {
+            joints: [joint, 0, 0, 0],
+            weights: [1.0, 0.0, 0.0, 0.0],
+        }
+    }
+
+    /// The weights scaled to add up to one, or the first bone alone if they are all zero
+    fn normalized(mut self) -> Self {
+        let total = self.weights.iter().sum::<f32>();
+        if total > f32::EPSILON {
+            self.weights.iter_mut().for_each(|i| *i /= total);
+        } else {
+            self.weights = [1.0, 0.0, 0.0, 0.0];
+        }
+
+        self
+    }
+}
+
+/// An animation sampled into the matrices of its bones at a fixed frame rate. Each matrix
+/// takes a vertex from the bind pose to where the bone has moved it, that is the world
+/// matrix of the bone times its inverse bind matrix
+#[derive(Debug, Clone, PartialEq)]
+pub struct BakedAnimation {
+    frame_rate: f32,
+    bone_count: u32,
+    matrices: Vec<Matrix4>,
+}
+
+impl BakedAnimation {
+    /// Creates the animation from the matrices of the bones of each frame. Returns an error if
+    /// there are no frames or bones, or if the frames do not have the same amount of bones
+    pub fn new(frame_rate: f32, frames: Vec<Vec<Matrix4>>) -> Result<Self, Error> {
+        let bone_count = frames.first().map_or(0, Vec::len);
+        if bone_count == 0 {
+            return Err(Error::Custom(
+                "The animation needs at least one frame with one bone".to_string(),
+            ));
+        }
+        if frames.iter().any(|i| i.len() != bone_count) {
+            return Err(Error::Custom(
+                "The frames of the animation do not have the same amount of bones".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            frame_rate: frame_rate.max(f32::EPSILON),
+            bone_count: bone_count as u32,
+            matrices: frames.into_iter().flatten().collect(),
+        })
+    }
+
+    /// The frames sampled every second
+    pub fn frame_rate(&self) -> f32 {
+        self.frame_rate
+    }
+
+    /// The amount of bones of each frame
+    pub fn bone_count(&self) -> u32 {
+        self.bone_count
+    }
+
+    /// The amount of frames
+    pub fn frame_count(&self) -> u32 {
+        self.matrices.len() as u32 / self.bone_count
+    }
+
+    /// The length of the animation in seconds, after which it loops
+    pub fn duration(&self) -> f32 {
+        self.frame_count() as f32 / self.frame_rate
+    }
+}
+
+/// The uniforms of a crowd, laid out as `SkinningInfo` in the shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkinningInfo {
+    time: f32,
+    frame_rate: f32,
+    frame_count: u32,
+    bone_count: u32,
+}
+
+/// The animation of the instances of an object, so crowds of characters are drawn in one
+/// draw call. See [Renderer::add_skinned_crowd].
+///
+/// The vertices are skinned in the vertex shader by the bones of the frame each instance is
+/// at. The instances are ahead of the time of the crowd by their own offset, so they do not
+/// move in step
+///
+/// ```ignore
+/// let animation = BakedAnimation::new(30.0, frames)?;
+/// renderer.add_skinned_crowd(&mut objects, "soldier", weights, animation, offsets)?;
+/// ```
+#[derive(Debug)]
+pub struct SkinnedCrowd {
+    /// Does the time of the crowd move forward. The instances stay where they are while paused
+    pub playing: bool,
+    /// How fast the time moves, 1 by default
+    pub speed: f32,
+    time: f32,
+    animation: BakedAnimation,
+    skin_weights: Vec<SkinWeights>,
+    time_offsets: Vec<f32>,
+    /// The info, bones, weights, and time offsets, bound from binding 2 of the object
+    buffers: [wgpu::Buffer; 4],
+}
+
+impl SkinnedCrowd {
+    /// The time of the crowd in seconds, which each instance is ahead of by its offset
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Sets the time of the crowd, such as to restart the animation with zero
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    /// The animation of the crowd
+    pub fn animation(&self) -> &BakedAnimation {
+        &self.animation
+    }
+
+    /// The time each instance is ahead in the animation, in seconds
+    pub fn time_offsets(&self) -> &[f32] {
+        &self.time_offsets
+    }
+
+    /// Gives the buffers to the object, in place of the ones it had
+    fn bind(&self, object: &mut crate::Object) {
+        if object.uniform_buffers.len() >= 6 {
+            object.uniform_buffers.splice(2..6, self.buffers.clone());
+            object.changed = true;
+        }
+    }
+}
+
+/// Makes the info, bones, weights, and time offsets buffers of a crowd
+fn build_buffers(
+    device: &wgpu::Device,
+    animation: &BakedAnimation,
+    skin_weights: &[SkinWeights],
+    time_offsets: &[f32],
+) -> [wgpu::Buffer; 4] {
+    crate::utils::allocation_audit::count_buffer();
+    let info = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Skinning Info"),
+        size: std::mem::size_of::<SkinningInfo>() as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    crate::utils::allocation_audit::count_buffer();
+    let bones = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Skinning Bones"),
+        contents: bytemuck::cast_slice(&animation.matrices),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+
+    crate::utils::allocation_audit::count_buffer();
+    let weights = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Skinning Weights"),
+        contents: bytemuck::cast_slice(skin_weights),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+
+    [
+        info,
+        bones,
+        weights,
+        build_time_offsets_buffer(device, time_offsets),
+    ]
+}
+
+/// An empty storage buffer can not be bound, so there is always at least one offset
+fn build_time_offsets_buffer(device: &wgpu::Device, time_offsets: &[f32]) -> wgpu::Buffer {
+    let zero = [0.0f32];
+    let time_offsets = match time_offsets.is_empty() {
+        true => &zero[..],
+        false => time_offsets,
+    };
+
+    crate::utils::allocation_audit::count_buffer();
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Skinning Time Offsets"),
+        contents: bytemuck::cast_slice(time_offsets),
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+    })
+}
+
+/// The skinned crowds of the objects, whose time is written before every frame
+#[derive(Debug)]
+pub struct SkinnedCrowds {
+    crowds: std::collections::HashMap<std::sync::Arc<str>, SkinnedCrowd>,
+    last_frame: Option<crate::time::Instant>,
+}
+
+impl SkinnedCrowds {
+    /// Creates a new empty list of crowds
+    pub fn new() -> Self {
+        Self {
+            crowds: std::collections::HashMap::new(),
+            last_frame: None,
+        }
+    }
+
+    /// The amount of crowds
+    pub fn len(&self) -> usize {
+        self.crowds.len()
+    }
+
+    /// Returns true if there are no crowds
+    pub fn is_empty(&self) -> bool {
+        self.crowds.is_empty()
+    }
+
+    /// Moves the time of the crowds forward, and writes it for the shaders
+    pub(crate) fn update(&mut self, queue: &wgpu::Queue, objects: &ObjectStorage) {
+        if self.crowds.is_empty() {
+            return;
+        }
+
+        let now = crate::time::Instant::now();
+        let delta_time = self.last_frame.map_or(0.0, |i| (now - i).as_secs_f32());
+        self.last_frame = Some(now);
+
+        for (name, crowd) in self.crowds.iter_mut() {
+            if !objects.contains_key(name.as_ref()) {
+                continue;
+            }
+            if crowd.playing {
+                crowd.time += delta_time * crowd.speed;
+            }
+
+            let info = SkinningInfo {
+                time: crowd.time,
+                frame_rate: crowd.animation.frame_rate,
+                frame_count: crowd.animation.frame_count(),
+                bone_count: crowd.animation.bone_count,
+            };
+            queue.write_buffer(&crowd.buffers[0], 0, bytemuck::bytes_of(&info));
+        }
+    }
+}
+impl Default for SkinnedCrowds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    /// Animates the instances of the object with the baked animation, each ahead in it by its
+    /// time offset, so a crowd of characters is drawn in one draw call. The skin weights are
+    /// for each vertex of the object, or of its shared mesh. Replaces the crowd the object had.
+    ///
+    /// The object gets the info, bones, weights, and time offsets at bindings 2 to 5 of its
+    /// uniforms, so its own uniforms start from binding 6, and it is no longer quantized or
+    /// pulling its vertices. The default shader is swapped for [SKINNED_INSTANCING_SHADER].
+    /// The shadows and the motion vectors see the object in its bind pose.
+    ///
+    /// Returns an error if the device can not read storage buffers in the vertex stage, if the
+    /// object has morph targets, or if there are not as many weights as vertices
+    pub fn add_skinned_crowd(
+        &mut self,
+        objects: &mut ObjectStorage,
+        object: impl StringBuffer,
+        skin_weights: Vec<SkinWeights>,
+        animation: BakedAnimation,
+        time_offsets: Vec<f32>,
+    ) -> Result<&mut SkinnedCrowd, Error> {
+        if !self.supports_vertex_pulling() {
+            return Err(Error::Custom(
+                "The device can not read storage buffers in the vertex stage".to_string(),
+            ));
+        }
+        let Some(object_value) = objects.get_mut(object.as_str()) else {
+            return Err(Error::Custom(format!(
+                "The object {} does not exist",
+                object.as_str()
+            )));
+        };
+        if object_value.shader_settings.morph_targets {
+            return Err(Error::Custom(format!(
+                "{} has morph targets, so it can not be a skinned crowd",
+                object_value.name
+            )));
+        }
+
+        let vertex_count = match object_value.mesh.as_ref() {
+            Some(mesh) => mesh.vertices.len(),
+            None => object_value.vertices.len(),
+        };
+        if skin_weights.is_empty() || skin_weights.len() != vertex_count {
+            return Err(Error::Custom(format!(
+                "There are {} skin weights for the {vertex_count} vertices of {}",
+                skin_weights.len(),
+                object_value.name
+            )));
+        }
+
+        let skin_weights = skin_weights
+            .into_iter()
+            .map(SkinWeights::normalized)
+            .collect::<Vec<_>>();
+        let crowd = SkinnedCrowd {
+            playing: true,
+            speed: 1.0,
+            time: 0.0,
+            buffers: build_buffers(&self.device, &animation, &skin_weights, &time_offsets),
+            animation,
+            skin_weights,
+            time_offsets,
+        };
+
+        // the buffers of a previous crowd are replaced in place
+        if !object_value.shader_settings.skinned_instancing {
+            object_value.set_quantized(false, self);
+            object_value.set_vertex_pulling(false, self);
+            object_value.shader_settings.skinned_instancing = true;
+            let at = 2.min(object_value.uniform_buffers.len());
+            object_value
+                .uniform_buffers
+                .splice(at..at, crowd.buffers.clone());
+
+            if object_value.shader_builder.source == DEFAULT_SHADER {
+                object_value.shader_builder = ShaderBuilder::new(
+                    SKINNED_INSTANCING_SHADER.to_string(),
+                    object_value.camera_effect.clone(),
+                );
+            }
+        }
+        crowd.bind(object_value);
+
+        let name = object_value.name.clone();
+        self.skinned_crowds.crowds.insert(name.clone(), crowd);
+        Ok(self
+            .skinned_crowds
+            .crowds
+            .get_mut(&name)
+            .expect("the crowd was just added"))
+    }
+
+    /// Returns the skinned crowd of the object, to play, pause, or change its speed
+    pub fn skinned_crowd(&mut self, object: impl StringBuffer) -> Option<&mut SkinnedCrowd> {
+        self.skinned_crowds.crowds.get_mut(object.as_str())
+    }
+
+    /// Sets the time each instance of the crowd is ahead in the animation, in seconds. The
+    /// instances without an offset are at the time of the crowd
+    pub fn set_crowd_time_offsets(
+        &mut self,
+        objects: &mut ObjectStorage,
+        object: impl StringBuffer,
+        time_offsets: Vec<f32>,
+    ) {
+        let Some(crowd) = self.skinned_crowds.crowds.get_mut(object.as_str()) else {
+            return;
+        };
+
+        if !time_offsets.is_empty() && time_offsets.len() == crowd.time_offsets.len() {
+            self.queue
+                .write_buffer(&crowd.buffers[3], 0, bytemuck::cast_slice(&time_offsets));
+        } else {
+            crowd.buffers[3] = build_time_offsets_buffer(&self.device, &time_offsets);
+            if let Some(object_value) = objects.get_mut(object.as_str()) {
+                crowd.bind(object_value);
+            }
+        }
+        crowd.time_offsets = time_offsets;
+    }
+
+    /// Removes the skinned crowd of the object, which is then drawn in its bind pose with the
+    /// default shader. Returns false if it had none
+    pub fn remove_skinned_crowd(
+        &mut self,
+        objects: &mut ObjectStorage,
+        object: impl StringBuffer,
+    ) -> bool {
+        if let Some(object_value) = objects.get_mut(object.as_str())
+            && object_value.shader_settings.skinned_instancing
+        {
+            object_value.shader_settings.skinned_instancing = false;
+            if object_value.uniform_buffers.len() >= 6 {
+                object_value.uniform_buffers.drain(2..6);
+            }
+            if object_value.shader_builder.source == SKINNED_INSTANCING_SHADER {
+                object_value.shader_builder = ShaderBuilder::new(
+                    DEFAULT_SHADER.to_string(),
+                    object_value.camera_effect.clone(),
+                );
+            }
+            object_value.changed = true;
+        }
+
+        self.skinned_crowds.crowds.remove(object.as_str()).is_some()
+    }
+
+    /// Makes the buffers of the crowds again on the new device, and gives them to their
+    /// objects in place of the empty ones made by their recovery
+    pub(crate) fn recover_skinned_crowds(&mut self, objects: &mut ObjectStorage) {
+        for (name, crowd) in self.skinned_crowds.crowds.iter_mut() {
+            crowd.buffers = build_buffers(
+                &self.device,
+                &crowd.animation,
+                &crowd.skin_weights,
+                &crowd.time_offsets,
+            );
+            if let Some(object) = objects.get_mut(name.as_ref()) {
+                crowd.bind(object);
+            }
+        }
+    }
+}
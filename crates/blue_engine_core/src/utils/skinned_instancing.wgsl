@@ -0,0 +1,131 @@
+// blocks
+
+//@CAMERA_STRUCT
+
+//@FOG
+
+//@VARIATION
+
+struct TransformationUniforms {
+    transform_matrix: mat4x4<f32>,
+};
+@group(2) @binding(0)
+var<uniform> transform_uniform: TransformationUniforms;
+
+struct SkinningInfo {
+    time: f32,
+    frame_rate: f32,
+    frame_count: u32,
+    bone_count: u32,
+};
+@group(2) @binding(2)
+var<uniform> skinning: SkinningInfo;
+
+// the matrices of the bones of every frame of the animation, one frame after the other
+@group(2) @binding(3)
+var<storage, read> bones: array<mat4x4<f32>>;
+
+struct SkinWeights {
+    joints: vec4<u32>,
+    weights: vec4<f32>,
+};
+@group(2) @binding(4)
+var<storage, read> skin_weights: array<SkinWeights>;
+
+// the time each instance is ahead in the animation, in seconds
+@group(2) @binding(5)
+var<storage, read> time_offsets: array<f32>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) texture_coordinates: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coordinates: vec2<f32>,
+    //@FOG_OUTPUT
+};
+
+struct InstanceInput {
+    @location(3) model_matrix_0: vec4<f32>,
+    @location(4) model_matrix_1: vec4<f32>,
+    @location(5) model_matrix_2: vec4<f32>,
+    @location(6) model_matrix_3: vec4<f32>,
+};
+
+fn bone_matrix(frame: u32, joint: u32) -> mat4x4<f32> {
+    return bones[frame * skinning.bone_count + min(joint, skinning.bone_count - 1u)];
+}
+
+// the bones of the two frames around the time of the instance, blended by the weights
+fn skin_matrix(vertex_index: u32, instance_index: u32) -> mat4x4<f32> {
+    var time = skinning.time;
+    if instance_index < arrayLength(&time_offsets) {
+        time += time_offsets[instance_index];
+    }
+
+    let frame_count = f32(skinning.frame_count);
+    let position = fract(time * skinning.frame_rate / frame_count) * frame_count;
+    let frame = u32(position) % skinning.frame_count;
+    let next_frame = (frame + 1u) % skinning.frame_count;
+    let blend = fract(position);
+
+    let skin = skin_weights[vertex_index];
+    var matrix = mat4x4<f32>(vec4<f32>(0.0), vec4<f32>(0.0), vec4<f32>(0.0), vec4<f32>(0.0));
+    for (var i = 0u; i < 4u; i += 1u) {
+        let bone = bone_matrix(frame, skin.joints[i]) * (1.0 - blend)
+            + bone_matrix(next_frame, skin.joints[i]) * blend;
+        matrix += bone * skin.weights[i];
+    }
+    return matrix;
+}
+
+// Vertex Stage
+@vertex
+fn vs_main(
+    vertex: VertexInput,
+    instance: InstanceInput,
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    var input = vertex;
+    input.position = (skin_matrix(vertex_index, instance_index) * vec4<f32>(vertex.position, 1.0)).xyz;
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    var out: VertexOutput;
+    out.texture_coordinates = input.texture_coordinates;
+    //@CAMERA_VERTEX
+    //@FOG_VERTEX
+    return out;
+}
+
+// Fragment Stage
+struct FragmentUniforms {
+    color: vec4<f32>,
+    random: f32,
+    time_offset: f32,
+    seed: u32,
+};
+@group(2) @binding(1)
+var<uniform> fragment_uniforms: FragmentUniforms;
+
+@group(0) @binding(0)
+var texture_diffuse: texture_2d<f32>;
+
+@group(0) @binding(1)
+var sampler_diffuse: sampler;
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    if fragment_uniforms.color.w == 0.0 {
+        return apply_fog(textureSample(texture_diffuse, sampler_diffuse, input.texture_coordinates), input.fog_distance);
+    } else {
+        return apply_fog(textureSample(texture_diffuse, sampler_diffuse, input.texture_coordinates) * fragment_uniforms.color, input.fog_distance);
+    }
+}
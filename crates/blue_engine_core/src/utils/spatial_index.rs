@@ -0,0 +1,1013 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{
+    Camera, Matrix4, Object, ObjectStorage, StringBuffer, Vector3,
+    utils::collision::{Aabb, ray_vs_aabb, sphere_vs_aabb},
+};
+
+/// A node of the tree, a leaf holding an object or a branch holding two nodes
+#[derive(Debug, Clone)]
+struct Node {
+    /// The box around the children, or around the object grown by the margin
+    aabb: Aabb,
+    /// The box around the object itself, for the leaves
+    tight: Aabb,
+    parent: Option<usize>,
+    children: Option<[usize; 2]>,
+    object: Option<Arc<str>>,
+    /// The longest way down to a leaf, zero for the leaves
+    height: u32,
+}
+
+/// A bounding volume hierarchy of the boxes of objects, so the objects in a region, in view,
+/// or under a ray are found without looking at every object. See
+/// [ObjectStorage::set_spatial_index].
+///
+/// The tree is changed one object at a time as they move, and is kept balanced. The boxes in
+/// it reach past the objects by the margin, so an object that moves a little stays where it
+/// is in the tree
+#[derive(Debug, Clone)]
+pub struct SpatialIndex {
+    /// How far the boxes in the tree reach past the objects, 0.1 by default
+    pub margin: f32,
+    nodes: Vec<Node>,
+    /// The nodes that can be reused
+    free: Vec<usize>,
+    root: Option<usize>,
+    leaves: HashMap<Arc<str>, usize>,
+}
+
+impl SpatialIndex {
+    /// Creates a new empty index
+    pub fn new() -> Self {
+        Self {
+            margin: 0.1,
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: None,
+            leaves: HashMap::new(),
+        }
+    }
+
+    /// The amount of objects in the index
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Returns true if there are no objects in the index
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Returns true if the object is in the index
+    pub fn contains(&self, object: impl StringBuffer) -> bool {
+        self.leaves.contains_key(object.as_str())
+    }
+
+    /// The box of the object as it is in the index
+    pub fn aabb(&self, object: impl StringBuffer) -> Option<Aabb> {
+        self.leaves
+            .get(object.as_str())
+            .map(|i| self.nodes[*i].tight)
+    }
+
+    /// The amount of levels of the tree, which grows with the logarithm of the objects
+    pub fn height(&self) -> u32 {
+        self.root.map_or(0, |i| self.nodes[i].height + 1)
+    }
+
+    /// Removes every object
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.free.clear();
+        self.root = None;
+        self.leaves.clear();
+    }
+
+    /// Adds the object with its box, or moves it to the box if it is already in the index
+    pub fn insert(&mut self, object: impl StringBuffer, aabb: Aabb) {
+        if let Some(leaf) = self.leaves.get(object.as_str()).copied() {
+            self.nodes[leaf].tight = aabb;
+            // moving inside the margin leaves the tree as it is
+            if contains(&self.nodes[leaf].aabb, &aabb) {
+                return;
+            }
+            self.remove_leaf(leaf);
+            self.nodes[leaf].aabb = self.grow(&aabb);
+            self.insert_leaf(leaf);
+            return;
+        }
+
+        let name = object.as_arc();
+        let leaf = self.allocate(Node {
+            aabb: self.grow(&aabb),
+            tight: aabb,
+            parent: None,
+            children: None,
+            object: Some(name.clone()),
+            height: 0,
+        });
+        self.leaves.insert(name, leaf);
+        self.insert_leaf(leaf);
+    }
+
+    /// Removes the object, returns false if it was not in the index
+    pub fn remove(&mut self, object: impl StringBuffer) -> bool {
+        let Some(leaf) = self.leaves.remove(object.as_str()) else {
+            return false;
+        };
+        self.remove_leaf(leaf);
+        self.release(leaf);
+
+        true
+    }
+
+    /// The objects whose box overlaps or touches the box
+    pub fn query_aabb(&self, aabb: &Aabb) -> Vec<Arc<str>> {
+        let mut found = Vec::new();
+        self.query(|i| i.intersects(aabb), |name, _| found.push(name.clone()));
+
+        found
+    }
+
+    /// The objects whose box overlaps or touches the sphere
+    pub fn query_sphere(&self, center: impl Into<Vector3>, radius: f32) -> Vec<Arc<str>> {
+        let center = center.into();
+        let mut found = Vec::new();
+        self.query(
+            |i| sphere_vs_aabb(center, radius, i),
+            |name, _| found.push(name.clone()),
+        );
+
+        found
+    }
+
+    /// The objects whose box is at least partly inside the view of the view projection
+    /// matrix, such as [Camera::view_data]
+    pub fn query_frustum(&self, view_projection: Matrix4) -> Vec<Arc<str>> {
+        let mut found = Vec::new();
+        self.visit_frustum(view_projection, &mut Vec::new(), |name| {
+            found.push(name.clone())
+        });
+
+        found
+    }
+
+    /// Visits the objects whose box is at least partly inside the view, walking the tree with
+    /// the given stack, so the walks of every frame do not allocate once it has grown
+    pub(crate) fn visit_frustum(
+        &self,
+        view_projection: Matrix4,
+        stack: &mut Vec<usize>,
+        mut visit: impl FnMut(&Arc<str>),
+    ) {
+        let planes = crate::utils::meshlets::frustum_planes(view_projection);
+        self.query_with(
+            stack,
+            |aabb| in_frustum(&planes, aabb),
+            |name, _| visit(name),
+        );
+    }
+
+    /// The first object whose box the ray enters, with how far along the ray, in lengths of
+    /// the direction. Zero if the ray starts inside the box
+    pub fn raycast(
+        &self,
+        origin: impl Into<Vector3>,
+        direction: impl Into<Vector3>,
+    ) -> Option<(Arc<str>, f32)> {
+        let (origin, direction) = (origin.into(), direction.into());
+        let mut closest: Option<(Arc<str>, f32)> = None;
+        let mut stack = self.root.into_iter().collect::<Vec<_>>();
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let Some(distance) = ray_vs_aabb(origin, direction, &node.aabb) else {
+                continue;
+            };
+            // the nodes further than the closest hit can not hold a closer one
+            if closest.as_ref().is_some_and(|i| distance >= i.1) {
+                continue;
+            }
+
+            match (node.children, node.object.as_ref()) {
+                (Some(children), _) => stack.extend(children),
+                (None, Some(name)) => {
+                    if let Some(distance) = ray_vs_aabb(origin, direction, &node.tight)
+                        && closest.as_ref().is_none_or(|i| distance < i.1)
+                    {
+                        closest = Some((name.clone(), distance));
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+
+        closest
+    }
+
+    /// The objects whose box the ray enters within the distance, in lengths of the direction,
+    /// in no particular order
+    pub fn query_ray(
+        &self,
+        origin: impl Into<Vector3>,
+        direction: impl Into<Vector3>,
+        max_distance: f32,
+    ) -> Vec<Arc<str>> {
+        let (origin, direction) = (origin.into(), direction.into());
+        let mut found = Vec::new();
+        self.query(
+            |i| ray_vs_aabb(origin, direction, i).is_some_and(|i| i <= max_distance),
+            |name, _| found.push(name.clone()),
+        );
+
+        found
+    }
+
+    /// Visits the objects whose box passes the test, skipping the branches whose box fails it
+    fn query(&self, test: impl Fn(&Aabb) -> bool, visit: impl FnMut(&Arc<str>, &Aabb)) {
+        self.query_with(&mut Vec::new(), test, visit);
+    }
+
+    /// [SpatialIndex::query] with the stack of the walk given
+    fn query_with(
+        &self,
+        stack: &mut Vec<usize>,
+        test: impl Fn(&Aabb) -> bool,
+        mut visit: impl FnMut(&Arc<str>, &Aabb),
+    ) {
+        stack.clear();
+        stack.extend(self.root);
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !test(&node.aabb) {
+                continue;
+            }
+
+            match (node.children, node.object.as_ref()) {
+                (Some(children), _) => stack.extend(children),
+                (None, Some(name)) if test(&node.tight) => visit(name, &node.tight),
+                _ => {}
+            }
+        }
+    }
+
+    /// The box grown by the margin on every side
+    fn grow(&self, aabb: &Aabb) -> Aabb {
+        let margin = Vector3::splat(self.margin.max(0.0));
+        Aabb {
+            min: aabb.min - margin,
+            max: aabb.max + margin,
+        }
+    }
+
+    fn allocate(&mut self, node: Node) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn release(&mut self, index: usize) {
+        self.nodes[index].object = None;
+        self.nodes[index].children = None;
+        self.nodes[index].parent = None;
+        self.free.push(index);
+    }
+
+    /// Puts the leaf next to the node it grows the tree the least with
+    fn insert_leaf(&mut self, leaf: usize) {
+        let Some(root) = self.root else {
+            self.root = Some(leaf);
+            self.nodes[leaf].parent = None;
+            return;
+        };
+        let aabb = self.nodes[leaf].aabb;
+
+        // the cost of a branch is the area of its box, which the leaf grows on its way down
+        let mut sibling = root;
+        while let Some(children) = self.nodes[sibling].children {
+            let area = surface_area(&self.nodes[sibling].aabb);
+            let combined = surface_area(&self.nodes[sibling].aabb.union(&aabb));
+            let cost = 2.0 * combined;
+            let inherited = 2.0 * (combined - area);
+
+            let child_cost = |child: usize| {
+                let node = &self.nodes[child];
+                let grown = surface_area(&node.aabb.union(&aabb));
+                match node.children {
+                    Some(_) => grown - surface_area(&node.aabb) + inherited,
+                    None => grown + inherited,
+                }
+            };
+            let costs = children.map(child_cost);
+            if cost < costs[0] && cost < costs[1] {
+                break;
+            }
+            sibling = match costs[0] < costs[1] {
+                true => children[0],
+                false => children[1],
+            };
+        }
+
+        let old_parent = self.nodes[sibling].parent;
+        let parent = self.allocate(Node {
+            aabb: self.nodes[sibling].aabb.union(&aabb),
+            tight: aabb,
+            parent: old_parent,
+            children: Some([sibling, leaf]),
+            object: None,
+            height: self.nodes[sibling].height + 1,
+        });
+        match old_parent {
+            Some(old_parent) => self.replace_child(old_parent, sibling, parent),
+            None => self.root = Some(parent),
+        }
+        self.nodes[sibling].parent = Some(parent);
+        self.nodes[leaf].parent = Some(parent);
+
+        self.fix_upwards(Some(parent));
+    }
+
+    /// Takes the leaf out of the tree, its sibling taking the place of their parent
+    fn remove_leaf(&mut self, leaf: usize) {
+        if self.root == Some(leaf) {
+            self.root = None;
+            return;
+        }
+        let Some(parent) = self.nodes[leaf].parent else {
+            return;
+        };
+        let Some(children) = self.nodes[parent].children else {
+            return;
+        };
+        let sibling = match children[0] == leaf {
+            true => children[1],
+            false => children[0],
+        };
+
+        let grandparent = self.nodes[parent].parent;
+        self.nodes[sibling].parent = grandparent;
+        match grandparent {
+            Some(grandparent) => self.replace_child(grandparent, parent, sibling),
+            None => self.root = Some(sibling),
+        }
+        self.release(parent);
+        self.nodes[leaf].parent = None;
+
+        self.fix_upwards(grandparent);
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if let Some(children) = self.nodes[parent].children.as_mut() {
+            for child in children.iter_mut() {
+                if *child == old {
+                    *child = new;
+                }
+            }
+        }
+    }
+
+    /// Balances and refits the branches from the node up to the root
+    fn fix_upwards(&mut self, mut index: Option<usize>) {
+        while let Some(node) = index {
+            let node = self.balance(node);
+            self.refit(node);
+            index = self.nodes[node].parent;
+        }
+    }
+
+    /// The box and height of a branch from its children
+    fn refit(&mut self, index: usize) {
+        if let Some([first, second]) = self.nodes[index].children {
+            self.nodes[index].aabb = self.nodes[first].aabb.union(&self.nodes[second].aabb);
+            self.nodes[index].height = 1 + self.nodes[first].height.max(self.nodes[second].height);
+        }
+    }
+
+    /// Rotates the higher child up if the heights of the children differ by more than one.
+    /// Returns the node now at the place of the given one
+    fn balance(&mut self, index: usize) -> usize {
+        let Some([first, second]) = self.nodes[index].children else {
+            return index;
+        };
+        let difference = self.nodes[second].height as i64 - self.nodes[first].height as i64;
+
+        match difference {
+            2.. => self.rotate(index, second, first),
+            ..=-2 => self.rotate(index, first, second),
+            _ => index,
+        }
+    }
+
+    /// Moves the child up in place of the node, which keeps the other child and takes the
+    /// lower of the children of the one moved up
+    fn rotate(&mut self, index: usize, up: usize, other: usize) -> usize {
+        let Some([first, second]) = self.nodes[up].children else {
+            return index;
+        };
+        let (higher, lower) = match self.nodes[first].height > self.nodes[second].height {
+            true => (first, second),
+            false => (second, first),
+        };
+
+        let parent = self.nodes[index].parent;
+        self.nodes[up].parent = parent;
+        match parent {
+            Some(parent) => self.replace_child(parent, index, up),
+            None => self.root = Some(up),
+        }
+
+        self.nodes[up].children = Some([index, higher]);
+        self.nodes[index].parent = Some(up);
+        self.nodes[index].children = Some([other, lower]);
+        self.nodes[lower].parent = Some(index);
+
+        self.refit(index);
+        self.refit(up);
+        up
+    }
+}
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The area of the sides of the box, which is what the tree is built to keep small
+fn surface_area(aabb: &Aabb) -> f32 {
+    let size = aabb.max - aabb.min;
+    2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+}
+
+/// Returns true if the box is at least partly on the inner side of every plane of the view
+fn in_frustum(planes: &[crate::Vector4; 6], aabb: &Aabb) -> bool {
+    planes.iter().all(|plane| {
+        // the corner furthest along the plane is in front of it
+        let normal = plane.truncate();
+        let corner = Vector3::select(normal.cmpge(Vector3::ZERO), aabb.max, aabb.min);
+        normal.dot(corner) + plane.w >= 0.0
+    })
+}
+
+/// Returns true if the object is drawn where its box is. The objects whose vertices are written
+/// or moved on the GPU are drawn even when their box is out of view
+pub(crate) fn can_be_culled(object: &Object) -> bool {
+    object.gpu_vertices.is_none()
+        && !object.shader_settings.skinned_instancing
+        && !object.shader_settings.morph_targets
+}
+
+/// Returns true if the inner box is inside the outer one
+fn contains(outer: &Aabb, inner: &Aabb) -> bool {
+    outer.min.cmple(inner.min).all() && inner.max.cmple(outer.max).all()
+}
+
+impl ObjectStorage {
+    /// Keeps the objects in a [SpatialIndex] by the boxes around their instances, so
+    /// [ObjectStorage::objects_within_radius], [ObjectStorage::objects_in_aabb],
+    /// [ObjectStorage::objects_in_view], and [ObjectStorage::pick] do not look at every
+    /// object. The index is kept by the engine every frame for the objects that changed, and
+    /// with [ObjectStorage::refresh_spatial_index] for the changes made since.
+    ///
+    /// While it is enabled, the render passes also ask the index for the objects in the view of
+    /// each camera, and leave out the ones whose box is outside the view of their camera. The
+    /// objects whose vertices are moved by their own shaders should stay out of the index, by
+    /// keeping it disabled
+    pub fn set_spatial_index(&mut self, enabled: bool) {
+        match enabled {
+            true if self.spatial_index.is_none() => {
+                let mut index = SpatialIndex::new();
+                for object in self.values() {
+                    if let Some(aabb) = object.instances_aabb() {
+                        index.insert(object.name.as_ref(), aabb);
+                    }
+                }
+                self.spatial_index = Some(index);
+            }
+            true => {}
+            false => self.spatial_index = None,
+        }
+    }
+
+    /// The spatial index of the objects, if it is enabled
+    pub fn spatial_index(&self) -> Option<&SpatialIndex> {
+        self.spatial_index.as_ref()
+    }
+
+    /// Moves the changed objects in the spatial index, adds the new ones, and drops the ones
+    /// that were removed. Done by the engine before the objects are updated each frame
+    pub fn refresh_spatial_index(&mut self) {
+        // taken out while the objects are read through the map
+        let Some(mut index) = self.spatial_index.take() else {
+            return;
+        };
+
+        for object in self.values().filter(|i| i.changed) {
+            match object.instances_aabb() {
+                Some(aabb) => index.insert(object.name.as_ref(), aabb),
+                None => {
+                    index.remove(object.name.as_ref());
+                }
+            }
+        }
+
+        let removed = index
+            .leaves
+            .keys()
+            .filter(|i| !self.contains_key(i.as_ref()))
+            .cloned()
+            .collect::<Vec<_>>();
+        for name in removed {
+            index.remove(name.as_ref());
+        }
+        self.spatial_index = Some(index);
+    }
+
+    /// The objects whose box overlaps or touches the sphere, such as the enemies in range
+    pub fn objects_within_radius(
+        &self,
+        point: impl Into<Vector3>,
+        radius: f32,
+    ) -> impl Iterator<Item = (&String, &Object)> {
+        let point = point.into();
+        self.spatially(
+            |index| index.query_sphere(point, radius),
+            move |aabb| sphere_vs_aabb(point, radius, aabb),
+        )
+    }
+
+    /// The objects whose box overlaps or touches the box
+    pub fn objects_in_aabb(&self, aabb: &Aabb) -> impl Iterator<Item = (&String, &Object)> {
+        let aabb = *aabb;
+        self.spatially(
+            |index| index.query_aabb(&aabb),
+            move |i| i.intersects(&aabb),
+        )
+    }
+
+    /// The objects whose box is at least partly in the view of the camera. Hidden objects
+    /// are included
+    pub fn objects_in_view(&self, camera: &Camera) -> impl Iterator<Item = (&String, &Object)> {
+        let view_projection = camera.view_data;
+        let planes = crate::utils::meshlets::frustum_planes(view_projection);
+        self.spatially(
+            |index| index.query_frustum(view_projection),
+            move |aabb| in_frustum(&planes, aabb),
+        )
+    }
+
+    /// The first object whose box the ray enters, with how far along the ray, such as the one
+    /// under the cursor with [Camera::cursor_ray]
+    pub fn pick(
+        &self,
+        origin: impl Into<Vector3>,
+        direction: impl Into<Vector3>,
+    ) -> Option<(&String, &Object, f32)> {
+        let (origin, direction) = (origin.into(), direction.into());
+
+        match self.spatial_index.as_ref() {
+            Some(index) => {
+                let (name, distance) = index.raycast(origin, direction)?;
+                let (key, object) = self.get_key_value(name.as_ref())?;
+                Some((key, object, distance))
+            }
+            None => self
+                .iter()
+                .filter_map(|(key, object)| {
+                    let distance = ray_vs_aabb(origin, direction, &object.instances_aabb()?)?;
+                    Some((key, object, distance))
+                })
+                .min_by(|a, b| a.2.total_cmp(&b.2)),
+        }
+    }
+
+    /// The objects whose box the ray enters within the distance, in lengths of the direction,
+    /// with how far along the ray, in no particular order. Such as the objects between two
+    /// points, with the direction from one to the other and a distance of 1
+    pub fn objects_on_ray(
+        &self,
+        origin: impl Into<Vector3>,
+        direction: impl Into<Vector3>,
+        max_distance: f32,
+    ) -> impl Iterator<Item = (&String, &Object, f32)> {
+        let (origin, direction) = (origin.into(), direction.into());
+        self.spatially(
+            |index| index.query_ray(origin, direction, max_distance),
+            move |aabb| ray_vs_aabb(origin, direction, aabb).is_some_and(|i| i <= max_distance),
+        )
+        .filter_map(move |(name, object)| {
+            let distance = ray_vs_aabb(origin, direction, &object.instances_aabb()?)?;
+            Some((name, object, distance))
+        })
+    }
+
+    /// The objects found by the index, or by testing the box of every object without one
+    fn spatially<'a>(
+        &'a self,
+        query: impl FnOnce(&SpatialIndex) -> Vec<Arc<str>>,
+        test: impl Fn(&Aabb) -> bool + 'a,
+    ) -> Box<dyn Iterator<Item = (&'a String, &'a Object)> + 'a> {
+        match self.spatial_index.as_ref() {
+            Some(index) => Box::new(
+                query(index)
+                    .into_iter()
+                    .filter_map(|i| self.get_key_value(i.as_ref())),
+            ),
+            None => Box::new(
+                self.iter()
+                    .filter(move |(_, object)| object.instances_aabb().is_some_and(|i| test(&i))),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A small generator of repeatable numbers, so the same boxes are made on every run
+    struct Lcg(u64);
+    impl Lcg {
+        fn next(&mut self) -> f32 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        fn range(&mut self, min: f32, max: f32) -> f32 {
+            min + (max - min) * self.next()
+        }
+
+        fn aabb(&mut self) -> Aabb {
+            let center = Vector3::new(
+                self.range(-50.0, 50.0),
+                self.range(-50.0, 50.0),
+                self.range(-50.0, 50.0),
+            );
+            let half_extents = Vector3::new(
+                self.range(0.1, 3.0),
+                self.range(0.1, 3.0),
+                self.range(0.1, 3.0),
+            );
+            Aabb::new(center - half_extents, center + half_extents)
+        }
+    }
+
+    fn sorted(mut names: Vec<Arc<str>>) -> Vec<Arc<str>> {
+        names.sort();
+        names
+    }
+
+    fn brute_force(boxes: &HashMap<Arc<str>, Aabb>, test: impl Fn(&Aabb) -> bool) -> Vec<Arc<str>> {
+        sorted(
+            boxes
+                .iter()
+                .filter(|(_, aabb)| test(aabb))
+                .map(|(name, _)| name.clone())
+                .collect(),
+        )
+    }
+
+    /// Checks the links, boxes, and heights of every branch, and that every leaf is reached
+    fn check_tree(index: &SpatialIndex) {
+        let mut stack = index.root.into_iter().collect::<Vec<_>>();
+        let mut leaves = 0;
+        if let Some(root) = index.root {
+            assert_eq!(index.nodes[root].parent, None);
+        }
+
+        while let Some(node) = stack.pop() {
+            let node_data = &index.nodes[node];
+            match node_data.children {
+                Some([first, second]) => {
+                    for child in [first, second] {
+                        assert_eq!(index.nodes[child].parent, Some(node));
+                        assert!(contains(&node_data.aabb, &index.nodes[child].aabb));
+                    }
+                    let (a, b) = (index.nodes[first].height, index.nodes[second].height);
+                    assert_eq!(node_data.height, 1 + a.max(b));
+                    assert!(a.abs_diff(b) <= 1, "the tree is not balanced");
+                    stack.extend([first, second]);
+                }
+                None => {
+                    let name = node_data.object.as_ref().expect("a leaf holds an object");
+                    assert_eq!(index.leaves.get(name), Some(&node));
+                    assert!(contains(&node_data.aabb, &node_data.tight));
+                    leaves += 1;
+                }
+            }
+        }
+        assert_eq!(leaves, index.len());
+    }
+
+    fn check_queries(index: &SpatialIndex, boxes: &HashMap<Arc<str>, Aabb>, random: &mut Lcg) {
+        for _ in 0..20 {
+            let region = random.aabb().union(&random.aabb());
+            assert_eq!(
+                sorted(index.query_aabb(&region)),
+                brute_force(boxes, |i| i.intersects(&region))
+            );
+
+            let (center, radius) = (random.aabb().center(), random.range(1.0, 20.0));
+            assert_eq!(
+                sorted(index.query_sphere(center, radius)),
+                brute_force(boxes, |i| sphere_vs_aabb(center, radius, i))
+            );
+
+            let origin = random.aabb().center() * 2.0;
+            let direction = (random.aabb().center() - origin).normalize_or(Vector3::X);
+            let expected = boxes
+                .values()
+                .filter_map(|i| ray_vs_aabb(origin, direction, i))
+                .min_by(f32::total_cmp);
+            assert_eq!(
+                sorted(index.query_ray(origin, direction, 40.0)),
+                brute_force(boxes, |i| ray_vs_aabb(origin, direction, i)
+                    .is_some_and(|i| i <= 40.0))
+            );
+            let found = index.raycast(origin, direction);
+            assert_eq!(found.as_ref().map(|i| i.1), expected);
+            if let Some((name, distance)) = found {
+                assert_eq!(
+                    ray_vs_aabb(origin, direction, &boxes[&name]),
+                    Some(distance)
+                );
+            }
+        }
+
+        let view_projection = Matrix4::perspective_rh(1.0, 1.5, 0.1, 60.0)
+            * Matrix4::look_at_rh(Vector3::new(0.0, 10.0, 70.0), Vector3::ZERO, Vector3::Y);
+        let planes = crate::utils::meshlets::frustum_planes(view_projection);
+        assert_eq!(
+            sorted(index.query_frustum(view_projection)),
+            brute_force(boxes, |aabb| {
+                planes.iter().all(|plane| {
+                    let normal = plane.truncate();
+                    let corner = Vector3::select(normal.cmpge(Vector3::ZERO), aabb.max, aabb.min);
+                    normal.dot(corner) + plane.w >= 0.0
+                })
+            })
+        );
+    }
+
+    #[test]
+    fn queries_match_a_brute_force_scan() {
+        let mut random = Lcg(7);
+        let mut index = SpatialIndex::new();
+        let mut boxes = HashMap::<Arc<str>, Aabb>::new();
+
+        for i in 0..300 {
+            let (name, aabb): (Arc<str>, _) = (format!("object {i}").into(), random.aabb());
+            index.insert(name.as_ref(), aabb);
+            boxes.insert(name, aabb);
+        }
+        check_tree(&index);
+        check_queries(&index, &boxes, &mut random);
+
+        // small moves stay inside the margin, large ones go elsewhere in the tree
+        for i in (0..300).step_by(3) {
+            let name: Arc<str> = format!("object {i}").into();
+            let aabb = match i % 2 == 0 {
+                true => {
+                    let offset = Vector3::splat(random.range(-0.05, 0.05));
+                    Aabb::new(boxes[&name].min + offset, boxes[&name].max + offset)
+                }
+                false => random.aabb(),
+            };
+            index.insert(name.as_ref(), aabb);
+            boxes.insert(name, aabb);
+        }
+        check_tree(&index);
+        check_queries(&index, &boxes, &mut random);
+
+        for i in (0..300).step_by(4) {
+            let name: Arc<str> = format!("object {i}").into();
+            assert!(index.remove(name.as_ref()));
+            boxes.remove(&name);
+        }
+        assert!(!index.remove("object 0"));
+        assert_eq!(index.len(), boxes.len());
+        check_tree(&index);
+        check_queries(&index, &boxes, &mut random);
+
+        // the freed nodes are reused by the new objects
+        let nodes = index.nodes.len();
+        for i in 300..350 {
+            let (name, aabb): (Arc<str>, _) = (format!("object {i}").into(), random.aabb());
+            index.insert(name.as_ref(), aabb);
+            boxes.insert(name, aabb);
+        }
+        assert_eq!(index.nodes.len(), nodes);
+        check_tree(&index);
+        check_queries(&index, &boxes, &mut random);
+    }
+
+    #[test]
+    fn the_tree_stays_shallow() {
+        let mut random = Lcg(11);
+        let mut index = SpatialIndex::new();
+        for i in 0..2000 {
+            index.insert(format!("object {i}"), random.aabb());
+        }
+        check_tree(&index);
+        // a balanced tree of 2000 leaves is at least 12 levels high
+        assert!(index.height() <= 16, "the height is {}", index.height());
+
+        // objects in a row are the worst case for a tree that is not balanced
+        let mut row = SpatialIndex::new();
+        for i in 0..1000 {
+            let x = i as f32 * 2.0;
+            row.insert(
+                format!("object {i}"),
+                Aabb::new([x, 0.0, 0.0], [x + 1.0, 1.0, 1.0]),
+            );
+        }
+        check_tree(&row);
+        assert!(row.height() <= 15, "the height is {}", row.height());
+    }
+
+    #[test]
+    fn emptied_index_is_empty() {
+        let mut index = SpatialIndex::new();
+        index.insert("a", Aabb::new(Vector3::ZERO, Vector3::ONE));
+        assert_eq!(index.height(), 1);
+        assert_eq!(
+            index
+                .raycast([-1.0, 0.5, 0.5], [1.0, 0.0, 0.0])
+                .map(|i| i.1),
+            Some(1.0)
+        );
+        assert!(index.remove("a"));
+        assert!(index.is_empty());
+        assert_eq!(index.height(), 0);
+        assert!(
+            index
+                .query_aabb(&Aabb::new(Vector3::splat(-9.0), Vector3::splat(9.0)))
+                .is_empty()
+        );
+        assert_eq!(index.raycast([-1.0, 0.5, 0.5], [1.0, 0.0, 0.0]), None);
+    }
+
+    #[test]
+    fn object_queries_match_with_and_without_the_index() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut random = Lcg(3);
+        let mut objects = ObjectStorage::new();
+        for i in 0..40 {
+            let (vertices, indices) = crate::primitive_shapes::triangle_data();
+            let mut object = Object::new(
+                format!("object {i}"),
+                vertices,
+                indices,
+                crate::ObjectSettings::default(),
+                &mut renderer,
+            )
+            .expect("the triangle is built");
+            object.set_position(random.aabb().center() * 0.2);
+            objects.add(format!("object {i}"), object);
+        }
+
+        let compare = |objects: &mut ObjectStorage| {
+            let names = |found: Vec<(&String, &Object)>| {
+                let mut found = found.into_iter().map(|i| i.0.clone()).collect::<Vec<_>>();
+                found.sort();
+                found
+            };
+            let region = Aabb::new([-5.0, -5.0, -5.0], [4.0, 6.0, 3.0]);
+
+            objects.set_spatial_index(true);
+            let indexed = (
+                names(
+                    objects
+                        .objects_within_radius([1.0, 2.0, 0.0], 4.0)
+                        .collect(),
+                ),
+                names(objects.objects_in_aabb(&region).collect()),
+                objects
+                    .pick([-20.0, 0.0, 0.0], [1.0, 0.0, 0.0])
+                    .map(|i| i.2),
+                names(
+                    objects
+                        .objects_on_ray([-20.0, 1.0, 0.0], [40.0, -1.0, 1.0], 1.0)
+                        .map(|i| (i.0, i.1))
+                        .collect(),
+                ),
+            );
+            objects.set_spatial_index(false);
+            let scanned = (
+                names(
+                    objects
+                        .objects_within_radius([1.0, 2.0, 0.0], 4.0)
+                        .collect(),
+                ),
+                names(objects.objects_in_aabb(&region).collect()),
+                objects
+                    .pick([-20.0, 0.0, 0.0], [1.0, 0.0, 0.0])
+                    .map(|i| i.2),
+                names(
+                    objects
+                        .objects_on_ray([-20.0, 1.0, 0.0], [40.0, -1.0, 1.0], 1.0)
+                        .map(|i| (i.0, i.1))
+                        .collect(),
+                ),
+            );
+            assert_eq!(indexed, scanned);
+            objects.set_spatial_index(true);
+        };
+        compare(&mut objects);
+
+        // the index is kept through the refresh rather than built again
+        for i in 0..10 {
+            let object = objects.get_mut(format!("object {i}").as_str()).unwrap();
+            object.set_position(random.aabb().center() * 0.2);
+        }
+        for i in 30..40 {
+            objects.remove(format!("object {i}").as_str());
+        }
+        objects.refresh_spatial_index();
+        let index = objects.spatial_index().unwrap();
+        assert_eq!(index.len(), 30);
+        for (name, object) in objects.iter() {
+            assert_eq!(index.aabb(name.as_str()), object.instances_aabb());
+        }
+        compare(&mut objects);
+    }
+
+    /// The names of the objects in the draw list of the renderer, in the order they are drawn
+    fn drawn<'a>(renderer: &crate::Renderer, objects: &'a ObjectStorage) -> Vec<&'a str> {
+        renderer
+            .draw_list
+            .iter()
+            .filter_map(|&(.., item)| match item {
+                crate::render::DrawItem::Object(id) => objects.name_of(id),
+                crate::render::DrawItem::Batch(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn render_passes_leave_out_the_objects_out_of_view() {
+        let Some(mut renderer) = crate::render::test_renderer() else {
+            return;
+        };
+        let mut camera =
+            crate::CameraContainer::new(winit::dpi::PhysicalSize::new(64, 64), &mut renderer);
+        camera.update_view_projection(&mut renderer);
+
+        let mut objects = ObjectStorage::new();
+        for (name, position) in [("near", 0.0), ("far", 1000.0), ("morphed", 1000.0)] {
+            let (vertices, indices) = crate::primitive_shapes::triangle_data();
+            let mut object = Object::new(
+                name,
+                vertices,
+                indices,
+                crate::ObjectSettings::default(),
+                &mut renderer,
+            )
+            .unwrap();
+            object.set_position((position, 0.0, 0.0));
+            objects.add(name, object);
+        }
+        // moved on the GPU, so where it is drawn is not known from its box
+        objects
+            .get_mut("morphed")
+            .unwrap()
+            .shader_settings
+            .morph_targets = true;
+
+        renderer.prepare_draw_list(&objects, &camera, &|_: &Object| true);
+        assert_eq!(drawn(&renderer, &objects), ["near", "far", "morphed"]);
+
+        objects.set_spatial_index(true);
+        renderer.prepare_draw_list(&objects, &camera, &|_: &Object| true);
+        assert_eq!(drawn(&renderer, &objects), ["near", "morphed"]);
+
+        // brought into view, once the index is refreshed
+        objects
+            .get_mut("far")
+            .unwrap()
+            .set_position((0.5, 0.0, 0.0));
+        objects.refresh_spatial_index();
+        renderer.prepare_draw_list(&objects, &camera, &|_: &Object| true);
+        assert_eq!(drawn(&renderer, &objects), ["near", "far", "morphed"]);
+    }
+}
@@ -0,0 +1,425 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{
+    CameraContainer, ObjectStorage, PipelineData, Projection, Quaternion, Renderer, StringBuffer,
+    Vector2, Vector3,
+    error::Error,
+    objects::ShaderBuilder,
+    utils::{default_resources::SPRITE_BAKING_SHADER, layer_capture::LayerCapture},
+};
+
+/// The direction an object is seen from in a baked sprite, in radians
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteAngle {
+    /// The angle around the object, counterclockwise seen from above, 0 looking at its front
+    /// along -Z
+    pub yaw: f32,
+    /// The angle above the object, 0 looking at it level and PI / 2 looking straight down
+    pub pitch: f32,
+}
+impl SpriteAngle {
+    /// Creates a new angle
+    pub fn new(yaw: f32, pitch: f32) -> Self {
+        Self { yaw, pitch }
+    }
+
+    /// The rotation that turns the object to be seen from this angle by a camera looking
+    /// down -Z
+    fn rotation(&self) -> Quaternion {
+        Quaternion::from_rotation_x(self.pitch) * Quaternion::from_rotation_y(-self.yaw)
+    }
+}
+
+/// How [Renderer::bake_sprites] renders an object into a sprite sheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteBake {
+    /// The width of each frame in pixels
+    pub frame_width: u32,
+    /// The height of each frame in pixels
+    pub frame_height: u32,
+    /// The angles the object is seen from, one frame each, in the order of the sheet
+    pub angles: Vec<SpriteAngle>,
+    /// The amount of frames in each row of the sheet, or `None` for as square a sheet as fits
+    pub columns: Option<u32>,
+    /// The vertical field of view of the camera, or `None` to bake without perspective as is
+    /// usual for isometric and top down games
+    pub field_of_view: Option<f32>,
+    /// Room left around the object, as a part of its size, so 0.1 leaves a tenth of it
+    pub margin: f32,
+    /// The frames are rendered at this many times their size and averaged down
+    pub supersampling: u32,
+    /// Also bakes a sheet of the normals of the object, for lighting the sprites in 2D
+    pub normals: bool,
+    /// Also bakes a sheet of the depth of the object, for sorting and shading the sprites
+    pub depth: bool,
+}
+impl SpriteBake {
+    /// Bakes the object from 8 directions around it at the level of its center, without
+    /// perspective, into frames of the given size
+    pub fn new(frame_width: u32, frame_height: u32) -> Self {
+        Self {
+            frame_width,
+            frame_height,
+            angles: Vec::new(),
+            columns: None,
+            field_of_view: None,
+            margin: 0.05,
+            supersampling: 4,
+            normals: false,
+            depth: false,
+        }
+        .with_directions(8, 0.0)
+    }
+
+    /// Sees the object from this many directions evenly around it, from the pitch above it
+    pub fn with_directions(mut self, count: u32, pitch: f32) -> Self {
+        self.angles = (0..count.max(1))
+            .map(|i| SpriteAngle::new(i as f32 * std::f32::consts::TAU / count as f32, pitch))
+            .collect();
+        self
+    }
+
+    /// Sees the object from these angles
+    pub fn with_angles(mut self, angles: impl IntoIterator<Item = SpriteAngle>) -> Self {
+        self.angles = angles.into_iter().collect();
+        self
+    }
+
+    /// Bakes the sheets of the normals and the depth as well
+    pub fn with_normals_and_depth(mut self, normals: bool, depth: bool) -> Self {
+        self.normals = normals;
+        self.depth = depth;
+        self
+    }
+
+    /// The amount of frames in each row of the sheet
+    fn columns(&self) -> u32 {
+        let frames = self.angles.len().max(1) as u32;
+        self.columns
+            .unwrap_or_else(|| (frames as f32).sqrt().ceil() as u32)
+            .clamp(1, frames)
+    }
+}
+
+/// The sheets baked by [Renderer::bake_sprites], with the frames in rows from the top left
+#[derive(Debug, Clone)]
+pub struct SpriteSheet {
+    /// The colors of the object, against a transparent background
+    pub color: image::RgbaImage,
+    /// The normals of the object, with X to the right, Y up and Z towards the viewer, mapped
+    /// from -1..1 to 0..255
+    pub normals: Option<image::RgbaImage>,
+    /// The depth of the object, brighter towards the viewer, with the origin of the object at
+    /// half and the farthest vertex from the origin at the ends
+    pub depth: Option<image::RgbaImage>,
+    /// The width of each frame in pixels
+    pub frame_width: u32,
+    /// The height of each frame in pixels
+    pub frame_height: u32,
+    /// The amount of frames in each row
+    pub columns: u32,
+    /// The angle each frame is seen from
+    pub angles: Vec<SpriteAngle>,
+}
+impl SpriteSheet {
+    /// The amount of frames in the sheet
+    pub fn len(&self) -> usize {
+        self.angles.len()
+    }
+
+    /// Returns true if the sheet has no frames
+    pub fn is_empty(&self) -> bool {
+        self.angles.is_empty()
+    }
+
+    /// The position and size of the frame in pixels, as `(x, y, width, height)`
+    pub fn frame(&self, index: usize) -> Option<(u32, u32, u32, u32)> {
+        (index < self.angles.len()).then(|| {
+            let index = index as u32;
+            (
+                index % self.columns * self.frame_width,
+                index / self.columns * self.frame_height,
+                self.frame_width,
+                self.frame_height,
+            )
+        })
+    }
+
+    /// The top left and bottom right texture coordinates of the frame, for drawing it on a
+    /// sprite with the sheet as its texture
+    pub fn frame_uv(&self, index: usize) -> Option<(Vector2, Vector2)> {
+        let (x, y, width, height) = self.frame(index)?;
+        let size = Vector2::new(self.color.width() as f32, self.color.height() as f32);
+
+        Some((
+            Vector2::new(x as f32, y as f32) / size,
+            Vector2::new((x + width) as f32, (y + height) as f32) / size,
+        ))
+    }
+
+    /// The frame seen from the angle closest to the yaw, such as the direction a character is
+    /// facing relative to the camera
+    pub fn closest_frame(&self, yaw: f32) -> Option<usize> {
+        let distance = |angle: &SpriteAngle| {
+            (angle.yaw - yaw + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU)
+                - std::f32::consts::PI
+        };
+
+        self.angles
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| distance(a).abs().total_cmp(&distance(b).abs()))
+            .map(|(i, _)| i)
+    }
+}
+
+/// What is drawn into the frames of a sheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BakeOutput {
+    Color,
+    Normals,
+    Depth,
+}
+
+impl Renderer {
+    /// Renders an object from each of the angles into the frames of a sprite sheet, with its
+    /// normals and depth if asked for, such as for the characters and props of 2.5D games.
+    ///
+    /// The object is turned to face the main camera from each angle, which is placed in
+    /// front of it to fit it in the frame. Both are set back after the bake. This waits for
+    /// the GPU, so it is meant for loading and tools rather than every frame. The objects in
+    /// a static batch and the objects with quantized vertices can not be baked
+    pub fn bake_sprites(
+        &mut self,
+        objects: &mut ObjectStorage,
+        camera: &mut CameraContainer,
+        object: impl StringBuffer,
+        bake: &SpriteBake,
+    ) -> Result<SpriteSheet, Error> {
+        let name = object.as_string();
+        let radius = {
+            let Some(object) = objects.get(&name) else {
+                return Err(Error::Custom(format!("The object {name} does not exist")));
+            };
+            if self.static_batches.is_batched(object) {
+                return Err(Error::Custom(format!(
+                    "The object {name} is drawn in a static batch, and can not be baked"
+                )));
+            }
+            if object.shader_settings.quantized_vertices {
+                return Err(Error::Custom(format!(
+                    "The object {name} has quantized vertices, and can not be baked"
+                )));
+            }
+
+            let vertices = match object.mesh.as_ref() {
+                Some(mesh) => mesh.vertices.as_slice(),
+                None => object.vertices.as_slice(),
+            };
+            vertices
+                .iter()
+                .map(|i| {
+                    object
+                        .scale_matrix
+                        .transform_point3(Vector3::from(i.position))
+                        .length()
+                })
+                .fold(0.0f32, f32::max)
+                .max(f32::EPSILON)
+        };
+
+        let columns = bake.columns();
+        let frames = bake.angles.len() as u32;
+        let rows = frames.div_ceil(columns).max(1);
+        let sheet = || image::RgbaImage::new(columns * bake.frame_width, rows * bake.frame_height);
+        let mut sprite_sheet = SpriteSheet {
+            color: sheet(),
+            normals: bake.normals.then(sheet),
+            depth: bake.depth.then(sheet),
+            frame_width: bake.frame_width,
+            frame_height: bake.frame_height,
+            columns,
+            angles: bake.angles.clone(),
+        };
+
+        let Some(main_camera) = camera.get_mut("main") else {
+            return Err(Error::Custom(
+                "There is no main camera to bake with".to_string(),
+            ));
+        };
+        let previous_camera = (
+            main_camera.position,
+            main_camera.target,
+            main_camera.up,
+            main_camera.projection.clone(),
+            main_camera.near,
+            main_camera.far,
+        );
+
+        // the camera is placed in front of the object, far enough to see all of it
+        let aspect = bake.frame_width.max(1) as f32 / bake.frame_height.max(1) as f32;
+        let fit = radius * (1.0 + bake.margin.max(0.0));
+        let (projection, distance) = match bake.field_of_view {
+            Some(fov) => {
+                let horizontal_fov = 2.0 * ((fov * 0.5).tan() * aspect).atan();
+                let distance = fit / (fov.min(horizontal_fov) * 0.5).sin();
+                (Projection::Perspective { fov }, distance)
+            }
+            None => (
+                Projection::Orthographic {
+                    zoom: 2.0 * fit * aspect.max(1.0),
+                },
+                fit * 2.0,
+            ),
+        };
+        let center = objects.get(&name).map(|i| i.position).unwrap_or_default();
+        main_camera.position = center + Vector3::new(0.0, 0.0, distance);
+        main_camera.target = center;
+        main_camera.up = Vector3::Y;
+        main_camera.projection = projection;
+        main_camera.near = (distance - fit).max(distance * 0.001);
+        main_camera.far = distance + fit;
+        main_camera.build_view_projection_matrix();
+
+        let result = self.bake_frames(objects, camera, &name, bake, radius, &mut sprite_sheet);
+
+        if let Some(main_camera) = camera.get_mut("main") {
+            let (position, target, up, projection, near, far) = previous_camera;
+            main_camera.position = position;
+            main_camera.target = target;
+            main_camera.up = up;
+            main_camera.projection = projection;
+            main_camera.near = near;
+            main_camera.far = far;
+            main_camera.build_view_projection_matrix();
+        }
+        camera.update_view_projection(self);
+
+        result.map(|_| sprite_sheet)
+    }
+
+    /// Draws every frame of the sheets, with the object turned and drawn by the main camera,
+    /// and sets the object back after
+    fn bake_frames(
+        &mut self,
+        objects: &mut ObjectStorage,
+        camera: &mut CameraContainer,
+        name: &str,
+        bake: &SpriteBake,
+        radius: f32,
+        sprite_sheet: &mut SpriteSheet,
+    ) -> Result<(), Error> {
+        let Some(object) = objects.get_mut(name) else {
+            return Ok(());
+        };
+        let rotation = object.rotation_quaternion;
+        // the object is drawn by the main camera, which is the one placed for the bake
+        let camera_effect = object.camera_effect.clone();
+        if camera_effect.is_some() {
+            object.camera_effect = Some("main".into());
+        }
+        let capture = LayerCapture {
+            supersampling: bake.supersampling,
+            ..LayerCapture::new(bake.frame_width, bake.frame_height).with_objects([name])
+        };
+
+        let outputs = [
+            (BakeOutput::Color, true),
+            (BakeOutput::Normals, bake.normals),
+            (BakeOutput::Depth, bake.depth),
+        ];
+        let mut result = Ok(());
+        for (output, _) in outputs.iter().filter(|(_, enabled)| *enabled) {
+            let shader = match output {
+                BakeOutput::Color => None,
+                _ => objects
+                    .get(name)
+                    .map(|i| self.bake_shader(i, *output, radius)),
+            };
+            let previous_shader = shader.and_then(|shader| {
+                objects
+                    .get_mut(name)
+                    .map(|i| std::mem::replace(&mut i.pipeline.shader, PipelineData::Data(shader)))
+            });
+
+            for (index, angle) in bake.angles.iter().enumerate() {
+                if let Some(object) = objects.get_mut(name) {
+                    object.rotation_quaternion = angle.rotation() * rotation;
+                    object.update_uniform_buffer(self);
+                }
+                camera.update_view_projection(self);
+
+                let frame = match self.capture_layers(objects, camera, &capture) {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        result = Err(e);
+                        break;
+                    }
+                };
+                let position = sprite_sheet.frame(index);
+                let sheet = match output {
+                    BakeOutput::Color => Some(&mut sprite_sheet.color),
+                    BakeOutput::Normals => sprite_sheet.normals.as_mut(),
+                    BakeOutput::Depth => sprite_sheet.depth.as_mut(),
+                };
+                if let (Some(sheet), Some((x, y, _, _))) = (sheet, position) {
+                    image::imageops::replace(sheet, &frame, x as i64, y as i64);
+                }
+            }
+
+            if let (Some(previous_shader), Some(object)) = (previous_shader, objects.get_mut(name))
+            {
+                object.pipeline.shader = previous_shader;
+            }
+            if result.is_err() {
+                break;
+            }
+        }
+
+        if let Some(object) = objects.get_mut(name) {
+            object.rotation_quaternion = rotation;
+            object.camera_effect = camera_effect;
+            object.update_uniform_buffer(self);
+        }
+
+        result
+    }
+
+    /// Builds the shader that draws the normals or the depth of the object
+    fn bake_shader(
+        &mut self,
+        object: &crate::Object,
+        output: BakeOutput,
+        radius: f32,
+    ) -> crate::Shaders {
+        let source = SPRITE_BAKING_SHADER
+            .replace(
+                "const OUTPUT: u32 = 0u;",
+                &format!(
+                    "const OUTPUT: u32 = {}u;",
+                    (output == BakeOutput::Depth) as u32
+                ),
+            )
+            .replace(
+                "const RADIUS: f32 = 1.0;",
+                &format!("const RADIUS: f32 = {radius:?};"),
+            )
+            .replace(
+                "const SRGB: bool = false;",
+                &format!("const SRGB: bool = {};", self.scene_format().is_srgb()),
+            );
+        let camera_effect = object.camera_effect.as_ref().map(|_| "main".into());
+        let shader = ShaderBuilder::new(source, camera_effect).shader;
+
+        self.build_shader(
+            format!("{} Sprite Baking", object.name),
+            shader,
+            Some(&object.uniform_layout),
+            object.shader_settings,
+        )
+    }
+}
@@ -0,0 +1,97 @@
+// The normals or depth of an object baked into a sprite. The object is turned to face the
+// camera, which looks down -Z, so its normals in the world are the normals of the sprite
+
+//@CAMERA_STRUCT
+
+// 0 for the normals, 1 for the depth
+const OUTPUT: u32 = 0u;
+// the distance from the origin of the object that is mapped to the range of the depth
+const RADIUS: f32 = 1.0;
+// true when the target encodes the colors in sRGB
+const SRGB: bool = false;
+
+struct TransformationUniforms {
+    transform_matrix: mat4x4<f32>,
+};
+@group(2) @binding(0)
+var<uniform> transform_uniform: TransformationUniforms;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) texture_coordinates: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coordinates: vec2<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) depth: f32,
+};
+
+struct InstanceInput {
+    @location(3) model_matrix_0: vec4<f32>,
+    @location(4) model_matrix_1: vec4<f32>,
+    @location(5) model_matrix_2: vec4<f32>,
+    @location(6) model_matrix_3: vec4<f32>,
+};
+
+@vertex
+fn vs_main(input: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let world_matrix = model_matrix * transform_uniform.transform_matrix;
+    let world_position = world_matrix * vec4<f32>(input.position, 1.0);
+    let origin = world_matrix * vec4<f32>(0.0, 0.0, 0.0, 1.0);
+
+    var out: VertexOutput;
+    out.texture_coordinates = input.texture_coordinates;
+    out.normal = (world_matrix * vec4<f32>(input.normal, 0.0)).xyz;
+    // towards the camera is brighter, and the origin of the object is at half
+    out.depth = 0.5 + (world_position.z - origin.z) / (2.0 * RADIUS);
+    //@CAMERA_VERTEX
+    return out;
+}
+
+struct FragmentUniforms {
+    color: vec4<f32>,
+};
+@group(2) @binding(1)
+var<uniform> fragment_uniforms: FragmentUniforms;
+
+@group(0) @binding(0)
+var texture_diffuse: texture_2d<f32>;
+
+@group(0) @binding(1)
+var sampler_diffuse: sampler;
+
+// the values are decoded from sRGB beforehand on sRGB targets, so they are stored as they are
+fn to_target(value: vec3<f32>) -> vec3<f32> {
+    if !SRGB {
+        return value;
+    }
+    return select(pow((value + 0.055) / 1.055, vec3<f32>(2.4)), value / 12.92, value <= vec3<f32>(0.04045));
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    var alpha = textureSample(texture_diffuse, sampler_diffuse, input.texture_coordinates).a;
+    if fragment_uniforms.color.w != 0.0 {
+        alpha *= fragment_uniforms.color.w;
+    }
+
+    if OUTPUT == 1u {
+        let depth = clamp(input.depth, 0.0, 1.0);
+        return vec4<f32>(to_target(vec3<f32>(depth)), alpha);
+    }
+
+    var normal = vec3<f32>(0.0, 0.0, 1.0);
+    if length(input.normal) > 0.0 {
+        normal = normalize(input.normal);
+    }
+    return vec4<f32>(to_target(normal * 0.5 + 0.5), alpha);
+}
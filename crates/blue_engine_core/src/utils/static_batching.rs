@@ -0,0 +1,316 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use crate::{
+    InstanceRaw, Matrix3, Matrix4, Object, ObjectStorage, PipelineData, Renderer, Shaders,
+    Textures, UnsignedIntType, Vertex, VertexBuffers,
+    utils::shader_variation::{FragmentUniform, ShaderVariation},
+    utils::uniform_pool::UniformSlot,
+};
+
+/// Objects with the same look, that are merged into one draw
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BatchKey {
+    shader: Shaders,
+    texture: Textures,
+    camera_effect: Option<Arc<str>>,
+    render_order: usize,
+    color: [u32; 4],
+    /// The seed and the bits of the time offset
+    variation: (u32, u32),
+}
+
+/// The merged geometry of the static objects that share a shader, texture, color, camera and
+/// render order, drawn with a single draw call
+#[derive(Debug)]
+pub(crate) struct StaticBatch {
+    pub(crate) shader: Shaders,
+    pub(crate) texture: Textures,
+    pub(crate) uniform: UniformSlot,
+    pub(crate) vertex_buffers: VertexBuffers,
+    pub(crate) camera_effect: Option<Arc<str>>,
+    pub(crate) render_order: usize,
+    /// One of the merged objects, which the passes filter the batch by
+    pub(crate) first: Arc<str>,
+}
+
+/// Merges the static objects that look the same into shared vertex and index buffers, so
+/// scenes with many small objects that do not move are drawn with a few draw calls.
+///
+/// The objects are opted in with [Object::set_static]. Their vertices are moved by their
+/// transform and instances once when merged, and the batches are rebuilt when any of the
+/// static objects change. Objects with sub meshes, shared vertex buffers or uniforms, or their
+/// own uniform buffers are drawn on their own.
+#[derive(Debug)]
+pub struct StaticBatches {
+    enabled: bool,
+    batches: Vec<StaticBatch>,
+    /// The names of the objects drawn by the batches
+    batched: HashSet<Arc<str>>,
+    /// A hash of everything the batches were built from
+    signature: Option<u64>,
+    /// One instance without any transform, shared by the batches
+    instance_buffer: Option<wgpu::Buffer>,
+}
+
+impl StaticBatches {
+    /// Creates the batching, enabled and without batches
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            batches: Vec::new(),
+            batched: HashSet::new(),
+            signature: None,
+            instance_buffer: None,
+        }
+    }
+
+    /// Drops the batches, so they are built again on the next frame
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.batches.clear();
+        self.batched.clear();
+        self.signature = None;
+        self.instance_buffer = None;
+    }
+
+    /// Turns the batching on or off. When off the static objects are drawn on their own
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.batches.clear();
+            self.batched.clear();
+            self.signature = None;
+        }
+    }
+
+    /// Returns true if the static objects are batched
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The amount of batches, which is the amount of draw calls they take
+    pub fn batch_count(&self) -> usize {
+        self.batches.len()
+    }
+
+    /// The amount of objects drawn by the batches
+    pub fn batched_count(&self) -> usize {
+        self.batched.len()
+    }
+
+    /// Returns true if the object is drawn by a batch instead of on its own
+    pub fn is_batched(&self, object: &Object) -> bool {
+        !self.batched.is_empty() && self.batched.contains(&object.name)
+    }
+
+    pub(crate) fn batches(&self) -> impl Iterator<Item = &StaticBatch> {
+        self.batches.iter()
+    }
+
+    pub(crate) fn batch(&self, index: usize) -> Option<&StaticBatch> {
+        self.batches.get(index)
+    }
+
+    pub(crate) fn instance_buffer(&self) -> Option<&wgpu::Buffer> {
+        self.instance_buffer.as_ref()
+    }
+}
+impl Default for StaticBatches {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The shader and texture of the object, if it can be merged with others
+fn batch_key(object: &Object, objects: &ObjectStorage) -> Option<BatchKey> {
+    if !object.is_static
+        || !object.is_visible
+        || object.meshlets.is_some()
+        || object.scissor.is_some()
+        || object.gpu_vertices.is_some()
+        || object.mesh_allocation.is_some()
+        || !object.active_sub_meshes().is_empty()
+        || object.uniform_buffers.len() != 2
+        || !matches!(object.pipeline.vertex_buffer, PipelineData::Data(_))
+        || !matches!(object.pipeline.uniform, PipelineData::Data(Some(_)))
+    {
+        return None;
+    }
+
+    Some(BatchKey {
+        shader: crate::render::get_pipeline_shader(&object.pipeline.shader, objects)?.clone(),
+        texture: crate::render::get_pipeline_texture(&object.pipeline.texture, objects)?.clone(),
+        camera_effect: object.camera_effect.clone(),
+        render_order: object.render_order,
+        color: object.color.to_array().map(f32::to_bits),
+        variation: (
+            object.variation.seed,
+            object.variation.time_offset.to_bits(),
+        ),
+    })
+}
+
+/// A hash of everything in the object that changes its batch
+fn object_signature(object: &Object, key: &BatchKey) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    object.name.hash(&mut hasher);
+    key.hash(&mut hasher);
+    if let PipelineData::Data(vertex_buffers) = &object.pipeline.vertex_buffer {
+        vertex_buffers.vertex_buffer.hash(&mut hasher);
+        vertex_buffers.index_buffer.hash(&mut hasher);
+    }
+    bytemuck::bytes_of(&object.translation_matrix).hash(&mut hasher);
+    bytemuck::bytes_of(&object.rotation_quaternion).hash(&mut hasher);
+    bytemuck::bytes_of(&object.scale_matrix).hash(&mut hasher);
+    for instance in object.instances.iter() {
+        bytemuck::bytes_of(&instance.position).hash(&mut hasher);
+        bytemuck::bytes_of(&instance.rotation).hash(&mut hasher);
+        bytemuck::bytes_of(&instance.scale).hash(&mut hasher);
+    }
+
+    hasher.finish()
+}
+
+/// The geometry of a batch while it is being merged
+#[derive(Default)]
+struct MergedGeometry {
+    vertices: Vec<Vertex>,
+    indices: Vec<UnsignedIntType>,
+    objects: Vec<Arc<str>>,
+}
+
+impl Renderer {
+    /// Turns the merging of the static objects on or off, see [StaticBatches]
+    pub fn set_static_batching(&mut self, enabled: bool) {
+        self.static_batches.set_enabled(enabled);
+    }
+
+    /// Rebuilds the batches if any of the static objects changed since the last time
+    // the shaders and textures are hashed by their handle, which does not change
+    #[allow(clippy::mutable_key_type)]
+    pub(crate) fn refresh_static_batches(&mut self, objects: &ObjectStorage) {
+        if !self.static_batches.enabled {
+            return;
+        }
+
+        // the objects are visited in no particular order, so their hashes are summed
+        let mut signature = 0u64;
+        let mut count = 0u64;
+        for object in objects.values() {
+            if let Some(key) = batch_key(object, objects) {
+                signature = signature.wrapping_add(object_signature(object, &key));
+                count += 1;
+            }
+        }
+        let signature = signature ^ count;
+        if self.static_batches.signature == Some(signature) {
+            return;
+        }
+        self.static_batches.signature = Some(signature);
+
+        let mut groups = HashMap::<BatchKey, Vec<&Object>>::new();
+        for object in objects.values() {
+            if let Some(key) = batch_key(object, objects) {
+                groups.entry(key).or_default().push(object);
+            }
+        }
+
+        let mut batches = Vec::new();
+        let mut batched = HashSet::new();
+        for (key, group) in groups {
+            // a single object is already one draw call
+            if group.len() < 2 {
+                continue;
+            }
+
+            let mut chunks = vec![MergedGeometry::default()];
+            for object in group {
+                let (vertices, indices) = match object.mesh.as_ref() {
+                    Some(mesh) => (mesh.vertices.as_slice(), mesh.indices.as_slice()),
+                    None => (object.vertices.as_slice(), object.indices.as_slice()),
+                };
+                let prepared = object.prepare_update();
+                let vertex_count = vertices.len() * prepared.instances.len();
+                // the indices could not reach all the vertices of the object
+                if vertex_count > UnsignedIntType::MAX as usize + 1 || vertex_count == 0 {
+                    continue;
+                }
+
+                if chunks.last().is_some_and(|i| {
+                    i.vertices.len() + vertex_count > UnsignedIntType::MAX as usize + 1
+                }) {
+                    chunks.push(MergedGeometry::default());
+                }
+                let Some(chunk) = chunks.last_mut() else {
+                    continue;
+                };
+
+                for instance in prepared.instances.iter() {
+                    let matrix = instance.model * prepared.transform_matrix;
+                    let normal_matrix = Matrix3::from_mat4(matrix).inverse().transpose();
+                    let base = chunk.vertices.len();
+
+                    chunk.vertices.extend(vertices.iter().map(|i| {
+                        Vertex {
+                            position: matrix.transform_point3(i.position.into()).to_array(),
+                            uv: i.uv,
+                            normal: (normal_matrix * crate::Vector3::from(i.normal))
+                                .normalize_or_zero()
+                                .to_array(),
+                        }
+                    }));
+                    chunk.indices.extend(
+                        indices
+                            .iter()
+                            .map(|i| (*i as usize + base) as UnsignedIntType),
+                    );
+                }
+                chunk.objects.push(object.name.clone());
+            }
+
+            for chunk in chunks {
+                // a chunk of a single object gains nothing from being merged
+                if chunk.objects.len() < 2 {
+                    continue;
+                }
+
+                let uniform = self.allocate_uniform_slot();
+                self.write_uniform_slot(
+                    &uniform,
+                    Matrix4::IDENTITY,
+                    FragmentUniform::new(
+                        crate::Vector4::from_array(key.color.map(f32::from_bits)),
+                        ShaderVariation::new(key.variation.0, f32::from_bits(key.variation.1)),
+                    ),
+                );
+                let vertex_buffers = self.build_vertex_buffer(&chunk.vertices, &chunk.indices);
+
+                batches.push(StaticBatch {
+                    shader: key.shader.clone(),
+                    texture: key.texture.clone(),
+                    uniform,
+                    vertex_buffers,
+                    camera_effect: key.camera_effect.clone(),
+                    render_order: key.render_order,
+                    first: chunk.objects[0].clone(),
+                });
+                batched.extend(chunk.objects);
+            }
+        }
+
+        if self.static_batches.instance_buffer.is_none() && !batches.is_empty() {
+            self.static_batches.instance_buffer = Some(self.build_instance(vec![InstanceRaw {
+                model: Matrix4::IDENTITY,
+            }]));
+        }
+        self.static_batches.batches = batches;
+        self.static_batches.batched = batched;
+    }
+}
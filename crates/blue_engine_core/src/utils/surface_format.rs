@@ -0,0 +1,161 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::Renderer;
+
+/// The color space the frames are presented to the window in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OutputColor {
+    /// The colors are presented in sRGB, which every window supports
+    #[default]
+    Sdr,
+    /// The colors are presented as linear half floats in the extended sRGB range, where 1.0 is
+    /// 80 nits and the bright parts go past it. Supported on Windows and macOS with an HDR
+    /// display
+    ScRgb,
+    /// The colors are presented as 10 bit values in the Rec. 2020 space, encoded with the PQ
+    /// curve of HDR10. This only looks right where the platform presents 10 bit surfaces as
+    /// HDR10, such as on Android with an HDR display
+    Hdr10,
+}
+impl OutputColor {
+    /// The format of the surface this is presented with
+    pub fn format(&self) -> Option<wgpu::TextureFormat> {
+        match self {
+            Self::Sdr => None,
+            Self::ScRgb => Some(wgpu::TextureFormat::Rgba16Float),
+            Self::Hdr10 => Some(wgpu::TextureFormat::Rgb10a2Unorm),
+        }
+    }
+
+    pub(crate) fn mode(&self) -> u32 {
+        match self {
+            Self::Sdr => 0,
+            Self::ScRgb => 1,
+            Self::Hdr10 => 2,
+        }
+    }
+}
+
+/// What was chosen for the surface of the window, and what the platform offered, see
+/// [Renderer::surface_report]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurfaceReport {
+    /// The format the frames are rendered in
+    pub format: wgpu::TextureFormat,
+    /// How the alpha of the frames is used by the compositor
+    pub alpha_mode: wgpu::CompositeAlphaMode,
+    /// How the frames are presented
+    pub present_mode: wgpu::PresentMode,
+    /// The color space the frames are presented in, which is [OutputColor::Sdr] when the one
+    /// asked for is not supported
+    pub output: OutputColor,
+    /// The formats the platform supports for the surface, the preferred one first
+    pub supported_formats: Vec<wgpu::TextureFormat>,
+    /// The alpha modes the platform supports for the surface
+    pub supported_alpha_modes: Vec<wgpu::CompositeAlphaMode>,
+    /// The present modes the platform supports for the surface
+    pub supported_present_modes: Vec<wgpu::PresentMode>,
+}
+
+/// Picks the format, alpha mode, and present mode of the surface from what the platform
+/// supports, closest to what was asked for. HDR output is only used when the scene is rendered
+/// in HDR, as it is brought into the color space of the window by the tonemapping
+pub(crate) fn negotiate(
+    capabilities: &wgpu::SurfaceCapabilities,
+    config: &wgpu::SurfaceConfiguration,
+    output: OutputColor,
+    hdr_scene: bool,
+    transparent: bool,
+) -> SurfaceReport {
+    let (format, output) = match output.format() {
+        Some(format) if hdr_scene && capabilities.formats.contains(&format) => (format, output),
+        _ => {
+            if output != OutputColor::Sdr {
+                eprintln!("The window does not support {output:?} output, using SDR");
+            }
+            // the sRGB formats are preferred in the order the platform gives them, so the
+            // colors are encoded by the GPU
+            let format = capabilities
+                .formats
+                .iter()
+                .copied()
+                .find(|f| f.is_srgb())
+                .unwrap_or(capabilities.formats[0]);
+            (format, OutputColor::Sdr)
+        }
+    };
+
+    let alpha_mode = if config.alpha_mode != wgpu::CompositeAlphaMode::Auto
+        && capabilities.alpha_modes.contains(&config.alpha_mode)
+    {
+        config.alpha_mode
+    } else {
+        // the alpha of the frame is only used by the compositor in these modes
+        let transparent_mode = transparent
+            .then(|| {
+                capabilities.alpha_modes.iter().copied().find(|i| {
+                    matches!(
+                        i,
+                        wgpu::CompositeAlphaMode::PreMultiplied
+                            | wgpu::CompositeAlphaMode::PostMultiplied
+                    )
+                })
+            })
+            .flatten();
+        if transparent && transparent_mode.is_none() {
+            eprintln!("The window surface does not support transparency");
+        }
+
+        transparent_mode.unwrap_or_else(|| {
+            if capabilities
+                .alpha_modes
+                .contains(&wgpu::CompositeAlphaMode::Opaque)
+            {
+                wgpu::CompositeAlphaMode::Opaque
+            } else {
+                capabilities.alpha_modes[0]
+            }
+        })
+    };
+
+    // the automatic modes are resolved by wgpu, and FIFO is supported everywhere
+    let present_mode = match config.present_mode {
+        wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync => config.present_mode,
+        present_mode if capabilities.present_modes.contains(&present_mode) => present_mode,
+        present_mode => {
+            eprintln!("The window does not support the present mode {present_mode:?}, using FIFO");
+            wgpu::PresentMode::Fifo
+        }
+    };
+
+    SurfaceReport {
+        format,
+        alpha_mode,
+        present_mode,
+        output,
+        supported_formats: capabilities.formats.clone(),
+        supported_alpha_modes: capabilities.alpha_modes.clone(),
+        supported_present_modes: capabilities.present_modes.clone(),
+    }
+}
+
+impl Renderer {
+    /// What was chosen for the surface of the window and what the platform supports, or
+    /// `None` before the window is created
+    pub fn surface_report(&self) -> Option<&SurfaceReport> {
+        self.surface_report.as_ref()
+    }
+
+    /// The color space the frames are presented in
+    pub fn output_color(&self) -> OutputColor {
+        self.surface_report
+            .as_ref()
+            .map(|i| i.output)
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,537 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use crate::{
+    CameraContainer, ObjectStorage, Renderer, SignalStorage, Window, asset_server::Handle,
+    asset_server::LoadState,
+};
+
+/// The amount of times a task can be resumed in a single frame, such as when it accesses the
+/// engine many times in a row
+const MAX_TASK_STEPS: u32 = 64;
+
+/// A handle to a task of the [TaskRunner], used to cancel it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TaskId(u64);
+
+/// The engine, as given to the closures of [TaskContext::with]
+pub struct TaskFrame<'a> {
+    /// The renderer of the engine
+    pub renderer: &'a mut Renderer,
+    /// The window of the engine
+    pub window: &'a mut Window,
+    /// The objects of the engine
+    pub objects: &'a mut ObjectStorage,
+    /// The input of this frame
+    pub input: &'a crate::utils::winit_input_helper::WinitInputHelper,
+    /// The cameras of the engine
+    pub camera: &'a mut CameraContainer,
+    /// The signals of the engine
+    pub signals: &'a mut SignalStorage,
+}
+
+type EngineAccess<R = ()> = Box<dyn FnOnce(&mut TaskFrame) -> R>;
+type BoxedTask = Pin<Box<dyn Future<Output = ()>>>;
+
+/// The state shared between the runner and the contexts of its tasks
+#[derive(Default)]
+struct TaskShared {
+    frame: Cell<u64>,
+    time: Cell<f64>,
+    delta_time: Cell<f32>,
+    next_id: Cell<u64>,
+    /// The closures waiting for the engine, ran between the polls of the tasks
+    accesses: RefCell<Vec<EngineAccess>>,
+    /// The tasks spawned from inside other tasks
+    spawned: RefCell<Vec<(TaskId, BoxedTask)>>,
+}
+
+impl TaskShared {
+    fn next_id(&self) -> TaskId {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        TaskId(id)
+    }
+}
+
+/// Given to each task, to wait for frames, time, and assets, and to reach the engine
+#[derive(Clone)]
+pub struct TaskContext {
+    shared: Rc<TaskShared>,
+}
+
+impl TaskContext {
+    /// Waits until the next frame
+    pub fn next_frame(&self) -> WaitFrames {
+        self.frames(1)
+    }
+
+    /// Waits for the amount of frames
+    pub fn frames(&self, frames: u64) -> WaitFrames {
+        WaitFrames {
+            shared: self.shared.clone(),
+            frames,
+            target: None,
+        }
+    }
+
+    /// Waits for the amount of seconds
+    pub fn seconds(&self, seconds: f32) -> WaitSeconds {
+        WaitSeconds {
+            shared: self.shared.clone(),
+            seconds,
+            target: None,
+        }
+    }
+
+    /// Runs the closure with the engine and returns its result. This is how a task changes
+    /// the objects, the camera, and the rest of the engine, as it can not keep them between
+    /// the frames
+    pub fn with<R: 'static>(
+        &self,
+        access: impl FnOnce(&mut TaskFrame) -> R + 'static,
+    ) -> WithEngine<R> {
+        WithEngine {
+            shared: self.shared.clone(),
+            access: Some(Box::new(access)),
+            result: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Waits until the asset is done loading, and returns if it loaded or failed
+    pub async fn asset<T: 'static>(&self, handle: Handle<T>) -> LoadState {
+        loop {
+            let state = self
+                .with(move |frame| frame.renderer.assets.state(handle))
+                .await;
+            if state != LoadState::Loading {
+                return state;
+            }
+            self.next_frame().await;
+        }
+    }
+
+    /// Starts another task, which runs alongside this one
+    pub fn spawn<F: Future<Output = ()> + 'static>(
+        &self,
+        task: impl FnOnce(TaskContext) -> F,
+    ) -> TaskId {
+        let id = self.shared.next_id();
+        let future = task(self.clone());
+        self.shared
+            .spawned
+            .borrow_mut()
+            .push((id, Box::pin(future)));
+
+        id
+    }
+
+    /// The amount of frames since the runner started
+    pub fn frame(&self) -> u64 {
+        self.shared.frame.get()
+    }
+
+    /// The time in seconds since the runner started
+    pub fn time(&self) -> f64 {
+        self.shared.time.get()
+    }
+
+    /// The time between the last frame and the current one, in seconds
+    pub fn delta_time(&self) -> f32 {
+        self.shared.delta_time.get()
+    }
+}
+
+/// The future of [TaskContext::frames]
+pub struct WaitFrames {
+    shared: Rc<TaskShared>,
+    frames: u64,
+    target: Option<u64>,
+}
+impl Future for WaitFrames {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        let frame = self.shared.frame.get();
+        let frames = self.frames;
+        let target = *self.target.get_or_insert(frame + frames);
+
+        if frame >= target {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// The future of [TaskContext::seconds]
+pub struct WaitSeconds {
+    shared: Rc<TaskShared>,
+    seconds: f32,
+    target: Option<f64>,
+}
+impl Future for WaitSeconds {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        let time = self.shared.time.get();
+        let seconds = self.seconds.max(0.0) as f64;
+        let target = *self.target.get_or_insert(time + seconds);
+
+        if time >= target {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// The future of [TaskContext::with]
+pub struct WithEngine<R> {
+    shared: Rc<TaskShared>,
+    access: Option<EngineAccess<R>>,
+    result: Rc<RefCell<Option<R>>>,
+}
+impl<R: 'static> Future for WithEngine<R> {
+    type Output = R;
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<R> {
+        if let Some(access) = self.access.take() {
+            // the runner has the engine, so the closure is handed to it and the task is
+            // resumed right after it ran
+            let result = self.result.clone();
+            self.shared
+                .accesses
+                .borrow_mut()
+                .push(Box::new(move |frame| {
+                    *result.borrow_mut() = Some(access(frame));
+                }));
+            return Poll::Pending;
+        }
+
+        match self.result.borrow_mut().take() {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Runs async tasks alongside the update loop, for logic that happens in steps over many
+/// frames, such as cutscenes and tutorials. The tasks are resumed every frame, after the
+/// update loop, and can wait for frames, time, and assets in between:
+///
+/// ```ignore
+/// engine.spawn(|ctx| async move {
+///     ctx.seconds(2.0).await;
+///     ctx.with(|frame| {
+///         if let Some(door) = frame.objects.get_mut("door") {
+///             door.set_position((0.0, 2.0, 0.0));
+///         }
+///     })
+///     .await;
+/// });
+/// ```
+pub struct TaskRunner {
+    shared: Rc<TaskShared>,
+    tasks: Vec<(TaskId, BoxedTask)>,
+}
+
+impl TaskRunner {
+    /// Creates a new runner without tasks
+    pub fn new() -> Self {
+        Self {
+            shared: Rc::new(TaskShared::default()),
+            tasks: Vec::new(),
+        }
+    }
+
+    /// Starts a task, which is first resumed on the next frame
+    pub fn spawn<F: Future<Output = ()> + 'static>(
+        &mut self,
+        task: impl FnOnce(TaskContext) -> F,
+    ) -> TaskId {
+        let context = TaskContext {
+            shared: self.shared.clone(),
+        };
+        let id = self.shared.next_id();
+        self.tasks.push((id, Box::pin(task(context))));
+
+        id
+    }
+
+    /// Stops the task. Returns false if it already finished
+    pub fn cancel(&mut self, task: TaskId) -> bool {
+        let count = self.tasks.len();
+        self.tasks.retain(|(id, _)| *id != task);
+        self.shared
+            .spawned
+            .borrow_mut()
+            .retain(|(id, _)| *id != task);
+
+        self.tasks.len() != count
+    }
+
+    /// Returns true if the task is still running
+    pub fn is_running(&self, task: TaskId) -> bool {
+        self.tasks.iter().any(|(id, _)| *id == task)
+            || self
+                .shared
+                .spawned
+                .borrow()
+                .iter()
+                .any(|(id, _)| *id == task)
+    }
+
+    /// The amount of tasks that are still running
+    pub fn len(&self) -> usize {
+        self.tasks.len() + self.shared.spawned.borrow().len()
+    }
+
+    /// Returns true if there are no tasks
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stops all the tasks
+    pub fn clear(&mut self) {
+        self.tasks.clear();
+        self.shared.spawned.borrow_mut().clear();
+    }
+
+    /// Moves to the next frame and resumes the tasks until they wait for something
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn poll(
+        &mut self,
+        delta_time: f32,
+        renderer: &mut Renderer,
+        window: &mut Window,
+        objects: &mut ObjectStorage,
+        input: &crate::utils::winit_input_helper::WinitInputHelper,
+        camera: &mut CameraContainer,
+        signals: &mut SignalStorage,
+    ) {
+        let mut frame = TaskFrame {
+            renderer,
+            window,
+            objects,
+            input,
+            camera,
+            signals,
+        };
+        self.resume(delta_time, |access| access(&mut frame));
+    }
+
+    /// Moves to the next frame and resumes the tasks, giving the closures they wait on to `run`
+    fn resume(&mut self, delta_time: f32, mut run: impl FnMut(EngineAccess)) {
+        self.shared.frame.set(self.shared.frame.get() + 1);
+        self.shared
+            .time
+            .set(self.shared.time.get() + delta_time as f64);
+        self.shared.delta_time.set(delta_time);
+
+        self.tasks.append(&mut self.shared.spawned.borrow_mut());
+        if self.tasks.is_empty() {
+            return;
+        }
+
+        let mut context = Context::from_waker(std::task::Waker::noop());
+
+        // the tasks are resumed every frame, so they do not need to be woken
+        self.tasks.retain_mut(|(_, task)| {
+            for _ in 0..MAX_TASK_STEPS {
+                if task.as_mut().poll(&mut context).is_ready() {
+                    return false;
+                }
+
+                let accesses = std::mem::take(&mut *self.shared.accesses.borrow_mut());
+                if accesses.is_empty() {
+                    break;
+                }
+                accesses.into_iter().for_each(&mut run);
+            }
+
+            true
+        });
+    }
+}
+impl Default for TaskRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl std::fmt::Debug for TaskRunner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TaskRunner")
+            .field("frame", &self.shared.frame.get())
+            .field("tasks", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A log the tasks write to, with the frame they wrote at
+    fn log() -> Rc<RefCell<Vec<(&'static str, u64)>>> {
+        Rc::new(RefCell::new(Vec::new()))
+    }
+
+    /// Resumes the tasks without an engine, dropping the closures they wait on
+    fn step(runner: &mut TaskRunner, delta_time: f32) {
+        runner.resume(delta_time, drop);
+    }
+
+    #[test]
+    fn tasks_start_on_the_next_frame_and_wait_for_frames() {
+        let mut runner = TaskRunner::new();
+        let ran = log();
+        let written = ran.clone();
+        let task = runner.spawn(|ctx| async move {
+            written.borrow_mut().push(("start", ctx.frame()));
+            ctx.next_frame().await;
+            written.borrow_mut().push(("next", ctx.frame()));
+            ctx.frames(3).await;
+            written.borrow_mut().push(("end", ctx.frame()));
+        });
+        assert!(ran.borrow().is_empty());
+        assert!(runner.is_running(task));
+
+        for _ in 0..5 {
+            step(&mut runner, 0.016);
+        }
+        assert_eq!(*ran.borrow(), [("start", 1), ("next", 2), ("end", 5)]);
+        assert!(!runner.is_running(task));
+        assert!(runner.is_empty());
+    }
+
+    #[test]
+    fn waiting_for_seconds() {
+        let mut runner = TaskRunner::new();
+        let ran = log();
+        let written = ran.clone();
+        runner.spawn(|ctx| async move {
+            ctx.seconds(0.5).await;
+            written.borrow_mut().push(("half", ctx.frame()));
+            ctx.seconds(0.0).await;
+            written.borrow_mut().push(("none", ctx.frame()));
+            ctx.seconds(1.0).await;
+            written.borrow_mut().push(("one", ctx.frame()));
+        });
+
+        for _ in 0..8 {
+            step(&mut runner, 0.25);
+        }
+        // the wait starts on the frame it is made, at 0.25 seconds, so it ends at 0.75
+        assert_eq!(*ran.borrow(), [("half", 3), ("none", 3), ("one", 7)]);
+    }
+
+    #[test]
+    fn time_and_delta_time() {
+        let mut runner = TaskRunner::new();
+        let times = Rc::new(RefCell::new(Vec::new()));
+        let written = times.clone();
+        runner.spawn(|ctx| async move {
+            loop {
+                written.borrow_mut().push((ctx.time(), ctx.delta_time()));
+                ctx.next_frame().await;
+            }
+        });
+
+        step(&mut runner, 0.5);
+        step(&mut runner, 0.25);
+        assert_eq!(*times.borrow(), [(0.5, 0.5), (0.75, 0.25)]);
+    }
+
+    #[test]
+    fn spawning_from_a_task() {
+        let mut runner = TaskRunner::new();
+        let ran = log();
+        let written = ran.clone();
+        let child = Rc::new(Cell::new(None));
+        let child_id = child.clone();
+        runner.spawn(|ctx| async move {
+            let written = written.clone();
+            child_id.set(Some(ctx.spawn(|ctx| async move {
+                written.borrow_mut().push(("child", ctx.frame()));
+                ctx.next_frame().await;
+            })));
+        });
+
+        step(&mut runner, 0.016);
+        // the spawned task is counted, and first resumed on the next frame
+        let child = child.get().expect("the child was spawned");
+        assert!(runner.is_running(child));
+        assert_eq!(runner.len(), 1);
+        assert!(ran.borrow().is_empty());
+
+        step(&mut runner, 0.016);
+        assert_eq!(*ran.borrow(), [("child", 2)]);
+        step(&mut runner, 0.016);
+        assert!(runner.is_empty());
+    }
+
+    #[test]
+    fn cancelling_and_clearing() {
+        let mut runner = TaskRunner::new();
+        let ran = log();
+        let tasks: Vec<_> = ["first", "second", "third"]
+            .into_iter()
+            .map(|name| {
+                let written = ran.clone();
+                runner.spawn(move |ctx| async move {
+                    loop {
+                        written.borrow_mut().push((name, ctx.frame()));
+                        ctx.next_frame().await;
+                    }
+                })
+            })
+            .collect();
+
+        step(&mut runner, 0.016);
+        assert!(runner.cancel(tasks[1]));
+        assert!(!runner.cancel(tasks[1]));
+        step(&mut runner, 0.016);
+        assert_eq!(
+            *ran.borrow(),
+            [
+                ("first", 1),
+                ("second", 1),
+                ("third", 1),
+                ("first", 2),
+                ("third", 2)
+            ]
+        );
+
+        runner.clear();
+        assert!(runner.is_empty());
+        assert!(!runner.is_running(tasks[0]));
+        step(&mut runner, 0.016);
+        assert_eq!(ran.borrow().len(), 5);
+    }
+
+    #[test]
+    fn engine_access_is_handed_to_the_runner() {
+        let mut runner = TaskRunner::new();
+        runner.spawn(|ctx| async move {
+            ctx.with(|frame| frame.objects.len()).await;
+        });
+
+        let mut accesses = 0;
+        for _ in 0..3 {
+            runner.resume(0.016, |_| accesses += 1);
+        }
+        // without the engine the closure never runs, so the task keeps waiting on it
+        assert_eq!(accesses, 1);
+        assert_eq!(runner.len(), 1);
+    }
+}
@@ -0,0 +1,208 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{ObjectStorage, PipelineData, Renderer, Textures};
+
+/// The smallest size a texture is downscaled to for the budget
+const MIN_BUDGET_SIZE: u32 = 64;
+
+/// What is done with the textures loaded while the budget is exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OverBudget {
+    /// The textures are loaded as they are, with a warning
+    #[default]
+    Warn,
+    /// The textures are halved in size until they fit, down to 64 pixels, with a warning if
+    /// they still do not fit
+    Downscale,
+}
+
+/// Limits on the size and GPU memory of the textures, applied as they are loaded. Changing
+/// them does not change the textures that are already loaded.
+///
+/// The memory is counted for the textures made by the renderer that are used by the objects,
+/// including their mipmap levels
+///
+/// ```ignore
+/// renderer.texture_budget.max_size = Some(2048);
+/// renderer.texture_budget.budget = Some(512 * 1024 * 1024);
+/// renderer.texture_budget.over_budget = OverBudget::Downscale;
+/// ```
+#[derive(Debug)]
+pub struct TextureBudget {
+    /// The largest width or height of the textures. The larger ones are downscaled to fit as
+    /// they are loaded, keeping their aspect ratio. The largest size the device supports is
+    /// always applied
+    pub max_size: Option<u32>,
+    /// The filter the textures are downscaled with
+    pub filter: image::imageops::FilterType,
+    /// The bytes of GPU memory the textures may take, or `None` for no limit
+    pub budget: Option<u64>,
+    /// What is done with the textures loaded while the budget is exceeded
+    pub over_budget: OverBudget,
+    /// The textures that are counted, and their size in bytes
+    textures: HashMap<Textures, u64>,
+    /// Set once the budget is exceeded, so it is only warned about once until it fits again
+    warned: bool,
+}
+
+impl TextureBudget {
+    /// Creates a budget without any limits, with the textures downscaled with Lanczos
+    pub fn new() -> Self {
+        Self {
+            max_size: None,
+            filter: image::imageops::FilterType::Lanczos3,
+            budget: None,
+            over_budget: OverBudget::Warn,
+            textures: HashMap::new(),
+            warned: false,
+        }
+    }
+
+    /// The bytes of GPU memory taken by the textures in use
+    pub fn used(&self) -> u64 {
+        self.textures.values().sum()
+    }
+
+    /// The amount of textures in use
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    /// Returns true if no texture is counted
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+
+    /// Returns true if the textures take more memory than the budget
+    pub fn is_exceeded(&self) -> bool {
+        self.budget.is_some_and(|i| self.used() > i)
+    }
+
+    /// Counts the texture until it is no longer used by an object
+    pub(crate) fn track(&mut self, texture: &Textures, bytes: u64) {
+        self.textures.insert(texture.clone(), bytes);
+    }
+
+    /// Forgets the textures no object uses anymore
+    // the textures are hashed by their handle, which does not change
+    #[allow(clippy::mutable_key_type)]
+    pub(crate) fn collect(&mut self, objects: &ObjectStorage) {
+        if self.textures.is_empty() {
+            return;
+        }
+
+        let in_use = objects
+            .values()
+            .flat_map(|i| {
+                let texture = match &i.pipeline.texture {
+                    PipelineData::Data(texture) => Some(texture),
+                    PipelineData::Copy(_) => None,
+                };
+                texture
+                    .into_iter()
+                    .chain(i.materials.iter().filter_map(|j| j.texture.as_ref()))
+            })
+            .collect::<HashSet<_>>();
+        self.textures.retain(|texture, _| in_use.contains(texture));
+
+        if !self.is_exceeded() {
+            self.warned = false;
+        }
+    }
+
+    /// Downscales the image to the largest size, and to the budget if it is set to
+    fn fit(
+        &mut self,
+        name: &str,
+        image: image::DynamicImage,
+        device_max: u32,
+    ) -> image::DynamicImage {
+        let max_size = self.max_size.unwrap_or(u32::MAX).min(device_max).max(1);
+        let (mut width, mut height) = (image.width(), image.height());
+        if width.max(height) > max_size {
+            let scale = max_size as f32 / width.max(height) as f32;
+            width = ((width as f32 * scale).round() as u32).clamp(1, max_size);
+            height = ((height as f32 * scale).round() as u32).clamp(1, max_size);
+        }
+
+        if let Some(budget) = self.budget {
+            let used = self.used();
+            let fits = |width: u32, height: u32| used + texture_bytes(width, height, 4) <= budget;
+            if !fits(width, height) {
+                if self.over_budget == OverBudget::Downscale {
+                    while !fits(width, height) && width.max(height) / 2 >= MIN_BUDGET_SIZE {
+                        width = (width / 2).max(1);
+                        height = (height / 2).max(1);
+                    }
+                }
+                if !fits(width, height) && !self.warned {
+                    eprintln!(
+                        "The textures exceed the budget of {budget} bytes with {name}, which is loaded at {width}x{height}"
+                    );
+                    self.warned = true;
+                }
+            }
+        }
+
+        if (width, height) == (image.width(), image.height()) {
+            image
+        } else {
+            image.resize_exact(width, height, self.filter)
+        }
+    }
+}
+impl Default for TextureBudget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The bytes taken by a texture with all of its mipmap levels
+pub fn texture_bytes(width: u32, height: u32, bytes_per_pixel: u64) -> u64 {
+    (0..crate::utils::mipmaps::mip_level_count(width, height))
+        .map(|level| ((width >> level).max(1) as u64) * ((height >> level).max(1) as u64))
+        .sum::<u64>()
+        * bytes_per_pixel
+}
+
+impl Renderer {
+    /// Downscales the image of a texture that is being loaded to the limits of
+    /// [Renderer::texture_budget]
+    pub(crate) fn fit_texture(
+        &mut self,
+        name: &str,
+        image: image::DynamicImage,
+    ) -> image::DynamicImage {
+        let device_max = self.device.limits().max_texture_dimension_2d;
+        self.texture_budget.fit(name, image, device_max)
+    }
+
+    /// Counts the texture made on the GPU in the budget
+    pub(crate) fn track_texture(&mut self, texture: &Textures, gpu_texture: &wgpu::Texture) {
+        let size = gpu_texture.size();
+        let bytes_per_pixel = gpu_texture.format().block_copy_size(None).unwrap_or(4) as u64;
+        let (block_width, block_height) = gpu_texture.format().block_dimensions();
+        let bytes = (0..gpu_texture.mip_level_count())
+            .map(|level| {
+                let width = (size.width >> level).max(1).div_ceil(block_width) as u64;
+                let height = (size.height >> level).max(1).div_ceil(block_height) as u64;
+                width * height * bytes_per_pixel
+            })
+            .sum();
+
+        self.texture_budget.track(texture, bytes);
+    }
+
+    /// Forgets the textures no longer in use, and updates the texture memory of the stats
+    pub(crate) fn collect_textures(&mut self, objects: &ObjectStorage) {
+        self.texture_budget.collect(objects);
+        self.stats.texture_memory = self.texture_budget.used();
+    }
+}
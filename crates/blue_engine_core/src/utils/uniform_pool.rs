@@ -0,0 +1,290 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    InstanceRaw, Matrix4, Renderer, UniformBuffers, utils::shader_variation::FragmentUniform,
+};
+
+/// The slots the pool starts with, doubled each time it runs out
+const INITIAL_SLOTS: u32 = 64;
+/// The size of the transformation matrix in a slot
+const TRANSFORM_SIZE: u64 = std::mem::size_of::<Matrix4>() as u64;
+/// The size of the color and variation in a slot
+const FRAGMENT_SIZE: u64 = std::mem::size_of::<FragmentUniform>() as u64;
+
+/// The place of an object in the [UniformPool], given back to the pool when dropped
+#[derive(Debug)]
+pub struct UniformSlot {
+    index: u32,
+    free: Arc<Mutex<Vec<u32>>>,
+}
+
+impl UniformSlot {
+    /// The index of the slot in the pool
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+}
+impl Drop for UniformSlot {
+    fn drop(&mut self) {
+        if let Ok(mut free) = self.free.lock() {
+            free.push(self.index);
+        }
+    }
+}
+
+/// One uniform buffer shared by all the objects that only have the default transformation
+/// and color uniforms, each at its own offset. Updating an object writes its slot in place,
+/// so no buffers or bind groups are made after the pool has grown to fit the scene.
+///
+/// The pool doubles in size when it runs out of slots, copying the slots over. Objects with
+/// uniform buffers of their own keep their own bind group, and write their buffers in place
+#[derive(Debug)]
+pub struct UniformPool {
+    buffer: Option<wgpu::Buffer>,
+    bind_group: Option<UniformBuffers>,
+    layout: Option<wgpu::BindGroupLayout>,
+    /// The distance between the transformation and the color, and between the slots
+    stride: u64,
+    capacity: u32,
+    next: u32,
+    free: Arc<Mutex<Vec<u32>>>,
+}
+
+impl UniformPool {
+    /// Creates an empty pool, the buffer is made when the first slot is taken
+    pub fn new() -> Self {
+        Self {
+            buffer: None,
+            bind_group: None,
+            layout: None,
+            stride: 0,
+            capacity: 0,
+            next: 0,
+            free: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// The amount of slots the buffer has room for
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// The amount of slots in use
+    pub fn len(&self) -> usize {
+        let free = self.free.lock().map(|i| i.len()).unwrap_or_default();
+        (self.next as usize).saturating_sub(free)
+    }
+
+    /// Returns true if no slots are in use
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The bind group of the whole buffer, drawn with the offsets of a slot
+    pub(crate) fn bind_group(&self) -> Option<&UniformBuffers> {
+        self.bind_group.as_ref()
+    }
+
+    /// The dynamic offsets of the transformation and color of the slot
+    pub(crate) fn offsets(&self, slot: &UniformSlot) -> [u32; 2] {
+        let start = slot.index as u64 * self.stride * 2;
+        [start as u32, (start + self.stride) as u32]
+    }
+}
+impl Default for UniformPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    /// The layout of the uniforms of the pool, with the offsets given when drawn
+    pub(crate) fn uniform_pool_layout(&mut self) -> wgpu::BindGroupLayout {
+        let pool = &mut self.uniform_pool;
+        if let Some(layout) = pool.layout.as_ref() {
+            return layout.clone();
+        }
+
+        let entry = |binding: u32, size: u64| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: wgpu::BufferSize::new(size),
+            },
+            count: None,
+        };
+        let layout = self.pipeline_cache.layout(
+            &self.device,
+            &[entry(0, TRANSFORM_SIZE), entry(1, FRAGMENT_SIZE)],
+        );
+        self.uniform_pool.layout = Some(layout.clone());
+
+        layout
+    }
+
+    /// Takes a free slot of the pool, growing it if there are none
+    pub(crate) fn allocate_uniform_slot(&mut self) -> UniformSlot {
+        let free = self.uniform_pool.free.lock().ok().and_then(|mut i| i.pop());
+        let index = match free {
+            Some(index) => index,
+            None => {
+                if self.uniform_pool.next >= self.uniform_pool.capacity {
+                    self.grow_uniform_pool();
+                }
+                self.uniform_pool.next += 1;
+                self.uniform_pool.next - 1
+            }
+        };
+
+        UniformSlot {
+            index,
+            free: self.uniform_pool.free.clone(),
+        }
+    }
+
+    /// Writes the transformation, and the color and variation of the slot
+    pub(crate) fn write_uniform_slot(
+        &self,
+        slot: &UniformSlot,
+        transform: Matrix4,
+        fragment: FragmentUniform,
+    ) {
+        let Some(buffer) = self.uniform_pool.buffer.as_ref() else {
+            return;
+        };
+        let [transform_offset, color_offset] = self.uniform_pool.offsets(slot);
+
+        self.queue.write_buffer(
+            buffer,
+            transform_offset as u64,
+            bytemuck::bytes_of(&transform),
+        );
+        self.queue
+            .write_buffer(buffer, color_offset as u64, bytemuck::bytes_of(&fragment));
+    }
+
+    /// The bind group and layout the objects of the pool are drawn with
+    pub(crate) fn uniform_pool_bind_group(&mut self) -> (UniformBuffers, wgpu::BindGroupLayout) {
+        let bind_group = match self.uniform_pool.bind_group.clone() {
+            Some(bind_group) => bind_group,
+            None => self.grow_uniform_pool(),
+        };
+
+        (bind_group, self.uniform_pool_layout())
+    }
+
+    /// Doubles the size of the pool, and copies the slots to the new buffer
+    fn grow_uniform_pool(&mut self) -> UniformBuffers {
+        let layout = self.uniform_pool_layout();
+        let pool = &mut self.uniform_pool;
+        if pool.stride == 0 {
+            let alignment = self.device.limits().min_uniform_buffer_offset_alignment as u64;
+            pool.stride = TRANSFORM_SIZE
+                .max(FRAGMENT_SIZE)
+                .next_multiple_of(alignment);
+        }
+        let capacity = (pool.capacity * 2).max(INITIAL_SLOTS);
+
+        crate::utils::allocation_audit::count_buffer();
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Uniform Pool"),
+            size: capacity as u64 * pool.stride * 2,
+            usage: wgpu::BufferUsages::UNIFORM
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        if let Some(old_buffer) = pool.buffer.as_ref() {
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Uniform Pool Grow Encoder"),
+                });
+            encoder.copy_buffer_to_buffer(old_buffer, 0, &buffer, 0, old_buffer.size());
+            self.queue.submit(Some(encoder.finish()));
+        }
+
+        let binding = |offset: u64, size: u64| {
+            wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &buffer,
+                offset,
+                size: wgpu::BufferSize::new(size),
+            })
+        };
+        crate::utils::allocation_audit::count_bind_group();
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Uniform Pool Bind Group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: binding(0, TRANSFORM_SIZE),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: binding(0, FRAGMENT_SIZE),
+                },
+            ],
+        });
+
+        pool.buffer = Some(buffer);
+        pool.bind_group = Some(bind_group.clone());
+        pool.capacity = capacity;
+
+        bind_group
+    }
+
+    /// Writes the uniform to the buffer in place, or replaces the buffer if it can not hold it.
+    /// Returns true if it was replaced, and so the bind groups with it must be built again
+    pub fn write_uniform_buffer_part<T: bytemuck::Zeroable + bytemuck::Pod>(
+        &self,
+        buffer: &mut wgpu::Buffer,
+        name: impl crate::StringBuffer,
+        value: T,
+    ) -> bool {
+        if buffer.size() == std::mem::size_of::<T>() as u64
+            && buffer.usage().contains(wgpu::BufferUsages::COPY_DST)
+        {
+            self.queue
+                .write_buffer(buffer, 0, bytemuck::cast_slice(&[value]));
+            false
+        } else {
+            *buffer = self.build_uniform_buffer_part(name, value);
+            true
+        }
+    }
+
+    /// Writes the instances to the buffer in place, or replaces it with a larger one if they do
+    /// not fit. The larger buffer has room to grow, so it is not replaced again soon
+    pub fn write_instance_buffer(&self, buffer: &mut wgpu::Buffer, instances: &[InstanceRaw]) {
+        let data: &[u8] = bytemuck::cast_slice(instances);
+        if buffer.size() >= data.len() as u64
+            && buffer.usage().contains(wgpu::BufferUsages::COPY_DST)
+        {
+            if !data.is_empty() {
+                self.queue.write_buffer(buffer, 0, data);
+            }
+            return;
+        }
+
+        crate::utils::allocation_audit::count_buffer();
+        let new_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size: (data.len() as u64)
+                .next_power_of_two()
+                .max(std::mem::size_of::<InstanceRaw>() as u64),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        self.queue.write_buffer(&new_buffer, 0, data);
+        *buffer = new_buffer;
+    }
+}
@@ -0,0 +1,60 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{Renderer, UnsignedIntType, Vertex, VertexBuffers};
+
+/// The amount of floats of each vertex in the storage buffer read by the shaders of the
+/// objects drawn with [crate::ShaderSettings::vertex_pulling]: the position, the texture
+/// coordinates, and the normal, as in [Vertex]
+pub const PULLED_VERTEX_FLOATS: u32 = 8;
+
+impl Renderer {
+    /// Returns true if the shaders can read storage buffers in the vertex stage, which the
+    /// vertex pulling needs. WebGL and some older GPUs can not
+    pub fn supports_vertex_pulling(&self) -> bool {
+        self.adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::VERTEX_STORAGE)
+            && self.device.limits().max_storage_buffers_per_shader_stage > 0
+    }
+
+    /// Uploads the vertices into a buffer that is both a vertex buffer and a storage buffer,
+    /// so the shader can read them by the index of the vertex. Compute shaders can write to
+    /// it to make or change the mesh on the GPU
+    pub fn build_pulled_vertex_buffer(
+        &mut self,
+        vertices: &[Vertex],
+        indices: &[UnsignedIntType],
+    ) -> VertexBuffers {
+        self.write_pulled_vertex_buffer(None, vertices, indices)
+    }
+
+    /// Uploads the vertices into the buffers of the previous call where they fit, so changing
+    /// the mesh does not make new buffers each time
+    pub(crate) fn write_pulled_vertex_buffer(
+        &mut self,
+        buffers: Option<VertexBuffers>,
+        vertices: &[Vertex],
+        indices: &[UnsignedIntType],
+    ) -> VertexBuffers {
+        // an empty storage buffer can not be bound
+        let empty = [<Vertex as bytemuck::Zeroable>::zeroed()];
+        let vertices = match vertices.is_empty() {
+            true => &empty[..],
+            false => vertices,
+        };
+
+        self.write_vertex_buffers(
+            buffers,
+            bytemuck::cast_slice(vertices),
+            bytemuck::cast_slice(indices),
+            "Pulled Vertex Buffer",
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+            indices.len() as u32,
+        )
+    }
+}
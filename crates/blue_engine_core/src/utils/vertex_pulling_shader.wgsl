@@ -0,0 +1,89 @@
+// blocks
+
+//@CAMERA_STRUCT
+
+//@FOG
+
+//@VARIATION
+
+struct TransformationUniforms {
+    transform_matrix: mat4x4<f32>,
+};
+@group(2) @binding(0)
+var<uniform> transform_uniform: TransformationUniforms;
+
+// the vertices of the object, 8 floats each: the position, texture coordinates, and normal
+@group(2) @binding(2)
+var<storage, read> vertices: array<f32>;
+
+struct VertexInput {
+    position: vec3<f32>,
+    texture_coordinates: vec2<f32>,
+    normal: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coordinates: vec2<f32>,
+    //@FOG_OUTPUT
+};
+
+struct InstanceInput {
+    @location(3) model_matrix_0: vec4<f32>,
+    @location(4) model_matrix_1: vec4<f32>,
+    @location(5) model_matrix_2: vec4<f32>,
+    @location(6) model_matrix_3: vec4<f32>,
+};
+
+fn pull_vertex(index: u32) -> VertexInput {
+    let offset = index * 8u;
+
+    var vertex: VertexInput;
+    vertex.position = vec3<f32>(vertices[offset], vertices[offset + 1u], vertices[offset + 2u]);
+    vertex.texture_coordinates = vec2<f32>(vertices[offset + 3u], vertices[offset + 4u]);
+    vertex.normal = vec3<f32>(vertices[offset + 5u], vertices[offset + 6u], vertices[offset + 7u]);
+    return vertex;
+}
+
+// Vertex Stage
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, instance: InstanceInput) -> VertexOutput {
+    let input = pull_vertex(vertex_index);
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    var out: VertexOutput;
+    out.texture_coordinates = input.texture_coordinates;
+    //@CAMERA_VERTEX
+    //@FOG_VERTEX
+    return out;
+}
+
+// Fragment Stage
+struct FragmentUniforms {
+    color: vec4<f32>,
+    random: f32,
+    time_offset: f32,
+    seed: u32,
+};
+@group(2) @binding(1)
+var<uniform> fragment_uniforms: FragmentUniforms;
+
+@group(0) @binding(0)
+var texture_diffuse: texture_2d<f32>;
+
+@group(0) @binding(1)
+var sampler_diffuse: sampler;
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    if fragment_uniforms.color.w == 0.0 {
+        return apply_fog(textureSample(texture_diffuse, sampler_diffuse, input.texture_coordinates), input.fog_distance);
+    } else {
+        return apply_fog(textureSample(texture_diffuse, sampler_diffuse, input.texture_coordinates) * fragment_uniforms.color, input.fog_distance);
+    }
+}
@@ -0,0 +1,350 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{Renderer, UnsignedIntType, Vector2, Vector3, Vertex, VertexBuffers};
+
+/// A vertex in half the size of [Vertex], for the objects drawn with
+/// [crate::ShaderSettings::quantized_vertices]. The position and texture coordinates are
+/// normalized 16 bit integers within the bounds of the mesh, and the normal is encoded on an
+/// octahedron in two of them.
+///
+/// The shader turns them back with the [QuantizationUniform] of the object, see the
+/// [crate::utils::default_resources::QUANTIZED_SHADER] for how
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct QuantizedVertex {
+    /// The position within the bounds of the mesh, the last is only padding
+    pub position: [i16; 4],
+    /// The texture coordinates within the bounds of those of the mesh
+    pub uv: [i16; 2],
+    /// The normal, encoded on an octahedron
+    pub normal: [i16; 2],
+}
+impl QuantizedVertex {
+    pub(crate) fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<QuantizedVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Snorm16x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[i16; 4]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Snorm16x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[i16; 6]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Snorm16x2,
+                },
+            ],
+        }
+    }
+}
+
+/// The scale and offset the quantized vertices of a mesh are turned back with, given to the
+/// shader at binding 2 of the uniforms of the object
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct QuantizationUniform {
+    /// The center of the bounds of the positions, the last is only padding
+    pub position_offset: [f32; 4],
+    /// Half the size of the bounds of the positions, the last is only padding
+    pub position_scale: [f32; 4],
+    /// The center of the bounds of the texture coordinates, then half their size
+    pub uv_offset_scale: [f32; 4],
+}
+impl QuantizationUniform {
+    /// The position of the quantized vertex
+    pub fn position(&self, vertex: &QuantizedVertex) -> Vector3 {
+        Vector3::from_slice(&self.position_offset[..3])
+            + Vector3::from_slice(&self.position_scale[..3])
+                * Vector3::new(
+                    from_snorm(vertex.position[0]),
+                    from_snorm(vertex.position[1]),
+                    from_snorm(vertex.position[2]),
+                )
+    }
+
+    /// The texture coordinates of the quantized vertex
+    pub fn uv(&self, vertex: &QuantizedVertex) -> Vector2 {
+        let [offset_x, offset_y, scale_x, scale_y] = self.uv_offset_scale;
+        Vector2::new(offset_x, offset_y)
+            + Vector2::new(scale_x, scale_y)
+                * Vector2::new(from_snorm(vertex.uv[0]), from_snorm(vertex.uv[1]))
+    }
+}
+impl Default for QuantizationUniform {
+    fn default() -> Self {
+        Self {
+            position_offset: [0.0; 4],
+            position_scale: [1.0, 1.0, 1.0, 0.0],
+            uv_offset_scale: [0.0, 0.0, 1.0, 1.0],
+        }
+    }
+}
+
+/// Quantizes the vertices within their bounds, and returns them with the uniform they are
+/// turned back with. The error is at most 1/65534 of the size of the bounds
+pub fn quantize_vertices(vertices: &[Vertex]) -> (Vec<QuantizedVertex>, QuantizationUniform) {
+    if vertices.is_empty() {
+        return (Vec::new(), QuantizationUniform::default());
+    }
+
+    let (mut position_min, mut position_max) = (Vector3::MAX, Vector3::MIN);
+    let (mut uv_min, mut uv_max) = (Vector2::MAX, Vector2::MIN);
+    for vertex in vertices.iter() {
+        position_min = position_min.min(vertex.position.into());
+        position_max = position_max.max(vertex.position.into());
+        uv_min = uv_min.min(vertex.uv.into());
+        uv_max = uv_max.max(vertex.uv.into());
+    }
+    // flat bounds still need a scale to divide by
+    let position_offset = (position_min + position_max) * 0.5;
+    let position_scale = ((position_max - position_min) * 0.5).max(Vector3::splat(f32::EPSILON));
+    let uv_offset = (uv_min + uv_max) * 0.5;
+    let uv_scale = ((uv_max - uv_min) * 0.5).max(Vector2::splat(f32::EPSILON));
+
+    let quantized = vertices
+        .iter()
+        .map(|vertex| {
+            let position = (Vector3::from(vertex.position) - position_offset) / position_scale;
+            let uv = (Vector2::from(vertex.uv) - uv_offset) / uv_scale;
+            let normal = encode_octahedral(vertex.normal.into());
+
+            QuantizedVertex {
+                position: [
+                    to_snorm(position.x),
+                    to_snorm(position.y),
+                    to_snorm(position.z),
+                    0,
+                ],
+                uv: [to_snorm(uv.x), to_snorm(uv.y)],
+                normal: [to_snorm(normal.x), to_snorm(normal.y)],
+            }
+        })
+        .collect();
+
+    let uniform = QuantizationUniform {
+        position_offset: position_offset.extend(0.0).to_array(),
+        position_scale: position_scale.extend(0.0).to_array(),
+        uv_offset_scale: [uv_offset.x, uv_offset.y, uv_scale.x, uv_scale.y],
+    };
+
+    (quantized, uniform)
+}
+
+/// Encodes a unit normal on an octahedron unfolded onto a square from -1 to 1
+pub fn encode_octahedral(normal: Vector3) -> Vector2 {
+    let length = normal.x.abs() + normal.y.abs() + normal.z.abs();
+    if length == 0.0 {
+        return Vector2::ZERO;
+    }
+    let folded = Vector2::new(normal.x, normal.y) / length;
+    if normal.z >= 0.0 {
+        return folded;
+    }
+
+    // the lower half is folded over the edges of the square
+    let sign = Vector2::new(
+        if folded.x >= 0.0 { 1.0 } else { -1.0 },
+        if folded.y >= 0.0 { 1.0 } else { -1.0 },
+    );
+    (Vector2::ONE - Vector2::new(folded.y.abs(), folded.x.abs())) * sign
+}
+
+/// Decodes a normal encoded by [encode_octahedral]
+pub fn decode_octahedral(encoded: Vector2) -> Vector3 {
+    let mut normal = Vector3::new(
+        encoded.x,
+        encoded.y,
+        1.0 - encoded.x.abs() - encoded.y.abs(),
+    );
+    let fold = (-normal.z).max(0.0);
+    normal.x += if normal.x >= 0.0 { -fold } else { fold };
+    normal.y += if normal.y >= 0.0 { -fold } else { fold };
+
+    normal.normalize_or_zero()
+}
+
+fn to_snorm(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+fn from_snorm(value: i16) -> f32 {
+    (value as f32 / i16::MAX as f32).max(-1.0)
+}
+
+impl Renderer {
+    /// Quantizes the vertices and uploads them, and returns their buffers with the uniform
+    /// they are turned back with
+    pub fn build_quantized_vertex_buffer(
+        &mut self,
+        vertices: &[Vertex],
+        indices: &[UnsignedIntType],
+    ) -> (VertexBuffers, QuantizationUniform) {
+        self.write_quantized_vertex_buffer(None, vertices, indices)
+    }
+
+    /// Quantizes the vertices into the buffers of the previous call where they fit, so
+    /// changing the mesh does not make new buffers each time
+    pub(crate) fn write_quantized_vertex_buffer(
+        &mut self,
+        buffers: Option<VertexBuffers>,
+        vertices: &[Vertex],
+        indices: &[UnsignedIntType],
+    ) -> (VertexBuffers, QuantizationUniform) {
+        let (quantized, uniform) = quantize_vertices(vertices);
+        let vertex_buffers = self.write_vertex_buffers(
+            buffers,
+            bytemuck::cast_slice(quantized.as_slice()),
+            bytemuck::cast_slice(indices),
+            "Quantized Vertex Buffer",
+            wgpu::BufferUsages::VERTEX,
+            indices.len() as u32,
+        );
+
+        (vertex_buffers, uniform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Directions spread over the whole sphere, including the poles and the folded edges
+    fn directions() -> Vec<Vector3> {
+        let mut directions = vec![
+            Vector3::X,
+            Vector3::NEG_X,
+            Vector3::Y,
+            Vector3::NEG_Y,
+            Vector3::Z,
+            Vector3::NEG_Z,
+            Vector3::new(1.0, 1.0, -1.0).normalize(),
+            Vector3::new(-1.0, 0.0, -1.0).normalize(),
+        ];
+        for latitude in 0..=32 {
+            let polar = latitude as f32 / 32.0 * std::f32::consts::PI;
+            for longitude in 0..64 {
+                let azimuth = longitude as f32 / 64.0 * std::f32::consts::TAU;
+                directions.push(Vector3::new(
+                    polar.sin() * azimuth.cos(),
+                    polar.sin() * azimuth.sin(),
+                    polar.cos(),
+                ));
+            }
+        }
+
+        directions
+    }
+
+    #[test]
+    fn octahedral_round_trip() {
+        for direction in directions() {
+            let encoded = encode_octahedral(direction);
+            assert!(encoded.x.abs() <= 1.0 && encoded.y.abs() <= 1.0);
+
+            let decoded = decode_octahedral(encoded);
+            assert!(
+                decoded.distance(direction) < 1e-5,
+                "{direction} came back as {decoded}"
+            );
+        }
+        // a zero normal is at the center of the square, which is the normal facing Z
+        assert_eq!(encode_octahedral(Vector3::ZERO), Vector2::ZERO);
+        assert_eq!(decode_octahedral(Vector2::ZERO), Vector3::Z);
+    }
+
+    #[test]
+    fn quantized_normals_error_bound() {
+        for direction in directions() {
+            let encoded = encode_octahedral(direction);
+            let quantized = Vector2::new(
+                from_snorm(to_snorm(encoded.x)),
+                from_snorm(to_snorm(encoded.y)),
+            );
+
+            // half a step of 16 bits on the square moves the normal by well under 1e-4
+            let error = decode_octahedral(quantized).distance(direction);
+            assert!(error < 1e-4, "{direction} is {error} off");
+        }
+    }
+
+    #[test]
+    fn positions_and_uvs_within_the_error_bound() {
+        let vertices: Vec<Vertex> = (0..100)
+            .map(|i| {
+                let t = i as f32 / 99.0;
+                Vertex {
+                    position: [t * 10.0 - 3.0, (t * 7.0).sin() * 2.0, 5.0 - t * t],
+                    uv: [t, 1.0 - t * 0.5],
+                    normal: [0.0, 1.0, 0.0],
+                }
+            })
+            .collect();
+
+        let (quantized, uniform) = quantize_vertices(&vertices);
+        assert_eq!(quantized.len(), vertices.len());
+
+        // the error is at most 1/65534 of the size of the bounds, which is twice the scale
+        let position_error =
+            Vector3::from_slice(&uniform.position_scale[..3]) / 32767.0 + Vector3::splat(1e-5);
+        let uv_error = Vector2::new(uniform.uv_offset_scale[2], uniform.uv_offset_scale[3])
+            / 32767.0
+            + Vector2::splat(1e-6);
+        for (vertex, quantized) in vertices.iter().zip(quantized.iter()) {
+            let position = uniform.position(quantized) - Vector3::from(vertex.position);
+            assert!(position.abs().cmple(position_error).all(), "{position}");
+
+            let uv = uniform.uv(quantized) - Vector2::from(vertex.uv);
+            assert!(uv.abs().cmple(uv_error).all(), "{uv}");
+
+            assert_eq!(quantized.position[3], 0);
+        }
+    }
+
+    #[test]
+    fn bounds_are_at_the_ends_of_the_range() {
+        let vertices = [
+            Vertex {
+                position: [-2.0, 0.0, 1.0],
+                uv: [0.0, 0.0],
+                normal: [0.0, 0.0, 1.0],
+            },
+            Vertex {
+                position: [4.0, 0.0, 3.0],
+                uv: [1.0, 1.0],
+                normal: [0.0, 0.0, -1.0],
+            },
+        ];
+
+        let (quantized, uniform) = quantize_vertices(&vertices);
+        assert_eq!(quantized[0].position, [-i16::MAX, 0, -i16::MAX, 0]);
+        assert_eq!(quantized[1].position, [i16::MAX, 0, i16::MAX, 0]);
+        assert_eq!(quantized[0].uv, [-i16::MAX, -i16::MAX]);
+        assert_eq!(quantized[1].uv, [i16::MAX, i16::MAX]);
+        assert_eq!(uniform.position_offset, [1.0, 0.0, 2.0, 0.0]);
+
+        // the flat axis is kept without dividing by zero
+        assert_eq!(
+            uniform.position(&quantized[0]),
+            Vector3::new(-2.0, 0.0, 1.0)
+        );
+        assert_eq!(uniform.position(&quantized[1]), Vector3::new(4.0, 0.0, 3.0));
+    }
+
+    #[test]
+    fn no_vertices() {
+        let (quantized, uniform) = quantize_vertices(&[]);
+        assert!(quantized.is_empty());
+        assert_eq!(uniform, QuantizationUniform::default());
+    }
+}
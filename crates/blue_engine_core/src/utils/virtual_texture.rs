@@ -0,0 +1,465 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    Renderer, SamplerSettings, StringBuffer, Textures, Vector2,
+    error::Error,
+    utils::dynamic_texture::{DynamicTexture, TextureRegion},
+};
+
+/// The pixels of a virtual texture, read a page at a time as they are needed
+pub trait PageSource: Send {
+    /// The size of the full resolution texture in pixels
+    fn size(&self) -> (u32, u32);
+
+    /// Reads a region of the mip level as tightly packed 8 bit RGBA rows. Each level is half
+    /// the size of the one before it, rounded up
+    fn read(&mut self, level: u32, x: u32, y: u32, width: u32, height: u32) -> Vec<u8>;
+}
+
+/// A page source over an image kept in memory, with the mip levels built when first read
+#[derive(Debug, Clone)]
+pub struct ImagePages {
+    levels: Vec<image::RgbaImage>,
+}
+
+impl ImagePages {
+    /// Creates the page source over the image
+    pub fn new(image: &image::DynamicImage) -> Self {
+        Self {
+            levels: vec![image.to_rgba8()],
+        }
+    }
+}
+
+impl PageSource for ImagePages {
+    fn size(&self) -> (u32, u32) {
+        self.levels[0].dimensions()
+    }
+
+    fn read(&mut self, level: u32, x: u32, y: u32, width: u32, height: u32) -> Vec<u8> {
+        while self.levels.len() <= level as usize {
+            let Some(last) = self.levels.last() else {
+                break;
+            };
+            let (last_width, last_height) = last.dimensions();
+            let next = image::imageops::resize(
+                last,
+                last_width.div_ceil(2).max(1),
+                last_height.div_ceil(2).max(1),
+                image::imageops::FilterType::Triangle,
+            );
+            self.levels.push(next);
+        }
+
+        image::imageops::crop_imm(&self.levels[level as usize], x, y, width, height)
+            .to_image()
+            .into_raw()
+    }
+}
+
+/// How a virtual texture is split into pages and how many of them are kept on the GPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualTextureSettings {
+    /// The width and height of a page in pixels
+    pub page_size: u32,
+    /// The amount of pages kept on each side of the page cache, up to 256
+    pub cache_pages: u32,
+    /// The most pages uploaded in a single [VirtualTexture::update]
+    pub uploads_per_frame: usize,
+}
+impl Default for VirtualTextureSettings {
+    fn default() -> Self {
+        Self {
+            page_size: 128,
+            cache_pages: 8,
+            uploads_per_frame: 4,
+        }
+    }
+}
+
+/// A page of a mip level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct PageId {
+    level: u32,
+    x: u32,
+    y: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ResidentPage {
+    slot: (u32, u32),
+    last_used: u64,
+}
+
+/// The pixels around each page copied from its neighbours, so the filtering does not bleed
+const PAGE_BORDER: u32 = 1;
+
+/// An experimental texture far larger than the GPU memory it takes, for megatexture style
+/// terrain. Only the pages that are asked for are kept in a fixed size page cache on the GPU,
+/// and an indirection table tells the shader which cached page to sample each part of the
+/// texture from, falling back to a coarser mip level while the finer page is still loading.
+///
+/// Both the page cache and the indirection table are in one texture, so it is set on the
+/// objects like any other, together with the shader of [VirtualTexture::shader_source]. The
+/// pages a frame needs are asked for with [VirtualTexture::request], and uploaded by
+/// [VirtualTexture::update]:
+///
+/// ```ignore
+/// let image = image::open("terrain.png")?;
+/// let mut terrain = engine.renderer.build_virtual_texture(
+///     "terrain",
+///     blue_engine::utils::virtual_texture::ImagePages::new(&image),
+///     Default::default(),
+/// )?;
+/// engine.objects.get_mut("ground").unwrap()
+///     .set_texture_raw(terrain.textures())
+///     .set_shader(terrain.shader_source())?;
+///
+/// // each frame, the part of the terrain in view
+/// terrain.request((0.25, 0.25), (0.5, 0.5), 0);
+/// terrain.update(&engine.renderer)?;
+/// ```
+pub struct VirtualTexture {
+    texture: DynamicTexture,
+    source: Box<dyn PageSource>,
+    settings: VirtualTextureSettings,
+    size: (u32, u32),
+    /// The amount of pages of the full resolution level
+    pages: (u32, u32),
+    /// The level that fits in a single page
+    max_level: u32,
+    resident: HashMap<PageId, ResidentPage>,
+    free_slots: Vec<(u32, u32)>,
+    /// The pages asked for since the last update
+    wanted: HashSet<PageId>,
+    /// The finest level asked for each page of the full resolution level
+    desired_levels: Vec<u32>,
+    /// The levels the indirection table was last written with
+    written_levels: Vec<u32>,
+    indirection_dirty: bool,
+    frame: u64,
+}
+
+impl std::fmt::Debug for VirtualTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VirtualTexture")
+            .field("settings", &self.settings)
+            .field("size", &self.size)
+            .field("max_level", &self.max_level)
+            .field("resident", &self.resident.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl VirtualTexture {
+    /// The texture for objects, to be set with [crate::Object::set_texture_raw]
+    pub fn textures(&self) -> Textures {
+        self.texture.textures()
+    }
+
+    /// The shader that samples the virtual texture, to be set with [crate::Object::set_shader]
+    pub fn shader_source(&self) -> String {
+        let constants = format!(
+            "const VT_SIZE: vec2<f32> = vec2<f32>({:.1}, {:.1});\n\
+             const VT_PAGE_SIZE: f32 = {:.1};\n\
+             const VT_BORDER: f32 = {:.1};\n\
+             const VT_PAGES: vec2<u32> = vec2<u32>({}u, {}u);\n\
+             const VT_INDIRECTION_ORIGIN: vec2<u32> = vec2<u32>(0u, {}u);",
+            self.size.0 as f32,
+            self.size.1 as f32,
+            self.settings.page_size as f32,
+            PAGE_BORDER as f32,
+            self.pages.0,
+            self.pages.1,
+            self.atlas_size(),
+        );
+
+        crate::utils::default_resources::VIRTUAL_TEXTURE_SHADER
+            .replace("//@VIRTUAL_TEXTURE_CONSTANTS", &constants)
+    }
+
+    /// The size of the full resolution texture in pixels
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// The coarsest mip level, which fits in a single page and is always kept on the GPU
+    pub fn max_level(&self) -> u32 {
+        self.max_level
+    }
+
+    /// The amount of pages on the GPU
+    pub fn resident_pages(&self) -> usize {
+        self.resident.len()
+    }
+
+    /// The bytes the texture takes on the GPU, which is mostly the page cache of the settings
+    pub fn memory_budget(&self) -> u64 {
+        self.texture.width() as u64 * self.texture.height() as u64 * 4
+    }
+
+    /// Asks for the pages of the mip level that cover the area between the texture
+    /// coordinates, for the next [VirtualTexture::update]. Meant to be called every frame with
+    /// what is in view, as the pages not asked for are the first to be replaced
+    pub fn request(&mut self, uv_min: impl Into<Vector2>, uv_max: impl Into<Vector2>, level: u32) {
+        let (uv_min, uv_max) = (uv_min.into(), uv_max.into());
+        let level = level.min(self.max_level);
+        let page_size = self.settings.page_size as f32;
+
+        let to_page = |uv: f32, size: u32, pages: u32| {
+            ((uv.clamp(0.0, 1.0) * size as f32 / page_size) as u32).min(pages - 1)
+        };
+        let x_range = to_page(uv_min.x.min(uv_max.x), self.size.0, self.pages.0)
+            ..=to_page(uv_min.x.max(uv_max.x), self.size.0, self.pages.0);
+        let y_range = to_page(uv_min.y.min(uv_max.y), self.size.1, self.pages.1)
+            ..=to_page(uv_min.y.max(uv_max.y), self.size.1, self.pages.1);
+
+        for y in y_range {
+            for x in x_range.clone() {
+                let desired = &mut self.desired_levels[(y * self.pages.0 + x) as usize];
+                *desired = (*desired).min(level);
+                self.wanted.insert(PageId {
+                    level,
+                    x: x >> level,
+                    y: y >> level,
+                });
+            }
+        }
+    }
+
+    /// Asks for the whole texture at the mip level
+    pub fn request_all(&mut self, level: u32) {
+        self.request((0.0, 0.0), (1.0, 1.0), level);
+    }
+
+    /// Uploads the pages asked for that are not on the GPU yet, coarsest first, replacing the
+    /// ones used the longest time ago, and points the indirection table at them
+    pub fn update(&mut self, renderer: &Renderer) -> Result<(), Error> {
+        self.frame += 1;
+        let coarsest = PageId {
+            level: self.max_level,
+            x: 0,
+            y: 0,
+        };
+        self.wanted.insert(coarsest);
+
+        let mut missing = Vec::new();
+        for page in self.wanted.iter() {
+            match self.resident.get_mut(page) {
+                Some(resident) => resident.last_used = self.frame,
+                None => missing.push(*page),
+            }
+        }
+        missing.sort_by_key(|i| (std::cmp::Reverse(i.level), i.y, i.x));
+
+        for page in missing.into_iter().take(self.settings.uploads_per_frame) {
+            let Some(slot) = self.free_slot(coarsest) else {
+                break;
+            };
+            self.write_page(renderer, page, slot)?;
+            self.resident.insert(
+                page,
+                ResidentPage {
+                    slot,
+                    last_used: self.frame,
+                },
+            );
+            self.indirection_dirty = true;
+        }
+
+        if self.indirection_dirty || self.desired_levels != self.written_levels {
+            self.write_indirection(renderer)?;
+            self.written_levels.clone_from(&self.desired_levels);
+            self.indirection_dirty = false;
+        }
+
+        self.wanted.clear();
+        self.desired_levels.fill(self.max_level);
+
+        Ok(())
+    }
+
+    /// The size of the page cache in pixels, where the indirection table starts below it
+    fn atlas_size(&self) -> u32 {
+        self.settings.cache_pages * (self.settings.page_size + PAGE_BORDER * 2)
+    }
+
+    /// The size of the mip level in pixels
+    fn level_size(&self, level: u32) -> (u32, u32) {
+        (
+            self.size.0.div_ceil(1 << level).max(1),
+            self.size.1.div_ceil(1 << level).max(1),
+        )
+    }
+
+    /// A slot of the page cache, freeing the page used the longest time ago if none are free
+    fn free_slot(&mut self, pinned: PageId) -> Option<(u32, u32)> {
+        if let Some(slot) = self.free_slots.pop() {
+            return Some(slot);
+        }
+
+        let (page, resident) = self
+            .resident
+            .iter()
+            .filter(|(page, resident)| **page != pinned && resident.last_used < self.frame)
+            .min_by_key(|(page, resident)| (resident.last_used, page.level))
+            .map(|(page, resident)| (*page, *resident))?;
+        self.resident.remove(&page);
+        self.indirection_dirty = true;
+
+        Some(resident.slot)
+    }
+
+    /// Reads the page from the source and writes it to the slot, with its border
+    fn write_page(
+        &mut self,
+        renderer: &Renderer,
+        page: PageId,
+        slot: (u32, u32),
+    ) -> Result<(), Error> {
+        let page_size = self.settings.page_size;
+        let padded = page_size + PAGE_BORDER * 2;
+        let (level_width, level_height) = self.level_size(page.level);
+
+        // the page and its border, as far as they are inside the level
+        let left = (page.x * page_size).saturating_sub(PAGE_BORDER);
+        let top = (page.y * page_size).saturating_sub(PAGE_BORDER);
+        let right = ((page.x + 1) * page_size + PAGE_BORDER).min(level_width);
+        let bottom = ((page.y + 1) * page_size + PAGE_BORDER).min(level_height);
+        let (width, height) = (right - left, bottom - top);
+        let source = self.source.read(page.level, left, top, width, height);
+        if source.len() < (width * height * 4) as usize {
+            return Err(Error::TextureWriteError(
+                "the page source returned less pixels than asked for",
+            ));
+        }
+
+        // the pixels outside of the level repeat the ones on its edge
+        let mut pixels = vec![0u8; (padded * padded * 4) as usize];
+        for y in 0..padded {
+            let source_y = (page.y * page_size + y)
+                .saturating_sub(PAGE_BORDER)
+                .clamp(top, bottom - 1)
+                - top;
+            for x in 0..padded {
+                let source_x = (page.x * page_size + x)
+                    .saturating_sub(PAGE_BORDER)
+                    .clamp(left, right - 1)
+                    - left;
+                let from = ((source_y * width + source_x) * 4) as usize;
+                let to = ((y * padded + x) * 4) as usize;
+                pixels[to..to + 4].copy_from_slice(&source[from..from + 4]);
+            }
+        }
+
+        self.texture.write_pixels(
+            renderer,
+            TextureRegion::new(slot.0 * padded, slot.1 * padded, padded, padded),
+            &pixels,
+        )
+    }
+
+    /// Points each page of the full resolution level at the finest page on the GPU that is
+    /// at or coarser than the level asked for it
+    fn write_indirection(&self, renderer: &Renderer) -> Result<(), Error> {
+        let mut entries = vec![0u8; (self.pages.0 * self.pages.1 * 4) as usize];
+        for y in 0..self.pages.1 {
+            for x in 0..self.pages.0 {
+                let index = (y * self.pages.0 + x) as usize;
+                let found = (self.desired_levels[index]..=self.max_level).find_map(|level| {
+                    self.resident
+                        .get(&PageId {
+                            level,
+                            x: x >> level,
+                            y: y >> level,
+                        })
+                        .map(|i| (level, i.slot))
+                });
+
+                if let Some((level, slot)) = found {
+                    entries[index * 4..index * 4 + 4].copy_from_slice(&[
+                        slot.0 as u8,
+                        slot.1 as u8,
+                        level as u8,
+                        255,
+                    ]);
+                }
+            }
+        }
+
+        self.texture.write_pixels(
+            renderer,
+            TextureRegion::new(0, self.atlas_size(), self.pages.0, self.pages.1),
+            &entries,
+        )
+    }
+}
+
+impl Renderer {
+    /// Creates a virtual texture over the page source, see [VirtualTexture]
+    pub fn build_virtual_texture(
+        &self,
+        name: impl StringBuffer,
+        source: impl PageSource + 'static,
+        settings: VirtualTextureSettings,
+    ) -> Result<VirtualTexture, Error> {
+        if settings.page_size == 0 || settings.cache_pages == 0 || settings.cache_pages > 256 {
+            return Err(Error::TextureWriteError(
+                "the page size and the cache pages must be above zero, with at most 256 cache pages",
+            ));
+        }
+
+        let size = source.size();
+        if size.0 == 0 || size.1 == 0 {
+            return Err(Error::TextureWriteError("the page source is empty"));
+        }
+        let pages = (
+            size.0.div_ceil(settings.page_size),
+            size.1.div_ceil(settings.page_size),
+        );
+        let mut max_level = 0;
+        while size.0.div_ceil(1 << max_level) > settings.page_size
+            || size.1.div_ceil(1 << max_level) > settings.page_size
+        {
+            max_level += 1;
+        }
+
+        let atlas_size = settings.cache_pages * (settings.page_size + PAGE_BORDER * 2);
+        let width = atlas_size.max(pages.0);
+        let height = atlas_size + pages.1;
+        if width.max(height) > self.device.limits().max_texture_dimension_2d {
+            return Err(Error::TextureWriteError(
+                "the page cache and indirection table do not fit in a texture of the device",
+            ));
+        }
+
+        let texture = self.build_dynamic_texture(name, width, height, SamplerSettings::default());
+        let free_slots = (0..settings.cache_pages)
+            .rev()
+            .flat_map(|y| (0..settings.cache_pages).rev().map(move |x| (x, y)))
+            .collect();
+        let page_count = (pages.0 * pages.1) as usize;
+
+        Ok(VirtualTexture {
+            texture,
+            source: Box::new(source),
+            settings,
+            size,
+            pages,
+            max_level,
+            resident: HashMap::new(),
+            free_slots,
+            wanted: HashSet::new(),
+            desired_levels: vec![max_level; page_count],
+            written_levels: Vec::new(),
+            indirection_dirty: true,
+            frame: 0,
+        })
+    }
+}
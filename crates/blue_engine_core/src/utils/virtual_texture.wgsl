@@ -0,0 +1,96 @@
+// blocks
+
+//@CAMERA_STRUCT
+
+struct TransformationUniforms {
+    transform_matrix: mat4x4<f32>,
+};
+@group(2) @binding(0)
+var<uniform> transform_uniform: TransformationUniforms;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) texture_coordinates: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coordinates: vec2<f32>,
+};
+
+struct InstanceInput {
+    @location(3) model_matrix_0: vec4<f32>,
+    @location(4) model_matrix_1: vec4<f32>,
+    @location(5) model_matrix_2: vec4<f32>,
+    @location(6) model_matrix_3: vec4<f32>,
+};
+
+// Vertex Stage
+@vertex
+fn vs_main(input: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    var out: VertexOutput;
+    out.texture_coordinates = input.texture_coordinates;
+    //@CAMERA_VERTEX
+    return out;
+}
+
+// Fragment Stage
+struct FragmentUniforms {
+    color: vec4<f32>,
+};
+@group(2) @binding(1)
+var<uniform> fragment_uniforms: FragmentUniforms;
+
+// the page cache, with the indirection table below the pages
+@group(0) @binding(0)
+var texture_diffuse: texture_2d<f32>;
+
+@group(0) @binding(1)
+var sampler_diffuse: sampler;
+
+//@VIRTUAL_TEXTURE_CONSTANTS
+
+// the texture is sRGB, so the bytes of the indirection table are encoded back
+fn vt_byte(value: f32) -> u32 {
+    var encoded = value * 12.92;
+    if value > 0.0031308 {
+        encoded = 1.055 * pow(value, 1.0 / 2.4) - 0.055;
+    }
+    return u32(round(encoded * 255.0));
+}
+
+fn virtual_texture_sample(uv: vec2<f32>) -> vec4<f32> {
+    let pixel = clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0)) * VT_SIZE;
+    let page = min(vec2<u32>(pixel / VT_PAGE_SIZE), VT_PAGES - vec2<u32>(1u));
+    let entry = textureLoad(texture_diffuse, VT_INDIRECTION_ORIGIN + page, 0);
+    if vt_byte(entry.a) == 0u {
+        return vec4<f32>(0.0);
+    }
+
+    // the page may be of a coarser level than asked for, while the finer one is loading
+    let level_scale = f32(1u << vt_byte(entry.b));
+    let level_pixel = min(pixel / level_scale, ceil(VT_SIZE / level_scale) - vec2<f32>(0.5));
+    let within = level_pixel - floor(level_pixel / VT_PAGE_SIZE) * VT_PAGE_SIZE;
+    let slot = vec2<f32>(f32(vt_byte(entry.r)), f32(vt_byte(entry.g)));
+    let atlas_pixel = slot * (VT_PAGE_SIZE + 2.0 * VT_BORDER) + VT_BORDER + within;
+
+    let atlas_size = vec2<f32>(textureDimensions(texture_diffuse));
+    return textureSampleLevel(texture_diffuse, sampler_diffuse, atlas_pixel / atlas_size, 0.0);
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let color = virtual_texture_sample(input.texture_coordinates);
+    if fragment_uniforms.color.w == 0.0 {
+        return color;
+    } else {
+        return color * fragment_uniforms.color;
+    }
+}
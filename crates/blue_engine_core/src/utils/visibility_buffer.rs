@@ -0,0 +1,640 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use std::sync::Arc;
+
+use super::default_resources::{VISIBILITY_BUFFER_SHADER, VISIBILITY_RESOLVE_SHADER};
+use crate::{
+    CameraContainer, InstanceRaw, Matrix4, Object, ObjectStorage, Vertex,
+    render::get_pipeline_vertex_buffer,
+};
+
+/// The format of the visibility buffer, the object on X, starting at 1 as 0 is empty, the
+/// instance on Y and the triangle on Z
+pub const VISIBILITY_BUFFER_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Uint;
+/// The triangle written on devices without [wgpu::Features::SHADER_PRIMITIVE_INDEX]
+pub const UNKNOWN_TRIANGLE: u32 = 0;
+
+/// The data of an object sent to the GPU, one for each object at its own offset
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct VisibilityUniform {
+    view_projection: [[f32; 4]; 4],
+    transform: [f32; 16],
+    object: [u32; 4],
+}
+/// The distance between the uniforms of the objects, which is a multiple of the offset
+/// alignment of every device
+const UNIFORM_STRIDE: u64 = 256;
+
+/// How the visibility buffer is turned into colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum VisibilityResolve {
+    /// Only the IDs are drawn, and the objects are drawn by the main pass as usual
+    #[default]
+    Off,
+    /// The objects are drawn only through the visibility buffer, in their color
+    Materials,
+    /// As [VisibilityResolve::Materials], but each object gets a color of its own
+    Objects,
+    /// As [VisibilityResolve::Materials], but each triangle gets a color of its own
+    Triangles,
+}
+impl VisibilityResolve {
+    /// The mode read by the resolve shader
+    fn mode(self) -> u32 {
+        match self {
+            Self::Off | Self::Materials => 0,
+            Self::Objects => 1,
+            Self::Triangles => 2,
+        }
+    }
+}
+
+/// An experimental visibility buffer renderer, for dense scenes and research. The first pass
+/// writes only which object, instance and triangle covers each pixel, to a texture of
+/// [VISIBILITY_BUFFER_FORMAT], so every pixel is shaded once however many triangles are
+/// drawn over it. The second pass resolves the IDs into the colors of the scene with a full
+/// screen triangle, see [VisibilityResolve].
+///
+/// The buffer is read through [VisibilityBuffer::view], or as
+/// [crate::utils::frame_graph::VISIBILITY_BUFFER] by the passes of the frame graph, which
+/// can resolve it in their own way, with [VisibilityBuffer::objects] telling which object
+/// an ID is. The triangles are only known on devices with
+/// [wgpu::Features::SHADER_PRIMITIVE_INDEX], and are [UNKNOWN_TRIANGLE] elsewhere.
+///
+/// For now the resolve reads only the color of the objects, not their textures or shaders,
+/// and the objects with quantized vertices, or of a camera drawn at a different render
+/// scale, are left to the main pass.
+#[derive(Debug)]
+pub struct VisibilityBuffer {
+    /// Should the visibility buffer be drawn
+    pub enabled: bool,
+    /// The camera the objects are drawn from
+    pub camera: Arc<str>,
+    /// How the buffer is turned into the colors of the scene
+    pub resolve: VisibilityResolve,
+    /// The buffer and its own depth, with the size they were made for
+    target: Option<(
+        (u32, u32),
+        wgpu::Texture,
+        wgpu::TextureView,
+        wgpu::TextureView,
+    )>,
+    pipeline: Option<(wgpu::BindGroupLayout, wgpu::RenderPipeline)>,
+    /// The resolve pipeline with the format of the scene it was made for
+    resolve_pipeline: Option<(
+        wgpu::TextureFormat,
+        wgpu::BindGroupLayout,
+        wgpu::RenderPipeline,
+    )>,
+    /// The uniforms of the objects, their bind group, and how many objects fit
+    uniforms: Option<(wgpu::Buffer, wgpu::BindGroup, usize)>,
+    /// The colors of the objects, and how many fit
+    colors: Option<(wgpu::Buffer, usize)>,
+    resolve_uniform: Option<wgpu::Buffer>,
+    /// The objects drawn in the latest frame, in the order of their IDs
+    objects: Vec<Arc<str>>,
+}
+
+impl VisibilityBuffer {
+    /// Creates the visibility buffer, disabled
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            camera: "main".into(),
+            resolve: VisibilityResolve::Off,
+            target: None,
+            pipeline: None,
+            resolve_pipeline: None,
+            uniforms: None,
+            colors: None,
+            resolve_uniform: None,
+            objects: Vec::new(),
+        }
+    }
+
+    /// Drops the buffer, the pipelines, and the uniforms of the objects, for a new device
+    pub(crate) fn clear_device_resources(&mut self) {
+        self.target = None;
+        self.pipeline = None;
+        self.resolve_pipeline = None;
+        self.uniforms = None;
+        self.colors = None;
+        self.resolve_uniform = None;
+    }
+
+    /// The visibility buffer of the latest frame, if it is enabled
+    pub fn view(&self) -> Option<&wgpu::TextureView> {
+        match self.enabled {
+            true => self.target.as_ref().map(|i| &i.2),
+            false => None,
+        }
+    }
+
+    /// The texture of the visibility buffer of the latest frame, if it is enabled
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        match self.enabled {
+            true => self.target.as_ref().map(|i| &i.1),
+            false => None,
+        }
+    }
+
+    /// The names of the objects drawn in the latest frame, where the object with the ID `n`
+    /// is at `n - 1`
+    pub fn objects(&self) -> &[Arc<str>] {
+        &self.objects
+    }
+
+    /// The name of the object with the ID read from the buffer, None for the empty pixels
+    pub fn object(&self, id: u32) -> Option<&str> {
+        self.objects
+            .get((id as usize).checked_sub(1)?)
+            .map(|i| i.as_ref())
+    }
+
+    /// Returns true if the objects are drawn through the visibility buffer instead of the
+    /// main pass
+    pub(crate) fn replaces_main_pass(&self) -> bool {
+        self.enabled && self.resolve != VisibilityResolve::Off
+    }
+
+    /// Draws the IDs of the objects, and resolves them onto the scene if the objects are
+    /// not drawn by the main pass. When resolving, the depth of the scene is used, so the
+    /// passes after see the objects
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        (scene, scene_format): (&wgpu::TextureView, wgpu::TextureFormat),
+        depth: &wgpu::TextureView,
+        size: (u32, u32),
+        objects: &ObjectStorage,
+        camera: &CameraContainer,
+        mesh_pool: &crate::utils::mesh_pool::MeshPool,
+    ) {
+        let Some(camera) = camera.get(self.camera.as_ref()) else {
+            return;
+        };
+        let view_projection = camera.view_data;
+
+        if self.target.as_ref().map(|i| i.0) != Some(size) {
+            let (texture, view) =
+                create_target(device, size, VISIBILITY_BUFFER_FORMAT, "Visibility Buffer");
+            let (_, depth_view) =
+                create_target(device, size, crate::DEPTH_FORMAT, "Visibility Buffer Depth");
+            self.target = Some((size, texture, view, depth_view));
+        }
+
+        let drawn = objects
+            .iter()
+            .filter(|(_, i)| draws(i, &self.camera))
+            .filter_map(|(name, i)| {
+                Some((
+                    name,
+                    i,
+                    get_pipeline_vertex_buffer(&i.pipeline.vertex_buffer, objects)?,
+                ))
+            })
+            .collect::<Vec<_>>();
+        self.objects = drawn
+            .iter()
+            .map(|(name, _, _)| Arc::from(name.as_str()))
+            .collect();
+
+        let uniforms = drawn
+            .iter()
+            .enumerate()
+            .map(|(index, (_, object, _))| {
+                // the objects without a camera are drawn on the screen as they are
+                let view_projection = match object.camera_effect {
+                    Some(_) => view_projection,
+                    None => Matrix4::IDENTITY,
+                };
+                VisibilityUniform {
+                    view_projection: view_projection.to_cols_array_2d(),
+                    transform: object.transformation_matrix().to_cols_array(),
+                    object: [index as u32 + 1, 0, 0, 0],
+                }
+            })
+            .collect::<Vec<_>>();
+        let colors = drawn
+            .iter()
+            .map(|(_, object, _)| object.color.to_array())
+            .collect::<Vec<_>>();
+
+        if self.pipeline.is_none() {
+            self.pipeline = Some(Self::build_pipeline(device));
+        }
+        let Some((layout, pipeline)) = self.pipeline.as_ref() else {
+            return;
+        };
+
+        if self.uniforms.as_ref().is_none_or(|i| i.2 < uniforms.len()) {
+            let capacity = uniforms.len().next_power_of_two().max(64);
+            crate::utils::allocation_audit::count_buffer();
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Visibility Buffer Uniform Buffer"),
+                size: capacity as u64 * UNIFORM_STRIDE,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            crate::utils::allocation_audit::count_bind_group();
+            let bind_group =
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Visibility Buffer Bind Group"),
+                    layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: &buffer,
+                            offset: 0,
+                            size: wgpu::BufferSize::new(
+                                std::mem::size_of::<VisibilityUniform>() as u64
+                            ),
+                        }),
+                    }],
+                });
+            self.uniforms = Some((buffer, bind_group, capacity));
+        }
+        let (Some((buffer, bind_group, _)), Some((_, _, view, own_depth))) =
+            (self.uniforms.as_ref(), self.target.as_ref())
+        else {
+            return;
+        };
+        if !uniforms.is_empty() {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(uniforms.as_slice()));
+        }
+
+        // when the main pass draws the objects too, the buffer has its own depth, so the
+        // objects do not fight with the depth they already wrote
+        let resolving = self.resolve != VisibilityResolve::Off;
+        let depth_ops = match resolving {
+            true => (depth, wgpu::LoadOp::Load, wgpu::StoreOp::Store),
+            false => (own_depth, wgpu::LoadOp::Clear(1.0), wgpu::StoreOp::Discard),
+        };
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Visibility Buffer Render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_ops.0,
+                    depth_ops: Some(wgpu::Operations {
+                        load: depth_ops.1,
+                        store: depth_ops.2,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(pipeline);
+            for (index, (_, object, vertex_buffer)) in drawn.iter().enumerate() {
+                if vertex_buffer.length == 0 {
+                    continue;
+                }
+                render_pass.set_bind_group(0, bind_group, &[index as u32 * UNIFORM_STRIDE as u32]);
+                let pooled = crate::utils::mesh_pool::allocation_of(object, objects)
+                    .zip(mesh_pool.buffers());
+                let (vertices, indices, first_index, base_vertex) = match pooled {
+                    Some((allocation, (vertices, indices))) => (
+                        vertices,
+                        indices,
+                        allocation.indices().start,
+                        allocation.base_vertex(),
+                    ),
+                    None => (
+                        &vertex_buffer.vertex_buffer,
+                        &vertex_buffer.index_buffer,
+                        0,
+                        0,
+                    ),
+                };
+                render_pass.set_vertex_buffer(0, vertices.slice(..));
+                render_pass.set_vertex_buffer(1, object.instance_buffer.slice(..));
+                render_pass.set_index_buffer(
+                    indices.slice(..),
+                    #[cfg(not(feature = "u32"))]
+                    wgpu::IndexFormat::Uint16,
+                    #[cfg(feature = "u32")]
+                    wgpu::IndexFormat::Uint32,
+                );
+                render_pass.draw_indexed(
+                    first_index..first_index + vertex_buffer.length,
+                    base_vertex,
+                    0..object.instances.len() as _,
+                );
+            }
+        }
+
+        if resolving {
+            self.draw_resolve(device, queue, encoder, scene, scene_format, &colors);
+        }
+    }
+
+    /// Resolves the IDs of the buffer onto the scene
+    fn draw_resolve(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        scene: &wgpu::TextureView,
+        scene_format: wgpu::TextureFormat,
+        colors: &[[f32; 4]],
+    ) {
+        if self.resolve_pipeline.as_ref().map(|i| i.0) != Some(scene_format) {
+            let (layout, pipeline) = Self::build_resolve_pipeline(device, scene_format);
+            self.resolve_pipeline = Some((scene_format, layout, pipeline));
+        }
+        if self.colors.as_ref().is_none_or(|i| i.1 < colors.len()) {
+            let capacity = colors.len().next_power_of_two().max(64);
+            crate::utils::allocation_audit::count_buffer();
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Visibility Resolve Color Buffer"),
+                size: (capacity * std::mem::size_of::<[f32; 4]>()) as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            self.colors = Some((buffer, capacity));
+        }
+        let resolve_uniform = self.resolve_uniform.get_or_insert_with(|| {
+            crate::utils::allocation_audit::count_buffer();
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Visibility Resolve Uniform Buffer"),
+                size: std::mem::size_of::<[u32; 4]>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        });
+        let (Some((_, layout, pipeline)), Some((color_buffer, _)), Some((_, _, view, _))) = (
+            self.resolve_pipeline.as_ref(),
+            self.colors.as_ref(),
+            self.target.as_ref(),
+        ) else {
+            return;
+        };
+        queue.write_buffer(
+            resolve_uniform,
+            0,
+            bytemuck::cast_slice(&[self.resolve.mode(), 0, 0, 0]),
+        );
+        if !colors.is_empty() {
+            queue.write_buffer(color_buffer, 0, bytemuck::cast_slice(colors));
+        }
+
+        // the buffer may be made again as the frame is resized, so it is bound every frame
+        crate::utils::allocation_audit::count_bind_group();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Visibility Resolve Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: resolve_uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: color_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Visibility Resolve Render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: scene,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn build_pipeline(device: &wgpu::Device) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline) {
+        // the triangles are only known where the device can tell them apart
+        let source = match device
+            .features()
+            .contains(wgpu::Features::SHADER_PRIMITIVE_INDEX)
+        {
+            true => VISIBILITY_BUFFER_SHADER
+                .replace(
+                    "/*@PRIMITIVE_INPUT*/",
+                    ", @builtin(primitive_index) primitive: u32",
+                )
+                .replace("/*@PRIMITIVE_ID*/0u/*@END*/", "primitive"),
+            false => VISIBILITY_BUFFER_SHADER.replace(
+                "/*@PRIMITIVE_ID*/0u/*@END*/",
+                &format!("{UNKNOWN_TRIANGLE}u"),
+            ),
+        };
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Visibility Buffer Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Visibility Buffer Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(
+                        std::mem::size_of::<VisibilityUniform>() as u64,
+                    ),
+                },
+                count: None,
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Visibility Buffer Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Visibility Buffer Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc(), InstanceRaw::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: VISIBILITY_BUFFER_FORMAT,
+                    write_mask: wgpu::ColorWrites::ALL,
+                    blend: None,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            // the faces are not culled, as the objects may cull either, and the depth keeps
+            // the closest surface anyway
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: crate::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        (bind_group_layout, pipeline)
+    }
+
+    fn build_resolve_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline) {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Visibility Resolve Shader"),
+            source: wgpu::ShaderSource::Wgsl(VISIBILITY_RESOLVE_SHADER.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Visibility Resolve Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Visibility Resolve Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Visibility Resolve Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    write_mask: wgpu::ColorWrites::ALL,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        (bind_group_layout, pipeline)
+    }
+}
+impl Default for VisibilityBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns true if the object is drawn into the visibility buffer of the camera
+pub(crate) fn draws(object: &Object, camera: &str) -> bool {
+    // the quantized vertices can not be read with the layout of the visibility buffer
+    object.is_visible
+        && !object.shader_settings.quantized_vertices
+        && object.camera_effect.as_deref().is_none_or(|i| i == camera)
+}
+
+/// Creates a texture of the size that can be rendered to, sampled and copied, with its view
+fn create_target(
+    device: &wgpu::Device,
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+    label: &str,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size.0.max(1),
+            height: size.1.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    (texture, view)
+}
@@ -0,0 +1,52 @@
+// The visibility buffer, the object, instance and triangle drawn at each pixel. The objects
+// are told apart by their place in the list drawn this frame, starting at 1 as 0 is empty
+
+struct VisibilityUniform {
+    view_projection: mat4x4<f32>,
+    transform: mat4x4<f32>,
+    // the place of the object in the list, plus 1
+    object: vec4<u32>,
+};
+@group(0) @binding(0)
+var<uniform> visibility: VisibilityUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(3) model_matrix_0: vec4<f32>,
+    @location(4) model_matrix_1: vec4<f32>,
+    @location(5) model_matrix_2: vec4<f32>,
+    @location(6) model_matrix_3: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) @interpolate(flat) instance: u32,
+};
+
+@vertex
+fn vs_main(
+    input: VertexInput,
+    instance: InstanceInput,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    var out: VertexOutput;
+    out.position = visibility.view_projection * model_matrix
+        * (visibility.transform * vec4<f32>(input.position, 1.0));
+    out.instance = instance_index;
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput/*@PRIMITIVE_INPUT*/) -> @location(0) vec4<u32> {
+    return vec4<u32>(visibility.object.x, input.instance, /*@PRIMITIVE_ID*/0u/*@END*/, 0u);
+}
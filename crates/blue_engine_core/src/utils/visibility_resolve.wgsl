@@ -0,0 +1,52 @@
+// Resolves the visibility buffer into colors, drawn with a full screen triangle over the
+// scene. The pixels without an object are left as they are
+
+struct ResolveUniform {
+    // 0 for the colors of the objects, 1 for a color for each object, 2 for each triangle
+    mode: vec4<u32>,
+};
+@group(0) @binding(0)
+var<uniform> resolve: ResolveUniform;
+@group(0) @binding(1)
+var visibility: texture_2d<u32>;
+@group(0) @binding(2)
+var<storage, read> colors: array<vec4<f32>>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// a color that differs a lot between numbers next to each other
+fn hash_color(value: u32) -> vec4<f32> {
+    var hash = value * 747796405u + 2891336453u;
+    hash = ((hash >> ((hash >> 28u) + 4u)) ^ hash) * 277803737u;
+    hash = (hash >> 22u) ^ hash;
+    return vec4<f32>(
+        f32(hash & 255u) / 255.0,
+        f32((hash >> 8u) & 255u) / 255.0,
+        f32((hash >> 16u) & 255u) / 255.0,
+        1.0,
+    );
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let ids = textureLoad(visibility, vec2<i32>(position.xy), 0);
+    if (ids.x == 0u) {
+        discard;
+    }
+
+    switch resolve.mode.x {
+        case 1u: {
+            return hash_color(ids.x * 65537u + ids.y);
+        }
+        case 2u: {
+            return hash_color(ids.x * 2654435761u + ids.y * 40503u + ids.z);
+        }
+        default: {
+            return colors[ids.x - 1u];
+        }
+    }
+}
@@ -0,0 +1,64 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+use crate::error::Error;
+
+/// Finds the canvas with the id on the page, or adds a new canvas to the body of the page if
+/// no id is given. The canvas is sized by the page, and the engine follows it as it resizes
+pub fn canvas(
+    canvas_id: Option<&str>,
+    width: u32,
+    height: u32,
+) -> Result<web_sys::HtmlCanvasElement, Error> {
+    let document = web_sys::window()
+        .and_then(|i| i.document())
+        .ok_or_else(|| Error::WebError("there is no document".to_string()))?;
+
+    let element = match canvas_id {
+        Some(canvas_id) => document.get_element_by_id(canvas_id).ok_or_else(|| {
+            Error::WebError(format!("there is no element with the id {canvas_id}"))
+        })?,
+        None => {
+            let element = document
+                .create_element("canvas")
+                .map_err(|e| Error::WebError(format!("{e:?}")))?;
+            element.set_attribute("width", &width.to_string()).ok();
+            element.set_attribute("height", &height.to_string()).ok();
+            document
+                .body()
+                .ok_or_else(|| Error::WebError("the page has no body".to_string()))?
+                .append_child(&element)
+                .map_err(|e| Error::WebError(format!("{e:?}")))?;
+            element
+        }
+    };
+
+    element
+        .dyn_into::<web_sys::HtmlCanvasElement>()
+        .map_err(|_| Error::WebError("the element is not a canvas".to_string()))
+}
+
+/// Downloads the file at the path, relative to the page, with the fetch API of the browser
+pub async fn fetch(path: &str) -> Result<Vec<u8>, String> {
+    let window = web_sys::window().ok_or_else(|| "there is no window".to_string())?;
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(path))
+        .await
+        .map_err(|e| format!("{e:?}"))?
+        .dyn_into()
+        .map_err(|_| "the fetch did not return a response".to_string())?;
+    if !response.ok() {
+        return Err(format!("the server responded with {}", response.status()));
+    }
+
+    let buffer = JsFuture::from(response.array_buffer().map_err(|e| format!("{e:?}"))?)
+        .await
+        .map_err(|e| format!("{e:?}"))?;
+
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
@@ -4,10 +4,10 @@ use winit::event::{DeviceEvent, MouseButton, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
 use winit::keyboard::{Key, KeyCode, PhysicalKey};
 
+use crate::time::Instant;
 use crate::utils::current_input::{
-    mouse_button_to_int, CurrentInput, KeyAction, MouseAction, ScanCodeAction,
+    CurrentInput, KeyAction, MouseAction, ScanCodeAction, mouse_button_to_int,
 };
-use std::time::Instant;
 use std::{path::PathBuf, time::Duration};
 
 /// main struct
@@ -23,6 +23,7 @@ pub struct WinitInputHelper {
     close_requested: bool,
     step_start: Option<Instant>,
     step_duration: Option<Duration>,
+    gamepads: crate::utils::gamepad::Gamepads,
 }
 
 impl Default for WinitInputHelper {
@@ -150,6 +151,7 @@ impl WinitInputHelper {
             close_requested: false,
             step_start: None,
             step_duration: None,
+            gamepads: crate::utils::gamepad::Gamepads::new(),
         }
     }
 
@@ -180,6 +182,7 @@ impl WinitInputHelper {
         if let Some(current) = &mut self.current {
             current.step();
         }
+        self.gamepads.step();
     }
 
     pub(crate) fn process_window_event(&mut self, event: &WindowEvent) {
@@ -410,6 +413,36 @@ impl WinitInputHelper {
         }
     }
 
+    /// Returns the keys that went from "not pressed" to "pressed" during the last step.
+    pub fn keys_pressed(&self) -> Vec<KeyCode> {
+        match &self.current {
+            Some(current) => current
+                .scancode_actions
+                .iter()
+                .filter_map(|action| match action {
+                    ScanCodeAction::Pressed(PhysicalKey::Code(keycode)) => Some(*keycode),
+                    _ => None,
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the mouse buttons that went from "not pressed" to "pressed" during the last step.
+    pub fn mouse_buttons_pressed(&self) -> Vec<MouseButton> {
+        match &self.current {
+            Some(current) => current
+                .mouse_actions
+                .iter()
+                .filter_map(|action| match action {
+                    MouseAction::Pressed(button) => Some(*button),
+                    _ => None,
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
     /// Returns `(0.0, 0.0)` when the window is not focused.
     /// Otherwise returns the amount scrolled by the mouse during the last step.
     /// Returns (horizontally, vertically)
@@ -508,6 +541,16 @@ impl WinitInputHelper {
         self.close_requested
     }
 
+    /// The connected gamepads, their buttons and axes, and the gamepad events of this step
+    pub fn gamepads(&self) -> &crate::utils::gamepad::Gamepads {
+        &self.gamepads
+    }
+
+    /// Allows changing the deadzone, or sending gamepad events from another source
+    pub fn gamepads_mut(&mut self) -> &mut crate::utils::gamepad::Gamepads {
+        &mut self.gamepads
+    }
+
     /// Returns the `std::time::Duration` elapsed since the last step.
     /// Returns `None` if the step is still in progress.
     pub fn delta_time(&self) -> Option<Duration> {
@@ -7,6 +7,8 @@
 use crate::{
     CameraContainer, ObjectStorage,
     prelude::{Engine, Renderer},
+    utils::allocation_audit::{self, Subsystem},
+    utils::plugin::PluginHook,
 };
 
 use winit::{
@@ -16,6 +18,11 @@ use winit::{
     window::WindowAttributes,
 };
 
+/// The refresh rate used by [FramePacing::RefreshRate] when the monitor does not report one
+pub const DEFAULT_REFRESH_RATE: f32 = 60.0;
+/// How long before a frame is due the event loop stops sleeping and waits precisely
+const FRAME_PACING_SPIN_MARGIN: std::time::Duration = std::time::Duration::from_millis(2);
+
 /// A wrapper for winit window to make it easier to use and more ergonomic.
 #[derive(Debug)]
 pub struct Window {
@@ -25,6 +32,46 @@ pub struct Window {
     pub default_attributes: winit::window::WindowAttributes,
     /// Whether the engine should close.
     pub should_close: bool,
+    /// Should the cursor be visible over the window
+    cursor_visible: bool,
+    /// How the cursor is grabbed by the window
+    cursor_grab: winit::window::CursorGrabMode,
+    /// A custom cursor waiting to be created by the event loop
+    pending_custom_cursor: Option<winit::window::CustomCursorSource>,
+    /// The most frames rendered per second, `None` for no limit
+    pub max_fps: Option<u32>,
+    /// When the window is redrawn
+    pub redraw_mode: RedrawMode,
+    /// How the frames are spaced in time
+    pub frame_pacing: FramePacing,
+    /// When the next frame is due with [FramePacing::RefreshRate]
+    next_redraw: Option<crate::time::Instant>,
+    /// Should the next frame be rendered
+    redraw_pending: bool,
+    /// When the last frame started
+    last_redraw: Option<crate::time::Instant>,
+    /// Should the clicks pass through the window to what is behind it
+    click_through: bool,
+    /// The opacity of the whole window
+    opacity: f32,
+    /// Should the window be hidden from the taskbar
+    skip_taskbar: bool,
+    /// The monitor the window was last seen on
+    monitor: Option<winit::monitor::MonitorHandle>,
+    /// The monitor the window moved to in this frame
+    monitor_changed: Option<MonitorInfo>,
+    /// How the window is rendered while it is not focused
+    pub unfocused_mode: BackgroundMode,
+    /// How the window is rendered while it is minimized or hidden
+    pub minimized_mode: BackgroundMode,
+    /// Does the window have the keyboard focus
+    focused: bool,
+    /// Is the window minimized or hidden behind other windows
+    minimized: bool,
+    /// The focus the window gained or lost in this frame
+    focus_changed: Option<bool>,
+    /// Is the app in the background without a surface, on mobile
+    suspended: bool,
 }
 crate::macros::impl_deref_field!(
     Window,
@@ -32,6 +79,143 @@ crate::macros::impl_deref_field!(
     window
 );
 
+/// When the window is redrawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedrawMode {
+    /// A new frame is rendered as soon as the last one is done
+    Continuous,
+    /// A new frame is only rendered when an event arrives, an object or camera changes, or
+    /// [Window::request_redraw] is called. Otherwise the engine sleeps, for tools and GUI
+    /// style apps that do not need to render all the time
+    OnDemand,
+}
+
+/// How the frames are spaced in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePacing {
+    /// The frames are limited by [Window::max_fps] if set, otherwise rendered as fast as possible
+    MaxFps,
+    /// The frames are rendered at the refresh rate of the monitor the window is on, such as
+    /// 120 or 144 Hz. The frames are kept on a fixed schedule, and the last moment before each
+    /// is waited out precisely, as the timers of the system are too coarse for it. Works best
+    /// with a present mode without VSync, and falls back to 60 Hz if the refresh rate is unknown
+    RefreshRate,
+}
+
+/// How the window is rendered while it is in the background, to save power
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackgroundMode {
+    /// The window is rendered as usual
+    Full,
+    /// The frame rate is limited to the given frames per second
+    Throttle(u32),
+    /// Nothing is rendered until the window is back, and the update loop does not run
+    Pause,
+}
+
+/// Which way the screen is held, see [Window::orientation]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    /// The screen is taller than it is wide
+    Portrait,
+    /// The screen is wider than it is tall
+    Landscape,
+}
+
+/// The details of a monitor, see [Window::monitors]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorInfo {
+    /// The name of the monitor, as reported by the system
+    pub name: Option<String>,
+    /// The resolution of the monitor in pixels
+    pub size: winit::dpi::PhysicalSize<u32>,
+    /// The refresh rate of the monitor in millihertz
+    pub refresh_rate_millihertz: Option<u32>,
+    /// The position of the top left of the monitor on the desktop, in pixels
+    pub position: winit::dpi::PhysicalPosition<i32>,
+    /// The DPI scale of the monitor, where 1 is 96 DPI
+    pub scale_factor: f64,
+    /// The winit monitor itself
+    pub handle: winit::monitor::MonitorHandle,
+}
+impl MonitorInfo {
+    /// Reads the details of the monitor
+    pub fn new(handle: winit::monitor::MonitorHandle) -> Self {
+        Self {
+            name: handle.name(),
+            size: handle.size(),
+            refresh_rate_millihertz: handle.refresh_rate_millihertz(),
+            position: handle.position(),
+            scale_factor: handle.scale_factor(),
+            handle,
+        }
+    }
+
+    /// The refresh rate of the monitor in hertz
+    pub fn refresh_rate(&self) -> Option<f32> {
+        self.refresh_rate_millihertz.map(|i| i as f32 / 1000.0)
+    }
+
+    /// The dots per inch of the monitor
+    pub fn dpi(&self) -> f64 {
+        self.scale_factor * 96.0
+    }
+
+    /// The video mode of the monitor with the largest resolution, and then the highest refresh rate
+    pub fn best_video_mode(&self) -> Option<winit::monitor::VideoModeHandle> {
+        self.handle.video_modes().max_by_key(|i| {
+            (
+                i.size().width as u64 * i.size().height as u64,
+                i.refresh_rate_millihertz(),
+                i.bit_depth(),
+            )
+        })
+    }
+}
+
+/// Makes the device again if it was lost, and tells the signals once it is, see
+/// [Renderer::recover_device]
+#[cfg(not(target_arch = "wasm32"))]
+fn recover_lost_device(
+    renderer: &mut Renderer,
+    window: &Window,
+    objects: &mut ObjectStorage,
+    camera: &mut CameraContainer,
+    signals: &mut crate::SignalStorage,
+) {
+    if !renderer.is_device_lost() {
+        return;
+    }
+
+    match renderer.recover_device(objects, camera) {
+        Ok(()) => signals.events.iter_mut().for_each(|i| {
+            i.1.device_recovered(renderer, window, objects, camera);
+        }),
+        Err(e) => eprintln!("The GPU device could not be made again: {e}"),
+    }
+}
+
+/// Resizes the window, or the offscreen target in headless mode, see [Engine::set_resolution]
+pub(crate) fn resize_window(
+    renderer: &mut Renderer,
+    window: &Window,
+    camera: &mut CameraContainer,
+    width: u32,
+    height: u32,
+) {
+    let size = winit::dpi::PhysicalSize { width, height };
+
+    let applied_size = match window.as_ref() {
+        Some(window) => window.request_inner_size(size),
+        None => Some(size),
+    };
+    if let Some(applied_size) = applied_size {
+        renderer.resize(applied_size);
+        camera.set_resolution(applied_size);
+        camera.update_view_projection(renderer);
+    }
+}
+
 /// Descriptor and settings for a window.
 #[derive(Debug, Clone)]
 pub struct WindowDescriptor {
@@ -45,10 +229,17 @@ pub struct WindowDescriptor {
     pub decorations: bool,
     /// Should the window be resizable
     pub resizable: bool,
-    /// Define how much power should the app ask for
+    /// Define how much power should the app ask for, such as the integrated or the discrete
+    /// GPU. Replaced by the `WGPU_POWER_PREF` environment variable when it is set
     pub power_preference: crate::PowerPreference,
-    /// The backend to use for the draw
+    /// The backend to use for the draw, such as only [crate::Backends::VULKAN] to look into a
+    /// driver issue. Replaced by the `WGPU_BACKEND` environment variable when it is set
     pub backends: crate::Backends,
+    /// Forces the adapter whose name has this in it, ignoring the case, such as `"nvidia"`.
+    /// The engine fails to start if none matches. See
+    /// [crate::utils::adapters::enumerate_adapters] for the names, and replaced by the
+    /// `WGPU_ADAPTER_NAME` environment variable when it is set
+    pub adapter_name: Option<&'static str>,
     /// The features to be enabled on a backend
     ///
     /// read more at [wgpu::Features]
@@ -76,6 +267,39 @@ pub struct WindowDescriptor {
     ///
     /// read more at [wgpu::MemoryHints]
     pub memory_hints: crate::wgpu::MemoryHints,
+    /// Should the performance HUD be shown on top of the scene
+    pub show_stats: bool,
+    /// The most frames rendered per second, `None` for no limit
+    pub max_fps: Option<u32>,
+    /// When the window is redrawn
+    pub redraw_mode: RedrawMode,
+    /// How the frames are spaced in time
+    pub frame_pacing: FramePacing,
+    /// How the window is rendered while it is not focused
+    pub unfocused_mode: BackgroundMode,
+    /// How the window is rendered while it is minimized or hidden behind other windows
+    pub minimized_mode: BackgroundMode,
+    /// Should the window be see through where the alpha of the frame is below 1.
+    /// The clear color is made fully transparent when enabled
+    pub transparent: bool,
+    /// Should the mouse clicks pass through the window to what is behind it
+    pub click_through: bool,
+    /// Should the window be kept above or below the other windows
+    pub window_level: winit::window::WindowLevel,
+    /// Should the window be hidden from the taskbar. Only supported on Windows
+    pub skip_taskbar: bool,
+    /// Renders the scene in HDR, and brings it into the range of the window with the given
+    /// tonemapping. The objects are built for the format of the scene, so this can not be
+    /// changed after the engine is created
+    pub hdr: Option<crate::utils::hdr::Tonemapping>,
+    /// The id of the canvas on the page to render to, or `None` to add a new canvas to the
+    /// body of the page
+    #[cfg(target_arch = "wasm32")]
+    pub canvas_id: Option<&'static str>,
+    /// The color space the frames are presented in. HDR output needs the scene to be rendered
+    /// in HDR with [WindowDescriptor::hdr], and falls back to SDR where the window does not
+    /// support it. What was chosen is reported by [Renderer::surface_report]
+    pub output_color: crate::utils::surface_format::OutputColor,
 }
 impl std::default::Default for WindowDescriptor {
     /// Will quickly create a window with default settings
@@ -89,6 +313,7 @@ impl std::default::Default for WindowDescriptor {
             resizable: true,
             power_preference: crate::PowerPreference::LowPower,
             backends,
+            adapter_name: None,
             features: if backends == wgpu::Backends::VULKAN {
                 wgpu::Features::POLYGON_MODE_LINE | wgpu::Features::POLYGON_MODE_POINT
             } else if backends
@@ -104,11 +329,40 @@ impl std::default::Default for WindowDescriptor {
             alpha_mode: crate::wgpu::CompositeAlphaMode::Auto,
             desired_maximum_frame_latency: 2,
             memory_hints: crate::MemoryHints::Performance,
+            show_stats: false,
+            max_fps: None,
+            redraw_mode: RedrawMode::Continuous,
+            frame_pacing: FramePacing::MaxFps,
+            unfocused_mode: BackgroundMode::Full,
+            minimized_mode: BackgroundMode::Pause,
+            transparent: false,
+            click_through: false,
+            window_level: winit::window::WindowLevel::Normal,
+            skip_taskbar: false,
+            hdr: None,
+            #[cfg(target_arch = "wasm32")]
+            canvas_id: None,
+            output_color: crate::utils::surface_format::OutputColor::Sdr,
         }
     }
 }
-unsafe impl Send for WindowDescriptor {}
-unsafe impl Sync for WindowDescriptor {}
+
+/// The settings the engine is created with, see [WindowDescriptor]
+///
+/// ```ignore
+/// let engine = Engine::new_config(EngineSettings::default().show_stats(true))?;
+/// ```
+pub type EngineSettings = WindowDescriptor;
+
+impl WindowDescriptor {
+    /// Shows the performance HUD on top of the scene, with the frame rate, the frame times,
+    /// the draw calls, the objects, the GPU memory, and the timings of the stages. It can be
+    /// toggled later with [Renderer::set_show_stats]
+    pub fn show_stats(mut self, show_stats: bool) -> Self {
+        self.show_stats = show_stats;
+        self
+    }
+}
 
 impl Engine {
     /// Creates a new window in current thread using default settings.
@@ -129,6 +383,139 @@ impl Engine {
         )
     }
 
+    /// Creates a new engine that renders into an offscreen texture instead of a window.
+    ///
+    /// No event loop is created, and frames are rendered by calling [Engine::render_frame].
+    /// The width and height of the settings are used as the size of the frames. This is
+    /// useful for screenshot tests, rendering in CI, and generating thumbnails on servers.
+    #[cfg(not(target_os = "android"))]
+    pub fn new_headless(settings: WindowDescriptor) -> Result<Self, crate::error::Error> {
+        let mut engine = Self::new_inner(settings.clone())?;
+        engine
+            .renderer
+            .enable_offscreen_target(winit::dpi::PhysicalSize {
+                width: settings.width,
+                height: settings.height,
+            });
+
+        Ok(engine)
+    }
+
+    /// Renders a single frame without the event loop, into the offscreen target or the
+    /// window surface if one exists. The result can be read with [Renderer::capture_frame].
+    ///
+    /// The update loop is not called, but the frame signals, the draw of the game states, and
+    /// the render hooks of the plugins are.
+    pub fn render_frame(&mut self) -> Result<(), crate::error::Error> {
+        let Self {
+            camera,
+            renderer,
+            window,
+            objects,
+            input_events,
+            signals,
+            states,
+            plugins,
+            ..
+        } = self;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        recover_lost_device(renderer, window, objects, camera, signals);
+        allocation_audit::enter(Subsystem::Assets);
+        objects.build_queued(renderer);
+        renderer.update_assets(objects);
+        renderer.stream_textures();
+        allocation_audit::enter(Subsystem::Update);
+        plugins.run(
+            PluginHook::PreRender,
+            renderer,
+            window,
+            objects,
+            input_events,
+            camera,
+            signals,
+        );
+        allocation_audit::enter(Subsystem::ObjectUpdate);
+        // there is no frame time without the event loop, so the cameras snap to their goal
+        camera.apply_constraints(objects, 0.0);
+        for camera_value in camera.values_mut() {
+            camera_value.update_view_projection(renderer);
+        }
+        objects.notify_observers();
+        objects.update_changed(renderer);
+
+        renderer.stats.begin_frame();
+        allocation_audit::enter(Subsystem::PreRender);
+        let size = renderer.size;
+        let Some((mut encoder, view, frame)) = renderer.pre_render(objects, size, camera)? else {
+            allocation_audit::enter(Subsystem::Other);
+            return Err(crate::error::Error::Custom(
+                "There is no window surface or offscreen target to render to".to_string(),
+            ));
+        };
+        objects.keep_previous_transforms();
+
+        allocation_audit::enter(Subsystem::Update);
+        states.draw(
+            renderer,
+            window,
+            objects,
+            input_events,
+            camera,
+            signals,
+            &mut encoder,
+            &view,
+        );
+        signals.events.iter_mut().for_each(|i| {
+            i.1.frame(
+                renderer,
+                window,
+                objects,
+                camera,
+                input_events,
+                &mut encoder,
+                &view,
+            );
+        });
+
+        allocation_audit::enter(Subsystem::Render);
+        renderer.stats.collect_objects(objects);
+        renderer.render(encoder, &view, frame);
+        allocation_audit::enter(Subsystem::Update);
+        plugins.run(
+            PluginHook::PostRender,
+            renderer,
+            window,
+            objects,
+            input_events,
+            camera,
+            signals,
+        );
+        allocation_audit::enter(Subsystem::Other);
+        renderer.stats.allocations = allocation_audit::take_report();
+
+        Ok(())
+    }
+
+    /// Changes the resolution of the window, or of the offscreen target in headless mode.
+    ///
+    /// The surface is reconfigured once the window is resized, the window manager may
+    /// also choose a different size.
+    pub fn set_resolution(&mut self, width: u32, height: u32) {
+        resize_window(
+            &mut self.renderer,
+            &self.window,
+            &mut self.camera,
+            width,
+            height,
+        );
+    }
+
+    /// Saves the next presented frame to the given path, see [Renderer::screenshot]
+    pub fn screenshot(&mut self, path: impl AsRef<std::path::Path>) {
+        self.renderer.screenshot(path);
+    }
+
     /// Creates a new window for android
     #[cfg(target_os = "android")]
     pub fn new_android(
@@ -138,6 +525,42 @@ impl Engine {
         Self::new_inner(settings, Some(app))
     }
 
+    /// Creates a new window without blocking, for the web where the GPU can only be reached
+    /// asynchronously. The canvas of [WindowDescriptor::canvas_id] is rendered to, and the
+    /// loop is started with [Engine::spawn_update_loop]:
+    ///
+    /// ```ignore
+    /// #[wasm_bindgen(start)]
+    /// pub fn start() {
+    ///     wasm_bindgen_futures::spawn_local(async {
+    ///         let engine = Engine::new_async(WindowDescriptor::default()).await.unwrap();
+    ///         engine.spawn_update_loop(|_, _, _, _, _, _| {}).unwrap();
+    ///     });
+    /// }
+    /// ```
+    #[cfg(target_arch = "wasm32")]
+    pub async fn new_async(settings: WindowDescriptor) -> Result<Self, crate::error::Error> {
+        let dimension = winit::dpi::PhysicalSize {
+            width: settings.width,
+            height: settings.height,
+        };
+
+        let canvas =
+            crate::utils::web::canvas(settings.canvas_id, settings.width, settings.height)?;
+        let default_attributes = {
+            use winit::platform::web::WindowAttributesExtWebSys;
+            window_attributes(&settings).with_canvas(Some(canvas.clone()))
+        };
+
+        let renderer = Renderer::new(dimension, settings.clone(), canvas).await?;
+        Ok(Self::with_renderer(
+            settings,
+            dimension,
+            default_attributes,
+            renderer,
+        ))
+    }
+
     /// Creates a new window in current thread.
     #[allow(unreachable_code)]
     pub(crate) fn new_inner(
@@ -148,6 +571,12 @@ impl Engine {
     ) -> Result<Self, crate::error::Error> {
         #[cfg(feature = "debug")]
         env_logger::init();
+        // the GPU can not be waited on without returning to the browser
+        #[cfg(target_arch = "wasm32")]
+        return Err(crate::error::Error::WebError(
+            "the engine is created with Engine::new_async on the web".to_string(),
+        ));
+
         // Dimensions of the window, as width and height
         // and then are set as a logical size that the window can accept
         #[cfg(not(target_os = "android"))]
@@ -158,26 +587,59 @@ impl Engine {
 
         // And we will create a new window and set all the options we stored
         #[cfg(not(target_os = "android"))]
-        let default_attributes = WindowAttributes::default()
-            .with_inner_size(dimension) // sets the width and height of window
-            .with_title(String::from(settings.title)) // sets title of the window
-            .with_decorations(settings.decorations) // sets if the window should have borders
-            .with_resizable(settings.resizable); // sets the window to be resizable
+        let default_attributes = window_attributes(&settings);
 
         // The renderer init on current window
-        let mut renderer = pollster::block_on(Renderer::new(dimension, settings.clone()))?;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let renderer = pollster::block_on(Renderer::new(dimension, settings.clone()))?;
+            Ok(Self::with_renderer(
+                settings,
+                dimension,
+                default_attributes,
+                renderer,
+            ))
+        }
+    }
+
+    /// Puts the engine together around the renderer
+    fn with_renderer(
+        settings: WindowDescriptor,
+        dimension: winit::dpi::PhysicalSize<u32>,
+        default_attributes: WindowAttributes,
+        mut renderer: Renderer,
+    ) -> Self {
         let camera = CameraContainer::new(dimension, &mut renderer);
 
-        Ok(Self {
-            window: Window::new(default_attributes),
+        let mut window = Window::new(default_attributes);
+        window.max_fps = settings.max_fps;
+        window.redraw_mode = settings.redraw_mode;
+        window.frame_pacing = settings.frame_pacing;
+        window.unfocused_mode = settings.unfocused_mode;
+        window.minimized_mode = settings.minimized_mode;
+        window.click_through = settings.click_through;
+        window.skip_taskbar = settings.skip_taskbar;
+
+        Self {
+            window,
             event_loop_control_flow: settings.control_flow,
             renderer,
             objects: ObjectStorage::new(),
             camera,
             signals: crate::SignalStorage::new(),
+            windows: crate::windows::WindowStorage::new(),
             update_loop: None,
+            fixed_update: None,
             input_events: crate::utils::winit_input_helper::WinitInputHelper::new(),
-        })
+            gamepad_backend: crate::utils::gamepad::GamepadBackend::new(),
+            scheduler: crate::utils::scheduler::Scheduler::new(),
+            tasks: crate::utils::tasks::TaskRunner::new(),
+            states: crate::utils::game_state::StateStack::new(),
+            settings: crate::utils::settings::SettingsStore::new(
+                crate::utils::settings::Settings::from_descriptor(&settings),
+            ),
+            plugins: crate::utils::plugin::PluginStorage::new(),
+        }
     }
 
     /// Runs the block of code that you pass to it every frame. The update code is used
@@ -200,7 +662,104 @@ impl Engine {
         ),
     ) -> Result<(), crate::error::Error> {
         self.update_loop = Some(Box::new(update_function));
+        self.run_event_loop()
+    }
+
+    /// Runs the update loop like [Engine::update_loop], handing the event loop to the browser
+    /// instead of blocking. The engine is moved into the loop, and this returns right away, so
+    /// it can be called from an async function such as after [Engine::new_async]. The frames
+    /// are drawn at the animation frames of the browser
+    #[cfg(target_arch = "wasm32")]
+    pub fn spawn_update_loop(
+        mut self,
+        update_function: impl 'static
+        + FnMut(
+            // Core
+            &mut Renderer,
+            &mut Window,
+            &mut ObjectStorage,
+            &crate::utils::winit_input_helper::WinitInputHelper,
+            &mut CameraContainer,
+            &mut crate::SignalStorage,
+        ),
+    ) -> Result<(), crate::error::Error> {
+        use winit::platform::web::EventLoopExtWebSys;
+
+        self.update_loop = Some(Box::new(update_function));
+        let event_loop = EventLoop::new()?;
+        event_loop.set_control_flow(self.event_loop_control_flow);
+        event_loop.spawn_app(self);
 
+        Ok(())
+    }
+
+    /// Runs the game logic at a fixed amount of ticks per second, separate from the frame rate.
+    ///
+    /// The `fixed_function` is ran for every tick that passed since the last frame, with the
+    /// tick length in seconds. The `render_function` is then ran once every frame, like the
+    /// [Engine::update_loop], with the interpolation alpha between the last tick and the next.
+    #[allow(clippy::type_complexity)]
+    pub fn update_loop_fixed(
+        &mut self,
+        tick_rate: f32,
+        fixed_function: impl 'static
+        + FnMut(
+            &mut Renderer,
+            &mut Window,
+            &mut ObjectStorage,
+            &crate::utils::winit_input_helper::WinitInputHelper,
+            &mut CameraContainer,
+            &mut crate::SignalStorage,
+            f32,
+        ),
+        render_function: impl 'static
+        + FnMut(
+            &mut Renderer,
+            &mut Window,
+            &mut ObjectStorage,
+            &crate::utils::winit_input_helper::WinitInputHelper,
+            &mut CameraContainer,
+            &mut crate::SignalStorage,
+            f32,
+        ),
+    ) -> Result<(), crate::error::Error> {
+        self.fixed_update = Some(crate::utils::fixed_timestep::FixedUpdate {
+            timestep: crate::utils::fixed_timestep::FixedTimestep::new(tick_rate),
+            fixed_function: Box::new(fixed_function),
+            render_function: Box::new(render_function),
+        });
+        self.run_event_loop()
+    }
+
+    /// Runs the callback once, after the delay in seconds. See [crate::utils::scheduler::Scheduler]
+    pub fn after(
+        &mut self,
+        delay: f32,
+        callback: impl FnOnce(&mut crate::utils::scheduler::SchedulerContext) + 'static,
+    ) -> crate::utils::scheduler::TimerId {
+        self.scheduler.after(delay, callback)
+    }
+
+    /// Runs the callback every interval in seconds. See [crate::utils::scheduler::Scheduler]
+    pub fn every(
+        &mut self,
+        interval: f32,
+        callback: impl FnMut(&mut crate::utils::scheduler::SchedulerContext) + 'static,
+    ) -> crate::utils::scheduler::TimerId {
+        self.scheduler.every(interval, callback)
+    }
+
+    /// Starts an async task that runs alongside the update loop. See
+    /// [crate::utils::tasks::TaskRunner]
+    pub fn spawn<F: std::future::Future<Output = ()> + 'static>(
+        &mut self,
+        task: impl FnOnce(crate::utils::tasks::TaskContext) -> F,
+    ) -> crate::utils::tasks::TaskId {
+        self.tasks.spawn(task)
+    }
+
+    #[allow(unreachable_code)]
+    fn run_event_loop(&mut self) -> Result<(), crate::error::Error> {
         // will create the main event loop of the window.
         // and will contain all the callbacks and button press
         // also will allow graphics API
@@ -234,12 +793,76 @@ impl Engine {
         #[cfg(not(target_os = "android"))]
         let event_loop = EventLoop::new()?;
         event_loop.set_control_flow(self.event_loop_control_flow);
+        // on the web this returns to the browser by throwing, see Engine::spawn_update_loop
         event_loop.run_app(self)?;
 
         Ok(())
     }
 }
 
+/// Converts the settings into the attributes of a winit window
+pub(crate) fn window_attributes(settings: &WindowDescriptor) -> WindowAttributes {
+    WindowAttributes::default()
+        .with_inner_size(winit::dpi::PhysicalSize {
+            width: settings.width,   // Which sets the width of the window
+            height: settings.height, // And sets the height of the window
+        })
+        .with_title(String::from(settings.title)) // sets title of the window
+        .with_decorations(settings.decorations) // sets if the window should have borders
+        .with_resizable(settings.resizable) // sets the window to be resizable
+        .with_transparent(settings.transparent) // sets if the window can be see through
+        .with_window_level(settings.window_level) // sets if the window is kept above others
+}
+
+impl Engine {
+    /// Opens the secondary windows that are not opened yet
+    fn create_secondary_windows(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        let Self {
+            renderer, windows, ..
+        } = self;
+
+        windows.retain(|name, window| match window.create(event_loop, renderer) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("Could not create the window {name}: {e}");
+                false
+            }
+        });
+    }
+
+    /// Handles the events of a secondary window
+    fn secondary_window_event(&mut self, name: &str, event: &WindowEvent) {
+        let Self {
+            renderer,
+            objects,
+            windows,
+            ..
+        } = self;
+        let Some(window) = windows.get_mut(name) else {
+            return;
+        };
+
+        match event {
+            WindowEvent::CloseRequested => {
+                windows.remove(name);
+            }
+            WindowEvent::Resized(size) => window.resize(renderer, *size),
+            WindowEvent::RedrawRequested => {
+                if window.window.should_close {
+                    windows.remove(name);
+                    return;
+                }
+
+                window.render(renderer, objects);
+                if let Some(window_inner) = &window.window.window {
+                    window_inner.request_redraw();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 impl ApplicationHandler for Engine {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
         let Self {
@@ -251,38 +874,11 @@ impl ApplicationHandler for Engine {
             ..
         } = self;
 
-        if window.is_none() {
+        let created = window.is_none();
+        if created {
             if let Ok(new_window) = event_loop.create_window(window.default_attributes.clone()) {
                 let new_window = std::sync::Arc::new(new_window);
 
-                if renderer.surface.is_none() {
-                    if let Ok(surface) = renderer.instance.create_surface(new_window.clone()) {
-                        let surface_capabilities = surface.get_capabilities(&renderer.adapter);
-                        let tex_format = surface_capabilities
-                            .formats
-                            .iter()
-                            .copied()
-                            .find(|f| f.is_srgb())
-                            .unwrap_or(surface_capabilities.formats[0]);
-
-                        renderer.config.format = tex_format;
-                        renderer.config.view_formats = vec![tex_format];
-
-                        surface.configure(&renderer.device, &renderer.config);
-                        renderer.depth_buffer = Renderer::build_depth_buffer(
-                            "Depth Buffer",
-                            &renderer.device,
-                            &renderer.config,
-                        );
-                        renderer.surface = Some(surface);
-
-                        renderer.build_default_data();
-                        objects.iter_mut().for_each(|i| {
-                            i.1.update(renderer);
-                        });
-                    }
-                }
-
                 new_window.set_min_inner_size(window.default_attributes.min_inner_size);
                 new_window.set_max_inner_size(window.default_attributes.max_inner_size);
                 if let Some(position) = window.default_attributes.position {
@@ -302,14 +898,87 @@ impl ApplicationHandler for Engine {
                 new_window.set_window_level(window.default_attributes.window_level);
                 new_window.set_cursor(window.default_attributes.cursor.clone());
                 new_window.set_fullscreen(window.default_attributes.fullscreen.clone());
+                new_window.set_cursor_visible(window.cursor_visible);
+                if window.click_through
+                    && let Err(e) = new_window.set_cursor_hittest(false)
+                {
+                    eprintln!("Could not make the window click through: {e}");
+                }
+                #[cfg(target_os = "windows")]
+                {
+                    use winit::platform::windows::WindowExtWindows;
+                    new_window.set_skip_taskbar(window.skip_taskbar);
+                }
+                window.monitor = new_window.current_monitor();
+                if let Err(e) = new_window.set_cursor_grab(window.cursor_grab) {
+                    eprintln!("Could not grab the cursor: {e}");
+                }
 
                 window.window = Some(new_window);
             }
+            window.apply_cursor(event_loop);
+        }
+
+        // the surface is made with the window, and again each time the app comes back from
+        // the background on mobile, where it is lost while suspended
+        if renderer.surface.is_none()
+            && let Some(window_inner) = window.window.clone()
+            && let Ok(surface) = renderer.instance.create_surface(window_inner)
+        {
+            let format = renderer.config.format;
+            renderer.attach_surface(surface, window.default_attributes.transparent);
+            // the screen may have been rotated while the app was in the background
+            if let Some(size) = window.surface_size() {
+                renderer.resize(size);
+                camera.set_resolution(size);
+                camera.update_view_projection(renderer);
+            }
 
+            if created || format != renderer.config.format {
+                renderer.build_default_data();
+                objects.iter_mut().for_each(|i| {
+                    i.1.update(renderer);
+                });
+            }
+        }
+
+        if created {
             signals.events.iter_mut().for_each(|i| {
                 i.1.init(renderer, &self.window, objects, camera);
             });
+        } else if window.suspended {
+            window.suspended = false;
+            window.request_redraw();
+            signals.events.iter_mut().for_each(|i| {
+                i.1.suspended_changed(renderer, &self.window, objects, false, camera);
+            });
         }
+
+        self.create_secondary_windows(event_loop);
+    }
+
+    fn suspended(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+        let Self {
+            window,
+            renderer,
+            objects,
+            signals,
+            camera,
+            ..
+        } = self;
+
+        // the native window behind the surface is destroyed by the system on mobile, so the
+        // surface is let go of and made again when the app is resumed
+        renderer.surface = None;
+        window.suspended = true;
+        signals.events.iter_mut().for_each(|i| {
+            i.1.suspended_changed(renderer, window, objects, true, camera);
+        });
+    }
+
+    fn about_to_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        self.window
+            .schedule_redraw(event_loop, self.event_loop_control_flow);
     }
 
     fn device_event(
@@ -337,9 +1006,28 @@ impl ApplicationHandler for Engine {
     fn window_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
-        _window_id: winit::window::WindowId,
+        window_id: winit::window::WindowId,
         event: WindowEvent,
     ) {
+        if let Some(name) = self.windows.find(window_id) {
+            // only the input of the secondary windows is shared with the main window
+            if !matches!(
+                event,
+                WindowEvent::CloseRequested
+                    | WindowEvent::Resized(_)
+                    | WindowEvent::ScaleFactorChanged { .. }
+                    | WindowEvent::RedrawRequested
+            ) {
+                self.input_events.process_window_event(&event);
+            }
+            self.secondary_window_event(&name, &event);
+            return;
+        }
+        if event == WindowEvent::RedrawRequested {
+            self.create_secondary_windows(event_loop);
+            self.window.apply_cursor(event_loop);
+        }
+
         let Self {
             camera,
             renderer,
@@ -348,6 +1036,13 @@ impl ApplicationHandler for Engine {
             input_events,
             signals,
             update_loop,
+            fixed_update,
+            gamepad_backend,
+            scheduler,
+            tasks,
+            states,
+            settings,
+            plugins,
             ..
         } = self;
 
@@ -355,11 +1050,44 @@ impl ApplicationHandler for Engine {
             i.1.window_events(renderer, window, objects, &event, input_events, camera);
         });
 
+        if matches!(
+            event,
+            WindowEvent::Moved(_)
+                | WindowEvent::Resized(_)
+                | WindowEvent::ScaleFactorChanged { .. }
+        ) && let Some(monitor) = window.update_monitor()
+        {
+            signals.events.iter_mut().for_each(|i| {
+                i.1.monitor_changed(renderer, window, objects, &monitor, camera);
+            });
+        }
+
+        match event {
+            WindowEvent::Focused(focused) => {
+                window.focused = focused;
+                window.focus_changed = Some(focused);
+                signals.events.iter_mut().for_each(|i| {
+                    i.1.focus_changed(renderer, window, objects, focused, camera);
+                });
+            }
+            WindowEvent::Occluded(occluded) => window.minimized = occluded,
+            WindowEvent::Resized(size) => {
+                window.minimized = size.width == 0
+                    || size.height == 0
+                    || window
+                        .as_ref()
+                        .and_then(|i| i.is_minimized())
+                        .unwrap_or(false);
+            }
+            _ => {}
+        }
+
         let mut _device_event: winit::event::DeviceEvent =
             DeviceEvent::MouseMotion { delta: (0.0, 0.0) };
 
         match event {
             WindowEvent::CloseRequested => {
+                renderer.flush_captures();
                 event_loop.exit();
                 std::process::exit(0);
             }
@@ -368,19 +1096,74 @@ impl ApplicationHandler for Engine {
                 renderer.resize(size);
                 camera.set_resolution(size);
                 camera.update_view_projection(renderer);
+                window.request_redraw();
             }
 
             WindowEvent::RedrawRequested => {
+                window.last_redraw = Some(crate::time::Instant::now());
                 input_events.end_step_time();
+                #[cfg(not(target_arch = "wasm32"))]
+                recover_lost_device(renderer, window, objects, camera, signals);
+                allocation_audit::enter(Subsystem::Assets);
+                objects.build_queued(renderer);
+                renderer.update_assets(objects);
+                renderer.stream_textures();
+                gamepad_backend.poll(input_events.gamepads_mut());
 
                 if window.should_close {
                     event_loop.exit();
                 }
 
+                if window.is_some() {
+                    allocation_audit::enter(Subsystem::Update);
+                    plugins.run(
+                        PluginHook::PreRender,
+                        renderer,
+                        window,
+                        objects,
+                        input_events,
+                        camera,
+                        signals,
+                    );
+                }
                 if let Some(window_ref) = window.as_ref() {
+                    renderer.stats.pacing.target_frame_time =
+                        window.target_frame_time().map(|i| i.as_secs_f32() * 1000.0);
+                    renderer.stats.begin_frame();
+                    let stage_start = crate::time::Instant::now();
+                    allocation_audit::enter(Subsystem::PreRender);
+
                     if let Ok(Some((mut encoder, view, frame))) =
                         renderer.pre_render(objects, window_ref.inner_size(), camera)
                     {
+                        objects.keep_previous_transforms();
+                        let pre_render_time = stage_start.elapsed();
+                        let stage_start = crate::time::Instant::now();
+                        allocation_audit::enter(Subsystem::Update);
+
+                        if let Some(fixed_update) = fixed_update {
+                            let tick_length = fixed_update.timestep.tick_length();
+                            for _ in 0..fixed_update.timestep.advance() {
+                                (fixed_update.fixed_function)(
+                                    renderer,
+                                    window,
+                                    objects,
+                                    input_events,
+                                    camera,
+                                    signals,
+                                    tick_length,
+                                );
+                            }
+                            (fixed_update.render_function)(
+                                renderer,
+                                window,
+                                objects,
+                                input_events,
+                                camera,
+                                signals,
+                                fixed_update.timestep.alpha(),
+                            );
+                        }
                         if let Some(update_function) = update_loop {
                             update_function(
                                 renderer,
@@ -391,7 +1174,50 @@ impl ApplicationHandler for Engine {
                                 signals,
                             );
                         }
+                        states.update(renderer, window, objects, input_events, camera, signals);
+                        plugins.run(
+                            PluginHook::Update,
+                            renderer,
+                            window,
+                            objects,
+                            input_events,
+                            camera,
+                            signals,
+                        );
+                        settings.apply(renderer, window, camera);
+                        let delta_time = input_events
+                            .delta_time()
+                            .map(|i| i.as_secs_f32())
+                            .unwrap_or(0.0);
+                        scheduler.advance(
+                            delta_time,
+                            renderer,
+                            window,
+                            objects,
+                            input_events,
+                            camera,
+                            signals,
+                        );
+                        tasks.poll(
+                            delta_time,
+                            renderer,
+                            window,
+                            objects,
+                            input_events,
+                            camera,
+                            signals,
+                        );
 
+                        states.draw(
+                            renderer,
+                            window,
+                            objects,
+                            input_events,
+                            camera,
+                            signals,
+                            &mut encoder,
+                            &view,
+                        );
                         signals.events.iter_mut().for_each(|i| {
                             i.1.frame(
                                 renderer,
@@ -404,25 +1230,70 @@ impl ApplicationHandler for Engine {
                             );
                         });
 
+                        let update_time = stage_start.elapsed();
+                        let stage_start = crate::time::Instant::now();
+                        allocation_audit::enter(Subsystem::ObjectUpdate);
+
+                        camera.apply_constraints(
+                            objects,
+                            input_events
+                                .delta_time()
+                                .map(|i| i.as_secs_f32())
+                                .unwrap_or(0.0),
+                        );
+                        objects.advance_fades(delta_time);
+
+                        let scene_changed = objects.values().any(|i| i.changed)
+                            || camera.values().any(|i| i.changed);
+                        if scene_changed {
+                            window.request_redraw();
+                        }
+
                         for camera_value in camera.values_mut() {
                             camera_value.update_view_projection(renderer);
                         }
-                        objects.iter_mut().for_each(|i| {
-                            if i.1.changed {
-                                i.1.update(renderer);
-                            }
-                        });
+                        objects.notify_observers();
+                        objects.update_changed(renderer);
+
+                        let object_update_time = stage_start.elapsed();
+                        let stage_start = crate::time::Instant::now();
+                        allocation_audit::enter(Subsystem::Render);
 
-                        renderer.render(encoder, frame);
+                        renderer.stats.collect_objects(objects);
+                        renderer.window_opacity = window.opacity();
+                        renderer.render(encoder, &view, frame);
+
+                        renderer.stats.timings = crate::utils::performance::StageTimings {
+                            pre_render: pre_render_time.as_secs_f32() * 1000.0,
+                            update: update_time.as_secs_f32() * 1000.0,
+                            object_update: object_update_time.as_secs_f32() * 1000.0,
+                            render: stage_start.elapsed().as_secs_f32() * 1000.0,
+                        };
+                        allocation_audit::enter(Subsystem::Update);
+                        plugins.run(
+                            PluginHook::PostRender,
+                            renderer,
+                            window,
+                            objects,
+                            input_events,
+                            camera,
+                            signals,
+                        );
                     }
                 }
+                allocation_audit::enter(Subsystem::Other);
+                renderer.stats.allocations = allocation_audit::take_report();
 
+                window.monitor_changed = None;
+                window.focus_changed = None;
                 _device_event = DeviceEvent::MouseMotion { delta: (0.0, 0.0) };
-                if let Some(window_inner) = &window.window {
-                    window_inner.request_redraw();
+                if window.redraw_mode == RedrawMode::Continuous
+                    || renderer.frame_recorder.is_recording()
+                {
+                    window.request_redraw();
                 }
             }
-            _ => {}
+            _ => window.request_redraw(),
         }
 
         input_events.process_window_event(&event);
@@ -453,9 +1324,220 @@ impl Window {
             window: None,
             default_attributes,
             should_close: false,
+            cursor_visible: true,
+            cursor_grab: winit::window::CursorGrabMode::None,
+            pending_custom_cursor: None,
+            max_fps: None,
+            redraw_mode: RedrawMode::Continuous,
+            frame_pacing: FramePacing::MaxFps,
+            next_redraw: None,
+            redraw_pending: true,
+            last_redraw: None,
+            click_through: false,
+            opacity: 1.0,
+            skip_taskbar: false,
+            monitor: None,
+            monitor_changed: None,
+            unfocused_mode: BackgroundMode::Full,
+            minimized_mode: BackgroundMode::Pause,
+            focused: true,
+            minimized: false,
+            focus_changed: None,
+            suspended: false,
+        }
+    }
+
+    /// Renders another frame, even if nothing changed. Only needed with [RedrawMode::OnDemand]
+    pub fn request_redraw(&mut self) {
+        self.redraw_pending = true;
+    }
+
+    /// Returns the refresh rate in hertz of the monitor the window is on
+    pub fn refresh_rate(&self) -> Option<f32> {
+        self.monitor
+            .as_ref()
+            .and_then(|i| i.refresh_rate_millihertz())
+            .map(|i| i as f32 / 1000.0)
+    }
+
+    /// Returns the time each frame is given by the frame pacing and the background mode,
+    /// `None` if not limited
+    pub fn target_frame_time(&self) -> Option<std::time::Duration> {
+        let fps = match self.frame_pacing {
+            FramePacing::MaxFps => self.max_fps.filter(|i| *i > 0).map(|i| i as f64),
+            FramePacing::RefreshRate => Some(
+                self.refresh_rate()
+                    .filter(|i| *i > 0.0)
+                    .unwrap_or(DEFAULT_REFRESH_RATE) as f64,
+            ),
+        };
+        // the throttle only lowers the frame rate, never raises it
+        let fps = match (self.background_mode(), fps) {
+            (BackgroundMode::Throttle(throttle), Some(fps)) if throttle > 0 => {
+                fps.min(throttle as f64)
+            }
+            (BackgroundMode::Throttle(throttle), None) if throttle > 0 => throttle as f64,
+            (_, fps) => fps?,
+        };
+
+        Some(std::time::Duration::from_secs_f64(1.0 / fps))
+    }
+
+    /// Returns true if the window has the keyboard focus
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    /// Returns true if the window is minimized or hidden behind other windows
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
+
+    /// Returns the focus the window gained or lost in this frame, if it changed.
+    /// Signals are notified through [crate::Signal::focus_changed]
+    pub fn focus_changed(&self) -> Option<bool> {
+        self.focus_changed
+    }
+
+    /// Returns true if the app is in the background without a surface, such as on Android
+    /// and iOS. Signals are notified through [crate::Signal::suspended_changed]
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    /// Which way the screen is held, from the size of the window. Square windows count as
+    /// landscape
+    pub fn orientation(&self) -> Orientation {
+        match self.window.as_ref().map(|i| i.inner_size()) {
+            Some(size) if size.height > size.width => Orientation::Portrait,
+            _ => Orientation::Landscape,
         }
     }
 
+    /// The part of the window that is not covered by notches, rounded corners, or the bars of
+    /// the system, as its top left corner and size in pixels. The scene still covers the whole
+    /// window, so the interface can be kept inside this. Only iOS reports it, elsewhere this is
+    /// the whole window
+    pub fn safe_area(
+        &self,
+    ) -> (
+        winit::dpi::PhysicalPosition<i32>,
+        winit::dpi::PhysicalSize<u32>,
+    ) {
+        let Some(window) = self.window.as_ref() else {
+            return (
+                winit::dpi::PhysicalPosition::new(0, 0),
+                winit::dpi::PhysicalSize::new(0, 0),
+            );
+        };
+
+        // the inner size of a window on iOS is its safe area, while its outer size covers the
+        // whole screen
+        #[cfg(target_os = "ios")]
+        let origin = match (window.inner_position(), window.outer_position()) {
+            (Ok(inner), Ok(outer)) => {
+                winit::dpi::PhysicalPosition::new(inner.x - outer.x, inner.y - outer.y)
+            }
+            _ => winit::dpi::PhysicalPosition::new(0, 0),
+        };
+        #[cfg(not(target_os = "ios"))]
+        let origin = winit::dpi::PhysicalPosition::new(0, 0);
+
+        (origin, window.inner_size())
+    }
+
+    /// The size the surface of the window covers, which on iOS is the whole screen and not
+    /// only the safe area
+    pub(crate) fn surface_size(&self) -> Option<winit::dpi::PhysicalSize<u32>> {
+        let window = self.window.as_ref()?;
+        #[cfg(target_os = "ios")]
+        return Some(window.outer_size());
+        #[cfg(not(target_os = "ios"))]
+        Some(window.inner_size())
+    }
+
+    /// Returns how the window is currently rendered, based on its focus and visibility
+    pub fn background_mode(&self) -> BackgroundMode {
+        if self.suspended {
+            BackgroundMode::Pause
+        } else if self.minimized {
+            self.minimized_mode
+        } else if !self.focused {
+            self.unfocused_mode
+        } else {
+            BackgroundMode::Full
+        }
+    }
+
+    /// Requests the next frame if one is needed and the frame rate limit allows it,
+    /// otherwise lets the event loop sleep until then
+    pub(crate) fn schedule_redraw(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        control_flow: winit::event_loop::ControlFlow,
+    ) {
+        let Some(window) = self.window.as_ref() else {
+            return;
+        };
+        if !self.redraw_pending || self.background_mode() == BackgroundMode::Pause {
+            event_loop.set_control_flow(winit::event_loop::ControlFlow::Wait);
+            return;
+        }
+
+        if let Some(frame_time) = self.target_frame_time()
+            && let Some(last_redraw) = self.last_redraw
+        {
+            let precise = self.frame_pacing == FramePacing::RefreshRate
+                && self.background_mode() == BackgroundMode::Full;
+            let next_redraw = if precise {
+                self.next_redraw.unwrap_or(last_redraw + frame_time)
+            } else {
+                last_redraw + frame_time
+            };
+
+            let now = crate::time::Instant::now();
+            if now < next_redraw {
+                // the browser can not be kept waiting, so the web only sleeps until the frame
+                if precise
+                    && !cfg!(target_arch = "wasm32")
+                    && next_redraw - now <= FRAME_PACING_SPIN_MARGIN
+                {
+                    while crate::time::Instant::now() < next_redraw {
+                        std::thread::yield_now();
+                    }
+                } else {
+                    let wake_up = if precise {
+                        next_redraw - FRAME_PACING_SPIN_MARGIN
+                    } else {
+                        next_redraw
+                    };
+                    event_loop.set_control_flow(winit::event_loop::ControlFlow::WaitUntil(wake_up));
+                    return;
+                }
+            }
+
+            if precise {
+                // the schedule only restarts if the frames fell behind by a whole frame,
+                // so small delays do not add up into drift
+                let now = crate::time::Instant::now();
+                let following = next_redraw + frame_time;
+                self.next_redraw = Some(if following < now {
+                    now + frame_time
+                } else {
+                    following
+                });
+            }
+        }
+
+        // the redraws on the web wait for the next animation frame of the browser, which
+        // paces the loop, so polling would only spin in between
+        #[cfg(target_arch = "wasm32")]
+        let control_flow = winit::event_loop::ControlFlow::Wait;
+        event_loop.set_control_flow(control_flow);
+        self.redraw_pending = false;
+        window.request_redraw();
+    }
+
     /// close the engine window
     pub fn close_engine(&mut self) {
         self.should_close = true;
@@ -484,7 +1566,6 @@ impl Window {
         Option<winit::dpi::Size>
     );
     gen_window_component_functions!(set_content_protected, content_protected, bool);
-    gen_window_component_functions!(set_window_level, window_level, winit::window::WindowLevel);
     gen_window_component_functions!(set_cursor, cursor, winit::window::Cursor);
 
     /// see [winit::window::Window::set_outer_position]
@@ -550,4 +1631,303 @@ impl Window {
             self.default_attributes.fullscreen = None;
         }
     }
+
+    // ====================================================== CURSOR ====================================================== //
+    //MARK: CURSOR
+
+    /// Shows or hides the cursor while it is over the window
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+        if let Some(window) = self.window.as_ref() {
+            window.set_cursor_visible(visible);
+        }
+    }
+
+    /// Returns true if the cursor is visible over the window
+    pub fn is_cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    /// Confines the cursor to the window, or locks it in place, see [winit::window::Window::set_cursor_grab]
+    ///
+    /// Returns an error if the platform does not support the grab mode.
+    pub fn set_cursor_grab(
+        &mut self,
+        mode: winit::window::CursorGrabMode,
+    ) -> Result<(), crate::error::Error> {
+        if let Some(window) = self.window.as_ref() {
+            window.set_cursor_grab(mode)?;
+        }
+        self.cursor_grab = mode;
+
+        Ok(())
+    }
+
+    /// Returns how the cursor is grabbed by the window
+    pub fn cursor_grab(&self) -> winit::window::CursorGrabMode {
+        self.cursor_grab
+    }
+
+    /// Grabs and hides the cursor for first person style controls, or releases it.
+    ///
+    /// The cursor is locked in place if supported, otherwise it is confined to the window.
+    pub fn grab_cursor(&mut self, grab: bool) -> Result<(), crate::error::Error> {
+        if grab {
+            if self
+                .set_cursor_grab(winit::window::CursorGrabMode::Locked)
+                .is_err()
+            {
+                self.set_cursor_grab(winit::window::CursorGrabMode::Confined)?;
+            }
+        } else {
+            self.set_cursor_grab(winit::window::CursorGrabMode::None)?;
+        }
+        self.set_cursor_visible(!grab);
+
+        Ok(())
+    }
+
+    /// Moves the cursor to a position relative to the top left of the window
+    ///
+    /// **Does not work unless during update_loop**
+    pub fn set_cursor_position(
+        &mut self,
+        position: impl Into<winit::dpi::Position>,
+    ) -> Result<(), crate::error::Error> {
+        if let Some(window) = self.window.as_ref() {
+            window.set_cursor_position(position)?;
+        }
+
+        Ok(())
+    }
+
+    /// Uses an image as the cursor, the hotspot is the pixel of the image that points
+    ///
+    /// The cursor is created on the next event of the window.
+    pub fn set_custom_cursor(
+        &mut self,
+        image: &image::RgbaImage,
+        hotspot_x: u16,
+        hotspot_y: u16,
+    ) -> Result<(), crate::error::Error> {
+        let width = u16::try_from(image.width())
+            .map_err(|_| crate::error::Error::Custom("The cursor image is too wide".to_string()))?;
+        let height = u16::try_from(image.height())
+            .map_err(|_| crate::error::Error::Custom("The cursor image is too tall".to_string()))?;
+
+        let source = winit::window::CustomCursor::from_rgba(
+            image.as_raw().clone(),
+            width,
+            height,
+            hotspot_x,
+            hotspot_y,
+        )
+        .map_err(|e| crate::error::Error::Custom(e.to_string()))?;
+        self.pending_custom_cursor = Some(source);
+
+        Ok(())
+    }
+
+    /// Applies the cursor settings that need the window or the event loop
+    pub(crate) fn apply_cursor(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        let Some(window) = self.window.as_ref() else {
+            return;
+        };
+
+        if let Some(source) = self.pending_custom_cursor.take() {
+            let cursor = event_loop.create_custom_cursor(source);
+            window.set_cursor(cursor.clone());
+            self.default_attributes.cursor = cursor.into();
+        }
+    }
+
+    /// Returns true if the window is fullscreen, either borderless or exclusive
+    pub fn is_fullscreen(&self) -> bool {
+        match self.window.as_ref() {
+            Some(window) => window.fullscreen().is_some(),
+            None => self.default_attributes.fullscreen.is_some(),
+        }
+    }
+
+    /// Switches between borderless fullscreen and windowed mode
+    pub fn toggle_fullscreen_borderless(&mut self) {
+        let is_fullscreen = self.is_fullscreen();
+        self.set_fullscreen_borderless(!is_fullscreen);
+    }
+
+    /// Returns the video modes of the monitor the window is on, which can be used with
+    /// [Window::set_fullscreen_video_mode]
+    ///
+    /// **Does not work unless during update_loop**
+    pub fn available_video_modes(&self) -> Vec<winit::monitor::VideoModeHandle> {
+        self.window
+            .as_ref()
+            .and_then(|window| window.current_monitor())
+            .map(|monitor| monitor.video_modes().collect())
+            .unwrap_or_default()
+    }
+
+    /// Makes the window exclusive fullscreen with the given video mode
+    ///
+    /// **Does not work unless during update_loop**
+    pub fn set_fullscreen_video_mode(&mut self, video_mode: winit::monitor::VideoModeHandle) {
+        if let Some(window) = self.window.as_mut() {
+            window.set_fullscreen(Some(winit::window::Fullscreen::Exclusive(video_mode)));
+        }
+    }
+
+    // ====================================================== TRANSPARENCY ====================================================== //
+    //MARK: TRANSPARENCY
+
+    /// Lets the mouse clicks pass through the window to what is behind it, such as for
+    /// overlays. The window stops receiving mouse input while enabled.
+    ///
+    /// Returns an error if the platform does not support it.
+    pub fn set_click_through(&mut self, click_through: bool) -> Result<(), crate::error::Error> {
+        if let Some(window) = self.window.as_ref() {
+            window.set_cursor_hittest(!click_through)?;
+        }
+        self.click_through = click_through;
+
+        Ok(())
+    }
+
+    /// Returns true if the mouse clicks pass through the window
+    pub fn is_click_through(&self) -> bool {
+        self.click_through
+    }
+
+    /// Returns true if the window was created to be see through, see [WindowDescriptor::transparent]
+    pub fn is_transparent(&self) -> bool {
+        self.default_attributes.transparent
+    }
+
+    /// Fades the whole window, from 0 for invisible to 1 for opaque.
+    /// Only has an effect on windows that are transparent, see [WindowDescriptor::transparent]
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity.clamp(0.0, 1.0);
+    }
+
+    /// Returns the opacity of the window
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    // ====================================================== WINDOW LEVEL ====================================================== //
+    //MARK: WINDOW LEVEL
+
+    /// Keeps the window above or below the other windows, see [winit::window::Window::set_window_level]
+    pub fn set_window_level(&mut self, level: winit::window::WindowLevel) {
+        if let Some(window) = self.window.as_ref() {
+            window.set_window_level(level);
+        }
+        self.default_attributes.window_level = level;
+    }
+
+    /// Returns how the window is kept relative to the other windows
+    pub fn window_level(&self) -> winit::window::WindowLevel {
+        self.default_attributes.window_level
+    }
+
+    /// Keeps the window above all the other windows, or lets it be covered again
+    pub fn set_always_on_top(&mut self, always_on_top: bool) {
+        self.set_window_level(if always_on_top {
+            winit::window::WindowLevel::AlwaysOnTop
+        } else {
+            winit::window::WindowLevel::Normal
+        });
+    }
+
+    /// Hides the window from the taskbar, such as for overlays and tool windows.
+    /// Only supported on Windows, ignored on other platforms
+    pub fn set_skip_taskbar(&mut self, skip_taskbar: bool) {
+        #[cfg(target_os = "windows")]
+        if let Some(window) = self.window.as_ref() {
+            use winit::platform::windows::WindowExtWindows;
+            window.set_skip_taskbar(skip_taskbar);
+        }
+        self.skip_taskbar = skip_taskbar;
+    }
+
+    /// Returns true if the window is hidden from the taskbar
+    pub fn is_skipping_taskbar(&self) -> bool {
+        self.skip_taskbar
+    }
+
+    // ====================================================== MONITORS ====================================================== //
+    //MARK: MONITORS
+
+    /// Returns all the monitors connected to the system
+    ///
+    /// **Does not work unless during update_loop**
+    pub fn monitors(&self) -> Vec<MonitorInfo> {
+        self.window
+            .as_ref()
+            .map(|window| window.available_monitors().map(MonitorInfo::new).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the primary monitor of the system, if the platform has one
+    ///
+    /// **Does not work unless during update_loop**
+    pub fn primary_monitor(&self) -> Option<MonitorInfo> {
+        self.window
+            .as_ref()
+            .and_then(|window| window.primary_monitor())
+            .map(MonitorInfo::new)
+    }
+
+    /// Returns the monitor the window is on
+    ///
+    /// **Does not work unless during update_loop**
+    pub fn current_monitor(&self) -> Option<MonitorInfo> {
+        self.window
+            .as_ref()
+            .and_then(|window| window.current_monitor())
+            .map(MonitorInfo::new)
+    }
+
+    /// Returns the monitor the window moved to in this frame, if it moved to another one.
+    /// Signals are notified through [crate::Signal::monitor_changed]
+    pub fn monitor_changed(&self) -> Option<&MonitorInfo> {
+        self.monitor_changed.as_ref()
+    }
+
+    /// Makes the window borderless fullscreen on the given monitor
+    pub fn set_fullscreen_borderless_on(&mut self, monitor: &MonitorInfo) {
+        let fullscreen = Some(winit::window::Fullscreen::Borderless(Some(
+            monitor.handle.clone(),
+        )));
+
+        if let Some(window) = self.window.as_mut() {
+            window.set_fullscreen(fullscreen);
+        } else {
+            self.default_attributes.fullscreen = fullscreen;
+        }
+    }
+
+    /// Makes the window exclusive fullscreen on the given monitor, with its best video mode.
+    /// See [MonitorInfo::best_video_mode]
+    ///
+    /// **Does not work unless during update_loop**
+    pub fn set_fullscreen_exclusive_on(&mut self, monitor: &MonitorInfo) {
+        match monitor.best_video_mode() {
+            Some(video_mode) => self.set_fullscreen_video_mode(video_mode),
+            None => eprintln!("The monitor does not have any video modes for fullscreen"),
+        }
+    }
+
+    /// Checks the monitor the window is on, and returns it if it changed since the last check
+    pub(crate) fn update_monitor(&mut self) -> Option<MonitorInfo> {
+        let monitor = self.window.as_ref()?.current_monitor()?;
+        if self.monitor.as_ref() == Some(&monitor) {
+            return None;
+        }
+
+        self.monitor = Some(monitor.clone());
+        let monitor = MonitorInfo::new(monitor);
+        self.monitor_changed = Some(monitor.clone());
+
+        Some(monitor)
+    }
 }
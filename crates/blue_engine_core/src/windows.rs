@@ -0,0 +1,241 @@
+/*
+ * Blue Engine by Elham Aryanpur
+ *
+ * The license is same as the one on the root.
+*/
+
+use crate::{CameraContainer, ObjectStorage, Renderer, StringBuffer, Window, WindowDescriptor};
+
+/// The callback that is ran before every frame of a secondary window
+pub type WindowRenderCallback =
+    Box<dyn 'static + FnMut(&mut Renderer, &mut Window, &mut ObjectStorage, &mut CameraContainer)>;
+
+/// An additional window that shares the renderer and objects of the engine,
+/// with its own surface and cameras.
+pub struct SecondaryWindow {
+    /// The window itself, not available before the update loop
+    pub window: Window,
+    /// The cameras used when rendering this window. Objects look for their camera by name here
+    pub camera: CameraContainer,
+    /// The surface of the window
+    pub surface: Option<wgpu::Surface<'static>>,
+    /// The configuration of the surface
+    pub config: wgpu::SurfaceConfiguration,
+    /// The depth buffer of the window
+    pub depth_buffer: (wgpu::Texture, wgpu::TextureView, wgpu::Sampler),
+    /// Ran before every frame of the window
+    pub render_callback: Option<WindowRenderCallback>,
+    /// What the window is cleared to, or the clear color of the renderer if None. Set to
+    /// [crate::utils::clear::ClearColor::Keep] to draw over what the window already shows
+    pub clear_color: Option<crate::utils::clear::ClearColor>,
+    /// The scene of the window before it is tonemapped, when HDR is enabled
+    hdr_target: Option<crate::utils::hdr::HdrTarget>,
+}
+
+impl SecondaryWindow {
+    /// Creates a new secondary window, the window itself is created once the update loop starts
+    pub fn new(settings: WindowDescriptor, renderer: &mut Renderer) -> Self {
+        let size = winit::dpi::PhysicalSize {
+            width: settings.width.max(1),
+            height: settings.height.max(1),
+        };
+        let mut config = renderer.config.clone();
+        config.width = size.width;
+        config.height = size.height;
+
+        Self {
+            window: Window::new(crate::window::window_attributes(&settings)),
+            camera: CameraContainer::new(size, renderer),
+            surface: None,
+            depth_buffer: Renderer::build_depth_buffer("Depth Buffer", &renderer.device, &config),
+            config,
+            render_callback: None,
+            clear_color: None,
+            hdr_target: None,
+        }
+    }
+
+    /// Sets the callback that is ran before every frame of the window
+    pub fn set_render_callback(
+        &mut self,
+        callback: impl 'static
+        + FnMut(&mut Renderer, &mut Window, &mut ObjectStorage, &mut CameraContainer),
+    ) -> &mut Self {
+        self.render_callback = Some(Box::new(callback));
+
+        self
+    }
+
+    /// Creates the window and its surface, if not created yet
+    pub(crate) fn create(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        renderer: &Renderer,
+    ) -> Result<(), crate::error::Error> {
+        if self.window.is_some() {
+            return Ok(());
+        }
+
+        let new_window = std::sync::Arc::new(
+            event_loop
+                .create_window(self.window.default_attributes.clone())
+                .map_err(|e| crate::error::Error::Custom(e.to_string()))?,
+        );
+        let surface = renderer
+            .instance
+            .create_surface(new_window.clone())
+            .map_err(|e| crate::error::Error::Custom(e.to_string()))?;
+
+        // the objects are built for the format of the main window, so the same format is used
+        let surface_capabilities = surface.get_capabilities(&renderer.adapter);
+        if !surface_capabilities
+            .formats
+            .contains(&renderer.config.format)
+        {
+            return Err(crate::error::Error::Custom(format!(
+                "The window does not support the format {:?} of the main window",
+                renderer.config.format
+            )));
+        }
+        self.config.format = renderer.config.format;
+        self.config.view_formats = vec![renderer.config.format];
+        self.config.usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+
+        let size = new_window.inner_size();
+        self.config.width = size.width.max(1);
+        self.config.height = size.height.max(1);
+        surface.configure(&renderer.device, &self.config);
+        self.depth_buffer =
+            Renderer::build_depth_buffer("Depth Buffer", &renderer.device, &self.config);
+
+        self.surface = Some(surface);
+        self.window.window = Some(new_window);
+
+        Ok(())
+    }
+
+    /// Resizes the surface of the window
+    pub(crate) fn resize(
+        &mut self,
+        renderer: &mut Renderer,
+        new_size: winit::dpi::PhysicalSize<u32>,
+    ) {
+        if new_size.width == 0 || new_size.height == 0 {
+            return;
+        }
+        self.config.width = new_size.width;
+        self.config.height = new_size.height;
+
+        if let Some(surface) = self.surface.as_ref() {
+            surface.configure(&renderer.device, &self.config);
+            self.depth_buffer =
+                Renderer::build_depth_buffer("Depth Buffer", &renderer.device, &self.config);
+        }
+        for camera in self.camera.values_mut() {
+            camera.set_resolution(new_size);
+            camera.update_view_projection(renderer);
+        }
+    }
+
+    /// Runs the render callback and renders the objects onto the window
+    pub(crate) fn render(&mut self, renderer: &mut Renderer, objects: &mut ObjectStorage) {
+        if let Some(render_callback) = self.render_callback.as_mut() {
+            render_callback(renderer, &mut self.window, objects, &mut self.camera);
+        }
+        for camera in self.camera.values_mut() {
+            camera.update_view_projection(renderer);
+        }
+
+        let Some(surface) = self.surface.as_ref() else {
+            return;
+        };
+        let Ok(frame) = surface.get_current_texture() else {
+            return;
+        };
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = renderer
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Secondary Window Encoder"),
+            });
+        let scene_view = renderer.hdr.as_mut().map(|hdr| {
+            hdr.scene_view(
+                &renderer.device,
+                &mut self.hdr_target,
+                self.config.width,
+                self.config.height,
+            )
+        });
+        renderer.draw_objects(
+            &mut encoder,
+            scene_view.as_ref().unwrap_or(&view),
+            &self.depth_buffer.1,
+            objects,
+            winit::dpi::PhysicalSize::new(self.config.width, self.config.height),
+            &self.camera,
+            None,
+            self.clear_color.unwrap_or(renderer.clear_color),
+        );
+        if let (Some(hdr), Some(target)) = (renderer.hdr.as_mut(), self.hdr_target.as_ref()) {
+            hdr.resolve(
+                &renderer.device,
+                &renderer.queue,
+                &mut encoder,
+                target,
+                &view,
+                self.config.format,
+            );
+        }
+
+        renderer.queue.submit(std::iter::once(encoder.finish()));
+        frame.present();
+    }
+}
+
+/// A container for the secondary windows of the engine, by their name
+pub struct WindowStorage(std::collections::HashMap<String, SecondaryWindow>);
+impl WindowStorage {
+    /// Creates a new window storage
+    pub fn new() -> Self {
+        WindowStorage(std::collections::HashMap::new())
+    }
+
+    /// Finds the name of the window with the given winit id
+    pub fn find(&self, window_id: winit::window::WindowId) -> Option<String> {
+        self.iter()
+            .find(|(_, i)| i.window.as_ref().map(|j| j.id()) == Some(window_id))
+            .map(|(name, _)| name.clone())
+    }
+}
+impl Default for WindowStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+crate::macros::impl_deref!(WindowStorage, std::collections::HashMap<String, SecondaryWindow>);
+
+impl crate::Engine {
+    /// Adds a new window that renders the same objects with its own cameras.
+    ///
+    /// The window is opened once the update loop starts, or on the next event if it already runs.
+    pub fn create_window(
+        &mut self,
+        name: impl StringBuffer,
+        settings: WindowDescriptor,
+    ) -> &mut SecondaryWindow {
+        let window = SecondaryWindow::new(settings, &mut self.renderer);
+
+        self.windows
+            .entry(name.as_string())
+            .insert_entry(window)
+            .into_mut()
+    }
+
+    /// Closes and removes a secondary window
+    pub fn close_window(&mut self, name: impl StringBuffer) -> Option<SecondaryWindow> {
+        self.windows.remove(name.as_str())
+    }
+}